@@ -0,0 +1,208 @@
+// cargo bench --bench serialization
+// 与上次保存的结果比较: cargo bench --bench serialization -- --baseline main
+// 结果记录在 benches/baseline.json, 修改热路径后与其中的数值比较
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use dashmap::DashMap;
+use mirror_rust::mirror::components::network_common_behaviour::NetworkCommonBehaviour;
+use mirror_rust::mirror::components::network_transform::transform_snapshot::TransformSnapshot;
+use mirror_rust::mirror::core::backend_data::{NetworkBehaviourSetting, SyncVarData};
+use mirror_rust::mirror::core::network_behaviour::{
+    GameObject, NetworkBehaviour, NetworkBehaviourTrait,
+};
+use mirror_rust::mirror::core::network_identity::NetworkIdentity;
+use mirror_rust::mirror::core::network_server::NETWORK_BEHAVIOURS;
+use mirror_rust::mirror::core::network_writer::{NetworkWriter, NetworkWriterTrait};
+use mirror_rust::mirror::core::snapshot_interpolation::snapshot_interpolation::SnapshotInterpolation;
+use mirror_rust::mirror::core::tools::delta_compression::DeltaCompression;
+use nalgebra::{Quaternion, Vector3, Vector4};
+use ordered_float::OrderedFloat;
+use std::collections::BTreeMap;
+
+fn network_writer_primitives(c: &mut Criterion) {
+    let mut group = c.benchmark_group("network_writer");
+    let mut writer = NetworkWriter::new();
+    group.bench_function("write_int", |b| {
+        b.iter(|| {
+            writer.reset();
+            for i in 0..64 {
+                writer.write_int(black_box(i));
+            }
+        })
+    });
+    group.bench_function("write_float", |b| {
+        b.iter(|| {
+            writer.reset();
+            for i in 0..64 {
+                writer.write_float(black_box(i as f32 * 0.5));
+            }
+        })
+    });
+    group.bench_function("write_vector3", |b| {
+        b.iter(|| {
+            writer.reset();
+            for i in 0..64 {
+                writer.write_vector3(black_box(Vector3::new(i as f32, 1.0, 2.0)));
+            }
+        })
+    });
+    group.bench_function("write_string", |b| {
+        b.iter(|| {
+            writer.reset();
+            writer.write_string(black_box("QuickStart.PlayerScript.playerName".to_string()));
+        })
+    });
+    group.finish();
+}
+
+fn var_int_compression(c: &mut Criterion) {
+    let mut group = c.benchmark_group("compress_var");
+    let mut writer = NetworkWriter::new();
+    // 1 / 3 / 5 / 9 字节的编码
+    for value in [100u64, 60_000, 4_000_000_000, u64::MAX] {
+        group.bench_with_input(BenchmarkId::new("ulong", value), &value, |b, value| {
+            b.iter(|| {
+                writer.reset();
+                writer.compress_var_ulong(black_box(*value));
+            })
+        });
+    }
+    group.bench_function("int_zigzag", |b| {
+        b.iter(|| {
+            writer.reset();
+            for i in -32..32 {
+                writer.compress_var_int(black_box(i * 1000));
+            }
+        })
+    });
+    group.finish();
+}
+
+fn delta_compression(c: &mut Criterion) {
+    let mut group = c.benchmark_group("delta_compression");
+    let mut writer = NetworkWriter::new();
+    let last = Vector3::new(1000i64, 20, -3000);
+    let current = Vector3::new(1004i64, 20, -2990);
+    group.bench_function("vector3long", |b| {
+        b.iter(|| {
+            writer.reset();
+            DeltaCompression::compress_vector3long(
+                &mut writer,
+                black_box(last),
+                black_box(current),
+            );
+        })
+    });
+    let last = Vector4::new(0i64, 0, 0, 1000);
+    let current = Vector4::new(3i64, -2, 1, 998);
+    group.bench_function("vector4long", |b| {
+        b.iter(|| {
+            writer.reset();
+            DeltaCompression::compress_vector4long(
+                &mut writer,
+                black_box(last),
+                black_box(current),
+            );
+        })
+    });
+    group.finish();
+}
+
+fn transform_snapshot_interpolation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("transform_snapshot");
+    let snapshot = |time: f64| {
+        TransformSnapshot::new(
+            time,
+            time,
+            Vector3::new(time as f32, 0.0, 0.0),
+            Quaternion::new(1.0, 0.0, 0.0, 0.0),
+            Vector3::new(1.0, 1.0, 1.0),
+        )
+    };
+    let from = snapshot(0.0);
+    let to = snapshot(1.0);
+    group.bench_function("lerp", |b| {
+        b.iter(|| TransformSnapshot::transform_snapshot(black_box(from), black_box(to), 0.5))
+    });
+    // 与 bufferLimit 默认值相同的 32 个快照
+    let buffer: BTreeMap<OrderedFloat<f64>, TransformSnapshot> = (0..32)
+        .map(|i| {
+            let time = i as f64 / 30.0;
+            (OrderedFloat(time), snapshot(time))
+        })
+        .collect();
+    group.bench_function("sample_32", |b| {
+        b.iter(|| SnapshotInterpolation::sample(black_box(&buffer), black_box(0.9)))
+    });
+    group.finish();
+}
+
+// 一个有三个同步变量的组件, 与 QuickStart 的 PlayerScript 相当
+fn spawn_identity(net_id: u32) -> NetworkIdentity {
+    let sync_vars = DashMap::new();
+    sync_vars.insert(
+        0,
+        SyncVarData::new("Bench.Player", "playerName", "System.String", 1)
+            .with_value(b"\x0c\x00player-12345".to_vec()),
+    );
+    sync_vars.insert(
+        1,
+        SyncVarData::new("Bench.Player", "position", "UnityEngine.Vector3", 2)
+            .with_value(vec![0; 12]),
+    );
+    sync_vars.insert(
+        2,
+        SyncVarData::new("Bench.Player", "health", "System.Int32", 4).with_value(vec![100]),
+    );
+    let mut behaviour = NetworkCommonBehaviour {
+        network_behaviour: NetworkBehaviour::new(
+            GameObject::default(),
+            NetworkBehaviourSetting::default(),
+            0,
+            "Bench.Player".to_string(),
+        ),
+        sync_vars,
+    };
+    behaviour.set_net_id(net_id);
+    NETWORK_BEHAVIOURS::add_behaviour(net_id, 0, Box::new(behaviour));
+    let mut identity = NetworkIdentity::new_with_asset_id(1);
+    identity.set_net_id(net_id);
+    identity.network_behaviours_count = 1;
+    identity
+}
+
+fn serialize_server(c: &mut Criterion) {
+    let mut group = c.benchmark_group("serialize_server");
+    let mut identity = spawn_identity(1);
+    let mut owner_writer = NetworkWriter::new();
+    let mut observers_writer = NetworkWriter::new();
+    group.bench_function("initial_state", |b| {
+        b.iter(|| {
+            owner_writer.reset();
+            observers_writer.reset();
+            identity.serialize_server(true, &mut owner_writer, &mut observers_writer);
+        })
+    });
+    group.bench_function("delta", |b| {
+        b.iter(|| {
+            if let Some(mut behaviour) = NETWORK_BEHAVIOURS.get_mut(&(1, 0)) {
+                behaviour.set_sync_var_dirty_bits(2);
+                behaviour.set_last_sync_time(-1.0);
+            }
+            owner_writer.reset();
+            observers_writer.reset();
+            identity.serialize_server(false, &mut owner_writer, &mut observers_writer);
+        })
+    });
+    group.finish();
+    NETWORK_BEHAVIOURS.remove(&(1, 0));
+}
+
+criterion_group!(
+    benches,
+    network_writer_primitives,
+    var_int_compression,
+    delta_compression,
+    transform_snapshot_interpolation,
+    serialize_server
+);
+criterion_main!(benches);
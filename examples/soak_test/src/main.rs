@@ -0,0 +1,215 @@
+// 压力测试: 在进程内启动服务器, 生成 N 个不断移动的对象和 M 个 MemoryTransport 客户端
+// 每秒输出 tick 耗时和带宽, 用于比较性能相关修改前后的结果
+// cargo run --release -- [entities] [bots] [seconds]
+use dashmap::DashMap;
+use mirror_rust::mirror::components::network_common_behaviour::NetworkCommonBehaviour;
+use mirror_rust::mirror::core::backend_data::{NetworkBehaviourSetting, SyncVarData};
+use mirror_rust::mirror::core::messages::ReadyMessage;
+use mirror_rust::mirror::core::network_behaviour::{
+    GameObject, NetworkBehaviour, NetworkBehaviourTrait,
+};
+use mirror_rust::mirror::core::network_identity::{NetworkIdentity, Visibility};
+use mirror_rust::mirror::core::network_server::{
+    NetworkServer, NetworkServerStatic, NETWORK_BEHAVIOURS,
+};
+use mirror_rust::mirror::core::network_time::NetworkTime;
+use mirror_rust::mirror::core::tools::bandwidth_report::BandwidthReport;
+use mirror_rust::mirror::core::transport::{TransportChannel, TransportTrait};
+use mirror_rust::mirror::transports::memory::memory_transport::MemoryTransport;
+use nalgebra::Vector3;
+use std::time::{Duration, Instant};
+
+const SUB_CLASS: &str = "Soak.Mover";
+const TICK_RATE: u32 = 60;
+
+struct Args {
+    entities: u32,
+    bots: u64,
+    seconds: u64,
+}
+
+impl Args {
+    fn parse() -> Self {
+        let args: Vec<u64> = std::env::args()
+            .skip(1)
+            .map(|arg| arg.parse().expect("arguments must be numbers"))
+            .collect();
+        Self {
+            entities: args.first().copied().unwrap_or(2000) as u32,
+            bots: args.get(1).copied().unwrap_or(50),
+            seconds: args.get(2).copied().unwrap_or(10),
+        }
+    }
+}
+
+// 每秒的统计
+#[derive(Default)]
+struct Stats {
+    ticks: u32,
+    total: Duration,
+    max: Duration,
+    bytes: usize,
+    packets: usize,
+}
+
+impl Stats {
+    fn print(&self, second: u64, bots: u64) {
+        let ticks = self.ticks.max(1);
+        println!(
+            "[{:>3}s] ticks {:>3} | tick avg {:>7.3} ms max {:>7.3} ms | sent {:>8.1} KB/s ({:>6.1} KB/s per bot, {} packets)",
+            second,
+            self.ticks,
+            self.total.as_secs_f64() * 1000.0 / ticks as f64,
+            self.max.as_secs_f64() * 1000.0,
+            self.bytes as f64 / 1024.0,
+            self.bytes as f64 / 1024.0 / bots.max(1) as f64,
+            self.packets,
+        );
+    }
+}
+
+fn tick() {
+    NetworkTime::increment_frame_count();
+    NetworkServer::network_early_update();
+    NetworkServer::network_late_update();
+}
+
+fn spawn_movers(entities: u32) -> Vec<u32> {
+    let mut net_ids = Vec::with_capacity(entities as usize);
+    for _ in 0..entities {
+        let net_id = NetworkIdentity::get_static_next_network_id();
+        let sync_vars = DashMap::new();
+        sync_vars.insert(
+            0,
+            SyncVarData::new(SUB_CLASS, "position", "UnityEngine.Vector3", 1)
+                .with_value(vec![0; 12]),
+        );
+        let mut behaviour = NetworkCommonBehaviour {
+            network_behaviour: NetworkBehaviour::new(
+                GameObject::new_with_prefab(SUB_CLASS.to_string()),
+                NetworkBehaviourSetting::default(),
+                0,
+                SUB_CLASS.to_string(),
+            ),
+            sync_vars,
+        };
+        behaviour.set_net_id(net_id);
+        NETWORK_BEHAVIOURS::add_behaviour(net_id, 0, Box::new(behaviour));
+        let mut identity = NetworkIdentity::new_with_asset_id(1);
+        identity.set_net_id(net_id);
+        identity.network_behaviours_count = 1;
+        NetworkServerStatic::add_spawned_network_identity(identity);
+        NetworkServer::set_visibility(net_id, Visibility::Default);
+        net_ids.push(net_id);
+    }
+    net_ids
+}
+
+// 每个对象绕圈移动, 位置写入同步变量
+fn move_movers(net_ids: &[u32], time: f32) {
+    for (i, net_id) in net_ids.iter().enumerate() {
+        let angle = time + i as f32 * 0.01;
+        let position = Vector3::new(angle.cos() * 50.0, 0.0, angle.sin() * 50.0);
+        if let Some(mut behaviour) = NETWORK_BEHAVIOURS.get_mut(&(*net_id, 0)) {
+            if let Some(behaviour) = behaviour
+                .as_any_mut()
+                .downcast_mut::<NetworkCommonBehaviour>()
+            {
+                if let Some(mut sync_var) = behaviour.sync_vars.get_mut(&0) {
+                    sync_var.value = position
+                        .iter()
+                        .flat_map(|value| value.to_le_bytes())
+                        .collect();
+                }
+                behaviour.set_sync_var_dirty_bits(1);
+            }
+        }
+    }
+}
+
+fn main() {
+    let args = Args::parse();
+    println!(
+        "soak test: {} entities, {} bots, {} seconds",
+        args.entities, args.bots, args.seconds
+    );
+
+    MemoryTransport::awake();
+    NetworkServerStatic::set_tick_rate(TICK_RATE);
+    NetworkServer::listen(args.bots as usize);
+    BandwidthReport::set_enabled(true);
+
+    for bot in 1..=args.bots {
+        MemoryTransport::client_connect(bot);
+    }
+    tick();
+    for bot in 1..=args.bots {
+        MemoryTransport::client_send_message(bot, &mut ReadyMessage, TransportChannel::Reliable);
+    }
+    tick();
+
+    let spawn_begin = Instant::now();
+    let net_ids = spawn_movers(args.entities);
+    tick();
+    for bot in 1..=args.bots {
+        MemoryTransport::client_receive(bot);
+    }
+    println!(
+        "spawned {} entities for {} bots in {:.1} ms",
+        net_ids.len(),
+        args.bots,
+        spawn_begin.elapsed().as_secs_f64() * 1000.0
+    );
+    BandwidthReport::reset();
+
+    let interval = Duration::from_secs_f64(1.0 / TICK_RATE as f64);
+    let begin = Instant::now();
+    let mut second = 1;
+    let mut stats = Stats::default();
+    let mut all = Stats::default();
+    while begin.elapsed() < Duration::from_secs(args.seconds) {
+        let tick_begin = Instant::now();
+        move_movers(&net_ids, begin.elapsed().as_secs_f32());
+        tick();
+        let elapsed = tick_begin.elapsed();
+
+        // 客户端读取数据, 避免队列无限增长
+        for bot in 1..=args.bots {
+            for (data, _) in MemoryTransport::client_receive(bot) {
+                stats.bytes += data.len();
+                stats.packets += 1;
+            }
+        }
+        stats.ticks += 1;
+        stats.total += elapsed;
+        stats.max = stats.max.max(elapsed);
+
+        if begin.elapsed() >= Duration::from_secs(second) {
+            stats.print(second, args.bots);
+            all.ticks += stats.ticks;
+            all.total += stats.total;
+            all.max = all.max.max(stats.max);
+            all.bytes += stats.bytes;
+            all.packets += stats.packets;
+            stats = Stats::default();
+            second += 1;
+        }
+        if let Some(remaining) = interval.checked_sub(tick_begin.elapsed()) {
+            std::thread::sleep(remaining);
+        }
+    }
+
+    println!("---");
+    let ticks = all.ticks.max(1);
+    println!(
+        "total: {} ticks, tick avg {:.3} ms max {:.3} ms, sent {:.1} KB/s",
+        all.ticks,
+        all.total.as_secs_f64() * 1000.0 / ticks as f64,
+        all.max.as_secs_f64() * 1000.0,
+        all.bytes as f64 / 1024.0 / (second - 1).max(1) as f64,
+    );
+    if let Some(summary) = BandwidthReport::summary(5) {
+        println!("{}", summary);
+    }
+    NetworkServer::shutdown();
+}
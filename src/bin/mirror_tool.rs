@@ -0,0 +1,156 @@
+// mirror-tool: cargo run --features tool --bin mirror-tool -- <command>
+use mirror_rust::mirror::core::backend_data::{
+    BackendData, BackendDataBuilder, BackendDataStatic, NetworkBehaviourComponent,
+};
+use mirror_rust::mirror::core::tools::stable_hash::StableHash;
+use std::collections::BTreeMap;
+use std::process::ExitCode;
+
+const USAGE: &str = "usage:
+    mirror-tool hash <signature>...     print the stable hashes of method signatures / message names
+    mirror-tool validate <file>         check a backend export (schema, duplicate ids, rpc and sync var references)
+    mirror-tool list <file>             list asset ids, scene ids and their components
+    mirror-tool diff <old> <new>        compare two backend exports";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        ["hash", signatures @ ..] if !signatures.is_empty() => {
+            hash(signatures);
+            Ok(())
+        }
+        ["validate", path] => load(path).map(|_| println!("{}: ok", path)),
+        ["list", path] => load(path).map(|backend_data| list(&backend_data)),
+        ["diff", old, new] => load(old).and_then(|old| load(new).map(|new| diff(&old, &new))),
+        _ => Err(USAGE.to_string()),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+// register_delegate 使用 fn hash, 网络消息使用 hash16
+fn hash(signatures: &[&str]) {
+    for signature in signatures {
+        println!("{}", signature);
+        println!("    fn hash:     {}", signature.get_fn_stable_hash_code());
+        println!("    hash16:      {}", signature.get_stable_hash_code16());
+        println!("    stable hash: {}", signature.get_stable_hash_code());
+    }
+}
+
+// 导入时迁移旧版本并检查字段, 再用 BackendDataBuilder 检查引用
+fn load(path: &str) -> Result<BackendData, String> {
+    let backend_data =
+        BackendDataStatic::try_import(path).map_err(|e| format!("{}: {}", path, e))?;
+    BackendDataBuilder::from_backend_data(backend_data)
+        .build()
+        .map_err(|e| format!("{}: {}", path, e))
+}
+
+fn list(backend_data: &BackendData) {
+    println!("assets:");
+    for asset in backend_data.assets.iter() {
+        println!("    {} {}", asset.key, asset.value);
+        print_components(
+            &backend_data
+                .get_network_identity_data_network_behaviour_components_by_asset_id(asset.key),
+        );
+    }
+    println!("scenes:");
+    for scene in backend_data.scene_ids.iter() {
+        println!("    {} {}", scene.value, scene.key);
+        if let Ok(scene_id) = scene.value.parse::<u64>() {
+            print_components(
+                &backend_data
+                    .get_network_identity_data_network_behaviour_components_by_scene_id(scene_id),
+            );
+        }
+    }
+}
+
+fn print_components(components: &[NetworkBehaviourComponent]) {
+    for component in components.iter() {
+        println!("        [{}] {}", component.index, component.sub_class);
+    }
+}
+
+// 每一项转换为 名字 -> 描述, 描述不同即为修改
+fn summary(backend_data: &BackendData) -> BTreeMap<String, String> {
+    let mut summary = BTreeMap::new();
+    for asset in backend_data.assets.iter() {
+        summary.insert(format!("asset {}", asset.key), asset.value.clone());
+    }
+    for scene in backend_data.scene_ids.iter() {
+        summary.insert(format!("scene {}", scene.key), scene.value.clone());
+    }
+    for identity in backend_data.network_identities.iter() {
+        let components = identity
+            .network_behaviour_components
+            .iter()
+            .map(|component| component.value.sub_class.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+        summary.insert(
+            format!("identity {}/{}", identity.asset_id, identity.scene_id),
+            format!("[{}]", components),
+        );
+    }
+    for method in backend_data.methods.iter() {
+        let sync_vars = method
+            .var_list
+            .iter()
+            .map(|sync_var| sync_var.value.as_str())
+            .collect::<Vec<_>>();
+        summary.insert(
+            format!("method {}", method.name),
+            format!(
+                "{:?} hash {} requiresAuthority {} rpcs [{}] syncVars [{}]",
+                method.r#type,
+                method.hash_code,
+                method.requires_authority,
+                method.rpc_list.join(", "),
+                sync_vars.join(", ")
+            ),
+        );
+    }
+    for sync_var in backend_data.sync_vars.iter() {
+        summary.insert(
+            format!("syncVar {}", sync_var.full_name),
+            format!("{} dirtyBit {}", sync_var.r#type, sync_var.dirty_bit),
+        );
+    }
+    summary
+}
+
+fn diff(old: &BackendData, new: &BackendData) {
+    let old = summary(old);
+    let new = summary(new);
+    let mut changes = 0;
+    for (key, value) in old.iter() {
+        match new.get(key) {
+            None => println!("- {}: {}", key, value),
+            Some(new_value) if new_value != value => {
+                println!("~ {}: {} -> {}", key, value, new_value)
+            }
+            Some(_) => continue,
+        }
+        changes += 1;
+    }
+    for (key, value) in new.iter() {
+        if !old.contains_key(key) {
+            println!("+ {}: {}", key, value);
+            changes += 1;
+        }
+    }
+    println!("{} change(s)", changes);
+}
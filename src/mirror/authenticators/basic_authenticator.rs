@@ -53,16 +53,10 @@ impl NetworkAuthenticatorTrait for BasicAuthenticator {
                             Self::server_accept(&mut conn);
                         }
                         TryResult::Absent => {
-                            log_error!(format!(
-                                "Failed because connection {} is absent.",
-                                connection_id
-                            ));
+                            log_error!("Failed because connection {} is absent.", connection_id);
                         }
                         TryResult::Locked => {
-                            log_error!(format!(
-                                "Failed because connection {} is locked.",
-                                connection_id
-                            ));
+                            log_error!("Failed because connection {} is locked.", connection_id);
                         }
                     }
                 }
@@ -78,16 +72,16 @@ impl NetworkAuthenticatorTrait for BasicAuthenticator {
                             Self::server_reject(&mut conn);
                         }
                         TryResult::Absent => {
-                            log_error!(format!(
+                            log_error!(
                                 "Failed to clear observers because connection {} is absent.",
                                 connection_id
-                            ));
+                            );
                         }
                         TryResult::Locked => {
-                            log_error!(format!(
+                            log_error!(
                                 "Failed to clear observers because connection {} is locked.",
                                 connection_id
-                            ));
+                            );
                         }
                     }
                 }
@@ -1,2 +1,4 @@
 pub mod network_authenticator;
-pub mod basic_authenticator;
\ No newline at end of file
+pub mod basic_authenticator;
+#[cfg(feature = "steam")]
+pub mod steam_authenticator;
\ No newline at end of file
@@ -1,15 +1,18 @@
+use crate::mirror::core::messages::DisconnectReason;
 use crate::mirror::core::network_connection::NetworkConnectionTrait;
 use crate::mirror::core::network_connection_to_client::NetworkConnectionToClient;
+use crate::mirror::core::network_context::ContextLocal;
 use crate::mirror::core::network_manager::NetworkManagerStatic;
 use crate::mirror::core::network_reader::NetworkReader;
+use crate::mirror::core::network_server::NetworkServer;
 use crate::mirror::core::transport::TransportChannel;
 use lazy_static::lazy_static;
 use std::any::Any;
 use std::sync::RwLock;
 
 lazy_static! {
-    static ref ON_SERVER_AUTHENTICATED: RwLock<fn(&mut NetworkConnectionToClient)> =
-        RwLock::new(|_| {});
+    static ref ON_SERVER_AUTHENTICATED: ContextLocal<RwLock<fn(&mut NetworkConnectionToClient)>> =
+        ContextLocal::new(|| RwLock::new(|_| {}));
 }
 
 pub struct NetworkAuthenticatorTraitStatic;
@@ -51,6 +54,14 @@ where
     fn on_server_authenticate(&mut self, conn: &mut NetworkConnectionToClient) {
         let _ = conn;
     }
+    // 玩家迁移到其他服务器实例时导出 / 导入连接的认证数据, 默认没有数据
+    fn export_authentication_data(&mut self, conn: &mut NetworkConnectionToClient) -> Vec<u8> {
+        let _ = conn;
+        Vec::new()
+    }
+    fn import_authentication_data(&mut self, conn: &mut NetworkConnectionToClient, data: &[u8]) {
+        let _ = (conn, data);
+    }
     fn server_accept(conn: &mut NetworkConnectionToClient)
     where
         Self: Sized,
@@ -62,7 +73,7 @@ where
         Self: Sized,
     {
         conn.set_authenticated(false);
-        conn.disconnect();
+        NetworkServer::disconnect_connection_with_reason(conn, DisconnectReason::AuthFailed);
     }
     fn as_any_mut(&mut self) -> &mut dyn Any;
     fn get_mut_dyn_any() -> Option<&'static mut dyn Any>
@@ -0,0 +1,248 @@
+use crate::mirror::authenticators::network_authenticator::NetworkAuthenticatorTrait;
+use crate::mirror::core::messages::NetworkMessageTrait;
+use crate::mirror::core::network_connection::NetworkConnectionTrait;
+use crate::mirror::core::network_reader::{NetworkReader, NetworkReaderTrait};
+use crate::mirror::core::network_server::{NetworkServer, NetworkServerStatic};
+use crate::mirror::core::network_writer::{NetworkWriter, NetworkWriterTrait};
+use crate::mirror::core::transport::TransportChannel;
+use crate::mirror::transports::steam::steam_transport::{SteamAuthResult, SteamTransport};
+use crate::{log_error, log_warn};
+use dashmap::try_result::TryResult;
+use std::any::Any;
+use std::sync::RwLock;
+
+// 使用 Steam 认证票据认证, 需要 SteamTransport
+// 客户端发送 GetAuthSessionTicket 得到的票据, 服务器交给 Steam 验证, 结果返回后接受或者拒绝
+// 认证数据为 SteamAuthData
+pub struct SteamAuthenticator {
+    // 是否接受通过家庭共享运行游戏的玩家
+    allow_family_sharing: bool,
+}
+
+impl SteamAuthenticator {
+    pub fn new(allow_family_sharing: bool) -> Self {
+        Self {
+            allow_family_sharing,
+        }
+    }
+
+    // SteamTransport 在 server_early_update 中调用
+    fn on_auth_result(result: SteamAuthResult) {
+        let allow_family_sharing = match Self::get_mut_dyn_any() {
+            Some(authenticator) => match authenticator.downcast_mut::<Self>() {
+                Some(steam_authenticator) => steam_authenticator.allow_family_sharing,
+                None => return,
+            },
+            None => return,
+        };
+        let family_sharing = result.owner_steam_id != result.steam_id;
+        let (code, message) = match (result.success, family_sharing && !allow_family_sharing) {
+            (true, false) => (100, "Success"),
+            (true, true) => (200, "Family Sharing Not Allowed"),
+            (false, _) => (200, "Invalid Ticket"),
+        };
+        match NetworkServerStatic::network_connections().try_get_mut(&result.connection_id) {
+            TryResult::Present(mut conn) => {
+                let mut response = SteamAuthResponseMessage::new(code, message.to_string());
+                conn.send_network_message(&mut response, TransportChannel::Reliable);
+                match code {
+                    100 => {
+                        conn.set_authenticated_data(Box::new(RwLock::new(SteamAuthData {
+                            steam_id: result.steam_id,
+                            owner_steam_id: result.owner_steam_id,
+                        })));
+                        Self::server_accept(&mut conn);
+                    }
+                    _ => {
+                        log_warn!(
+                            "SteamAuthenticator rejected connection {} ({}): {}",
+                            result.connection_id,
+                            result.steam_id,
+                            message
+                        );
+                        Self::server_reject(&mut conn);
+                    }
+                }
+            }
+            TryResult::Absent => {
+                log_error!(
+                    "Failed because connection {} is absent.",
+                    result.connection_id
+                );
+            }
+            TryResult::Locked => {
+                log_error!(
+                    "Failed because connection {} is locked.",
+                    result.connection_id
+                );
+            }
+        }
+    }
+}
+
+impl NetworkAuthenticatorTrait for SteamAuthenticator {
+    fn on_auth_request_message(
+        connection_id: u64,
+        reader: &mut NetworkReader,
+        channel: TransportChannel,
+    ) {
+        let message = SteamAuthRequestMessage::deserialize(reader);
+        if !message.ticket.is_empty() {
+            // 验证结果在之后的 tick 中通过 on_auth_result 返回
+            SteamTransport::begin_auth_session(connection_id, message.ticket);
+            return;
+        }
+        match NetworkServerStatic::network_connections().try_get_mut(&connection_id) {
+            TryResult::Present(mut conn) => {
+                let mut response = SteamAuthResponseMessage::new(200, "Empty Ticket".to_string());
+                conn.send_network_message(&mut response, channel);
+                Self::server_reject(&mut conn);
+            }
+            TryResult::Absent => {
+                log_error!("Failed because connection {} is absent.", connection_id);
+            }
+            TryResult::Locked => {
+                log_error!("Failed because connection {} is locked.", connection_id);
+            }
+        }
+    }
+    fn on_start_server(&mut self) {
+        SteamTransport::set_auth_result_handler(Some(Self::on_auth_result));
+        NetworkServer::register_handler::<SteamAuthRequestMessage>(
+            Self::on_auth_request_message,
+            false,
+        );
+    }
+    fn on_stop_server(&mut self) {
+        NetworkServer::unregister_handler::<SteamAuthRequestMessage>();
+        SteamTransport::set_auth_result_handler(None);
+    }
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+// auth请求消息, ticket 为客户端 GetAuthSessionTicket 得到的票据
+#[derive(Debug, Default)]
+pub struct SteamAuthRequestMessage {
+    pub ticket: Vec<u8>,
+}
+
+impl NetworkMessageTrait for SteamAuthRequestMessage {
+    fn deserialize(reader: &mut NetworkReader) -> Self {
+        Self {
+            ticket: reader.read_bytes_and_size(),
+        }
+    }
+
+    fn serialize(&mut self, writer: &mut NetworkWriter) {
+        writer.write_ushort(Self::get_hash_code());
+        writer.write_bytes_and_size(self.ticket.clone());
+    }
+
+    fn get_full_name() -> &'static str
+    where
+        Self: Sized,
+    {
+        "Mirror.Authenticators.SteamAuthenticator+AuthRequestMessage"
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+// auth响应消息
+#[derive(Debug, Default)]
+pub struct SteamAuthResponseMessage {
+    pub code: u8,
+    pub message: String,
+}
+
+impl SteamAuthResponseMessage {
+    pub fn new(code: u8, message: String) -> Self {
+        Self { code, message }
+    }
+}
+
+impl NetworkMessageTrait for SteamAuthResponseMessage {
+    fn deserialize(reader: &mut NetworkReader) -> Self {
+        Self {
+            code: reader.read_byte(),
+            message: reader.read_string(),
+        }
+    }
+
+    fn serialize(&mut self, writer: &mut NetworkWriter) {
+        writer.write_ushort(Self::get_hash_code());
+        writer.write_byte(self.code);
+        writer.write_string(self.message.to_string());
+    }
+
+    fn get_full_name() -> &'static str
+    where
+        Self: Sized,
+    {
+        "Mirror.Authenticators.SteamAuthenticator+AuthResponseMessage"
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+// 认证成功的连接的认证数据
+#[derive(Debug, Default)]
+pub struct SteamAuthData {
+    pub steam_id: u64,
+    // 家庭共享时为游戏拥有者的 SteamID
+    pub owner_steam_id: u64,
+}
+
+impl NetworkMessageTrait for SteamAuthData {
+    fn deserialize(reader: &mut NetworkReader) -> Self {
+        Self {
+            steam_id: reader.read_ulong(),
+            owner_steam_id: reader.read_ulong(),
+        }
+    }
+
+    fn serialize(&mut self, writer: &mut NetworkWriter) {
+        writer.write_ulong(self.steam_id);
+        writer.write_ulong(self.owner_steam_id);
+    }
+
+    fn get_full_name() -> &'static str
+    where
+        Self: Sized,
+    {
+        "Mirror.Authenticators.SteamAuthenticator+SteamAuthData"
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_steam_auth_request_message() {
+        let mut message = SteamAuthRequestMessage {
+            ticket: vec![1, 2, 3, 4],
+        };
+        let mut writer = NetworkWriter::new();
+        message.serialize(&mut writer);
+        let mut reader = NetworkReader::new_with_bytes(writer.to_bytes());
+        assert_eq!(
+            reader.read_ushort(),
+            SteamAuthRequestMessage::get_hash_code()
+        );
+        assert_eq!(
+            SteamAuthRequestMessage::deserialize(&mut reader).ticket,
+            vec![1, 2, 3, 4]
+        );
+    }
+}
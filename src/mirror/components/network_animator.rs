@@ -1,4 +1,4 @@
-use crate::log_error;
+use crate::{fn_hash, log_error};
 use crate::mirror::core::backend_data::NetworkBehaviourComponent;
 use crate::mirror::core::network_behaviour::{
     GameObject, NetworkBehaviour, NetworkBehaviourTrait, SyncDirection, SyncMode,
@@ -124,7 +124,7 @@ impl NetworkAnimator {
         }
 
         // 获取 NetworkBehaviour
-        match NETWORK_BEHAVIOURS.try_get_mut(&format!("{}_{}", net_id, component_index)) {
+        match NETWORK_BEHAVIOURS.try_get_mut(&(net_id, component_index)) {
             TryResult::Present(mut component) => {
                 component
                     .as_any_mut()
@@ -190,7 +190,7 @@ impl NetworkAnimator {
             return;
         }
         // 获取 NetworkBehaviour
-        match NETWORK_BEHAVIOURS.try_get_mut(&format!("{}_{}", net_id, component_index)) {
+        match NETWORK_BEHAVIOURS.try_get_mut(&(net_id, component_index)) {
             TryResult::Present(mut component) => {
                 component
                     .as_any_mut()
@@ -240,7 +240,7 @@ impl NetworkAnimator {
         }
 
         // 获取 NetworkBehaviour
-        match NETWORK_BEHAVIOURS.try_get_mut(&format!("{}_{}", net_id, component_index)) {
+        match NETWORK_BEHAVIOURS.try_get_mut(&(net_id, component_index)) {
             TryResult::Present(mut component) => {
                 component
                     .as_any_mut()
@@ -290,7 +290,7 @@ impl NetworkAnimator {
         }
 
         // 获取 NetworkBehaviour
-        match NETWORK_BEHAVIOURS.try_get_mut(&format!("{}_{}", net_id, component_index)) {
+        match NETWORK_BEHAVIOURS.try_get_mut(&(net_id, component_index)) {
             TryResult::Present(mut component) => {
                 component
                     .as_any_mut()
@@ -340,7 +340,7 @@ impl NetworkAnimator {
         }
 
         // 获取 NetworkBehaviour
-        match NETWORK_BEHAVIOURS.try_get_mut(&format!("{}_{}", net_id, component_index)) {
+        match NETWORK_BEHAVIOURS.try_get_mut(&(net_id, component_index)) {
             TryResult::Present(mut component) => {
                 component
                     .as_any_mut()
@@ -390,7 +390,15 @@ impl NetworkAnimator {
             writer.compress_var_int(layer_id);
             writer.write_float(weight);
             writer.write_bytes_and_size(parameters);
-            self.send_rpc_internal("System.Void Mirror.NetworkAnimator::RpcOnAnimationClientMessage(System.Int32,System.Single,System.Int32,System.Single,System.Byte[])", -392669502, writer, TransportChannel::Reliable, true);
+            const NAME: &str =
+                "System.Void Mirror.NetworkAnimator::RpcOnAnimationClientMessage(System.Int32,System.Single,System.Int32,System.Single,System.Byte[])";
+            self.send_rpc_internal_fn_hash(
+                NAME,
+                fn_hash!(NAME),
+                writer,
+                TransportChannel::Reliable,
+                true,
+            );
         });
     }
 
@@ -398,7 +406,15 @@ impl NetworkAnimator {
     fn rpc_on_animation_parameters_client_message(&mut self, parameters: Vec<u8>) {
         NetworkWriterPool::get_return(|writer| {
             writer.write_bytes_and_size(parameters);
-            self.send_rpc_internal("System.Void Mirror.NetworkAnimator::RpcOnAnimationParametersClientMessage(System.Byte[])", -2095336766, writer, TransportChannel::Reliable, true);
+            const NAME: &str =
+                "System.Void Mirror.NetworkAnimator::RpcOnAnimationParametersClientMessage(System.Byte[])";
+            self.send_rpc_internal_fn_hash(
+                NAME,
+                fn_hash!(NAME),
+                writer,
+                TransportChannel::Reliable,
+                true,
+            );
         });
     }
 
@@ -406,7 +422,15 @@ impl NetworkAnimator {
     fn rpc_on_animation_trigger_client_message(&mut self, state_hash: i32) {
         NetworkWriterPool::get_return(|writer| {
             writer.compress_var_int(state_hash);
-            self.send_rpc_internal("System.Void Mirror.NetworkAnimator::RpcOnAnimationTriggerClientMessage(System.Int32)", 1759094990, writer, TransportChannel::Reliable, true);
+            const NAME: &str =
+                "System.Void Mirror.NetworkAnimator::RpcOnAnimationTriggerClientMessage(System.Int32)";
+            self.send_rpc_internal_fn_hash(
+                NAME,
+                fn_hash!(NAME),
+                writer,
+                TransportChannel::Reliable,
+                true,
+            );
         });
     }
 
@@ -414,7 +438,15 @@ impl NetworkAnimator {
     fn rpc_on_animation_reset_trigger_client_message(&mut self, state_hash: i32) {
         NetworkWriterPool::get_return(|writer| {
             writer.compress_var_int(state_hash);
-            self.send_rpc_internal("System.Void Mirror.NetworkAnimator::RpcOnAnimationResetTriggerClientMessage(System.Int32)", 1545278305, writer, TransportChannel::Reliable, true);
+            const NAME: &str =
+                "System.Void Mirror.NetworkAnimator::RpcOnAnimationResetTriggerClientMessage(System.Int32)";
+            self.send_rpc_internal_fn_hash(
+                NAME,
+                fn_hash!(NAME),
+                writer,
+                TransportChannel::Reliable,
+                true,
+            );
         });
     }
 }
@@ -529,7 +561,7 @@ impl NetworkBehaviourTrait for NetworkAnimator {
         self.network_behaviour.sync_direction = value
     }
 
-    fn sync_mode(&mut self) -> &SyncMode {
+    fn sync_mode(&self) -> &SyncMode {
         &self.network_behaviour.sync_mode
     }
 
@@ -625,6 +657,14 @@ impl NetworkBehaviourTrait for NetworkAnimator {
         self.network_behaviour.sync_var_hook_guard = value
     }
 
+    fn enabled(&self) -> bool {
+        self.network_behaviour.enabled
+    }
+
+    fn __set_enabled(&mut self, value: bool) {
+        self.network_behaviour.enabled = value
+    }
+
     fn is_dirty(&self) -> bool {
         self.network_behaviour.is_dirty()
     }
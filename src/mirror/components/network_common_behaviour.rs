@@ -12,6 +12,7 @@ use crate::mirror::core::network_writer::{NetworkWriter, NetworkWriterTrait};
 use crate::mirror::core::network_writer_pool::NetworkWriterPool;
 use crate::mirror::core::remote_calls::RemoteProcedureCalls;
 use crate::mirror::core::sync_object::SyncObject;
+use crate::mirror::core::sync_var_inspector::SyncVarField;
 use crate::mirror::core::tools::stable_hash::StableHash;
 use crate::mirror::core::transport::TransportChannel;
 use dashmap::try_result::TryResult;
@@ -72,6 +73,14 @@ impl NetworkCommonBehaviour {
                     value = writer.to_bytes();
                 });
             }
+            // 对象引用, 没有生成的对象写入 null
+            "Mirror.NetworkIdentity" | "UnityEngine.GameObject" | "UnityEngine.Transform" => {
+                let net_id = reader.read_network_identity();
+                NetworkWriterPool::get_return(|writer| {
+                    writer.write_network_identity(net_id);
+                    value = writer.to_bytes();
+                });
+            }
             // 常规类型
 
             // 压缩类型
@@ -81,7 +90,7 @@ impl NetworkCommonBehaviour {
             }
 
             // 4 字节
-            "System.Float" => {
+            "System.Float" | "UnityEngine.Color32" => {
                 value = reader.read_bytes(4);
             }
             // 8 字节
@@ -93,9 +102,25 @@ impl NetworkCommonBehaviour {
                 value = reader.read_bytes(12);
             }
             // 16 字节
-            "UnityEngine.Color" => {
+            "UnityEngine.Color" | "UnityEngine.Rect" | "UnityEngine.Plane" => {
                 value = reader.read_bytes(16);
             }
+            // 24 字节
+            "UnityEngine.Ray" => {
+                value = reader.read_bytes(24);
+            }
+            // 64 字节
+            "UnityEngine.Matrix4x4" => {
+                value = reader.read_bytes(64);
+            }
+            // NetworkBehaviour 子类的引用
+            _ if BackendDataStatic::get_backend_data().is_network_behaviour_type(r#type) => {
+                let behaviour = reader.read_network_behaviour();
+                NetworkWriterPool::get_return(|writer| {
+                    writer.write_network_behaviour(behaviour);
+                    value = writer.to_bytes();
+                });
+            }
             // 未知类型
             _ => {}
         };
@@ -121,7 +146,7 @@ impl NetworkCommonBehaviour {
             return;
         }
         // 获取 NetworkBehaviour
-        match NETWORK_BEHAVIOURS.try_get_mut(&format!("{}_{}", net_id, component_index)) {
+        match NETWORK_BEHAVIOURS.try_get_mut(&(net_id, component_index)) {
             TryResult::Present(mut component) => {
                 component
                     .as_any_mut()
@@ -168,9 +193,9 @@ impl NetworkCommonBehaviour {
             NetworkWriterPool::get_return(|writer| {
                 writer.write_array_segment_all(reader.to_array_segment());
                 for rpc in method_data.rpc_list.iter() {
-                    self.send_rpc_internal(
+                    self.send_rpc_internal_fn_hash(
                         rpc.as_str(),
-                        rpc.get_stable_hash_code(),
+                        rpc.get_fn_stable_hash_code(),
                         writer,
                         TransportChannel::Reliable,
                         true,
@@ -235,10 +260,7 @@ impl NetworkBehaviourTrait for NetworkCommonBehaviour {
                 function();
             }
             Err(e) => {
-                log_error!(format!(
-                    "NetworkCommonBehaviour.register_delegate() error: {}",
-                    e
-                ));
+                log_error!("NetworkCommonBehaviour.register_delegate() error: {}", e);
             }
         }
     }
@@ -275,7 +297,7 @@ impl NetworkBehaviourTrait for NetworkCommonBehaviour {
         self.network_behaviour.sync_direction = value
     }
 
-    fn sync_mode(&mut self) -> &SyncMode {
+    fn sync_mode(&self) -> &SyncMode {
         &self.network_behaviour.sync_mode
     }
 
@@ -372,6 +394,14 @@ impl NetworkBehaviourTrait for NetworkCommonBehaviour {
         self.network_behaviour.sync_var_hook_guard = value
     }
 
+    fn enabled(&self) -> bool {
+        self.network_behaviour.enabled
+    }
+
+    fn __set_enabled(&mut self, value: bool) {
+        self.network_behaviour.enabled = value
+    }
+
     fn is_dirty(&self) -> bool {
         self.network_behaviour.is_dirty()
     }
@@ -407,4 +437,86 @@ impl NetworkBehaviourTrait for NetworkCommonBehaviour {
     fn deserialize_sync_vars(&mut self, _reader: &mut NetworkReader, _initial_state: bool) -> bool {
         true
     }
+
+    fn inspect_sync_vars(&self) -> Vec<SyncVarField> {
+        (0..self.sync_vars.len() as u8)
+            .filter_map(|i| self.sync_vars.get(&i))
+            .map(|sync_var| {
+                SyncVarField::new(&sync_var.name, &sync_var.r#type, sync_var.value.clone())
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::core::backend_data::NetworkBehaviourSetting;
+
+    fn common_behaviour() -> NetworkCommonBehaviour {
+        let sync_vars = DashMap::new();
+        sync_vars.insert(
+            0,
+            SyncVarData {
+                full_name: "Test.Health".to_string(),
+                sub_class: "Test".to_string(),
+                name: "Health".to_string(),
+                r#type: "System.Int32".to_string(),
+                value: vec![100, 0, 0, 0],
+                dirty_bit: 1,
+            },
+        );
+        NetworkCommonBehaviour {
+            network_behaviour: NetworkBehaviour::new(
+                GameObject::default(),
+                NetworkBehaviourSetting::default(),
+                0,
+                "Test".to_string(),
+            ),
+            sync_vars,
+        }
+    }
+
+    #[test]
+    fn test_set_enabled() {
+        let mut behaviour = common_behaviour();
+        behaviour.__set_sync_var_dirty_bits(1);
+        behaviour.__set_sync_object_dirty_bits(0);
+        behaviour.set_last_sync_time(-1.0);
+        assert!(behaviour.is_dirty());
+
+        // 禁用后只同步 enabled
+        behaviour.set_enabled(false);
+        let mut writer = NetworkWriter::new();
+        behaviour.serialize(&mut writer, false);
+        assert_eq!(writer.to_bytes(), vec![1, 0]);
+        behaviour.__set_sync_var_dirty_bits(1);
+        assert!(!behaviour.is_dirty());
+
+        // 初始状态末尾带上 enabled, 反序列化时恢复
+        let mut writer = NetworkWriter::new();
+        behaviour.serialize(&mut writer, true);
+        assert_eq!(writer.to_bytes(), vec![5, 100, 0, 0, 0, 0]);
+
+        // 服务器端不读取 NetworkCommonBehaviour 的 SyncVar, 用没有 SyncVar 的组件验证
+        let mut empty = common_behaviour();
+        empty.sync_vars.clear();
+        empty.set_enabled(false);
+        let mut writer = NetworkWriter::new();
+        empty.serialize(&mut writer, true);
+        assert_eq!(writer.to_bytes(), vec![1, 0]);
+        let mut restored = common_behaviour();
+        restored.sync_vars.clear();
+        let mut reader = NetworkReader::new_with_bytes(writer.to_bytes());
+        assert!(restored.deserialize(&mut reader, true));
+        assert!(!restored.enabled());
+
+        behaviour.set_enabled(true);
+        assert!(behaviour.enabled_dirty());
+        behaviour.set_enabled(true);
+        assert_eq!(
+            behaviour.sync_var_dirty_bits(),
+            1 | NetworkBehaviour::ENABLED_DIRTY_BIT
+        );
+    }
 }
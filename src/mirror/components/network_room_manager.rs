@@ -3,6 +3,7 @@ use crate::mirror::authenticators::network_authenticator::{
 };
 use crate::mirror::components::network_room_player::NetworkRoomPlayer;
 use crate::mirror::core::backend_data::{BackendDataStatic, SnapshotInterpolationSetting};
+use crate::mirror::core::loadout_phase::LoadoutPhase;
 use crate::mirror::core::messages::{AddPlayerMessage, ReadyMessage, SceneMessage, SceneOperation};
 use crate::mirror::core::network_behaviour::{GameObject, NetworkBehaviourTrait};
 use crate::mirror::core::network_connection::NetworkConnectionTrait;
@@ -14,6 +15,7 @@ use crate::mirror::core::network_reader::NetworkReader;
 use crate::mirror::core::network_server::{
     EventHandlerType, NetworkServer, NetworkServerStatic, ReplacePlayerOptions,
 };
+use crate::mirror::core::tools::handshake_timing::{HandshakeStage, HandshakeTiming};
 use crate::mirror::core::transport::{TransportChannel, TransportError};
 use crate::{log_debug, log_error, log_warn};
 use dashmap::try_result::TryResult;
@@ -78,6 +80,7 @@ impl NetworkRoomManager {
     fn on_server_authenticated(conn: &mut NetworkConnectionToClient) {
         // 获取 NetworkManagerTrait 的单例
         conn.set_authenticated(true);
+        HandshakeTiming::record(conn.connection_id(), HandshakeStage::Authenticated);
 
         // 获取 NetworkManagerTrait 的单例
         let network_manager = NetworkManagerStatic::network_manager_singleton();
@@ -98,6 +101,9 @@ impl NetworkRoomManager {
             conn.send_network_message(&mut scene_message, TransportChannel::Reliable);
         }
 
+        // 可选的角色 / 装备选择阶段
+        LoadoutPhase::on_authenticated(conn);
+
         Self::on_server_connect(conn);
     }
 
@@ -445,10 +451,10 @@ impl NetworkManagerTrait for NetworkRoomManager {
                                 ReplacePlayerOptions::KeepAuthority,
                             );
                         }) {
-                            log_error!(format!(
+                            log_error!(
                                 "Failed to on_server_disconnect for identity {} because of absent",
                                 net_id
-                            ));
+                            );
                         }
                     }
                     TryResult::Absent => {
@@ -496,10 +502,10 @@ impl NetworkManagerTrait for NetworkRoomManager {
                     if !identity.get_component::<NetworkRoomPlayer, _>(|player| {
                         player.ready_to_begin = false;
                     }) {
-                        log_error!(format!(
+                        log_error!(
                             "Failed to on_server_disconnect for identity {} because of absent",
                             net_id
-                        ));
+                        );
                     }
                 }
                 TryResult::Absent => {
@@ -546,16 +552,16 @@ impl NetworkManagerTrait for NetworkRoomManager {
                 net_id = conn.net_id();
             }
             TryResult::Absent => {
-                log_error!(format!(
+                log_error!(
                     "Failed to on_server_ready for conn {} because of absent",
                     conn_id
-                ));
+                );
             }
             TryResult::Locked => {
-                log_error!(format!(
+                log_error!(
                     "Failed to on_server_ready for conn {} because of locked",
                     conn_id
-                ));
+                );
             }
         }
         // 如果 net_id 为 0
@@ -568,23 +574,23 @@ impl NetworkManagerTrait for NetworkRoomManager {
                 if !identity.get_component::<NetworkRoomPlayer, _>(|player| {
                     room_player = player.game_object().clone();
                 }) {
-                    log_error!(format!(
+                    log_error!(
                         "Failed to on_server_ready for identity {} because of absent",
                         net_id
-                    ));
+                    );
                 }
             }
             TryResult::Absent => {
-                log_error!(format!(
+                log_error!(
                     "Failed to on_server_ready for identity {} because of absent",
                     net_id
-                ));
+                );
             }
             TryResult::Locked => {
-                log_error!(format!(
+                log_error!(
                     "Failed to on_server_ready for identity {} because of locked",
                     net_id
-                ));
+                );
             }
         }
         // 如果 room_player 不为空
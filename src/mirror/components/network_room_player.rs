@@ -7,8 +7,10 @@ use crate::mirror::core::network_manager::NetworkManagerStatic;
 use crate::mirror::core::network_reader::{NetworkReader, NetworkReaderTrait};
 use crate::mirror::core::network_server::{NetworkServerStatic, NETWORK_BEHAVIOURS};
 use crate::mirror::core::network_writer::{NetworkWriter, NetworkWriterTrait};
+use crate::mirror::core::network_writer_pool::NetworkWriterPool;
 use crate::mirror::core::remote_calls::RemoteProcedureCalls;
 use crate::mirror::core::sync_object::SyncObject;
+use crate::mirror::core::sync_var_inspector::SyncVarField;
 use dashmap::try_result::TryResult;
 use std::any::Any;
 use std::sync::Once;
@@ -34,7 +36,7 @@ impl NetworkRoomPlayer {
             return;
         }
         // 获取 NetworkBehaviour
-        match NETWORK_BEHAVIOURS.try_get_mut(&format!("{}_{}", net_id, component_index)) {
+        match NETWORK_BEHAVIOURS.try_get_mut(&(net_id, component_index)) {
             TryResult::Present(mut component) => {
                 component
                     .as_any_mut()
@@ -129,7 +131,7 @@ impl NetworkBehaviourTrait for NetworkRoomPlayer {
         self.network_behaviour.sync_direction = value;
     }
 
-    fn sync_mode(&mut self) -> &SyncMode {
+    fn sync_mode(&self) -> &SyncMode {
         &self.network_behaviour.sync_mode
     }
 
@@ -225,6 +227,14 @@ impl NetworkBehaviourTrait for NetworkRoomPlayer {
         self.network_behaviour.sync_var_hook_guard = value
     }
 
+    fn enabled(&self) -> bool {
+        self.network_behaviour.enabled
+    }
+
+    fn __set_enabled(&mut self, value: bool) {
+        self.network_behaviour.enabled = value
+    }
+
     fn is_dirty(&self) -> bool {
         self.network_behaviour.is_dirty()
     }
@@ -280,4 +290,24 @@ impl NetworkBehaviourTrait for NetworkRoomPlayer {
     fn deserialize_sync_vars(&mut self, _reader: &mut NetworkReader, _initial_state: bool) -> bool {
         true
     }
+
+    fn inspect_sync_vars(&self) -> Vec<SyncVarField> {
+        let mut fields = Vec::new();
+        NetworkWriterPool::get_return(|writer| {
+            writer.write_bool(self.ready_to_begin);
+            fields.push(SyncVarField::new(
+                "readyToBegin",
+                "System.Boolean",
+                writer.to_bytes(),
+            ));
+            writer.reset();
+            writer.compress_var_int(self.index);
+            fields.push(SyncVarField::new(
+                "index",
+                "System.Int32",
+                writer.to_bytes(),
+            ));
+        });
+        fields
+    }
 }
@@ -2,4 +2,6 @@ pub mod network_transform_reliable;
 pub mod network_transform_unreliable;
 pub mod transform_sync_data;
 pub mod transform_snapshot;
-pub mod network_transform_base;
\ No newline at end of file
+pub mod network_transform_base;
+pub mod network_bone_sync;
+pub mod rotation_sync;
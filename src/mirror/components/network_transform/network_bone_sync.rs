@@ -0,0 +1,450 @@
+use crate::log_error;
+use crate::mirror::components::network_transform::network_transform_base::NetworkTransformBase;
+use crate::mirror::core::backend_data::NetworkBehaviourComponent;
+use crate::mirror::core::network_behaviour::{
+    GameObject, NetworkBehaviourTrait, SyncDirection, SyncMode,
+};
+use crate::mirror::core::network_reader::{NetworkReader, NetworkReaderTrait};
+use crate::mirror::core::network_server::NetworkServerStatic;
+use crate::mirror::core::network_time::NetworkTime;
+use crate::mirror::core::network_writer::{NetworkWriter, NetworkWriterTrait};
+use crate::mirror::core::sync_object::SyncObject;
+use crate::mirror::core::tools::accurateinterval::AccurateInterval;
+use crate::mirror::core::tools::compress::Compress;
+use crate::mirror::core::tools::delta_compression::DeltaCompression;
+use nalgebra::{Quaternion, Vector4};
+use std::any::Any;
+use std::fmt::Debug;
+use std::sync::Once;
+
+// 骨骼的本地旋转 (死亡布娃娃 / 程序动画)
+// 每根骨骼的旋转按 rotation_precision 量化, delta 只写入与上一帧不同的骨骼
+// 发送间隔与 NetworkTransform 一致, 由 NetworkTransformBaseSetting 的 send_interval_multiplier / only_sync_on_change 控制
+#[derive(Debug)]
+pub struct NetworkBoneSync {
+    network_transform_base: NetworkTransformBase,
+    rotation_precision: f32,
+    bones: Vec<Quaternion<f32>>,
+    send_interval_counter: u32,
+    last_send_interval_time: f64,
+    last_serialized_bones: Vec<Vector4<i64>>,
+    last_deserialized_bones: Vec<Vector4<i64>>,
+}
+
+impl NetworkBoneSync {
+    pub const COMPONENT_TAG: &'static str = "Mirror.NetworkBoneSync";
+
+    pub fn bones(&self) -> &[Quaternion<f32>] {
+        &self.bones
+    }
+
+    pub fn set_bone(&mut self, index: usize, rotation: Quaternion<f32>) {
+        match self.bones.get_mut(index) {
+            Some(bone) => *bone = rotation,
+            None => {
+                log_error!(
+                    "NetworkBoneSync bone index {} out of range, bone count is {}.",
+                    index,
+                    self.bones.len()
+                );
+            }
+        }
+    }
+
+    // 骨骼数量改变时下一次 delta 会包含全部骨骼
+    pub fn set_bones(&mut self, bones: Vec<Quaternion<f32>>) {
+        self.bones = bones;
+    }
+
+    fn quantize(&self) -> Vec<Vector4<i64>> {
+        self.bones
+            .iter()
+            .map(|bone| Compress::quaternion_to_vector4long(*bone, self.rotation_precision))
+            .collect()
+    }
+
+    fn changed(&self) -> bool {
+        self.quantize() != self.last_serialized_bones
+    }
+
+    // CheckLastSendTime
+    fn u_check_last_send_time(&mut self) {
+        if self.send_interval_counter >= self.network_transform_base.send_interval_multiplier {
+            self.send_interval_counter = 0;
+        }
+
+        if AccurateInterval::elapsed(
+            NetworkTime::local_time(),
+            NetworkServerStatic::send_interval() as f64,
+            &mut self.last_send_interval_time,
+        ) {
+            self.send_interval_counter += 1;
+        }
+    }
+
+    fn reset_state(&mut self) {
+        self.network_transform_base.reset_state();
+        self.last_serialized_bones.clear();
+        self.last_deserialized_bones.clear();
+    }
+}
+
+impl NetworkBehaviourTrait for NetworkBoneSync {
+    fn new(game_object: GameObject, network_behaviour_component: &NetworkBehaviourComponent) -> Self
+    where
+        Self: Sized,
+    {
+        Self::call_register_delegate();
+        let setting = network_behaviour_component.network_bone_sync_setting;
+        Self {
+            network_transform_base: NetworkTransformBase::new(
+                game_object,
+                network_behaviour_component.network_transform_base_setting,
+                network_behaviour_component.network_behaviour_setting,
+                network_behaviour_component.index,
+                network_behaviour_component.sub_class.clone(),
+            ),
+            rotation_precision: setting.rotation_precision,
+            bones: vec![Quaternion::identity(); setting.bone_count as usize],
+            send_interval_counter: 0,
+            last_send_interval_time: f64::MIN,
+            last_serialized_bones: Vec::new(),
+            last_deserialized_bones: Vec::new(),
+        }
+    }
+
+    fn register_delegate()
+    where
+        Self: Sized,
+    {
+    }
+
+    fn get_once() -> &'static Once
+    where
+        Self: Sized,
+    {
+        static ONCE: Once = Once::new();
+        &ONCE
+    }
+
+    fn sync_interval(&self) -> f64 {
+        self.network_transform_base.network_behaviour.sync_interval
+    }
+
+    fn set_sync_interval(&mut self, value: f64) {
+        self.network_transform_base.network_behaviour.sync_interval = value
+    }
+
+    fn last_sync_time(&self) -> f64 {
+        self.network_transform_base.network_behaviour.last_sync_time
+    }
+
+    fn set_last_sync_time(&mut self, value: f64) {
+        self.network_transform_base.network_behaviour.last_sync_time = value
+    }
+
+    fn sync_direction(&mut self) -> &SyncDirection {
+        &self.network_transform_base.network_behaviour.sync_direction
+    }
+
+    fn set_sync_direction(&mut self, value: SyncDirection) {
+        self.network_transform_base.network_behaviour.sync_direction = value
+    }
+
+    fn sync_mode(&self) -> &SyncMode {
+        &self.network_transform_base.network_behaviour.sync_mode
+    }
+
+    fn set_sync_mode(&mut self, value: SyncMode) {
+        self.network_transform_base.network_behaviour.sync_mode = value
+    }
+
+    fn index(&self) -> u8 {
+        self.network_transform_base.network_behaviour.index
+    }
+
+    fn set_index(&mut self, value: u8) {
+        self.network_transform_base.network_behaviour.index = value
+    }
+
+    fn sub_class(&self) -> String {
+        self.network_transform_base
+            .network_behaviour
+            .sub_class
+            .clone()
+    }
+
+    fn set_sub_class(&mut self, value: String) {
+        self.network_transform_base.network_behaviour.sub_class = value
+    }
+
+    fn sync_var_dirty_bits(&self) -> u64 {
+        self.network_transform_base
+            .network_behaviour
+            .sync_var_dirty_bits
+    }
+
+    fn __set_sync_var_dirty_bits(&mut self, value: u64) {
+        self.network_transform_base
+            .network_behaviour
+            .sync_var_dirty_bits = value
+    }
+
+    fn sync_object_dirty_bits(&self) -> u64 {
+        self.network_transform_base
+            .network_behaviour
+            .sync_object_dirty_bits
+    }
+
+    fn __set_sync_object_dirty_bits(&mut self, value: u64) {
+        self.network_transform_base
+            .network_behaviour
+            .sync_object_dirty_bits = value
+    }
+
+    fn net_id(&self) -> u32 {
+        self.network_transform_base.network_behaviour.net_id
+    }
+
+    fn set_net_id(&mut self, value: u32) {
+        self.network_transform_base.network_behaviour.net_id = value
+    }
+
+    fn connection_to_client(&self) -> u64 {
+        self.network_transform_base
+            .network_behaviour
+            .connection_to_client
+    }
+
+    fn set_connection_to_client(&mut self, value: u64) {
+        self.network_transform_base
+            .network_behaviour
+            .connection_to_client = value
+    }
+
+    fn observers(&self) -> &Vec<u64> {
+        &self.network_transform_base.network_behaviour.observers
+    }
+
+    fn add_observer(&mut self, conn_id: u64) {
+        self.network_transform_base
+            .network_behaviour
+            .observers
+            .push(conn_id);
+    }
+
+    fn remove_observer(&mut self, value: u64) {
+        self.network_transform_base
+            .network_behaviour
+            .observers
+            .retain(|&x| x != value);
+    }
+
+    fn game_object(&self) -> &GameObject {
+        &self.network_transform_base.network_behaviour.game_object
+    }
+
+    fn set_game_object(&mut self, value: GameObject) {
+        self.network_transform_base.network_behaviour.game_object = value
+    }
+
+    fn sync_objects(&mut self) -> &mut Vec<Box<dyn SyncObject>> {
+        &mut self.network_transform_base.network_behaviour.sync_objects
+    }
+
+    fn set_sync_objects(&mut self, value: Vec<Box<dyn SyncObject>>) {
+        self.network_transform_base.network_behaviour.sync_objects = value
+    }
+
+    fn add_sync_object(&mut self, value: Box<dyn SyncObject>) {
+        self.network_transform_base
+            .network_behaviour
+            .sync_objects
+            .push(value);
+    }
+
+    fn sync_var_hook_guard(&self) -> u64 {
+        self.network_transform_base
+            .network_behaviour
+            .sync_var_hook_guard
+    }
+
+    fn __set_sync_var_hook_guard(&mut self, value: u64) {
+        self.network_transform_base
+            .network_behaviour
+            .sync_var_hook_guard = value
+    }
+
+    fn enabled(&self) -> bool {
+        self.network_transform_base.network_behaviour.enabled
+    }
+
+    fn __set_enabled(&mut self, value: bool) {
+        self.network_transform_base.network_behaviour.enabled = value
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.network_transform_base.network_behaviour.is_dirty()
+    }
+
+    // 初始状态: 骨骼数量 + 全部骨骼 (相对 0 的 delta)
+    // delta: 骨骼数量 + 改变的骨骼数量 + 每根骨骼的索引和相对上一帧的 delta
+    fn on_serialize(&mut self, writer: &mut NetworkWriter, initial_state: bool) {
+        if initial_state {
+            // 与 NetworkTransformReliable 一样写入上一次序列化的值, 新观察者的 delta 基准与其他观察者一致
+            if self.last_serialized_bones.is_empty() {
+                self.last_serialized_bones = self.quantize();
+            }
+            writer.compress_var_uint(self.last_serialized_bones.len() as u32);
+            for bone in self.last_serialized_bones.iter() {
+                DeltaCompression::compress_vector4long(writer, Vector4::zeros(), *bone);
+            }
+            return;
+        }
+        let quantized = self.quantize();
+        // 骨骼数量改变时与 0 比较
+        if self.last_serialized_bones.len() != quantized.len() {
+            self.last_serialized_bones = vec![Vector4::zeros(); quantized.len()];
+        }
+        let changed: Vec<usize> = (0..quantized.len())
+            .filter(|i| quantized[*i] != self.last_serialized_bones[*i])
+            .collect();
+        writer.compress_var_uint(quantized.len() as u32);
+        writer.compress_var_uint(changed.len() as u32);
+        for i in changed {
+            writer.compress_var_uint(i as u32);
+            DeltaCompression::compress_vector4long(
+                writer,
+                self.last_serialized_bones[i],
+                quantized[i],
+            );
+        }
+        // save serialized as 'last' for next delta compression
+        self.last_serialized_bones = quantized;
+    }
+
+    fn on_deserialize(&mut self, reader: &mut NetworkReader, initial_state: bool) -> bool {
+        let count = reader.decompress_var_uint() as usize;
+        if self.last_deserialized_bones.len() != count {
+            self.last_deserialized_bones = vec![Vector4::zeros(); count];
+        }
+        if initial_state {
+            for i in 0..count {
+                self.last_deserialized_bones[i] =
+                    DeltaCompression::decompress_vector4long(reader, Vector4::zeros());
+            }
+        } else {
+            let changed = reader.decompress_var_uint() as usize;
+            for _ in 0..changed {
+                let i = reader.decompress_var_uint() as usize;
+                if i >= count {
+                    log_error!(
+                        "NetworkBoneSync bone index {} out of range, bone count is {}.",
+                        i,
+                        count
+                    );
+                    return false;
+                }
+                self.last_deserialized_bones[i] = DeltaCompression::decompress_vector4long(
+                    reader,
+                    self.last_deserialized_bones[i],
+                );
+            }
+        }
+        // 只接受客户端权限的骨骼
+        if self.sync_direction() == &SyncDirection::ClientToServer {
+            self.bones = self
+                .last_deserialized_bones
+                .iter()
+                .map(|bone| Compress::vector4long_to_quaternion(*bone, self.rotation_precision))
+                .collect();
+        }
+        true
+    }
+
+    fn on_stop_server(&mut self) {
+        self.reset_state();
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn late_update(&mut self) {
+        if self.send_interval_counter == self.network_transform_base.send_interval_multiplier
+            && (!self.network_transform_base.only_sync_on_change || self.changed())
+        {
+            self.set_dirty()
+        }
+        self.u_check_last_send_time();
+    }
+
+    fn serialize_sync_vars(&mut self, _writer: &mut NetworkWriter, _initial_state: bool) {}
+
+    fn deserialize_sync_vars(&mut self, _reader: &mut NetworkReader, _initial_state: bool) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::core::backend_data::NetworkBehaviourSetting;
+    use std::f32::consts::FRAC_1_SQRT_2;
+
+    fn bone_sync(bone_count: usize) -> NetworkBoneSync {
+        NetworkBoneSync {
+            network_transform_base: NetworkTransformBase::new(
+                GameObject::default(),
+                Default::default(),
+                NetworkBehaviourSetting::default(),
+                0,
+                NetworkBoneSync::COMPONENT_TAG.to_string(),
+            ),
+            rotation_precision: 0.001,
+            bones: vec![Quaternion::identity(); bone_count],
+            send_interval_counter: 0,
+            last_send_interval_time: f64::MIN,
+            last_serialized_bones: Vec::new(),
+            last_deserialized_bones: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_bone_delta() {
+        let mut server = bone_sync(16);
+        // 客户端视角
+        let mut client = bone_sync(0);
+        client.set_sync_direction(SyncDirection::ClientToServer);
+
+        let mut writer = NetworkWriter::new();
+        server.on_serialize(&mut writer, true);
+        let mut reader = NetworkReader::new_with_bytes(writer.to_bytes());
+        assert!(client.on_deserialize(&mut reader, true));
+        assert_eq!(client.bones().len(), 16);
+
+        // 没有改变时只有两个字节
+        let mut writer = NetworkWriter::new();
+        server.on_serialize(&mut writer, false);
+        let mut reader = NetworkReader::new_with_bytes(writer.to_bytes());
+        assert_eq!(reader.remaining(), 2);
+        assert!(client.on_deserialize(&mut reader, false));
+        assert!(!server.changed());
+
+        let rotation = Quaternion::new(FRAC_1_SQRT_2, 0.0, FRAC_1_SQRT_2, 0.0);
+        server.set_bone(3, rotation);
+        assert!(server.changed());
+        let mut writer = NetworkWriter::new();
+        server.on_serialize(&mut writer, false);
+        // 数量 + 改变数量 + 索引 + 4 个 varlong
+        assert!(writer.to_bytes().len() <= 3 + 4 * 3);
+        let mut reader = NetworkReader::new_with_bytes(writer.to_bytes());
+        assert!(client.on_deserialize(&mut reader, false));
+        assert!((client.bones()[3] - rotation).norm() < 0.002);
+        assert_eq!(client.bones()[4], Quaternion::identity());
+
+        let mut writer = NetworkWriter::new();
+        server.on_serialize(&mut writer, false);
+        assert_eq!(writer.to_bytes(), vec![16, 0]);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::mirror::components::network_transform::rotation_sync::RotationSyncMode;
 use crate::mirror::components::network_transform::transform_snapshot::TransformSnapshot;
 use crate::mirror::core::backend_data::{NetworkBehaviourSetting, NetworkTransformBaseSetting};
 use crate::mirror::core::network_behaviour::{GameObject, NetworkBehaviour};
@@ -31,14 +32,15 @@ pub struct NetworkTransformBase {
     pub coordinate_space: CoordinateSpace,
     pub is_client_with_authority: bool,
     pub server_snapshots: BTreeMap<OrderedFloat<f64>, TransformSnapshot>,
-    pub time_stamp_adjustment: f64,
-    pub offset: f64,
     // pub network_behaviour_setting: NetworkBehaviourSetting,
     pub sync_position: bool,
     pub sync_rotation: bool,
     pub sync_scale: bool,
+    pub sync_position_axes: [bool; 3],
+    pub sync_scale_axes: [bool; 3],
     pub only_sync_on_change: bool,
     pub compress_rotation: bool,
+    pub rotation_sync_mode: RotationSyncMode,
     pub interpolate_position: bool,
     pub interpolate_rotation: bool,
     pub interpolate_scale: bool,
@@ -48,30 +50,58 @@ pub struct NetworkTransformBase {
 
 impl NetworkTransformBase {
     pub fn new(game_object: GameObject, network_transform_base_setting: NetworkTransformBaseSetting, network_behaviour_setting: NetworkBehaviourSetting, component_index: u8, sub_class: String) -> Self {
-        let mut base = Self {
+        Self {
             network_behaviour: NetworkBehaviour::new(game_object, network_behaviour_setting, component_index, sub_class),
             is_client_with_authority: false,
             server_snapshots: Default::default(),
-            time_stamp_adjustment: 0.0,
-            offset: 0.0,
             // network_behaviour_setting: NetworkBehaviourSetting::new(network_behaviour_setting),
             sync_position: network_transform_base_setting.sync_position,
             sync_rotation: network_transform_base_setting.sync_rotation,
             sync_scale: network_transform_base_setting.sync_scale,
+            sync_position_axes: [
+                network_transform_base_setting.sync_position_x,
+                network_transform_base_setting.sync_position_y,
+                network_transform_base_setting.sync_position_z,
+            ],
+            sync_scale_axes: [
+                network_transform_base_setting.sync_scale_x,
+                network_transform_base_setting.sync_scale_y,
+                network_transform_base_setting.sync_scale_z,
+            ],
             only_sync_on_change: network_transform_base_setting.only_sync_on_change,
             compress_rotation: network_transform_base_setting.compress_rotation,
+            rotation_sync_mode: RotationSyncMode::from_setting(&network_transform_base_setting),
             interpolate_position: network_transform_base_setting.interpolate_position,
             interpolate_rotation: network_transform_base_setting.interpolate_rotation,
             interpolate_scale: network_transform_base_setting.interpolate_scale,
             coordinate_space: CoordinateSpace::from_u8(network_transform_base_setting.coordinate_space),
             send_interval_multiplier: network_transform_base_setting.send_interval_multiplier,
             timeline_offset: network_transform_base_setting.timeline_offset,
-        };
-        base.time_stamp_adjustment = NetworkServerStatic::send_interval() as f64 * (base.send_interval_multiplier as f64 - 1.0);
-        if base.timeline_offset {
-            base.offset = NetworkServerStatic::send_interval() as f64 * base.send_interval_multiplier as f64;
         }
-        base
+    }
+    // 与 C# 一样按当前的 send_interval 计算, 服务器运行中修改 send_rate 后仍然正确
+    // timeStampAdjustment: 客户端每 send_interval_multiplier 个 send_interval 才发送一次,
+    // 而连接的 remote_timeline 按每个 send_interval 的时间快照推进, 需要把时间戳往后移动 (multiplier - 1) 个间隔
+    pub fn time_stamp_adjustment(&self) -> f64 {
+        Self::time_stamp_adjustment_for(NetworkServerStatic::send_interval() as f64, self.send_interval_multiplier)
+    }
+    // timelineOffset 开启时再往后移动一个发送周期, 用更高的延迟换取更少的外推
+    pub fn offset(&self) -> f64 {
+        Self::offset_for(NetworkServerStatic::send_interval() as f64, self.send_interval_multiplier, self.timeline_offset)
+    }
+    // 客户端发送的 remote_time_stamp 对应的快照在 server_snapshots 中的时间
+    pub fn snapshot_time(&self, remote_time_stamp: f64) -> f64 {
+        remote_time_stamp + self.time_stamp_adjustment() + self.offset()
+    }
+    pub fn time_stamp_adjustment_for(send_interval: f64, send_interval_multiplier: u32) -> f64 {
+        send_interval * (send_interval_multiplier as f64 - 1.0)
+    }
+    pub fn offset_for(send_interval: f64, send_interval_multiplier: u32, timeline_offset: bool) -> f64 {
+        if timeline_offset {
+            send_interval * send_interval_multiplier as f64
+        } else {
+            0.0
+        }
     }
     pub fn reset_state(&mut self) {
         self.server_snapshots.clear();
@@ -245,4 +275,155 @@ impl Transform {
                   Quaternion::new(1.0, 0.0, 0.0, 0.0),
                   Vector3::new(1.0, 1.0, 1.0))
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::core::network_time::ExponentialMovingAverage;
+    use crate::mirror::core::snapshot_interpolation::snapshot_interpolation_settings::SnapshotInterpolationSettings;
+    use crate::mirror::core::snapshot_interpolation::time_snapshot::TimeSnapshot;
+
+    const SEND_RATE: f64 = 30.0;
+    const SEND_INTERVAL: f64 = 1.0 / SEND_RATE;
+    const FRAME_TIME: f64 = 1.0 / 60.0;
+    const DURATION: f64 = 10.0;
+    // 前两秒等待缓冲区和 timescale 稳定
+    const WARMUP: f64 = 2.0;
+
+    // 客户端的运动轨迹, 非线性, 在错误的快照之间插值会产生误差
+    fn client_position(time: f64) -> f32 {
+        (5.0 * (2.0 * time).sin()) as f32
+    }
+
+    struct SimulationResult {
+        max_error: f32,
+        // 预热之后 timeline 超出缓冲区, 只能保持最后位置的帧的比例
+        extrapolated: f64,
+    }
+
+    // 模拟服务器收到一个客户端的 NetworkTransform 更新:
+    // 客户端每个 send_interval 发送一个批次 (时间快照), 每 multiplier 个批次附带一次位置,
+    // 批次经过 latency + [0, jitter) 的延迟到达, 服务器按 FRAME_TIME 运行
+    // 与 NetworkConnectionToClient::on_time_snapshot / update_time_interpolation 以及
+    // NetworkTransform 的 on_client_to_server_sync / update_server 的步骤相同
+    fn simulate(latency: f64, jitter: f64, multiplier: u32, timeline_offset: bool) -> SimulationResult {
+        let settings = SnapshotInterpolationSettings::default();
+        let adjustment = NetworkTransformBase::time_stamp_adjustment_for(SEND_INTERVAL, multiplier);
+        let offset = NetworkTransformBase::offset_for(SEND_INTERVAL, multiplier, timeline_offset);
+
+        // (到达时间, 发送时间, 是否附带位置)
+        let mut seed = 0x2545F4914F6CDD1Du64;
+        let mut batches = Vec::new();
+        let mut index = 0u32;
+        while index as f64 * SEND_INTERVAL < DURATION {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            let random = (seed >> 11) as f64 / (1u64 << 53) as f64;
+            let send_time = index as f64 * SEND_INTERVAL;
+            batches.push((send_time + latency + random * jitter, send_time, index.is_multiple_of(multiplier)));
+            index += 1;
+        }
+        batches.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+        let mut time_snapshots = BTreeMap::new();
+        let mut remote_timeline = 0.0;
+        let mut remote_timescale = 1.0;
+        let mut buffer_time_multiplier = settings.buffer_time_multiplier;
+        let mut drift_ema = ExponentialMovingAverage::new((SEND_RATE * settings.drift_ema_duration as f64) as u32);
+        let mut delivery_time_ema = ExponentialMovingAverage::new((SEND_RATE * settings.delivery_time_ema_duration as f64) as u32);
+        let mut server_snapshots = BTreeMap::new();
+
+        let mut result = SimulationResult { max_error: 0.0, extrapolated: 0.0 };
+        let mut frames = 0;
+        let mut extrapolated_frames = 0;
+        let mut next_batch = 0;
+        let mut now = 0.0;
+        while now < DURATION {
+            while next_batch < batches.len() && batches[next_batch].0 <= now {
+                let (_, send_time, has_position) = batches[next_batch];
+                next_batch += 1;
+                if settings.dynamic_adjustment {
+                    buffer_time_multiplier = SnapshotInterpolation::dynamic_adjustment(SEND_INTERVAL,
+                                                                                       delivery_time_ema.standard_deviation,
+                                                                                       settings.dynamic_adjustment_tolerance as f64);
+                }
+                SnapshotInterpolation::insert_and_adjust(&mut time_snapshots,
+                                                         settings.buffer_limit as usize,
+                                                         TimeSnapshot::new(send_time, now),
+                                                         &mut remote_timeline,
+                                                         &mut remote_timescale,
+                                                         SEND_INTERVAL,
+                                                         SEND_INTERVAL * buffer_time_multiplier,
+                                                         settings.catchup_speed,
+                                                         settings.slowdown_speed,
+                                                         &mut drift_ema,
+                                                         settings.catchup_negative_threshold as f64,
+                                                         settings.catchup_positive_threshold as f64,
+                                                         &mut delivery_time_ema);
+                if has_position {
+                    let snapshot = TransformSnapshot::new(send_time + adjustment + offset,
+                                                          now,
+                                                          Vector3::new(client_position(send_time), 0.0, 0.0),
+                                                          Quaternion::new(1.0, 0.0, 0.0, 0.0),
+                                                          Vector3::new(1.0, 1.0, 1.0));
+                    SnapshotInterpolation::insert_if_not_exists(&mut server_snapshots, settings.buffer_limit as usize, snapshot);
+                }
+            }
+
+            if !time_snapshots.is_empty() {
+                SnapshotInterpolation::step_time(FRAME_TIME, &mut remote_timeline, remote_timescale);
+                SnapshotInterpolation::step_interpolation(&mut time_snapshots, remote_timeline);
+            }
+            if !server_snapshots.is_empty() && now >= WARMUP {
+                let (from, to, t) = SnapshotInterpolation::step_interpolation(&mut server_snapshots, remote_timeline);
+                frames += 1;
+                if from.remote_time == to.remote_time {
+                    extrapolated_frames += 1;
+                } else {
+                    // C# 参考: 在 remote_timeline 上采样得到客户端在 remote_timeline - timeStampAdjustment - offset 时
+                    // 所在的两个发送位置之间的线性插值
+                    let client_interval = SEND_INTERVAL * multiplier as f64;
+                    let client_time = remote_timeline - SEND_INTERVAL * (multiplier as f64 - 1.0)
+                        - if timeline_offset { client_interval } else { 0.0 };
+                    let previous = (client_time / client_interval).floor() * client_interval;
+                    let alpha = ((client_time - previous) / client_interval) as f32;
+                    let expected = client_position(previous) + (client_position(previous + client_interval) - client_position(previous)) * alpha;
+                    let computed = TransformSnapshot::transform_snapshot(from, to, t);
+                    result.max_error = result.max_error.max((computed.position.x - expected).abs());
+                }
+            } else if !server_snapshots.is_empty() {
+                SnapshotInterpolation::step_interpolation(&mut server_snapshots, remote_timeline);
+            }
+            now += FRAME_TIME;
+        }
+        result.extrapolated = extrapolated_frames as f64 / frames as f64;
+        result
+    }
+
+    #[test]
+    fn test_time_stamp_adjustment_and_offset() {
+        assert_eq!(NetworkTransformBase::time_stamp_adjustment_for(SEND_INTERVAL, 1), 0.0);
+        assert!((NetworkTransformBase::time_stamp_adjustment_for(SEND_INTERVAL, 3) - 2.0 / 30.0).abs() < 1e-12);
+        assert_eq!(NetworkTransformBase::offset_for(SEND_INTERVAL, 3, false), 0.0);
+        assert!((NetworkTransformBase::offset_for(SEND_INTERVAL, 3, true) - 0.1).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_interpolation_matches_reference() {
+        // (latency, jitter), jitter 小于发送间隔, 位置不会乱序到达
+        let networks = [(0.0, 0.0), (0.025, 0.0), (0.1, 0.015), (0.15, 0.03)];
+        for (latency, jitter) in networks {
+            for multiplier in [1, 2, 3] {
+                for timeline_offset in [false, true] {
+                    let result = simulate(latency, jitter, multiplier, timeline_offset);
+                    assert!(result.max_error < 1e-3,
+                            "latency {} jitter {} multiplier {} offset {}: max error {}",
+                            latency, jitter, multiplier, timeline_offset, result.max_error);
+                    // 缓冲区中始终有可以插值的快照
+                    assert!(result.extrapolated < 0.05,
+                            "latency {} jitter {} multiplier {} offset {}: extrapolated {}",
+                            latency, jitter, multiplier, timeline_offset, result.extrapolated);
+                }
+            }
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use crate::log_error;
+use crate::{fn_hash, log_error};
 use crate::mirror::components::network_transform::network_transform_base::{
     CoordinateSpace, NetworkTransformBase, NetworkTransformBaseTrait,
 };
@@ -6,11 +6,13 @@ use crate::mirror::components::network_transform::transform_snapshot::TransformS
 use crate::mirror::core::backend_data::NetworkBehaviourComponent;
 use crate::mirror::core::network_behaviour::{GameObject, NetworkBehaviour, NetworkBehaviourTrait, SyncDirection, SyncMode};
 use crate::mirror::core::network_connection::NetworkConnectionTrait;
+use crate::mirror::core::network_manager::NetworkManagerStatic;
 use crate::mirror::core::network_reader::{NetworkReader, NetworkReaderTrait};
-use crate::mirror::core::network_server::{NetworkServerStatic, NETWORK_BEHAVIOURS};
+use crate::mirror::core::network_server::{NetworkServer, NetworkServerStatic, NETWORK_BEHAVIOURS};
 use crate::mirror::core::network_time::NetworkTime;
 use crate::mirror::core::network_writer::{NetworkWriter, NetworkWriterTrait};
 use crate::mirror::core::network_writer_pool::NetworkWriterPool;
+use crate::mirror::core::overload_controller::OverloadController;
 use crate::mirror::core::remote_calls::RemoteProcedureCalls;
 use crate::mirror::core::snapshot_interpolation::snapshot_interpolation::SnapshotInterpolation;
 use crate::mirror::core::sync_object::SyncObject;
@@ -69,16 +71,10 @@ impl NetworkTransformReliable {
                     self.apply(computed, to);
                 }
                 TryResult::Absent => {
-                    log_error!(format!(
-                        "connection not found: {}",
-                        self.connection_to_client()
-                    ));
+                    log_error!("connection not found: {}", self.connection_to_client());
                 }
                 TryResult::Locked => {
-                    log_error!(format!(
-                        "connection locked: {}",
-                        self.connection_to_client()
-                    ));
+                    log_error!("connection locked: {}", self.connection_to_client());
                 }
             }
         }
@@ -117,7 +113,7 @@ impl NetworkTransformReliable {
 
         if AccurateInterval::elapsed(
             NetworkTime::local_time(),
-            NetworkServerStatic::send_interval() as f64,
+            OverloadController::send_interval(),
             &mut self.last_send_interval_time,
         ) {
             self.send_interval_counter += 1;
@@ -136,30 +132,28 @@ impl NetworkTransformReliable {
         }
 
         let mut timestamp = 0f64;
-        let mut buffer_time_multiplier: f64 = 2.0;
+        let mut snapshot_buffer_size_limit = usize::MAX;
         match NetworkServerStatic::network_connections().try_get(&self.connection_to_client()) {
             TryResult::Present(conn) => {
-                if self.network_transform_base.server_snapshots.len()
-                    >= conn.snapshot_buffer_size_limit as usize
-                {
-                    return;
-                }
+                snapshot_buffer_size_limit = conn.snapshot_buffer_size_limit as usize;
                 timestamp = conn.remote_time_stamp();
-                buffer_time_multiplier = conn.buffer_time_multiplier;
             }
             TryResult::Absent => {
-                log_error!(format!(
-                    "connection not found: {}",
-                    self.connection_to_client()
-                ));
+                log_error!("connection not found: {}", self.connection_to_client());
             }
             TryResult::Locked => {
-                log_error!(format!(
-                    "connection locked: {}",
-                    self.connection_to_client()
-                ));
+                log_error!("connection locked: {}", self.connection_to_client());
             }
         }
+        let connection_id = self.connection_to_client();
+        if self.network_transform_base.server_snapshots.len() >= snapshot_buffer_size_limit
+            && !NetworkServer::on_snapshot_overflow(
+                &mut self.network_transform_base.server_snapshots,
+                connection_id,
+            )
+        {
+            return;
+        }
 
         if self.network_transform_base.only_sync_on_change
             && Self::needs_correction(
@@ -182,16 +176,16 @@ impl NetworkTransformReliable {
                 position,
                 rotation,
                 scale,
-                buffer_time_multiplier as usize,
+                NetworkManagerStatic::network_manager_singleton()
+                    .snapshot_interpolation_settings()
+                    .buffer_limit,
             );
         }
 
         let mut server_snapshots = take(&mut self.network_transform_base.server_snapshots);
         self.add_snapshot(
             &mut server_snapshots,
-            timestamp
-                + self.network_transform_base.time_stamp_adjustment
-                + self.network_transform_base.offset,
+            self.network_transform_base.snapshot_time(timestamp),
             Some(position),
             Some(rotation),
             Some(scale),
@@ -218,7 +212,7 @@ impl NetworkTransformReliable {
         position: Vector3<f32>,
         rotation: Quaternion<f32>,
         scale: Vector3<f32>,
-        buffer_time_multiplier: usize,
+        buffer_limit: usize,
     ) {
         snapshots.clear();
         let snapshot = TransformSnapshot::new(
@@ -228,7 +222,42 @@ impl NetworkTransformReliable {
             rotation,
             scale,
         );
-        SnapshotInterpolation::insert_if_not_exists(snapshots, buffer_time_multiplier, snapshot);
+        SnapshotInterpolation::insert_if_not_exists(snapshots, buffer_limit, snapshot);
+    }
+
+    // 按轴写入, 全部轴同步时与 write_vector3 的格式一致
+    fn write_vector3_axes(writer: &mut NetworkWriter, value: Vector3<f32>, axes: [bool; 3]) {
+        for (i, sync) in axes.iter().enumerate() {
+            if *sync {
+                writer.write_float(value[i]);
+            }
+        }
+    }
+
+    // 按轴读取, 未同步的轴使用 fallback 的值
+    fn read_vector3_axes(
+        reader: &mut NetworkReader,
+        fallback: Vector3<f32>,
+        axes: [bool; 3],
+    ) -> Vector3<f32> {
+        let mut value = fallback;
+        for (i, sync) in axes.iter().enumerate() {
+            if *sync {
+                value[i] = reader.read_float();
+            }
+        }
+        value
+    }
+
+    // 未同步的轴使用 fallback 的值
+    fn merge_axes(value: Vector3<f32>, fallback: Vector3<f32>, axes: [bool; 3]) -> Vector3<f32> {
+        let mut merged = fallback;
+        for (i, sync) in axes.iter().enumerate() {
+            if *sync {
+                merged[i] = value[i];
+            }
+        }
+        merged
     }
 
     // NetworkTransformBase start
@@ -247,7 +276,7 @@ impl NetworkTransformReliable {
         }
 
         // 获取 NetworkBehaviour
-        match NETWORK_BEHAVIOURS.try_get_mut(&format!("{}_{}", net_id, component_index)) {
+        match NETWORK_BEHAVIOURS.try_get_mut(&(net_id, component_index)) {
             TryResult::Present(mut component) => {
                 component
                     .as_any_mut()
@@ -275,9 +304,6 @@ impl NetworkTransformReliable {
 
     // UserCode_CmdTeleport_Vector3
     fn user_code_cmd_teleport_vector3(&mut self, position: Vector3<f32>) {
-        if *self.sync_direction() != SyncDirection::ServerToClient {
-            return;
-        }
         self.on_teleport_vector3(position);
         self.rpc_teleport_vector3(position);
     }
@@ -290,9 +316,11 @@ impl NetworkTransformReliable {
     fn rpc_teleport_vector3(&mut self, position: Vector3<f32>) {
         NetworkWriterPool::get_return(|writer| {
             writer.write_vector3(position);
-            self.send_rpc_internal(
-                "System.Void Mirror.NetworkTransformBase::RpcTeleport(UnityEngine.Vector3)",
-                -1933368736,
+            const NAME: &str =
+                "System.Void Mirror.NetworkTransformBase::RpcTeleport(UnityEngine.Vector3)";
+            self.send_rpc_internal_fn_hash(
+                NAME,
+                fn_hash!(NAME),
                 writer,
                 TransportChannel::Reliable,
                 true,
@@ -314,7 +342,7 @@ impl NetworkTransformReliable {
         }
 
         // 获取 NetworkBehaviour
-        match NETWORK_BEHAVIOURS.try_get_mut(&format!("{}_{}", net_id, component_index)) {
+        match NETWORK_BEHAVIOURS.try_get_mut(&(net_id, component_index)) {
             TryResult::Present(mut component) => {
                 component
                     .as_any_mut()
@@ -349,9 +377,6 @@ impl NetworkTransformReliable {
         position: Vector3<f32>,
         rotation: Quaternion<f32>,
     ) {
-        if *self.sync_direction() != SyncDirection::ServerToClient {
-            return;
-        }
         self.on_teleport_vector3_quaternion(position, rotation);
         self.rpc_teleport_vector3_quaternion(position, rotation);
     }
@@ -374,9 +399,11 @@ impl NetworkTransformReliable {
         NetworkWriterPool::get_return(|writer| {
             writer.write_vector3(position);
             writer.write_quaternion(rotation);
-            self.send_rpc_internal(
-                "System.Void Mirror.NetworkTransformBase::RpcTeleport(UnityEngine.Vector3,UnityEngine.Quaternion)",
-                -1675599861,
+            const NAME: &str =
+                "System.Void Mirror.NetworkTransformBase::RpcTeleport(UnityEngine.Vector3,UnityEngine.Quaternion)";
+            self.send_rpc_internal_fn_hash(
+                NAME,
+                fn_hash!(NAME),
                 writer,
                 TransportChannel::Reliable,
                 true,
@@ -411,7 +438,9 @@ impl NetworkBehaviourTrait for NetworkTransformReliable {
             scale_precision: network_behaviour_component
                 .network_transform_reliable_setting
                 .scale_precision,
-            compress_rotation: true,
+            compress_rotation: network_behaviour_component
+                .network_transform_base_setting
+                .compress_rotation,
             send_interval_counter: 0,
             last_send_interval_time: f64::MIN,
             last_snapshot: TransformSnapshot::default(),
@@ -427,17 +456,15 @@ impl NetworkBehaviourTrait for NetworkTransformReliable {
         Self: Sized,
     {
         // System.Void Mirror.NetworkTransformBase::CmdTeleport(UnityEngine.Vector3)
-        RemoteProcedureCalls::register_command_delegate::<Self>(
+        RemoteProcedureCalls::register_state_command_delegate::<Self>(
             "System.Void Mirror.NetworkTransformBase::CmdTeleport(UnityEngine.Vector3)",
             Self::invoke_user_code_cmd_teleport_vector3,
-            true,
         );
 
         // System.Void Mirror.NetworkTransformBase::CmdTeleport(UnityEngine.Vector3,UnityEngine.Quaternion)
-        RemoteProcedureCalls::register_command_delegate::<Self>(
+        RemoteProcedureCalls::register_state_command_delegate::<Self>(
             "System.Void Mirror.NetworkTransformBase::CmdTeleport(UnityEngine.Vector3,UnityEngine.Quaternion)",
             Self::invoke_user_code_cmd_teleport_vector3_quaternion,
-            true,
         );
     }
 
@@ -473,7 +500,7 @@ impl NetworkBehaviourTrait for NetworkTransformReliable {
         self.network_transform_base.network_behaviour.sync_direction = value
     }
 
-    fn sync_mode(&mut self) -> &SyncMode {
+    fn sync_mode(&self) -> &SyncMode {
         &self.network_transform_base.network_behaviour.sync_mode
     }
 
@@ -598,6 +625,14 @@ impl NetworkBehaviourTrait for NetworkTransformReliable {
             .sync_var_hook_guard = value
     }
 
+    fn enabled(&self) -> bool {
+        self.network_transform_base.network_behaviour.enabled
+    }
+
+    fn __set_enabled(&mut self, value: bool) {
+        self.network_transform_base.network_behaviour.enabled = value
+    }
+
     fn is_dirty(&self) -> bool {
         self.network_transform_base.network_behaviour.is_dirty()
     }
@@ -611,7 +646,11 @@ impl NetworkBehaviourTrait for NetworkTransformReliable {
             }
             // 写入位置
             if self.sync_position() {
-                writer.write_vector3(snapshot.position);
+                Self::write_vector3_axes(
+                    writer,
+                    snapshot.position,
+                    self.network_transform_base.sync_position_axes,
+                );
             }
             // 写入旋转
             if self.sync_rotation() {
@@ -623,7 +662,11 @@ impl NetworkBehaviourTrait for NetworkTransformReliable {
             }
             // 写入缩放
             if self.sync_scale() {
-                writer.write_vector3(snapshot.scale);
+                Self::write_vector3_axes(
+                    writer,
+                    snapshot.scale,
+                    self.network_transform_base.sync_scale_axes,
+                );
             }
         } else {
             if self.sync_position() {
@@ -631,10 +674,11 @@ impl NetworkBehaviourTrait for NetworkTransformReliable {
                     snapshot.position,
                     self.position_precision,
                 );
-                DeltaCompression::compress_vector3long(
+                DeltaCompression::compress_vector3long_axes(
                     writer,
                     self.last_serialized_position,
                     quantized,
+                    self.network_transform_base.sync_position_axes,
                 );
             }
             if self.sync_rotation() {
@@ -647,10 +691,11 @@ impl NetworkBehaviourTrait for NetworkTransformReliable {
             if self.sync_scale() {
                 let (_, quantized) =
                     Compress::vector3float_to_vector3long(snapshot.scale, self.scale_precision);
-                DeltaCompression::compress_vector3long(
+                DeltaCompression::compress_vector3long_axes(
                     writer,
                     self.last_serialized_scale,
                     quantized,
+                    self.network_transform_base.sync_scale_axes,
                 );
             }
             // save serialized as 'last' for next delta compression
@@ -676,7 +721,11 @@ impl NetworkBehaviourTrait for NetworkTransformReliable {
         let mut scale = Vector3::identity();
         if initial_state {
             if self.sync_position() {
-                position = reader.read_vector3();
+                position = Self::read_vector3_axes(
+                    reader,
+                    self.get_position(),
+                    self.network_transform_base.sync_position_axes,
+                );
             }
             if self.sync_rotation() {
                 if self.compress_rotation {
@@ -688,16 +737,25 @@ impl NetworkBehaviourTrait for NetworkTransformReliable {
                 }
             }
             if self.sync_scale() {
-                scale = reader.read_vector3();
+                scale = Self::read_vector3_axes(
+                    reader,
+                    self.get_scale(),
+                    self.network_transform_base.sync_scale_axes,
+                );
             }
         } else {
             if self.sync_position() {
-                let quantized = DeltaCompression::decompress_vector3long(
+                let axes = self.network_transform_base.sync_position_axes;
+                let quantized = DeltaCompression::decompress_vector3long_axes(
                     reader,
                     self.last_deserialized_position,
+                    axes,
+                );
+                position = Self::merge_axes(
+                    Compress::vector3long_to_vector3float(quantized, self.position_precision),
+                    self.get_position(),
+                    axes,
                 );
-                position =
-                    Compress::vector3long_to_vector3float(quantized, self.position_precision);
             }
             if self.sync_rotation() {
                 if self.compress_rotation {
@@ -708,9 +766,17 @@ impl NetworkBehaviourTrait for NetworkTransformReliable {
                 }
             }
             if self.sync_scale() {
-                let quantized =
-                    DeltaCompression::decompress_vector3long(reader, self.last_deserialized_scale);
-                scale = Compress::vector3long_to_vector3float(quantized, self.scale_precision);
+                let axes = self.network_transform_base.sync_scale_axes;
+                let quantized = DeltaCompression::decompress_vector3long_axes(
+                    reader,
+                    self.last_deserialized_scale,
+                    axes,
+                );
+                scale = Self::merge_axes(
+                    Compress::vector3long_to_vector3float(quantized, self.scale_precision),
+                    self.get_scale(),
+                    axes,
+                );
             }
         }
 
@@ -1,18 +1,22 @@
-use crate::log_error;
+use crate::{fn_hash, log_error};
 use crate::mirror::components::network_transform::network_transform_base::{
     CoordinateSpace, NetworkTransformBase, NetworkTransformBaseTrait,
 };
+use crate::mirror::components::network_transform::rotation_sync::RotationSync;
 use crate::mirror::components::network_transform::transform_snapshot::TransformSnapshot;
 use crate::mirror::components::network_transform::transform_sync_data::{Changed, SyncData};
 use crate::mirror::core::backend_data::NetworkBehaviourComponent;
 use crate::mirror::core::messages::NetworkMessageTrait;
-use crate::mirror::core::network_behaviour::{GameObject, NetworkBehaviour, NetworkBehaviourTrait, SyncDirection, SyncMode};
+use crate::mirror::core::network_behaviour::{
+    GameObject, NetworkBehaviour, NetworkBehaviourTrait, SyncDirection, SyncMode,
+};
 use crate::mirror::core::network_connection::NetworkConnectionTrait;
 use crate::mirror::core::network_reader::{NetworkReader, NetworkReaderTrait};
-use crate::mirror::core::network_server::{NetworkServerStatic, NETWORK_BEHAVIOURS};
+use crate::mirror::core::network_server::{NetworkServer, NetworkServerStatic, NETWORK_BEHAVIOURS};
 use crate::mirror::core::network_time::NetworkTime;
 use crate::mirror::core::network_writer::{NetworkWriter, NetworkWriterTrait};
 use crate::mirror::core::network_writer_pool::NetworkWriterPool;
+use crate::mirror::core::overload_controller::OverloadController;
 use crate::mirror::core::remote_calls::RemoteProcedureCalls;
 use crate::mirror::core::snapshot_interpolation::snapshot_interpolation::SnapshotInterpolation;
 use crate::mirror::core::sync_object::SyncObject;
@@ -20,7 +24,7 @@ use crate::mirror::core::tools::accurateinterval::AccurateInterval;
 use crate::mirror::core::tools::compress::CompressTrait;
 use crate::mirror::core::transport::TransportChannel;
 use dashmap::try_result::TryResult;
-use nalgebra::{Quaternion, UnitQuaternion, Vector3};
+use nalgebra::{Quaternion, Vector3};
 use ordered_float::OrderedFloat;
 use std::any::Any;
 use std::collections::BTreeMap;
@@ -65,16 +69,16 @@ impl NetworkTransformUnreliable {
                     self.apply(computed, to);
                 }
                 TryResult::Absent => {
-                    log_error!(format!(
+                    log_error!(
                         "Failed because connection {} is absent.",
                         self.connection_to_client()
-                    ));
+                    );
                 }
                 TryResult::Locked => {
-                    log_error!(format!(
+                    log_error!(
                         "Failed because connection {} is locked.",
                         &self.connection_to_client()
-                    ));
+                    );
                 }
             }
         }
@@ -122,7 +126,7 @@ impl NetworkTransformUnreliable {
 
         if AccurateInterval::elapsed(
             NetworkTime::local_time(),
-            NetworkServerStatic::send_interval() as f64,
+            OverloadController::send_interval(),
             &mut self.last_send_interval_time,
         ) {
             self.send_interval_counter += 1;
@@ -155,34 +159,12 @@ impl NetworkTransformUnreliable {
         }
 
         if self.sync_rotation() {
-            if self.network_transform_base.compress_rotation {
-                let rotation_changed = UnitQuaternion::from_quaternion(self.last_snapshot.rotation)
-                    .angle_to(&UnitQuaternion::from_quaternion(snapshot.rotation))
-                    .to_degrees()
-                    > self.rotation_sensitivity;
-                if rotation_changed {
-                    changed |= Changed::CompressRot.to_u8();
-                    changed |= Changed::Rot.to_u8();
-                } else {
-                    changed |= Changed::CompressRot.to_u8();
-                }
-            } else {
-                if (self.last_snapshot.rotation.coords.x - snapshot.rotation.coords.x).abs()
-                    > self.rotation_sensitivity
-                {
-                    changed |= Changed::RotX.to_u8();
-                }
-                if (self.last_snapshot.rotation.coords.y - snapshot.rotation.coords.y).abs()
-                    > self.rotation_sensitivity
-                {
-                    changed |= Changed::RotY.to_u8();
-                }
-                if (self.last_snapshot.rotation.coords.z - snapshot.rotation.coords.z).abs()
-                    > self.rotation_sensitivity
-                {
-                    changed |= Changed::RotZ.to_u8();
-                }
-            }
+            changed |= RotationSync::changed(
+                self.network_transform_base.rotation_sync_mode,
+                self.last_snapshot.rotation,
+                snapshot.rotation,
+                self.rotation_sensitivity,
+            );
         }
 
         if self.sync_scale() {
@@ -209,33 +191,12 @@ impl NetworkTransformUnreliable {
             self.last_snapshot.position.z = current_snapshot.position.z;
         }
 
-        if self.network_transform_base.compress_rotation {
-            if changed & Changed::Rot.to_u8() > 0 {
-                self.last_snapshot.rotation = current_snapshot.rotation;
-            }
-        } else {
-            let euler_angles =
-                UnitQuaternion::from_quaternion(self.last_snapshot.rotation).euler_angles();
-            let mut new_rotation = Vector3::new(euler_angles.0, euler_angles.1, euler_angles.2);
-            if changed & Changed::RotX.to_u8() > 0 {
-                new_rotation.x = UnitQuaternion::from_quaternion(current_snapshot.rotation)
-                    .euler_angles()
-                    .0;
-            }
-            if changed & Changed::RotY.to_u8() > 0 {
-                new_rotation.y = UnitQuaternion::from_quaternion(current_snapshot.rotation)
-                    .euler_angles()
-                    .1;
-            }
-            if changed & Changed::RotZ.to_u8() > 0 {
-                new_rotation.z = UnitQuaternion::from_quaternion(current_snapshot.rotation)
-                    .euler_angles()
-                    .2;
-            }
-            self.last_snapshot.rotation =
-                *UnitQuaternion::from_euler_angles(new_rotation.x, new_rotation.y, new_rotation.z)
-                    .quaternion();
-        }
+        self.last_snapshot.rotation = RotationSync::merge(
+            self.network_transform_base.rotation_sync_mode,
+            changed,
+            self.last_snapshot.rotation,
+            current_snapshot.rotation,
+        );
 
         if changed & Changed::Scale.to_u8() > 0 {
             self.last_snapshot.scale = current_snapshot.scale;
@@ -254,7 +215,7 @@ impl NetworkTransformUnreliable {
             return;
         }
         // 获取 NetworkBehaviour
-        match NETWORK_BEHAVIOURS.try_get_mut(&format!("{}_{}", net_id, component_index)) {
+        match NETWORK_BEHAVIOURS.try_get_mut(&(net_id, component_index)) {
             TryResult::Present(mut component) => {
                 component
                     .as_any_mut()
@@ -292,9 +253,6 @@ impl NetworkTransformUnreliable {
         scale: Option<Vector3<f32>>,
     ) {
         self.on_client_to_server_sync_nullable_1_nullable_1_nullable_1(position, rotation, scale);
-        if *self.sync_direction() != SyncDirection::ClientToServer {
-            return;
-        }
         self.rpc_server_to_client_sync_nullable_1_nullable_1_nullable_1(position, rotation, scale);
     }
 
@@ -311,7 +269,7 @@ impl NetworkTransformUnreliable {
         }
 
         // 获取 NetworkBehaviour
-        match NETWORK_BEHAVIOURS.try_get_mut(&format!("{}_{}", net_id, component_index)) {
+        match NETWORK_BEHAVIOURS.try_get_mut(&(net_id, component_index)) {
             TryResult::Present(mut component) => {
                 component
                     .as_any_mut()
@@ -381,7 +339,7 @@ impl NetworkTransformUnreliable {
         let sync_data = SyncData::deserialize(reader);
 
         // 获取 NetworkBehaviour
-        match NETWORK_BEHAVIOURS.try_get_mut(&format!("{}_{}", net_id, component_index)) {
+        match NETWORK_BEHAVIOURS.try_get_mut(&(net_id, component_index)) {
             TryResult::Present(mut component) => {
                 component
                     .as_any_mut()
@@ -410,9 +368,6 @@ impl NetworkTransformUnreliable {
     // UserCode_CmdClientToServerSync__SyncData
     fn user_code_cmd_client_to_server_sync_sync_data(&mut self, sync_data: SyncData) {
         self.on_client_to_server_sync(sync_data);
-        if *self.sync_direction() != SyncDirection::ClientToServer {
-            return;
-        }
         self.rpc_server_to_client_sync(sync_data);
     }
 
@@ -426,34 +381,39 @@ impl NetworkTransformUnreliable {
         rotation: Option<Quaternion<f32>>,
         scale: Option<Vector3<f32>>,
     ) {
-        // only apply if in client authority mode
         if *self.sync_direction() != SyncDirection::ClientToServer {
             return;
         }
 
         let mut timestamp = 0f64;
+        let mut snapshot_buffer_size_limit = usize::MAX;
         match NetworkServerStatic::network_connections().try_get(&self.connection_to_client()) {
             TryResult::Present(conn) => {
-                if self.network_transform_base.server_snapshots.len()
-                    >= conn.snapshot_buffer_size_limit as usize
-                {
-                    return;
-                }
+                snapshot_buffer_size_limit = conn.snapshot_buffer_size_limit as usize;
                 timestamp = conn.remote_time_stamp();
             }
             TryResult::Absent => {
-                log_error!(format!(
+                log_error!(
                     "Failed because connection {} is absent.",
                     self.connection_to_client()
-                ));
+                );
             }
             TryResult::Locked => {
-                log_error!(format!(
+                log_error!(
                     "Failed because connection {} is locked.",
                     &self.connection_to_client()
-                ));
+                );
             }
         }
+        let connection_id = self.connection_to_client();
+        if self.network_transform_base.server_snapshots.len() >= snapshot_buffer_size_limit
+            && !NetworkServer::on_snapshot_overflow(
+                &mut self.network_transform_base.server_snapshots,
+                connection_id,
+            )
+        {
+            return;
+        }
 
         if self.network_transform_base.only_sync_on_change {
             let time_interval_check = self.buffer_reset_multiplier as f64
@@ -469,40 +429,51 @@ impl NetworkTransformUnreliable {
             }
         }
         let mut server_snapshots = take(&mut self.network_transform_base.server_snapshots);
-        self.add_snapshot(&mut server_snapshots, timestamp, position, rotation, scale);
+        self.add_snapshot(
+            &mut server_snapshots,
+            self.network_transform_base.snapshot_time(timestamp),
+            position,
+            rotation,
+            scale,
+        );
         self.network_transform_base.server_snapshots = server_snapshots;
     }
 
     // void OnClientToServerSync
     fn on_client_to_server_sync(&mut self, mut sync_data: SyncData) {
-        // only apply if in client authority mode
         if *self.sync_direction() != SyncDirection::ClientToServer {
             return;
         }
 
         let mut timestamp = 0f64;
+        let mut snapshot_buffer_size_limit = usize::MAX;
         match NetworkServerStatic::network_connections().try_get(&self.connection_to_client()) {
             TryResult::Present(conn) => {
-                if self.network_transform_base.server_snapshots.len()
-                    >= conn.snapshot_buffer_size_limit as usize
-                {
-                    return;
-                }
+                snapshot_buffer_size_limit = conn.snapshot_buffer_size_limit as usize;
                 timestamp = conn.remote_time_stamp();
             }
             TryResult::Absent => {
-                log_error!(format!(
+                log_error!(
                     "Failed because connection {} is absent.",
                     self.connection_to_client()
-                ));
+                );
             }
             TryResult::Locked => {
-                log_error!(format!(
+                log_error!(
                     "Failed because connection {} is locked.",
                     &self.connection_to_client()
-                ));
+                );
             }
         }
+        let connection_id = self.connection_to_client();
+        if self.network_transform_base.server_snapshots.len() >= snapshot_buffer_size_limit
+            && !NetworkServer::on_snapshot_overflow(
+                &mut self.network_transform_base.server_snapshots,
+                connection_id,
+            )
+        {
+            return;
+        }
 
         if self.network_transform_base.only_sync_on_change {
             let time_interval_check = self.buffer_reset_multiplier as f64
@@ -524,9 +495,7 @@ impl NetworkTransformUnreliable {
         let mut server_snapshots = take(&mut self.network_transform_base.server_snapshots);
         self.add_snapshot(
             &mut server_snapshots,
-            timestamp
-                + self.network_transform_base.time_stamp_adjustment
-                + self.network_transform_base.offset,
+            self.network_transform_base.snapshot_time(timestamp),
             Some(sync_data.position),
             Some(sync_data.quat_rotation),
             Some(sync_data.scale),
@@ -578,51 +547,22 @@ impl NetworkTransformUnreliable {
                 }
             }
 
+            let last_rotation = match snapshots.iter().last() {
+                Some((_, last_snapshot)) => last_snapshot.rotation,
+                None => self.get_rotation(),
+            };
             if sync_data.changed_data_byte & Changed::CompressRot.to_u8() == 0 {
-                // Rot x
-                if sync_data.changed_data_byte & Changed::RotX.to_u8() <= 0 {
-                    if let Some((_, last_snapshot)) = snapshots.iter().last() {
-                        let euler_angles =
-                            UnitQuaternion::from_quaternion(last_snapshot.rotation).euler_angles();
-                        sync_data.vec_rotation.x = euler_angles.0;
-                    } else {
-                        let euler_angles =
-                            UnitQuaternion::from_quaternion(self.get_rotation()).euler_angles();
-                        sync_data.vec_rotation.x = euler_angles.0;
-                    }
-                }
-                // Rot y
-                if sync_data.changed_data_byte & Changed::RotY.to_u8() <= 0 {
-                    if let Some((_, last_snapshot)) = snapshots.iter().last() {
-                        let euler_angles =
-                            UnitQuaternion::from_quaternion(last_snapshot.rotation).euler_angles();
-                        sync_data.vec_rotation.y = euler_angles.1;
-                    } else {
-                        let euler_angles =
-                            UnitQuaternion::from_quaternion(self.get_rotation()).euler_angles();
-                        sync_data.vec_rotation.y = euler_angles.1;
-                    }
-                }
-                // Rot z
-                if sync_data.changed_data_byte & Changed::RotZ.to_u8() <= 0 {
-                    if let Some((_, last_snapshot)) = snapshots.iter().last() {
-                        let euler_angles =
-                            UnitQuaternion::from_quaternion(last_snapshot.rotation).euler_angles();
-                        sync_data.vec_rotation.z = euler_angles.2;
-                    } else {
-                        let euler_angles =
-                            UnitQuaternion::from_quaternion(self.get_rotation()).euler_angles();
-                        sync_data.vec_rotation.z = euler_angles.2;
-                    }
-                }
-            } else {
-                if sync_data.changed_data_byte & Changed::CompressRot.to_u8() <= 0 {
-                    if let Some((_, last_snapshot)) = snapshots.iter().last() {
-                        sync_data.quat_rotation = last_snapshot.rotation;
-                    } else {
-                        sync_data.quat_rotation = self.get_rotation();
-                    }
-                }
+                // 只有改变的轴有效, 其他轴使用上一次的旋转, 合并后重新计算四元数
+                sync_data.quat_rotation = RotationSync::merge_euler(
+                    self.network_transform_base.rotation_sync_mode,
+                    sync_data.changed_data_byte,
+                    last_rotation,
+                    sync_data.vec_rotation,
+                );
+                sync_data.vec_rotation = RotationSync::to_euler(sync_data.quat_rotation);
+            } else if sync_data.changed_data_byte & Changed::Rot.to_u8() == 0 {
+                sync_data.quat_rotation = last_rotation;
+                sync_data.vec_rotation = RotationSync::to_euler(last_rotation);
             }
             if sync_data.changed_data_byte & Changed::Scale.to_u8() <= 0 {
                 if let Some((_, last_snapshot)) = snapshots.iter().last() {
@@ -637,11 +577,16 @@ impl NetworkTransformUnreliable {
     // RpcServerToClientSync
     // [ClientRpc(channel = Un)]
     fn rpc_server_to_client_sync(&mut self, mut sync_data: SyncData) {
+        if !self.has_rpc_observers() {
+            return;
+        }
         NetworkWriterPool::get_return(|writer| {
             sync_data.serialize(writer);
-            self.send_rpc_internal(
-                "System.Void Mirror.NetworkTransformUnreliable::RpcServerToClientSync(Mirror.SyncData)",
-                -1891602648,
+            const NAME: &str =
+                "System.Void Mirror.NetworkTransformUnreliable::RpcServerToClientSync(Mirror.SyncData)";
+            self.send_rpc_internal_fn_hash(
+                NAME,
+                fn_hash!(NAME),
                 writer,
                 TransportChannel::Unreliable,
                 true,
@@ -656,13 +601,18 @@ impl NetworkTransformUnreliable {
         rotation: Option<Quaternion<f32>>,
         scale: Option<Vector3<f32>>,
     ) {
+        if !self.has_rpc_observers() {
+            return;
+        }
         NetworkWriterPool::get_return(|writer| {
             writer.write_vector3_nullable(position);
             writer.write_quaternion_nullable(rotation);
             writer.write_vector3_nullable(scale);
-            self.send_rpc_internal(
-                "System.Void Mirror.NetworkTransformUnreliable::RpcServerToClientSync(System.Nullable`1<UnityEngine.Vector3>,System.Nullable`1<UnityEngine.Quaternion>,System.Nullable`1<UnityEngine.Vector3>)",
-                1202296400,
+            const NAME: &str =
+                "System.Void Mirror.NetworkTransformUnreliable::RpcServerToClientSync(System.Nullable`1<UnityEngine.Vector3>,System.Nullable`1<UnityEngine.Quaternion>,System.Nullable`1<UnityEngine.Vector3>)";
+            self.send_rpc_internal_fn_hash(
+                NAME,
+                fn_hash!(NAME),
                 writer,
                 TransportChannel::Unreliable,
                 true,
@@ -686,7 +636,7 @@ impl NetworkTransformUnreliable {
         }
 
         // 获取 NetworkBehaviour
-        match NETWORK_BEHAVIOURS.try_get_mut(&format!("{}_{}", net_id, component_index)) {
+        match NETWORK_BEHAVIOURS.try_get_mut(&(net_id, component_index)) {
             TryResult::Present(mut component) => {
                 component
                     .as_any_mut()
@@ -714,9 +664,6 @@ impl NetworkTransformUnreliable {
 
     // UserCode_CmdTeleport_Vector3
     fn user_code_cmd_teleport_vector3(&mut self, position: Vector3<f32>) {
-        if *self.sync_direction() != SyncDirection::ServerToClient {
-            return;
-        }
         self.on_teleport_vector3(position);
         self.rpc_teleport_vector3(position);
     }
@@ -729,9 +676,11 @@ impl NetworkTransformUnreliable {
     fn rpc_teleport_vector3(&mut self, position: Vector3<f32>) {
         NetworkWriterPool::get_return(|writer| {
             writer.write_vector3(position);
-            self.send_rpc_internal(
-                "System.Void Mirror.NetworkTransformBase::RpcTeleport(UnityEngine.Vector3)",
-                -1933368736,
+            const NAME: &str =
+                "System.Void Mirror.NetworkTransformBase::RpcTeleport(UnityEngine.Vector3)";
+            self.send_rpc_internal_fn_hash(
+                NAME,
+                fn_hash!(NAME),
                 writer,
                 TransportChannel::Reliable,
                 true,
@@ -753,7 +702,7 @@ impl NetworkTransformUnreliable {
         }
 
         // 获取 NetworkBehaviour
-        match NETWORK_BEHAVIOURS.try_get_mut(&format!("{}_{}", net_id, component_index)) {
+        match NETWORK_BEHAVIOURS.try_get_mut(&(net_id, component_index)) {
             TryResult::Present(mut component) => {
                 component
                     .as_any_mut()
@@ -788,9 +737,6 @@ impl NetworkTransformUnreliable {
         position: Vector3<f32>,
         rotation: Quaternion<f32>,
     ) {
-        if *self.sync_direction() != SyncDirection::ServerToClient {
-            return;
-        }
         self.on_teleport_vector3_quaternion(position, rotation);
         self.rpc_teleport_vector3_quaternion(position, rotation);
     }
@@ -813,9 +759,11 @@ impl NetworkTransformUnreliable {
         NetworkWriterPool::get_return(|writer| {
             writer.write_vector3(position);
             writer.write_quaternion(rotation);
-            self.send_rpc_internal(
-                "System.Void Mirror.NetworkTransformBase::RpcTeleport(UnityEngine.Vector3,UnityEngine.Quaternion)",
-                -1675599861,
+            const NAME: &str =
+                "System.Void Mirror.NetworkTransformBase::RpcTeleport(UnityEngine.Vector3,UnityEngine.Quaternion)";
+            self.send_rpc_internal_fn_hash(
+                NAME,
+                fn_hash!(NAME),
                 writer,
                 TransportChannel::Reliable,
                 true,
@@ -864,38 +812,33 @@ impl NetworkBehaviourTrait for NetworkTransformUnreliable {
         Self: Sized,
     {
         // System.Void Mirror.NetworkTransformUnreliable::CmdClientToServerSync(System.Nullable`1<UnityEngine.Vector3>,System.Nullable`1<UnityEngine.Quaternion>,System.Nullable`1<UnityEngine.Vector3>)
-        RemoteProcedureCalls::register_command_delegate::<Self>(
+        RemoteProcedureCalls::register_latest_only_state_command_delegate::<Self>(
             "System.Void Mirror.NetworkTransformUnreliable::CmdClientToServerSync(System.Nullable`1<UnityEngine.Vector3>,System.Nullable`1<UnityEngine.Quaternion>,System.Nullable`1<UnityEngine.Vector3>)",
             Self::invoke_user_code_cmd_client_to_server_sync_nullable_1_nullable_1_nullable_1,
-            true,
         );
 
         // System.Void Mirror.NetworkTransformUnreliable::CmdClientToServerSyncCompressRotation(System.Nullable`1<UnityEngine.Vector3>,System.Nullable`1<System.UInt32>,System.Nullable`1<UnityEngine.Vector3>)
-        RemoteProcedureCalls::register_command_delegate::<Self>(
+        RemoteProcedureCalls::register_latest_only_state_command_delegate::<Self>(
             "System.Void Mirror.NetworkTransformUnreliable::CmdClientToServerSyncCompressRotation(System.Nullable`1<UnityEngine.Vector3>,System.Nullable`1<System.UInt32>,System.Nullable`1<UnityEngine.Vector3>)",
             Self::invoke_user_code_cmd_client_to_server_sync_compress_rotation_nullable_1_nullable_1_nullable_1,
-            true,
         );
 
         // System.Void Mirror.NetworkTransformUnreliable::CmdClientToServerSync(Mirror.SyncData)
-        RemoteProcedureCalls::register_command_delegate::<Self>(
+        RemoteProcedureCalls::register_latest_only_state_command_delegate::<Self>(
             "System.Void Mirror.NetworkTransformUnreliable::CmdClientToServerSync(Mirror.SyncData)",
             Self::invoke_user_code_cmd_client_to_server_sync_sync_data,
-            true,
         );
 
         // System.Void Mirror.NetworkTransformBase::CmdTeleport(UnityEngine.Vector3)
-        RemoteProcedureCalls::register_command_delegate::<Self>(
+        RemoteProcedureCalls::register_state_command_delegate::<Self>(
             "System.Void Mirror.NetworkTransformBase::CmdTeleport(UnityEngine.Vector3)",
             Self::invoke_user_code_cmd_teleport_vector3,
-            true,
         );
 
         // System.Void Mirror.NetworkTransformBase::CmdTeleport(UnityEngine.Vector3,UnityEngine.Quaternion)
-        RemoteProcedureCalls::register_command_delegate::<Self>(
+        RemoteProcedureCalls::register_state_command_delegate::<Self>(
             "System.Void Mirror.NetworkTransformBase::CmdTeleport(UnityEngine.Vector3,UnityEngine.Quaternion)",
             Self::invoke_user_code_cmd_teleport_vector3_quaternion,
-            true,
         );
     }
 
@@ -931,7 +874,7 @@ impl NetworkBehaviourTrait for NetworkTransformUnreliable {
         self.network_transform_base.network_behaviour.sync_direction = value
     }
 
-    fn sync_mode(&mut self) -> &SyncMode {
+    fn sync_mode(&self) -> &SyncMode {
         &self.network_transform_base.network_behaviour.sync_mode
     }
 
@@ -1020,7 +963,6 @@ impl NetworkBehaviourTrait for NetworkTransformUnreliable {
             .retain(|&x| x != value);
     }
 
-
     fn game_object(&self) -> &GameObject {
         &self.network_transform_base.network_behaviour.game_object
     }
@@ -1056,6 +998,14 @@ impl NetworkBehaviourTrait for NetworkTransformUnreliable {
             .sync_var_hook_guard = value
     }
 
+    fn enabled(&self) -> bool {
+        self.network_transform_base.network_behaviour.enabled
+    }
+
+    fn __set_enabled(&mut self, value: bool) {
+        self.network_transform_base.network_behaviour.enabled = value
+    }
+
     fn is_dirty(&self) -> bool {
         self.network_transform_base.network_behaviour.is_dirty()
     }
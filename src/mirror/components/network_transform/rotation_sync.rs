@@ -0,0 +1,347 @@
+use crate::mirror::components::network_transform::transform_sync_data::Changed;
+use crate::mirror::core::backend_data::NetworkTransformBaseSetting;
+use nalgebra::{Quaternion, UnitQuaternion, Vector3};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RotationSyncMode {
+    // 压缩后的四元数 (4 字节), 任意朝向都没有万向节问题
+    Compressed,
+    // 按轴发送改变的欧拉角, 每个轴 4 字节
+    Euler,
+    // 只同步绕 Y 轴的朝向 (角色), 接收时 X / Z 轴为 0
+    YawOnly,
+}
+
+impl RotationSyncMode {
+    pub fn from(value: u8) -> Self {
+        match value {
+            2 => RotationSyncMode::Euler,
+            3 => RotationSyncMode::YawOnly,
+            _ => RotationSyncMode::Compressed,
+        }
+    }
+
+    pub fn to_u8(&self) -> u8 {
+        match self {
+            RotationSyncMode::Compressed => 1,
+            RotationSyncMode::Euler => 2,
+            RotationSyncMode::YawOnly => 3,
+        }
+    }
+
+    // rotationSyncMode 为 0 (旧的配置) 时按 compressRotation 选择
+    pub fn from_setting(setting: &NetworkTransformBaseSetting) -> Self {
+        match setting.rotation_sync_mode {
+            0 if setting.compress_rotation => RotationSyncMode::Compressed,
+            0 => RotationSyncMode::Euler,
+            value => Self::from(value),
+        }
+    }
+}
+
+// 旋转同步的欧拉角与 Unity 一致: 单位为度, 范围 [0, 360), 按 Z、X、Y 的顺序旋转
+// 分解方式与 transform.eulerAngles 相同, 只同步部分轴时与客户端的另外几个轴可以直接组合
+pub struct RotationSync;
+
+impl RotationSync {
+    // cos(x) 小于这个值时视为万向节锁定 (X 轴为 ±90 度), 此时 Z 轴固定为 0
+    const GIMBAL_EPSILON: f64 = 1e-4;
+
+    // Quaternion.Euler
+    pub fn from_euler(euler: Vector3<f32>) -> Quaternion<f32> {
+        let axis = |x: f32, y: f32, z: f32, angle: f32| {
+            let (sin, cos) = (angle.to_radians() * 0.5).sin_cos();
+            Quaternion::new(cos, x * sin, y * sin, z * sin)
+        };
+        axis(0.0, 1.0, 0.0, euler.y) * axis(1.0, 0.0, 0.0, euler.x) * axis(0.0, 0.0, 1.0, euler.z)
+    }
+
+    // transform.eulerAngles
+    pub fn to_euler(rotation: Quaternion<f32>) -> Vector3<f32> {
+        let q = UnitQuaternion::from_quaternion(rotation.cast::<f64>());
+        let (x, y, z, w) = (q.i, q.j, q.k, q.w);
+        let sin_x = 2.0 * (w * x - y * z);
+        let m02 = 2.0 * (x * z + w * y);
+        let m22 = 1.0 - 2.0 * (x * x + y * y);
+        let cos_x = (m02 * m02 + m22 * m22).sqrt();
+        let (euler_x, euler_y, euler_z) = if cos_x > Self::GIMBAL_EPSILON {
+            (
+                sin_x.atan2(cos_x),
+                m02.atan2(m22),
+                (2.0 * (x * y + w * z)).atan2(1.0 - 2.0 * (x * x + z * z)),
+            )
+        } else {
+            (
+                std::f64::consts::FRAC_PI_2.copysign(sin_x),
+                (2.0 * (w * y - x * z)).atan2(1.0 - 2.0 * (y * y + z * z)),
+                0.0,
+            )
+        };
+        Vector3::new(
+            Self::normalize(euler_x.to_degrees() as f32),
+            Self::normalize(euler_y.to_degrees() as f32),
+            Self::normalize(euler_z.to_degrees() as f32),
+        )
+    }
+
+    pub fn yaw(rotation: Quaternion<f32>) -> f32 {
+        Self::to_euler(rotation).y
+    }
+
+    pub fn from_yaw(yaw: f32) -> Quaternion<f32> {
+        Self::from_euler(Vector3::new(0.0, yaw, 0.0))
+    }
+
+    // 转换到 [0, 360)
+    pub fn normalize(angle: f32) -> f32 {
+        let angle = angle.rem_euclid(360.0);
+        if angle >= 360.0 {
+            0.0
+        } else {
+            angle
+        }
+    }
+
+    // Mathf.DeltaAngle: from 到 to 的最短角度, 范围 (-180, 180]
+    pub fn delta_angle(from: f32, to: f32) -> f32 {
+        let delta = Self::normalize(to - from);
+        if delta > 180.0 {
+            delta - 360.0
+        } else {
+            delta
+        }
+    }
+
+    // 旋转部分的 Changed 标记, sensitivity 单位为度
+    pub fn changed(
+        mode: RotationSyncMode,
+        last: Quaternion<f32>,
+        current: Quaternion<f32>,
+        sensitivity: f32,
+    ) -> u8 {
+        match mode {
+            RotationSyncMode::Compressed => {
+                let angle = UnitQuaternion::from_quaternion(last)
+                    .angle_to(&UnitQuaternion::from_quaternion(current))
+                    .to_degrees();
+                if angle > sensitivity {
+                    Changed::CompressRot.to_u8() | Changed::Rot.to_u8()
+                } else {
+                    Changed::CompressRot.to_u8()
+                }
+            }
+            RotationSyncMode::Euler => {
+                let (last, current) = (Self::to_euler(last), Self::to_euler(current));
+                let mut changed = Changed::None.to_u8();
+                for (axis, flag) in [Changed::RotX, Changed::RotY, Changed::RotZ]
+                    .iter()
+                    .enumerate()
+                {
+                    if Self::delta_angle(last[axis], current[axis]).abs() > sensitivity {
+                        changed |= flag.to_u8();
+                    }
+                }
+                changed
+            }
+            RotationSyncMode::YawOnly => {
+                if Self::delta_angle(Self::yaw(last), Self::yaw(current)).abs() > sensitivity {
+                    Changed::RotY.to_u8()
+                } else {
+                    Changed::None.to_u8()
+                }
+            }
+        }
+    }
+
+    // 把 current 中 changed 标记的部分合并到 last, 用于记录上一次发送的旋转
+    pub fn merge(
+        mode: RotationSyncMode,
+        changed: u8,
+        last: Quaternion<f32>,
+        current: Quaternion<f32>,
+    ) -> Quaternion<f32> {
+        match mode {
+            RotationSyncMode::Compressed => {
+                if changed & Changed::Rot.to_u8() != 0 {
+                    current
+                } else {
+                    last
+                }
+            }
+            RotationSyncMode::Euler | RotationSyncMode::YawOnly => {
+                Self::merge_euler(mode, changed, last, Self::to_euler(current))
+            }
+        }
+    }
+
+    // 收到的欧拉角只有 changed 标记的轴有效, 其他轴使用 last 的值
+    pub fn merge_euler(
+        mode: RotationSyncMode,
+        changed: u8,
+        last: Quaternion<f32>,
+        euler: Vector3<f32>,
+    ) -> Quaternion<f32> {
+        let mut merged = Self::to_euler(last);
+        for (axis, flag) in [Changed::RotX, Changed::RotY, Changed::RotZ]
+            .iter()
+            .enumerate()
+        {
+            if changed & flag.to_u8() != 0 {
+                merged[axis] = euler[axis];
+            }
+        }
+        if mode == RotationSyncMode::YawOnly {
+            return Self::from_yaw(merged.y);
+        }
+        Self::from_euler(merged)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::components::network_transform::transform_sync_data::SyncData;
+    use crate::mirror::core::messages::NetworkMessageTrait;
+    use crate::mirror::core::network_reader::NetworkReader;
+    use crate::mirror::core::network_writer::NetworkWriter;
+
+    fn assert_rotation_eq(a: Quaternion<f32>, b: Quaternion<f32>) {
+        let angle = UnitQuaternion::from_quaternion(a)
+            .angle_to(&UnitQuaternion::from_quaternion(b))
+            .to_degrees();
+        assert!(angle < 0.01, "{:?} != {:?} ({} degrees)", a, b, angle);
+    }
+
+    #[test]
+    fn test_euler() {
+        // Unity: Quaternion.Euler(10, 20, 30)
+        let rotation = RotationSync::from_euler(Vector3::new(10.0, 20.0, 30.0));
+        assert!(
+            (rotation.coords - Quaternion::new(0.9515485, 0.1276794, 0.1448781, 0.2392983).coords)
+                .norm()
+                < 1e-5
+        );
+        let euler = RotationSync::to_euler(rotation);
+        assert!((euler - Vector3::new(10.0, 20.0, 30.0)).norm() < 1e-3);
+
+        // 负角度转换到 [0, 360)
+        let euler =
+            RotationSync::to_euler(RotationSync::from_euler(Vector3::new(-30.0, -90.0, 0.0)));
+        assert!((euler - Vector3::new(330.0, 270.0, 0.0)).norm() < 1e-3);
+
+        // 万向节锁定时 Z 轴为 0, 旋转不变
+        for euler in [
+            Vector3::new(90.0, 30.0, 40.0),
+            Vector3::new(-90.0, 10.0, 20.0),
+            Vector3::new(89.999, 45.0, 10.0),
+        ] {
+            let rotation = RotationSync::from_euler(euler);
+            assert_rotation_eq(
+                RotationSync::from_euler(RotationSync::to_euler(rotation)),
+                rotation,
+            );
+        }
+        let euler =
+            RotationSync::to_euler(RotationSync::from_euler(Vector3::new(90.0, 30.0, 40.0)));
+        assert!((euler.x - 90.0).abs() < 1e-3 && euler.z == 0.0);
+
+        assert_eq!(RotationSync::delta_angle(350.0, 10.0), 20.0);
+        assert_eq!(RotationSync::delta_angle(10.0, 350.0), -20.0);
+    }
+
+    #[test]
+    fn test_rotation_sync_modes() {
+        let last = RotationSync::from_euler(Vector3::new(0.0, 359.0, 0.0));
+        let current = RotationSync::from_euler(Vector3::new(5.0, 1.0, 0.0));
+
+        // 跨过 0 度只算 2 度
+        assert_eq!(
+            RotationSync::changed(RotationSyncMode::Euler, last, current, 1.0),
+            Changed::RotX.to_u8() | Changed::RotY.to_u8()
+        );
+        assert_eq!(
+            RotationSync::changed(RotationSyncMode::Euler, last, current, 3.0),
+            Changed::RotX.to_u8()
+        );
+        assert_eq!(
+            RotationSync::changed(RotationSyncMode::YawOnly, last, current, 1.0),
+            Changed::RotY.to_u8()
+        );
+        assert_eq!(
+            RotationSync::changed(RotationSyncMode::Compressed, last, current, 10.0),
+            Changed::CompressRot.to_u8()
+        );
+
+        // 只合并改变的轴
+        let merged = RotationSync::merge(
+            RotationSyncMode::Euler,
+            Changed::RotY.to_u8(),
+            last,
+            current,
+        );
+        assert_rotation_eq(
+            merged,
+            RotationSync::from_euler(Vector3::new(0.0, 1.0, 0.0)),
+        );
+        let merged = RotationSync::merge(
+            RotationSyncMode::YawOnly,
+            Changed::RotY.to_u8(),
+            last,
+            current,
+        );
+        assert_rotation_eq(merged, RotationSync::from_yaw(1.0));
+        let merged = RotationSync::merge(
+            RotationSyncMode::Compressed,
+            Changed::CompressRot.to_u8(),
+            last,
+            current,
+        );
+        assert_eq!(merged, last);
+
+        assert_eq!(
+            RotationSyncMode::from(RotationSyncMode::YawOnly.to_u8()),
+            RotationSyncMode::YawOnly
+        );
+        let mut setting = NetworkTransformBaseSetting::default();
+        assert_eq!(
+            RotationSyncMode::from_setting(&setting),
+            RotationSyncMode::Euler
+        );
+        setting.compress_rotation = true;
+        assert_eq!(
+            RotationSyncMode::from_setting(&setting),
+            RotationSyncMode::Compressed
+        );
+        setting.rotation_sync_mode = RotationSyncMode::YawOnly.to_u8();
+        assert_eq!(
+            RotationSyncMode::from_setting(&setting),
+            RotationSyncMode::YawOnly
+        );
+    }
+
+    #[test]
+    fn test_sync_data_yaw_only() {
+        // 只发送 Y 轴, 与 C# 的 SyncData 格式相同
+        let rotation = RotationSync::from_euler(Vector3::new(0.0, 90.0, 0.0));
+        let mut sync_data = SyncData::new(
+            Changed::RotY.to_u8(),
+            Vector3::zeros(),
+            rotation,
+            Vector3::zeros(),
+        );
+        let mut writer = NetworkWriter::new();
+        sync_data.serialize(&mut writer);
+        assert_eq!(writer.to_bytes().len(), 1 + 4);
+        let received = SyncData::deserialize(&mut NetworkReader::new_with_bytes(writer.to_bytes()));
+        assert!((received.vec_rotation.y - 90.0).abs() < 1e-3);
+
+        let last = RotationSync::from_euler(Vector3::new(10.0, 0.0, 0.0));
+        let merged = RotationSync::merge_euler(
+            RotationSyncMode::YawOnly,
+            received.changed_data_byte,
+            last,
+            received.vec_rotation,
+        );
+        assert_rotation_eq(merged, rotation);
+    }
+}
@@ -1,8 +1,9 @@
+use crate::mirror::components::network_transform::rotation_sync::RotationSync;
 use crate::mirror::core::messages::NetworkMessageTrait;
 use crate::mirror::core::network_reader::{NetworkReader, NetworkReaderTrait};
 use crate::mirror::core::network_writer::{NetworkWriter, NetworkWriterTrait};
 use crate::mirror::core::tools::compress::CompressTrait;
-use nalgebra::{Quaternion, UnitQuaternion, Vector3};
+use nalgebra::{Quaternion, Vector3};
 use std::any::Any;
 use std::fmt::Debug;
 use std::ops::BitOrAssign;
@@ -23,12 +24,11 @@ pub struct SyncData {
 
 impl SyncData {
     pub fn new(changed: u8, position: Vector3<f32>, quat_rotation: Quaternion<f32>, scale: Vector3<f32>) -> Self {
-        let rotation = UnitQuaternion::from_quaternion(quat_rotation);
         Self {
             changed_data_byte: changed,
             position,
             quat_rotation,
-            vec_rotation: Vector3::new(rotation.euler_angles().0, rotation.euler_angles().1, rotation.euler_angles().2),
+            vec_rotation: RotationSync::to_euler(quat_rotation),
             scale,
         }
     }
@@ -80,10 +80,11 @@ impl NetworkMessageTrait for SyncData {
             scale.z = reader.read_float();
         }
 
+        // 欧拉角与 Unity 的 eulerAngles 一致, 没有发送的轴为 0, 由接收的组件用上一次的旋转补全
         if changed & Changed::CompressRot.to_u8() > 0 {
-            (vec_rotation.x, vec_rotation.y, vec_rotation.z) = UnitQuaternion::from_quaternion(quaternion).euler_angles();
+            vec_rotation = RotationSync::to_euler(quaternion);
         } else {
-            quaternion = *UnitQuaternion::from_euler_angles(vec_rotation.x, vec_rotation.y, vec_rotation.z);
+            quaternion = RotationSync::from_euler(vec_rotation);
         }
 
         Self {
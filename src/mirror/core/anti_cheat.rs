@@ -0,0 +1,398 @@
+use crate::mirror::core::messages::DisconnectReason;
+use crate::mirror::core::network_context::ContextLocal;
+use crate::mirror::core::network_server::NetworkServer;
+use crate::mirror::core::network_time::NetworkTime;
+use crate::mirror::core::transport::Transport;
+use crate::{log_error, log_warn};
+use atomic::Atomic;
+use dashmap::{DashMap, DashSet};
+use lazy_static::lazy_static;
+use std::collections::VecDeque;
+use std::sync::atomic::Ordering;
+use std::sync::RwLock;
+
+// 可疑行为的来源
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum CheatSignal {
+    // Command 频率超过上限
+    CommandRate,
+    // 移动校验失败 (由组件或游戏逻辑上报)
+    MovementViolation,
+    // 客户端时间戳倒退或者比服务器时间走得更快
+    ImpossibleTimestamp,
+    // 无法解析的数据包
+    MalformedPacket,
+}
+
+// 分数超过阈值后触发的动作
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum AntiCheatAction {
+    Log,
+    Kick,
+    Ban,
+}
+
+// 自定义处理: (connection_id, action, signal, score)
+pub type AntiCheatActionHandler = fn(u64, AntiCheatAction, CheatSignal, f64);
+
+#[derive(Debug, Clone)]
+pub struct AntiCheatSettings {
+    // 每秒允许的 Command 数量
+    pub command_rate_limit: u32,
+    // 客户端时间允许的误差 (秒), 超过则认为时间戳倒退或走得过快
+    pub timestamp_tolerance: f64,
+    // 每种信号增加的分数
+    pub command_rate_weight: f64,
+    pub movement_violation_weight: f64,
+    pub impossible_timestamp_weight: f64,
+    pub malformed_packet_weight: f64,
+    // 每秒衰减的分数
+    pub decay_per_second: f64,
+    pub log_threshold: f64,
+    pub kick_threshold: f64,
+    pub ban_threshold: f64,
+}
+
+impl Default for AntiCheatSettings {
+    fn default() -> Self {
+        Self {
+            command_rate_limit: 120,
+            timestamp_tolerance: 1.0,
+            command_rate_weight: 5.0,
+            movement_violation_weight: 10.0,
+            impossible_timestamp_weight: 10.0,
+            malformed_packet_weight: 20.0,
+            decay_per_second: 1.0,
+            log_threshold: 20.0,
+            kick_threshold: 50.0,
+            ban_threshold: 100.0,
+        }
+    }
+}
+
+impl AntiCheatSettings {
+    pub fn weight(&self, signal: CheatSignal) -> f64 {
+        match signal {
+            CheatSignal::CommandRate => self.command_rate_weight,
+            CheatSignal::MovementViolation => self.movement_violation_weight,
+            CheatSignal::ImpossibleTimestamp => self.impossible_timestamp_weight,
+            CheatSignal::MalformedPacket => self.malformed_packet_weight,
+        }
+    }
+
+    pub fn action_for_score(&self, score: f64) -> Option<AntiCheatAction> {
+        if score >= self.ban_threshold {
+            Some(AntiCheatAction::Ban)
+        } else if score >= self.kick_threshold {
+            Some(AntiCheatAction::Kick)
+        } else if score >= self.log_threshold {
+            Some(AntiCheatAction::Log)
+        } else {
+            None
+        }
+    }
+}
+
+// 每个连接的可疑分数
+#[derive(Debug, Clone, Default)]
+pub struct SuspicionScore {
+    pub score: f64,
+    pub last_update_time: f64,
+    pub last_action: Option<AntiCheatAction>,
+    pub command_count: u32,
+    pub command_window_start: f64,
+    pub last_remote_time: f64,
+    pub last_local_time: f64,
+}
+
+impl SuspicionScore {
+    pub fn decay(&mut self, local_time: f64, settings: &AntiCheatSettings) {
+        let elapsed = (local_time - self.last_update_time).max(0.0);
+        self.score = (self.score - elapsed * settings.decay_per_second).max(0.0);
+        self.last_update_time = local_time;
+        // 分数降下来之后允许再次触发
+        if settings.action_for_score(self.score) < self.last_action {
+            self.last_action = settings.action_for_score(self.score);
+        }
+    }
+
+    // 增加分数, 返回新触发的动作
+    pub fn report(
+        &mut self,
+        signal: CheatSignal,
+        local_time: f64,
+        settings: &AntiCheatSettings,
+    ) -> Option<AntiCheatAction> {
+        self.decay(local_time, settings);
+        self.score += settings.weight(signal);
+        let action = settings.action_for_score(self.score);
+        if action > self.last_action {
+            self.last_action = action;
+            return action;
+        }
+        None
+    }
+
+    // 统计一秒内的 Command 数量, 超过上限返回 true
+    pub fn on_command(&mut self, local_time: f64, settings: &AntiCheatSettings) -> bool {
+        if local_time - self.command_window_start >= 1.0 {
+            self.command_window_start = local_time;
+            self.command_count = 0;
+        }
+        self.command_count += 1;
+        self.command_count > settings.command_rate_limit
+    }
+
+    // 客户端时间戳必须单调递增, 并且不能比服务器时间走得更快
+    pub fn on_time_snapshot(
+        &mut self,
+        remote_time: f64,
+        local_time: f64,
+        settings: &AntiCheatSettings,
+    ) -> bool {
+        let first = self.last_local_time == 0.0;
+        let remote_elapsed = remote_time - self.last_remote_time;
+        let local_elapsed = local_time - self.last_local_time;
+        self.last_remote_time = remote_time;
+        self.last_local_time = local_time;
+        !first
+            && (remote_elapsed < -settings.timestamp_tolerance
+                || remote_elapsed > local_elapsed + settings.timestamp_tolerance)
+    }
+}
+
+// (connection_id, 动作, 信号, 时间)
+type PendingAction = (u64, AntiCheatAction, CheatSignal, f64);
+
+// AntiCheat 静态变量
+lazy_static! {
+    static ref ENABLED: ContextLocal<Atomic<bool>> = ContextLocal::new(|| Atomic::new(false));
+    static ref SETTINGS: ContextLocal<RwLock<AntiCheatSettings>> =
+        ContextLocal::new(|| RwLock::new(AntiCheatSettings::default()));
+    static ref ACTION_HANDLER: ContextLocal<RwLock<Option<AntiCheatActionHandler>>> =
+        ContextLocal::new(|| RwLock::new(None));
+    static ref SUSPICION_SCORES: ContextLocal<DashMap<u64, SuspicionScore>> =
+        ContextLocal::new(DashMap::new);
+    static ref BANNED_ADDRESSES: ContextLocal<DashSet<String>> = ContextLocal::new(DashSet::new);
+    // 上报时可能持有连接的锁, 触发的动作在 update 中统一处理
+    static ref PENDING_ACTIONS: ContextLocal<RwLock<VecDeque<PendingAction>>> =
+        ContextLocal::new(|| RwLock::new(VecDeque::new()));
+}
+
+pub struct AntiCheatStatic;
+
+impl AntiCheatStatic {
+    pub fn enabled() -> bool {
+        ENABLED.load(Ordering::Relaxed)
+    }
+    pub fn set_enabled(value: bool) {
+        ENABLED.store(value, Ordering::Relaxed);
+    }
+    pub fn settings() -> AntiCheatSettings {
+        match SETTINGS.read() {
+            Ok(settings) => settings.clone(),
+            Err(e) => {
+                log_error!("AntiCheat failed to get settings: {:?}", e);
+                AntiCheatSettings::default()
+            }
+        }
+    }
+    pub fn set_settings(settings: AntiCheatSettings) {
+        match SETTINGS.write() {
+            Ok(mut s) => *s = settings,
+            Err(e) => {
+                log_error!("AntiCheat failed to set settings: {:?}", e);
+            }
+        }
+    }
+    // 设置后由游戏自己处理 log / kick / ban
+    pub fn set_action_handler(handler: Option<AntiCheatActionHandler>) {
+        match ACTION_HANDLER.write() {
+            Ok(mut h) => *h = handler,
+            Err(e) => {
+                log_error!("AntiCheat failed to set action handler: {:?}", e);
+            }
+        }
+    }
+    pub fn action_handler() -> Option<AntiCheatActionHandler> {
+        match ACTION_HANDLER.read() {
+            Ok(handler) => *handler,
+            Err(e) => {
+                log_error!("AntiCheat failed to get action handler: {:?}", e);
+                None
+            }
+        }
+    }
+    pub fn suspicion_scores() -> &'static DashMap<u64, SuspicionScore> {
+        &SUSPICION_SCORES
+    }
+    pub fn banned_addresses() -> &'static DashSet<String> {
+        &BANNED_ADDRESSES
+    }
+}
+
+pub struct AntiCheat;
+
+impl AntiCheat {
+    // 上报一次可疑行为
+    pub fn report(connection_id: u64, signal: CheatSignal) {
+        if !AntiCheatStatic::enabled() {
+            return;
+        }
+        let settings = AntiCheatStatic::settings();
+        let (action, score) = {
+            let mut suspicion = SUSPICION_SCORES.entry(connection_id).or_default();
+            let action = suspicion.report(signal, NetworkTime::local_time(), &settings);
+            (action, suspicion.score)
+        };
+        if let Some(action) = action {
+            match PENDING_ACTIONS.write() {
+                Ok(mut pending) => pending.push_back((connection_id, action, signal, score)),
+                Err(e) => {
+                    log_error!("AntiCheat failed to get pending actions: {:?}", e);
+                }
+            }
+        }
+    }
+
+    // 在 NetworkServer::network_late_update 中调用
+    pub fn update() {
+        let pending: Vec<(u64, AntiCheatAction, CheatSignal, f64)> = match PENDING_ACTIONS.write() {
+            Ok(mut pending) => pending.drain(..).collect(),
+            Err(e) => {
+                log_error!("AntiCheat failed to get pending actions: {:?}", e);
+                return;
+            }
+        };
+        for (connection_id, action, signal, score) in pending {
+            Self::trigger(connection_id, action, signal, score);
+        }
+    }
+
+    pub fn on_command(connection_id: u64) {
+        if !AntiCheatStatic::enabled() {
+            return;
+        }
+        let settings = AntiCheatStatic::settings();
+        let exceeded = SUSPICION_SCORES
+            .entry(connection_id)
+            .or_default()
+            .on_command(NetworkTime::local_time(), &settings);
+        if exceeded {
+            Self::report(connection_id, CheatSignal::CommandRate);
+        }
+    }
+
+    pub fn on_time_snapshot(connection_id: u64, remote_time: f64) {
+        if !AntiCheatStatic::enabled() {
+            return;
+        }
+        let settings = AntiCheatStatic::settings();
+        let impossible = SUSPICION_SCORES
+            .entry(connection_id)
+            .or_default()
+            .on_time_snapshot(remote_time, NetworkTime::local_time(), &settings);
+        if impossible {
+            Self::report(connection_id, CheatSignal::ImpossibleTimestamp);
+        }
+    }
+
+    pub fn on_disconnected(connection_id: u64) {
+        SUSPICION_SCORES.remove(&connection_id);
+    }
+
+    pub fn reset() {
+        SUSPICION_SCORES.clear();
+        if let Ok(mut pending) = PENDING_ACTIONS.write() {
+            pending.clear();
+        }
+    }
+
+    pub fn is_banned(address: &str) -> bool {
+        BANNED_ADDRESSES.contains(address)
+    }
+
+    fn trigger(connection_id: u64, action: AntiCheatAction, signal: CheatSignal, score: f64) {
+        if let Some(handler) = AntiCheatStatic::action_handler() {
+            handler(connection_id, action, signal, score);
+            return;
+        }
+        log_warn!(
+            "AntiCheat: connectionId: {} {:?} after {:?} (score: {:.1})",
+            connection_id,
+            action,
+            signal,
+            score
+        );
+        match action {
+            AntiCheatAction::Log => {}
+            AntiCheatAction::Kick => {
+                NetworkServer::disconnect_with_reason(connection_id, DisconnectReason::Kick);
+            }
+            AntiCheatAction::Ban => {
+                if let Some(transport) = Transport::active_transport() {
+                    BANNED_ADDRESSES.insert(transport.server_get_client_address(connection_id));
+                }
+                NetworkServer::disconnect_with_reason(connection_id, DisconnectReason::Kick);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_suspicion_score() {
+        let settings = AntiCheatSettings::default();
+        let mut suspicion = SuspicionScore::default();
+
+        assert_eq!(
+            suspicion.report(CheatSignal::MovementViolation, 0.0, &settings),
+            None
+        );
+        assert_eq!(
+            suspicion.report(CheatSignal::MovementViolation, 0.0, &settings),
+            Some(AntiCheatAction::Log)
+        );
+        // 同一个动作不会重复触发
+        assert_eq!(
+            suspicion.report(CheatSignal::MovementViolation, 0.0, &settings),
+            None
+        );
+        assert_eq!(
+            suspicion.report(CheatSignal::MalformedPacket, 0.0, &settings),
+            Some(AntiCheatAction::Kick)
+        );
+
+        // 衰减之后重新触发
+        suspicion.decay(100.0, &settings);
+        assert_eq!(suspicion.score, 0.0);
+        assert_eq!(suspicion.last_action, None);
+        assert_eq!(
+            suspicion.report(CheatSignal::MalformedPacket, 100.0, &settings),
+            Some(AntiCheatAction::Log)
+        );
+    }
+
+    #[test]
+    fn test_command_rate_and_timestamps() {
+        let settings = AntiCheatSettings {
+            command_rate_limit: 2,
+            ..AntiCheatSettings::default()
+        };
+        let mut suspicion = SuspicionScore::default();
+        assert!(!suspicion.on_command(1.0, &settings));
+        assert!(!suspicion.on_command(1.1, &settings));
+        assert!(suspicion.on_command(1.2, &settings));
+        assert!(!suspicion.on_command(2.0, &settings));
+
+        assert!(!suspicion.on_time_snapshot(10.0, 1.0, &settings));
+        assert!(!suspicion.on_time_snapshot(10.5, 1.5, &settings));
+        // 倒退
+        assert!(suspicion.on_time_snapshot(9.0, 2.0, &settings));
+        // 比服务器快太多
+        assert!(suspicion.on_time_snapshot(20.0, 2.5, &settings));
+    }
+}
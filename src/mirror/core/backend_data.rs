@@ -1,16 +1,22 @@
 use crate::mirror::components::network_animator::Animator;
+use crate::mirror::core::master_server::MasterServerConfig;
 use crate::mirror::core::network_behaviour::GameObject;
+use crate::mirror::core::network_context::ContextLocal;
 use crate::mirror::core::network_identity::NetworkIdentity;
 use crate::mirror::core::network_loop::NetworkLoop;
+use crate::mirror::core::tools::stable_hash::StableHash;
 use crate::mirror::transports::kcp2k::kcp2k_transport::Kcp2kTransportConfig;
+use crate::mirror::transports::relay::relay_transport::RelayTransportConfig;
 use crate::{log_error, log_info};
 use config::Config;
 use lazy_static::lazy_static;
 use notify::event::{DataChange, ModifyKind};
 use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
 use serde_repr::{Deserialize_repr, Serialize_repr};
-use std::collections::{HashSet, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::{Display, Formatter};
 use std::path::Path;
 use std::sync::mpsc::channel;
 use std::sync::{OnceLock, RwLock};
@@ -18,6 +24,11 @@ use std::time::Duration;
 
 lazy_static! {
     static ref BACKEND_DATA_FILE: String = "tobackend.json".to_string();
+    // 代码中注册的 BackendData, 优先于 tobackend.json
+    static ref ACTIVE_BACKEND_DATA: ContextLocal<RwLock<Option<BackendData>>> =
+        ContextLocal::new(|| RwLock::new(None));
+    // 当前版本的字段模板, 数组中放一个元素的模板
+    static ref SCHEMA_TEMPLATE: Value = BackendDataSchema::template();
 }
 
 pub struct BackendDataStatic;
@@ -30,7 +41,10 @@ impl BackendDataStatic {
             if !Path::new(BACKEND_DATA_FILE.as_str()).exists() {
                 std::fs::write(BACKEND_DATA_FILE.as_str(), {
                     let backend_data = BackendData {
+                        schema_version: BackendDataSchema::CURRENT_VERSION,
                         kcp2k_config: Default::default(),
+                        master_server_config: Default::default(),
+                        relay_config: Default::default(),
                         methods: Vec::new(),
                         network_identities: Vec::new(),
                         network_manager_settings: Vec::new(),
@@ -95,15 +109,15 @@ impl BackendDataStatic {
                         Ok(backend_data) => {
                             *Self::tobackend().write().unwrap() = backend_data;
                             NetworkLoop::set_stop(true);
-                            log_info!(format!("{} has been updated", BACKEND_DATA_FILE.as_str()));
+                            log_info!("{} has been updated", BACKEND_DATA_FILE.as_str());
                         }
                         Err(e) => {
-                            log_error!(format!("watch error: {:?}", e));
+                            log_error!("watch error: {:?}", e);
                         }
                     }
                 }
                 Err(e) => {
-                    log_error!(format!("watch error: {:?}", e));
+                    log_error!("watch error: {:?}", e);
                 }
                 _ => {}
             }
@@ -111,28 +125,292 @@ impl BackendDataStatic {
     }
 
     pub fn get_backend_data() -> BackendData {
-        match Self::tobackend().read().unwrap().clone().try_deserialize() {
+        match Self::try_get_backend_data() {
             Ok(backend_data) => backend_data,
             Err(e) => {
-                panic!("Failed to deserialize BackendData: {:?}", e);
+                panic!("Failed to deserialize BackendData: {}", e);
             }
         }
     }
 
+    pub fn try_get_backend_data() -> Result<BackendData, BackendDataImportError> {
+        if let Ok(active) = ACTIVE_BACKEND_DATA.read() {
+            if let Some(backend_data) = active.as_ref() {
+                return Ok(backend_data.clone());
+            }
+        }
+        match Self::tobackend().read().unwrap().clone().try_deserialize::<Value>() {
+            Ok(value) => BackendDataSchema::load(value),
+            Err(e) => Err(BackendDataImportError::Parse(e.to_string())),
+        }
+    }
+
+    // 在当前上下文中使用 backend_data 代替 tobackend.json, 通常由 BackendDataBuilder::register 调用
+    // 需要在 NetworkManager 初始化之前设置
+    pub fn set_backend_data(backend_data: BackendData) {
+        if let Ok(mut active) = ACTIVE_BACKEND_DATA.write() {
+            *active = Some(backend_data);
+        }
+    }
+
+    // 恢复使用 tobackend.json
+    pub fn clear_backend_data() {
+        if let Ok(mut active) = ACTIVE_BACKEND_DATA.write() {
+            *active = None;
+        }
+    }
+
     pub fn import(path: &'static str) -> BackendData {
-        // 读取 JSON 文件内容
-        match std::fs::read_to_string(path) {
-            Ok(data) => {
-                // 将 JSON 文件内容反序列化为 Data 结构体
-                match serde_json::from_str::<BackendData>(&data) {
-                    Ok(backend_data) => backend_data,
-                    Err(e) => {
-                        panic!("Failed to deserialize BackendData: {:?}", e);
+        match Self::try_import(path) {
+            Ok(backend_data) => backend_data,
+            Err(e) => {
+                panic!("Failed to import BackendData: {}", e);
+            }
+        }
+    }
+
+    // 旧版本的导出数据先迁移到当前版本, 字段不匹配时返回所有缺少和未知的字段
+    pub fn try_import(path: &str) -> Result<BackendData, BackendDataImportError> {
+        let data = std::fs::read_to_string(path)
+            .map_err(|e| BackendDataImportError::Io(format!("{}: {}", path, e)))?;
+        let value = serde_json::from_str::<Value>(&data)
+            .map_err(|e| BackendDataImportError::Parse(e.to_string()))?;
+        BackendDataSchema::load(value)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum BackendDataImportError {
+    Io(String),
+    Parse(String),
+    // 导出数据的版本比当前支持的新, 需要升级服务器
+    UnsupportedVersion(u32),
+    // 路径形如 networkIdentities[0].networkBehaviourComponents[1].value.componentType
+    Schema {
+        missing: Vec<String>,
+        unknown: Vec<String>,
+    },
+}
+
+impl Display for BackendDataImportError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BackendDataImportError::Io(e) => write!(f, "read error: {}", e),
+            BackendDataImportError::Parse(e) => write!(f, "parse error: {}", e),
+            BackendDataImportError::UnsupportedVersion(version) => write!(
+                f,
+                "schemaVersion {} is newer than supported version {}",
+                version,
+                BackendDataSchema::CURRENT_VERSION
+            ),
+            BackendDataImportError::Schema { missing, unknown } => write!(
+                f,
+                "schema mismatch, missing fields: [{}], unknown fields: [{}]",
+                missing.join(", "),
+                unknown.join(", ")
+            ),
+        }
+    }
+}
+
+// 导出数据的版本, 没有 schemaVersion 字段的旧数据为版本 1
+// Unity 端的导出工具修改字段时增加版本, 并在 MIGRATIONS 中添加从上一个版本迁移的函数
+pub struct BackendDataSchema;
+
+impl BackendDataSchema {
+    pub const CURRENT_VERSION: u32 = 2;
+    // MIGRATIONS[i] 把版本 i + 1 迁移到版本 i + 2
+    const MIGRATIONS: [fn(&mut Value); 1] = [Self::migrate_v1_to_v2];
+    // 可以整体省略的字段, kcp2k_config 和 relay_config 中的字段也可以省略
+    const OPTIONAL_FIELDS: [&'static str; 3] =
+        ["kcp2k_config", "master_server_config", "relay_config"];
+
+    pub fn version(value: &Value) -> Result<u32, BackendDataImportError> {
+        match value.get("schemaVersion") {
+            None => Ok(1),
+            Some(version) => match version.as_u64() {
+                Some(version) => Ok((version as u32).max(1)),
+                None => Err(BackendDataImportError::Parse(format!(
+                    "invalid schemaVersion: {}",
+                    version
+                ))),
+            },
+        }
+    }
+
+    pub fn load(mut value: Value) -> Result<BackendData, BackendDataImportError> {
+        Self::migrate(&mut value)?;
+        let (missing, unknown) = Self::validate(&value);
+        if !missing.is_empty() || !unknown.is_empty() {
+            return Err(BackendDataImportError::Schema { missing, unknown });
+        }
+        serde_json::from_value::<BackendData>(value)
+            .map_err(|e| BackendDataImportError::Parse(e.to_string()))
+    }
+
+    // 依次执行迁移函数, 完成后 schemaVersion 为 CURRENT_VERSION
+    pub fn migrate(value: &mut Value) -> Result<(), BackendDataImportError> {
+        if !value.is_object() {
+            return Err(BackendDataImportError::Parse(
+                "BackendData must be a JSON object".to_string(),
+            ));
+        }
+        let version = Self::version(value)?;
+        if version > Self::CURRENT_VERSION {
+            return Err(BackendDataImportError::UnsupportedVersion(version));
+        }
+        for from in version..Self::CURRENT_VERSION {
+            Self::MIGRATIONS[from as usize - 1](value);
+            log_info!(
+                "BackendData migrated from schemaVersion {} to {}",
+                from,
+                from + 1
+            );
+        }
+        value["schemaVersion"] = json!(Self::CURRENT_VERSION);
+        Ok(())
+    }
+
+    // 版本 1 的导出数据没有 syncMode、同步轴和 networkBoneSyncSetting
+    fn migrate_v1_to_v2(value: &mut Value) {
+        let Some(identities) = value
+            .get_mut("networkIdentities")
+            .and_then(Value::as_array_mut)
+        else {
+            return;
+        };
+        for identity in identities.iter_mut() {
+            let Some(components) = identity
+                .get_mut("networkBehaviourComponents")
+                .and_then(Value::as_array_mut)
+            else {
+                continue;
+            };
+            for component in components.iter_mut() {
+                let Some(component) = component.get_mut("value").and_then(Value::as_object_mut)
+                else {
+                    continue;
+                };
+                if let Some(setting) = component
+                    .get_mut("networkBehaviourSetting")
+                    .and_then(Value::as_object_mut)
+                {
+                    setting.entry("syncMode").or_insert(json!(0));
+                }
+                if let Some(setting) = component
+                    .get_mut("networkTransformBaseSetting")
+                    .and_then(Value::as_object_mut)
+                {
+                    for axis in [
+                        "syncPositionX",
+                        "syncPositionY",
+                        "syncPositionZ",
+                        "syncScaleX",
+                        "syncScaleY",
+                        "syncScaleZ",
+                    ] {
+                        setting.entry(axis).or_insert(json!(true));
                     }
                 }
+                component
+                    .entry("networkBoneSyncSetting")
+                    .or_insert(json!(NetworkBoneSyncSetting::default()));
             }
-            Err(e) => {
-                panic!("Failed to read BackendData: {:?}", e);
+        }
+    }
+
+    fn template() -> Value {
+        let mut template = json!(BackendData::default());
+        template["methods"] = json!([MethodData::new(MethodType::None, "", "")
+            .with_parameter("", "")
+            .with_sync_var("")
+            .with_rpc("")]);
+        template["networkIdentities"] = json!([NetworkIdentityData::new(0)
+            .with_component(NetworkBehaviourComponent::new(""))]);
+        template["networkManagerSettings"] = json!([NetworkManagerSetting::default()]);
+        template["networkRoomManagerSettings"] = json!([{
+            "showRoomGUI": false,
+            "minPlayers": 0,
+            "roomPlayerPrefab": "",
+            "roomScene": "",
+            "gameplayScene": "",
+            "networkManagerSetting": NetworkManagerSetting::default(),
+        }]);
+        template["sceneIds"] = json!([{ "key": "", "value": "" }]);
+        template["syncVars"] = json!([SyncVarData::new("", "", "", 0)]);
+        template["assets"] = json!([{ "key": 0, "value": "" }]);
+        template
+    }
+
+    // 返回 (缺少的字段, 未知的字段)
+    pub fn validate(value: &Value) -> (Vec<String>, Vec<String>) {
+        let mut missing = Vec::new();
+        let mut unknown = Vec::new();
+        Self::compare("", &SCHEMA_TEMPLATE, value, false, &mut missing, &mut unknown);
+        (missing, unknown)
+    }
+
+    fn compare(
+        path: &str,
+        template: &Value,
+        value: &Value,
+        allow_missing: bool,
+        missing: &mut Vec<String>,
+        unknown: &mut Vec<String>,
+    ) {
+        let field_path = |key: &str| match path {
+            "" => key.to_string(),
+            _ => format!("{}.{}", path, key),
+        };
+        match (template, value) {
+            (Value::Object(template), Value::Object(value)) => {
+                for (key, field) in template.iter() {
+                    let optional = allow_missing
+                        || (path.is_empty() && Self::OPTIONAL_FIELDS.contains(&key.as_str()));
+                    match value.get(key) {
+                        Some(child) => Self::compare(
+                            &field_path(key),
+                            field,
+                            child,
+                            path.is_empty() && (key == "kcp2k_config" || key == "relay_config"),
+                            missing,
+                            unknown,
+                        ),
+                        None if !optional => missing.push(field_path(key)),
+                        None => {}
+                    }
+                }
+                Self::unknown_fields(&field_path, template, value, unknown);
+            }
+            // 模板数组为空时不检查元素
+            (Value::Array(template), Value::Array(value)) => {
+                if let Some(template) = template.first() {
+                    for (index, child) in value.iter().enumerate() {
+                        Self::compare(
+                            &format!("{}[{}]", path, index),
+                            template,
+                            child,
+                            allow_missing,
+                            missing,
+                            unknown,
+                        );
+                    }
+                }
+            }
+            // 类型错误由 serde 报告
+            _ => {}
+        }
+    }
+
+    fn unknown_fields<F: Fn(&str) -> String>(
+        field_path: &F,
+        template: &Map<String, Value>,
+        value: &Map<String, Value>,
+        unknown: &mut Vec<String>,
+    ) {
+        for key in value.keys() {
+            if !template.contains_key(key) {
+                unknown.push(field_path(key));
             }
         }
     }
@@ -173,6 +451,50 @@ pub struct MethodData {
     pub var_list: Vec<KeyValue<u8, String>>,
 }
 
+impl MethodData {
+    // name 是 C# 的完整签名, 例如 "System.Void QuickStart.PlayerScript::CmdSetupPlayer(System.String)"
+    pub fn new(r#type: MethodType, sub_class: &str, name: &str) -> Self {
+        Self {
+            hash_code: name.get_fn_stable_hash_code(),
+            sub_class: sub_class.to_string(),
+            name: name.to_string(),
+            requires_authority: true,
+            r#type,
+            parameters: Vec::new(),
+            rpc_list: Vec::new(),
+            var_list: Vec::new(),
+        }
+    }
+
+    pub fn with_requires_authority(mut self, requires_authority: bool) -> Self {
+        self.requires_authority = requires_authority;
+        self
+    }
+
+    pub fn with_parameter(mut self, name: &str, r#type: &str) -> Self {
+        self.parameters.push(KeyValue {
+            key: name.to_string(),
+            value: r#type.to_string(),
+        });
+        self
+    }
+
+    // NetworkCommonBehaviour 按参数顺序把参数写入同步变量, 每个参数对应一个
+    pub fn with_sync_var(mut self, full_name: &str) -> Self {
+        self.var_list.push(KeyValue {
+            key: self.var_list.len() as u8,
+            value: full_name.to_string(),
+        });
+        self
+    }
+
+    // 执行后发送的 ClientRpc
+    pub fn with_rpc(mut self, name: &str) -> Self {
+        self.rpc_list.push(name.to_string());
+        self
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct SyncVarData {
     #[serde(rename = "fullname")]
@@ -189,11 +511,33 @@ pub struct SyncVarData {
     pub dirty_bit: u32,
 }
 
+impl SyncVarData {
+    // full_name 为 "{sub_class}.{name}"
+    pub fn new(sub_class: &str, name: &str, r#type: &str, dirty_bit: u32) -> Self {
+        Self {
+            full_name: format!("{}.{}", sub_class, name),
+            sub_class: sub_class.to_string(),
+            name: name.to_string(),
+            r#type: r#type.to_string(),
+            value: Vec::new(),
+            dirty_bit,
+        }
+    }
+
+    pub fn with_value(mut self, value: Vec<u8>) -> Self {
+        self.value = value;
+        self
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Copy, Clone, Default)]
 pub struct NetworkBehaviourSetting {
     #[serde(rename = "syncDirection")]
     /// need fix
     pub sync_direction: u8,
+    // 0: Observers, 1: Owner, 旧的导出数据没有这个字段
+    #[serde(rename = "syncMode", default)]
+    pub sync_mode: u8,
 }
 
 #[derive(Serialize, Deserialize, Debug, Copy, Clone, Default)]
@@ -205,10 +549,27 @@ pub struct NetworkTransformBaseSetting {
     #[serde(rename = "syncScale")]
     pub sync_scale: bool,
 
+    // 单轴同步, 旧的配置没有这些字段时默认同步全部轴
+    #[serde(rename = "syncPositionX", default = "NetworkTransformBaseSetting::default_sync_axis")]
+    pub sync_position_x: bool,
+    #[serde(rename = "syncPositionY", default = "NetworkTransformBaseSetting::default_sync_axis")]
+    pub sync_position_y: bool,
+    #[serde(rename = "syncPositionZ", default = "NetworkTransformBaseSetting::default_sync_axis")]
+    pub sync_position_z: bool,
+    #[serde(rename = "syncScaleX", default = "NetworkTransformBaseSetting::default_sync_axis")]
+    pub sync_scale_x: bool,
+    #[serde(rename = "syncScaleY", default = "NetworkTransformBaseSetting::default_sync_axis")]
+    pub sync_scale_y: bool,
+    #[serde(rename = "syncScaleZ", default = "NetworkTransformBaseSetting::default_sync_axis")]
+    pub sync_scale_z: bool,
+
     #[serde(rename = "onlySyncOnChange")]
     pub only_sync_on_change: bool,
     #[serde(rename = "compressRotation")]
     pub compress_rotation: bool,
+    // NetworkTransformUnreliable 的旋转同步方式, 见 RotationSyncMode, 0 表示按 compressRotation 选择
+    #[serde(rename = "rotationSyncMode", default)]
+    pub rotation_sync_mode: u8,
 
     #[serde(rename = "interpolatePosition")]
     pub interpolate_position: bool,
@@ -228,6 +589,12 @@ pub struct NetworkTransformBaseSetting {
     pub timeline_offset: bool,
 }
 
+impl NetworkTransformBaseSetting {
+    fn default_sync_axis() -> bool {
+        true
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Copy, Clone)]
 pub struct NetworkTransformReliableSetting {
     #[serde(rename = "onlySyncOnChangeCorrectionMultiplier")]
@@ -242,6 +609,18 @@ pub struct NetworkTransformReliableSetting {
     pub scale_precision: f32,
 }
 
+// 与 Unity 中 NetworkTransformReliable 的默认值相同
+impl Default for NetworkTransformReliableSetting {
+    fn default() -> Self {
+        Self {
+            only_sync_on_change_correction_multiplier: 2.0,
+            rotation_sensitivity: 0.01,
+            position_precision: 0.01,
+            scale_precision: 0.01,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Copy, Clone, Default)]
 pub struct NetworkTransformUnreliableSetting {
     #[serde(rename = "bufferResetMultiplier")]
@@ -254,6 +633,23 @@ pub struct NetworkTransformUnreliableSetting {
     pub scale_sensitivity: f32,
 }
 
+#[derive(Serialize, Deserialize, Debug, Copy, Clone)]
+pub struct NetworkBoneSyncSetting {
+    #[serde(rename = "boneCount")]
+    pub bone_count: u16,
+    #[serde(rename = "rotationPrecision")]
+    pub rotation_precision: f32,
+}
+
+impl Default for NetworkBoneSyncSetting {
+    fn default() -> Self {
+        Self {
+            bone_count: 0,
+            rotation_precision: 0.001,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct NetworkAnimatorSetting {
     #[serde(rename = "clientAuthority")]
@@ -266,6 +662,17 @@ pub struct NetworkAnimatorSetting {
     pub previous_speed: f32,
 }
 
+impl Default for NetworkAnimatorSetting {
+    fn default() -> Self {
+        Self {
+            client_authority: false,
+            animator: Animator::default(),
+            animator_speed: 1.0,
+            previous_speed: 1.0,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct NetworkBehaviourComponent {
     #[serde(rename = "componentIndex")]
@@ -282,6 +689,61 @@ pub struct NetworkBehaviourComponent {
     pub network_transform_unreliable_setting: NetworkTransformUnreliableSetting,
     #[serde(rename = "networkAnimatorSetting")]
     pub network_animator_setting: NetworkAnimatorSetting,
+    // 旧的配置没有这个字段
+    #[serde(rename = "networkBoneSyncSetting", default)]
+    pub network_bone_sync_setting: NetworkBoneSyncSetting,
+}
+
+impl NetworkBehaviourComponent {
+    // index 由 NetworkIdentityData::with_component 按添加顺序分配
+    pub fn new(sub_class: &str) -> Self {
+        Self {
+            index: 0,
+            sub_class: sub_class.to_string(),
+            network_behaviour_setting: NetworkBehaviourSetting::default(),
+            network_transform_base_setting: NetworkTransformBaseSetting::default(),
+            network_transform_reliable_setting: NetworkTransformReliableSetting::default(),
+            network_transform_unreliable_setting: NetworkTransformUnreliableSetting::default(),
+            network_animator_setting: NetworkAnimatorSetting::default(),
+            network_bone_sync_setting: NetworkBoneSyncSetting::default(),
+        }
+    }
+
+    pub fn with_behaviour_setting(mut self, setting: NetworkBehaviourSetting) -> Self {
+        self.network_behaviour_setting = setting;
+        self
+    }
+
+    pub fn with_transform_base_setting(mut self, setting: NetworkTransformBaseSetting) -> Self {
+        self.network_transform_base_setting = setting;
+        self
+    }
+
+    pub fn with_transform_reliable_setting(
+        mut self,
+        setting: NetworkTransformReliableSetting,
+    ) -> Self {
+        self.network_transform_reliable_setting = setting;
+        self
+    }
+
+    pub fn with_transform_unreliable_setting(
+        mut self,
+        setting: NetworkTransformUnreliableSetting,
+    ) -> Self {
+        self.network_transform_unreliable_setting = setting;
+        self
+    }
+
+    pub fn with_animator_setting(mut self, setting: NetworkAnimatorSetting) -> Self {
+        self.network_animator_setting = setting;
+        self
+    }
+
+    pub fn with_bone_sync_setting(mut self, setting: NetworkBoneSyncSetting) -> Self {
+        self.network_bone_sync_setting = setting;
+        self
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -308,6 +770,24 @@ pub struct SnapshotInterpolationSetting {
     pub delivery_time_ema_duration: i32,
 }
 
+// 与 Unity 中 SnapshotInterpolationSettings 的默认值相同
+impl Default for SnapshotInterpolationSetting {
+    fn default() -> Self {
+        Self {
+            buffer_time_multiplier: 2.0,
+            buffer_limit: 32,
+            catchup_negative_threshold: -1.0,
+            catchup_positive_threshold: 1.0,
+            catchup_speed: 0.02,
+            slowdown_speed: 0.04,
+            drift_ema_duration: 1,
+            dynamic_adjustment: true,
+            dynamic_adjustment_tolerance: 1.0,
+            delivery_time_ema_duration: 2,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct NetworkManagerSetting {
     #[serde(rename = "dontDestroyOnLoad")]
@@ -356,6 +836,36 @@ pub struct NetworkManagerSetting {
     pub time_interpolation_gui: bool,
 }
 
+// 与 Unity 中 NetworkManager 的默认值相同
+impl Default for NetworkManagerSetting {
+    fn default() -> Self {
+        Self {
+            dont_destroy_on_load: true,
+            run_in_background: true,
+            headless_start_mode: "DoNothing".to_string(),
+            editor_auto_start: false,
+            send_rate: 60,
+            offline_scene: "".to_string(),
+            online_scene: "".to_string(),
+            transport: "".to_string(),
+            network_address: "localhost".to_string(),
+            max_connections: 100,
+            disconnect_inactive_connections: false,
+            disconnect_inactive_timeout: 60.0,
+            authenticator: "".to_string(),
+            player_prefab: "".to_string(),
+            auto_create_player: true,
+            player_spawn_method: "Random".to_string(),
+            spawn_prefabs: Vec::new(),
+            exceptions_disconnect: true,
+            snapshot_interpolation_setting: SnapshotInterpolationSetting::default(),
+            evaluation_method: "Simple".to_string(),
+            evaluation_interval: 3.0,
+            time_interpolation_gui: false,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct NetworkRoomManagerSetting {
     #[serde(rename = "showRoomGUI")]
@@ -383,10 +893,43 @@ pub struct NetworkIdentityData {
     pub network_behaviour_components: Vec<KeyValue<u8, NetworkBehaviourComponent>>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+impl NetworkIdentityData {
+    pub fn new(asset_id: u32) -> Self {
+        Self {
+            asset_id,
+            scene_id: "0".to_string(),
+            network_behaviour_components: Vec::new(),
+        }
+    }
+
+    pub fn with_scene_id(mut self, scene_id: u64) -> Self {
+        self.scene_id = scene_id.to_string();
+        self
+    }
+
+    // 组件的 index 为添加顺序
+    pub fn with_component(mut self, mut component: NetworkBehaviourComponent) -> Self {
+        let index = self.network_behaviour_components.len() as u8;
+        component.index = index;
+        self.network_behaviour_components.push(KeyValue {
+            key: index,
+            value: component,
+        });
+        self
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct BackendData {
+    // 没有这个字段的旧数据为版本 1, 导入时迁移
+    #[serde(rename = "schemaVersion", default)]
+    pub schema_version: u32,
     #[serde(rename = "kcp2k_config", default)]
     pub kcp2k_config: Kcp2kTransportConfig,
+    #[serde(rename = "master_server_config", default)]
+    pub master_server_config: MasterServerConfig,
+    #[serde(rename = "relay_config", default)]
+    pub relay_config: RelayTransportConfig,
     #[serde(rename = "methods")]
     pub methods: Vec<MethodData>,
     #[serde(rename = "networkIdentities")]
@@ -402,6 +945,24 @@ pub struct BackendData {
     #[serde(rename = "assets")]
     pub assets: Vec<KeyValue<u32, String>>,
 }
+impl Default for BackendData {
+    fn default() -> Self {
+        Self {
+            schema_version: BackendDataSchema::CURRENT_VERSION,
+            kcp2k_config: Kcp2kTransportConfig::default(),
+            master_server_config: MasterServerConfig::default(),
+            relay_config: RelayTransportConfig::default(),
+            methods: Vec::new(),
+            network_identities: Vec::new(),
+            network_manager_settings: Vec::new(),
+            network_room_manager_settings: Vec::new(),
+            scene_ids: Vec::new(),
+            sync_vars: Vec::new(),
+            assets: Vec::new(),
+        }
+    }
+}
+
 #[allow(dead_code)]
 impl BackendData {
     pub fn get_kcp2k_config(&self) -> &Kcp2kTransportConfig {
@@ -517,7 +1078,7 @@ impl BackendData {
                         return Some(scene_id);
                     }
                     Err(err) => {
-                        log_error!(format!("Failed to parse scene_id: {:?}", err));
+                        log_error!("Failed to parse scene_id: {:?}", err);
                     }
                 }
             }
@@ -532,6 +1093,14 @@ impl BackendData {
         }
         None
     }
+    pub fn get_asset_name_by_asset_id(&self, asset_id: u32) -> Option<String> {
+        for asset in self.assets.iter() {
+            if asset.key == asset_id {
+                return Some(asset.value.clone());
+            }
+        }
+        None
+    }
 
     pub fn get_sync_var_data_s_by_sub_class(&self, sub_class: &str) -> Vec<SyncVarData> {
         let mut sync_var_data_s = Vec::new();
@@ -546,6 +1115,19 @@ impl BackendData {
         sync_var_data_s
     }
 
+    // RPC 参数类型是否为 NetworkBehaviour 或其子类
+    pub fn is_network_behaviour_type(&self, r#type: &str) -> bool {
+        if r#type == "Mirror.NetworkBehaviour" {
+            return true;
+        }
+        self.network_identities.iter().any(|network_identity_data| {
+            network_identity_data
+                .network_behaviour_components
+                .iter()
+                .any(|component| component.value.sub_class == r#type)
+        })
+    }
+
     pub fn find_scene_network_identity_all(&self) -> VecDeque<NetworkIdentity> {
         let mut network_identities = VecDeque::new();
         for scene_ids in self.scene_ids.iter() {
@@ -561,10 +1143,210 @@ impl BackendData {
     }
 }
 
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum BackendDataError {
+    DuplicateAssetId(u32),
+    DuplicateSceneId(u64),
+    // 两个方法的 hash 相同, 客户端无法区分
+    MethodHashCollision(String, String),
+    // rpc_list 中的方法没有定义
+    UnknownRpc { method: String, rpc: String },
+    // 参数多于 var_list, NetworkCommonBehaviour 无法写入
+    MissingSyncVar { method: String, parameter: String },
+    // var_list 中的同步变量没有定义
+    UnknownSyncVar { method: String, sync_var: String },
+}
+
+impl Display for BackendDataError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BackendDataError::DuplicateAssetId(asset_id) => {
+                write!(f, "duplicate assetId {}", asset_id)
+            }
+            BackendDataError::DuplicateSceneId(scene_id) => {
+                write!(f, "duplicate sceneId {}", scene_id)
+            }
+            BackendDataError::MethodHashCollision(a, b) => {
+                write!(f, "method hash collision: {} and {}", a, b)
+            }
+            BackendDataError::UnknownRpc { method, rpc } => {
+                write!(f, "{} sends unknown rpc {}", method, rpc)
+            }
+            BackendDataError::MissingSyncVar { method, parameter } => {
+                write!(f, "{} parameter {} has no sync var", method, parameter)
+            }
+            BackendDataError::UnknownSyncVar { method, sync_var } => {
+                write!(f, "{} updates unknown sync var {}", method, sync_var)
+            }
+        }
+    }
+}
+
+// 在代码中定义 BackendData, 用于没有 Unity 导出数据的纯 Rust 项目
+// 设置使用带类型的结构体, build 时检查 asset / scene id 重复以及方法引用的 Rpc 和同步变量
+pub struct BackendDataBuilder {
+    backend_data: BackendData,
+}
+
+impl Default for BackendDataBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BackendDataBuilder {
+    pub fn new() -> Self {
+        Self {
+            backend_data: BackendData::default(),
+        }
+    }
+
+    // 在导入的数据上继续添加
+    pub fn from_backend_data(backend_data: BackendData) -> Self {
+        Self { backend_data }
+    }
+
+    pub fn kcp2k_config(mut self, config: Kcp2kTransportConfig) -> Self {
+        self.backend_data.kcp2k_config = config;
+        self
+    }
+
+    pub fn master_server_config(mut self, config: MasterServerConfig) -> Self {
+        self.backend_data.master_server_config = config;
+        self
+    }
+
+    pub fn relay_config(mut self, config: RelayTransportConfig) -> Self {
+        self.backend_data.relay_config = config;
+        self
+    }
+
+    // NetworkManager 使用第一个
+    pub fn network_manager(mut self, setting: NetworkManagerSetting) -> Self {
+        self.backend_data.network_manager_settings.push(setting);
+        self
+    }
+
+    pub fn network_room_manager(mut self, setting: NetworkRoomManagerSetting) -> Self {
+        self.backend_data
+            .network_room_manager_settings
+            .push(setting);
+        self
+    }
+
+    pub fn scene(mut self, scene_name: &str, scene_id: u64) -> Self {
+        self.backend_data.scene_ids.push(KeyValue {
+            key: scene_name.to_string(),
+            value: scene_id.to_string(),
+        });
+        self
+    }
+
+    // 预制体, asset_name 用于 spawn_prefabs 和 player_prefab
+    pub fn asset(mut self, asset_id: u32, asset_name: &str, identity: NetworkIdentityData) -> Self {
+        self.backend_data.assets.push(KeyValue {
+            key: asset_id,
+            value: asset_name.to_string(),
+        });
+        self.identity(NetworkIdentityData {
+            asset_id,
+            ..identity
+        })
+    }
+
+    // 场景对象使用 NetworkIdentityData::with_scene_id
+    pub fn identity(mut self, identity: NetworkIdentityData) -> Self {
+        self.backend_data.network_identities.push(identity);
+        self
+    }
+
+    pub fn method(mut self, method: MethodData) -> Self {
+        self.backend_data.methods.push(method);
+        self
+    }
+
+    pub fn sync_var(mut self, sync_var: SyncVarData) -> Self {
+        self.backend_data.sync_vars.push(sync_var);
+        self
+    }
+
+    pub fn build(self) -> Result<BackendData, BackendDataError> {
+        let backend_data = self.backend_data;
+
+        let mut asset_ids = HashSet::new();
+        let mut scene_ids = HashSet::new();
+        for identity in backend_data.network_identities.iter() {
+            if identity.asset_id != 0 && !asset_ids.insert(identity.asset_id) {
+                return Err(BackendDataError::DuplicateAssetId(identity.asset_id));
+            }
+            let scene_id = identity.scene_id.parse::<u64>().unwrap_or(0);
+            if scene_id != 0 && !scene_ids.insert(scene_id) {
+                return Err(BackendDataError::DuplicateSceneId(scene_id));
+            }
+        }
+
+        let mut hashes: HashMap<u16, &str> = HashMap::new();
+        for method in backend_data.methods.iter() {
+            if let Some(other) = hashes.insert(method.hash_code, &method.name) {
+                if other != method.name {
+                    return Err(BackendDataError::MethodHashCollision(
+                        other.to_string(),
+                        method.name.clone(),
+                    ));
+                }
+            }
+        }
+
+        let sync_vars: HashSet<&str> = backend_data
+            .sync_vars
+            .iter()
+            .map(|sync_var| sync_var.full_name.as_str())
+            .collect();
+        for method in backend_data.methods.iter() {
+            if let Some(rpc) = method
+                .rpc_list
+                .iter()
+                .find(|rpc| backend_data.get_method_data_by_method_name(rpc).is_none())
+            {
+                return Err(BackendDataError::UnknownRpc {
+                    method: method.name.clone(),
+                    rpc: rpc.clone(),
+                });
+            }
+            if method.var_list.is_empty() {
+                continue;
+            }
+            if let Some(parameter) = method.parameters.get(method.var_list.len()) {
+                return Err(BackendDataError::MissingSyncVar {
+                    method: method.name.clone(),
+                    parameter: parameter.key.clone(),
+                });
+            }
+            if let Some(sync_var) = method
+                .var_list
+                .iter()
+                .find(|sync_var| !sync_vars.contains(sync_var.value.as_str()))
+            {
+                return Err(BackendDataError::UnknownSyncVar {
+                    method: method.name.clone(),
+                    sync_var: sync_var.value.clone(),
+                });
+            }
+        }
+        Ok(backend_data)
+    }
+
+    // build 并在当前上下文中代替 tobackend.json
+    pub fn register(self) -> Result<(), BackendDataError> {
+        BackendDataStatic::set_backend_data(self.build()?);
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::mirror::core::tools::stable_hash::StableHash;
+    use crate::mirror::transports::memory::memory_transport::test_util::with_isolated_context;
 
     #[test]
     fn test_import_data() {
@@ -588,4 +1370,163 @@ mod tests {
         );
         println!("{:?}", method_data);
     }
+
+    #[test]
+    fn test_backend_data_builder() {
+        with_isolated_context(|| {
+            let sub_class = "QuickStart.PlayerScript";
+            let cmd = "System.Void QuickStart.PlayerScript::CmdSetName(System.String)";
+            let rpc = "System.Void QuickStart.PlayerScript::RpcNameChanged(System.String)";
+            let builder = || {
+                BackendDataBuilder::new()
+                    .network_manager(NetworkManagerSetting {
+                        player_prefab: "Player".to_string(),
+                        ..NetworkManagerSetting::default()
+                    })
+                    .asset(
+                        7,
+                        "Player",
+                        NetworkIdentityData::new(0)
+                            .with_component(NetworkBehaviourComponent::new(
+                                "Mirror.NetworkTransformUnreliable",
+                            ))
+                            .with_component(NetworkBehaviourComponent::new(sub_class)),
+                    )
+                    .scene("Door", 42)
+                    .identity(
+                        NetworkIdentityData::new(0)
+                            .with_scene_id(42)
+                            .with_component(NetworkBehaviourComponent::new(sub_class)),
+                    )
+                    .sync_var(SyncVarData::new(
+                        sub_class,
+                        "playerName",
+                        "System.String",
+                        1,
+                    ))
+                    .method(MethodData::new(MethodType::ClientRpc, sub_class, rpc))
+            };
+
+            let backend_data = builder()
+                .method(
+                    MethodData::new(MethodType::Command, sub_class, cmd)
+                        .with_parameter("name", "System.String")
+                        .with_sync_var("QuickStart.PlayerScript.playerName")
+                        .with_rpc(rpc),
+                )
+                .build()
+                .unwrap();
+            assert_eq!(backend_data.get_asset_id_by_asset_name("Player"), Some(7));
+            assert_eq!(backend_data.get_scene_id_by_scene_name("Door"), Some(42));
+            let components =
+                backend_data.get_network_identity_data_network_behaviour_components_by_asset_id(7);
+            assert_eq!(components[1].index, 1);
+            assert_eq!(components[1].sub_class, sub_class);
+            assert!(backend_data
+                .get_network_identity_data_by_scene_id(42)
+                .is_some());
+            assert_eq!(
+                backend_data.get_rpc_hash_code_s(cmd.get_fn_stable_hash_code()),
+                vec![rpc.get_fn_stable_hash_code()]
+            );
+
+            // 参数没有对应的同步变量
+            assert_eq!(
+                builder()
+                    .method(
+                        MethodData::new(MethodType::Command, sub_class, cmd)
+                            .with_parameter("name", "System.String")
+                            .with_parameter("color", "UnityEngine.Color")
+                            .with_sync_var("QuickStart.PlayerScript.playerName"),
+                    )
+                    .build()
+                    .unwrap_err(),
+                BackendDataError::MissingSyncVar {
+                    method: cmd.to_string(),
+                    parameter: "color".to_string(),
+                }
+            );
+            assert!(matches!(
+                builder()
+                    .method(MethodData::new(MethodType::Command, sub_class, cmd).with_rpc("Rpc"))
+                    .build(),
+                Err(BackendDataError::UnknownRpc { .. })
+            ));
+            assert_eq!(
+                builder()
+                    .asset(7, "Enemy", NetworkIdentityData::new(0))
+                    .build()
+                    .unwrap_err(),
+                BackendDataError::DuplicateAssetId(7)
+            );
+
+            // 注册后代替 tobackend.json, 只影响当前上下文
+            builder().register().unwrap();
+            let active = BackendDataStatic::get_backend_data();
+            assert_eq!(active.network_manager_settings[0].player_prefab, "Player");
+            assert_eq!(active.network_manager_settings[0].send_rate, 60);
+            BackendDataStatic::clear_backend_data();
+        });
+    }
+
+    #[test]
+    fn test_backend_data_schema_migration() {
+        let backend_data = BackendDataBuilder::new()
+            .asset(
+                7,
+                "Player",
+                NetworkIdentityData::new(0)
+                    .with_component(NetworkBehaviourComponent::new("QuickStart.PlayerScript")),
+            )
+            .build()
+            .unwrap();
+
+        // 版本 1 的导出数据: 没有 schemaVersion、syncMode、同步轴和 networkBoneSyncSetting
+        let mut v1 = serde_json::to_value(&backend_data).unwrap();
+        v1.as_object_mut().unwrap().remove("schemaVersion");
+        let component = &mut v1["networkIdentities"][0]["networkBehaviourComponents"][0]["value"];
+        component["networkBehaviourSetting"]
+            .as_object_mut()
+            .unwrap()
+            .remove("syncMode");
+        component["networkTransformBaseSetting"]
+            .as_object_mut()
+            .unwrap()
+            .remove("syncPositionX");
+        component.as_object_mut().unwrap().remove("networkBoneSyncSetting");
+        assert_eq!(BackendDataSchema::version(&v1), Ok(1));
+
+        let migrated = BackendDataSchema::load(v1.clone()).unwrap();
+        assert_eq!(migrated.schema_version, BackendDataSchema::CURRENT_VERSION);
+        let components =
+            migrated.get_network_identity_data_network_behaviour_components_by_asset_id(7);
+        assert_eq!(components[0].network_behaviour_setting.sync_mode, 0);
+        assert!(components[0].network_transform_base_setting.sync_position_x);
+        assert_eq!(components[0].network_bone_sync_setting.bone_count, 0);
+
+        // 字段不匹配时列出所有缺少和未知的字段
+        let mut changed = v1.clone();
+        changed["networkIdentities"][0]["networkBehaviourComponents"][0]["value"]
+            .as_object_mut()
+            .unwrap()
+            .remove("componentType");
+        changed["networkIdentities"][0]["prefabName"] = json!("Player");
+        assert_eq!(
+            BackendDataSchema::load(changed).unwrap_err(),
+            BackendDataImportError::Schema {
+                missing: vec![
+                    "networkIdentities[0].networkBehaviourComponents[0].value.componentType"
+                        .to_string()
+                ],
+                unknown: vec!["networkIdentities[0].prefabName".to_string()],
+            }
+        );
+
+        let mut newer = v1;
+        newer["schemaVersion"] = json!(BackendDataSchema::CURRENT_VERSION + 1);
+        assert_eq!(
+            BackendDataSchema::load(newer).unwrap_err(),
+            BackendDataImportError::UnsupportedVersion(BackendDataSchema::CURRENT_VERSION + 1)
+        );
+    }
 }
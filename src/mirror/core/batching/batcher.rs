@@ -86,6 +86,15 @@ impl Batcher {
         NetworkWriterPool::return_(batcher);
     }
 
+    // 等待发送的批次占用的内存
+    pub fn memory_usage(&self) -> usize {
+        self.batches
+            .iter()
+            .chain(self.batcher.iter())
+            .map(|writer| size_of::<NetworkWriter>() + writer.capacity())
+            .sum()
+    }
+
     pub fn clear(&mut self) {
         if let Some(batcher) = self.batcher.take() {
             NetworkWriterPool::return_(batcher);
@@ -95,3 +104,28 @@ impl Batcher {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::transports::memory::memory_transport::test_util::captured_golden;
+
+    // timestamp (double) + VarUInt 消息长度 + ReadyMessage
+    // C# Mirror 抓取的数据为 tests/golden/Batch.bin
+    const BATCH: &[u8] = &[
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xF0, 0x3F, 0x02, 0xBC, 0xAA,
+    ];
+
+    #[test]
+    fn test_golden_batch() {
+        let mut batcher = Batcher::new(1200);
+        batcher.add_message(&[0xBC, 0xAA], 1.0);
+        let mut writer = NetworkWriter::new();
+        assert!(batcher.get_batcher_writer(&mut writer));
+        assert_eq!(writer.to_array_segment(), BATCH);
+        if let Some(captured) = captured_golden("Batch") {
+            assert_eq!(BATCH, captured, "Batch (C#)");
+        }
+        assert!(!batcher.get_batcher_writer(&mut writer));
+    }
+}
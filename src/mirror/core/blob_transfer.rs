@@ -0,0 +1,447 @@
+use crate::log_error;
+use crate::mirror::core::messages::{BlobAckMessage, BlobChunkMessage, NetworkMessageTrait};
+use crate::mirror::core::network_connection::NetworkConnectionTrait;
+use crate::mirror::core::network_context::ContextLocal;
+use crate::mirror::core::network_events::Subscribers;
+use crate::mirror::core::network_reader::NetworkReader;
+use crate::mirror::core::network_server::NetworkServerStatic;
+use crate::mirror::core::transport::TransportChannel;
+use atomic::Atomic;
+use crossbeam_channel::Receiver;
+use dashmap::try_result::TryResult;
+use lazy_static::lazy_static;
+use std::collections::VecDeque;
+use std::fmt::{Display, Formatter};
+use std::sync::atomic::Ordering;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum BlobTransferError {
+    ConnectionNotFound,
+    // 同一个连接上已经有相同 id 的传输
+    DuplicateId,
+    TooLarge,
+    Locked,
+}
+
+impl Display for BlobTransferError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BlobTransferError::ConnectionNotFound => write!(f, "connection not found"),
+            BlobTransferError::DuplicateId => write!(f, "transfer id already in use"),
+            BlobTransferError::TooLarge => write!(f, "blob exceeds max blob size"),
+            BlobTransferError::Locked => write!(f, "connection is locked"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlobProgressEvent {
+    pub connection_id: u64,
+    pub transfer_id: u32,
+    // 客户端已经确认的字节数
+    pub acked: u32,
+    pub total: u32,
+}
+
+impl BlobProgressEvent {
+    pub fn completed(&self) -> bool {
+        self.acked == self.total
+    }
+}
+
+struct OutgoingBlob {
+    id: u32,
+    data: Vec<u8>,
+    sent: usize,
+    acked: usize,
+    // 空数据或从末尾续传时也要发送一个分块, 客户端才知道总大小
+    started: bool,
+}
+
+impl OutgoingBlob {
+    fn new(id: u32, data: Vec<u8>, offset: usize) -> Self {
+        let offset = offset.min(data.len());
+        Self {
+            id,
+            data,
+            sent: offset,
+            acked: offset,
+            started: false,
+        }
+    }
+}
+
+// 每个连接的传输队列, 存放在连接的 ext 中, 断开连接时一起释放
+#[derive(Default)]
+struct BlobTransfers {
+    active: Vec<OutgoingBlob>,
+    queued: VecDeque<OutgoingBlob>,
+}
+
+impl BlobTransfers {
+    fn contains(&self, id: u32) -> bool {
+        self.active.iter().any(|blob| blob.id == id) || self.queued.iter().any(|blob| blob.id == id)
+    }
+
+    fn promote(&mut self, max_concurrent: usize) {
+        while self.active.len() < max_concurrent.max(1) {
+            match self.queued.pop_front() {
+                Some(blob) => self.active.push(blob),
+                None => break,
+            }
+        }
+    }
+}
+
+// BlobTransfer 静态变量
+lazy_static! {
+    static ref CHUNK_SIZE: ContextLocal<Atomic<usize>> = ContextLocal::new(|| Atomic::new(1024));
+    // 每个传输未确认的最大字节数
+    static ref WINDOW_SIZE: ContextLocal<Atomic<usize>> =
+        ContextLocal::new(|| Atomic::new(16 * 1024));
+    // 每个连接同时进行的传输数量, 超出的排队
+    static ref MAX_CONCURRENT_TRANSFERS: ContextLocal<Atomic<usize>> =
+        ContextLocal::new(|| Atomic::new(4));
+    static ref MAX_BLOB_SIZE: ContextLocal<Atomic<usize>> =
+        ContextLocal::new(|| Atomic::new(64 * 1024 * 1024));
+    static ref PROGRESS: ContextLocal<Subscribers<BlobProgressEvent>> =
+        ContextLocal::new(Subscribers::new);
+}
+
+// 大数据传输: 按 CHUNK_SIZE 分块通过 Reliable 通道发送, 客户端用 BlobAckMessage 确认已收到的字节数,
+// 未确认的数据不超过 WINDOW_SIZE, 避免一次性塞满发送缓冲区影响正常的状态同步
+// 重连后客户端上报已收到的字节数, 服务器用 resume 从该位置继续发送
+pub struct BlobTransfer;
+
+impl BlobTransfer {
+    pub fn chunk_size() -> usize {
+        CHUNK_SIZE.load(Ordering::Relaxed)
+    }
+
+    // 分块加上消息头必须小于 transport 的 Reliable 最大包大小
+    pub fn set_chunk_size(value: usize) {
+        CHUNK_SIZE.store(value.max(1), Ordering::Relaxed);
+    }
+
+    pub fn window_size() -> usize {
+        WINDOW_SIZE.load(Ordering::Relaxed)
+    }
+
+    pub fn set_window_size(value: usize) {
+        WINDOW_SIZE.store(value, Ordering::Relaxed);
+    }
+
+    pub fn max_concurrent_transfers() -> usize {
+        MAX_CONCURRENT_TRANSFERS.load(Ordering::Relaxed)
+    }
+
+    pub fn set_max_concurrent_transfers(value: usize) {
+        MAX_CONCURRENT_TRANSFERS.store(value, Ordering::Relaxed);
+    }
+
+    pub fn max_blob_size() -> usize {
+        MAX_BLOB_SIZE.load(Ordering::Relaxed)
+    }
+
+    pub fn set_max_blob_size(value: usize) {
+        MAX_BLOB_SIZE.store(value, Ordering::Relaxed);
+    }
+
+    // 每次客户端确认时发布, acked == total 表示传输完成
+    pub fn on_progress() -> Receiver<BlobProgressEvent> {
+        PROGRESS.subscribe()
+    }
+
+    pub fn send(conn_id: u64, id: u32, bytes: Vec<u8>) -> Result<(), BlobTransferError> {
+        Self::resume(conn_id, id, bytes, 0)
+    }
+
+    // 客户端已经有前 offset 个字节 (上一个连接中断前收到的)
+    pub fn resume(
+        conn_id: u64,
+        id: u32,
+        bytes: Vec<u8>,
+        offset: usize,
+    ) -> Result<(), BlobTransferError> {
+        if bytes.len() > Self::max_blob_size() || bytes.len() > u32::MAX as usize {
+            return Err(BlobTransferError::TooLarge);
+        }
+        match NetworkServerStatic::network_connections().try_get_mut(&conn_id) {
+            TryResult::Present(mut connection) => {
+                if !connection.has_ext::<BlobTransfers>() {
+                    connection.insert_ext(BlobTransfers::default());
+                }
+                match connection.get_ext_mut::<BlobTransfers>() {
+                    Some(transfers) => {
+                        if transfers.contains(id) {
+                            return Err(BlobTransferError::DuplicateId);
+                        }
+                        transfers
+                            .queued
+                            .push_back(OutgoingBlob::new(id, bytes, offset));
+                        transfers.promote(Self::max_concurrent_transfers());
+                        Ok(())
+                    }
+                    None => Err(BlobTransferError::ConnectionNotFound),
+                }
+            }
+            TryResult::Absent => Err(BlobTransferError::ConnectionNotFound),
+            TryResult::Locked => Err(BlobTransferError::Locked),
+        }
+    }
+
+    // 取消后客户端不会再收到分块, 需要由上层通知客户端
+    pub fn cancel(conn_id: u64, id: u32) -> bool {
+        match NetworkServerStatic::network_connections().try_get_mut(&conn_id) {
+            TryResult::Present(mut connection) => match connection.get_ext_mut::<BlobTransfers>() {
+                Some(transfers) => {
+                    let count = transfers.active.len() + transfers.queued.len();
+                    transfers.active.retain(|blob| blob.id != id);
+                    transfers.queued.retain(|blob| blob.id != id);
+                    transfers.promote(Self::max_concurrent_transfers());
+                    count != transfers.active.len() + transfers.queued.len()
+                }
+                None => false,
+            },
+            _ => false,
+        }
+    }
+
+    // (已确认的字节数, 总字节数), 传输完成或不存在时返回 None
+    pub fn progress(conn_id: u64, id: u32) -> Option<(u32, u32)> {
+        NetworkServerStatic::network_connections()
+            .get(&conn_id)
+            .and_then(|connection| {
+                connection.get_ext::<BlobTransfers>().and_then(|transfers| {
+                    transfers
+                        .active
+                        .iter()
+                        .chain(transfers.queued.iter())
+                        .find(|blob| blob.id == id)
+                        .map(|blob| (blob.acked as u32, blob.data.len() as u32))
+                })
+            })
+    }
+
+    // 正在发送的传输数量 (不包括排队的)
+    pub fn active_count(conn_id: u64) -> usize {
+        NetworkServerStatic::network_connections()
+            .get(&conn_id)
+            .and_then(|connection| {
+                connection
+                    .get_ext::<BlobTransfers>()
+                    .map(|transfers| transfers.active.len())
+            })
+            .unwrap_or(0)
+    }
+
+    pub fn queued_count(conn_id: u64) -> usize {
+        NetworkServerStatic::network_connections()
+            .get(&conn_id)
+            .and_then(|connection| {
+                connection
+                    .get_ext::<BlobTransfers>()
+                    .map(|transfers| transfers.queued.len())
+            })
+            .unwrap_or(0)
+    }
+
+    // 在 NetworkServer::register_message_handlers 中注册
+    pub(crate) fn on_blob_ack_message(
+        connection_id: u64,
+        reader: &mut NetworkReader,
+        _channel: TransportChannel,
+    ) {
+        let message = BlobAckMessage::deserialize(reader);
+        let event = match NetworkServerStatic::network_connections().try_get_mut(&connection_id) {
+            TryResult::Present(mut connection) => {
+                let transfers = match connection.get_ext_mut::<BlobTransfers>() {
+                    Some(transfers) => transfers,
+                    None => return,
+                };
+                let index = match transfers
+                    .active
+                    .iter()
+                    .position(|blob| blob.id == message.transfer_id)
+                {
+                    Some(index) => index,
+                    None => return,
+                };
+                let blob = &mut transfers.active[index];
+                let received = message.received as usize;
+                // 重复或乱序的确认, 或者确认了还没发送的数据
+                if received <= blob.acked || received > blob.sent {
+                    return;
+                }
+                blob.acked = received;
+                let event = BlobProgressEvent {
+                    connection_id,
+                    transfer_id: blob.id,
+                    acked: blob.acked as u32,
+                    total: blob.data.len() as u32,
+                };
+                if event.completed() {
+                    transfers.active.remove(index);
+                    transfers.promote(Self::max_concurrent_transfers());
+                }
+                event
+            }
+            TryResult::Absent => {
+                log_error!(
+                    "Server.HandleBlobAck: connectionId {} not found.",
+                    connection_id
+                );
+                return;
+            }
+            TryResult::Locked => {
+                log_error!(
+                    "Server.HandleBlobAck: connectionId {} is locked.",
+                    connection_id
+                );
+                return;
+            }
+        };
+        PROGRESS.publish(event);
+    }
+
+    // 在 NetworkServer::network_late_update 中 broadcast 之前调用
+    pub fn update() {
+        let conn_ids: Vec<u64> = NetworkServerStatic::network_connections()
+            .iter()
+            .filter(|connection| connection.has_ext::<BlobTransfers>())
+            .map(|connection| connection.connection_id())
+            .collect();
+        let chunk_size = Self::chunk_size();
+        let window_size = Self::window_size();
+
+        for conn_id in conn_ids {
+            let mut chunks = Vec::new();
+            let mut completed = Vec::new();
+            match NetworkServerStatic::network_connections().try_get_mut(&conn_id) {
+                TryResult::Present(mut connection) => {
+                    if let Some(transfers) = connection.get_ext_mut::<BlobTransfers>() {
+                        for blob in transfers.active.iter_mut() {
+                            let total = blob.data.len();
+                            while (!blob.started || blob.sent < total)
+                                && blob.sent - blob.acked < window_size.max(chunk_size)
+                            {
+                                let end = (blob.sent + chunk_size).min(total);
+                                chunks.push(BlobChunkMessage::new(
+                                    blob.id,
+                                    total as u32,
+                                    blob.sent as u32,
+                                    blob.data[blob.sent..end].to_vec(),
+                                ));
+                                blob.sent = end;
+                                blob.started = true;
+                            }
+                            // 从末尾续传的不需要等待确认
+                            if blob.acked == total && blob.started {
+                                completed.push(BlobProgressEvent {
+                                    connection_id: conn_id,
+                                    transfer_id: blob.id,
+                                    acked: total as u32,
+                                    total: total as u32,
+                                });
+                            }
+                        }
+                        if !completed.is_empty() {
+                            transfers
+                                .active
+                                .retain(|blob| blob.acked != blob.data.len());
+                            transfers.promote(Self::max_concurrent_transfers());
+                        }
+                    }
+                    for mut chunk in chunks {
+                        connection.send_network_message(&mut chunk, TransportChannel::Reliable);
+                    }
+                }
+                TryResult::Absent => continue,
+                TryResult::Locked => {
+                    log_error!("BlobTransfer.Update: connectionId {} is locked.", conn_id);
+                    continue;
+                }
+            }
+            for event in completed {
+                PROGRESS.publish(event);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::transports::memory::memory_transport::test_util::*;
+    use crate::mirror::transports::memory::memory_transport::MemoryTransport;
+
+    #[test]
+    fn test_blob_transfer() {
+        with_server(|| {
+            BlobTransfer::set_chunk_size(100);
+            BlobTransfer::set_window_size(200);
+            BlobTransfer::set_max_concurrent_transfers(1);
+            let progress = BlobTransfer::on_progress();
+            MemoryTransport::client_connect(1);
+            tick();
+            MemoryTransport::client_receive(1);
+
+            let blob: Vec<u8> = (0..250).map(|i| i as u8).collect();
+            assert!(BlobTransfer::send(1, 7, blob.clone()).is_ok());
+            assert!(BlobTransfer::send(1, 8, vec![1, 2, 3]).is_ok());
+            assert_eq!(
+                BlobTransfer::send(1, 7, Vec::new()),
+                Err(BlobTransferError::DuplicateId)
+            );
+            assert_eq!(BlobTransfer::active_count(1), 1);
+            assert_eq!(BlobTransfer::queued_count(1), 1);
+
+            // 窗口内只发送两个分块
+            tick();
+            let chunks = received::<BlobChunkMessage>(1);
+            assert_eq!(chunks.len(), 2);
+            assert_eq!(
+                chunks[1],
+                BlobChunkMessage::new(7, 250, 100, blob[100..200].to_vec())
+            );
+            tick();
+            assert!(received::<BlobChunkMessage>(1).is_empty());
+
+            let mut ack = BlobAckMessage::new(7, 200);
+            MemoryTransport::client_send_message(1, &mut ack, TransportChannel::Reliable);
+            tick();
+            assert_eq!(
+                received::<BlobChunkMessage>(1),
+                vec![BlobChunkMessage::new(7, 250, 200, blob[200..].to_vec())]
+            );
+            assert_eq!(BlobTransfer::progress(1, 7), Some((200, 250)));
+
+            // 完成后开始排队的传输
+            let mut ack = BlobAckMessage::new(7, 250);
+            MemoryTransport::client_send_message(1, &mut ack, TransportChannel::Reliable);
+            tick();
+            assert_eq!(
+                received::<BlobChunkMessage>(1),
+                vec![BlobChunkMessage::new(8, 3, 0, vec![1, 2, 3])]
+            );
+            let events: Vec<BlobProgressEvent> = progress.try_iter().collect();
+            assert_eq!(events.len(), 2);
+            assert!(!events[0].completed());
+            assert!(events[1].completed() && events[1].transfer_id == 7);
+            assert_eq!(BlobTransfer::progress(1, 7), None);
+
+            // 从中断处续传
+            assert!(BlobTransfer::cancel(1, 8));
+            assert!(BlobTransfer::resume(1, 9, blob.clone(), 230).is_ok());
+            tick();
+            assert_eq!(
+                received::<BlobChunkMessage>(1),
+                vec![BlobChunkMessage::new(9, 250, 230, blob[230..].to_vec())]
+            );
+
+            BlobTransfer::set_chunk_size(1024);
+            BlobTransfer::set_window_size(16 * 1024);
+            BlobTransfer::set_max_concurrent_transfers(4);
+        });
+    }
+}
@@ -0,0 +1,230 @@
+use crate::log_error;
+use crate::mirror::core::network_behaviour::SyncDirection;
+use crate::mirror::core::network_context::ContextLocal;
+use crate::mirror::core::network_server::NETWORK_BEHAVIOURS;
+use dashmap::try_result::TryResult;
+use dashmap::DashMap;
+use lazy_static::lazy_static;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum AuthorityMode {
+    // 使用对象的权限: 所有者可以调用需要权限的 Command, 状态写入按组件的同步方向
+    Inherit,
+    // 服务器权威: 任何客户端都不能调用需要权限的 Command, 也不能写入状态
+    Server,
+    // 所有者权威: 所有者可以调用需要权限的 Command 和写入状态
+    Owner,
+}
+
+impl AuthorityMode {
+    pub fn from(value: u8) -> Self {
+        match value {
+            1 => AuthorityMode::Server,
+            2 => AuthorityMode::Owner,
+            _ => AuthorityMode::Inherit,
+        }
+    }
+
+    pub fn to_u8(&self) -> u8 {
+        match self {
+            AuthorityMode::Inherit => 0,
+            AuthorityMode::Server => 1,
+            AuthorityMode::Owner => 2,
+        }
+    }
+}
+
+// ComponentAuthority 静态变量
+lazy_static! {
+    // (net_id, component_index) -> (AuthorityMode, 覆盖之前的同步方向)
+    static ref OVERRIDES: ContextLocal<DashMap<(u32, u8), (u8, SyncDirection)>> =
+        ContextLocal::new(DashMap::new);
+}
+
+// 按组件覆盖对象的权限, 同一个对象可以有客户端权威的组件 (例如 NetworkTransform) 和服务器权威的组件
+// NetworkServer 分发 Command 时按组件检查权限, 状态写入通过修改组件的同步方向限制
+// 客户端不会收到覆盖, 客户端组件的同步方向需要与覆盖一致, 否则整个 EntityStateMessage 都会被拒绝
+pub struct ComponentAuthority;
+
+impl ComponentAuthority {
+    // 组件不存在时返回 false, Inherit 恢复覆盖之前的同步方向
+    pub fn set(net_id: u32, component_index: u8, mode: AuthorityMode) -> bool {
+        let key = (net_id, component_index);
+        let mut component = match NETWORK_BEHAVIOURS.try_get_mut(&key) {
+            TryResult::Present(component) => component,
+            TryResult::Absent => {
+                log_error!(
+                    "ComponentAuthority.Set: netId {} component [index={}] not found",
+                    net_id,
+                    component_index
+                );
+                return false;
+            }
+            TryResult::Locked => {
+                log_error!(
+                    "ComponentAuthority.Set: netId {} component [index={}] is locked",
+                    net_id,
+                    component_index
+                );
+                return false;
+            }
+        };
+        let original = match OVERRIDES.get(&key) {
+            Some(entry) => entry.1,
+            None => *component.sync_direction(),
+        };
+        match mode {
+            AuthorityMode::Inherit => {
+                component.set_sync_direction(original);
+                OVERRIDES.remove(&key);
+            }
+            AuthorityMode::Server => {
+                component.set_sync_direction(SyncDirection::ServerToClient);
+                OVERRIDES.insert(key, (mode.to_u8(), original));
+            }
+            AuthorityMode::Owner => {
+                component.set_sync_direction(SyncDirection::ClientToServer);
+                OVERRIDES.insert(key, (mode.to_u8(), original));
+            }
+        }
+        true
+    }
+
+    pub fn clear(net_id: u32, component_index: u8) -> bool {
+        Self::set(net_id, component_index, AuthorityMode::Inherit)
+    }
+
+    pub fn mode(net_id: u32, component_index: u8) -> AuthorityMode {
+        OVERRIDES
+            .get(&(net_id, component_index))
+            .map_or(AuthorityMode::Inherit, |entry| AuthorityMode::from(entry.0))
+    }
+
+    pub fn count() -> usize {
+        OVERRIDES.len()
+    }
+
+    // connection_id 是否可以调用组件上需要权限的 Command, owner 是对象的所有者
+    pub fn has_authority(net_id: u32, component_index: u8, owner: u64, connection_id: u64) -> bool {
+        match Self::mode(net_id, component_index) {
+            AuthorityMode::Server => false,
+            AuthorityMode::Inherit | AuthorityMode::Owner => owner == connection_id,
+        }
+    }
+
+    // 在 NetworkServer::un_spawn_internal 中调用
+    pub(crate) fn on_despawn(net_id: u32) {
+        OVERRIDES.retain(|key, _| key.0 != net_id);
+    }
+
+    pub fn reset() {
+        OVERRIDES.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::core::messages::{CommandMessage, NetworkMessageTrait};
+    use crate::mirror::core::network_behaviour::GameObject;
+    use crate::mirror::core::network_connection::NetworkConnectionTrait;
+    use crate::mirror::core::network_reader::NetworkReader;
+    use crate::mirror::core::network_server::{NetworkServer, NetworkServerStatic};
+    use crate::mirror::core::network_writer::NetworkWriter;
+    use crate::mirror::core::remote_calls::RemoteProcedureCalls;
+    use crate::mirror::transports::memory::memory_transport::test_util::*;
+    use crate::mirror::transports::memory::memory_transport::MemoryTransport;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    static AUTHORITY_COMMANDS: AtomicU32 = AtomicU32::new(0);
+
+    fn on_test_authority_command(_: u64, _: u32, _: u8, _: u16, _: &mut NetworkReader) {
+        AUTHORITY_COMMANDS.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[test]
+    fn test_component_authority() {
+        with_server(|| {
+            let function_hash = RemoteProcedureCalls::register_command_delegate::<TestHit>(
+                "System.Void Test.TestHit::CmdUse()",
+                on_test_authority_command,
+                true,
+            );
+            for conn_id in [1, 2] {
+                MemoryTransport::client_connect(conn_id);
+            }
+            tick();
+            for conn_id in [1, 2] {
+                NetworkServer::set_client_ready(conn_id);
+                NetworkServerStatic::network_connections()
+                    .get_mut(&conn_id)
+                    .unwrap()
+                    .set_authenticated(true);
+            }
+
+            spawn_test_identity(710, 1, 2);
+            NetworkServerStatic::spawned_network_identities()
+                .get_mut(&710)
+                .unwrap()
+                .set_game_object(GameObject::new_with_prefab("Test".to_string()));
+
+            // 组件 0 所有者权威, 组件 1 服务器权威
+            assert!(ComponentAuthority::set(710, 0, AuthorityMode::Owner));
+            assert!(ComponentAuthority::set(710, 1, AuthorityMode::Server));
+            assert!(!ComponentAuthority::set(710, 2, AuthorityMode::Server));
+            assert_eq!(
+                *NETWORK_BEHAVIOURS
+                    .get_mut(&(710, 0))
+                    .unwrap()
+                    .sync_direction(),
+                SyncDirection::ClientToServer
+            );
+
+            let command = |conn_id: u64, index: u8| {
+                let commands = AUTHORITY_COMMANDS.load(Ordering::Relaxed);
+                let mut writer = NetworkWriter::new();
+                CommandMessage::new(710, index, function_hash, Vec::new()).serialize(&mut writer);
+                send_raw(conn_id, &writer.to_bytes());
+                tick();
+                AUTHORITY_COMMANDS.load(Ordering::Relaxed) - commands == 1
+            };
+            assert!(command(1, 0));
+            assert!(!command(2, 0));
+            assert!(!command(1, 1));
+            assert!(!command(2, 1));
+
+            // 恢复对象的权限和原来的同步方向
+            assert!(ComponentAuthority::clear(710, 1));
+            assert!(command(1, 1));
+            assert!(ComponentAuthority::clear(710, 0));
+            assert_eq!(
+                *NETWORK_BEHAVIOURS
+                    .get_mut(&(710, 0))
+                    .unwrap()
+                    .sync_direction(),
+                SyncDirection::ServerToClient
+            );
+            assert_eq!(ComponentAuthority::count(), 0);
+
+            ComponentAuthority::set(710, 1, AuthorityMode::Server);
+            {
+                let mut connection = NetworkServerStatic::network_connections()
+                    .get_mut(&1)
+                    .unwrap();
+                let mut identity = NetworkServerStatic::spawned_network_identities()
+                    .get_mut(&710)
+                    .unwrap();
+                NetworkServer::destroy(&mut connection, &mut identity);
+            }
+            tick();
+            assert_eq!(ComponentAuthority::mode(710, 1), AuthorityMode::Inherit);
+
+            RemoteProcedureCalls::remove_delegate(function_hash);
+            for index in 0..2 {
+                NETWORK_BEHAVIOURS.remove(&(710, index));
+            }
+            NetworkServerStatic::remove_spawned_network_identity(&710);
+            ComponentAuthority::reset();
+        });
+    }
+}
@@ -1,5 +1,6 @@
 #[allow(warnings)]
 #[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum ConnectionQuality {
     ESTIMATING,
     POOR,
@@ -0,0 +1,213 @@
+use crate::log_error;
+use crate::mirror::core::messages::{
+    EphemeralDespawnMessage, EphemeralSpawnMessage, EphemeralUpdateMessage, NetworkMessageTrait,
+};
+use crate::mirror::core::network_connection::NetworkConnectionTrait;
+use crate::mirror::core::network_context::ContextLocal;
+use crate::mirror::core::network_server::NetworkServerStatic;
+use crate::mirror::core::network_time::NetworkTime;
+use crate::mirror::core::transport::TransportChannel;
+use atomic::Atomic;
+use dashmap::try_result::TryResult;
+use dashmap::DashMap;
+use lazy_static::lazy_static;
+use nalgebra::Vector3;
+use std::sync::atomic::Ordering;
+
+// 短生命周期对象, 不经过 NetworkIdentity / NetworkBehaviour
+#[derive(Debug, Clone)]
+pub struct EphemeralEntity {
+    pub id: u32,
+    pub kind: u32,
+    pub position: Vector3<f32>,
+    pub velocity: Vector3<f32>,
+    pub spawn_time: f64,
+    pub ttl: f32,
+    // 生成时已经准备好的连接
+    pub observers: Vec<u64>,
+}
+
+impl EphemeralEntity {
+    pub fn expired(&self, local_time: f64) -> bool {
+        local_time >= self.spawn_time + self.ttl as f64
+    }
+}
+
+// Ephemeral 静态变量
+lazy_static! {
+    static ref ENTITIES: ContextLocal<DashMap<u32, EphemeralEntity>> =
+        ContextLocal::new(DashMap::new);
+    // 与 net_id 是两个独立的 id 空间
+    static ref NEXT_ID: ContextLocal<Atomic<u32>> = ContextLocal::new(|| Atomic::new(1));
+}
+
+pub struct EphemeralStatic;
+
+impl EphemeralStatic {
+    pub fn entities() -> &'static DashMap<u32, EphemeralEntity> {
+        &ENTITIES
+    }
+    fn next_id() -> u32 {
+        NEXT_ID.fetch_add(1, Ordering::Relaxed)
+    }
+}
+
+// 投射物 / 特效的快速通道: 生成 / 更新 / 销毁都是一条 Unreliable 的小消息
+// 服务器在 ttl 到期后自动销毁, 客户端也应该按 ttl 自己销毁, 丢包不会留下残留
+pub struct Ephemeral;
+
+impl Ephemeral {
+    // 生成并发送给所有准备好的连接, 返回 id
+    pub fn spawn(kind: u32, position: Vector3<f32>, velocity: Vector3<f32>, ttl: f32) -> u32 {
+        let id = EphemeralStatic::next_id();
+        let mut observers = Vec::new();
+        NetworkServerStatic::for_each_network_connection(|connection| {
+            if connection.is_ready() {
+                observers.push(connection.connection_id());
+            }
+        });
+        let mut message = EphemeralSpawnMessage::new(id, kind, position, velocity, ttl);
+        Self::send_to_observers(&observers, &mut message);
+        ENTITIES.insert(
+            id,
+            EphemeralEntity {
+                id,
+                kind,
+                position,
+                velocity,
+                spawn_time: NetworkTime::local_time(),
+                ttl,
+                observers,
+            },
+        );
+        id
+    }
+
+    // 更新位置和速度, 对象不存在 (已经到期) 返回 false
+    pub fn send_update(id: u32, position: Vector3<f32>, velocity: Vector3<f32>) -> bool {
+        let observers = match ENTITIES.get_mut(&id) {
+            Some(mut entity) => {
+                entity.position = position;
+                entity.velocity = velocity;
+                entity.observers.clone()
+            }
+            None => return false,
+        };
+        let mut message = EphemeralUpdateMessage::new(id, position, velocity);
+        Self::send_to_observers(&observers, &mut message);
+        true
+    }
+
+    pub fn despawn(id: u32) -> bool {
+        match ENTITIES.remove(&id) {
+            Some((_, entity)) => {
+                let mut message = EphemeralDespawnMessage::new(id);
+                Self::send_to_observers(&entity.observers, &mut message);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // 在 NetworkServer::network_late_update 中调用, 销毁到期的对象
+    pub fn update() {
+        let local_time = NetworkTime::local_time();
+        let expired: Vec<u32> = ENTITIES
+            .iter()
+            .filter(|entity| entity.expired(local_time))
+            .map(|entity| entity.id)
+            .collect();
+        for id in expired {
+            Self::despawn(id);
+        }
+    }
+
+    pub fn on_disconnected(connection_id: u64) {
+        ENTITIES.iter_mut().for_each(|mut entity| {
+            entity.observers.retain(|id| *id != connection_id);
+        });
+    }
+
+    pub fn reset() {
+        ENTITIES.clear();
+    }
+
+    fn send_to_observers<T>(observers: &[u64], message: &mut T)
+    where
+        T: NetworkMessageTrait + Send,
+    {
+        for connection_id in observers.iter() {
+            match NetworkServerStatic::network_connections().try_get_mut(connection_id) {
+                TryResult::Present(mut connection) => {
+                    // 切换场景期间不发送
+                    if connection.is_ready() {
+                        connection.send_network_message(message, TransportChannel::Unreliable);
+                    }
+                }
+                TryResult::Absent => {}
+                TryResult::Locked => {
+                    log_error!(
+                        "Ephemeral failed to send {}: connectionId {} is locked",
+                        T::get_full_name(),
+                        connection_id
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::core::network_reader::{NetworkReader, NetworkReaderTrait};
+    use crate::mirror::core::network_server::NetworkServer;
+    use crate::mirror::transports::memory::memory_transport::test_util::*;
+    use crate::mirror::transports::memory::memory_transport::MemoryTransport;
+
+    #[test]
+    fn test_ephemeral_entities() {
+        with_server(|| {
+            MemoryTransport::client_connect(1);
+            tick();
+            NetworkServer::set_client_ready(1);
+            tick();
+            MemoryTransport::client_receive(1);
+
+            let position = Vector3::new(1.0, 2.0, 3.0);
+            let velocity = Vector3::new(0.0, 0.0, 10.0);
+            let id = Ephemeral::spawn(9, position, velocity, 0.0);
+            assert!(Ephemeral::send_update(id, position + velocity, velocity));
+            let long_lived = Ephemeral::spawn(9, position, velocity, 60.0);
+            assert!(Ephemeral::despawn(long_lived));
+
+            // ttl 到期后自动销毁
+            tick();
+            assert!(!EphemeralStatic::entities().contains_key(&id));
+            assert!(!Ephemeral::send_update(id, position, velocity));
+            let messages = MemoryTransport::client_receive_messages(1);
+            let ids = |hash: u16| -> Vec<u32> {
+                messages
+                    .iter()
+                    .filter_map(|(message, _)| {
+                        let mut reader = NetworkReader::new_with_bytes(message.clone());
+                        if reader.read_ushort() == hash {
+                            Some(reader.decompress_var_uint())
+                        } else {
+                            None
+                        }
+                    })
+                    .collect()
+            };
+            assert_eq!(
+                ids(EphemeralSpawnMessage::get_hash_code()),
+                vec![id, long_lived]
+            );
+            assert_eq!(ids(EphemeralUpdateMessage::get_hash_code()), vec![id]);
+            assert_eq!(
+                ids(EphemeralDespawnMessage::get_hash_code()),
+                vec![long_lived, id]
+            );
+        });
+    }
+}
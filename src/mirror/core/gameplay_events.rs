@@ -0,0 +1,318 @@
+use crate::log_warn;
+use crate::mirror::core::messages::{NetworkMessageTrait, RpcMessage};
+use crate::mirror::core::network_connection::NetworkConnectionTrait;
+use crate::mirror::core::network_context::ContextLocal;
+use crate::mirror::core::network_server::NetworkServerStatic;
+use crate::mirror::core::network_time::NetworkTime;
+use crate::mirror::core::network_writer_pool::NetworkWriterPool;
+use crate::mirror::core::tools::stable_hash::StableHash;
+use crate::mirror::core::transport::TransportChannel;
+use dashmap::try_result::TryResult;
+use dashmap::DashMap;
+use lazy_static::lazy_static;
+use nalgebra::Vector3;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// 一种事件的发送规则, 没有注册的事件不限制频率和距离
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GameplayEvent {
+    // 每个对象每秒最多发送的次数, 0 表示不限制
+    pub max_per_second: f32,
+    // 只发送给玩家对象在这个距离内的观察者, 0 表示所有观察者
+    pub radius: f32,
+    // 所有者通常已经在本地播放了 (例如开枪的枪口火焰)
+    pub include_owner: bool,
+}
+
+impl Default for GameplayEvent {
+    fn default() -> Self {
+        Self {
+            max_per_second: 0.0,
+            radius: 0.0,
+            include_owner: true,
+        }
+    }
+}
+
+// 令牌桶, 单位为次
+#[derive(Debug, Clone, Copy)]
+struct EventBudget {
+    tokens: f64,
+    last_refill: f64,
+}
+
+// GameplayEvents 静态变量
+lazy_static! {
+    // function_hash -> 规则
+    static ref EVENTS: ContextLocal<DashMap<u16, GameplayEvent>> = ContextLocal::new(DashMap::new);
+    // (net_id, function_hash) -> 剩余次数
+    static ref BUDGETS: ContextLocal<DashMap<(u32, u16), EventBudget>> =
+        ContextLocal::new(DashMap::new);
+    static ref SENT_COUNT: ContextLocal<AtomicU64> = ContextLocal::new(|| AtomicU64::new(0));
+    static ref DROPPED_COUNT: ContextLocal<AtomicU64> = ContextLocal::new(|| AtomicU64::new(0));
+}
+
+// 一次性的表现类事件 (脚步声、枪口火焰、伤害数字): 作为 ClientRpc 通过 Unreliable 通道发送,
+// 客户端使用普通的 [ClientRpc(channel = Channels.Unreliable)] 方法接收, 丢失也没有关系
+// 只发送给对象的观察者 (兴趣管理), 可以按事件限制频率和距离
+pub struct GameplayEvents;
+
+impl GameplayEvents {
+    // function_full_name 与 C# 一致, 例如 "System.Void Player::RpcFootstep(System.Int32)"
+    pub fn register(function_full_name: &str, event: GameplayEvent) {
+        EVENTS.insert(function_full_name.get_fn_stable_hash_code(), event);
+    }
+
+    pub fn unregister(function_full_name: &str) {
+        let function_hash = function_full_name.get_fn_stable_hash_code();
+        EVENTS.remove(&function_hash);
+        BUDGETS.retain(|(_, hash), _| *hash != function_hash);
+    }
+
+    pub fn event(function_full_name: &str) -> Option<GameplayEvent> {
+        EVENTS
+            .get(&(function_full_name.get_fn_stable_hash_code()))
+            .map(|event| *event)
+    }
+
+    // 发送给每个观察者计一次
+    pub fn sent_count() -> u64 {
+        SENT_COUNT.load(Ordering::Relaxed)
+    }
+
+    // 超过频率限制被丢弃的事件
+    pub fn dropped_count() -> u64 {
+        DROPPED_COUNT.load(Ordering::Relaxed)
+    }
+
+    // 参数按 T::serialize 写入, 返回收到事件的连接数量
+    pub fn emit<T: NetworkMessageTrait>(
+        net_id: u32,
+        component_index: u8,
+        function_full_name: &str,
+        args: &mut T,
+    ) -> usize {
+        let mut sent = 0;
+        NetworkWriterPool::get_return(|writer| {
+            args.serialize(writer);
+            sent = Self::emit_raw(
+                net_id,
+                component_index,
+                function_full_name,
+                writer.to_array_segment(),
+            );
+        });
+        sent
+    }
+
+    // payload 是已经序列化好的 Rpc 参数
+    pub fn emit_raw(
+        net_id: u32,
+        component_index: u8,
+        function_full_name: &str,
+        payload: &[u8],
+    ) -> usize {
+        if !NetworkServerStatic::active() {
+            return 0;
+        }
+        let function_hash = function_full_name.get_fn_stable_hash_code();
+        let event = EVENTS
+            .get(&function_hash)
+            .map(|event| *event)
+            .unwrap_or_default();
+
+        let (owner, position, observers) =
+            match NetworkServerStatic::spawned_network_identities().try_get(&net_id) {
+                TryResult::Present(identity) => {
+                    if identity.observers().is_empty() {
+                        return 0;
+                    }
+                    (
+                        identity.connection_to_client(),
+                        identity.game_object().transform.position,
+                        identity.observers().clone(),
+                    )
+                }
+                TryResult::Absent => return 0,
+                TryResult::Locked => {
+                    log_warn!("GameplayEvents: netId {} is locked.", net_id);
+                    return 0;
+                }
+            };
+
+        if !Self::consume_budget(net_id, function_hash, event.max_per_second) {
+            DROPPED_COUNT.fetch_add(1, Ordering::Relaxed);
+            return 0;
+        }
+
+        let mut message: Option<RpcMessage> = None;
+        let mut sent = 0;
+        for observer in observers.iter() {
+            if *observer == owner && !event.include_owner {
+                continue;
+            }
+            if event.radius > 0.0 && !Self::in_radius(*observer, position, event.radius) {
+                continue;
+            }
+            if let TryResult::Present(mut connection) =
+                NetworkServerStatic::network_connections().try_get_mut(observer)
+            {
+                if !connection.is_ready() {
+                    continue;
+                }
+                let message = message.get_or_insert_with(|| {
+                    RpcMessage::new(net_id, component_index, function_hash, payload.to_vec())
+                });
+                connection.send_network_message(message, TransportChannel::Unreliable);
+                sent += 1;
+            }
+        }
+        SENT_COUNT.fetch_add(sent as u64, Ordering::Relaxed);
+        sent
+    }
+
+    // 还没有玩家对象的连接 (例如观战) 收到所有事件
+    fn in_radius(connection_id: u64, position: Vector3<f32>, radius: f32) -> bool {
+        let player = match NetworkServerStatic::network_connections().try_get(&connection_id) {
+            TryResult::Present(connection) => connection.net_id(),
+            _ => return false,
+        };
+        if player == 0 {
+            return true;
+        }
+        match NetworkServerStatic::spawned_network_identities().try_get(&player) {
+            TryResult::Present(identity) => {
+                (identity.game_object().transform.position - position).norm_squared()
+                    <= radius * radius
+            }
+            TryResult::Absent => true,
+            // 事件来自玩家自己的对象
+            TryResult::Locked => true,
+        }
+    }
+
+    fn consume_budget(net_id: u32, function_hash: u16, max_per_second: f32) -> bool {
+        if max_per_second <= 0.0 {
+            return true;
+        }
+        let max = max_per_second as f64;
+        // 至少允许一次, 低于每秒 1 次的限制也能发送
+        let capacity = max.max(1.0);
+        let local_time = NetworkTime::local_time();
+        let mut budget = BUDGETS
+            .entry((net_id, function_hash))
+            .or_insert(EventBudget {
+                tokens: capacity,
+                last_refill: local_time,
+            });
+        budget.tokens = (budget.tokens + (local_time - budget.last_refill) * max).min(capacity);
+        budget.last_refill = local_time;
+        if budget.tokens < 1.0 {
+            return false;
+        }
+        budget.tokens -= 1.0;
+        true
+    }
+
+    // 在 NetworkServer::un_spawn_internal 中调用
+    pub(crate) fn on_despawn(net_id: u32) {
+        if BUDGETS.is_empty() {
+            return;
+        }
+        BUDGETS.retain(|(id, _), _| *id != net_id);
+    }
+
+    // 注册的事件保留
+    pub fn reset() {
+        BUDGETS.clear();
+        SENT_COUNT.store(0, Ordering::Relaxed);
+        DROPPED_COUNT.store(0, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::core::network_behaviour::GameObject;
+    use crate::mirror::core::network_identity::{NetworkIdentity, Visibility};
+    use crate::mirror::core::network_server::NetworkServer;
+    use crate::mirror::transports::memory::memory_transport::test_util::*;
+    use crate::mirror::transports::memory::memory_transport::MemoryTransport;
+
+    #[test]
+    fn test_gameplay_events() {
+        with_server(|| {
+            for conn_id in [1, 2, 3] {
+                MemoryTransport::client_connect(conn_id);
+            }
+            tick();
+            for conn_id in [1, 2, 3] {
+                NetworkServer::set_client_ready(conn_id);
+            }
+            // 980 / 981 / 982 是连接 1 / 2 / 3 的玩家对象
+            for (net_id, owner, x) in [(980, 1, 0.0), (981, 2, 5.0), (982, 3, 50.0)] {
+                let mut game_object = GameObject::default();
+                game_object.transform.position = Vector3::new(x, 0.0, 0.0);
+                let mut identity = NetworkIdentity::new_with_asset_id(0);
+                identity.set_net_id(net_id);
+                identity.set_connection_to_client(owner);
+                identity.set_game_object(game_object);
+                NetworkServerStatic::add_spawned_network_identity(identity);
+                NetworkServer::set_visibility(net_id, Visibility::Default);
+                NetworkServerStatic::network_connections()
+                    .get_mut(&owner)
+                    .unwrap()
+                    .set_net_id(net_id);
+            }
+            tick();
+            for conn_id in [1, 2, 3] {
+                MemoryTransport::client_receive_messages(conn_id);
+            }
+
+            let name = "System.Void Player::RpcFootstep(System.Int32)";
+            GameplayEvents::register(
+                name,
+                GameplayEvent {
+                    max_per_second: 2.0,
+                    radius: 10.0,
+                    include_owner: false,
+                },
+            );
+            // 所有者和 50 米外的连接 3 收不到, 每秒最多 2 次
+            assert_eq!(GameplayEvents::emit(980, 0, name, &mut TestHit(7)), 1);
+            assert_eq!(GameplayEvents::emit(980, 0, name, &mut TestHit(8)), 1);
+            assert_eq!(GameplayEvents::emit(980, 0, name, &mut TestHit(9)), 0);
+            assert_eq!(GameplayEvents::sent_count(), 2);
+            assert_eq!(GameplayEvents::dropped_count(), 1);
+            // 限制按对象计算, 没有注册的事件不限制
+            assert_eq!(GameplayEvents::emit(981, 0, name, &mut TestHit(1)), 1);
+            assert_eq!(
+                GameplayEvents::emit(980, 1, "System.Void Player::RpcFlash()", &mut TestHit(1)),
+                3
+            );
+            assert_eq!(GameplayEvents::emit(999, 0, name, &mut TestHit(1)), 0);
+
+            tick();
+            let messages = MemoryTransport::client_receive_messages(2);
+            assert!(messages
+                .iter()
+                .all(|(_, channel)| *channel == TransportChannel::Unreliable));
+            let rpcs = decode::<RpcMessage>(&messages);
+            assert_eq!(rpcs.len(), 3);
+            assert_eq!(
+                rpcs[0],
+                RpcMessage::new(980, 0, name.get_fn_stable_hash_code(), vec![7, 0, 0, 0])
+            );
+            assert_eq!(rpcs[1].payload, vec![8, 0, 0, 0]);
+            assert_eq!(rpcs[2].component_index, 1);
+            assert_eq!(received::<RpcMessage>(3).len(), 1);
+
+            GameplayEvents::unregister(name);
+            assert!(GameplayEvents::event(name).is_none());
+            GameplayEvents::reset();
+            for net_id in [980, 981, 982] {
+                NetworkServerStatic::remove_spawned_network_identity(&net_id);
+            }
+        });
+    }
+}
@@ -0,0 +1,362 @@
+use crate::log_error;
+use crate::mirror::core::lag_compensation::LagCompensation;
+use crate::mirror::core::network_connection::NetworkConnectionTrait;
+use crate::mirror::core::network_context::ContextLocal;
+use crate::mirror::core::network_events::Subscribers;
+use crate::mirror::core::network_server::NetworkServerStatic;
+use crate::mirror::core::network_time::NetworkTime;
+use atomic::Atomic;
+use crossbeam_channel::Receiver;
+use dashmap::try_result::TryResult;
+use lazy_static::lazy_static;
+use nalgebra::Vector3;
+use std::fmt::{Display, Formatter};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WeaponSpec {
+    pub range: f32,
+    // 散布半角, 单位弧度
+    pub spread: f32,
+}
+
+// 客户端上报的一次命中
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShotClaim {
+    pub shooter: u64,
+    pub weapon: WeaponSpec,
+    pub target: u32,
+    // 射击方向, 不需要归一化
+    pub direction: Vector3<f32>,
+    // 客户端开枪时看到的服务器时间
+    pub timestamp: f64,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum HitRejectReason {
+    // 射击者没有连接或者没有玩家对象
+    ShooterNotFound,
+    TargetNotFound,
+    SelfHit,
+    // 超过 max_rewind 或者在未来
+    TimestampOutOfRange,
+    OutOfRange,
+    OutsideSpread,
+    LineOfSightBlocked,
+}
+
+impl Display for HitRejectReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HitRejectReason::ShooterNotFound => write!(f, "shooter has no player"),
+            HitRejectReason::TargetNotFound => write!(f, "target is not spawned"),
+            HitRejectReason::SelfHit => write!(f, "shooter hit itself"),
+            HitRejectReason::TimestampOutOfRange => write!(f, "timestamp out of rewind range"),
+            HitRejectReason::OutOfRange => write!(f, "target out of weapon range"),
+            HitRejectReason::OutsideSpread => write!(f, "target outside weapon spread"),
+            HitRejectReason::LineOfSightBlocked => write!(f, "line of sight blocked"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HitEvent {
+    pub shooter: u64,
+    pub shooter_net_id: u32,
+    pub target: u32,
+    // 回退后的目标位置
+    pub position: Vector3<f32>,
+    pub distance: f32,
+    pub timestamp: f64,
+}
+
+// 视线检查: (射击起点, 目标位置), 没有遮挡时返回 true
+pub type LineOfSightFunc = fn(Vector3<f32>, Vector3<f32>) -> bool;
+
+// HitRegistration 静态变量
+lazy_static! {
+    // 最多回退多少秒
+    static ref MAX_REWIND: ContextLocal<Atomic<f64>> = ContextLocal::new(|| Atomic::new(0.5));
+    // 允许的客户端时间超前量
+    static ref MAX_FUTURE: ContextLocal<Atomic<f64>> = ContextLocal::new(|| Atomic::new(0.05));
+    // 目标的半径, 用于距离和散布检查
+    static ref TARGET_RADIUS: ContextLocal<Atomic<f32>> = ContextLocal::new(|| Atomic::new(0.5));
+    static ref LINE_OF_SIGHT: ContextLocal<RwLock<Option<LineOfSightFunc>>> =
+        ContextLocal::new(|| RwLock::new(None));
+    static ref CONFIRMED_COUNT: ContextLocal<AtomicU64> = ContextLocal::new(|| AtomicU64::new(0));
+    static ref REJECTED_COUNT: ContextLocal<AtomicU64> = ContextLocal::new(|| AtomicU64::new(0));
+    static ref HITS: ContextLocal<Subscribers<HitEvent>> = ContextLocal::new(Subscribers::new);
+}
+
+// 命中判定: 通过 LagCompensation 把目标回退到客户端开枪的时刻, 检查时间戳、距离、散布和视线
+// 射击起点使用服务器上射击者玩家当前的位置
+// 需要先 LagCompensation::set_enabled(true), 否则按目标当前的位置判定
+pub struct HitRegistration;
+
+impl HitRegistration {
+    pub fn max_rewind() -> f64 {
+        MAX_REWIND.load(Ordering::Relaxed)
+    }
+
+    pub fn set_max_rewind(value: f64) {
+        MAX_REWIND.store(value, Ordering::Relaxed);
+        // 历史至少要覆盖回退范围
+        if LagCompensation::history_duration() < value {
+            LagCompensation::set_history_duration(value);
+        }
+    }
+
+    pub fn max_future() -> f64 {
+        MAX_FUTURE.load(Ordering::Relaxed)
+    }
+
+    pub fn set_max_future(value: f64) {
+        MAX_FUTURE.store(value, Ordering::Relaxed);
+    }
+
+    pub fn target_radius() -> f32 {
+        TARGET_RADIUS.load(Ordering::Relaxed)
+    }
+
+    pub fn set_target_radius(value: f32) {
+        TARGET_RADIUS.store(value, Ordering::Relaxed);
+    }
+
+    pub fn line_of_sight() -> Option<LineOfSightFunc> {
+        match LINE_OF_SIGHT.read() {
+            Ok(func) => *func,
+            Err(e) => {
+                log_error!("HitRegistration failed to read LINE_OF_SIGHT: {:?}", e);
+                None
+            }
+        }
+    }
+
+    // 服务器没有场景碰撞数据, 视线检查由游戏逻辑提供, None 表示不检查
+    pub fn set_line_of_sight(func: Option<LineOfSightFunc>) {
+        match LINE_OF_SIGHT.write() {
+            Ok(mut line_of_sight) => *line_of_sight = func,
+            Err(e) => {
+                log_error!("HitRegistration failed to write LINE_OF_SIGHT: {:?}", e);
+            }
+        }
+    }
+
+    pub fn confirmed_count() -> u64 {
+        CONFIRMED_COUNT.load(Ordering::Relaxed)
+    }
+
+    pub fn rejected_count() -> u64 {
+        REJECTED_COUNT.load(Ordering::Relaxed)
+    }
+
+    pub fn on_hit() -> Receiver<HitEvent> {
+        HITS.subscribe()
+    }
+
+    // 确认的命中同时发布给 on_hit 的订阅者
+    pub fn register(shot: ShotClaim) -> Result<HitEvent, HitRejectReason> {
+        match Self::validate(&shot) {
+            Ok(event) => {
+                CONFIRMED_COUNT.fetch_add(1, Ordering::Relaxed);
+                HITS.publish(event.clone());
+                Ok(event)
+            }
+            Err(reason) => {
+                REJECTED_COUNT.fetch_add(1, Ordering::Relaxed);
+                Err(reason)
+            }
+        }
+    }
+
+    fn validate(shot: &ShotClaim) -> Result<HitEvent, HitRejectReason> {
+        let local_time = NetworkTime::local_time();
+        if shot.timestamp < local_time - Self::max_rewind()
+            || shot.timestamp > local_time + Self::max_future()
+        {
+            return Err(HitRejectReason::TimestampOutOfRange);
+        }
+
+        let shooter_net_id = match NetworkServerStatic::network_connections().try_get(&shot.shooter)
+        {
+            TryResult::Present(connection) if connection.net_id() != 0 => connection.net_id(),
+            _ => return Err(HitRejectReason::ShooterNotFound),
+        };
+        if shooter_net_id == shot.target {
+            return Err(HitRejectReason::SelfHit);
+        }
+        let spawned = NetworkServerStatic::spawned_network_identities();
+        let origin = match spawned.try_get(&shooter_net_id) {
+            TryResult::Present(identity) => identity.game_object().transform.position,
+            _ => return Err(HitRejectReason::ShooterNotFound),
+        };
+        let current = match spawned.try_get(&shot.target) {
+            TryResult::Present(identity) => identity.game_object().transform.position,
+            _ => return Err(HitRejectReason::TargetNotFound),
+        };
+        let position = LagCompensation::position_at(shot.target, shot.timestamp).unwrap_or(current);
+
+        let radius = Self::target_radius();
+        let offset = position - origin;
+        let distance = offset.norm();
+        if distance - radius > shot.weapon.range {
+            return Err(HitRejectReason::OutOfRange);
+        }
+        // 目标的半径也算在散布内, 距离越近允许的角度越大
+        if distance > radius {
+            let angle = match shot.direction.try_normalize(f32::EPSILON) {
+                Some(direction) => direction.angle(&offset),
+                None => return Err(HitRejectReason::OutsideSpread),
+            };
+            if angle > shot.weapon.spread + (radius / distance).asin() {
+                return Err(HitRejectReason::OutsideSpread);
+            }
+        }
+        if let Some(line_of_sight) = Self::line_of_sight() {
+            if !line_of_sight(origin, position) {
+                return Err(HitRejectReason::LineOfSightBlocked);
+            }
+        }
+
+        Ok(HitEvent {
+            shooter: shot.shooter,
+            shooter_net_id,
+            target: shot.target,
+            position,
+            distance,
+            timestamp: shot.timestamp,
+        })
+    }
+
+    pub fn reset() {
+        CONFIRMED_COUNT.store(0, Ordering::Relaxed);
+        REJECTED_COUNT.store(0, Ordering::Relaxed);
+        Self::set_line_of_sight(None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::core::network_behaviour::GameObject;
+    use crate::mirror::core::network_identity::NetworkIdentity;
+    use crate::mirror::transports::memory::memory_transport::test_util::*;
+    use crate::mirror::transports::memory::memory_transport::MemoryTransport;
+
+    fn blocked(_origin: Vector3<f32>, _target: Vector3<f32>) -> bool {
+        false
+    }
+
+    #[test]
+    fn test_hit_registration() {
+        with_server(|| {
+            MemoryTransport::client_connect(1);
+            tick();
+            for (net_id, x) in [(901, 0.0), (902, 10.0)] {
+                let mut game_object = GameObject::default();
+                game_object.transform.position = Vector3::new(x, 0.0, 0.0);
+                let mut identity = NetworkIdentity::new_with_asset_id(0);
+                identity.set_net_id(net_id);
+                identity.set_game_object(game_object);
+                NetworkServerStatic::add_spawned_network_identity(identity);
+            }
+            NetworkServerStatic::network_connections()
+                .get_mut(&1)
+                .unwrap()
+                .set_net_id(901);
+
+            // 目标开枪时在 (10, 0, 0), 之后移动到 (10, 5, 0)
+            LagCompensation::set_enabled(true);
+            LagCompensation::record();
+            let fired = NetworkTime::local_time();
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            {
+                let mut identity = NetworkServerStatic::spawned_network_identities()
+                    .get_mut(&902)
+                    .unwrap();
+                let mut game_object = identity.game_object().clone();
+                game_object.transform.position = Vector3::new(10.0, 5.0, 0.0);
+                identity.set_game_object(game_object);
+            }
+            LagCompensation::record();
+            assert_eq!(LagCompensation::history_len(902), 2);
+
+            let hits = HitRegistration::on_hit();
+            let shot = ShotClaim {
+                shooter: 1,
+                weapon: WeaponSpec {
+                    range: 50.0,
+                    spread: 0.01,
+                },
+                target: 902,
+                direction: Vector3::new(1.0, 0.0, 0.0),
+                timestamp: fired,
+            };
+            let event = HitRegistration::register(shot).unwrap();
+            assert_eq!((event.shooter_net_id, event.target), (901, 902));
+            assert!((event.position - Vector3::new(10.0, 0.0, 0.0)).norm() < 0.1);
+            assert_eq!(hits.try_recv().unwrap(), event);
+
+            // 朝目标当前的位置射击, 但开枪时目标不在那里
+            let at_current = ShotClaim {
+                direction: Vector3::new(10.0, 5.0, 0.0),
+                ..shot
+            };
+            for (claim, reason) in [
+                (at_current, HitRejectReason::OutsideSpread),
+                (
+                    ShotClaim {
+                        timestamp: fired - 1.0,
+                        ..shot
+                    },
+                    HitRejectReason::TimestampOutOfRange,
+                ),
+                (
+                    ShotClaim {
+                        weapon: WeaponSpec {
+                            range: 5.0,
+                            spread: 0.01,
+                        },
+                        ..shot
+                    },
+                    HitRejectReason::OutOfRange,
+                ),
+                (
+                    ShotClaim {
+                        target: 901,
+                        ..shot
+                    },
+                    HitRejectReason::SelfHit,
+                ),
+                (
+                    ShotClaim {
+                        target: 999,
+                        ..shot
+                    },
+                    HitRejectReason::TargetNotFound,
+                ),
+                (
+                    ShotClaim { shooter: 2, ..shot },
+                    HitRejectReason::ShooterNotFound,
+                ),
+            ] {
+                assert_eq!(HitRegistration::register(claim), Err(reason));
+            }
+
+            HitRegistration::set_line_of_sight(Some(blocked));
+            assert_eq!(
+                HitRegistration::register(shot),
+                Err(HitRejectReason::LineOfSightBlocked)
+            );
+            assert_eq!(HitRegistration::confirmed_count(), 1);
+            assert_eq!(HitRegistration::rejected_count(), 7);
+            assert!(hits.try_recv().is_err());
+            for net_id in [901, 902] {
+                NetworkServerStatic::remove_spawned_network_identity(&net_id);
+            }
+            HitRegistration::reset();
+            LagCompensation::reset();
+        });
+    }
+}
@@ -0,0 +1,285 @@
+use crate::mirror::core::network_connection::NetworkConnectionTrait;
+use crate::mirror::core::network_reader::{NetworkReader, NetworkReaderTrait};
+use crate::mirror::core::network_server::NetworkServerStatic;
+use crate::mirror::core::network_writer::NetworkWriterTrait;
+use crate::mirror::core::network_writer_pool::NetworkWriterPool;
+use crate::mirror::core::world_snapshot::{WorldSnapshot, WorldSnapshotEntry};
+use crate::{log_error, log_warn};
+use dashmap::try_result::TryResult;
+use std::collections::{BTreeSet, HashSet};
+
+// 一个已生成的对象和它在原服务器上的所有者
+#[derive(Debug, Clone, PartialEq)]
+pub struct HostMigrationEntry {
+    // 原服务器上的连接 id, 0 表示没有所有者
+    pub owner: u64,
+    // 是否是 owner 的玩家对象
+    pub is_player: bool,
+    pub object: WorldSnapshotEntry,
+}
+
+// 主机迁移时由另一个节点接管所需的最小权威状态
+// 与 WorldSnapshot 相比保留所有者, 只有紧凑的二进制格式
+#[derive(Debug, Clone, PartialEq)]
+pub struct HostMigrationState {
+    pub version: u16,
+    pub entries: Vec<HostMigrationEntry>,
+}
+
+impl HostMigrationState {
+    pub const VERSION: u16 = 1;
+    const MAGIC: &'static [u8; 4] = b"MHMS";
+
+    pub fn capture() -> Self {
+        let players: HashSet<u32> = NetworkServerStatic::network_connections()
+            .iter()
+            .map(|connection| connection.net_id())
+            .filter(|net_id| *net_id != 0)
+            .collect();
+        let entries = WorldSnapshot::capture()
+            .entries
+            .into_iter()
+            .map(|object| {
+                let owner = match NetworkServerStatic::spawned_network_identities()
+                    .try_get(&object.net_id)
+                {
+                    TryResult::Present(identity) => identity.connection_to_client(),
+                    _ => 0,
+                };
+                HostMigrationEntry {
+                    owner,
+                    is_player: owner != 0 && players.contains(&object.net_id),
+                    object,
+                }
+            })
+            .collect();
+        Self {
+            version: Self::VERSION,
+            entries,
+        }
+    }
+
+    // 原服务器上拥有对象的连接 id, 接管前需要为每个连接建立映射
+    pub fn owners(&self) -> BTreeSet<u64> {
+        self.entries
+            .iter()
+            .map(|entry| entry.owner)
+            .filter(|owner| *owner != 0)
+            .collect()
+    }
+
+    // 在新的服务器上恢复所有对象, remap 把原来的连接 id 映射为新的连接 id
+    // 映射不到的连接视为没有回来, 它拥有的对象不会恢复 (与断开连接时销毁 owned 对象一致)
+    // 返回恢复的数量
+    pub fn restore<F: Fn(u64) -> Option<u64>>(&self, remap: F) -> usize {
+        if !NetworkServerStatic::active() {
+            log_error!("HostMigration.Restore: NetworkServer is not active.");
+            return 0;
+        }
+        // 先预留所有 net_id, 恢复过程中新分配的 net_id 不会占用后面的对象
+        WorldSnapshot::reserve_net_ids(self.entries.iter().map(|entry| entry.object.net_id).max());
+        let mut restored = 0;
+        let mut missing = BTreeSet::new();
+        for entry in self.entries.iter() {
+            let owner = match entry.owner {
+                0 => 0,
+                owner => match remap(owner) {
+                    Some(new_owner)
+                        if NetworkServerStatic::network_connections().contains_key(&new_owner) =>
+                    {
+                        new_owner
+                    }
+                    _ => {
+                        missing.insert(owner);
+                        continue;
+                    }
+                },
+            };
+            // 生成前设置玩家, SpawnMessage 中的 is_local_player 才正确
+            if entry.is_player {
+                Self::set_player(owner, entry.object.net_id);
+            }
+            let net_id = WorldSnapshot::restore_entry(&entry.object, owner);
+            if net_id == 0 {
+                continue;
+            }
+            if entry.is_player && net_id != entry.object.net_id {
+                Self::set_player(owner, net_id);
+            }
+            restored += 1;
+        }
+        if !missing.is_empty() {
+            log_warn!(
+                "HostMigration.Restore: skipped objects of unmapped connections {:?}",
+                missing
+            );
+        }
+        restored
+    }
+
+    fn set_player(conn_id: u64, net_id: u32) {
+        if let TryResult::Present(mut connection) =
+            NetworkServerStatic::network_connections().try_get_mut(&conn_id)
+        {
+            connection.set_net_id(net_id);
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        NetworkWriterPool::get_return(|writer| {
+            writer.write_array_segment_all(Self::MAGIC);
+            writer.write_ushort(self.version);
+            writer.compress_var_uint(self.entries.len() as u32);
+            for entry in self.entries.iter() {
+                writer.compress_var_ulong(entry.owner);
+                writer.write_bool(entry.is_player);
+                entry.object.write(writer);
+            }
+            bytes = writer.to_bytes();
+        });
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        if !bytes.starts_with(Self::MAGIC) {
+            return Err("not a host migration state".to_string());
+        }
+        let mut reader = NetworkReader::new_with_array_segment(&bytes[Self::MAGIC.len()..]);
+        let version = reader.read_ushort();
+        if version != Self::VERSION {
+            return Err(format!("unsupported host migration version {}", version));
+        }
+        let count = reader.decompress_var_uint();
+        let mut entries = Vec::new();
+        for _ in 0..count {
+            if reader.remaining() == 0 {
+                break;
+            }
+            entries.push(HostMigrationEntry {
+                owner: reader.decompress_var_ulong(),
+                is_player: reader.read_bool(),
+                object: WorldSnapshotEntry::read(&mut reader),
+            });
+        }
+        // 越界时 NetworkReader 只返回默认值, 重新序列化一遍比较长度
+        let state = Self { version, entries };
+        if state.entries.len() != count as usize
+            || reader.remaining() != 0
+            || state.to_bytes().len() != bytes.len()
+        {
+            return Err("truncated host migration state".to_string());
+        }
+        Ok(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::core::net_id_allocator::NetIdAllocator;
+    use crate::mirror::core::network_identity::NetworkIdentity;
+    use crate::mirror::transports::memory::memory_transport::test_util::*;
+    use crate::mirror::transports::memory::memory_transport::MemoryTransport;
+
+    #[test]
+    fn test_binary_round_trip() {
+        let state = HostMigrationState {
+            version: HostMigrationState::VERSION,
+            entries: vec![
+                HostMigrationEntry {
+                    owner: 0,
+                    is_player: false,
+                    object: WorldSnapshotEntry {
+                        net_id: 1,
+                        asset_id: 7,
+                        scene_id: 0,
+                        server_only: false,
+                        position: [1.0, 2.0, 3.0],
+                        rotation: [0.0, 0.0, 0.0, 1.0],
+                        scale: [1.0; 3],
+                        state: vec![1, 2, 3],
+                    },
+                },
+                HostMigrationEntry {
+                    owner: 300,
+                    is_player: true,
+                    object: WorldSnapshotEntry {
+                        net_id: 2,
+                        asset_id: 8,
+                        scene_id: 0,
+                        server_only: false,
+                        position: [0.0; 3],
+                        rotation: [0.0, 0.0, 0.0, 1.0],
+                        scale: [1.0; 3],
+                        state: Vec::new(),
+                    },
+                },
+            ],
+        };
+        assert_eq!(state.owners().into_iter().collect::<Vec<_>>(), vec![300]);
+        let bytes = state.to_bytes();
+        assert_eq!(HostMigrationState::from_bytes(&bytes), Ok(state));
+        assert!(HostMigrationState::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(HostMigrationState::from_bytes(b"MWSS").is_err());
+    }
+
+    #[test]
+    fn test_host_migration_export_and_import() {
+        with_server(|| {
+            MemoryTransport::client_connect(1);
+            MemoryTransport::client_connect(2);
+            tick();
+            // (net_id, asset_id, owner)
+            for (net_id, asset_id, owner) in [(300, 7, 1), (301, 8, 1), (302, 9, 2), (303, 10, 0)] {
+                let mut identity = NetworkIdentity::new_with_asset_id(asset_id);
+                identity.set_net_id(net_id);
+                identity.set_connection_to_client(owner);
+                NetworkServerStatic::add_spawned_network_identity(identity);
+            }
+            NetworkServerStatic::network_connections()
+                .get_mut(&1)
+                .unwrap()
+                .set_net_id(300);
+
+            let bytes = HostMigrationState::capture().to_bytes();
+            let state = HostMigrationState::from_bytes(&bytes).unwrap();
+            assert_eq!(state.owners().into_iter().collect::<Vec<_>>(), vec![1, 2]);
+
+            // 新的主机: 清空对象, 只有原来的连接 1 以连接 5 回来
+            for net_id in [300, 301, 302, 303] {
+                NetworkServerStatic::remove_spawned_network_identity(&net_id);
+            }
+            MemoryTransport::client_connect(5);
+            tick();
+            assert_eq!(state.restore(|owner| (owner == 1).then_some(5)), 3);
+
+            let mut restored = Vec::new();
+            NetworkServerStatic::for_each_spawned(|identity| {
+                restored.push((identity.net_id(), identity.connection_to_client()));
+            });
+            restored.sort();
+            assert_eq!(restored, vec![(300, 5), (301, 5), (303, 0)]);
+            let connection = NetworkServerStatic::network_connections().get(&5).unwrap();
+            assert_eq!(connection.net_id(), 300);
+            drop(connection);
+
+            // 303 已经存在, 换用的新 net_id 不能占用后面条目的 net_id
+            let next = NetIdAllocator::default_next();
+            let mut colliding = state.clone();
+            colliding.entries.retain(|entry| entry.owner == 0);
+            colliding.entries.push(colliding.entries[0].clone());
+            colliding.entries[1].object.net_id = next;
+            colliding.entries[1].object.asset_id = 12;
+            assert_eq!(colliding.restore(|_| None), 2);
+            let spawned = NetworkServerStatic::spawned_network_identities();
+            assert_eq!(spawned.get(&next).unwrap().asset_id, 12);
+            assert_eq!(spawned.get(&(next + 1)).unwrap().asset_id, 10);
+
+            for net_id in [300, 301, 303, next, next + 1] {
+                NetworkServerStatic::remove_spawned_network_identity(&net_id);
+            }
+            NetIdAllocator::reset();
+        });
+    }
+}
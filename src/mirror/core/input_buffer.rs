@@ -0,0 +1,347 @@
+use crate::log_error;
+use crate::mirror::core::messages::{InputAckMessage, InputMessage, NetworkMessageTrait};
+use crate::mirror::core::network_connection::NetworkConnectionTrait;
+use crate::mirror::core::network_context::ContextLocal;
+use crate::mirror::core::network_reader::NetworkReader;
+use crate::mirror::core::network_reader_pool::NetworkReaderPool;
+use crate::mirror::core::network_server::{NetworkServer, NetworkServerStatic};
+use crate::mirror::core::network_writer::NetworkWriter;
+use crate::mirror::core::transport::TransportChannel;
+use atomic::Atomic;
+use dashmap::try_result::TryResult;
+use lazy_static::lazy_static;
+use std::sync::atomic::Ordering;
+
+// 每个 tick 的客户端输入
+pub trait InputCommand: Default + Clone + Send + Sync + 'static {
+    fn serialize(&self, writer: &mut NetworkWriter);
+    fn deserialize(reader: &mut NetworkReader) -> Self;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct InputBufferStats {
+    // 重复收到 (冗余发送) 的输入
+    pub duplicates: u64,
+    // 到达时对应 tick 已经处理过
+    pub late: u64,
+    // 超出缓冲区范围
+    pub overflow: u64,
+    // 处理时缺失, 用上一个输入代替
+    pub missing: u64,
+}
+
+// 按 tick 存放输入的环形缓冲区, 每个 tick 只处理一次
+pub struct InputBuffer<T: InputCommand> {
+    slots: Vec<Option<(u32, T)>>,
+    // 0 表示还没有处理过任何输入
+    last_processed_tick: u32,
+    last_input: T,
+    stats: InputBufferStats,
+}
+
+impl<T: InputCommand> InputBuffer<T> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            slots: vec![None; capacity.max(1)],
+            last_processed_tick: 0,
+            last_input: T::default(),
+            stats: InputBufferStats::default(),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn last_processed_tick(&self) -> u32 {
+        self.last_processed_tick
+    }
+
+    pub fn stats(&self) -> InputBufferStats {
+        self.stats
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.iter().filter(|slot| slot.is_some()).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.iter().all(|slot| slot.is_none())
+    }
+
+    fn index(&self, tick: u32) -> usize {
+        tick as usize % self.slots.len()
+    }
+
+    // tick 从 1 开始, 重复 / 过期 / 超出范围的输入返回 false
+    pub fn insert(&mut self, tick: u32, input: T) -> bool {
+        if tick == 0 || tick <= self.last_processed_tick {
+            self.stats.late += 1;
+            return false;
+        }
+        if self.last_processed_tick != 0
+            && tick - self.last_processed_tick > self.slots.len() as u32
+        {
+            self.stats.overflow += 1;
+            return false;
+        }
+        let index = self.index(tick);
+        match &self.slots[index] {
+            Some((existing, _)) if *existing == tick => {
+                self.stats.duplicates += 1;
+                false
+            }
+            // 还没开始处理时, 旧的 tick 会被新的覆盖
+            Some((existing, _)) if *existing > tick => {
+                self.stats.overflow += 1;
+                false
+            }
+            _ => {
+                self.slots[index] = Some((tick, input));
+                true
+            }
+        }
+    }
+
+    // 在服务器的模拟 tick 中调用, 返回下一个 tick 的输入
+    // 缺失但之后的输入已经到达时重复上一个输入, 缓冲区为空时返回 None (等待客户端)
+    pub fn consume(&mut self) -> Option<(u32, T)> {
+        let next = if self.last_processed_tick == 0 {
+            self.slots.iter().flatten().map(|(tick, _)| *tick).min()?
+        } else {
+            self.last_processed_tick + 1
+        };
+        let index = self.index(next);
+        let input = match self.slots[index].take() {
+            Some((tick, input)) if tick == next => input,
+            other => {
+                self.slots[index] = other;
+                if !self.slots.iter().flatten().any(|(tick, _)| *tick > next) {
+                    return None;
+                }
+                self.stats.missing += 1;
+                self.last_input.clone()
+            }
+        };
+        self.last_processed_tick = next;
+        self.last_input = input.clone();
+        Some((next, input))
+    }
+
+    pub fn reset(&mut self) {
+        self.slots.iter_mut().for_each(|slot| *slot = None);
+        self.last_processed_tick = 0;
+        self.last_input = T::default();
+        self.stats = InputBufferStats::default();
+    }
+}
+
+// NetworkInput 静态变量
+lazy_static! {
+    static ref INPUT_BUFFER_CAPACITY: ContextLocal<Atomic<usize>> =
+        ContextLocal::new(|| Atomic::new(64));
+}
+
+// 竞技类游戏的输入通道: 客户端按 tick 发送 InputMessage, 服务器按连接缓存,
+// 模拟时逐 tick 取出并回复 InputAckMessage, 客户端据此做预测和回滚
+// 缓冲区存放在连接的 ext 中, 断开连接时一起释放
+pub struct NetworkInput;
+
+impl NetworkInput {
+    pub fn register<T: InputCommand>(capacity: usize) {
+        INPUT_BUFFER_CAPACITY.store(capacity, Ordering::Relaxed);
+        NetworkServer::register_handler::<InputMessage>(Self::on_input_message::<T>, true);
+    }
+
+    fn on_input_message<T: InputCommand>(
+        connection_id: u64,
+        reader: &mut NetworkReader,
+        _channel: TransportChannel,
+    ) {
+        let message = InputMessage::deserialize(reader);
+        match NetworkServerStatic::network_connections().try_get_mut(&connection_id) {
+            TryResult::Present(mut connection) => {
+                if !connection.has_ext::<InputBuffer<T>>() {
+                    connection.insert_ext(InputBuffer::<T>::new(
+                        INPUT_BUFFER_CAPACITY.load(Ordering::Relaxed),
+                    ));
+                }
+                if let Some(buffer) = connection.get_ext_mut::<InputBuffer<T>>() {
+                    for (i, bytes) in message.inputs.into_iter().enumerate() {
+                        let tick = message.first_tick.wrapping_add(i as u32);
+                        NetworkReaderPool::get_with_bytes_return(bytes, |reader| {
+                            buffer.insert(tick, T::deserialize(reader));
+                        });
+                    }
+                }
+            }
+            TryResult::Absent => {
+                log_error!(
+                    "Server.HandleInput: connectionId {} not found.",
+                    connection_id
+                );
+            }
+            TryResult::Locked => {
+                log_error!(
+                    "Server.HandleInput: connectionId {} is locked.",
+                    connection_id
+                );
+            }
+        }
+    }
+
+    // 取出连接的下一个输入并确认
+    pub fn consume<T: InputCommand>(connection_id: u64) -> Option<(u32, T)> {
+        match NetworkServerStatic::network_connections().try_get_mut(&connection_id) {
+            TryResult::Present(mut connection) => {
+                let consumed = connection
+                    .get_ext_mut::<InputBuffer<T>>()
+                    .and_then(|buffer| buffer.consume());
+                if let Some((tick, _)) = consumed.as_ref() {
+                    let mut message = InputAckMessage::new(*tick);
+                    connection.send_network_message(&mut message, TransportChannel::Unreliable);
+                }
+                consumed
+            }
+            TryResult::Absent => None,
+            TryResult::Locked => {
+                log_error!(
+                    "NetworkInput.Consume: connectionId {} is locked.",
+                    connection_id
+                );
+                None
+            }
+        }
+    }
+
+    pub fn last_processed_tick<T: InputCommand>(connection_id: u64) -> u32 {
+        match NetworkServerStatic::network_connections().get(&connection_id) {
+            Some(connection) => connection
+                .get_ext::<InputBuffer<T>>()
+                .map(|buffer| buffer.last_processed_tick())
+                .unwrap_or(0),
+            None => 0,
+        }
+    }
+
+    pub fn stats<T: InputCommand>(connection_id: u64) -> Option<InputBufferStats> {
+        NetworkServerStatic::network_connections()
+            .get(&connection_id)
+            .and_then(|connection| {
+                connection
+                    .get_ext::<InputBuffer<T>>()
+                    .map(|buffer| buffer.stats())
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::core::network_reader::NetworkReaderTrait;
+    use crate::mirror::core::network_writer::NetworkWriterTrait;
+    use crate::mirror::transports::memory::memory_transport::test_util::*;
+    use crate::mirror::transports::memory::memory_transport::MemoryTransport;
+
+    #[derive(Debug, Clone, Default, PartialEq)]
+    struct Move(i8);
+
+    impl InputCommand for Move {
+        fn serialize(&self, writer: &mut NetworkWriter) {
+            writer.write_sbyte(self.0);
+        }
+        fn deserialize(reader: &mut NetworkReader) -> Self {
+            Move(reader.read_sbyte())
+        }
+    }
+
+    #[test]
+    fn test_input_buffer() {
+        let mut buffer = InputBuffer::new(4);
+        assert_eq!(buffer.consume(), None);
+
+        // 第一个包: tick 10, 11
+        assert!(buffer.insert(10, Move(1)));
+        assert!(buffer.insert(11, Move(2)));
+        assert_eq!(buffer.consume(), Some((10, Move(1))));
+
+        // 冗余包: 11 重复, 12 丢失, 13 到达
+        assert!(!buffer.insert(11, Move(2)));
+        assert!(!buffer.insert(10, Move(1)));
+        assert!(buffer.insert(13, Move(4)));
+        assert!(!buffer.insert(20, Move(9)));
+        assert_eq!(buffer.consume(), Some((11, Move(2))));
+        assert_eq!(buffer.consume(), Some((12, Move(2))));
+        assert_eq!(buffer.consume(), Some((13, Move(4))));
+        assert_eq!(buffer.consume(), None);
+        assert_eq!(buffer.last_processed_tick(), 13);
+
+        assert_eq!(
+            buffer.stats(),
+            InputBufferStats {
+                duplicates: 1,
+                late: 1,
+                overflow: 1,
+                missing: 1,
+            }
+        );
+        assert!(buffer.is_empty());
+    }
+
+    #[derive(Debug, Clone, Default, PartialEq)]
+    struct TestInput(u8);
+
+    impl InputCommand for TestInput {
+        fn serialize(&self, writer: &mut NetworkWriter) {
+            writer.write_byte(self.0);
+        }
+        fn deserialize(reader: &mut NetworkReader) -> Self {
+            TestInput(reader.read_byte())
+        }
+    }
+
+    #[test]
+    fn test_network_input() {
+        with_server(|| {
+            NetworkInput::register::<TestInput>(8);
+            MemoryTransport::client_connect(1);
+            tick();
+            MemoryTransport::client_receive(1);
+
+            // 第二个包冗余携带 tick 5, 6 丢失但 7 到达
+            for (first_tick, inputs) in [
+                (5, vec![vec![1]]),
+                (5, vec![vec![1], vec![]]),
+                (7, vec![vec![3]]),
+            ] {
+                let mut message = InputMessage::new(first_tick, inputs);
+                MemoryTransport::client_send_message(1, &mut message, TransportChannel::Unreliable);
+            }
+            tick();
+            assert_eq!(
+                NetworkInput::consume::<TestInput>(1),
+                Some((5, TestInput(1)))
+            );
+            assert_eq!(
+                NetworkInput::consume::<TestInput>(1),
+                Some((6, TestInput(0)))
+            );
+            assert_eq!(
+                NetworkInput::consume::<TestInput>(1),
+                Some((7, TestInput(3)))
+            );
+            assert_eq!(NetworkInput::consume::<TestInput>(1), None);
+            assert_eq!(NetworkInput::last_processed_tick::<TestInput>(1), 7);
+            assert_eq!(NetworkInput::stats::<TestInput>(1).unwrap().duplicates, 1);
+
+            tick();
+            let acks: Vec<u32> = received::<InputAckMessage>(1)
+                .iter()
+                .map(|ack| ack.tick)
+                .collect();
+            assert_eq!(acks, vec![5, 6, 7]);
+            NetworkServer::unregister_handler::<InputMessage>();
+            INPUT_BUFFER_CAPACITY.store(64, Ordering::Relaxed);
+        });
+    }
+}
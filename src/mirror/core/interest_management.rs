@@ -0,0 +1,303 @@
+use crate::log_error;
+use crate::mirror::core::network_context::ContextLocal;
+use crate::mirror::core::network_identity::{NetworkIdentity, Visibility};
+use crate::mirror::core::network_server::{NetworkServer, NetworkServerStatic};
+use crate::mirror::core::network_time::NetworkTime;
+use atomic::Atomic;
+use dashmap::try_result::TryResult;
+use lazy_static::lazy_static;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+
+// 自定义兴趣管理, 例如战争迷雾、潜行
+// 只决定 Visibility::Default 的对象, 所有者、observe_all、ForceShown 和 ForceHidden 的规则不变
+pub trait InterestManagement: Send + Sync {
+    // connection_id 是否可以观察 identity, 在 InterestRadius 和 RegionStreaming 之后检查
+    fn on_check_observer(&self, identity: &NetworkIdentity, connection_id: u64) -> bool;
+
+    // 两轮重建之间的间隔, 单位秒, 0 表示每个 tick 开始新的一轮
+    fn interval(&self) -> f64 {
+        0.5
+    }
+
+    // 每个 tick 最多重建的对象数, 0 表示把一轮的对象平均分配到 interval 内的 tick
+    fn rebuilds_per_tick(&self) -> usize {
+        0
+    }
+
+    // 一轮需要重建的对象, 默认所有 Visibility::Default 的对象, 按 net_id 排序
+    fn rebuild_all(&self) -> Vec<u32> {
+        let mut net_ids: Vec<u32> = NetworkServerStatic::spawned_network_identities()
+            .iter()
+            .filter(|identity| identity.visibility == Visibility::Default)
+            .map(|identity| *identity.key())
+            .collect();
+        net_ids.sort();
+        net_ids
+    }
+
+    // 重建一个对象的观察者
+    fn rebuild_for(&self, identity: &mut NetworkIdentity) {
+        NetworkServer::rebuild_observers(identity, true);
+    }
+}
+
+// InterestManagementStatic 静态变量
+lazy_static! {
+    static ref INTEREST_MANAGEMENT: ContextLocal<RwLock<Option<Arc<dyn InterestManagement>>>> =
+        ContextLocal::new(|| RwLock::new(None));
+    // 等待重建的 net_id
+    static ref QUEUE: ContextLocal<RwLock<VecDeque<u32>>> =
+        ContextLocal::new(|| RwLock::new(VecDeque::new()));
+    // 这一轮每个 tick 重建的对象数
+    static ref BUDGET: ContextLocal<AtomicUsize> = ContextLocal::new(|| AtomicUsize::new(1));
+    static ref LAST_REBUILD_TIME: ContextLocal<Atomic<f64>> =
+        ContextLocal::new(|| Atomic::new(0.0));
+}
+
+// 调度自定义兴趣管理的重建: 每 interval 开始一轮, 一轮的对象分摊到多个 tick, 上一轮没有完成时不开始新的一轮
+// 通过 NetworkManager 的 interest_management 配置, 或者直接调用 set
+pub struct InterestManagementStatic;
+
+impl InterestManagementStatic {
+    pub fn set(interest_management: Arc<dyn InterestManagement>) {
+        match INTEREST_MANAGEMENT.write() {
+            Ok(mut current) => *current = Some(interest_management),
+            Err(e) => {
+                log_error!(
+                    "InterestManagement failed to write INTEREST_MANAGEMENT: {:?}",
+                    e
+                );
+            }
+        }
+        Self::clear_queue();
+    }
+
+    pub fn clear() {
+        if let Ok(mut current) = INTEREST_MANAGEMENT.write() {
+            *current = None;
+        }
+        Self::clear_queue();
+    }
+
+    pub fn exists() -> bool {
+        INTEREST_MANAGEMENT
+            .read()
+            .is_ok_and(|current| current.is_some())
+    }
+
+    // 取出后释放锁, 回调中会再次检查可见性
+    fn get() -> Option<Arc<dyn InterestManagement>> {
+        INTEREST_MANAGEMENT
+            .read()
+            .ok()
+            .and_then(|current| current.clone())
+    }
+
+    // 在 NetworkServer::is_visible_to 中调用, 没有设置时所有连接可见
+    pub(crate) fn check_observer(identity: &NetworkIdentity, connection_id: u64) -> bool {
+        match Self::get() {
+            Some(interest_management) => {
+                interest_management.on_check_observer(identity, connection_id)
+            }
+            None => true,
+        }
+    }
+
+    // 在下一个 tick 重建 net_id, 例如对象进入潜行状态
+    pub fn request_rebuild(net_id: u32) {
+        if let Ok(mut queue) = QUEUE.write() {
+            if !queue.contains(&net_id) {
+                queue.push_front(net_id);
+            }
+        }
+    }
+
+    // 立即重建所有对象, 不分摊
+    pub fn rebuild_all_now() {
+        let interest_management = match Self::get() {
+            Some(interest_management) => interest_management,
+            None => return,
+        };
+        Self::clear_queue();
+        for net_id in interest_management.rebuild_all() {
+            Self::rebuild(&*interest_management, net_id);
+        }
+    }
+
+    // 等待重建的对象数
+    pub fn pending() -> usize {
+        QUEUE.read().map(|queue| queue.len()).unwrap_or(0)
+    }
+
+    // 在 NetworkServer::network_late_update 中调用, 过载时与 InterestRadius 一起暂停
+    pub fn update() {
+        let interest_management = match Self::get() {
+            Some(interest_management) => interest_management,
+            None => return,
+        };
+        let local_time = NetworkTime::local_time();
+        let interval = interest_management.interval();
+        if Self::pending() == 0
+            && local_time - LAST_REBUILD_TIME.load(Ordering::Relaxed) >= interval
+        {
+            LAST_REBUILD_TIME.store(local_time, Ordering::Relaxed);
+            Self::start_round(&*interest_management, interval);
+        }
+
+        let net_ids: Vec<u32> = match QUEUE.write() {
+            Ok(mut queue) => {
+                let count = BUDGET.load(Ordering::Relaxed).min(queue.len());
+                queue.drain(..count).collect()
+            }
+            Err(e) => {
+                log_error!("InterestManagement failed to write QUEUE: {:?}", e);
+                return;
+            }
+        };
+        for net_id in net_ids {
+            Self::rebuild(&*interest_management, net_id);
+        }
+    }
+
+    fn start_round(interest_management: &dyn InterestManagement, interval: f64) {
+        let net_ids = interest_management.rebuild_all();
+        let budget = match interest_management.rebuilds_per_tick() {
+            0 => {
+                let ticks = (interval * NetworkServerStatic::tick_rate() as f64).floor() as usize;
+                net_ids.len().div_ceil(ticks.max(1))
+            }
+            budget => budget,
+        };
+        BUDGET.store(budget.max(1), Ordering::Relaxed);
+        if let Ok(mut queue) = QUEUE.write() {
+            // 保留 request_rebuild 加入的对象
+            for net_id in net_ids {
+                if !queue.contains(&net_id) {
+                    queue.push_back(net_id);
+                }
+            }
+        }
+    }
+
+    fn rebuild(interest_management: &dyn InterestManagement, net_id: u32) {
+        match NetworkServerStatic::spawned_network_identities().try_get_mut(&net_id) {
+            TryResult::Present(mut identity) => {
+                interest_management.rebuild_for(&mut identity);
+            }
+            // 已经销毁
+            TryResult::Absent => {}
+            TryResult::Locked => {
+                log_error!("InterestManagement: netId {} is locked.", net_id);
+            }
+        }
+    }
+
+    fn clear_queue() {
+        if let Ok(mut queue) = QUEUE.write() {
+            queue.clear();
+        }
+        LAST_REBUILD_TIME.store(0.0, Ordering::Relaxed);
+    }
+
+    // 设置保留, 等待重建的对象清空
+    pub fn reset() {
+        Self::clear_queue();
+        BUDGET.store(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::transports::memory::memory_transport::test_util::*;
+    use crate::mirror::transports::memory::memory_transport::MemoryTransport;
+    use std::sync::Mutex;
+
+    // 战争迷雾: hidden 中的 (连接, 对象) 不可见, 每个 tick 重建一个对象
+    struct FogOfWar {
+        hidden: Arc<Mutex<Vec<(u64, u32)>>>,
+    }
+
+    impl InterestManagement for FogOfWar {
+        fn on_check_observer(&self, identity: &NetworkIdentity, connection_id: u64) -> bool {
+            !self
+                .hidden
+                .lock()
+                .unwrap()
+                .contains(&(connection_id, identity.net_id()))
+        }
+
+        fn interval(&self) -> f64 {
+            0.0
+        }
+
+        fn rebuilds_per_tick(&self) -> usize {
+            1
+        }
+    }
+
+    #[test]
+    fn test_interest_management() {
+        with_server(|| {
+            for conn_id in [1, 2] {
+                MemoryTransport::client_connect(conn_id);
+            }
+            tick();
+            for conn_id in [1, 2] {
+                NetworkServer::set_client_ready(conn_id);
+            }
+            let hidden = Arc::new(Mutex::new(Vec::new()));
+            InterestManagementStatic::set(Arc::new(FogOfWar {
+                hidden: hidden.clone(),
+            }));
+            for net_id in [975, 976, 977] {
+                let mut identity = NetworkIdentity::new_with_asset_id(0);
+                identity.set_net_id(net_id);
+                NetworkServerStatic::add_spawned_network_identity(identity);
+                NetworkServer::set_visibility(net_id, Visibility::Default);
+            }
+            let observing = |conn_id: u64| {
+                let mut observing = NetworkServerStatic::network_connections()
+                    .get(&conn_id)
+                    .unwrap()
+                    .observing
+                    .clone();
+                observing.sort();
+                observing
+            };
+            assert_eq!(observing(2), vec![975, 976, 977]);
+
+            // 一轮的 3 个对象分 3 个 tick 重建, 977 在最后
+            hidden.lock().unwrap().push((2, 977));
+            tick();
+            assert_eq!(InterestManagementStatic::pending(), 2);
+            assert_eq!(observing(2), vec![975, 976, 977]);
+            tick();
+            tick();
+            assert_eq!(InterestManagementStatic::pending(), 0);
+            assert_eq!(observing(1), vec![975, 976, 977]);
+            assert_eq!(observing(2), vec![975, 976]);
+
+            // request_rebuild 在下一个 tick 重建
+            hidden.lock().unwrap().clear();
+            InterestManagementStatic::request_rebuild(977);
+            tick();
+            assert_eq!(observing(2), vec![975, 976, 977]);
+
+            // 设置为 ForceShown 的对象不受影响
+            hidden.lock().unwrap().push((1, 975));
+            NetworkServer::set_visibility(975, Visibility::ForceShown);
+            InterestManagementStatic::rebuild_all_now();
+            assert_eq!(observing(1), vec![975, 976, 977]);
+
+            InterestManagementStatic::clear();
+            assert!(!InterestManagementStatic::exists());
+            InterestManagementStatic::reset();
+            for net_id in [975, 976, 977] {
+                NetworkServerStatic::remove_spawned_network_identity(&net_id);
+            }
+        });
+    }
+}
@@ -0,0 +1,399 @@
+use crate::log_error;
+use crate::mirror::core::connection_quality::{ConnectionQuality, ConnectionQualityHeuristics};
+use crate::mirror::core::network_connection::NetworkConnectionTrait;
+use crate::mirror::core::network_context::ContextLocal;
+use crate::mirror::core::network_identity::{NetworkIdentity, Visibility};
+use crate::mirror::core::network_server::{NetworkServer, NetworkServerStatic};
+use crate::mirror::core::network_time::NetworkTime;
+use atomic::Atomic;
+use dashmap::try_result::TryResult;
+use dashmap::DashMap;
+use lazy_static::lazy_static;
+use nalgebra::Vector3;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+
+// 根据连接质量和发送带宽调整连接的兴趣半径倍数, 可以替换为自定义策略
+pub trait InterestPolicy: Send + Sync {
+    // current 是当前的倍数, 返回 (0, 1] 之间的新倍数
+    fn radius_multiplier(
+        &self,
+        connection_id: u64,
+        quality: ConnectionQuality,
+        bytes_per_second: f64,
+        current: f32,
+    ) -> f32;
+}
+
+// 默认策略: 连接质量差或者超过带宽目标时每次缩小 step, 恢复后每次放大 step
+#[derive(Debug, Clone)]
+pub struct BandwidthInterestPolicy {
+    // 每个连接的发送带宽目标, 单位字节每秒, 0 表示不限制
+    pub target_bytes_per_second: f64,
+    pub min_multiplier: f32,
+    pub step: f32,
+}
+
+impl Default for BandwidthInterestPolicy {
+    fn default() -> Self {
+        Self {
+            target_bytes_per_second: 0.0,
+            min_multiplier: 0.25,
+            step: 0.25,
+        }
+    }
+}
+
+impl InterestPolicy for BandwidthInterestPolicy {
+    fn radius_multiplier(
+        &self,
+        _connection_id: u64,
+        quality: ConnectionQuality,
+        bytes_per_second: f64,
+        current: f32,
+    ) -> f32 {
+        let target = self.target_bytes_per_second;
+        if quality == ConnectionQuality::POOR || (target > 0.0 && bytes_per_second > target) {
+            return (current - self.step).max(self.min_multiplier);
+        }
+        // 留一些余量再放大, 避免在目标附近来回变化
+        if quality >= ConnectionQuality::FAIR && (target <= 0.0 || bytes_per_second < target * 0.8)
+        {
+            return (current + self.step).min(1.0);
+        }
+        current
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ConnectionInterest {
+    multiplier: f32,
+    quality: ConnectionQuality,
+    last_bytes_sent: u64,
+    bytes_per_second: f64,
+    // 玩家对象的位置, 没有玩家对象时为 None
+    position: Option<Vector3<f32>>,
+}
+
+// InterestRadius 静态变量
+lazy_static! {
+    // 0 表示不按距离裁剪
+    static ref RADIUS: ContextLocal<Atomic<f32>> = ContextLocal::new(|| Atomic::new(0.0));
+    // 单位秒
+    static ref REBUILD_INTERVAL: ContextLocal<Atomic<f64>> = ContextLocal::new(|| Atomic::new(0.5));
+    static ref LAST_REBUILD_TIME: ContextLocal<Atomic<f64>> =
+        ContextLocal::new(|| Atomic::new(0.0));
+    static ref ADAPTIVE: ContextLocal<AtomicBool> = ContextLocal::new(|| AtomicBool::new(true));
+    static ref POLICY: ContextLocal<RwLock<Box<dyn InterestPolicy>>> =
+        ContextLocal::new(|| RwLock::new(Box::new(BandwidthInterestPolicy::default())));
+    static ref CONNECTIONS: ContextLocal<DashMap<u64, ConnectionInterest>> =
+        ContextLocal::new(DashMap::new);
+}
+
+// 按距离的兴趣管理: Visibility::Default 的对象只对玩家对象在半径内的连接可见
+// 每个连接的半径 = radius * 倍数, 倍数按 rebuild_interval 由 InterestPolicy 根据连接质量和发送带宽调整,
+// 质量差的连接看到更少的对象, 带宽随之下降
+pub struct InterestRadius;
+
+impl InterestRadius {
+    pub fn radius() -> f32 {
+        RADIUS.load(Ordering::Relaxed)
+    }
+
+    pub fn set_radius(value: f32) {
+        RADIUS.store(value, Ordering::Relaxed);
+    }
+
+    pub fn enabled() -> bool {
+        Self::radius() > 0.0
+    }
+
+    pub fn rebuild_interval() -> f64 {
+        REBUILD_INTERVAL.load(Ordering::Relaxed)
+    }
+
+    pub fn set_rebuild_interval(value: f64) {
+        REBUILD_INTERVAL.store(value, Ordering::Relaxed);
+    }
+
+    pub fn adaptive() -> bool {
+        ADAPTIVE.load(Ordering::Relaxed)
+    }
+
+    // 关闭时所有连接使用完整的半径
+    pub fn set_adaptive(value: bool) {
+        ADAPTIVE.store(value, Ordering::Relaxed);
+        if !value {
+            for mut interest in CONNECTIONS.iter_mut() {
+                interest.multiplier = 1.0;
+            }
+        }
+    }
+
+    pub fn set_policy<P: InterestPolicy + 'static>(policy: P) {
+        match POLICY.write() {
+            Ok(mut value) => *value = Box::new(policy),
+            Err(e) => {
+                log_error!("InterestRadius failed to write POLICY: {:?}", e);
+            }
+        }
+    }
+
+    pub fn multiplier(connection_id: u64) -> f32 {
+        CONNECTIONS
+            .get(&connection_id)
+            .map(|interest| interest.multiplier)
+            .unwrap_or(1.0)
+    }
+
+    pub fn radius_for(connection_id: u64) -> f32 {
+        Self::radius() * Self::multiplier(connection_id)
+    }
+
+    pub fn quality(connection_id: u64) -> Option<ConnectionQuality> {
+        CONNECTIONS
+            .get(&connection_id)
+            .map(|interest| interest.quality)
+    }
+
+    pub fn bytes_per_second(connection_id: u64) -> f64 {
+        CONNECTIONS
+            .get(&connection_id)
+            .map(|interest| interest.bytes_per_second)
+            .unwrap_or(0.0)
+    }
+
+    // 在 NetworkServer::is_visible_to 中调用, 还没有玩家对象的连接看到所有对象
+    pub(crate) fn in_range(identity: &NetworkIdentity, connection_id: u64) -> bool {
+        if !Self::enabled() {
+            return true;
+        }
+        let (position, multiplier) = match CONNECTIONS.get(&connection_id) {
+            Some(interest) => match interest.position {
+                Some(position) => (position, interest.multiplier),
+                None => return true,
+            },
+            None => return true,
+        };
+        let radius = Self::radius() * multiplier;
+        (identity.game_object().transform.position - position).norm_squared() <= radius * radius
+    }
+
+    // 在 NetworkServer::network_late_update 中调用
+    pub fn update() {
+        if !Self::enabled() {
+            return;
+        }
+        let local_time = NetworkTime::local_time();
+        let elapsed = local_time - LAST_REBUILD_TIME.load(Ordering::Relaxed);
+        if elapsed < Self::rebuild_interval() {
+            return;
+        }
+        LAST_REBUILD_TIME.store(local_time, Ordering::Relaxed);
+
+        Self::evaluate_connections(elapsed);
+
+        // 重建所有按距离可见的对象的观察者
+        let spawned = NetworkServerStatic::spawned_network_identities();
+        let net_ids: Vec<u32> = NetworkServerStatic::spawned_network_ids()
+            .iter()
+            .map(|net_id| *net_id)
+            .collect();
+        for net_id in net_ids {
+            match spawned.try_get_mut(&net_id) {
+                TryResult::Present(mut identity) => {
+                    if identity.visibility == Visibility::Default {
+                        NetworkServer::rebuild_observers(&mut identity, true);
+                    }
+                }
+                TryResult::Absent => {}
+                TryResult::Locked => {
+                    log_error!("InterestRadius: netId {} is locked.", net_id);
+                }
+            }
+        }
+    }
+
+    fn evaluate_connections(elapsed: f64) {
+        let mut samples = Vec::new();
+        NetworkServerStatic::for_each_network_connection(|connection| {
+            let quality = if connection._rtt.value <= 0.0 {
+                ConnectionQuality::ESTIMATING
+            } else {
+                ConnectionQualityHeuristics::simple(connection._rtt.value, connection._rtt.variance)
+            };
+            samples.push((
+                connection.connection_id(),
+                connection.net_id(),
+                quality,
+                connection.bytes_sent(),
+            ));
+        });
+
+        let spawned = NetworkServerStatic::spawned_network_identities();
+        let adaptive = Self::adaptive();
+        for (connection_id, net_id, quality, bytes_sent) in samples {
+            let position = match spawned.try_get(&net_id) {
+                TryResult::Present(identity) => Some(identity.game_object().transform.position),
+                _ => None,
+            };
+            let mut interest = CONNECTIONS
+                .entry(connection_id)
+                .or_insert(ConnectionInterest {
+                    multiplier: 1.0,
+                    quality,
+                    last_bytes_sent: bytes_sent,
+                    bytes_per_second: 0.0,
+                    position,
+                });
+            if elapsed > 0.0 {
+                interest.bytes_per_second =
+                    bytes_sent.saturating_sub(interest.last_bytes_sent) as f64 / elapsed;
+            }
+            interest.last_bytes_sent = bytes_sent;
+            interest.quality = quality;
+            interest.position = position;
+            if adaptive {
+                if let Ok(policy) = POLICY.read() {
+                    let multiplier = policy.radius_multiplier(
+                        connection_id,
+                        quality,
+                        interest.bytes_per_second,
+                        interest.multiplier,
+                    );
+                    interest.multiplier = multiplier.clamp(f32::EPSILON, 1.0);
+                }
+            }
+        }
+    }
+
+    // 在 NetworkServer::on_transport_disconnected 中调用
+    pub(crate) fn on_disconnected(connection_id: u64) {
+        CONNECTIONS.remove(&connection_id);
+    }
+
+    pub fn reset() {
+        CONNECTIONS.clear();
+        LAST_REBUILD_TIME.store(0.0, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::core::network_behaviour::GameObject;
+    use crate::mirror::transports::memory::memory_transport::test_util::*;
+    use crate::mirror::transports::memory::memory_transport::MemoryTransport;
+
+    // 连接 2 的半径减半
+    struct HalveSecondConnection;
+
+    impl InterestPolicy for HalveSecondConnection {
+        fn radius_multiplier(
+            &self,
+            connection_id: u64,
+            _: ConnectionQuality,
+            _: f64,
+            _: f32,
+        ) -> f32 {
+            if connection_id == 2 {
+                0.5
+            } else {
+                1.0
+            }
+        }
+    }
+
+    #[test]
+    fn test_interest_radius() {
+        with_server(|| {
+            for conn_id in [1, 2] {
+                MemoryTransport::client_connect(conn_id);
+            }
+            tick();
+            for conn_id in [1, 2] {
+                NetworkServer::set_client_ready(conn_id);
+            }
+            InterestRadius::set_radius(10.0);
+            InterestRadius::set_rebuild_interval(0.0);
+            // 970 / 971 是连接 1 / 2 的玩家对象
+            for (net_id, owner, x) in [(970, 1, 0.0), (971, 2, 8.0), (972, 0, 30.0)] {
+                let mut game_object = GameObject::default();
+                game_object.transform.position = Vector3::new(x, 0.0, 0.0);
+                let mut identity = NetworkIdentity::new_with_asset_id(0);
+                identity.set_net_id(net_id);
+                identity.set_connection_to_client(owner);
+                identity.set_game_object(game_object);
+                NetworkServerStatic::add_spawned_network_identity(identity);
+                NetworkServer::set_visibility(net_id, Visibility::Default);
+                if owner != 0 {
+                    NetworkServerStatic::network_connections()
+                        .get_mut(&owner)
+                        .unwrap()
+                        .set_net_id(net_id);
+                }
+            }
+            let observing = |conn_id: u64| {
+                let mut observing = NetworkServerStatic::network_connections()
+                    .get(&conn_id)
+                    .unwrap()
+                    .observing
+                    .clone();
+                observing.sort();
+                observing
+            };
+
+            tick();
+            assert_eq!(observing(1), vec![970, 971]);
+            assert_eq!(observing(2), vec![970, 971]);
+            assert_eq!(InterestRadius::radius_for(2), 10.0);
+
+            // 连接 2 的半径缩小, 看不到 8 米外的对象, 自己的玩家对象始终可见
+            InterestRadius::set_policy(HalveSecondConnection);
+            tick();
+            assert_eq!(InterestRadius::radius_for(2), 5.0);
+            assert_eq!(observing(1), vec![970, 971]);
+            assert_eq!(observing(2), vec![971]);
+
+            // 恢复
+            InterestRadius::set_adaptive(false);
+            tick();
+            assert_eq!(observing(2), vec![970, 971]);
+
+            // 默认策略: 质量差或者超过带宽目标时缩小, 低于目标的 80% 时放大
+            let policy = BandwidthInterestPolicy {
+                target_bytes_per_second: 1000.0,
+                ..BandwidthInterestPolicy::default()
+            };
+            assert_eq!(
+                policy.radius_multiplier(1, ConnectionQuality::GOOD, 2000.0, 1.0),
+                0.75
+            );
+            assert_eq!(
+                policy.radius_multiplier(1, ConnectionQuality::POOR, 0.0, 0.25),
+                0.25
+            );
+            assert_eq!(
+                policy.radius_multiplier(1, ConnectionQuality::GOOD, 500.0, 0.5),
+                0.75
+            );
+            assert_eq!(
+                policy.radius_multiplier(1, ConnectionQuality::FAIR, 900.0, 0.5),
+                0.5
+            );
+            assert_eq!(
+                policy.radius_multiplier(1, ConnectionQuality::GOOD, 0.0, 1.0),
+                1.0
+            );
+
+            InterestRadius::set_radius(0.0);
+            InterestRadius::set_adaptive(true);
+            InterestRadius::set_policy(BandwidthInterestPolicy::default());
+            InterestRadius::set_rebuild_interval(0.5);
+            for net_id in [970, 971, 972] {
+                NetworkServerStatic::remove_spawned_network_identity(&net_id);
+            }
+            InterestRadius::reset();
+        });
+    }
+}
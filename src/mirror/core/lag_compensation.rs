@@ -0,0 +1,98 @@
+use crate::mirror::core::network_context::ContextLocal;
+use crate::mirror::core::network_server::NetworkServerStatic;
+use crate::mirror::core::network_time::NetworkTime;
+use atomic::Atomic;
+use dashmap::try_result::TryResult;
+use dashmap::DashMap;
+use lazy_static::lazy_static;
+use nalgebra::Vector3;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// (local_time, position), 按时间顺序
+type PositionHistory = VecDeque<(f64, Vector3<f32>)>;
+
+// LagCompensation 静态变量
+lazy_static! {
+    static ref ENABLED: ContextLocal<AtomicBool> = ContextLocal::new(|| AtomicBool::new(false));
+    // 保留多长时间的历史, 单位秒
+    static ref HISTORY_DURATION: ContextLocal<Atomic<f64>> = ContextLocal::new(|| Atomic::new(1.0));
+    // net_id -> (local_time, position), 按时间顺序
+    static ref HISTORY: ContextLocal<DashMap<u32, PositionHistory>> =
+        ContextLocal::new(DashMap::new);
+}
+
+// 延迟补偿: 每个 tick 记录已生成对象的位置, 用于把目标回退到客户端看到的时刻
+pub struct LagCompensation;
+
+impl LagCompensation {
+    pub fn enabled() -> bool {
+        ENABLED.load(Ordering::Relaxed)
+    }
+
+    pub fn set_enabled(value: bool) {
+        ENABLED.store(value, Ordering::Relaxed);
+        if !value {
+            HISTORY.clear();
+        }
+    }
+
+    pub fn history_duration() -> f64 {
+        HISTORY_DURATION.load(Ordering::Relaxed)
+    }
+
+    pub fn set_history_duration(value: f64) {
+        HISTORY_DURATION.store(value, Ordering::Relaxed);
+    }
+
+    // 在 NetworkServer::network_late_update 末尾调用
+    pub fn record() {
+        if !Self::enabled() {
+            return;
+        }
+        let local_time = NetworkTime::local_time();
+        let oldest = local_time - Self::history_duration();
+        let spawned = NetworkServerStatic::spawned_network_identities();
+        for net_id in NetworkServerStatic::spawned_network_ids().iter() {
+            let position = match spawned.try_get(&net_id) {
+                TryResult::Present(identity) => identity.game_object().transform.position,
+                _ => continue,
+            };
+            let mut history = HISTORY.entry(*net_id).or_default();
+            history.push_back((local_time, position));
+            // 至少保留一个比 oldest 更早的样本, 保证 oldest 时刻可以插值
+            while history.len() > 2 && history[1].0 <= oldest {
+                history.pop_front();
+            }
+        }
+        HISTORY.retain(|net_id, _| NetworkServerStatic::spawned_network_ids().contains(net_id));
+    }
+
+    // time 时刻的位置, 在相邻的两个样本之间线性插值, 超出历史范围时取最近的样本
+    pub fn position_at(net_id: u32, time: f64) -> Option<Vector3<f32>> {
+        let history = HISTORY.get(&net_id)?;
+        let (first, last) = (history.front()?, history.back()?);
+        if time <= first.0 {
+            return Some(first.1);
+        }
+        if time >= last.0 {
+            return Some(last.1);
+        }
+        let index = history.partition_point(|(sample_time, _)| *sample_time <= time);
+        let (from, to) = (history[index - 1], history[index]);
+        let t = ((time - from.0) / (to.0 - from.0)) as f32;
+        Some(from.1.lerp(&to.1, t))
+    }
+
+    pub fn history_len(net_id: u32) -> usize {
+        HISTORY
+            .get(&net_id)
+            .map(|history| history.len())
+            .unwrap_or(0)
+    }
+
+    pub fn reset() {
+        ENABLED.store(false, Ordering::Relaxed);
+        HISTORY.clear();
+    }
+}
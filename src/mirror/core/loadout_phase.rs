@@ -0,0 +1,387 @@
+use crate::mirror::core::messages::{
+    AddPlayerMessage, LoadoutMessage, LoadoutOptionsMessage, NetworkMessageTrait,
+};
+use crate::mirror::core::network_connection::NetworkConnectionTrait;
+use crate::mirror::core::network_connection_to_client::NetworkConnectionToClient;
+use crate::mirror::core::network_context::ContextLocal;
+use crate::mirror::core::network_reader::NetworkReader;
+use crate::mirror::core::network_server::{NetworkServer, NetworkServerStatic};
+use crate::mirror::core::network_time::NetworkTime;
+use crate::mirror::core::transport::TransportChannel;
+use crate::{log_error, log_warn};
+use atomic::Atomic;
+use dashmap::try_result::TryResult;
+use dashmap::DashMap;
+use lazy_static::lazy_static;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{RwLock, RwLockWriteGuard};
+
+// 返回 false 时拒绝这个选择, 例如玩家没有解锁
+pub type LoadoutValidator = fn(u64, &str) -> bool;
+
+// 连接选择的结果, 保存在连接的 ext 中
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadoutSelection {
+    pub selection: String,
+    // 超时后使用的默认选项
+    pub defaulted: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PendingLoadout {
+    deadline: f64,
+    // 选择之前收到了 AddPlayerMessage
+    add_player: bool,
+}
+
+// LoadoutPhase 静态变量
+lazy_static! {
+    static ref ENABLED: ContextLocal<AtomicBool> = ContextLocal::new(|| AtomicBool::new(false));
+    static ref OPTIONS: ContextLocal<RwLock<Vec<String>>> =
+        ContextLocal::new(|| RwLock::new(Vec::new()));
+    // 为空时使用第一个选项
+    static ref DEFAULT_OPTION: ContextLocal<RwLock<String>> =
+        ContextLocal::new(|| RwLock::new(String::new()));
+    // 单位秒
+    static ref TIMEOUT: ContextLocal<Atomic<f64>> = ContextLocal::new(|| Atomic::new(30.0));
+    static ref VALIDATOR: ContextLocal<RwLock<Option<LoadoutValidator>>> =
+        ContextLocal::new(|| RwLock::new(None));
+    static ref PENDING: ContextLocal<DashMap<u64, PendingLoadout>> =
+        ContextLocal::new(DashMap::new);
+    // 已经选择完成, 等待重新处理 AddPlayerMessage 的连接
+    static ref DEFERRED_ADD_PLAYERS: ContextLocal<RwLock<Vec<u64>>> =
+        ContextLocal::new(|| RwLock::new(Vec::new()));
+}
+
+// 角色 / 装备选择阶段: 认证之后服务器发送 LoadoutOptionsMessage, 客户端回复 LoadoutMessage 之前
+// AddPlayerMessage 被推迟, 选择完成后在 NetworkServer::network_late_update 中重新处理
+// 超时后使用默认选项
+pub struct LoadoutPhase;
+
+impl LoadoutPhase {
+    pub fn enabled() -> bool {
+        ENABLED.load(Ordering::Relaxed)
+    }
+
+    pub fn set_enabled(value: bool) {
+        ENABLED.store(value, Ordering::Relaxed);
+    }
+
+    pub fn options() -> Vec<String> {
+        OPTIONS.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub fn set_options(value: Vec<String>) {
+        *OPTIONS.write().unwrap_or_else(|e| e.into_inner()) = value;
+    }
+
+    pub fn default_option() -> String {
+        let default_option = DEFAULT_OPTION
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        if !default_option.is_empty() {
+            return default_option;
+        }
+        Self::options().into_iter().next().unwrap_or_default()
+    }
+
+    pub fn set_default_option(value: String) {
+        *DEFAULT_OPTION.write().unwrap_or_else(|e| e.into_inner()) = value;
+    }
+
+    pub fn timeout() -> f64 {
+        TIMEOUT.load(Ordering::Relaxed)
+    }
+
+    pub fn set_timeout(value: f64) {
+        TIMEOUT.store(value, Ordering::Relaxed);
+    }
+
+    pub fn set_validator(validator: Option<LoadoutValidator>) {
+        *VALIDATOR.write().unwrap_or_else(|e| e.into_inner()) = validator;
+    }
+
+    pub fn is_pending(connection_id: u64) -> bool {
+        PENDING.contains_key(&connection_id)
+    }
+
+    pub fn selection(connection_id: u64) -> Option<LoadoutSelection> {
+        match NetworkServerStatic::network_connections().try_get(&connection_id) {
+            TryResult::Present(connection) => connection.get_ext::<LoadoutSelection>().cloned(),
+            _ => None,
+        }
+    }
+
+    // 在 NetworkManager::on_server_authenticated 中调用, 自定义认证流程认证完成后也需要调用
+    pub fn on_authenticated(connection: &mut NetworkConnectionToClient) {
+        if !Self::enabled() {
+            return;
+        }
+        let deadline = NetworkTime::local_time() + Self::timeout();
+        PENDING.insert(
+            connection.connection_id(),
+            PendingLoadout {
+                deadline,
+                add_player: false,
+            },
+        );
+        let mut message = LoadoutOptionsMessage::new(
+            Self::options(),
+            Self::default_option(),
+            Self::timeout() as f32,
+        );
+        connection.send_network_message(&mut message, TransportChannel::Reliable);
+    }
+
+    // 在 NetworkServer::unpack_and_invoke 中调用, 返回 true 时 AddPlayerMessage 等选择完成后再处理
+    pub(crate) fn defer_add_player(connection_id: u64) -> bool {
+        match PENDING.get_mut(&connection_id) {
+            Some(mut pending) => {
+                pending.add_player = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn is_valid(connection_id: u64, selection: &str) -> bool {
+        if !Self::options().iter().any(|option| option == selection) {
+            return false;
+        }
+        // 调用验证函数时不持有锁
+        let validator = *VALIDATOR.read().unwrap_or_else(|e| e.into_inner());
+        match validator {
+            Some(validator) => validator(connection_id, selection),
+            None => true,
+        }
+    }
+
+    // 处理 LoadoutMessage 消息
+    pub(crate) fn on_loadout_message(
+        connection_id: u64,
+        reader: &mut NetworkReader,
+        _channel: TransportChannel,
+    ) {
+        let message = LoadoutMessage::deserialize(reader);
+        let deadline = match PENDING.get(&connection_id) {
+            Some(pending) => pending.deadline,
+            // 已经选择过或者超时
+            None => return,
+        };
+        if Self::is_valid(connection_id, &message.selection) {
+            Self::complete(connection_id, message.selection, false);
+            return;
+        }
+
+        log_warn!(
+            "LoadoutPhase: connectionId: {} invalid selection: {}",
+            connection_id,
+            message.selection
+        );
+        // 重新发送选项, 超时时间不重置
+        let remaining = (deadline - NetworkTime::local_time()).max(0.0);
+        match NetworkServerStatic::network_connections().try_get_mut(&connection_id) {
+            TryResult::Present(mut connection) => {
+                let mut message = LoadoutOptionsMessage::new(
+                    Self::options(),
+                    Self::default_option(),
+                    remaining as f32,
+                );
+                connection.send_network_message(&mut message, TransportChannel::Reliable);
+            }
+            TryResult::Absent => {
+                log_error!("LoadoutPhase: connectionId: {} not found.", connection_id);
+            }
+            TryResult::Locked => {
+                log_error!("LoadoutPhase: connectionId: {} is locked.", connection_id);
+            }
+        }
+    }
+
+    fn complete(connection_id: u64, selection: String, defaulted: bool) {
+        let pending = match PENDING.remove(&connection_id) {
+            Some((_, pending)) => pending,
+            None => return,
+        };
+        match NetworkServerStatic::network_connections().try_get_mut(&connection_id) {
+            TryResult::Present(mut connection) => {
+                connection.insert_ext(LoadoutSelection {
+                    selection,
+                    defaulted,
+                });
+            }
+            TryResult::Absent => return,
+            TryResult::Locked => {
+                log_error!("LoadoutPhase: connectionId: {} is locked.", connection_id);
+                return;
+            }
+        }
+        if pending.add_player {
+            Self::deferred_add_players().push(connection_id);
+        }
+    }
+
+    // 在 NetworkServer::network_late_update 中调用
+    pub fn update() {
+        if !PENDING.is_empty() {
+            let local_time = NetworkTime::local_time();
+            let expired: Vec<u64> = PENDING
+                .iter()
+                .filter(|pending| pending.deadline <= local_time)
+                .map(|pending| *pending.key())
+                .collect();
+            for connection_id in expired {
+                Self::complete(connection_id, Self::default_option(), true);
+            }
+        }
+
+        let deferred = std::mem::take(&mut *Self::deferred_add_players());
+        for connection_id in deferred {
+            if NetworkServerStatic::network_connections().contains_key(&connection_id) {
+                NetworkServer::invoke_handler::<AddPlayerMessage>(connection_id, &[]);
+            }
+        }
+    }
+
+    // 在 NetworkServer::on_transport_disconnected 中调用
+    pub(crate) fn on_disconnected(connection_id: u64) {
+        PENDING.remove(&connection_id);
+        Self::deferred_add_players().retain(|id| *id != connection_id);
+    }
+
+    pub fn reset() {
+        PENDING.clear();
+        Self::deferred_add_players().clear();
+    }
+
+    // 处理器 panic 时锁可能被毒化, 继续使用里面的数据
+    fn deferred_add_players() -> RwLockWriteGuard<'static, Vec<u64>> {
+        DEFERRED_ADD_PLAYERS
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::transports::memory::memory_transport::test_util::*;
+    use crate::mirror::transports::memory::memory_transport::MemoryTransport;
+    use std::sync::atomic::AtomicU32;
+
+    static ADD_PLAYERS: AtomicU32 = AtomicU32::new(0);
+
+    fn on_test_add_player(_: u64, _: &mut NetworkReader, _: TransportChannel) {
+        ADD_PLAYERS.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn no_heavy(_: u64, selection: &str) -> bool {
+        selection != "Heavy"
+    }
+
+    #[test]
+    fn test_loadout_phase() {
+        with_server(|| {
+            ADD_PLAYERS.store(0, Ordering::Relaxed);
+            NetworkServer::register_handler::<AddPlayerMessage>(on_test_add_player, true);
+            LoadoutPhase::set_enabled(true);
+            LoadoutPhase::set_options(vec![
+                "Light".to_string(),
+                "Medium".to_string(),
+                "Heavy".to_string(),
+            ]);
+            LoadoutPhase::set_default_option("Medium".to_string());
+            LoadoutPhase::set_validator(Some(no_heavy));
+            for conn_id in [1, 2] {
+                MemoryTransport::client_connect(conn_id);
+            }
+            tick();
+            let authenticate = |conn_id: u64| {
+                let mut connection = NetworkServerStatic::network_connections()
+                    .get_mut(&conn_id)
+                    .unwrap();
+                connection.set_authenticated(true);
+                LoadoutPhase::on_authenticated(&mut connection);
+            };
+            authenticate(1);
+            tick();
+            let options = received::<LoadoutOptionsMessage>(1);
+            assert_eq!(options.len(), 1);
+            assert_eq!(options[0].options, LoadoutPhase::options());
+            assert_eq!(options[0].default_option, "Medium");
+            assert!(LoadoutPhase::is_pending(1));
+
+            // 选择之前的 AddPlayerMessage 被推迟
+            MemoryTransport::client_send_message(
+                1,
+                &mut AddPlayerMessage,
+                TransportChannel::Reliable,
+            );
+            tick();
+            assert_eq!(ADD_PLAYERS.load(Ordering::Relaxed), 0);
+
+            // 不在选项中或者被拒绝的选择, 重新发送选项
+            for selection in ["Tank", "Heavy"] {
+                let mut message = LoadoutMessage::new(selection.to_string());
+                MemoryTransport::client_send_message(1, &mut message, TransportChannel::Reliable);
+                tick();
+                assert_eq!(received::<LoadoutOptionsMessage>(1).len(), 1);
+            }
+            assert!(LoadoutPhase::is_pending(1));
+            assert_eq!(ADD_PLAYERS.load(Ordering::Relaxed), 0);
+
+            let mut message = LoadoutMessage::new("Light".to_string());
+            MemoryTransport::client_send_message(1, &mut message, TransportChannel::Reliable);
+            tick();
+            assert!(!LoadoutPhase::is_pending(1));
+            assert_eq!(ADD_PLAYERS.load(Ordering::Relaxed), 1);
+            assert_eq!(
+                LoadoutPhase::selection(1),
+                Some(LoadoutSelection {
+                    selection: "Light".to_string(),
+                    defaulted: false,
+                })
+            );
+
+            // 选择完成后 AddPlayerMessage 直接处理, 再次选择被忽略
+            MemoryTransport::client_send_message(
+                1,
+                &mut AddPlayerMessage,
+                TransportChannel::Reliable,
+            );
+            let mut message = LoadoutMessage::new("Medium".to_string());
+            MemoryTransport::client_send_message(1, &mut message, TransportChannel::Reliable);
+            tick();
+            assert_eq!(ADD_PLAYERS.load(Ordering::Relaxed), 2);
+            assert_eq!(LoadoutPhase::selection(1).unwrap().selection, "Light");
+
+            // 超时使用默认选项
+            LoadoutPhase::set_timeout(0.0);
+            authenticate(2);
+            MemoryTransport::client_send_message(
+                2,
+                &mut AddPlayerMessage,
+                TransportChannel::Reliable,
+            );
+            tick();
+            assert!(!LoadoutPhase::is_pending(2));
+            assert_eq!(ADD_PLAYERS.load(Ordering::Relaxed), 3);
+            assert_eq!(
+                LoadoutPhase::selection(2),
+                Some(LoadoutSelection {
+                    selection: "Medium".to_string(),
+                    defaulted: true,
+                })
+            );
+
+            LoadoutPhase::set_enabled(false);
+            LoadoutPhase::set_options(Vec::new());
+            LoadoutPhase::set_default_option(String::new());
+            LoadoutPhase::set_validator(None);
+            LoadoutPhase::set_timeout(30.0);
+            NetworkServer::unregister_handler::<AddPlayerMessage>();
+            LoadoutPhase::reset();
+        });
+    }
+}
@@ -0,0 +1,443 @@
+use crate::mirror::core::backend_data::BackendDataStatic;
+use crate::mirror::core::network_context::{ContextLocal, NetworkContext};
+use crate::mirror::core::network_manager::NetworkManagerStatic;
+use crate::mirror::core::network_server::{NetworkServer, NetworkServerStatic};
+use crate::mirror::core::network_time::NetworkTime;
+use crate::mirror::core::transport::Transport;
+use crate::{log_error, log_info, log_warn};
+use atomic::Atomic;
+use crossbeam_channel::{bounded, unbounded, Receiver, RecvTimeoutError, Sender};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::atomic::Ordering;
+use std::sync::RwLock;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MasterServerConfig {
+    pub enabled: bool,
+    // 只支持 http://host[:port]/path
+    pub url: String,
+    // 心跳间隔 (秒)
+    pub interval: f64,
+    pub name: String,
+    // 对外公布的地址, 为空时使用 Transport 的监听地址
+    pub public_address: String,
+    // 为空时使用 NetworkServer::PROTOCOL_VERSION
+    pub version: String,
+}
+
+impl Default for MasterServerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            url: "".to_string(),
+            interval: 30.0,
+            name: "".to_string(),
+            public_address: "".to_string(),
+            version: "".to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MasterServerEvent {
+    Register,
+    Heartbeat,
+    Deregister,
+}
+
+// POST 给主服务器的内容
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MasterServerStatus {
+    pub event: MasterServerEvent,
+    pub name: String,
+    pub address: String,
+    pub players: usize,
+    pub max_players: usize,
+    pub map: String,
+    pub version: String,
+}
+
+// 后台上报线程, 按顺序发送队列中的状态
+struct Reporter {
+    sender: Sender<(String, MasterServerStatus)>,
+    // 线程退出时关闭, 用于带超时地等待线程结束
+    finished: Receiver<()>,
+    handle: JoinHandle<()>,
+}
+
+lazy_static! {
+    static ref CONFIG: ContextLocal<RwLock<Option<MasterServerConfig>>> =
+        ContextLocal::new(|| RwLock::new(None));
+    static ref LAST_REPORT_TIME: ContextLocal<Atomic<f64>> = ContextLocal::new(|| Atomic::new(0.0));
+    static ref REPORTER: ContextLocal<RwLock<Option<Reporter>>> =
+        ContextLocal::new(|| RwLock::new(None));
+}
+
+// 向主服务器注册 / 定期上报 / 注销, 供社区服务器列表使用
+// 所有请求由同一个后台线程发送, 不阻塞主循环; shutdown 时等待注销发送完成, 最多 STOP_TIMEOUT
+pub struct MasterServer;
+
+impl MasterServer {
+    const TIMEOUT: Duration = Duration::from_secs(3);
+    const STOP_TIMEOUT: Duration = Duration::from_secs(5);
+
+    // 在 NetworkServer::listen 中调用
+    pub fn start() {
+        let config = BackendDataStatic::get_backend_data().master_server_config;
+        if !config.enabled {
+            return;
+        }
+        if let Err(e) = Self::parse_url(&config.url) {
+            log_error!("MasterServer disabled: {}", e);
+            return;
+        }
+        Self::start_with_config(config);
+    }
+
+    fn start_with_config(config: MasterServerConfig) {
+        match REPORTER.write() {
+            Ok(mut reporter) => {
+                if reporter.is_none() {
+                    match Self::spawn_reporter() {
+                        Ok(spawned) => {
+                            reporter.replace(spawned);
+                        }
+                        Err(e) => {
+                            log_error!("MasterServer failed to start reporter thread: {}", e);
+                            return;
+                        }
+                    }
+                }
+            }
+            Err(e) => {
+                log_error!("MasterServer failed to write REPORTER: {:?}", e);
+                return;
+            }
+        }
+        match CONFIG.write() {
+            Ok(mut current) => {
+                current.replace(config);
+            }
+            Err(e) => {
+                log_error!("MasterServer failed to write CONFIG: {:?}", e);
+                return;
+            }
+        }
+        LAST_REPORT_TIME.store(NetworkTime::local_time(), Ordering::Relaxed);
+        Self::report(MasterServerEvent::Register);
+    }
+
+    fn spawn_reporter() -> std::io::Result<Reporter> {
+        let (sender, receiver) = unbounded::<(String, MasterServerStatus)>();
+        let (finished_sender, finished) = bounded::<()>(0);
+        let context = NetworkContext::current();
+        let handle = thread::Builder::new()
+            .name("master-server".to_string())
+            .spawn(move || {
+                NetworkContext::enter(context, || {
+                    let _finished = finished_sender;
+                    // 所有 Sender 被 drop 后发送完剩余的状态再退出
+                    for (url, status) in receiver.iter() {
+                        match Self::post(&url, &status) {
+                            Ok(_) => {
+                                if status.event == MasterServerEvent::Register {
+                                    log_info!("MasterServer registered at {}", url);
+                                }
+                            }
+                            Err(e) => {
+                                log_warn!(
+                                    "MasterServer failed to report {:?}: {}",
+                                    status.event,
+                                    e
+                                );
+                            }
+                        }
+                    }
+                })
+            })?;
+        Ok(Reporter {
+            sender,
+            finished,
+            handle,
+        })
+    }
+
+    // 在 NetworkServer::network_late_update 中调用
+    pub fn update() {
+        let interval = match CONFIG.read() {
+            Ok(config) => match config.as_ref() {
+                Some(config) => config.interval,
+                None => return,
+            },
+            Err(_) => return,
+        };
+        let local_time = NetworkTime::local_time();
+        if local_time - LAST_REPORT_TIME.load(Ordering::Relaxed) < interval {
+            return;
+        }
+        LAST_REPORT_TIME.store(local_time, Ordering::Relaxed);
+        Self::report(MasterServerEvent::Heartbeat);
+    }
+
+    // 在 NetworkServer::shutdown 中调用
+    pub fn stop() {
+        let config = match CONFIG.write() {
+            Ok(mut config) => config.take(),
+            Err(_) => None,
+        };
+        let reporter = match REPORTER.write() {
+            Ok(mut reporter) => reporter.take(),
+            Err(_) => None,
+        };
+        let Some(reporter) = reporter else {
+            return;
+        };
+        if let Some(config) = config {
+            let status = Self::status(&config, MasterServerEvent::Deregister);
+            if reporter.sender.send((config.url, status)).is_err() {
+                log_warn!("MasterServer failed to deregister: reporter thread stopped");
+            }
+        }
+        // 关闭队列, 线程发送完剩余的状态后退出
+        drop(reporter.sender);
+        match reporter.finished.recv_timeout(Self::STOP_TIMEOUT) {
+            Err(RecvTimeoutError::Timeout) => {
+                log_warn!(
+                    "MasterServer reporter did not finish within {:?}",
+                    Self::STOP_TIMEOUT
+                );
+            }
+            _ => {
+                let _ = reporter.handle.join();
+            }
+        }
+    }
+
+    pub fn status(config: &MasterServerConfig, event: MasterServerEvent) -> MasterServerStatus {
+        let address = if config.public_address.is_empty() {
+            Transport::active_transport()
+                .and_then(|transport| transport.server_local_endpoint())
+                .map(|endpoint| endpoint.to_string())
+                .unwrap_or_default()
+        } else {
+            config.public_address.clone()
+        };
+        let version = if config.version.is_empty() {
+            NetworkServer::PROTOCOL_VERSION.to_string()
+        } else {
+            config.version.clone()
+        };
+        MasterServerStatus {
+            event,
+            name: config.name.clone(),
+            address,
+            players: NetworkServerStatic::network_connections_size(),
+            max_players: NetworkServerStatic::max_connections(),
+            map: NetworkManagerStatic::network_scene_name(),
+            version,
+        }
+    }
+
+    fn report(event: MasterServerEvent) {
+        let config = match CONFIG.read() {
+            Ok(config) => match config.as_ref() {
+                Some(config) => config.clone(),
+                None => return,
+            },
+            Err(_) => return,
+        };
+        // 状态在主线程收集, 后台线程只负责发送
+        let status = Self::status(&config, event);
+        if let Ok(reporter) = REPORTER.read() {
+            if let Some(reporter) = reporter.as_ref() {
+                if reporter.sender.send((config.url, status)).is_err() {
+                    log_warn!(
+                        "MasterServer failed to report {:?}: reporter thread stopped",
+                        event
+                    );
+                }
+            }
+        }
+    }
+
+    // 返回 (host, port, path)
+    pub fn parse_url(url: &str) -> Result<(String, u16, String), String> {
+        let rest = match url.strip_prefix("http://") {
+            Some(rest) => rest,
+            None => return Err(format!("unsupported master server url: '{}'", url)),
+        };
+        let (authority, path) = match rest.find('/') {
+            Some(index) => (&rest[..index], &rest[index..]),
+            None => (rest, "/"),
+        };
+        let (host, port) = match authority.rfind(':') {
+            // IPv6 地址 [::1] 中的冒号不是端口分隔符
+            Some(index) if !authority[index..].contains(']') => {
+                let port = authority[index + 1..]
+                    .parse::<u16>()
+                    .map_err(|_| format!("invalid port in master server url: '{}'", url))?;
+                (&authority[..index], port)
+            }
+            _ => (authority, 80),
+        };
+        if host.is_empty() {
+            return Err(format!("missing host in master server url: '{}'", url));
+        }
+        Ok((host.to_string(), port, path.to_string()))
+    }
+
+    // 发送一个 HTTP/1.1 POST, 返回状态码, 非 2xx 视为失败
+    pub fn post(url: &str, status: &MasterServerStatus) -> Result<u16, String> {
+        let (host, port, path) = Self::parse_url(url)?;
+        let body = serde_json::to_string(status).map_err(|e| e.to_string())?;
+        let endpoint = (host.trim_start_matches('[').trim_end_matches(']'), port)
+            .to_socket_addrs()
+            .map_err(|e| e.to_string())?
+            .next()
+            .ok_or_else(|| format!("failed to resolve {}", host))?;
+
+        let mut stream =
+            TcpStream::connect_timeout(&endpoint, Self::TIMEOUT).map_err(|e| e.to_string())?;
+        stream
+            .set_read_timeout(Some(Self::TIMEOUT))
+            .map_err(|e| e.to_string())?;
+        let request = format!(
+            "POST {} HTTP/1.1\r\nHost: {}:{}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            path,
+            host,
+            port,
+            body.len(),
+            body
+        );
+        stream
+            .write_all(request.as_bytes())
+            .map_err(|e| e.to_string())?;
+
+        let mut response = String::new();
+        stream
+            .read_to_string(&mut response)
+            .map_err(|e| e.to_string())?;
+        let code = response
+            .split_whitespace()
+            .nth(1)
+            .and_then(|code| code.parse::<u16>().ok())
+            .ok_or_else(|| "invalid http response".to_string())?;
+        if !(200..300).contains(&code) {
+            return Err(format!("http status {}", code));
+        }
+        Ok(code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::transports::memory::memory_transport::test_util::with_isolated_context;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_parse_url() {
+        assert_eq!(
+            MasterServer::parse_url("http://example.com/servers"),
+            Ok(("example.com".to_string(), 80, "/servers".to_string()))
+        );
+        assert_eq!(
+            MasterServer::parse_url("http://[::1]:8080"),
+            Ok(("[::1]".to_string(), 8080, "/".to_string()))
+        );
+        assert!(MasterServer::parse_url("https://example.com").is_err());
+        assert!(MasterServer::parse_url("http://:80/").is_err());
+    }
+
+    // 依次接受 count 个请求并回复 204, 返回 url 和收到的请求
+    fn serve(count: usize) -> (String, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/servers", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let mut requests = Vec::new();
+            for _ in 0..count {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = Vec::new();
+                let mut buffer = [0u8; 1024];
+                // 读到 body 结束
+                while !request.ends_with(b"}") {
+                    let n = stream.read(&mut buffer).unwrap();
+                    if n == 0 {
+                        break;
+                    }
+                    request.extend_from_slice(&buffer[..n]);
+                }
+                stream
+                    .write_all(b"HTTP/1.1 204 No Content\r\nContent-Length: 0\r\n\r\n")
+                    .unwrap();
+                requests.push(String::from_utf8(request).unwrap());
+            }
+            requests
+        });
+        (url, server)
+    }
+
+    fn body(request: &str) -> MasterServerStatus {
+        serde_json::from_str(&request[request.find("\r\n\r\n").unwrap() + 4..]).unwrap()
+    }
+
+    #[test]
+    fn test_post() {
+        let (url, server) = serve(1);
+
+        let status = MasterServerStatus {
+            event: MasterServerEvent::Heartbeat,
+            name: "Rust".to_string(),
+            address: "10.0.0.1:7777".to_string(),
+            players: 3,
+            max_players: 16,
+            map: "Game".to_string(),
+            version: "1".to_string(),
+        };
+        assert_eq!(MasterServer::post(&url, &status), Ok(204));
+        let request = server.join().unwrap().remove(0);
+        assert!(request.starts_with("POST /servers HTTP/1.1\r\n"));
+        assert_eq!(body(&request), status);
+        assert!(request.contains("\"event\":\"heartbeat\""));
+    }
+
+    #[test]
+    fn test_reporter_thread() {
+        with_isolated_context(|| {
+            let (url, server) = serve(4);
+            MasterServer::start_with_config(MasterServerConfig {
+                enabled: true,
+                url,
+                interval: 0.0,
+                ..Default::default()
+            });
+            MasterServer::update();
+            MasterServer::update();
+
+            // stop 发送注销并等待上报线程退出
+            MasterServer::stop();
+            assert!(REPORTER.read().unwrap().is_none());
+            let events: Vec<MasterServerEvent> = server
+                .join()
+                .unwrap()
+                .iter()
+                .map(|request| body(request).event)
+                .collect();
+            assert_eq!(
+                events,
+                vec![
+                    MasterServerEvent::Register,
+                    MasterServerEvent::Heartbeat,
+                    MasterServerEvent::Heartbeat,
+                    MasterServerEvent::Deregister,
+                ]
+            );
+        });
+    }
+}
@@ -409,7 +409,7 @@ impl NetworkMessageTrait for SpawnMessage {
     }
 
     fn serialize(&mut self, writer: &mut NetworkWriter) {
-        // 12504
+        // 34916
         writer.write_ushort(Self::get_full_name().get_stable_hash_code16());
         writer.compress_var_uint(self.net_id);
         writer.write_bool(self.is_local_player);
@@ -548,7 +548,7 @@ impl NetworkMessageTrait for ObjectDestroyMessage {
     }
 
     fn serialize(&mut self, writer: &mut NetworkWriter) {
-        // 12504
+        // 44385
         writer.write_ushort(Self::get_full_name().get_stable_hash_code16());
         writer.compress_var_uint(self.net_id);
     }
@@ -582,7 +582,7 @@ impl NetworkMessageTrait for ObjectHideMessage {
     }
 
     fn serialize(&mut self, writer: &mut NetworkWriter) {
-        // 12504
+        // 32753
         writer.write_ushort(Self::get_full_name().get_stable_hash_code16());
         writer.compress_var_uint(self.net_id);
     }
@@ -623,7 +623,7 @@ impl NetworkMessageTrait for EntityStateMessage {
     }
 
     fn serialize(&mut self, writer: &mut NetworkWriter) {
-        // 12504
+        // 12339
         writer.write_ushort(Self::get_full_name().get_stable_hash_code16());
         writer.compress_var_uint(self.net_id);
         writer.write_array_segment_and_size(self.payload.as_slice());
@@ -736,3 +736,1556 @@ impl NetworkMessageTrait for NetworkPongMessage {
         self
     }
 }
+
+// 协议版本握手消息
+// 连接建立后 (认证之前) 服务器发送自己的版本信息, 客户端回复自己的版本信息
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct ProtocolVersionMessage {
+    pub version: u16,
+    pub features: u32,
+    pub tick_rate: u32,
+}
+impl ProtocolVersionMessage {
+    #[allow(dead_code)]
+    pub fn new(version: u16, features: u32, tick_rate: u32) -> ProtocolVersionMessage {
+        Self {
+            version,
+            features,
+            tick_rate,
+        }
+    }
+}
+impl NetworkMessageTrait for ProtocolVersionMessage {
+    fn deserialize(reader: &mut NetworkReader) -> Self {
+        let version = reader.read_ushort();
+        let features = reader.read_uint();
+        let tick_rate = reader.read_uint();
+        Self {
+            version,
+            features,
+            tick_rate,
+        }
+    }
+
+    fn serialize(&mut self, writer: &mut NetworkWriter) {
+        // 22167
+        writer.write_ushort(Self::get_full_name().get_stable_hash_code16());
+        writer.write_ushort(self.version);
+        writer.write_uint(self.features);
+        writer.write_uint(self.tick_rate);
+    }
+
+    fn get_full_name() -> &'static str
+    where
+        Self: Sized,
+    {
+        "Mirror.ProtocolVersionMessage"
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+// 协议版本不兼容时发送给客户端的拒绝消息
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct ProtocolRejectMessage {
+    pub server_version: u16,
+    pub reason: String,
+}
+impl ProtocolRejectMessage {
+    #[allow(dead_code)]
+    pub fn new(server_version: u16, reason: String) -> ProtocolRejectMessage {
+        Self {
+            server_version,
+            reason,
+        }
+    }
+}
+impl NetworkMessageTrait for ProtocolRejectMessage {
+    fn deserialize(reader: &mut NetworkReader) -> Self {
+        let server_version = reader.read_ushort();
+        let reason = reader.read_string();
+        Self {
+            server_version,
+            reason,
+        }
+    }
+
+    fn serialize(&mut self, writer: &mut NetworkWriter) {
+        // 19070
+        writer.write_ushort(Self::get_full_name().get_stable_hash_code16());
+        writer.write_ushort(self.server_version);
+        writer.write_str(self.reason.as_str());
+    }
+
+    fn get_full_name() -> &'static str
+    where
+        Self: Sized,
+    {
+        "Mirror.ProtocolRejectMessage"
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+// 服务器无法处理客户端消息时的回复
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct ErrorMessage {
+    pub message_id: u16,
+    pub reason: String,
+}
+impl ErrorMessage {
+    #[allow(dead_code)]
+    pub fn new(message_id: u16, reason: String) -> ErrorMessage {
+        Self { message_id, reason }
+    }
+}
+impl NetworkMessageTrait for ErrorMessage {
+    fn deserialize(reader: &mut NetworkReader) -> Self {
+        let message_id = reader.read_ushort();
+        let reason = reader.read_string();
+        Self { message_id, reason }
+    }
+
+    fn serialize(&mut self, writer: &mut NetworkWriter) {
+        // 56944
+        writer.write_ushort(Self::get_full_name().get_stable_hash_code16());
+        writer.write_ushort(self.message_id);
+        writer.write_str(self.reason.as_str());
+    }
+
+    fn get_full_name() -> &'static str
+    where
+        Self: Sized,
+    {
+        "Mirror.ErrorMessage"
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+// 断开连接的原因
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+#[repr(u8)]
+pub enum DisconnectReason {
+    #[default]
+    None = 0,
+    Kick = 1,
+    AuthFailed = 2,
+    Timeout = 3,
+    ServerShutdown = 4,
+    ProtocolMismatch = 5,
+    SessionTakenOver = 6,
+}
+impl DisconnectReason {
+    pub fn from(value: u8) -> DisconnectReason {
+        match value {
+            1 => DisconnectReason::Kick,
+            2 => DisconnectReason::AuthFailed,
+            3 => DisconnectReason::Timeout,
+            4 => DisconnectReason::ServerShutdown,
+            5 => DisconnectReason::ProtocolMismatch,
+            6 => DisconnectReason::SessionTakenOver,
+            _ => DisconnectReason::None,
+        }
+    }
+    pub fn to_u8(&self) -> u8 {
+        *self as u8
+    }
+    pub fn text(&self) -> &'static str {
+        match self {
+            DisconnectReason::None => "Disconnected.",
+            DisconnectReason::Kick => "Kicked by server.",
+            DisconnectReason::AuthFailed => "Authentication failed.",
+            DisconnectReason::Timeout => "Connection timed out.",
+            DisconnectReason::ServerShutdown => "Server is shutting down.",
+            DisconnectReason::ProtocolMismatch => "Protocol version mismatch.",
+            DisconnectReason::SessionTakenOver => "Session resumed on another connection.",
+        }
+    }
+}
+
+// 服务器主动断开连接之前发送给客户端
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct DisconnectMessage {
+    pub reason: u8,
+    pub text: String,
+}
+impl DisconnectMessage {
+    #[allow(dead_code)]
+    pub fn new(reason: u8, text: String) -> DisconnectMessage {
+        Self { reason, text }
+    }
+}
+impl NetworkMessageTrait for DisconnectMessage {
+    fn deserialize(reader: &mut NetworkReader) -> Self {
+        let reason = reader.read_byte();
+        let text = reader.read_string();
+        Self { reason, text }
+    }
+
+    fn serialize(&mut self, writer: &mut NetworkWriter) {
+        // 55406
+        writer.write_ushort(Self::get_full_name().get_stable_hash_code16());
+        writer.write_byte(self.reason);
+        writer.write_str(self.text.as_str());
+    }
+
+    fn get_full_name() -> &'static str
+    where
+        Self: Sized,
+    {
+        "Mirror.DisconnectMessage"
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+// 服务器暂停 / 恢复世界模拟时广播给所有客户端
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct PauseMessage {
+    pub paused: bool,
+}
+impl PauseMessage {
+    #[allow(dead_code)]
+    pub fn new(paused: bool) -> PauseMessage {
+        Self { paused }
+    }
+}
+impl NetworkMessageTrait for PauseMessage {
+    fn deserialize(reader: &mut NetworkReader) -> Self {
+        let paused = reader.read_bool();
+        Self { paused }
+    }
+
+    fn serialize(&mut self, writer: &mut NetworkWriter) {
+        // 7565
+        writer.write_ushort(Self::get_full_name().get_stable_hash_code16());
+        writer.write_bool(self.paused);
+    }
+
+    fn get_full_name() -> &'static str
+    where
+        Self: Sized,
+    {
+        "Mirror.PauseMessage"
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+// BatchSpawnMessage 中的一个实例
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct BatchSpawnEntry {
+    pub net_id: u32,
+    pub position: Vector3<f32>,
+    pub rotation: Quaternion<f32>,
+}
+impl BatchSpawnEntry {
+    // net_id (VarUInt 最多 5 字节) + position + rotation
+    pub const MAX_SIZE: usize = 5 + 12 + 16;
+
+    #[allow(dead_code)]
+    pub fn new(net_id: u32, position: Vector3<f32>, rotation: Quaternion<f32>) -> Self {
+        Self {
+            net_id,
+            position,
+            rotation,
+        }
+    }
+}
+
+// 一次生成多个同一 asset_id 的对象 (子弹 / 掉落物 / 投射物)
+// 没有 payload 和 scale, 只发送给支持 NetworkServer::FEATURE_BATCH_SPAWN 的客户端
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct BatchSpawnMessage {
+    pub asset_id: u32,
+    pub entries: Vec<BatchSpawnEntry>,
+}
+impl BatchSpawnMessage {
+    // 消息id + asset_id + 数量
+    pub const HEADER_SIZE: usize = 2 + 5 + 5;
+
+    #[allow(dead_code)]
+    pub fn new(asset_id: u32, entries: Vec<BatchSpawnEntry>) -> BatchSpawnMessage {
+        Self { asset_id, entries }
+    }
+}
+impl NetworkMessageTrait for BatchSpawnMessage {
+    fn deserialize(reader: &mut NetworkReader) -> Self {
+        let asset_id = reader.decompress_var_uint();
+        let count = reader.decompress_var_uint() as usize;
+        let mut entries = Vec::with_capacity(count.min(reader.remaining()));
+        for _ in 0..count {
+            let net_id = reader.decompress_var_uint();
+            let position = reader.read_vector3();
+            let rotation = reader.read_quaternion();
+            entries.push(BatchSpawnEntry {
+                net_id,
+                position,
+                rotation,
+            });
+        }
+        Self { asset_id, entries }
+    }
+
+    fn serialize(&mut self, writer: &mut NetworkWriter) {
+        // 32476
+        writer.write_ushort(Self::get_full_name().get_stable_hash_code16());
+        writer.compress_var_uint(self.asset_id);
+        writer.compress_var_uint(self.entries.len() as u32);
+        for entry in self.entries.iter() {
+            writer.compress_var_uint(entry.net_id);
+            writer.write_vector3(entry.position);
+            writer.write_quaternion(entry.rotation);
+        }
+    }
+
+    fn get_full_name() -> &'static str
+    where
+        Self: Sized,
+    {
+        "Mirror.BatchSpawnMessage"
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+// 短生命周期对象 (投射物 / 特效) 的生成, 不创建 NetworkIdentity
+// ttl 秒之后客户端自己销毁, 所以这组消息都可以走 Unreliable
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct EphemeralSpawnMessage {
+    pub id: u32,
+    pub kind: u32,
+    pub position: Vector3<f32>,
+    pub velocity: Vector3<f32>,
+    pub ttl: f32,
+}
+impl EphemeralSpawnMessage {
+    #[allow(dead_code)]
+    pub fn new(
+        id: u32,
+        kind: u32,
+        position: Vector3<f32>,
+        velocity: Vector3<f32>,
+        ttl: f32,
+    ) -> EphemeralSpawnMessage {
+        Self {
+            id,
+            kind,
+            position,
+            velocity,
+            ttl,
+        }
+    }
+}
+impl NetworkMessageTrait for EphemeralSpawnMessage {
+    fn deserialize(reader: &mut NetworkReader) -> Self {
+        let id = reader.decompress_var_uint();
+        let kind = reader.decompress_var_uint();
+        let position = reader.read_vector3();
+        let velocity = reader.read_vector3();
+        let ttl = reader.read_float();
+        Self {
+            id,
+            kind,
+            position,
+            velocity,
+            ttl,
+        }
+    }
+
+    fn serialize(&mut self, writer: &mut NetworkWriter) {
+        // 51135
+        writer.write_ushort(Self::get_full_name().get_stable_hash_code16());
+        writer.compress_var_uint(self.id);
+        writer.compress_var_uint(self.kind);
+        writer.write_vector3(self.position);
+        writer.write_vector3(self.velocity);
+        writer.write_float(self.ttl);
+    }
+
+    fn get_full_name() -> &'static str
+    where
+        Self: Sized,
+    {
+        "Mirror.EphemeralSpawnMessage"
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct EphemeralUpdateMessage {
+    pub id: u32,
+    pub position: Vector3<f32>,
+    pub velocity: Vector3<f32>,
+}
+impl EphemeralUpdateMessage {
+    #[allow(dead_code)]
+    pub fn new(id: u32, position: Vector3<f32>, velocity: Vector3<f32>) -> EphemeralUpdateMessage {
+        Self {
+            id,
+            position,
+            velocity,
+        }
+    }
+}
+impl NetworkMessageTrait for EphemeralUpdateMessage {
+    fn deserialize(reader: &mut NetworkReader) -> Self {
+        let id = reader.decompress_var_uint();
+        let position = reader.read_vector3();
+        let velocity = reader.read_vector3();
+        Self {
+            id,
+            position,
+            velocity,
+        }
+    }
+
+    fn serialize(&mut self, writer: &mut NetworkWriter) {
+        // 50523
+        writer.write_ushort(Self::get_full_name().get_stable_hash_code16());
+        writer.compress_var_uint(self.id);
+        writer.write_vector3(self.position);
+        writer.write_vector3(self.velocity);
+    }
+
+    fn get_full_name() -> &'static str
+    where
+        Self: Sized,
+    {
+        "Mirror.EphemeralUpdateMessage"
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct EphemeralDespawnMessage {
+    pub id: u32,
+}
+impl EphemeralDespawnMessage {
+    #[allow(dead_code)]
+    pub fn new(id: u32) -> EphemeralDespawnMessage {
+        Self { id }
+    }
+}
+impl NetworkMessageTrait for EphemeralDespawnMessage {
+    fn deserialize(reader: &mut NetworkReader) -> Self {
+        let id = reader.decompress_var_uint();
+        Self { id }
+    }
+
+    fn serialize(&mut self, writer: &mut NetworkWriter) {
+        // 49084
+        writer.write_ushort(Self::get_full_name().get_stable_hash_code16());
+        writer.compress_var_uint(self.id);
+    }
+
+    fn get_full_name() -> &'static str
+    where
+        Self: Sized,
+    {
+        "Mirror.EphemeralDespawnMessage"
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+// 服务器满员时, 排队中的连接会定期收到自己的位置
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct QueuePositionMessage {
+    // 从 1 开始
+    pub position: u32,
+    pub queue_length: u32,
+}
+impl QueuePositionMessage {
+    #[allow(dead_code)]
+    pub fn new(position: u32, queue_length: u32) -> QueuePositionMessage {
+        Self {
+            position,
+            queue_length,
+        }
+    }
+}
+impl NetworkMessageTrait for QueuePositionMessage {
+    fn deserialize(reader: &mut NetworkReader) -> Self {
+        let position = reader.decompress_var_uint();
+        let queue_length = reader.decompress_var_uint();
+        Self {
+            position,
+            queue_length,
+        }
+    }
+
+    fn serialize(&mut self, writer: &mut NetworkWriter) {
+        // 5677
+        writer.write_ushort(Self::get_full_name().get_stable_hash_code16());
+        writer.compress_var_uint(self.position);
+        writer.compress_var_uint(self.queue_length);
+    }
+
+    fn get_full_name() -> &'static str
+    where
+        Self: Sized,
+    {
+        "Mirror.QueuePositionMessage"
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+// 客户端每个 tick 的输入, 带上最近几个 tick 的输入作为冗余, 丢包时服务器仍然能拿到
+// inputs[i] 对应 first_tick + i
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct InputMessage {
+    pub first_tick: u32,
+    pub inputs: Vec<Vec<u8>>,
+}
+impl InputMessage {
+    #[allow(dead_code)]
+    pub fn new(first_tick: u32, inputs: Vec<Vec<u8>>) -> InputMessage {
+        Self { first_tick, inputs }
+    }
+}
+impl NetworkMessageTrait for InputMessage {
+    fn deserialize(reader: &mut NetworkReader) -> Self {
+        let first_tick = reader.decompress_var_uint();
+        let count = reader.decompress_var_uint() as usize;
+        let mut inputs = Vec::with_capacity(count.min(reader.remaining()));
+        for _ in 0..count {
+            inputs.push(reader.read_bytes_and_size());
+        }
+        Self { first_tick, inputs }
+    }
+
+    fn serialize(&mut self, writer: &mut NetworkWriter) {
+        // 6533
+        writer.write_ushort(Self::get_full_name().get_stable_hash_code16());
+        writer.compress_var_uint(self.first_tick);
+        writer.compress_var_uint(self.inputs.len() as u32);
+        for input in self.inputs.iter() {
+            writer.write_array_segment_and_size(input);
+        }
+    }
+
+    fn get_full_name() -> &'static str
+    where
+        Self: Sized,
+    {
+        "Mirror.InputMessage"
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+// 服务器最后处理的输入 tick, 客户端据此丢弃已确认的输入并重新预测
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct InputAckMessage {
+    pub tick: u32,
+}
+impl InputAckMessage {
+    #[allow(dead_code)]
+    pub fn new(tick: u32) -> InputAckMessage {
+        Self { tick }
+    }
+}
+impl NetworkMessageTrait for InputAckMessage {
+    fn deserialize(reader: &mut NetworkReader) -> Self {
+        let tick = reader.decompress_var_uint();
+        Self { tick }
+    }
+
+    fn serialize(&mut self, writer: &mut NetworkWriter) {
+        // 46047
+        writer.write_ushort(Self::get_full_name().get_stable_hash_code16());
+        writer.compress_var_uint(self.tick);
+    }
+
+    fn get_full_name() -> &'static str
+    where
+        Self: Sized,
+    {
+        "Mirror.InputAckMessage"
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+// 对象附着到另一个对象 (驾驶员进入载具), parent_net_id 为 0 表示分离
+// 附着期间客户端应该把对象挂到父对象下, 位置为 local_offset
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct AttachMessage {
+    pub net_id: u32,
+    pub parent_net_id: u32,
+    pub local_offset: Vector3<f32>,
+}
+impl AttachMessage {
+    #[allow(dead_code)]
+    pub fn new(net_id: u32, parent_net_id: u32, local_offset: Vector3<f32>) -> AttachMessage {
+        Self {
+            net_id,
+            parent_net_id,
+            local_offset,
+        }
+    }
+}
+impl NetworkMessageTrait for AttachMessage {
+    fn deserialize(reader: &mut NetworkReader) -> Self {
+        let net_id = reader.decompress_var_uint();
+        let parent_net_id = reader.decompress_var_uint();
+        let local_offset = reader.read_vector3();
+        Self {
+            net_id,
+            parent_net_id,
+            local_offset,
+        }
+    }
+
+    fn serialize(&mut self, writer: &mut NetworkWriter) {
+        // 53471
+        writer.write_ushort(Self::get_full_name().get_stable_hash_code16());
+        writer.compress_var_uint(self.net_id);
+        writer.compress_var_uint(self.parent_net_id);
+        writer.write_vector3(self.local_offset);
+    }
+
+    fn get_full_name() -> &'static str
+    where
+        Self: Sized,
+    {
+        "Mirror.AttachMessage"
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+// 语音帧 (opus 编码), 客户端发送时 net_id 为 0, 服务器转发时为说话者的玩家 net_id
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct VoiceMessage {
+    pub net_id: u32,
+    pub frame: Vec<u8>,
+}
+impl VoiceMessage {
+    #[allow(dead_code)]
+    pub fn new(net_id: u32, frame: Vec<u8>) -> VoiceMessage {
+        Self { net_id, frame }
+    }
+}
+impl NetworkMessageTrait for VoiceMessage {
+    fn deserialize(reader: &mut NetworkReader) -> Self {
+        let net_id = reader.decompress_var_uint();
+        let frame = reader.read_bytes_and_size();
+        Self { net_id, frame }
+    }
+
+    fn serialize(&mut self, writer: &mut NetworkWriter) {
+        // 55288
+        writer.write_ushort(Self::get_full_name().get_stable_hash_code16());
+        writer.compress_var_uint(self.net_id);
+        writer.write_array_segment_and_size(&self.frame);
+    }
+
+    fn get_full_name() -> &'static str
+    where
+        Self: Sized,
+    {
+        "Mirror.VoiceMessage"
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+// 大数据分块传输 (地图数据, 回放等), 服务器通过 Reliable 通道发送
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct BlobChunkMessage {
+    pub transfer_id: u32,
+    pub total_size: u32,
+    pub offset: u32,
+    pub data: Vec<u8>,
+}
+impl BlobChunkMessage {
+    #[allow(dead_code)]
+    pub fn new(transfer_id: u32, total_size: u32, offset: u32, data: Vec<u8>) -> BlobChunkMessage {
+        Self {
+            transfer_id,
+            total_size,
+            offset,
+            data,
+        }
+    }
+}
+impl NetworkMessageTrait for BlobChunkMessage {
+    fn deserialize(reader: &mut NetworkReader) -> Self {
+        let transfer_id = reader.decompress_var_uint();
+        let total_size = reader.decompress_var_uint();
+        let offset = reader.decompress_var_uint();
+        let data = reader.read_bytes_and_size();
+        Self {
+            transfer_id,
+            total_size,
+            offset,
+            data,
+        }
+    }
+
+    fn serialize(&mut self, writer: &mut NetworkWriter) {
+        // 18501
+        writer.write_ushort(Self::get_full_name().get_stable_hash_code16());
+        writer.compress_var_uint(self.transfer_id);
+        writer.compress_var_uint(self.total_size);
+        writer.compress_var_uint(self.offset);
+        writer.write_array_segment_and_size(&self.data);
+    }
+
+    fn get_full_name() -> &'static str
+    where
+        Self: Sized,
+    {
+        "Mirror.BlobChunkMessage"
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+// 客户端确认已经连续收到的字节数, 用于流量控制和断点续传
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct BlobAckMessage {
+    pub transfer_id: u32,
+    pub received: u32,
+}
+impl BlobAckMessage {
+    #[allow(dead_code)]
+    pub fn new(transfer_id: u32, received: u32) -> BlobAckMessage {
+        Self {
+            transfer_id,
+            received,
+        }
+    }
+}
+impl NetworkMessageTrait for BlobAckMessage {
+    fn deserialize(reader: &mut NetworkReader) -> Self {
+        let transfer_id = reader.decompress_var_uint();
+        let received = reader.decompress_var_uint();
+        Self {
+            transfer_id,
+            received,
+        }
+    }
+
+    fn serialize(&mut self, writer: &mut NetworkWriter) {
+        // 32797
+        writer.write_ushort(Self::get_full_name().get_stable_hash_code16());
+        writer.compress_var_uint(self.transfer_id);
+        writer.compress_var_uint(self.received);
+    }
+
+    fn get_full_name() -> &'static str
+    where
+        Self: Sized,
+    {
+        "Mirror.BlobAckMessage"
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+// 支持 FEATURE_STATE_TICK 的连接用它代替 TimeSnapshotMessage, 附带服务器 tick
+// 客户端发送时 tick 为最近收到的服务器 tick
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct TickSnapshotMessage {
+    pub tick: u32,
+}
+impl TickSnapshotMessage {
+    #[allow(dead_code)]
+    pub fn new(tick: u32) -> TickSnapshotMessage {
+        Self { tick }
+    }
+}
+impl NetworkMessageTrait for TickSnapshotMessage {
+    fn deserialize(reader: &mut NetworkReader) -> Self {
+        let tick = reader.decompress_var_uint();
+        Self { tick }
+    }
+
+    fn serialize(&mut self, writer: &mut NetworkWriter) {
+        // 53433
+        writer.write_ushort(Self::get_full_name().get_stable_hash_code16());
+        writer.compress_var_uint(self.tick);
+    }
+
+    fn get_full_name() -> &'static str
+    where
+        Self: Sized,
+    {
+        "Mirror.TickSnapshotMessage"
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+// 带服务器 tick 的 EntityStateMessage, 客户端据此检测乱序和丢失, 并把状态对齐到模拟 tick
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct TickedEntityStateMessage {
+    pub net_id: u32,
+    pub tick: u32,
+    pub payload: Vec<u8>,
+}
+impl TickedEntityStateMessage {
+    #[allow(dead_code)]
+    pub fn new(net_id: u32, tick: u32, payload: Vec<u8>) -> TickedEntityStateMessage {
+        Self {
+            net_id,
+            tick,
+            payload,
+        }
+    }
+}
+impl NetworkMessageTrait for TickedEntityStateMessage {
+    fn deserialize(reader: &mut NetworkReader) -> Self {
+        let net_id = reader.decompress_var_uint();
+        let tick = reader.decompress_var_uint();
+        let payload = reader.read_bytes_and_size();
+        Self {
+            net_id,
+            tick,
+            payload,
+        }
+    }
+
+    fn serialize(&mut self, writer: &mut NetworkWriter) {
+        // 57735
+        writer.write_ushort(Self::get_full_name().get_stable_hash_code16());
+        writer.compress_var_uint(self.net_id);
+        writer.compress_var_uint(self.tick);
+        writer.write_array_segment_and_size(self.payload.as_slice());
+    }
+
+    fn get_full_name() -> &'static str
+    where
+        Self: Sized,
+    {
+        "Mirror.TickedEntityStateMessage"
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+// NetworkScoreboard 中一个玩家的一项统计
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ScoreEntry {
+    pub player: u64,
+    pub stat: String,
+    pub value: i32,
+}
+impl ScoreEntry {
+    #[allow(dead_code)]
+    pub fn new(player: u64, stat: String, value: i32) -> Self {
+        Self {
+            player,
+            stat,
+            value,
+        }
+    }
+
+    fn deserialize_all(reader: &mut NetworkReader) -> Vec<ScoreEntry> {
+        let count = reader.decompress_var_uint() as usize;
+        let mut entries = Vec::with_capacity(count.min(reader.remaining()));
+        for _ in 0..count {
+            let player = reader.decompress_var_ulong();
+            let stat = reader.read_string();
+            let value = reader.decompress_var_int();
+            entries.push(ScoreEntry {
+                player,
+                stat,
+                value,
+            });
+        }
+        entries
+    }
+
+    fn serialize_all(entries: &[ScoreEntry], writer: &mut NetworkWriter) {
+        writer.compress_var_uint(entries.len() as u32);
+        for entry in entries.iter() {
+            writer.compress_var_ulong(entry.player);
+            writer.write_str(entry.stat.as_str());
+            writer.compress_var_int(entry.value);
+        }
+    }
+}
+
+// 上次同步之后变化的统计和移除的玩家, 按 NetworkScoreboard 的同步间隔发送给所有准备好的客户端
+// 客户端先处理 removed 再处理 entries, 移除后重新加入的玩家只保留新的统计
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct ScoreboardDeltaMessage {
+    pub entries: Vec<ScoreEntry>,
+    pub removed: Vec<u64>,
+}
+impl ScoreboardDeltaMessage {
+    #[allow(dead_code)]
+    pub fn new(entries: Vec<ScoreEntry>, removed: Vec<u64>) -> ScoreboardDeltaMessage {
+        Self { entries, removed }
+    }
+}
+impl NetworkMessageTrait for ScoreboardDeltaMessage {
+    fn deserialize(reader: &mut NetworkReader) -> Self {
+        let entries = ScoreEntry::deserialize_all(reader);
+        let count = reader.decompress_var_uint() as usize;
+        let mut removed = Vec::with_capacity(count.min(reader.remaining()));
+        for _ in 0..count {
+            removed.push(reader.decompress_var_ulong());
+        }
+        Self { entries, removed }
+    }
+
+    fn serialize(&mut self, writer: &mut NetworkWriter) {
+        // 11778
+        writer.write_ushort(Self::get_full_name().get_stable_hash_code16());
+        ScoreEntry::serialize_all(&self.entries, writer);
+        writer.compress_var_uint(self.removed.len() as u32);
+        for player in self.removed.iter() {
+            writer.compress_var_ulong(*player);
+        }
+    }
+
+    fn get_full_name() -> &'static str
+    where
+        Self: Sized,
+    {
+        "Mirror.ScoreboardDeltaMessage"
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+// 完整的记分板, 客户端准备好时发送一次, 客户端收到后替换本地的记分板
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct ScoreboardFullMessage {
+    pub entries: Vec<ScoreEntry>,
+}
+impl ScoreboardFullMessage {
+    #[allow(dead_code)]
+    pub fn new(entries: Vec<ScoreEntry>) -> ScoreboardFullMessage {
+        Self { entries }
+    }
+}
+impl NetworkMessageTrait for ScoreboardFullMessage {
+    fn deserialize(reader: &mut NetworkReader) -> Self {
+        Self {
+            entries: ScoreEntry::deserialize_all(reader),
+        }
+    }
+
+    fn serialize(&mut self, writer: &mut NetworkWriter) {
+        // 17541
+        writer.write_ushort(Self::get_full_name().get_stable_hash_code16());
+        ScoreEntry::serialize_all(&self.entries, writer);
+    }
+
+    fn get_full_name() -> &'static str
+    where
+        Self: Sized,
+    {
+        "Mirror.ScoreboardFullMessage"
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+// 认证之后、AddPlayer 之前服务器发送可选的角色 / 装备, 客户端回复 LoadoutMessage
+// timeout 秒内没有回复时服务器使用 default_option
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct LoadoutOptionsMessage {
+    pub options: Vec<String>,
+    pub default_option: String,
+    pub timeout: f32,
+}
+impl LoadoutOptionsMessage {
+    #[allow(dead_code)]
+    pub fn new(
+        options: Vec<String>,
+        default_option: String,
+        timeout: f32,
+    ) -> LoadoutOptionsMessage {
+        Self {
+            options,
+            default_option,
+            timeout,
+        }
+    }
+}
+impl NetworkMessageTrait for LoadoutOptionsMessage {
+    fn deserialize(reader: &mut NetworkReader) -> Self {
+        let count = reader.decompress_var_uint() as usize;
+        let mut options = Vec::with_capacity(count.min(reader.remaining()));
+        for _ in 0..count {
+            options.push(reader.read_string());
+        }
+        let default_option = reader.read_string();
+        let timeout = reader.read_float();
+        Self {
+            options,
+            default_option,
+            timeout,
+        }
+    }
+
+    fn serialize(&mut self, writer: &mut NetworkWriter) {
+        // 23593
+        writer.write_ushort(Self::get_full_name().get_stable_hash_code16());
+        writer.compress_var_uint(self.options.len() as u32);
+        for option in self.options.iter() {
+            writer.write_str(option.as_str());
+        }
+        writer.write_str(self.default_option.as_str());
+        writer.write_float(self.timeout);
+    }
+
+    fn get_full_name() -> &'static str
+    where
+        Self: Sized,
+    {
+        "Mirror.LoadoutOptionsMessage"
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+// 客户端选择的角色 / 装备, 必须是 LoadoutOptionsMessage.options 中的一项
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct LoadoutMessage {
+    pub selection: String,
+}
+impl LoadoutMessage {
+    #[allow(dead_code)]
+    pub fn new(selection: String) -> LoadoutMessage {
+        Self { selection }
+    }
+}
+impl NetworkMessageTrait for LoadoutMessage {
+    fn deserialize(reader: &mut NetworkReader) -> Self {
+        Self {
+            selection: reader.read_string(),
+        }
+    }
+
+    fn serialize(&mut self, writer: &mut NetworkWriter) {
+        // 59216
+        writer.write_ushort(Self::get_full_name().get_stable_hash_code16());
+        writer.write_str(self.selection.as_str());
+    }
+
+    fn get_full_name() -> &'static str
+    where
+        Self: Sized,
+    {
+        "Mirror.LoadoutMessage"
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+// 服务器当前的发送间隔和建议的插值缓冲, 只发送给支持 FEATURE_INTERPOLATION_HINT 的客户端
+// 协议握手完成时发送一次, 之后变化时跟在 NetworkPongMessage 后面发送, 客户端据此调整插值延迟
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct InterpolationHintMessage {
+    pub send_interval: f64,
+    pub buffer_time_multiplier: f64,
+    pub buffer_limit: u32,
+}
+impl InterpolationHintMessage {
+    #[allow(dead_code)]
+    pub fn new(
+        send_interval: f64,
+        buffer_time_multiplier: f64,
+        buffer_limit: u32,
+    ) -> InterpolationHintMessage {
+        Self {
+            send_interval,
+            buffer_time_multiplier,
+            buffer_limit,
+        }
+    }
+
+    // 建议的缓冲时间, 单位秒
+    pub fn buffer_time(&self) -> f64 {
+        self.send_interval * self.buffer_time_multiplier
+    }
+}
+impl NetworkMessageTrait for InterpolationHintMessage {
+    fn deserialize(reader: &mut NetworkReader) -> Self {
+        let send_interval = reader.read_double();
+        let buffer_time_multiplier = reader.read_double();
+        let buffer_limit = reader.decompress_var_uint();
+        Self {
+            send_interval,
+            buffer_time_multiplier,
+            buffer_limit,
+        }
+    }
+
+    fn serialize(&mut self, writer: &mut NetworkWriter) {
+        // 39832
+        writer.write_ushort(Self::get_full_name().get_stable_hash_code16());
+        writer.write_double(self.send_interval);
+        writer.write_double(self.buffer_time_multiplier);
+        writer.compress_var_uint(self.buffer_limit);
+    }
+
+    fn get_full_name() -> &'static str
+    where
+        Self: Sized,
+    {
+        "Mirror.InterpolationHintMessage"
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+// 客户端第一次准备好时服务器发放的会话令牌, 断开后在 SessionResume 的保留时间内可以用它恢复会话
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct SessionTokenMessage {
+    pub token: u64,
+}
+impl SessionTokenMessage {
+    #[allow(dead_code)]
+    pub fn new(token: u64) -> SessionTokenMessage {
+        Self { token }
+    }
+}
+impl NetworkMessageTrait for SessionTokenMessage {
+    fn deserialize(reader: &mut NetworkReader) -> Self {
+        let token = reader.read_ulong();
+        Self { token }
+    }
+
+    fn serialize(&mut self, writer: &mut NetworkWriter) {
+        // 24143
+        writer.write_ushort(Self::get_full_name().get_stable_hash_code16());
+        writer.write_ulong(self.token);
+    }
+
+    fn get_full_name() -> &'static str
+    where
+        Self: Sized,
+    {
+        "Mirror.SessionTokenMessage"
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+// 客户端重新连接后 (可以是另一个 Transport) 代替认证发送, 恢复之前的会话
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct SessionResumeMessage {
+    pub token: u64,
+}
+impl SessionResumeMessage {
+    #[allow(dead_code)]
+    pub fn new(token: u64) -> SessionResumeMessage {
+        Self { token }
+    }
+}
+impl NetworkMessageTrait for SessionResumeMessage {
+    fn deserialize(reader: &mut NetworkReader) -> Self {
+        let token = reader.read_ulong();
+        Self { token }
+    }
+
+    fn serialize(&mut self, writer: &mut NetworkWriter) {
+        // 35865
+        writer.write_ushort(Self::get_full_name().get_stable_hash_code16());
+        writer.write_ulong(self.token);
+    }
+
+    fn get_full_name() -> &'static str
+    where
+        Self: Sized,
+    {
+        "Mirror.SessionResumeMessage"
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+// 恢复成功时 token 为新的会话令牌, 之后客户端照常发送 ReadyMessage; 失败时 token 为 0, 客户端走正常的认证流程
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct SessionResumeResultMessage {
+    pub success: bool,
+    pub token: u64,
+}
+impl SessionResumeResultMessage {
+    #[allow(dead_code)]
+    pub fn new(success: bool, token: u64) -> SessionResumeResultMessage {
+        Self { success, token }
+    }
+}
+impl NetworkMessageTrait for SessionResumeResultMessage {
+    fn deserialize(reader: &mut NetworkReader) -> Self {
+        let success = reader.read_bool();
+        let token = reader.read_ulong();
+        Self { success, token }
+    }
+
+    fn serialize(&mut self, writer: &mut NetworkWriter) {
+        // 23323
+        writer.write_ushort(Self::get_full_name().get_stable_hash_code16());
+        writer.write_bool(self.success);
+        writer.write_ulong(self.token);
+    }
+
+    fn get_full_name() -> &'static str
+    where
+        Self: Sized,
+    {
+        "Mirror.SessionResumeResultMessage"
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::core::tools::stable_hash_registry::StableHashRegistry;
+    use crate::mirror::transports::memory::memory_transport::test_util::captured_golden;
+    use std::fmt::Debug;
+
+    // 按 NetworkWriter 的格式手工推导的字节 (小端, ushort 消息id, VarUInt netId / 长度前缀)
+    // 不是从 C# 抓取的数据, 只用来防止消息的序列化格式被意外修改
+    // C# Mirror 抓取的数据放在 tests/golden/<full_name>.bin, 存在时 assert_golden 同时与它比较
+    const TIME_SNAPSHOT: &[u8] = &[0x09, 0xDF];
+    const READY: &[u8] = &[0xBC, 0xAA];
+    const NOT_READY: &[u8] = &[0x72, 0xA9];
+    const ADD_PLAYER: &[u8] = &[0x06, 0xC1];
+    const SCENE: &[u8] = &[0xE0, 0x0D, 0x05, 0x00, 0x47, 0x61, 0x6D, 0x65, 0x01, 0x01];
+    const COMMAND: &[u8] = &[0xD4, 0x98, 0x07, 0x01, 0x34, 0x12, 0x04, 0x01, 0x02, 0x03];
+    const RPC: &[u8] = &[0x2E, 0x9D, 0x07, 0x01, 0x34, 0x12, 0x04, 0x01, 0x02, 0x03];
+    const SPAWN: &[u8] = &[
+        0x64, 0x88, 0x05, 0x01, 0x00, 0x00, 0xF1, 0x3C, 0x00, 0x00, 0x80, 0x3F, 0x00, 0x00, 0x00,
+        0x40, 0x00, 0x00, 0x40, 0x40, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x80, 0x3F, 0x00, 0x00, 0x80, 0x3F, 0x00, 0x00, 0x80, 0x3F, 0x00,
+        0x00, 0x80, 0x3F, 0x02, 0x09,
+    ];
+    const CHANGE_OWNER: &[u8] = &[0xAB, 0x10, 0x05, 0x01, 0x00];
+    const OBJECT_SPAWN_STARTED: &[u8] = &[0xD8, 0x30];
+    const OBJECT_SPAWN_FINISHED: &[u8] = &[0xB4, 0xA9];
+    const OBJECT_DESTROY: &[u8] = &[0x61, 0xAD, 0x05];
+    const OBJECT_HIDE: &[u8] = &[0xF1, 0x7F, 0x05];
+    const ENTITY_STATE: &[u8] = &[0x33, 0x30, 0x05, 0x03, 0x01, 0x02];
+    const NETWORK_PING: &[u8] = &[
+        0x4F, 0x44, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xF0, 0x3F, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x40,
+    ];
+    const PAUSE: &[u8] = &[0x8D, 0x1D, 0x01];
+    const EPHEMERAL_DESPAWN: &[u8] = &[0xBC, 0xBF, 0x05];
+    const QUEUE_POSITION: &[u8] = &[0x2D, 0x16, 0x03, 0x0A];
+    const INPUT: &[u8] = &[0x85, 0x19, 0xF1, 0x3C, 0x02, 0x02, 0x01, 0x00];
+    const INPUT_ACK: &[u8] = &[0xDF, 0xB3, 0xF1, 0x3C];
+    const VOICE: &[u8] = &[0xF8, 0xD7, 0x05, 0x04, 0x01, 0x02, 0x03];
+    const BLOB_CHUNK: &[u8] = &[0x45, 0x48, 0x05, 0x0A, 0x04, 0x03, 0x01, 0x02];
+    const BLOB_ACK: &[u8] = &[0x1D, 0x80, 0x05, 0xF1, 0x3C];
+    const TICK_SNAPSHOT: &[u8] = &[0xB9, 0xD0, 0xF1, 0x3C];
+    const TICKED_ENTITY_STATE: &[u8] = &[0x87, 0xE1, 0x05, 0xF1, 0x3C, 0x03, 0x01, 0x02];
+    const SCOREBOARD_DELTA: &[u8] = &[
+        0x02, 0x2E, 0x01, 0xF1, 0x3C, 0x06, 0x00, 0x4B, 0x69, 0x6C, 0x6C, 0x73, 0x03, 0x01, 0x05,
+    ];
+    const SCOREBOARD_FULL: &[u8] = &[
+        0x85, 0x44, 0x01, 0xF1, 0x3C, 0x06, 0x00, 0x4B, 0x69, 0x6C, 0x6C, 0x73, 0x03,
+    ];
+    const LOADOUT_OPTIONS: &[u8] = &[
+        0x29, 0x5C, 0x02, 0x02, 0x00, 0x41, 0x02, 0x00, 0x42, 0x02, 0x00, 0x42, 0x00, 0x00, 0xF0,
+        0x41,
+    ];
+    const LOADOUT: &[u8] = &[0x50, 0xE7, 0x02, 0x00, 0x42];
+    const ATTACH: &[u8] = &[
+        0xDF, 0xD0, 0x05, 0x06, 0x00, 0x00, 0x80, 0x3F, 0x00, 0x00, 0x00, 0x40, 0x00, 0x00, 0x40,
+        0x40,
+    ];
+    const BATCH_SPAWN: &[u8] = &[
+        0xDC, 0x7E, 0xF1, 0x3C, 0x01, 0x05, 0x00, 0x00, 0x80, 0x3F, 0x00, 0x00, 0x00, 0x40, 0x00,
+        0x00, 0x40, 0x40, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x80, 0x3F,
+    ];
+    const INTERPOLATION_HINT: &[u8] = &[
+        0x98, 0x9B, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xF0, 0x3F, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x40, 0x20,
+    ];
+    const SESSION_TOKEN: &[u8] = &[0x4F, 0x5E, 0x05, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+    const SESSION_RESUME: &[u8] = &[0x19, 0x8C, 0x05, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+    const SESSION_RESUME_RESULT: &[u8] = &[
+        0x1B, 0x5B, 0x01, 0x06, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ];
+    const PROTOCOL_VERSION: &[u8] = &[
+        0x97, 0x56, 0x03, 0x00, 0x01, 0x00, 0x00, 0x00, 0x3C, 0x00, 0x00, 0x00,
+    ];
+    const PROTOCOL_REJECT: &[u8] = &[0x7E, 0x4A, 0x03, 0x00, 0x04, 0x00, 0x6F, 0x6C, 0x64];
+    const ERROR: &[u8] = &[0x70, 0xDE, 0x34, 0x12, 0x04, 0x00, 0x62, 0x61, 0x64];
+    const DISCONNECT: &[u8] = &[0x6E, 0xD8, 0x01, 0x05, 0x00, 0x6B, 0x69, 0x63, 0x6B];
+    const EPHEMERAL_SPAWN: &[u8] = &[
+        0xBF, 0xC7, 0x05, 0x02, 0x00, 0x00, 0x80, 0x3F, 0x00, 0x00, 0x00, 0x40, 0x00, 0x00, 0x40,
+        0x40, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x80, 0x3F, 0x00, 0x00,
+        0x00, 0x40,
+    ];
+    const EPHEMERAL_UPDATE: &[u8] = &[
+        0x5B, 0xC5, 0x05, 0x00, 0x00, 0x80, 0x3F, 0x00, 0x00, 0x00, 0x40, 0x00, 0x00, 0x40, 0x40,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x80, 0x3F,
+    ];
+    const NETWORK_PONG: &[u8] = &[
+        0xD7, 0x69, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xF0, 0x3F, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x40, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x08, 0x40,
+    ];
+
+    fn assert_golden<T>(mut message: T, golden: &[u8])
+    where
+        T: NetworkMessageTrait + PartialEq + Debug,
+    {
+        let mut writer = NetworkWriter::new();
+        message.serialize(&mut writer);
+        assert_eq!(writer.to_array_segment(), golden, "{}", T::get_full_name());
+        if let Some(captured) = captured_golden(T::get_full_name()) {
+            assert_eq!(golden, captured, "{} (C#)", T::get_full_name());
+        }
+
+        let mut reader = NetworkReader::new_with_array_segment(golden);
+        assert_eq!(
+            reader.read_ushort(),
+            T::get_hash_code(),
+            "{}",
+            T::get_full_name()
+        );
+        assert_eq!(
+            T::deserialize(&mut reader),
+            message,
+            "{}",
+            T::get_full_name()
+        );
+        assert_eq!(reader.remaining(), 0, "{}", T::get_full_name());
+    }
+
+    #[test]
+    fn test_golden_empty_messages() {
+        assert_golden(TimeSnapshotMessage, TIME_SNAPSHOT);
+        assert_golden(ReadyMessage, READY);
+        assert_golden(NotReadyMessage, NOT_READY);
+        assert_golden(AddPlayerMessage, ADD_PLAYER);
+        assert_golden(ObjectSpawnStartedMessage, OBJECT_SPAWN_STARTED);
+        assert_golden(ObjectSpawnFinishedMessage, OBJECT_SPAWN_FINISHED);
+    }
+
+    #[test]
+    fn test_golden_messages() {
+        assert_golden(
+            SceneMessage::new("Game".to_string(), SceneOperation::LoadAdditive, true),
+            SCENE,
+        );
+        assert_golden(CommandMessage::new(7, 1, 0x1234, vec![1, 2, 3]), COMMAND);
+        assert_golden(RpcMessage::new(7, 1, 0x1234, vec![1, 2, 3]), RPC);
+        assert_golden(
+            SpawnMessage::new(
+                5,
+                true,
+                false,
+                0,
+                300,
+                Vector3::new(1.0, 2.0, 3.0),
+                Quaternion::identity(),
+                Vector3::new(1.0, 1.0, 1.0),
+                vec![9],
+            ),
+            SPAWN,
+        );
+        assert_golden(ChangeOwnerMessage::new(5, true, false), CHANGE_OWNER);
+        assert_golden(ObjectDestroyMessage::new(5), OBJECT_DESTROY);
+        assert_golden(ObjectHideMessage::new(5), OBJECT_HIDE);
+        assert_golden(EntityStateMessage::new(5, vec![1, 2]), ENTITY_STATE);
+        assert_golden(NetworkPingMessage::new(1.0, 2.0), NETWORK_PING);
+        assert_golden(NetworkPongMessage::new(1.0, 2.0, 3.0), NETWORK_PONG);
+        assert_golden(PauseMessage::new(true), PAUSE);
+        assert_golden(EphemeralDespawnMessage::new(5), EPHEMERAL_DESPAWN);
+        assert_golden(QueuePositionMessage::new(3, 10), QUEUE_POSITION);
+        assert_golden(InputMessage::new(300, vec![vec![1], vec![]]), INPUT);
+        assert_golden(InputAckMessage::new(300), INPUT_ACK);
+        assert_golden(AttachMessage::new(5, 6, Vector3::new(1.0, 2.0, 3.0)), ATTACH);
+        assert_golden(VoiceMessage::new(5, vec![1, 2, 3]), VOICE);
+        assert_golden(BlobChunkMessage::new(5, 10, 4, vec![1, 2]), BLOB_CHUNK);
+        assert_golden(BlobAckMessage::new(5, 300), BLOB_ACK);
+        assert_golden(TickSnapshotMessage::new(300), TICK_SNAPSHOT);
+        assert_golden(
+            TickedEntityStateMessage::new(5, 300, vec![1, 2]),
+            TICKED_ENTITY_STATE,
+        );
+        assert_golden(
+            ScoreboardDeltaMessage::new(
+                vec![ScoreEntry::new(300, "Kills".to_string(), -2)],
+                vec![5],
+            ),
+            SCOREBOARD_DELTA,
+        );
+        assert_golden(
+            ScoreboardFullMessage::new(vec![ScoreEntry::new(300, "Kills".to_string(), -2)]),
+            SCOREBOARD_FULL,
+        );
+        assert_golden(
+            LoadoutOptionsMessage::new(
+                vec!["A".to_string(), "B".to_string()],
+                "B".to_string(),
+                30.0,
+            ),
+            LOADOUT_OPTIONS,
+        );
+        assert_golden(LoadoutMessage::new("B".to_string()), LOADOUT);
+        assert_golden(
+            InterpolationHintMessage::new(1.0, 2.0, 32),
+            INTERPOLATION_HINT,
+        );
+        assert_golden(
+            BatchSpawnMessage::new(
+                300,
+                vec![BatchSpawnEntry::new(
+                    5,
+                    Vector3::new(1.0, 2.0, 3.0),
+                    Quaternion::identity(),
+                )],
+            ),
+            BATCH_SPAWN,
+        );
+        assert_golden(SessionTokenMessage::new(5), SESSION_TOKEN);
+        assert_golden(SessionResumeMessage::new(5), SESSION_RESUME);
+        assert_golden(
+            SessionResumeResultMessage::new(true, 6),
+            SESSION_RESUME_RESULT,
+        );
+        assert_golden(ProtocolVersionMessage::new(3, 1, 60), PROTOCOL_VERSION);
+        assert_golden(
+            ProtocolRejectMessage::new(3, "old".to_string()),
+            PROTOCOL_REJECT,
+        );
+        assert_golden(ErrorMessage::new(0x1234, "bad".to_string()), ERROR);
+        assert_golden(
+            DisconnectMessage::new(DisconnectReason::Kick as u8, "kick".to_string()),
+            DISCONNECT,
+        );
+        assert_golden(
+            EphemeralSpawnMessage::new(
+                5,
+                2,
+                Vector3::new(1.0, 2.0, 3.0),
+                Vector3::new(0.0, 0.0, 1.0),
+                2.0,
+            ),
+            EPHEMERAL_SPAWN,
+        );
+        assert_golden(
+            EphemeralUpdateMessage::new(
+                5,
+                Vector3::new(1.0, 2.0, 3.0),
+                Vector3::new(0.0, 0.0, 1.0),
+            ),
+            EPHEMERAL_UPDATE,
+        );
+    }
+
+    #[test]
+    fn test_message_ids_unique() {
+        let names = [
+            TimeSnapshotMessage::get_full_name(),
+            ReadyMessage::get_full_name(),
+            NotReadyMessage::get_full_name(),
+            AddPlayerMessage::get_full_name(),
+            SceneMessage::get_full_name(),
+            CommandMessage::get_full_name(),
+            RpcMessage::get_full_name(),
+            SpawnMessage::get_full_name(),
+            ChangeOwnerMessage::get_full_name(),
+            ObjectSpawnStartedMessage::get_full_name(),
+            ObjectSpawnFinishedMessage::get_full_name(),
+            ObjectDestroyMessage::get_full_name(),
+            ObjectHideMessage::get_full_name(),
+            EntityStateMessage::get_full_name(),
+            NetworkPingMessage::get_full_name(),
+            NetworkPongMessage::get_full_name(),
+            ProtocolVersionMessage::get_full_name(),
+            ProtocolRejectMessage::get_full_name(),
+            ErrorMessage::get_full_name(),
+            DisconnectMessage::get_full_name(),
+            PauseMessage::get_full_name(),
+            BatchSpawnMessage::get_full_name(),
+            EphemeralSpawnMessage::get_full_name(),
+            EphemeralUpdateMessage::get_full_name(),
+            EphemeralDespawnMessage::get_full_name(),
+            QueuePositionMessage::get_full_name(),
+            InputMessage::get_full_name(),
+            InputAckMessage::get_full_name(),
+            AttachMessage::get_full_name(),
+            VoiceMessage::get_full_name(),
+            BlobChunkMessage::get_full_name(),
+            BlobAckMessage::get_full_name(),
+            TickSnapshotMessage::get_full_name(),
+            TickedEntityStateMessage::get_full_name(),
+            ScoreboardDeltaMessage::get_full_name(),
+            ScoreboardFullMessage::get_full_name(),
+            LoadoutOptionsMessage::get_full_name(),
+            LoadoutMessage::get_full_name(),
+            InterpolationHintMessage::get_full_name(),
+            SessionTokenMessage::get_full_name(),
+            SessionResumeMessage::get_full_name(),
+            SessionResumeResultMessage::get_full_name(),
+        ];
+        let collisions =
+            StableHashRegistry::find_collisions(names, |name| name.get_stable_hash_code16());
+        assert!(collisions.is_empty(), "{:?}", collisions);
+    }
+}
@@ -4,6 +4,7 @@ pub mod network_server;
 pub mod tools;
 pub mod network_time;
 pub mod network_writer;
+pub mod unity_types;
 pub mod snapshot_interpolation;
 pub mod backend_data;
 pub mod network_identity;
@@ -12,9 +13,34 @@ pub mod messages;
 
 mod network_writer_extensions;
 pub mod network_writer_pool;
-mod batching;
+pub(crate) mod batching;
 pub mod connection_quality;
+pub mod anti_cheat;
+pub mod ephemeral;
+pub mod master_server;
+pub mod world_snapshot;
+pub mod host_migration;
+pub mod net_id_allocator;
+pub mod network_events;
+pub mod input_buffer;
+pub mod network_attachment;
+pub mod voice_relay;
+pub mod blob_transfer;
+pub mod world_query;
+pub mod lag_compensation;
+pub mod hit_registration;
+pub mod network_scoreboard;
+pub mod scheduler;
+pub mod task_bridge;
+pub mod parallel_serialization;
+pub mod loadout_phase;
+pub mod unreliable_sequencing;
+pub mod interest_radius;
+pub mod network_context;
+pub mod sync_var_inspector;
+pub mod gameplay_events;
 pub mod network_reader;
+pub mod network_serialization;
 mod network_reader_extensions;
 pub mod remote_calls;
 pub mod network_reader_pool;
@@ -24,3 +50,15 @@ pub mod sync_object;
 pub mod network_loop;
 pub mod network_behaviour;
 pub mod network_start_position;
+pub mod overload_controller;
+pub mod region_streaming;
+pub mod steering;
+pub mod payload_decoder;
+pub mod sync_object_persistence;
+pub mod component_authority;
+pub mod interest_management;
+pub mod sync_var_events;
+pub mod network_client;
+pub mod network_connection_to_server;
+pub mod session_resume;
+pub mod outbound_interceptors;
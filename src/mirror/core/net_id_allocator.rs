@@ -0,0 +1,290 @@
+use crate::mirror::core::network_context::ContextLocal;
+use crate::mirror::core::network_server::NetworkServerStatic;
+use crate::{log_error, log_warn};
+use atomic::Atomic;
+use dashmap::DashMap;
+use lazy_static::lazy_static;
+use std::fmt::{Display, Formatter};
+use std::sync::atomic::Ordering;
+use std::sync::RwLock;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum NetIdError {
+    // 范围内的 id 已经用完
+    Exhausted,
+    // 与已预留的范围或已分配的默认 id 重叠
+    Overlap,
+    UnknownNamespace,
+    InvalidRange,
+}
+
+impl Display for NetIdError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NetIdError::Exhausted => write!(f, "net_id range exhausted"),
+            NetIdError::Overlap => write!(f, "net_id range overlaps an existing range"),
+            NetIdError::UnknownNamespace => write!(f, "unknown net_id namespace"),
+            NetIdError::InvalidRange => write!(f, "invalid net_id range"),
+        }
+    }
+}
+
+// [start, end) 内的 id, next 为下一个分配的 id
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct NetIdRange {
+    pub start: u32,
+    pub end: u32,
+    pub next: u32,
+}
+
+impl NetIdRange {
+    pub fn contains(&self, net_id: u32) -> bool {
+        net_id >= self.start && net_id < self.end
+    }
+    pub fn remaining(&self) -> u32 {
+        self.end - self.next
+    }
+    fn overlaps(&self, start: u32, end: u32) -> bool {
+        start < self.end && self.start < end
+    }
+}
+
+// NetIdAllocator 静态变量
+lazy_static! {
+    // 默认命名空间从 1 向上分配
+    static ref NEXT_NETWORK_ID: ContextLocal<Atomic<u32>> = ContextLocal::new(|| Atomic::new(1));
+    static ref NAMESPACES: ContextLocal<DashMap<String, NetIdRange>> =
+        ContextLocal::new(DashMap::new);
+    // 不为 None 时 NetworkServer 生成的对象从该命名空间分配
+    static ref ACTIVE_NAMESPACE: ContextLocal<RwLock<Option<String>>> =
+        ContextLocal::new(|| RwLock::new(None));
+}
+
+// net_id 分配器
+// 默认命名空间从 1 向上增长并跳过所有预留的范围, reserve 自动预留的范围从 u32::MAX 向下增长
+// 同样的预留顺序得到同样的范围, 每场比赛 / 每次恢复快照可以使用各自的命名空间
+pub struct NetIdAllocator;
+
+impl NetIdAllocator {
+    pub fn default_next() -> u32 {
+        NEXT_NETWORK_ID.load(Ordering::Relaxed)
+    }
+
+    pub fn set_default_next(net_id: u32) {
+        NEXT_NETWORK_ID.store(net_id, Ordering::Relaxed);
+    }
+
+    // 从高位自动预留 count 个 id
+    pub fn reserve(namespace: &str, count: u32) -> Result<NetIdRange, NetIdError> {
+        if count == 0 {
+            return Err(NetIdError::InvalidRange);
+        }
+        let ceiling = NAMESPACES
+            .iter()
+            .map(|range| range.start)
+            .min()
+            .unwrap_or(u32::MAX);
+        match ceiling.checked_sub(count) {
+            Some(start) if start >= Self::default_next() => {
+                Self::reserve_at(namespace, start, count)
+            }
+            _ => Err(NetIdError::Exhausted),
+        }
+    }
+
+    // 预留 [start, start + count), 用于需要固定 net_id 的场合
+    pub fn reserve_at(namespace: &str, start: u32, count: u32) -> Result<NetIdRange, NetIdError> {
+        let end = match start.checked_add(count) {
+            Some(end) if start != 0 && count != 0 => end,
+            _ => return Err(NetIdError::InvalidRange),
+        };
+        if NAMESPACES.contains_key(namespace) {
+            return Err(NetIdError::Overlap);
+        }
+        // 默认命名空间已经分配过的 id 不能再预留
+        if start < Self::default_next() || NAMESPACES.iter().any(|range| range.overlaps(start, end))
+        {
+            return Err(NetIdError::Overlap);
+        }
+        let range = NetIdRange {
+            start,
+            end,
+            next: start,
+        };
+        NAMESPACES.insert(namespace.to_string(), range);
+        Ok(range)
+    }
+
+    // 释放后范围内已生成的对象不受影响, 但它们的 id 可能被重新预留
+    pub fn release(namespace: &str) -> bool {
+        if let Ok(mut active) = ACTIVE_NAMESPACE.write() {
+            if active.as_deref() == Some(namespace) {
+                active.take();
+            }
+        }
+        NAMESPACES.remove(namespace).is_some()
+    }
+
+    pub fn range(namespace: &str) -> Option<NetIdRange> {
+        NAMESPACES.get(namespace).map(|range| *range)
+    }
+
+    pub fn namespace_of(net_id: u32) -> Option<String> {
+        NAMESPACES
+            .iter()
+            .find(|range| range.contains(net_id))
+            .map(|range| range.key().clone())
+    }
+
+    pub fn active_namespace() -> Option<String> {
+        match ACTIVE_NAMESPACE.read() {
+            Ok(active) => active.clone(),
+            Err(_) => None,
+        }
+    }
+
+    pub fn set_active_namespace(namespace: Option<&str>) -> Result<(), NetIdError> {
+        if let Some(namespace) = namespace {
+            if !NAMESPACES.contains_key(namespace) {
+                return Err(NetIdError::UnknownNamespace);
+            }
+        }
+        match ACTIVE_NAMESPACE.write() {
+            Ok(mut active) => {
+                *active = namespace.map(|namespace| namespace.to_string());
+            }
+            Err(e) => {
+                log_error!("NetIdAllocator failed to write ACTIVE_NAMESPACE: {:?}", e);
+            }
+        }
+        Ok(())
+    }
+
+    // 在活动命名空间 (没有时为默认命名空间) 中分配
+    pub fn allocate() -> Result<u32, NetIdError> {
+        match Self::active_namespace() {
+            Some(namespace) => Self::allocate_in(&namespace),
+            None => Self::allocate_default(),
+        }
+    }
+
+    pub fn allocate_in(namespace: &str) -> Result<u32, NetIdError> {
+        let mut range = match NAMESPACES.get_mut(namespace) {
+            Some(range) => range,
+            None => return Err(NetIdError::UnknownNamespace),
+        };
+        while range.next < range.end {
+            let net_id = range.next;
+            range.next += 1;
+            if !Self::is_spawned(net_id) {
+                return Ok(net_id);
+            }
+        }
+        Err(NetIdError::Exhausted)
+    }
+
+    fn allocate_default() -> Result<u32, NetIdError> {
+        loop {
+            let net_id = Self::default_next();
+            if net_id == 0 || net_id == u32::MAX {
+                return Err(NetIdError::Exhausted);
+            }
+            // 跳过预留的范围
+            let reserved_end = NAMESPACES
+                .iter()
+                .find(|range| range.contains(net_id))
+                .map(|range| range.end);
+            if let Some(end) = reserved_end {
+                Self::set_default_next(end);
+                continue;
+            }
+            Self::set_default_next(net_id + 1);
+            if !Self::is_spawned(net_id) {
+                return Ok(net_id);
+            }
+        }
+    }
+
+    fn is_spawned(net_id: u32) -> bool {
+        if NetworkServerStatic::spawned_network_identities().contains_key(&net_id) {
+            log_warn!(
+                "NetIdAllocator: netId {} is already spawned, skipping",
+                net_id
+            );
+            return true;
+        }
+        false
+    }
+
+    pub fn reset() {
+        Self::set_default_next(1);
+        NAMESPACES.clear();
+        if let Ok(mut active) = ACTIVE_NAMESPACE.write() {
+            active.take();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::core::network_identity::NetworkIdentity;
+    use crate::mirror::transports::memory::memory_transport::test_util::*;
+
+    #[test]
+    fn test_net_id_ranges() {
+        let a = NetIdRange {
+            start: 10,
+            end: 20,
+            next: 12,
+        };
+        assert!(a.contains(10) && a.contains(19) && !a.contains(20));
+        assert_eq!(a.remaining(), 8);
+        assert!(a.overlaps(19, 25));
+        assert!(!a.overlaps(20, 25));
+        assert!(!a.overlaps(0, 10));
+    }
+
+    #[test]
+    fn test_net_id_namespaces() {
+        with_server(|| {
+            NetIdAllocator::reset();
+            let high = NetIdAllocator::reserve("match-1", 4).unwrap();
+            assert_eq!((high.start, high.end), (u32::MAX - 4, u32::MAX));
+            let fixed = NetIdAllocator::reserve_at("match-2", 3, 2).unwrap();
+            assert_eq!(
+                NetIdAllocator::reserve_at("match-3", 4, 10),
+                Err(NetIdError::Overlap)
+            );
+            assert_eq!(
+                NetIdAllocator::set_active_namespace(Some("unknown")),
+                Err(NetIdError::UnknownNamespace)
+            );
+
+            // 默认命名空间跳过预留的 3..5, 也跳过已经生成的对象
+            let mut identity = NetworkIdentity::new_with_asset_id(1);
+            identity.set_net_id(6);
+            NetworkServerStatic::add_spawned_network_identity(identity);
+            let ids: Vec<u32> = (0..4)
+                .map(|_| NetworkIdentity::get_static_next_network_id())
+                .collect();
+            assert_eq!(ids, vec![1, 2, 5, 7]);
+
+            NetIdAllocator::set_active_namespace(Some("match-2")).unwrap();
+            assert_eq!(NetworkIdentity::get_static_next_network_id(), 3);
+            assert_eq!(NetworkIdentity::get_static_next_network_id(), 4);
+            assert_eq!(NetIdAllocator::allocate(), Err(NetIdError::Exhausted));
+            assert_eq!(NetworkIdentity::get_static_next_network_id(), 0);
+            assert_eq!(
+                NetIdAllocator::namespace_of(fixed.start).as_deref(),
+                Some("match-2")
+            );
+
+            assert!(NetIdAllocator::release("match-2"));
+            assert_eq!(NetIdAllocator::active_namespace(), None);
+            assert_eq!(NetIdAllocator::allocate_in("match-1"), Ok(u32::MAX - 4));
+            NetworkServerStatic::remove_spawned_network_identity(&6);
+            NetIdAllocator::reset();
+        });
+    }
+}
@@ -0,0 +1,385 @@
+use crate::log_error;
+use crate::mirror::components::network_transform::network_transform_reliable::NetworkTransformReliable;
+use crate::mirror::components::network_transform::network_transform_unreliable::NetworkTransformUnreliable;
+use crate::mirror::core::messages::AttachMessage;
+use crate::mirror::core::network_connection::NetworkConnectionTrait;
+use crate::mirror::core::network_context::ContextLocal;
+use crate::mirror::core::network_server::{NetworkServer, NetworkServerStatic, NETWORK_BEHAVIOURS};
+use crate::mirror::core::transport::TransportChannel;
+use dashmap::try_result::TryResult;
+use dashmap::DashMap;
+use lazy_static::lazy_static;
+use nalgebra::{UnitQuaternion, Vector3};
+use std::fmt::{Display, Formatter};
+use std::sync::RwLock;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum AttachError {
+    // 子对象或父对象没有生成
+    NotSpawned,
+    // 附着到自己或自己的子对象
+    Cycle,
+    Locked,
+}
+
+impl Display for AttachError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AttachError::NotSpawned => write!(f, "identity is not spawned"),
+            AttachError::Cycle => write!(f, "attachment would create a cycle"),
+            AttachError::Locked => write!(f, "identity is locked"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Attachment {
+    pub parent: u32,
+    // 父对象坐标系下的位置
+    pub local_offset: Vector3<f32>,
+    // 附着时把父对象的权限交给子对象的所有者 (驾驶员控制载具)
+    pub transfer_authority: bool,
+    // 附着前父对象的所有者, 分离时恢复
+    pub previous_parent_owner: u64,
+}
+
+// NetworkAttachment 静态变量
+lazy_static! {
+    // 子对象 net_id -> 附着信息
+    static ref ATTACHMENTS: ContextLocal<DashMap<u32, Attachment>> =
+        ContextLocal::new(DashMap::new);
+    // un_spawn 时可能持有 SPAWNED_NETWORK_IDENTITIES 的锁, 在 late_update 中再分离
+    static ref DESPAWNED: ContextLocal<RwLock<Vec<u32>>> =
+        ContextLocal::new(|| RwLock::new(Vec::new()));
+}
+
+// 载具 / 挂载: 附着的对象停止同步自己的 NetworkTransform, 位置跟随父对象
+// 可以选择把父对象的权限交给子对象的所有者, 分离或任意一方销毁时自动恢复
+pub struct NetworkAttachment;
+
+impl NetworkAttachment {
+    pub fn attach(
+        child: u32,
+        parent: u32,
+        local_offset: Vector3<f32>,
+        transfer_authority: bool,
+    ) -> Result<(), AttachError> {
+        let spawned = NetworkServerStatic::spawned_network_ids();
+        if !spawned.contains(&child) || !spawned.contains(&parent) {
+            return Err(AttachError::NotSpawned);
+        }
+        if child == parent || Self::root_of(parent) == child {
+            return Err(AttachError::Cycle);
+        }
+        if ATTACHMENTS.contains_key(&child) {
+            Self::detach(child);
+        }
+
+        let child_owner = match NetworkServerStatic::spawned_network_identities().try_get(&child) {
+            TryResult::Present(identity) => identity.connection_to_client(),
+            TryResult::Absent => return Err(AttachError::NotSpawned),
+            TryResult::Locked => return Err(AttachError::Locked),
+        };
+        let previous_parent_owner = if transfer_authority {
+            Self::transfer_owner(parent, child_owner)
+        } else {
+            0
+        };
+
+        ATTACHMENTS.insert(
+            child,
+            Attachment {
+                parent,
+                local_offset,
+                transfer_authority,
+                previous_parent_owner,
+            },
+        );
+        Self::set_transforms_enabled(child, false);
+        Self::follow(child);
+        Self::send_to_observers(child, AttachMessage::new(child, parent, local_offset));
+        Ok(())
+    }
+
+    // 分离并恢复父对象的所有者, 没有附着时返回 false
+    pub fn detach(child: u32) -> bool {
+        let attachment = match ATTACHMENTS.remove(&child) {
+            Some((_, attachment)) => attachment,
+            None => return false,
+        };
+        if attachment.transfer_authority {
+            Self::transfer_owner(attachment.parent, attachment.previous_parent_owner);
+        }
+        Self::set_transforms_enabled(child, true);
+        Self::send_to_observers(child, AttachMessage::new(child, 0, Vector3::zeros()));
+        true
+    }
+
+    pub fn attachment(child: u32) -> Option<Attachment> {
+        ATTACHMENTS.get(&child).map(|attachment| *attachment)
+    }
+
+    pub fn parent_of(child: u32) -> Option<u32> {
+        ATTACHMENTS.get(&child).map(|attachment| attachment.parent)
+    }
+
+    pub fn children_of(parent: u32) -> Vec<u32> {
+        ATTACHMENTS
+            .iter()
+            .filter(|attachment| attachment.parent == parent)
+            .map(|attachment| *attachment.key())
+            .collect()
+    }
+
+    fn root_of(mut net_id: u32) -> u32 {
+        while let Some(parent) = Self::parent_of(net_id) {
+            net_id = parent;
+        }
+        net_id
+    }
+
+    // 在 NetworkServer::un_spawn_internal 中调用
+    pub(crate) fn on_despawn(net_id: u32) {
+        match DESPAWNED.write() {
+            Ok(mut despawned) => despawned.push(net_id),
+            Err(e) => {
+                log_error!("NetworkAttachment failed to write DESPAWNED: {:?}", e);
+            }
+        }
+    }
+
+    // 在 NetworkServer::network_late_update 中 broadcast 之前调用
+    pub fn update() {
+        let despawned = match DESPAWNED.write() {
+            Ok(mut despawned) => std::mem::take(&mut *despawned),
+            Err(_) => Vec::new(),
+        };
+        for net_id in despawned {
+            // 被销毁的子对象不需要再通知客户端
+            if let Some((_, attachment)) = ATTACHMENTS.remove(&net_id) {
+                if attachment.transfer_authority {
+                    Self::transfer_owner(attachment.parent, attachment.previous_parent_owner);
+                }
+            }
+            for child in Self::children_of(net_id) {
+                Self::detach(child);
+            }
+        }
+
+        // 父对象可能也是子对象, 从根开始更新
+        let mut children: Vec<(u32, usize)> = ATTACHMENTS
+            .iter()
+            .map(|attachment| (*attachment.key(), Self::depth_of(*attachment.key())))
+            .collect();
+        children.sort_by_key(|(_, depth)| *depth);
+        for (child, _) in children {
+            Self::follow(child);
+        }
+    }
+
+    fn depth_of(mut net_id: u32) -> usize {
+        let mut depth = 0;
+        while let Some(parent) = Self::parent_of(net_id) {
+            net_id = parent;
+            depth += 1;
+        }
+        depth
+    }
+
+    // 子对象的位置 = 父对象的位置 + 父对象的旋转 * local_offset, 旋转与父对象一致
+    fn follow(child: u32) {
+        let attachment = match Self::attachment(child) {
+            Some(attachment) => attachment,
+            None => return,
+        };
+        let parent_transform =
+            match NetworkServerStatic::spawned_network_identities().try_get(&attachment.parent) {
+                TryResult::Present(identity) => identity.game_object().transform,
+                _ => return,
+            };
+        let rotation = UnitQuaternion::from_quaternion(parent_transform.local_rotation);
+        let position = parent_transform.local_position + rotation * attachment.local_offset;
+        if let TryResult::Present(mut identity) =
+            NetworkServerStatic::spawned_network_identities().try_get_mut(&child)
+        {
+            let mut game_object = identity.game_object().clone();
+            game_object.transform.position = position;
+            game_object.transform.local_position = position;
+            game_object.transform.rotation = parent_transform.local_rotation;
+            game_object.transform.local_rotation = parent_transform.local_rotation;
+            identity.set_game_object(game_object);
+        }
+    }
+
+    // 返回之前的所有者
+    fn transfer_owner(net_id: u32, new_owner: u64) -> u64 {
+        let old_owner = match NetworkServerStatic::spawned_network_identities().try_get_mut(&net_id)
+        {
+            TryResult::Present(mut identity) => {
+                let old_owner = identity.connection_to_client();
+                if old_owner == new_owner {
+                    return old_owner;
+                }
+                identity.remove_client_authority();
+                if new_owner != 0 {
+                    identity.set_connection_to_client(new_owner);
+                }
+                old_owner
+            }
+            TryResult::Absent => return 0,
+            TryResult::Locked => {
+                log_error!(
+                    "NetworkAttachment failed to transfer authority because identity {} is locked.",
+                    net_id
+                );
+                return 0;
+            }
+        };
+        if old_owner != 0 {
+            if let TryResult::Present(mut connection) =
+                NetworkServerStatic::network_connections().try_get_mut(&old_owner)
+            {
+                connection.remove_owned_object(net_id);
+            }
+        }
+        if new_owner != 0 {
+            NetworkServer::send_change_owner_message_for_net_id(new_owner, net_id);
+        }
+        old_owner
+    }
+
+    fn set_transforms_enabled(net_id: u32, enabled: bool) {
+        let count = match NetworkServerStatic::spawned_network_identities().try_get(&net_id) {
+            TryResult::Present(identity) => identity.network_behaviours_count,
+            _ => return,
+        };
+        for i in 0..count {
+            if let TryResult::Present(mut component) = NETWORK_BEHAVIOURS.try_get_mut(&(net_id, i))
+            {
+                let is_transform = component
+                    .as_any_mut()
+                    .downcast_mut::<NetworkTransformUnreliable>()
+                    .is_some()
+                    || component
+                        .as_any_mut()
+                        .downcast_mut::<NetworkTransformReliable>()
+                        .is_some();
+                if is_transform {
+                    component.set_enabled(enabled);
+                }
+            }
+        }
+    }
+
+    fn send_to_observers(net_id: u32, mut message: AttachMessage) {
+        let observers = match NetworkServerStatic::spawned_network_identities().try_get(&net_id) {
+            TryResult::Present(identity) => identity.observers().clone(),
+            _ => return,
+        };
+        for connection_id in observers.iter() {
+            if let TryResult::Present(mut connection) =
+                NetworkServerStatic::network_connections().try_get_mut(connection_id)
+            {
+                if connection.is_ready() {
+                    connection.send_network_message(&mut message, TransportChannel::Reliable);
+                }
+            }
+        }
+    }
+
+    pub fn reset() {
+        ATTACHMENTS.clear();
+        if let Ok(mut despawned) = DESPAWNED.write() {
+            despawned.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::core::network_behaviour::GameObject;
+    use crate::mirror::core::network_identity::{NetworkIdentity, Visibility};
+    use crate::mirror::transports::memory::memory_transport::test_util::*;
+    use crate::mirror::transports::memory::memory_transport::MemoryTransport;
+
+    #[test]
+    fn test_network_attachment() {
+        with_server(|| {
+            MemoryTransport::client_connect(1);
+            tick();
+            NetworkServer::set_client_ready(1);
+            // 300 载具 (没有所有者), 301 驾驶员
+            for (net_id, owner) in [(300, 0), (301, 1)] {
+                let mut identity = NetworkIdentity::new_with_asset_id(0);
+                identity.set_net_id(net_id);
+                identity.set_connection_to_client(owner);
+                identity.set_game_object(GameObject::new_with_prefab(net_id.to_string()));
+                NetworkServerStatic::add_spawned_network_identity(identity);
+                NetworkServer::set_visibility(net_id, Visibility::Default);
+            }
+            tick();
+            MemoryTransport::client_receive(1);
+
+            assert_eq!(
+                NetworkAttachment::attach(300, 300, Vector3::zeros(), true),
+                Err(AttachError::Cycle)
+            );
+            let offset = Vector3::new(0.0, 1.0, 0.0);
+            assert!(NetworkAttachment::attach(301, 300, offset, true).is_ok());
+            assert_eq!(
+                NetworkAttachment::attach(300, 301, Vector3::zeros(), false),
+                Err(AttachError::Cycle)
+            );
+            let owner_of = |net_id: u32| {
+                NetworkServerStatic::spawned_network_identities()
+                    .get(&net_id)
+                    .unwrap()
+                    .connection_to_client()
+            };
+            assert_eq!(owner_of(300), 1);
+            assert_eq!(NetworkAttachment::children_of(300), vec![301]);
+
+            // 驾驶员跟随载具
+            {
+                let mut vehicle = NetworkServerStatic::spawned_network_identities()
+                    .get_mut(&300)
+                    .unwrap();
+                let mut game_object = vehicle.game_object().clone();
+                game_object.transform.local_position = Vector3::new(10.0, 0.0, 5.0);
+                vehicle.set_game_object(game_object);
+            }
+            tick();
+            let position = NetworkServerStatic::spawned_network_identities()
+                .get(&301)
+                .unwrap()
+                .game_object()
+                .transform
+                .local_position;
+            assert_eq!(position, Vector3::new(10.0, 1.0, 5.0));
+            let attaches = received::<AttachMessage>(1);
+            assert_eq!(attaches, vec![AttachMessage::new(301, 300, offset)]);
+
+            assert!(NetworkAttachment::detach(301));
+            assert!(!NetworkAttachment::detach(301));
+            assert_eq!(owner_of(300), 0);
+
+            // 驾驶员销毁时自动分离并归还载具
+            assert!(NetworkAttachment::attach(301, 300, offset, true).is_ok());
+            {
+                let mut connection = NetworkServerStatic::network_connections()
+                    .get_mut(&1)
+                    .unwrap();
+                let mut driver = NetworkServerStatic::spawned_network_identities()
+                    .get_mut(&301)
+                    .unwrap();
+                NetworkServer::destroy(&mut connection, &mut driver);
+            }
+            tick();
+            assert_eq!(NetworkAttachment::parent_of(301), None);
+            assert_eq!(owner_of(300), 0);
+            NetworkServerStatic::remove_spawned_network_identity(&300);
+            NetworkAttachment::reset();
+        });
+    }
+}
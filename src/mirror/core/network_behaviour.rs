@@ -3,20 +3,26 @@ use crate::mirror::components::network_common_behaviour::NetworkCommonBehaviour;
 use crate::mirror::components::network_rigidbody::network_rigidbody_reliable::NetworkRigidbodyReliable;
 use crate::mirror::components::network_rigidbody::network_rigidbody_unreliable::NetworkRigidbodyUnreliable;
 use crate::mirror::components::network_room_player::NetworkRoomPlayer;
+use crate::mirror::components::network_transform::network_bone_sync::NetworkBoneSync;
 use crate::mirror::components::network_transform::network_transform_base::Transform;
 use crate::mirror::components::network_transform::network_transform_reliable::NetworkTransformReliable;
 use crate::mirror::components::network_transform::network_transform_unreliable::NetworkTransformUnreliable;
 use crate::mirror::core::backend_data::{
     BackendDataStatic, NetworkBehaviourComponent, NetworkBehaviourSetting,
 };
-use crate::mirror::core::messages::{EntityStateMessage, RpcMessage};
+use crate::mirror::core::messages::{EntityStateMessage, NetworkMessageTrait, RpcMessage};
 use crate::mirror::core::network_connection::NetworkConnectionTrait;
 use crate::mirror::core::network_identity::NetworkIdentity;
 use crate::mirror::core::network_reader::{NetworkReader, NetworkReaderTrait};
 use crate::mirror::core::network_server::NetworkServerStatic;
 use crate::mirror::core::network_time::NetworkTime;
 use crate::mirror::core::network_writer::{NetworkWriter, NetworkWriterTrait};
+use crate::mirror::core::network_writer_pool::NetworkWriterPool;
+use crate::mirror::core::overload_controller::OverloadController;
 use crate::mirror::core::sync_object::SyncObject;
+use crate::mirror::core::sync_var_inspector::SyncVarField;
+use crate::mirror::core::tools::bandwidth_report::BandwidthReport;
+use crate::mirror::core::tools::stable_hash::StableHash;
 use crate::mirror::core::transport::TransportChannel;
 use crate::{log_error, log_warn};
 use dashmap::try_result::TryResult;
@@ -82,6 +88,13 @@ impl NetworkBehaviourFactory {
                 Box::new(NetworkTransformReliable::new(game_object, component))
             },
         );
+        // NetworkBoneSync
+        Self::add_network_behaviour_factory(
+            NetworkBoneSync::COMPONENT_TAG.to_string(),
+            |game_object: GameObject, component: &NetworkBehaviourComponent| {
+                Box::new(NetworkBoneSync::new(game_object, component))
+            },
+        );
         // NetworkAnimator
         Self::add_network_behaviour_factory(
             NetworkAnimator::COMPONENT_TAG.to_string(),
@@ -99,6 +112,28 @@ impl NetworkBehaviourFactory {
     }
 }
 
+// NetworkBehaviour 引用, 对应 C# RPC 参数中的 NetworkBehaviour 及其子类
+// 组件保存在 NETWORK_BEHAVIOURS 中, 用 (net_id, component_index) 查找
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NetworkBehaviourRef {
+    pub net_id: u32,
+    pub component_index: u8,
+}
+
+impl NetworkBehaviourRef {
+    pub fn new(net_id: u32, component_index: u8) -> Self {
+        Self {
+            net_id,
+            component_index,
+        }
+    }
+
+    // NETWORK_BEHAVIOURS 的键
+    pub fn key(&self) -> (u32, u8) {
+        (self.net_id, self.component_index)
+    }
+}
+
 // GameObject
 #[derive(Debug, Clone)]
 pub struct GameObject {
@@ -106,16 +141,22 @@ pub struct GameObject {
     pub prefab: String,
     pub transform: Transform,
     pub active: bool,
+    pub tag: String,
+    // 0 ~ 31, 与 Unity 的 layer 相同
+    pub layer: u8,
 }
 
 // GameObject 的默认实现
 impl GameObject {
+    pub const UNTAGGED: &'static str = "Untagged";
     pub fn new_with_prefab(prefab: String) -> Self {
         Self {
             scene_name: "".to_string(),
             prefab,
             transform: Transform::default(),
             active: false,
+            tag: Self::UNTAGGED.to_string(),
+            layer: 0,
         }
     }
     pub fn new_with_scene_name(scene_name: String) -> Self {
@@ -124,6 +165,8 @@ impl GameObject {
             prefab: "".to_string(),
             transform: Transform::default(),
             active: false,
+            tag: Self::UNTAGGED.to_string(),
+            layer: 0,
         }
     }
     pub fn default() -> Self {
@@ -132,6 +175,8 @@ impl GameObject {
             prefab: "".to_string(),
             transform: Transform::default(),
             active: false,
+            tag: Self::UNTAGGED.to_string(),
+            layer: 0,
         }
     }
     pub fn is_has_component(&self) -> bool {
@@ -173,6 +218,18 @@ impl GameObject {
     pub fn set_active(&mut self, value: bool) {
         self.active = value;
     }
+    pub fn compare_tag(&self, tag: &str) -> bool {
+        self.tag == tag
+    }
+    pub fn set_tag(&mut self, tag: &str) {
+        self.tag = tag.to_string();
+    }
+    pub fn set_layer(&mut self, layer: u8) {
+        self.layer = layer.min(31);
+    }
+    pub fn in_layer_mask(&self, layer_mask: u32) -> bool {
+        layer_mask & (1 << self.layer) != 0
+    }
 }
 // GameObject 的 PartialEq 实现
 impl PartialEq for GameObject {
@@ -181,7 +238,7 @@ impl PartialEq for GameObject {
     }
 }
 
-#[derive(Debug, PartialOrd, PartialEq)]
+#[derive(Debug, PartialOrd, PartialEq, Clone, Copy)]
 pub enum SyncDirection {
     ServerToClient,
     ClientToServer,
@@ -195,14 +252,41 @@ impl SyncDirection {
             _ => SyncDirection::ServerToClient,
         }
     }
+
+    // 同步方向的检查都通过下面两个方法, 不要在组件中单独判断
+
+    // 服务器序列化的状态是否发送给所有者, ClientToServer 的所有者就是数据来源
+    pub fn sends_to_owner(&self) -> bool {
+        *self == SyncDirection::ServerToClient
+    }
+
+    // 客户端写入状态 (EntityStateMessage 和状态类 Command) 只接受所有者对 ClientToServer 组件的写入
+    pub fn accepts_client_write(&self, is_owner: bool) -> bool {
+        *self == SyncDirection::ClientToServer && is_owner
+    }
 }
 
-#[derive(Debug, PartialOrd, PartialEq)]
+#[derive(Debug, PartialOrd, PartialEq, Clone, Copy)]
 pub enum SyncMode {
     Observers,
     Owners,
 }
 
+impl SyncMode {
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            0 => SyncMode::Observers,
+            1 => SyncMode::Owners,
+            _ => SyncMode::Observers,
+        }
+    }
+
+    // Owners 组件的状态和 EntityStateMessage 只发送给所有者
+    pub fn sends_to_observers(&self) -> bool {
+        *self == SyncMode::Observers
+    }
+}
+
 #[derive(Debug)]
 pub struct NetworkBehaviour {
     pub sync_interval: f64,
@@ -223,6 +307,8 @@ pub struct NetworkBehaviour {
     pub sync_objects: Vec<Box<dyn SyncObject>>,
     pub sync_var_hook_guard: u64,
     pub run_start: bool,
+    // 禁用后不再调用 update / late_update, 也不再序列化
+    pub enabled: bool,
 }
 
 impl NetworkBehaviour {
@@ -236,7 +322,7 @@ impl NetworkBehaviour {
             sync_interval: 0.0,
             last_sync_time: 0.0,
             sync_direction: SyncDirection::from_u8(network_behaviour_setting.sync_direction),
-            sync_mode: SyncMode::Observers,
+            sync_mode: SyncMode::from_u8(network_behaviour_setting.sync_mode),
             index: component_index,
             sub_class,
             sync_var_dirty_bits: u64::MAX,
@@ -248,11 +334,19 @@ impl NetworkBehaviour {
             sync_objects: Default::default(),
             sync_var_hook_guard: 0,
             run_start: true,
+            enabled: true,
         }
     }
+    // 内置的 enabled 同步位, 使用 syncVarDirtyBits 的最高位
+    pub const ENABLED_DIRTY_BIT: u64 = 1 << 63;
     pub fn is_dirty(&self) -> bool {
-        self.sync_var_dirty_bits | self.sync_object_dirty_bits != 0u64
-            && NetworkTime::local_time() - self.last_sync_time > self.sync_interval
+        // 禁用时只有 enabled 本身的修改需要同步
+        let dirty = if self.enabled {
+            self.sync_var_dirty_bits | self.sync_object_dirty_bits != 0u64
+        } else {
+            self.sync_var_dirty_bits & Self::ENABLED_DIRTY_BIT != 0
+        };
+        dirty && NetworkTime::local_time() - self.last_sync_time > self.sync_interval
     }
     pub fn late_invoke(net_id: u32, game_object: GameObject) {
         match NetworkServerStatic::spawned_network_identities().try_get_mut(&net_id) {
@@ -260,10 +354,10 @@ impl NetworkBehaviour {
                 identity.set_game_object(game_object);
             }
             TryResult::Absent => {
-                log_error!(format!("Failed because identity {} is absent.", net_id));
+                log_error!("Failed because identity {} is absent.", net_id);
             }
             TryResult::Locked => {
-                log_error!(format!("Failed because identity {} is locked.", net_id));
+                log_error!("Failed because identity {} is locked.", net_id);
             }
         }
     }
@@ -271,6 +365,14 @@ impl NetworkBehaviour {
         let cleared = size & 0xFFFFFF00;
         cleared | safety as usize
     }
+    // count 个 SyncObject 对应的索引位
+    pub fn sync_objects_index_mask(count: usize) -> u64 {
+        if count >= 64 {
+            u64::MAX
+        } else {
+            (1u64 << count) - 1
+        }
+    }
     pub fn sync_var_equal<T>(a: &T, b: &T) -> bool
     where
         T: PartialEq,
@@ -305,7 +407,7 @@ pub trait NetworkBehaviourTrait: Any + Send + Sync + Debug {
     fn set_last_sync_time(&mut self, value: f64);
     fn sync_direction(&mut self) -> &SyncDirection;
     fn set_sync_direction(&mut self, value: SyncDirection);
-    fn sync_mode(&mut self) -> &SyncMode;
+    fn sync_mode(&self) -> &SyncMode;
     fn set_sync_mode(&mut self, value: SyncMode);
     fn index(&self) -> u8;
     fn set_index(&mut self, value: u8);
@@ -322,6 +424,17 @@ pub trait NetworkBehaviourTrait: Any + Send + Sync + Debug {
         self.__set_sync_object_dirty_bits(self.sync_object_dirty_bits() | value);
     }
     fn __set_sync_object_dirty_bits(&mut self, value: u64);
+    // 标记第 index 个 SyncObject 有修改
+    fn set_sync_object_dirty_bit(&mut self, index: usize) {
+        if index >= 64 {
+            log_error!(
+                "SyncObject index {} out of range, only 64 SyncObjects are supported.",
+                index
+            );
+            return;
+        }
+        self.set_sync_object_dirty_bits(1 << index);
+    }
     fn net_id(&self) -> u32;
     fn set_net_id(&mut self, value: u32);
     fn connection_to_client(&self) -> u64;
@@ -350,6 +463,19 @@ pub trait NetworkBehaviourTrait: Any + Send + Sync + Debug {
         }
     }
     fn __set_sync_var_hook_guard(&mut self, value: u64);
+    fn enabled(&self) -> bool;
+    // 运行时启用 / 禁用组件, 通过 ENABLED_DIRTY_BIT 同步到客户端
+    fn set_enabled(&mut self, value: bool) {
+        if self.enabled() == value {
+            return;
+        }
+        self.__set_enabled(value);
+        self.set_sync_var_dirty_bits(NetworkBehaviour::ENABLED_DIRTY_BIT);
+    }
+    fn __set_enabled(&mut self, value: bool);
+    fn enabled_dirty(&self) -> bool {
+        self.sync_var_dirty_bits() & NetworkBehaviour::ENABLED_DIRTY_BIT != 0
+    }
     fn set_sync_var_with_guard(&mut self, dirty_bit: u64) {
         self.set_sync_var_dirty_bits(dirty_bit);
         if self.get_sync_var_hook_guard(dirty_bit) {
@@ -366,7 +492,14 @@ pub trait NetworkBehaviourTrait: Any + Send + Sync + Debug {
         let header_position = writer.get_position();
         writer.write_byte(0);
         let content_position = writer.get_position();
-        self.on_serialize(writer, initial_state);
+        // 禁用的组件只在初始状态时序列化
+        if self.enabled() || initial_state {
+            self.on_serialize(writer, initial_state);
+        }
+        // enabled 写在块的末尾: 初始状态时组件被禁用, 或者 delta 中 ENABLED_DIRTY_BIT 被设置
+        if (initial_state && !self.enabled()) || (!initial_state && self.enabled_dirty()) {
+            writer.write_bool(self.enabled());
+        }
         let end_position = writer.get_position();
         writer.set_position(header_position);
         let size = (end_position - content_position) as u8;
@@ -384,7 +517,7 @@ pub trait NetworkBehaviourTrait: Any + Send + Sync + Debug {
         if initial_state {
             self.serialize_objects_all(writer);
         } else {
-            self.serialize_sync_object_delta(writer);
+            self.serialize_objects_delta(writer);
         }
     }
     fn serialize_objects_all(&mut self, writer: &mut NetworkWriter) {
@@ -392,11 +525,24 @@ pub trait NetworkBehaviourTrait: Any + Send + Sync + Debug {
             sync_object.on_serialize_all(writer);
         }
     }
-    fn serialize_sync_object_delta(&mut self, writer: &mut NetworkWriter) {
-        writer.write_ulong(self.sync_object_dirty_bits());
-        for i in 0..self.sync_objects().len() {
-            if self.sync_object_dirty_bits() & (1 << i) != 0 {
-                let sync_object = &mut self.sync_objects()[i];
+    // 有修改的 SyncObject 的掩码, 只包含实际存在的 SyncObject
+    fn sync_objects_dirty_mask(&mut self) -> u64 {
+        let mut mask = self.sync_object_dirty_bits();
+        for (i, sync_object) in self.sync_objects().iter().enumerate().take(64) {
+            if sync_object.is_dirty() {
+                mask |= 1 << i;
+            }
+        }
+        mask & NetworkBehaviour::sync_objects_index_mask(self.sync_objects().len())
+    }
+    // SerializeObjectsDelta
+    // 先写入 ulong 掩码, 再按索引顺序写入有修改的 SyncObject
+    // 修改记录在发送后由 clear_all_dirty_bits 清除
+    fn serialize_objects_delta(&mut self, writer: &mut NetworkWriter) {
+        let dirty = self.sync_objects_dirty_mask();
+        writer.write_ulong(dirty);
+        for (i, sync_object) in self.sync_objects().iter().enumerate().take(64) {
+            if dirty & (1 << i) != 0 {
                 sync_object.on_serialize_delta(writer);
             }
         }
@@ -415,13 +561,19 @@ pub trait NetworkBehaviourTrait: Any + Send + Sync + Debug {
 
         result = self.on_deserialize(reader, initial_state);
 
-        let size = reader.get_position() - chunk_start;
+        let mut size = reader.get_position() - chunk_start;
+        // 初始状态末尾的 enabled, 见 serialize (例如 WorldSnapshot 恢复时)
+        if initial_state && (size + 1) as u8 == safety {
+            self.__set_enabled(reader.read_bool());
+            size += 1;
+        }
         let size_hash = size as u8 & 0xFF;
         if size_hash != safety {
-            log_warn!(format!(
+            log_warn!(
                 "Deserialize failed. Size mismatch. Expected: {}, Received: {}",
-                size_hash, safety
-            ));
+                size_hash,
+                safety
+            );
             let corrected_size = NetworkBehaviour::error_correction(size, safety);
             reader.set_position(chunk_start + corrected_size);
             result = false;
@@ -437,7 +589,7 @@ pub trait NetworkBehaviourTrait: Any + Send + Sync + Debug {
         if initial_state {
             self.deserialize_objects_all(reader)
         } else {
-            self.deserialize_sync_object_delta(reader)
+            self.deserialize_objects_delta(reader)
         }
     }
     // deserializeObjectsAll
@@ -451,13 +603,20 @@ pub trait NetworkBehaviourTrait: Any + Send + Sync + Debug {
         }
         result
     }
-    // DeserializeSyncObjectDelta
-    fn deserialize_sync_object_delta(&mut self, reader: &mut NetworkReader) -> bool {
+    // DeserializeObjectsDelta
+    fn deserialize_objects_delta(&mut self, reader: &mut NetworkReader) -> bool {
         let mut result = true;
         let dirty = reader.read_ulong();
-        for i in 0..self.sync_objects().len() {
+        let count = self.sync_objects().len();
+        if dirty & !NetworkBehaviour::sync_objects_index_mask(count) != 0 {
+            log_warn!(
+                "DeserializeObjectsDelta failed. Dirty mask {:#x} has bits for non existing SyncObjects, count: {}",
+                dirty, count
+            );
+            return false;
+        }
+        for (i, sync_object) in self.sync_objects().iter_mut().enumerate().take(64) {
             if dirty & (1 << i) != 0 {
-                let sync_object = &mut self.sync_objects()[i];
                 let succ = sync_object.on_deserialize_delta(reader);
                 if !succ {
                     result = false;
@@ -479,6 +638,40 @@ pub trait NetworkBehaviourTrait: Any + Send + Sync + Debug {
         }
     }
     fn as_any_mut(&mut self) -> &mut dyn Any;
+    // 没有观察者时返回 false 并计入 rpc_suppressed_count, 调用者可以跳过 RPC 参数的序列化
+    fn has_rpc_observers(&self) -> bool {
+        if self.observers().is_empty() {
+            NetworkServerStatic::add_rpc_suppressed_count();
+            return false;
+        }
+        true
+    }
+    // ClientRpc, 每次调用可以选择通道: 参数按 T::serialize 写入, function_hash 由函数全名计算
+    // function_full_name 与 C# 一致, 例如 "System.Void Player::RpcHit(System.Int32)"
+    fn rpc<T: NetworkMessageTrait>(
+        &self,
+        function_full_name: &str,
+        args: &mut T,
+        channel: TransportChannel,
+        include_owner: bool,
+    ) where
+        Self: Sized,
+    {
+        if !self.has_rpc_observers() {
+            return;
+        }
+        NetworkWriterPool::get_return(|writer| {
+            args.serialize(writer);
+            self.send_rpc_internal_fn_hash(
+                function_full_name,
+                function_full_name.get_fn_stable_hash_code(),
+                writer,
+                channel,
+                include_owner,
+            );
+        });
+    }
+    // function_hash_code 为 get_stable_hash_code, 与之前一样只取低 16 位
     fn send_rpc_internal(
         &self,
         function_full_name: &str,
@@ -486,36 +679,77 @@ pub trait NetworkBehaviourTrait: Any + Send + Sync + Debug {
         writer: &NetworkWriter,
         channel: TransportChannel,
         include_owner: bool,
+    ) {
+        self.send_rpc_internal_fn_hash(
+            function_full_name,
+            function_hash_code as u16,
+            writer,
+            channel,
+            include_owner,
+        );
+    }
+    // function_hash_code 为 fn_hash!/get_fn_stable_hash_code 计算的 16 位 hash
+    fn send_rpc_internal_fn_hash(
+        &self,
+        function_full_name: &str,
+        function_hash_code: u16,
+        writer: &NetworkWriter,
+        channel: TransportChannel,
+        include_owner: bool,
     ) {
         if !NetworkServerStatic::active() {
-            log_error!(format!(
+            log_error!(
                 "RPC Function {} called without an active server.",
                 function_full_name
-            ));
+            );
             return;
         }
-        let mut rpc = RpcMessage::new(
-            self.net_id(),
-            self.index(),
-            function_hash_code as u16,
-            writer.to_bytes(),
-        );
+        // 没有观察者时不创建 RpcMessage
+        if !self.has_rpc_observers() {
+            return;
+        }
+        // 确认有可以接收的连接之后再创建 RpcMessage
+        let mut rpc: Option<RpcMessage> = None;
+        let mut sent = 0;
+        // 过载时限制每个 RPC 的接收者数量, 所有者不受限制
+        let fan_out_limit = OverloadController::rpc_fan_out_limit();
+        let mut capped = 0;
         self.observers().iter().for_each(
             |observer| match NetworkServerStatic::network_connections().try_get_mut(observer) {
                 TryResult::Present(mut conn_to_client) => {
                     let is_owner = conn_to_client.connection_id() == self.connection_to_client();
                     if (!is_owner || include_owner) && conn_to_client.is_ready() {
-                        conn_to_client.send_network_message(&mut rpc, channel);
+                        if !is_owner && sent >= fan_out_limit {
+                            capped += 1;
+                            return;
+                        }
+                        let rpc = rpc.get_or_insert_with(|| {
+                            RpcMessage::new(
+                                self.net_id(),
+                                self.index(),
+                                function_hash_code,
+                                writer.to_bytes(),
+                            )
+                        });
+                        conn_to_client.send_network_message(rpc, channel);
+                        sent += 1;
                     }
                 }
                 TryResult::Absent => {
-                    log_error!(format!("Failed because connection {} is absent.", observer));
+                    log_error!("Failed because connection {} is absent.", observer);
                 }
                 TryResult::Locked => {
-                    log_error!(format!("Failed because connection {} is locked.", observer));
+                    log_error!("Failed because connection {} is locked.", observer);
                 }
             },
         );
+        if rpc.is_none() {
+            NetworkServerStatic::add_rpc_suppressed_count();
+        }
+        if capped > 0 {
+            OverloadController::add_rpc_capped_count(capped);
+        }
+        self.record_bandwidth(writer.get_position(), sent as u64);
     }
     fn send_entity_internal(
         &self,
@@ -528,22 +762,45 @@ pub trait NetworkBehaviourTrait: Any + Send + Sync + Debug {
             return;
         }
         let mut entity_message = EntityStateMessage::new(self.net_id(), writer.to_bytes());
+        let to_observers = self.sync_mode().sends_to_observers();
+        let mut sent = 0;
         for observer in self.observers().iter() {
             match NetworkServerStatic::network_connections().try_get_mut(observer) {
                 TryResult::Present(mut conn_to_client) => {
                     let is_owner = conn_to_client.connection_id() == self.connection_to_client();
-                    if (!is_owner || include_owner) && conn_to_client.is_ready() {
+                    let receives = if is_owner {
+                        include_owner
+                    } else {
+                        to_observers
+                    };
+                    if receives && conn_to_client.is_ready() {
                         conn_to_client.send_network_message(&mut entity_message, channel);
+                        sent += 1;
                     }
                 }
                 TryResult::Absent => {
-                    log_error!(format!("Failed because connection {} is absent.", observer));
+                    log_error!("Failed because connection {} is absent.", observer);
                 }
                 TryResult::Locked => {
-                    log_error!(format!("Failed because connection {} is locked.", observer));
+                    log_error!("Failed because connection {} is locked.", observer);
                 }
             }
         }
+        self.record_bandwidth(writer.get_position(), sent);
+    }
+    // 记录到 BandwidthReport, 对象被锁定时 asset_id 记为 0
+    fn record_bandwidth(&self, bytes: usize, messages: u64) {
+        if !BandwidthReport::enabled() || messages == 0 {
+            return;
+        }
+        let asset_id =
+            match NetworkServerStatic::spawned_network_identities().try_get(&self.net_id()) {
+                TryResult::Present(identity) => identity.asset_id,
+                _ => 0,
+            };
+        BandwidthReport::record_messages(asset_id, self.index(), bytes, messages, || {
+            self.sub_class()
+        });
     }
     fn on_start_server(&mut self) {}
     fn on_stop_server(&mut self) {}
@@ -554,6 +811,103 @@ pub trait NetworkBehaviourTrait: Any + Send + Sync + Debug {
     fn late_update(&mut self) {}
     // SerializeSyncVars
     fn serialize_sync_vars(&mut self, writer: &mut NetworkWriter, initial_state: bool);
+    // 同步变量的名称、类型和当前值, 供 SyncVarInspector 使用, 默认没有注解
+    fn inspect_sync_vars(&self) -> Vec<SyncVarField> {
+        Vec::new()
+    }
     // DeserializeSyncVars
     fn deserialize_sync_vars(&mut self, reader: &mut NetworkReader, initial_state: bool) -> bool;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::core::network_server::{NetworkServer, NETWORK_BEHAVIOURS};
+    use crate::mirror::transports::memory::memory_transport::test_util::*;
+    use crate::mirror::transports::memory::memory_transport::MemoryTransport;
+
+    #[test]
+    fn test_rpc_channel_override() {
+        with_server(|| {
+            for conn_id in [1, 2] {
+                MemoryTransport::client_connect(conn_id);
+                tick();
+                NetworkServer::set_client_ready(conn_id);
+                MemoryTransport::client_receive(conn_id);
+            }
+            spawn_test_identity(500, 1, 1);
+            {
+                let mut behaviour = NETWORK_BEHAVIOURS.get_mut(&(500, 0)).unwrap();
+                behaviour.add_observer(1);
+                behaviour.add_observer(2);
+            }
+
+            let name = "System.Void Test::RpcHit(System.Int32)";
+            // rpc 需要具体类型, 每次调用时获取, 不在 tick 期间持有组件
+            let rpc = |channel: TransportChannel, include_owner: bool| {
+                NETWORK_BEHAVIOURS
+                    .get_mut(&(500, 0))
+                    .unwrap()
+                    .as_any_mut()
+                    .downcast_mut::<NetworkCommonBehaviour>()
+                    .unwrap()
+                    .rpc(name, &mut TestHit(7), channel, include_owner);
+            };
+            let expected =
+                RpcMessage::new(500, 0, name.get_stable_hash_code() as u16, vec![7, 0, 0, 0]);
+            let rpcs = |conn_id: u64| -> Vec<(RpcMessage, TransportChannel)> {
+                MemoryTransport::client_receive_messages(conn_id)
+                    .into_iter()
+                    .filter_map(|(message, channel)| {
+                        let mut reader = NetworkReader::new_with_bytes(message);
+                        if reader.read_ushort() == RpcMessage::get_hash_code() {
+                            Some((RpcMessage::deserialize(&mut reader), channel))
+                        } else {
+                            None
+                        }
+                    })
+                    .collect()
+            };
+
+            // 不发给所有者, 走 Unreliable 通道
+            rpc(TransportChannel::Unreliable, false);
+            tick();
+            assert!(rpcs(1).is_empty());
+            assert_eq!(
+                rpcs(2),
+                vec![(expected.clone(), TransportChannel::Unreliable)]
+            );
+
+            rpc(TransportChannel::Reliable, true);
+            tick();
+            for conn_id in [1, 2] {
+                assert_eq!(
+                    rpcs(conn_id),
+                    vec![(expected.clone(), TransportChannel::Reliable)]
+                );
+            }
+
+            // 旧的 i32 hash 发送相同的 RpcMessage
+            let mut writer = NetworkWriter::new();
+            TestHit(7).serialize(&mut writer);
+            NETWORK_BEHAVIOURS
+                .get(&(500, 0))
+                .unwrap()
+                .send_rpc_internal(
+                    name,
+                    name.get_stable_hash_code(),
+                    &writer,
+                    TransportChannel::Reliable,
+                    true,
+                );
+            tick();
+            for conn_id in [1, 2] {
+                assert_eq!(
+                    rpcs(conn_id),
+                    vec![(expected.clone(), TransportChannel::Reliable)]
+                );
+            }
+            NetworkServerStatic::remove_spawned_network_identity(&500);
+        });
+    }
+}
@@ -0,0 +1,396 @@
+use crate::mirror::core::messages::{NetworkMessageTrait, NetworkPingMessage, NetworkPongMessage};
+use crate::mirror::core::network_connection_to_server::NetworkConnectionToServer;
+use crate::mirror::core::network_context::{ContextCell, ContextLocal};
+use crate::mirror::core::network_messages::NetworkMessages;
+use crate::mirror::core::network_reader::NetworkReader;
+use crate::mirror::core::network_reader_pool::NetworkReaderPool;
+use crate::mirror::core::network_time::NetworkTime;
+use crate::mirror::core::transport::{
+    TransportCallback, TransportCallbackType, TransportChannel, TransportTrait,
+};
+use crate::{log_error, log_info, log_warn};
+use dashmap::DashMap;
+use lazy_static::lazy_static;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::RwLock;
+
+pub type NetworkClientMessageHandlerFunc = fn(&mut NetworkReader, TransportChannel);
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ConnectState {
+    None,
+    Connecting,
+    Connected,
+    Disconnected,
+}
+
+impl ConnectState {
+    pub fn from(value: u8) -> Self {
+        match value {
+            1 => ConnectState::Connecting,
+            2 => ConnectState::Connected,
+            3 => ConnectState::Disconnected,
+            _ => ConnectState::None,
+        }
+    }
+
+    pub fn to_u8(&self) -> u8 {
+        match self {
+            ConnectState::None => 0,
+            ConnectState::Connecting => 1,
+            ConnectState::Connected => 2,
+            ConnectState::Disconnected => 3,
+        }
+    }
+}
+
+// NetworkClient 静态变量
+lazy_static! {
+    // 客户端使用独立的 Transport 实例, 不影响服务器的 active_transport
+    static ref CLIENT_TRANSPORT: ContextLocal<ContextCell<Option<Box<dyn TransportTrait>>>> =
+        ContextLocal::new(|| ContextCell::new(None));
+    static ref CONNECTION: ContextLocal<RwLock<Option<NetworkConnectionToServer>>> =
+        ContextLocal::new(|| RwLock::new(None));
+    static ref STATE: ContextLocal<AtomicU8> = ContextLocal::new(|| AtomicU8::new(0));
+    static ref HANDLERS: ContextLocal<DashMap<u16, NetworkClientMessageHandlerFunc>> =
+        ContextLocal::new(DashMap::new);
+}
+
+// 客户端模式: 连接另一个 Mirror 服务器, 用于中继、跨服务器 NPC 或测试
+// 每个上下文一个连接, 与 NetworkServer 共用消息格式和批次格式, 由 NetworkLoop 在服务器之后更新
+// 自动回复服务器的 NetworkPingMessage, 其他消息 (包括握手和 ReadyMessage) 由使用者发送和注册处理
+pub struct NetworkClient;
+
+impl NetworkClient {
+    #[allow(warnings)]
+    fn transport() -> Option<&'static mut Box<dyn TransportTrait>> {
+        let cell: &'static ContextCell<_> = &CLIENT_TRANSPORT;
+        unsafe { (*cell.as_ptr()).as_mut() }
+    }
+
+    #[allow(warnings)]
+    fn set_transport(transport: Option<Box<dyn TransportTrait>>) {
+        unsafe {
+            *CLIENT_TRANSPORT.as_ptr() = transport;
+        }
+    }
+
+    pub fn state() -> ConnectState {
+        ConnectState::from(STATE.load(Ordering::Relaxed))
+    }
+
+    fn set_state(state: ConnectState) {
+        STATE.store(state.to_u8(), Ordering::Relaxed);
+    }
+
+    pub fn is_connected() -> bool {
+        Self::state() == ConnectState::Connected
+    }
+
+    // 连接远端服务器, transport 的客户端事件回调到 NetworkClient
+    // 已经在连接中或者 Transport 不支持客户端模式时返回 false
+    pub fn connect(mut transport: Box<dyn TransportTrait>, address: &str) -> bool {
+        match Self::state() {
+            ConnectState::Connecting | ConnectState::Connected => {
+                log_warn!(
+                    "NetworkClient.Connect: already connecting or connected, ignoring {}",
+                    address
+                );
+                return false;
+            }
+            ConnectState::None | ConnectState::Disconnected => {}
+        }
+        transport.set_transport_cb_fn(Self::transport_callback);
+        Self::register_handler::<NetworkPingMessage>(Self::on_ping);
+        let connection = NetworkConnectionToServer::new(
+            transport.get_batcher_threshold(TransportChannel::Reliable),
+            transport.get_batcher_threshold(TransportChannel::Unreliable),
+        );
+        match CONNECTION.write() {
+            Ok(mut current) => *current = Some(connection),
+            Err(e) => {
+                log_error!("NetworkClient.Connect failed to write CONNECTION: {:?}", e);
+                return false;
+            }
+        }
+        Self::set_transport(Some(transport));
+        Self::set_state(ConnectState::Connecting);
+        log_info!("NetworkClient.Connect: {}", address);
+        let connected =
+            Self::transport().is_some_and(|transport| transport.client_connect(address));
+        if !connected {
+            Self::on_disconnected();
+        }
+        connected
+    }
+
+    // 发出等待发送的消息后断开, Transport 在下一次 network_early_update 中释放
+    pub fn disconnect() {
+        match Self::state() {
+            ConnectState::Connecting | ConnectState::Connected => {}
+            ConnectState::None | ConnectState::Disconnected => return,
+        }
+        Self::flush();
+        if let Some(transport) = Self::transport() {
+            transport.client_disconnect();
+        }
+        Self::on_disconnected();
+    }
+
+    // 未连接时返回 false
+    pub fn send<T>(message: &mut T, channel: TransportChannel) -> bool
+    where
+        T: NetworkMessageTrait + Send,
+    {
+        if !Self::is_connected() {
+            log_error!("NetworkClient.Send: not connected.");
+            return false;
+        }
+        match CONNECTION.write() {
+            Ok(mut connection) => match connection.as_mut() {
+                Some(connection) => {
+                    connection.send_network_message(message, channel);
+                    true
+                }
+                None => false,
+            },
+            Err(e) => {
+                log_error!("NetworkClient.Send failed to write CONNECTION: {:?}", e);
+                false
+            }
+        }
+    }
+
+    pub fn bytes_sent() -> u64 {
+        match CONNECTION.read() {
+            Ok(connection) => connection
+                .as_ref()
+                .map_or(0, |connection| connection.bytes_sent()),
+            Err(_) => 0,
+        }
+    }
+
+    // 在 NetworkLoop::early_update 中调用
+    pub fn network_early_update() {
+        if let Some(transport) = Self::transport() {
+            transport.client_early_update();
+        }
+        // 回调中不能释放 Transport, 断开之后在这里释放
+        if Self::state() == ConnectState::Disconnected && Self::transport().is_some() {
+            Self::set_transport(None);
+        }
+    }
+
+    // 在 NetworkLoop::late_update 中调用
+    pub fn network_late_update() {
+        if Self::is_connected() {
+            Self::flush();
+        }
+        if let Some(transport) = Self::transport() {
+            transport.client_late_update();
+        }
+    }
+
+    fn flush() {
+        let transport = match Self::transport() {
+            Some(transport) => transport,
+            None => return,
+        };
+        if let Ok(mut connection) = CONNECTION.write() {
+            if let Some(connection) = connection.as_mut() {
+                connection.flush(|data, channel| transport.client_send(data, channel));
+            }
+        }
+    }
+
+    pub(crate) fn transport_callback(tcb: TransportCallback) {
+        match tcb.r#type {
+            TransportCallbackType::OnClientConnected => {
+                log_info!("Client.HandleConnect");
+                if Self::state() == ConnectState::Connecting {
+                    Self::set_state(ConnectState::Connected);
+                }
+            }
+            TransportCallbackType::OnClientDataReceived => {
+                Self::on_transport_data(tcb.data, tcb.channel)
+            }
+            TransportCallbackType::OnClientDisconnected => {
+                log_info!("Client.HandleDisconnect");
+                Self::on_disconnected();
+            }
+            TransportCallbackType::OnClientError => {
+                log_error!("Client.HandleError: error: {:?}", tcb.error);
+            }
+            _ => {
+                log_warn!(
+                    "NetworkClient ignoring server transport callback: {:?}",
+                    tcb.r#type
+                );
+            }
+        }
+    }
+
+    fn on_transport_data(data: Vec<u8>, channel: TransportChannel) {
+        let messages = match CONNECTION.write() {
+            Ok(mut connection) => match connection.as_mut() {
+                Some(connection) => connection.un_batch(data),
+                None => return,
+            },
+            Err(e) => {
+                log_error!("Client.HandleData failed to write CONNECTION: {:?}", e);
+                return;
+            }
+        };
+        let messages = match messages {
+            Some(messages) => messages,
+            None => {
+                log_warn!("Client.HandleData: failed to add un_batch. Disconnecting.");
+                Self::disconnect();
+                return;
+            }
+        };
+        for message in messages {
+            NetworkReaderPool::get_with_array_segment_return(&message, |reader| {
+                if reader.remaining() < NetworkMessages::ID_SIZE {
+                    log_warn!("Client.HandleData: message too short.");
+                    return;
+                }
+                let message_id = NetworkMessages::unpack_id(reader);
+                // 取出后释放, 处理程序中可以注册或者替换处理程序
+                let handler = HANDLERS.get(&message_id).map(|handler| *handler);
+                match handler {
+                    Some(handler) => handler(reader, channel),
+                    None => {
+                        log_warn!("Client.HandleData: unknown message id: {}", message_id);
+                    }
+                }
+            });
+        }
+    }
+
+    fn on_disconnected() {
+        Self::set_state(ConnectState::Disconnected);
+        if let Ok(mut connection) = CONNECTION.write() {
+            if let Some(mut connection) = connection.take() {
+                connection.cleanup();
+            }
+        }
+    }
+
+    fn on_ping(reader: &mut NetworkReader, _channel: TransportChannel) {
+        let message = NetworkPingMessage::deserialize(reader);
+        let local_time = NetworkTime::local_time();
+        let mut pong_message = NetworkPongMessage::new(
+            message.local_time,
+            local_time - message.local_time,
+            local_time - message.predicted_time_adjusted,
+        );
+        Self::send(&mut pong_message, TransportChannel::Reliable);
+    }
+
+    // 同一个消息只能有一个处理程序, 已经注册时替换
+    pub fn register_handler<T>(handler: NetworkClientMessageHandlerFunc)
+    where
+        T: NetworkMessageTrait + Send + Sync + 'static,
+    {
+        HANDLERS.insert(T::get_hash_code(), handler);
+    }
+
+    pub fn unregister_handler<T>()
+    where
+        T: NetworkMessageTrait + Send + Sync + 'static,
+    {
+        HANDLERS.remove(&T::get_hash_code());
+    }
+
+    // 断开连接, 释放 Transport, 清空处理程序
+    pub fn shutdown() {
+        Self::disconnect();
+        Self::set_transport(None);
+        Self::set_state(ConnectState::None);
+        HANDLERS.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::core::network_server::NetworkServerStatic;
+    use crate::mirror::core::transport::Transport;
+    use crate::mirror::transports::memory::memory_transport::test_util::*;
+    use crate::mirror::transports::memory::memory_transport::MemoryTransport;
+    use std::sync::atomic::AtomicU32;
+
+    static CLIENT_PONGS: AtomicU32 = AtomicU32::new(0);
+
+    fn on_client_pong(reader: &mut NetworkReader, _channel: TransportChannel) {
+        let message = NetworkPongMessage::deserialize(reader);
+        assert_eq!(message.local_time, 12.5);
+        CLIENT_PONGS.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[test]
+    fn test_network_client() {
+        with_server(|| {
+            let tick_all = || {
+                tick();
+                NetworkClient::network_early_update();
+                NetworkClient::network_late_update();
+            };
+            CLIENT_PONGS.store(0, Ordering::Relaxed);
+
+            // 0 保留给本地玩家
+            assert!(!NetworkClient::connect(
+                Box::new(MemoryTransport::default()),
+                "memory://0"
+            ));
+            assert_eq!(NetworkClient::state(), ConnectState::Disconnected);
+
+            // 客户端模式连接同一个上下文中的服务器
+            assert!(NetworkClient::connect(
+                Box::new(MemoryTransport::default()),
+                "memory://730"
+            ));
+            assert_eq!(NetworkClient::state(), ConnectState::Connecting);
+            assert!(!NetworkClient::send(
+                &mut NetworkPingMessage::new(12.5, 0.0),
+                TransportChannel::Reliable
+            ));
+            tick_all();
+            assert!(NetworkClient::is_connected());
+            assert!(NetworkServerStatic::network_connections().contains_key(&730));
+
+            // 使用相同的批次格式, 服务器回复 pong
+            NetworkClient::register_handler::<NetworkPongMessage>(on_client_pong);
+            assert!(NetworkClient::send(
+                &mut NetworkPingMessage::new(12.5, 0.0),
+                TransportChannel::Reliable
+            ));
+            tick_all();
+            assert!(NetworkClient::bytes_sent() > 0);
+            tick_all();
+            assert_eq!(CLIENT_PONGS.load(Ordering::Relaxed), 1);
+
+            // 客户端断开
+            NetworkClient::disconnect();
+            assert_eq!(NetworkClient::state(), ConnectState::Disconnected);
+            tick_all();
+            assert!(!NetworkServerStatic::network_connections().contains_key(&730));
+
+            // 服务器断开
+            assert!(NetworkClient::connect(
+                Box::new(MemoryTransport::default()),
+                "memory://731"
+            ));
+            tick_all();
+            assert!(NetworkClient::is_connected());
+            Transport::active_transport()
+                .unwrap()
+                .server_disconnect(731);
+            tick_all();
+            assert_eq!(NetworkClient::state(), ConnectState::Disconnected);
+            NetworkClient::shutdown();
+            assert_eq!(NetworkClient::state(), ConnectState::None);
+        });
+    }
+}
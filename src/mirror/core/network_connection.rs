@@ -3,6 +3,9 @@ use crate::mirror::core::messages::{NetworkMessageTrait, NetworkPingMessage};
 use crate::mirror::core::network_messages::NetworkMessages;
 use crate::mirror::core::network_time::NetworkTime;
 use crate::mirror::core::network_writer_pool::NetworkWriterPool;
+use crate::mirror::core::outbound_interceptors::OutboundInterceptors;
+use crate::mirror::core::tools::frame_report::FrameReports;
+use crate::mirror::core::tools::traffic_log::{TrafficDirection, TrafficLog};
 use crate::mirror::core::transport::{Transport, TransportChannel};
 use crate::{log_error, log_warn};
 use std::sync::RwLock;
@@ -21,6 +24,8 @@ pub struct NetworkConnection {
     owned: Vec<u32>,
     remote_time_stamp: f64,
     first_conn_loc_time_stamp: f64,
+    // 发送给传输层的总字节数
+    bytes_sent: u64,
 }
 
 pub trait NetworkConnectionTrait {
@@ -35,6 +40,7 @@ pub trait NetworkConnectionTrait {
     fn remote_time_stamp(&self) -> f64;
     fn set_remote_time_stamp(&mut self, time: f64);
     fn first_conn_loc_time_stamp(&self) -> f64;
+    fn bytes_sent(&self) -> u64;
     fn is_ready(&self) -> bool;
     fn set_ready(&mut self, ready: bool);
     fn is_authenticated(&self) -> bool;
@@ -43,23 +49,28 @@ pub trait NetworkConnectionTrait {
     fn authenticated_data(&mut self) -> &Option<Box<RwLock<dyn NetworkMessageTrait>>>;
     fn owned(&mut self) -> &mut Vec<u32>;
     fn set_owned(&mut self, owned: Vec<u32>);
-    fn send_network_message<T>(&mut self, message: &mut T, channel: TransportChannel)
+    // 返回交给 batcher 的字节数, 消息过大被丢弃时返回 0
+    fn send_network_message<T>(&mut self, message: &mut T, channel: TransportChannel) -> usize
     where
         T: NetworkMessageTrait + Send,
     {
-        NetworkWriterPool::get_return(|writer| {
+        let mut sent = 0;
+        NetworkWriterPool::get_return_for_message::<T, _>(|writer| {
             message.serialize(writer);
             let max = NetworkMessages::max_message_size(channel);
             if writer.get_position() > max {
-                log_error!("Message too large to send: ", writer.get_position());
+                log_error!("Message too large to send: {}", writer.get_position());
                 return;
             }
             self.send(writer.to_array_segment(), channel);
+            sent = writer.get_position();
         });
+        sent
     }
     fn send(&mut self, segment: &[u8], channel: TransportChannel);
     fn send_to_transport(&self, segment: Vec<u8>, channel: TransportChannel) {
         if let Some(transport) = Transport::active_transport() {
+            FrameReports::record_bytes_sent(segment.len());
             transport.server_send(self.connection_id(), segment, channel);
         }
     }
@@ -86,6 +97,11 @@ pub trait NetworkConnectionTrait {
 
 impl NetworkConnection {
     pub const LOCAL_CONNECTION_ID: i32 = 0;
+
+    // reliable + unreliable batcher 中等待发送的数据
+    pub fn batcher_memory_usage(&self) -> usize {
+        self.reliable_batcher.memory_usage() + self.unreliable_batcher.memory_usage()
+    }
 }
 
 impl NetworkConnectionTrait for NetworkConnection {
@@ -122,6 +138,7 @@ impl NetworkConnectionTrait for NetworkConnection {
             unreliable_batcher: Batcher::new(unreliable_batcher_threshold),
             last_ping_time: ts,
             first_conn_loc_time_stamp: NetworkTime::local_time(),
+            bytes_sent: 0,
         }
     }
 
@@ -165,6 +182,10 @@ impl NetworkConnectionTrait for NetworkConnection {
         self.first_conn_loc_time_stamp
     }
 
+    fn bytes_sent(&self) -> u64 {
+        self.bytes_sent
+    }
+
     fn is_ready(&self) -> bool {
         self.is_ready
     }
@@ -198,14 +219,20 @@ impl NetworkConnectionTrait for NetworkConnection {
     }
 
     fn send(&mut self, segment: &[u8], channel: TransportChannel) {
+        // 进入 batcher 之前经过拦截器, None 表示丢弃
+        let segment = match OutboundInterceptors::apply(self.id, channel, segment) {
+            Some(segment) => segment,
+            None => return,
+        };
+        TrafficLog::log(TrafficDirection::Send, self.id, channel, &segment);
         match channel {
             TransportChannel::Reliable => {
                 self.reliable_batcher
-                    .add_message(segment, NetworkTime::local_time());
+                    .add_message(&segment, NetworkTime::local_time());
             }
             TransportChannel::Unreliable => {
                 self.unreliable_batcher
-                    .add_message(segment, NetworkTime::local_time());
+                    .add_message(&segment, NetworkTime::local_time());
             }
         }
     }
@@ -215,11 +242,13 @@ impl NetworkConnectionTrait for NetworkConnection {
 
         NetworkWriterPool::get_return(|writer| {
             while self.reliable_batcher.get_batcher_writer(writer) {
+                self.bytes_sent += writer.get_position() as u64;
                 self.send_to_transport(writer.to_bytes(), TransportChannel::Reliable);
                 writer.reset();
             }
 
             while self.unreliable_batcher.get_batcher_writer(writer) {
+                self.bytes_sent += writer.get_position() as u64;
                 self.send_to_transport(writer.to_bytes(), TransportChannel::Unreliable);
                 writer.reset();
             }
@@ -1,19 +1,27 @@
-use crate::log_error;
-use crate::mirror::core::messages::NetworkMessageTrait;
+use crate::{log_error, log_warn};
+use crate::mirror::core::anti_cheat::{AntiCheat, CheatSignal};
+use crate::mirror::core::backend_data::SnapshotInterpolationSetting;
+use crate::mirror::core::messages::{
+    DisconnectReason, InterpolationHintMessage, NetworkMessageTrait,
+};
 use crate::mirror::core::network_connection::{NetworkConnection, NetworkConnectionTrait};
 use crate::mirror::core::network_identity::NetworkIdentity;
 use crate::mirror::core::network_manager::NetworkManagerStatic;
 use crate::mirror::core::network_server::{
-    NetworkServer, NetworkServerStatic, RemovePlayerOptions,
+    NetworkServer, NetworkServerStatic, RemoteTimestampPolicy, RemovePlayerOptions,
+};
+use crate::mirror::core::network_time::{
+    ClockOffsetEstimator, ExponentialMovingAverage, NetworkTime,
 };
-use crate::mirror::core::network_time::{ExponentialMovingAverage, NetworkTime};
 use crate::mirror::core::network_writer::NetworkWriter;
 use crate::mirror::core::snapshot_interpolation::snapshot_interpolation::SnapshotInterpolation;
 use crate::mirror::core::snapshot_interpolation::time_snapshot::TimeSnapshot;
+use crate::mirror::core::tools::alloc_audit::{AllocAudit, AllocSite};
 use crate::mirror::core::transport::{Transport, TransportChannel};
 use dashmap::try_result::TryResult;
 use ordered_float::OrderedFloat;
-use std::collections::BTreeMap;
+use std::any::{Any, TypeId};
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::sync::RwLock;
 
 pub struct NetworkConnectionToClient {
@@ -31,6 +39,30 @@ pub struct NetworkConnectionToClient {
     pub snapshots: BTreeMap<OrderedFloat<f64>, TimeSnapshot>,
     pub snapshot_buffer_size_limit: i32,
     pub _rtt: ExponentialMovingAverage,
+    pub clock_offset: ClockOffsetEstimator,
+    pub protocol_verified: bool,
+    // 客户端在协议握手中声明支持的功能
+    pub features: u32,
+    // 客户端最近收到的服务器 tick (FEATURE_STATE_TICK)
+    pub remote_tick: u32,
+    // 最近发送的 InterpolationHintMessage (FEATURE_INTERPOLATION_HINT)
+    pub interpolation_hint: Option<InterpolationHintMessage>,
+    pub disconnect_reason: Option<DisconnectReason>,
+    // 观战 / 管理员连接, 观察所有对象
+    pub observe_all: bool,
+    // 分帧发送的初始生成, 按优先级排序
+    pub pending_spawns: VecDeque<u32>,
+    pub spawn_streaming: bool,
+    // 本 tick 已发送的 SpawnMessage 字节数
+    pub spawn_bytes_sent: usize,
+    // 下一次发送 TimeSnapshotMessage 的时间, 每个连接单独计算
+    pub next_time_snapshot_time: f64,
+    // 第一个客户端时间戳与本地时间的差, 用于检查之后的时间戳
+    pub remote_time_offset: Option<f64>,
+    // 异常时间戳的次数
+    pub timestamp_violations: u32,
+    // 认证 / 兴趣管理 / 游戏逻辑附加的数据, 每个类型一份
+    ext: HashMap<TypeId, Box<dyn Any + Send + Sync>>,
 }
 impl Default for NetworkConnectionToClient {
     fn default() -> Self {
@@ -44,12 +76,26 @@ impl Default for NetworkConnectionToClient {
             drift_ema: ExponentialMovingAverage::new(60),
             delivery_time_ema: ExponentialMovingAverage::new(10),
             remote_timeline: ts,
-            remote_timescale: ts,
+            remote_timescale: 1.0,
             buffer_time_multiplier: 2.0,
             buffer_time: 0.0,
             snapshots: Default::default(),
             snapshot_buffer_size_limit: 64,
             _rtt: ExponentialMovingAverage::new(NetworkTime::PING_WINDOW_SIZE),
+            clock_offset: ClockOffsetEstimator::new(NetworkTime::CLOCK_OFFSET_WINDOW_SIZE),
+            protocol_verified: false,
+            features: 0,
+            remote_tick: 0,
+            interpolation_hint: None,
+            disconnect_reason: None,
+            observe_all: false,
+            pending_spawns: VecDeque::new(),
+            spawn_streaming: false,
+            spawn_bytes_sent: 0,
+            next_time_snapshot_time: 0.0,
+            remote_time_offset: None,
+            timestamp_violations: 0,
+            ext: HashMap::new(),
         }
     }
 }
@@ -65,17 +111,32 @@ impl NetworkConnectionTrait for NetworkConnectionToClient {
             drift_ema: ExponentialMovingAverage::new(60),
             delivery_time_ema: ExponentialMovingAverage::new(10),
             remote_timeline: ts,
-            remote_timescale: ts,
+            remote_timescale: 1.0,
             buffer_time_multiplier: 2.0,
             buffer_time: 0.0,
             snapshots: Default::default(),
             snapshot_buffer_size_limit: 64,
             _rtt: ExponentialMovingAverage::new(NetworkTime::PING_WINDOW_SIZE),
+            clock_offset: ClockOffsetEstimator::new(NetworkTime::CLOCK_OFFSET_WINDOW_SIZE),
+            protocol_verified: false,
+            features: 0,
+            remote_tick: 0,
+            interpolation_hint: None,
+            disconnect_reason: None,
+            observe_all: false,
+            pending_spawns: VecDeque::new(),
+            spawn_streaming: false,
+            spawn_bytes_sent: 0,
+            next_time_snapshot_time: 0.0,
+            remote_time_offset: None,
+            timestamp_violations: 0,
+            ext: HashMap::new(),
         };
         network_connection_to_client.buffer_time = NetworkServerStatic::send_interval() as f64
             * network_connection_to_client.buffer_time_multiplier;
         if let Some(transport) = Transport::active_transport() {
-            network_connection_to_client.address = transport.server_get_client_address(conn_id);
+            network_connection_to_client.address =
+                Transport::canonical_address(&transport.server_get_client_address(conn_id));
         }
         network_connection_to_client
     }
@@ -120,6 +181,10 @@ impl NetworkConnectionTrait for NetworkConnectionToClient {
         self.network_connection.first_conn_loc_time_stamp()
     }
 
+    fn bytes_sent(&self) -> u64 {
+        self.network_connection.bytes_sent()
+    }
+
     fn is_ready(&self) -> bool {
         self.network_connection.is_ready()
     }
@@ -172,8 +237,81 @@ impl NetworkConnectionTrait for NetworkConnectionToClient {
 }
 
 impl NetworkConnectionToClient {
+    // 客户端时间戳必须单调递增, 并且不能比服务器时间走得更快
+    // 按 NetworkServerStatic::remote_timestamp_policy 处理异常值, 返回 None 表示保留上一个时间戳
+    pub fn validate_remote_time_stamp(&mut self, remote_time: f64, local_time: f64) -> Option<f64> {
+        let policy = NetworkServerStatic::remote_timestamp_policy();
+        if policy == RemoteTimestampPolicy::Off {
+            return Some(remote_time);
+        }
+        if remote_time.is_finite() {
+            let Some(offset) = self.remote_time_offset else {
+                self.remote_time_offset = Some(remote_time - local_time);
+                return Some(remote_time);
+            };
+            let min = self.remote_time_stamp();
+            let max = (local_time + offset + NetworkServerStatic::remote_timestamp_tolerance())
+                .max(min);
+            if remote_time >= min && remote_time <= max {
+                return Some(remote_time);
+            }
+            self.on_timestamp_violation(remote_time);
+            match policy {
+                RemoteTimestampPolicy::Clamp => Some(remote_time.clamp(min, max)),
+                _ => None,
+            }
+        } else {
+            self.on_timestamp_violation(remote_time);
+            None
+        }
+    }
+
+    fn on_timestamp_violation(&mut self, remote_time: f64) {
+        if self.timestamp_violations == 0 {
+            log_warn!(
+                "Server: connection {} sent an implausible timestamp {}.",
+                self.connection_id(),
+                remote_time
+            );
+        }
+        self.timestamp_violations += 1;
+        AntiCheat::report(self.connection_id(), CheatSignal::ImpossibleTimestamp);
+    }
+
+    // batcher 和 rpc 批次占用的内存
+    pub fn batcher_memory_usage(&self) -> usize {
+        self.network_connection.batcher_memory_usage()
+            + self.reliable_rpcs_batch.capacity()
+            + self.unreliable_rpcs_batch.capacity()
+    }
+
+    // snapshot 缓冲区占用的内存
+    pub fn snapshots_memory_usage(&self) -> usize {
+        self.snapshots.len() * (size_of::<OrderedFloat<f64>>() + size_of::<TimeSnapshot>())
+    }
+
+    // 按 NetworkServerStatic::time_snapshot_interval 判断本次 broadcast 是否发送 TimeSnapshotMessage
+    pub fn time_snapshot_due(&mut self, local_time: f64) -> bool {
+        let interval = NetworkServerStatic::time_snapshot_interval();
+        if interval <= 0.0 {
+            return true;
+        }
+        if local_time < self.next_time_snapshot_time {
+            return false;
+        }
+        // 保持固定频率, 落后超过一个间隔时从现在重新开始
+        self.next_time_snapshot_time += interval;
+        if self.next_time_snapshot_time <= local_time {
+            self.next_time_snapshot_time = local_time + interval;
+        }
+        true
+    }
+
     pub fn on_time_snapshot(&mut self, snapshot: TimeSnapshot) {
-        if self.snapshots.len() >= self.snapshot_buffer_size_limit as usize {
+        let connection_id = self.connection_id();
+        if self.snapshots.len() >= self.snapshot_buffer_size_limit as usize
+            && !NetworkServer::on_snapshot_overflow(&mut self.snapshots, connection_id)
+        {
             return;
         }
 
@@ -188,10 +326,13 @@ impl NetworkConnectionToClient {
                 snapshot_settings.dynamic_adjustment_tolerance as f64,
             )
         }
+        // C# 中 bufferTime 是 sendInterval * bufferTimeMultiplier 的属性, 动态调整后需要重新计算
+        self.buffer_time =
+            NetworkServerStatic::send_interval() as f64 * self.buffer_time_multiplier;
 
         SnapshotInterpolation::insert_and_adjust(
             &mut self.snapshots,
-            self.snapshot_buffer_size_limit as usize,
+            snapshot_settings.buffer_limit,
             snapshot,
             &mut self.remote_timeline,
             &mut self.remote_timescale,
@@ -205,10 +346,60 @@ impl NetworkConnectionToClient {
             &mut self.delivery_time_ema,
         );
     }
+
+    // 这个连接建议的插值缓冲
+    // 服务器看不到客户端收到快照的间隔, 用 rtt 的标准差估算抖动
+    pub fn recommended_interpolation_hint(&self) -> InterpolationHintMessage {
+        let settings = if NetworkManagerStatic::network_manager_singleton_exists() {
+            NetworkManagerStatic::network_manager_singleton()
+                .snapshot_interpolation_settings()
+                .clone()
+        } else {
+            SnapshotInterpolationSetting::default()
+        };
+        let send_interval = NetworkServerStatic::send_interval() as f64;
+        let buffer_time_multiplier = if settings.dynamic_adjustment {
+            SnapshotInterpolation::dynamic_adjustment(
+                send_interval,
+                self._rtt.standard_deviation,
+                settings.dynamic_adjustment_tolerance as f64,
+            )
+        } else {
+            settings.buffer_time_multiplier
+        };
+        InterpolationHintMessage::new(
+            send_interval,
+            buffer_time_multiplier,
+            settings.buffer_limit as u32,
+        )
+    }
+
+    // 只发送给支持 FEATURE_INTERPOLATION_HINT 的客户端
+    // force 为 false 时只在发送间隔、缓冲上限变化或者倍数变化超过 0.25 时发送, 避免每次 pong 都发送
+    pub fn send_interpolation_hint(&mut self, force: bool) -> bool {
+        if self.features & NetworkServer::FEATURE_INTERPOLATION_HINT == 0 {
+            return false;
+        }
+        let mut hint = self.recommended_interpolation_hint();
+        if !force {
+            if let Some(last) = self.interpolation_hint {
+                if last.send_interval == hint.send_interval
+                    && last.buffer_limit == hint.buffer_limit
+                    && (last.buffer_time_multiplier - hint.buffer_time_multiplier).abs() < 0.25
+                {
+                    return false;
+                }
+            }
+        }
+        self.interpolation_hint = Some(hint);
+        self.send_network_message(&mut hint, TransportChannel::Reliable);
+        true
+    }
+
     pub fn update_time_interpolation(&mut self) {
         if self.snapshots.len() > 0 {
             SnapshotInterpolation::step_time(
-                NetworkTime::get_ping_interval(),
+                NetworkTime::delta_time(),
                 &mut self.remote_timeline,
                 self.remote_timescale,
             );
@@ -217,6 +408,11 @@ impl NetworkConnectionToClient {
         }
     }
     pub fn add_to_observing(&mut self, identity: &mut NetworkIdentity) {
+        AllocAudit::record_growth(
+            AllocSite::ObserverGrowth,
+            self.observing.len(),
+            self.observing.capacity(),
+        );
         self.observing.push(identity.net_id());
         NetworkServer::show_for_connection(identity, self);
     }
@@ -234,16 +430,16 @@ impl NetworkConnectionToClient {
                     identity.remove_observer(conn_id);
                 }
                 TryResult::Absent => {
-                    log_error!(format!(
+                    log_error!(
                         "RemoveFromObservingsObservers: identity not found for net_id: {}",
                         net_id
-                    ));
+                    );
                 }
                 TryResult::Locked => {
-                    log_error!(format!(
+                    log_error!(
                         "RemoveFromObservingsObservers: identity is locked for net_id: {}",
                         net_id
-                    ));
+                    );
                 }
             }
         }
@@ -257,8 +453,12 @@ impl NetworkConnectionToClient {
         self.owned().retain(|id| *id != net_id);
     }
 
+    // 先销毁附属对象 (宠物 / 召唤物), 最后销毁玩家, 附属对象销毁时玩家仍然存在
     pub fn destroy_owned_objects(&mut self) {
-        for owned_net_id in self.owned().to_vec().iter() {
+        let player_net_id = self.net_id();
+        let mut owned = self.owned().to_vec();
+        owned.sort_by_key(|net_id| *net_id == player_net_id);
+        for owned_net_id in owned.iter() {
             if *owned_net_id != 0 {
                 // 记录当前的scene_id 避免 remove_player_for_connection 内再次 get_mut(&net_id) 造成死锁
                 let mut scene_id = 0;
@@ -270,6 +470,10 @@ impl NetworkConnectionToClient {
                             0 => {
                                 NetworkServer::destroy(self, &mut identity);
                             }
+                            // 场景中的附属对象只移除所有权
+                            _ if *owned_net_id != player_net_id => {
+                                identity.set_connection_to_client(0);
+                            }
                             // 如果scene_id不为0，记录scene_id
                             _ => {
                                 scene_id = identity.scene_id;
@@ -277,16 +481,16 @@ impl NetworkConnectionToClient {
                         }
                     }
                     TryResult::Absent => {
-                        log_error!(format!(
+                        log_error!(
                             "DestroyOwnedObjects: identity not found for net_id: {}",
                             owned_net_id
-                        ));
+                        );
                     }
                     TryResult::Locked => {
-                        log_error!(format!(
+                        log_error!(
                             "DestroyOwnedObjects: identity is locked for net_id: {}",
                             owned_net_id
-                        ));
+                        );
                     }
                 }
                 // 如果scene_id不为0，移除player
@@ -311,4 +515,95 @@ impl NetworkConnectionToClient {
             NetworkServer::hide_for_connection(self, identity);
         }
     }
+
+    // 附加类型为 T 的数据, 返回之前的值
+    pub fn insert_ext<T: Any + Send + Sync>(&mut self, value: T) -> Option<T> {
+        self.ext
+            .insert(TypeId::of::<T>(), Box::new(value))
+            .and_then(|old| old.downcast::<T>().ok())
+            .map(|old| *old)
+    }
+
+    pub fn get_ext<T: Any + Send + Sync>(&self) -> Option<&T> {
+        self.ext
+            .get(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_ref::<T>())
+    }
+
+    pub fn get_ext_mut<T: Any + Send + Sync>(&mut self) -> Option<&mut T> {
+        self.ext
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_mut::<T>())
+    }
+
+    pub fn remove_ext<T: Any + Send + Sync>(&mut self) -> Option<T> {
+        self.ext
+            .remove(&TypeId::of::<T>())
+            .and_then(|value| value.downcast::<T>().ok())
+            .map(|value| *value)
+    }
+
+    pub fn has_ext<T: Any + Send + Sync>(&self) -> bool {
+        self.ext.contains_key(&TypeId::of::<T>())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::transports::memory::memory_transport::test_util::with_isolated_context;
+
+    #[derive(Debug, PartialEq)]
+    struct Team(u8);
+
+    #[derive(Debug, PartialEq)]
+    struct Account {
+        name: String,
+    }
+
+    #[test]
+    fn test_ext() {
+        let mut conn = NetworkConnectionToClient::default();
+        assert_eq!(conn.get_ext::<Team>(), None);
+        assert_eq!(conn.insert_ext(Team(1)), None);
+        conn.insert_ext(Account {
+            name: "rust".to_string(),
+        });
+        assert_eq!(conn.insert_ext(Team(2)), Some(Team(1)));
+
+        conn.get_ext_mut::<Team>().unwrap().0 += 1;
+        assert_eq!(conn.get_ext::<Team>(), Some(&Team(3)));
+        assert_eq!(conn.get_ext::<Account>().unwrap().name, "rust");
+
+        assert_eq!(conn.remove_ext::<Team>(), Some(Team(3)));
+        assert!(!conn.has_ext::<Team>());
+        assert!(conn.has_ext::<Account>());
+    }
+
+    #[test]
+    fn test_validate_remote_time_stamp() {
+        with_isolated_context(|| {
+            let mut conn = NetworkConnectionToClient::default();
+            // 默认不检查
+            assert_eq!(conn.validate_remote_time_stamp(-5.0, 10.0), Some(-5.0));
+
+            NetworkServerStatic::set_remote_timestamp_policy(RemoteTimestampPolicy::Clamp);
+            // 客户端时间比服务器时间早 90 秒
+            assert_eq!(conn.validate_remote_time_stamp(100.0, 10.0), Some(100.0));
+            conn.set_remote_time_stamp(100.0);
+            assert_eq!(conn.validate_remote_time_stamp(100.5, 10.5), Some(100.5));
+            conn.set_remote_time_stamp(100.5);
+            // 倒退
+            assert_eq!(conn.validate_remote_time_stamp(99.0, 11.0), Some(100.5));
+            // 走得太快, 限制在 offset + tolerance
+            assert_eq!(conn.validate_remote_time_stamp(150.0, 11.0), Some(102.0));
+            assert_eq!(conn.validate_remote_time_stamp(f64::NAN, 11.0), None);
+            assert_eq!(conn.timestamp_violations, 3);
+
+            NetworkServerStatic::set_remote_timestamp_policy(RemoteTimestampPolicy::Reject);
+            assert_eq!(conn.validate_remote_time_stamp(150.0, 11.0), None);
+            assert_eq!(conn.validate_remote_time_stamp(101.0, 11.0), Some(101.0));
+            assert_eq!(conn.timestamp_violations, 4);
+        });
+    }
 }
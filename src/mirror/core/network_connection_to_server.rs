@@ -0,0 +1,96 @@
+use crate::mirror::core::batching::batcher::Batcher;
+use crate::mirror::core::batching::un_batcher::UnBatcher;
+use crate::mirror::core::messages::NetworkMessageTrait;
+use crate::mirror::core::network_time::NetworkTime;
+use crate::mirror::core::network_writer_pool::NetworkWriterPool;
+use crate::mirror::core::transport::TransportChannel;
+
+// NetworkClient 到远端服务器的连接, 与 NetworkConnectionToClient 使用相同的批次格式
+pub struct NetworkConnectionToServer {
+    reliable_batcher: Batcher,
+    unreliable_batcher: Batcher,
+    un_batcher: UnBatcher,
+    last_message_time: f64,
+    // 发送给传输层的总字节数
+    bytes_sent: u64,
+}
+
+impl NetworkConnectionToServer {
+    pub fn new(reliable_batcher_threshold: usize, unreliable_batcher_threshold: usize) -> Self {
+        Self {
+            reliable_batcher: Batcher::new(reliable_batcher_threshold),
+            unreliable_batcher: Batcher::new(unreliable_batcher_threshold),
+            un_batcher: UnBatcher::new(),
+            last_message_time: NetworkTime::local_time(),
+            bytes_sent: 0,
+        }
+    }
+
+    pub fn last_message_time(&self) -> f64 {
+        self.last_message_time
+    }
+
+    pub fn bytes_sent(&self) -> u64 {
+        self.bytes_sent
+    }
+
+    pub fn send_network_message<T>(&mut self, message: &mut T, channel: TransportChannel)
+    where
+        T: NetworkMessageTrait + Send,
+    {
+        NetworkWriterPool::get_return_for_message::<T, _>(|writer| {
+            message.serialize(writer);
+            self.send(writer.to_array_segment(), channel);
+        });
+    }
+
+    pub fn send(&mut self, segment: &[u8], channel: TransportChannel) {
+        match channel {
+            TransportChannel::Reliable => {
+                self.reliable_batcher
+                    .add_message(segment, NetworkTime::local_time());
+            }
+            TransportChannel::Unreliable => {
+                self.unreliable_batcher
+                    .add_message(segment, NetworkTime::local_time());
+            }
+        }
+    }
+
+    // 在 NetworkClient::network_late_update 中调用, 把等待发送的批次交给 send
+    pub(crate) fn flush<F: FnMut(Vec<u8>, TransportChannel)>(&mut self, mut send: F) {
+        NetworkWriterPool::get_return(|writer| {
+            while self.reliable_batcher.get_batcher_writer(writer) {
+                self.bytes_sent += writer.get_position() as u64;
+                send(writer.to_bytes(), TransportChannel::Reliable);
+                writer.reset();
+            }
+
+            while self.unreliable_batcher.get_batcher_writer(writer) {
+                self.bytes_sent += writer.get_position() as u64;
+                send(writer.to_bytes(), TransportChannel::Unreliable);
+                writer.reset();
+            }
+        });
+    }
+
+    // 拆包收到的批次, 批次无效时返回 None
+    // 消息先复制出来, 处理消息时可以再次访问连接
+    pub(crate) fn un_batch(&mut self, data: Vec<u8>) -> Option<Vec<Vec<u8>>> {
+        if !self.un_batcher.add_batch_with_bytes(data) {
+            return None;
+        }
+        let mut messages = Vec::new();
+        while let Some((message, _)) = self.un_batcher.get_next_message() {
+            messages.push(message.to_vec());
+        }
+        self.last_message_time = NetworkTime::local_time();
+        Some(messages)
+    }
+
+    pub fn cleanup(&mut self) {
+        self.reliable_batcher.clear();
+        self.unreliable_batcher.clear();
+        self.un_batcher.clear();
+    }
+}
@@ -0,0 +1,163 @@
+use dashmap::DashMap;
+use lazy_static::lazy_static;
+use std::cell::{Cell, UnsafeCell};
+use std::ops::Deref;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread::{self, JoinHandle};
+
+// NetworkContext 静态变量
+lazy_static! {
+    static ref NEXT_CONTEXT_ID: AtomicUsize = AtomicUsize::new(1);
+}
+
+thread_local! {
+    static CURRENT: Cell<usize> = const { Cell::new(NetworkContext::DEFAULT) };
+}
+
+// 服务器实例上下文: 每个上下文有独立的 NetworkServer / NetworkManager / Transport 和各个子系统的状态,
+// 同一个进程中可以同时运行多个互不影响的服务器, 例如两张地图
+// 上下文按线程生效, 没有进入任何上下文的线程使用默认上下文
+// TaskBridge、ParallelSerialization 和 Transport 接收线程会带上提交任务时的上下文
+// 进程级的状态仍然共享: NetworkLoop 的函数注册和 stop 信号、RemoteProcedureCalls、NetworkBehaviour 工厂、对象池
+pub struct NetworkContext;
+
+impl NetworkContext {
+    pub const DEFAULT: usize = 0;
+
+    // 上下文创建后不会释放, 它的状态在整个进程中保留
+    pub fn create() -> usize {
+        NEXT_CONTEXT_ID.fetch_add(1, Ordering::Relaxed)
+    }
+
+    pub fn current() -> usize {
+        CURRENT.with(|current| current.get())
+    }
+
+    // 在 context 中执行 func, 返回或者 panic 后恢复之前的上下文
+    pub fn enter<R, F: FnOnce() -> R>(context: usize, func: F) -> R {
+        struct Restore(usize);
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                CURRENT.with(|current| current.set(self.0));
+            }
+        }
+        let _restore = Restore(CURRENT.with(|current| current.replace(context)));
+        func()
+    }
+
+    // 创建一个新的上下文并在新线程中运行 func, 通常在 func 中初始化 NetworkManager 并调用 NetworkLoop::run
+    pub fn spawn<F: FnOnce() + Send + 'static>(
+        name: &str,
+        func: F,
+    ) -> std::io::Result<(usize, JoinHandle<()>)> {
+        let context = Self::create();
+        let handle = thread::Builder::new()
+            .name(name.to_string())
+            .spawn(move || Self::enter(context, func))?;
+        Ok((context, handle))
+    }
+}
+
+// 每个上下文一份的全局变量, 在 lazy_static 中使用, 通过 Deref 访问当前上下文的值
+// 默认上下文的值直接保存, 其他上下文第一次访问时用 init 创建
+pub struct ContextLocal<T: 'static> {
+    init: fn() -> T,
+    default: T,
+    contexts: DashMap<usize, &'static T>,
+}
+
+impl<T: Sync + 'static> ContextLocal<T> {
+    pub fn new(init: fn() -> T) -> Self {
+        Self {
+            init,
+            default: init(),
+            contexts: DashMap::new(),
+        }
+    }
+
+    // 不作为方法, 避免遮盖 T 的同名方法
+    fn instance(this: &Self) -> &T {
+        let context = NetworkContext::current();
+        if context == NetworkContext::DEFAULT {
+            return &this.default;
+        }
+        if let Some(value) = this.contexts.get(&context) {
+            return *value;
+        }
+        *this
+            .contexts
+            .entry(context)
+            .or_insert_with(|| Box::leak(Box::new((this.init)())))
+    }
+}
+
+impl<T: Sync + 'static> Deref for ContextLocal<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        Self::instance(self)
+    }
+}
+
+// 替代 static mut 的单例, 和原来一样只在所属上下文的网络循环线程中访问
+pub struct ContextCell<T>(UnsafeCell<T>);
+
+unsafe impl<T> Sync for ContextCell<T> {}
+unsafe impl<T> Send for ContextCell<T> {}
+
+impl<T> ContextCell<T> {
+    pub fn new(value: T) -> Self {
+        Self(UnsafeCell::new(value))
+    }
+
+    pub fn as_ptr(&self) -> *mut T {
+        self.0.get()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::core::network_identity::NetworkIdentity;
+    use crate::mirror::core::network_server::{NetworkServer, NetworkServerStatic};
+    use crate::mirror::core::transport::TransportTrait;
+    use crate::mirror::transports::memory::memory_transport::test_util::*;
+    use crate::mirror::transports::memory::memory_transport::MemoryTransport;
+
+    #[test]
+    fn test_network_context() {
+        with_server(|| {
+            MemoryTransport::client_connect(1);
+            tick();
+            let net_id = NetworkIdentity::get_static_next_network_id();
+            let mut identity = NetworkIdentity::new_with_asset_id(1);
+            identity.set_net_id(net_id);
+            NetworkServerStatic::add_spawned_network_identity(identity);
+
+            // 第二个服务器实例有自己的 Transport、连接、对象和 net_id 计数
+            let context = NetworkContext::create();
+            NetworkContext::enter(context, || {
+                assert!(!NetworkServerStatic::active());
+                assert!(NetworkServerStatic::spawned_network_identities().is_empty());
+                MemoryTransport::awake();
+                NetworkServer::listen(16);
+                MemoryTransport::client_connect(1);
+                MemoryTransport::client_connect(2);
+                tick();
+                assert_eq!(NetworkServerStatic::network_connections().len(), 2);
+                assert_eq!(NetworkIdentity::get_static_next_network_id(), 1);
+                NetworkServer::pause();
+            });
+            assert_eq!(NetworkServerStatic::network_connections().len(), 1);
+            assert_eq!(NetworkServerStatic::spawned_network_identities().len(), 1);
+            assert!(!NetworkServerStatic::paused());
+
+            // 关闭第二个实例不影响默认实例
+            NetworkContext::enter(context, NetworkServer::shutdown);
+            assert!(NetworkServerStatic::active());
+            assert!(NetworkServerStatic::network_connections().contains_key(&1));
+            assert_eq!(NetworkContext::current(), NetworkContext::DEFAULT);
+            NetworkServerStatic::remove_spawned_network_identity(&net_id);
+        });
+    }
+}
@@ -0,0 +1,301 @@
+use crate::mirror::core::network_context::ContextLocal;
+use crate::mirror::core::network_identity::NetworkIdentity;
+use crate::mirror::core::network_server::NetworkServerStatic;
+use crate::mirror::core::overload_controller::OverloadLevel;
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use lazy_static::lazy_static;
+use std::sync::RwLock;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpawnEvent {
+    pub net_id: u32,
+    pub asset_id: u32,
+    pub scene_id: u64,
+    // 0 表示没有所有者
+    pub owner: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DespawnEvent {
+    pub net_id: u32,
+    // false 表示只是 un_spawn (场景对象), 对象仍然存在
+    pub destroyed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectEvent {
+    pub connection_id: u64,
+    pub address: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DisconnectEvent {
+    pub connection_id: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthorityChangeEvent {
+    pub net_id: u32,
+    pub old_owner: u64,
+    pub new_owner: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OverloadEvent {
+    pub previous: OverloadLevel,
+    pub level: OverloadLevel,
+    // 触发变化时的平均 tick 时间 (秒)
+    pub average_tick_time: f64,
+}
+
+// 服务器上同步变量的修改, 在状态广播序列化时发布, 见 SyncVarEvents
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyncVarChangeEvent {
+    pub net_id: u32,
+    pub component_index: u8,
+    pub component: String,
+    // 同步变量的脏位序号
+    pub var_index: u8,
+    // 组件没有实现 inspect_sync_vars 时 name 和 value 为空
+    pub name: String,
+    // 序列化后的新值, 与 SyncVarInspector 的格式相同
+    pub value: Vec<u8>,
+}
+
+pub(crate) struct Subscribers<T> {
+    senders: RwLock<Vec<Sender<T>>>,
+}
+
+impl<T: Clone> Subscribers<T> {
+    pub(crate) fn new() -> Self {
+        Self {
+            senders: RwLock::new(Vec::new()),
+        }
+    }
+
+    pub(crate) fn subscribe(&self) -> Receiver<T> {
+        let (sender, receiver) = unbounded();
+        if let Ok(mut senders) = self.senders.write() {
+            senders.push(sender);
+        }
+        receiver
+    }
+
+    fn is_empty(&self) -> bool {
+        match self.senders.read() {
+            Ok(senders) => senders.is_empty(),
+            Err(_) => true,
+        }
+    }
+
+    // Receiver 被 drop 后自动取消订阅
+    pub(crate) fn publish(&self, event: T) {
+        if self.is_empty() {
+            return;
+        }
+        if let Ok(mut senders) = self.senders.write() {
+            senders.retain(|sender| sender.send(event.clone()).is_ok());
+        }
+    }
+
+    fn clear(&self) {
+        if let Ok(mut senders) = self.senders.write() {
+            senders.clear();
+        }
+    }
+}
+
+// NetworkEvents 静态变量
+lazy_static! {
+    static ref SPAWN: ContextLocal<Subscribers<SpawnEvent>> = ContextLocal::new(Subscribers::new);
+    static ref DESPAWN: ContextLocal<Subscribers<DespawnEvent>> =
+        ContextLocal::new(Subscribers::new);
+    static ref CONNECT: ContextLocal<Subscribers<ConnectEvent>> =
+        ContextLocal::new(Subscribers::new);
+    static ref DISCONNECT: ContextLocal<Subscribers<DisconnectEvent>> =
+        ContextLocal::new(Subscribers::new);
+    static ref AUTHORITY_CHANGE: ContextLocal<Subscribers<AuthorityChangeEvent>> =
+        ContextLocal::new(Subscribers::new);
+    static ref OVERLOAD: ContextLocal<Subscribers<OverloadEvent>> =
+        ContextLocal::new(Subscribers::new);
+    static ref SYNC_VAR_CHANGE: ContextLocal<Subscribers<SyncVarChangeEvent>> =
+        ContextLocal::new(Subscribers::new);
+}
+
+// 给 NetworkBehaviour 之外的系统 (计分板, 统计等) 订阅服务器事件, 不需要轮询 DashMap
+// 每次订阅返回一个独立的 Receiver, 事件在主线程发布, 可以在任意线程接收
+pub struct NetworkEvents;
+
+impl NetworkEvents {
+    pub fn on_spawn() -> Receiver<SpawnEvent> {
+        SPAWN.subscribe()
+    }
+
+    pub fn on_despawn() -> Receiver<DespawnEvent> {
+        DESPAWN.subscribe()
+    }
+
+    pub fn on_connect() -> Receiver<ConnectEvent> {
+        CONNECT.subscribe()
+    }
+
+    pub fn on_disconnect() -> Receiver<DisconnectEvent> {
+        DISCONNECT.subscribe()
+    }
+
+    // 只针对已经生成的对象, 生成时的初始所有者见 SpawnEvent::owner
+    pub fn on_authority_change() -> Receiver<AuthorityChangeEvent> {
+        AUTHORITY_CHANGE.subscribe()
+    }
+
+    // OverloadController 的级别变化
+    pub fn on_overload() -> Receiver<OverloadEvent> {
+        OVERLOAD.subscribe()
+    }
+
+    // 同步变量的修改, 采样规则见 SyncVarEvents, 没有订阅者时不产生任何开销
+    pub fn on_sync_var_change() -> Receiver<SyncVarChangeEvent> {
+        SYNC_VAR_CHANGE.subscribe()
+    }
+
+    pub(crate) fn publish_spawn(identity: &NetworkIdentity) {
+        SPAWN.publish(SpawnEvent {
+            net_id: identity.net_id(),
+            asset_id: identity.asset_id,
+            scene_id: identity.scene_id,
+            owner: identity.connection_to_client(),
+        });
+    }
+
+    pub(crate) fn publish_despawn(net_id: u32, destroyed: bool) {
+        DESPAWN.publish(DespawnEvent { net_id, destroyed });
+    }
+
+    pub(crate) fn publish_connect(connection_id: u64, address: &str) {
+        CONNECT.publish(ConnectEvent {
+            connection_id,
+            address: address.to_string(),
+        });
+    }
+
+    pub(crate) fn publish_disconnect(connection_id: u64) {
+        DISCONNECT.publish(DisconnectEvent { connection_id });
+    }
+
+    pub(crate) fn publish_authority_change(net_id: u32, old_owner: u64, new_owner: u64) {
+        // 调用方可能持有 SPAWNED_NETWORK_IDENTITIES 的锁, 这里只查 SPAWNED_NETWORK_IDS
+        if old_owner == new_owner
+            || AUTHORITY_CHANGE.is_empty()
+            || !NetworkServerStatic::spawned_network_ids().contains(&net_id)
+        {
+            return;
+        }
+        AUTHORITY_CHANGE.publish(AuthorityChangeEvent {
+            net_id,
+            old_owner,
+            new_owner,
+        });
+    }
+
+    pub(crate) fn publish_overload(
+        previous: OverloadLevel,
+        level: OverloadLevel,
+        average_tick_time: f64,
+    ) {
+        OVERLOAD.publish(OverloadEvent {
+            previous,
+            level,
+            average_tick_time,
+        });
+    }
+
+    pub(crate) fn has_sync_var_change_subscribers() -> bool {
+        !SYNC_VAR_CHANGE.is_empty()
+    }
+
+    pub(crate) fn publish_sync_var_change(event: SyncVarChangeEvent) {
+        SYNC_VAR_CHANGE.publish(event);
+    }
+
+    pub fn reset() {
+        SPAWN.clear();
+        DESPAWN.clear();
+        CONNECT.clear();
+        DISCONNECT.clear();
+        AUTHORITY_CHANGE.clear();
+        OVERLOAD.clear();
+        SYNC_VAR_CHANGE.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::core::network_server::NetworkServer;
+    use crate::mirror::transports::memory::memory_transport::test_util::*;
+    use crate::mirror::transports::memory::memory_transport::MemoryTransport;
+
+    #[test]
+    fn test_subscribers() {
+        let subscribers = Subscribers::<u32>::new();
+        subscribers.publish(1);
+        let first = subscribers.subscribe();
+        let second = subscribers.subscribe();
+        subscribers.publish(2);
+        assert_eq!(first.try_recv(), Ok(2));
+        assert_eq!(second.try_recv(), Ok(2));
+
+        drop(second);
+        subscribers.publish(3);
+        assert_eq!(first.try_iter().collect::<Vec<_>>(), vec![3]);
+        assert_eq!(subscribers.senders.read().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_network_events() {
+        with_server(|| {
+            let connects = NetworkEvents::on_connect();
+            let disconnects = NetworkEvents::on_disconnect();
+            let spawns = NetworkEvents::on_spawn();
+            let authority = NetworkEvents::on_authority_change();
+
+            MemoryTransport::client_connect(1);
+            tick();
+            let connected: Vec<u64> = connects.try_iter().map(|e| e.connection_id).collect();
+            assert_eq!(connected, vec![1]);
+
+            let net_ids = NetworkServer::batch_spawn(vec![NetworkIdentity::new_with_asset_id(3)]);
+            assert_eq!(
+                spawns.try_recv(),
+                Ok(SpawnEvent {
+                    net_id: net_ids[0],
+                    asset_id: 3,
+                    scene_id: 0,
+                    owner: 0,
+                })
+            );
+
+            NetworkServerStatic::spawned_network_identities()
+                .get_mut(&net_ids[0])
+                .unwrap()
+                .set_connection_to_client(1);
+            assert_eq!(
+                authority.try_recv(),
+                Ok(AuthorityChangeEvent {
+                    net_id: net_ids[0],
+                    old_owner: 0,
+                    new_owner: 1,
+                })
+            );
+
+            MemoryTransport::client_disconnect(1);
+            tick();
+            assert_eq!(
+                disconnects.try_recv(),
+                Ok(DisconnectEvent { connection_id: 1 })
+            );
+            NetworkServerStatic::remove_spawned_network_identity(&net_ids[0]);
+            NetworkEvents::reset();
+        });
+    }
+}
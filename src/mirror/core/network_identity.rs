@@ -1,28 +1,32 @@
 use crate::log_error;
+use crate::log_warn;
 use crate::mirror::core::backend_data::BackendDataStatic;
+use crate::mirror::core::net_id_allocator::NetIdAllocator;
 use crate::mirror::core::network_behaviour::{
-    GameObject, NetworkBehaviourFactory, NetworkBehaviourTrait, SyncDirection, SyncMode,
+    GameObject, NetworkBehaviour, NetworkBehaviourFactory, NetworkBehaviourTrait,
 };
 use crate::mirror::core::network_connection::NetworkConnectionTrait;
 use crate::mirror::core::network_connection_to_client::NetworkConnectionToClient;
+use crate::mirror::core::network_events::NetworkEvents;
 use crate::mirror::core::network_reader::{NetworkReader, NetworkReaderTrait};
 use crate::mirror::core::network_server::{NetworkServer, NetworkServerStatic, NETWORK_BEHAVIOURS};
+use crate::mirror::core::network_time::NetworkTime;
 use crate::mirror::core::network_writer::{NetworkWriter, NetworkWriterTrait};
 use crate::mirror::core::network_writer_pool::NetworkWriterPool;
+use crate::mirror::core::parallel_serialization::ParallelSerialization;
 use crate::mirror::core::remote_calls::{RemoteCallType, RemoteProcedureCalls};
-use atomic::Atomic;
+use crate::mirror::core::sync_var_events::SyncVarEvents;
+use crate::mirror::core::tools::alloc_audit::{AllocAudit, AllocSite};
+use crate::mirror::core::tools::bandwidth_report::BandwidthReport;
+use crate::mirror::core::tools::behaviour_profiler::{BehaviourProfiler, ProfilePhase};
+use crate::mirror::core::tools::serialization_check::SerializationCheck;
 use dashmap::mapref::one::RefMut;
 use dashmap::try_result::TryResult;
 use dashmap::DashMap;
-use lazy_static::lazy_static;
 use std::default::Default;
-use std::sync::atomic::Ordering;
+use std::mem::take;
 
-lazy_static! {
-    static ref NEXT_NETWORK_ID: Atomic<u32> = Atomic::new(1);
-}
-
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum Visibility {
     Default,
     ForceHidden,
@@ -35,11 +39,17 @@ pub enum OwnedType {
     Server,
 }
 
+// (组件索引, 字节数)
+pub type ComponentBytes = Vec<(u8, usize)>;
+
 #[derive(Debug)]
 pub struct NetworkIdentitySerialization {
     pub tick: u32,
     pub owner_writer: NetworkWriter,
     pub observers_writer: NetworkWriter,
+    // 只在 BandwidthReport 开启时记录
+    pub owner_component_bytes: ComponentBytes,
+    pub observers_component_bytes: ComponentBytes,
 }
 
 impl NetworkIdentitySerialization {
@@ -48,11 +58,15 @@ impl NetworkIdentitySerialization {
             tick,
             owner_writer: NetworkWriter::new(),
             observers_writer: NetworkWriter::new(),
+            owner_component_bytes: Vec::new(),
+            observers_component_bytes: Vec::new(),
         }
     }
     pub fn reset_writers(&mut self) {
         self.owner_writer.reset();
         self.observers_writer.reset();
+        self.owner_component_bytes.clear();
+        self.observers_component_bytes.clear();
     }
 }
 
@@ -163,6 +177,7 @@ impl NetworkIdentity {
             conn_id,
             self.network_behaviours_count,
         );
+        NetworkEvents::publish_authority_change(self.net_id, self.conn_to_client, conn_id);
         // 设置 conn_id
         self.conn_to_client = conn_id;
         // 如果 conn_to_client 不为0，设置 connection_to_client 的 net_id
@@ -189,7 +204,7 @@ impl NetworkIdentity {
         self.game_object = game_object;
         for i in 0..self.network_behaviours_count {
             if let TryResult::Present(mut component) =
-                NETWORK_BEHAVIOURS.try_get_mut(&format!("{}_{}", self.net_id, i))
+                NETWORK_BEHAVIOURS.try_get_mut(&(self.net_id, i))
             {
                 component.set_game_object(self.game_object.clone());
             }
@@ -214,7 +229,7 @@ impl NetworkIdentity {
             remote_call_type,
         ) {
             log_error!(
-                "Failed to invoke remote call for function hash: ",
+                "Failed to invoke remote call for function hash: {}",
                 function_hash
             );
         }
@@ -223,7 +238,7 @@ impl NetworkIdentity {
         Self::reset_server_statics();
     }
     pub fn reset_server_statics() {
-        Self::set_static_next_network_id(1);
+        NetIdAllocator::reset();
     }
     pub fn get_scene_identity(&self, scene_id: u64) -> Option<RefMut<u64, u32>> {
         if let Some(scene_identity) = self.scene_ids.get_mut(&scene_id) {
@@ -304,7 +319,7 @@ impl NetworkIdentity {
     }
     pub fn on_start_server(&mut self) {
         for i in 0..self.network_behaviours_count {
-            match NETWORK_BEHAVIOURS.try_get_mut(&format!("{}_{}", self.net_id, i)) {
+            match NETWORK_BEHAVIOURS.try_get_mut(&(self.net_id, i)) {
                 TryResult::Present(mut component) => {
                     component.on_start_server();
                 }
@@ -319,7 +334,7 @@ impl NetworkIdentity {
     }
     pub fn on_stop_server(&mut self) {
         for i in 0..self.network_behaviours_count {
-            match NETWORK_BEHAVIOURS.try_get_mut(&format!("{}_{}", self.net_id, i)) {
+            match NETWORK_BEHAVIOURS.try_get_mut(&(self.net_id, i)) {
                 TryResult::Present(mut component) => {
                     component.on_stop_server();
                 }
@@ -336,21 +351,19 @@ impl NetworkIdentity {
         let mut owner_mask: u64 = 0;
         let mut observers_mask: u64 = 0;
         for i in 0..self.network_behaviours_count {
-            match NETWORK_BEHAVIOURS.try_get_mut(&format!("{}_{}", self.net_id, i)) {
+            match ParallelSerialization::retry_locked(|| {
+                NETWORK_BEHAVIOURS.try_get_mut(&(self.net_id, i))
+            }) {
                 TryResult::Present(mut component) => {
                     let nth_bit = 1 << i;
                     let dirty = component.is_dirty();
 
-                    if initial_state
-                        || (*component.sync_direction() == SyncDirection::ServerToClient) && dirty
-                    {
+                    if initial_state || component.sync_direction().sends_to_owner() && dirty {
                         owner_mask |= nth_bit;
                     }
 
-                    if *component.sync_mode() == SyncMode::Observers {
-                        if initial_state || dirty {
-                            observers_mask |= nth_bit;
-                        }
+                    if component.sync_mode().sends_to_observers() && (initial_state || dirty) {
+                        observers_mask |= nth_bit;
                     }
                 }
                 TryResult::Absent => {
@@ -371,6 +384,16 @@ impl NetworkIdentity {
         initial_state: bool,
         owner_writer: &mut NetworkWriter,
         observers_writer: &mut NetworkWriter,
+    ) {
+        self.serialize_server_components(initial_state, owner_writer, observers_writer, None);
+    }
+    // component_bytes 不为 None 时记录每个组件写入 owner / observers 的字节数
+    fn serialize_server_components(
+        &mut self,
+        initial_state: bool,
+        owner_writer: &mut NetworkWriter,
+        observers_writer: &mut NetworkWriter,
+        mut component_bytes: Option<(&mut ComponentBytes, &mut ComponentBytes)>,
     ) {
         self.validate_components();
         let (owner_mask, observers_mask) = self.server_dirty_masks(initial_state);
@@ -384,7 +407,9 @@ impl NetworkIdentity {
 
         if (owner_mask | observers_mask) != 0 {
             for i in 0..self.network_behaviours_count {
-                match NETWORK_BEHAVIOURS.try_get_mut(&format!("{}_{}", self.net_id, i)) {
+                match ParallelSerialization::retry_locked(|| {
+                    NETWORK_BEHAVIOURS.try_get_mut(&(self.net_id, i))
+                }) {
                     TryResult::Present(mut component) => {
                         let owner_dirty = Self::is_dirty(owner_mask, i);
                         let observers_dirty = Self::is_dirty(observers_mask, i);
@@ -392,9 +417,22 @@ impl NetworkIdentity {
                         if owner_dirty || observers_dirty {
                             NetworkWriterPool::get_return(|temp| {
                                 // Serialize the component
+                                let begin = BehaviourProfiler::begin();
                                 component.serialize(temp, initial_state);
+                                BehaviourProfiler::end(
+                                    begin,
+                                    ProfilePhase::Serialize,
+                                    &**component,
+                                );
 
                                 let segment = temp.to_bytes();
+                                SerializationCheck::check(
+                                    self.asset_id,
+                                    self.scene_id,
+                                    &mut **component,
+                                    &segment,
+                                    initial_state,
+                                );
 
                                 if owner_dirty {
                                     owner_writer.write_array_segment_all(&segment);
@@ -402,9 +440,29 @@ impl NetworkIdentity {
                                 if observers_dirty {
                                     observers_writer.write_array_segment_all(&segment);
                                 }
+                                if let Some((owner_bytes, observers_bytes)) =
+                                    component_bytes.as_mut()
+                                {
+                                    if owner_dirty {
+                                        owner_bytes.push((i, segment.len()));
+                                    }
+                                    if observers_dirty {
+                                        observers_bytes.push((i, segment.len()));
+                                    }
+                                }
                             });
                             if !initial_state {
-                                component.clear_all_dirty_bits();
+                                if component.enabled() {
+                                    SyncVarEvents::record(&**component);
+                                    component.clear_all_dirty_bits();
+                                } else {
+                                    // 禁用期间的修改保留到重新启用时再同步
+                                    let dirty_bits = component.sync_var_dirty_bits();
+                                    component.__set_sync_var_dirty_bits(
+                                        dirty_bits & !NetworkBehaviour::ENABLED_DIRTY_BIT,
+                                    );
+                                    component.set_last_sync_time(NetworkTime::local_time());
+                                }
                             }
                         }
                     }
@@ -418,21 +476,31 @@ impl NetworkIdentity {
             }
         }
     }
-    pub fn deserialize_server(&mut self, reader: &mut NetworkReader) -> bool {
+    // 组件数据只有一个字节的 safety, 无法跳过不允许写入的组件, 整个状态都会被拒绝
+    pub fn deserialize_server(&mut self, reader: &mut NetworkReader, connection_id: u64) -> bool {
         self.validate_components();
 
         let mask = reader.decompress_var_ulong();
+        let is_owner = self.connection_to_client() == connection_id;
 
         for i in 0..self.network_behaviours_count {
             if Self::is_dirty(mask, i) {
-                match NETWORK_BEHAVIOURS.try_get_mut(&format!("{}_{}", self.net_id, i)) {
+                match NETWORK_BEHAVIOURS.try_get_mut(&(self.net_id, i)) {
                     TryResult::Present(mut component) => {
-                        if component.sync_direction() == &SyncDirection::ServerToClient {
-                            if !component.deserialize(reader, false) {
-                                return false;
-                            }
-                            component.set_dirty();
+                        if !component.sync_direction().accepts_client_write(is_owner) {
+                            log_warn!(
+                                "Server rejected client state for netId={} component [index={}] with sync direction {:?} from connectionId {}.",
+                                self.net_id,
+                                i,
+                                component.sync_direction(),
+                                connection_id
+                            );
+                            return false;
+                        }
+                        if !component.deserialize(reader, false) {
+                            return false;
                         }
+                        component.set_dirty();
                     }
                     TryResult::Absent => {
                         log_error!("Failed to deserialize server because component is absent.");
@@ -445,15 +513,55 @@ impl NetworkIdentity {
         }
         true
     }
+    // 导入 serialize_server(true, ..) 的 owner 数据, 不区分同步方向
+    pub fn deserialize_initial_state(&mut self, reader: &mut NetworkReader) -> bool {
+        if self.network_behaviours_count == 0 || reader.remaining() == 0 {
+            return true;
+        }
+        let mask = reader.decompress_var_ulong();
+        for i in 0..self.network_behaviours_count {
+            if Self::is_dirty(mask, i) {
+                match NETWORK_BEHAVIOURS.try_get_mut(&(self.net_id, i)) {
+                    TryResult::Present(mut component) => {
+                        if !component.deserialize(reader, true) {
+                            return false;
+                        }
+                    }
+                    TryResult::Absent => {
+                        log_error!(
+                            "Failed to deserialize initial state because component is absent."
+                        );
+                        return false;
+                    }
+                    TryResult::Locked => {
+                        log_error!(
+                            "Failed to deserialize initial state because component is locked."
+                        );
+                        return false;
+                    }
+                }
+            }
+        }
+        true
+    }
     pub fn get_server_serialization_at_tick(
         &mut self,
         tick: u32,
     ) -> &mut NetworkIdentitySerialization {
         if self.last_serialization.tick != tick {
             self.last_serialization.reset_writers();
+            let mut owner_bytes = take(&mut self.last_serialization.owner_component_bytes);
+            let mut observers_bytes = take(&mut self.last_serialization.observers_component_bytes);
             NetworkWriterPool::get_return(|owner_writer| {
                 NetworkWriterPool::get_return(|observers_writer| {
-                    self.serialize_server(false, owner_writer, observers_writer);
+                    let component_bytes = BandwidthReport::enabled()
+                        .then_some((&mut owner_bytes, &mut observers_bytes));
+                    self.serialize_server_components(
+                        false,
+                        owner_writer,
+                        observers_writer,
+                        component_bytes,
+                    );
                     self.last_serialization
                         .owner_writer
                         .write_array_segment_all(owner_writer.to_array_segment());
@@ -462,6 +570,8 @@ impl NetworkIdentity {
                         .write_array_segment_all(observers_writer.to_array_segment());
                 });
             });
+            self.last_serialization.owner_component_bytes = owner_bytes;
+            self.last_serialization.observers_component_bytes = observers_bytes;
             self.last_serialization.tick = tick;
         }
         &mut self.last_serialization
@@ -476,10 +586,10 @@ impl NetworkIdentity {
                     // do nothing
                 }
                 TryResult::Locked => {
-                    log_error!(format!(
+                    log_error!(
                         "Failed to clear observers because connection {} is locked.",
                         conn_id
-                    ));
+                    );
                 }
             }
         }
@@ -504,11 +614,29 @@ impl NetworkIdentity {
 
     // AddObserver(NetworkConnectionToClient conn)
     pub fn add_observer(&mut self, conn_id: u64) {
+        self.add_observer_with_spawn(conn_id, true);
+    }
+
+    // 添加观察者但不发送 SpawnMessage, 由调用者批量发送 (BatchSpawnMessage)
+    pub fn add_observer_without_spawn(&mut self, conn_id: u64) {
+        self.add_observer_with_spawn(conn_id, false);
+    }
+
+    fn add_observer_with_spawn(&mut self, conn_id: u64, spawn: bool) {
         // 如果观察者已存在
         if self.observers.contains(&conn_id) {
             return;
         }
 
+        // 没有准备好的连接 (例如正在切换场景) 不能成为观察者, 重新准备好时再添加
+        if let TryResult::Present(conn) =
+            NetworkServerStatic::network_connections().try_get(&conn_id)
+        {
+            if !conn.is_ready() {
+                return;
+            }
+        }
+
         // 如果没有观察者
         if self.observers.len() == 0 {
             self.clear_all_components_dirty_bits()
@@ -516,7 +644,7 @@ impl NetworkIdentity {
 
         // 添加观察者
         for i in 0..self.network_behaviours_count {
-            match NETWORK_BEHAVIOURS.try_get_mut(&format!("{}_{}", self.net_id, i)) {
+            match NETWORK_BEHAVIOURS.try_get_mut(&(self.net_id, i)) {
                 TryResult::Present(mut component) => {
                     component.add_observer(conn_id);
                 }
@@ -529,12 +657,26 @@ impl NetworkIdentity {
             }
         }
         // 添加观察者
+        AllocAudit::record_growth(
+            AllocSite::ObserverGrowth,
+            self.observers.len(),
+            self.observers.capacity(),
+        );
         self.observers.push(conn_id);
 
         // 添加到观察者
         match NetworkServerStatic::network_connections().try_get_mut(&conn_id) {
             TryResult::Present(mut conn) => {
-                conn.add_to_observing(self);
+                if spawn {
+                    conn.add_to_observing(self);
+                } else {
+                    AllocAudit::record_growth(
+                        AllocSite::ObserverGrowth,
+                        conn.observing.len(),
+                        conn.observing.capacity(),
+                    );
+                    conn.observing.push(self.net_id);
+                }
             }
             TryResult::Absent => {
                 log_error!("Failed to add observer because connection is absent.");
@@ -546,7 +688,7 @@ impl NetworkIdentity {
     }
     fn clear_all_components_dirty_bits(&mut self) {
         for i in 0..self.network_behaviours_count {
-            match NETWORK_BEHAVIOURS.try_get_mut(&format!("{}_{}", self.net_id, i)) {
+            match NETWORK_BEHAVIOURS.try_get_mut(&(self.net_id, i)) {
                 TryResult::Present(mut component) => {
                     component.clear_all_dirty_bits();
                 }
@@ -566,7 +708,7 @@ impl NetworkIdentity {
     pub fn remove_observer(&mut self, conn_id: u64) {
         // 清理组件的 observer
         for i in 0..self.network_behaviours_count {
-            match NETWORK_BEHAVIOURS.try_get_mut(&format!("{}_{}", self.net_id, i)) {
+            match NETWORK_BEHAVIOURS.try_get_mut(&(self.net_id, i)) {
                 TryResult::Present(mut component) => {
                     component.remove_observer(conn_id);
                 }
@@ -585,15 +727,21 @@ impl NetworkIdentity {
         if self.conn_to_client != 0 {
             return;
         }
+        NetworkEvents::publish_authority_change(self.net_id, 0, conn_id);
         self.conn_to_client = conn_id;
     }
+    // 从 NetIdAllocator 的活动命名空间分配, 用完时返回 0
     pub fn get_static_next_network_id() -> u32 {
-        let id = NEXT_NETWORK_ID.load(Ordering::Relaxed);
-        NEXT_NETWORK_ID.store(id + 1, Ordering::Relaxed);
-        id
+        match NetIdAllocator::allocate() {
+            Ok(id) => id,
+            Err(e) => {
+                log_error!("Failed to allocate netId: {}", e);
+                0
+            }
+        }
     }
     pub fn set_static_next_network_id(id: u32) {
-        NEXT_NETWORK_ID.store(id, Ordering::Relaxed);
+        NetIdAllocator::set_default_next(id);
     }
 
     pub fn set_active(&mut self, active: bool) {
@@ -607,7 +755,7 @@ impl NetworkIdentity {
     {
         for i in 0..self.network_behaviours_count {
             if let TryResult::Present(mut component) =
-                NETWORK_BEHAVIOURS.try_get_mut(&format!("{}_{}", self.net_id, i))
+                NETWORK_BEHAVIOURS.try_get_mut(&(self.net_id, i))
             {
                 if let Some(component) = component.as_any_mut().downcast_mut::<T>() {
                     func(component);
@@ -653,7 +801,14 @@ impl NetworkIdentity {
         match NetworkServerStatic::network_connections().try_get_mut(&self.conn_to_client) {
             TryResult::Present(mut conn) => {
                 // TODO clientAuthorityCallback?.Invoke(connectionToClient, this, false);
+                NetworkEvents::publish_authority_change(self.net_id, self.conn_to_client, 0);
+                NETWORK_BEHAVIOURS::update_behaviour_conn_id(
+                    self.net_id,
+                    0,
+                    self.network_behaviours_count,
+                );
                 self.conn_to_client = 0;
+                conn.remove_owned_object(self.net_id);
                 NetworkServer::send_change_owner_message(self, &mut conn);
             }
             TryResult::Absent => {
@@ -665,3 +820,83 @@ impl NetworkIdentity {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::core::messages::{CommandMessage, NetworkMessageTrait};
+    use crate::mirror::core::network_behaviour::SyncDirection;
+    use crate::mirror::transports::memory::memory_transport::test_util::*;
+    use crate::mirror::transports::memory::memory_transport::MemoryTransport;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    static STATE_WRITES: AtomicU32 = AtomicU32::new(0);
+
+    fn on_test_state_write(_: u64, _: u32, _: u8, _: u16, _: &mut NetworkReader) {
+        STATE_WRITES.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[test]
+    fn test_sync_direction_enforcement() {
+        with_server(|| {
+            let function_hash = RemoteProcedureCalls::register_state_command_delegate::<TestHit>(
+                "System.Void Test.TestHit::CmdWrite()",
+                on_test_state_write,
+            );
+            for conn_id in [1, 2] {
+                MemoryTransport::client_connect(conn_id);
+            }
+            tick();
+            for conn_id in [1, 2] {
+                NetworkServer::set_client_ready(conn_id);
+                NetworkServerStatic::network_connections()
+                    .get_mut(&conn_id)
+                    .unwrap()
+                    .set_authenticated(true);
+            }
+
+            spawn_test_identity(700, 1, 1);
+
+            // 连接 1 是所有者, 只有所有者对 ClientToServer 组件的写入被接受
+            for (direction, conn_id, expected) in [
+                (SyncDirection::ServerToClient, 1, false),
+                (SyncDirection::ServerToClient, 2, false),
+                (SyncDirection::ClientToServer, 1, true),
+                (SyncDirection::ClientToServer, 2, false),
+            ] {
+                NETWORK_BEHAVIOURS
+                    .get_mut(&(700, 0))
+                    .unwrap()
+                    .set_sync_direction(direction);
+                // 组件 0 的数据: safety + 空的 SyncObject 脏标记
+                let mut writer = NetworkWriter::new();
+                writer.compress_var_ulong(1);
+                writer.write_byte(8);
+                writer.write_ulong(0);
+                let state = writer.to_bytes();
+                let mut reader = NetworkReader::new_with_array_segment(&state);
+                let accepted = NetworkServerStatic::spawned_network_identities()
+                    .get_mut(&700)
+                    .unwrap()
+                    .deserialize_server(&mut reader, conn_id);
+                assert_eq!(accepted, expected, "state {:?} from {}", direction, conn_id);
+
+                let writes = STATE_WRITES.load(Ordering::Relaxed);
+                let mut writer = NetworkWriter::new();
+                CommandMessage::new(700, 0, function_hash, Vec::new()).serialize(&mut writer);
+                send_raw(conn_id, &writer.to_bytes());
+                tick();
+                assert_eq!(
+                    STATE_WRITES.load(Ordering::Relaxed) - writes,
+                    expected as u32,
+                    "command {:?} from {}",
+                    direction,
+                    conn_id
+                );
+            }
+
+            RemoteProcedureCalls::remove_delegate(function_hash);
+            NetworkServerStatic::remove_spawned_network_identity(&700);
+        });
+    }
+}
@@ -1,12 +1,19 @@
 use crate::log_error;
 use crate::mirror::core::network_behaviour::NetworkBehaviourFactory;
+use crate::mirror::core::network_client::NetworkClient;
+use crate::mirror::core::network_context::ContextLocal;
 use crate::mirror::core::network_manager::NetworkManagerStatic;
 use crate::mirror::core::network_server::{NetworkServer, NetworkServerStatic, NETWORK_BEHAVIOURS};
 use crate::mirror::core::network_time::NetworkTime;
+use crate::mirror::core::scheduler::Scheduler;
+use crate::mirror::core::task_bridge::TaskBridge;
+use crate::mirror::core::tools::behaviour_profiler::{BehaviourProfiler, ProfilePhase};
+use crate::mirror::core::tools::frame_report::FrameReports;
+use atomic::Atomic;
 use dashmap::try_result::TryResult;
 use lazy_static::lazy_static;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Condvar, Mutex, RwLock};
 use std::thread;
 use std::time::Duration;
 
@@ -33,6 +40,18 @@ lazy_static! {
     static ref NETWORK_COMMON_BEHAVIOUR_DELEGATE_FUNCTION: RwLock<fn()> = RwLock::new(||{});
     // 是否停止
     static ref STOP: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
+    // 空闲模式
+    static ref IDLE_ENABLED: ContextLocal<AtomicBool> =
+        ContextLocal::new(|| AtomicBool::new(false));
+    // 空闲时最长的睡眠时间, 单位秒
+    static ref IDLE_MAX_SLEEP: ContextLocal<Atomic<f64>> = ContextLocal::new(|| Atomic::new(0.5));
+    // 连续空闲的帧数
+    static ref IDLE_TICKS: ContextLocal<AtomicU32> = ContextLocal::new(|| AtomicU32::new(0));
+    // 上次 wake 之后还没有被睡眠消耗
+    static ref WAKE_PENDING: ContextLocal<AtomicBool> =
+        ContextLocal::new(|| AtomicBool::new(false));
+    static ref WAKE: ContextLocal<(Mutex<()>, Condvar)> =
+        ContextLocal::new(|| (Mutex::new(()), Condvar::new()));
 }
 
 pub struct NetworkLoop;
@@ -44,18 +63,93 @@ impl NetworkLoop {
 
     pub fn set_stop(value: bool) {
         STOP.store(value, Ordering::Relaxed);
+        if value {
+            Self::wake();
+        }
     }
 
     pub fn stop_signal() -> bool {
         STOP.load(Ordering::Relaxed)
     }
+
+    pub fn idle_enabled() -> bool {
+        IDLE_ENABLED.load(Ordering::Relaxed)
+    }
+
+    // 空闲模式: 没有连接且没有脏对象时逐渐加长睡眠, 最长 idle_max_sleep, 传输层有数据时立即唤醒
+    pub fn set_idle_enabled(value: bool) {
+        IDLE_ENABLED.store(value, Ordering::Relaxed);
+    }
+
+    pub fn idle_max_sleep() -> f64 {
+        IDLE_MAX_SLEEP.load(Ordering::Relaxed)
+    }
+
+    pub fn set_idle_max_sleep(value: f64) {
+        IDLE_MAX_SLEEP.store(value, Ordering::Relaxed);
+    }
+
+    // 当前是否处于空闲睡眠
+    pub fn idling() -> bool {
+        IDLE_TICKS.load(Ordering::Relaxed) > 0
+    }
+
+    // 传输层收到数据或者连接时调用, 可以在任意线程调用
+    pub fn wake() {
+        // 同一次睡眠只通知一次
+        if WAKE_PENDING.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let _guard = WAKE.0.lock().unwrap_or_else(|e| e.into_inner());
+        WAKE.1.notify_one();
+    }
+
+    pub(crate) fn is_idle() -> bool {
+        if !Self::idle_enabled()
+            || !NetworkServerStatic::network_connections().is_empty()
+            || TaskBridge::pending_count() > 0
+        {
+            return false;
+        }
+        // 没有连接时不会广播, 这里只是避免脏对象的修改长时间不清除
+        !NETWORK_BEHAVIOURS.iter().any(|network_behaviour| {
+            network_behaviour.sync_var_dirty_bits() | network_behaviour.sync_object_dirty_bits()
+                != 0
+        })
+    }
+
+    // 睡眠 duration 或者直到 wake, 被唤醒时返回 true
+    pub(crate) fn idle_sleep(duration: Duration) -> bool {
+        let guard = WAKE.0.lock().unwrap_or_else(|e| e.into_inner());
+        // 上次睡眠之后已经有数据
+        if WAKE_PENDING.swap(false, Ordering::SeqCst) {
+            return true;
+        }
+        let _ = WAKE
+            .1
+            .wait_timeout_while(guard, duration, |_| !WAKE_PENDING.load(Ordering::SeqCst))
+            .unwrap_or_else(|e| e.into_inner());
+        WAKE_PENDING.swap(false, Ordering::SeqCst)
+    }
+
+    // 空闲时每一帧的睡眠时间加倍, 不超过 idle_max_sleep 和下一个定时器的到期时间
+    fn idle_sleep_time(target_frame_time: Duration) -> Duration {
+        let idle_ticks = IDLE_TICKS.fetch_add(1, Ordering::Relaxed).min(16);
+        let mut sleep_time = target_frame_time * 2u32.pow(idle_ticks);
+        sleep_time = sleep_time.min(Duration::from_secs_f64(Self::idle_max_sleep().max(0.0)));
+        if let Some(next_due) = Scheduler::next_due() {
+            let until_due = (next_due - NetworkTime::local_time()).max(0.0);
+            sleep_time = sleep_time.min(Duration::from_secs_f64(until_due));
+        }
+        sleep_time.max(target_frame_time)
+    }
     pub fn add_awake_function(func: fn()) {
         match AWAKE_FUNCTIONS.write() {
             Ok(mut awake_functions) => {
                 awake_functions.push(func);
             }
             Err(e) => {
-                log_error!(format!("add_awake_function error: {}", e));
+                log_error!("add_awake_function error: {}", e);
             }
         }
     }
@@ -70,7 +164,7 @@ impl NetworkLoop {
                 on_enable_functions.push(func);
             }
             Err(e) => {
-                log_error!(format!("add_on_enable_function error: {}", e));
+                log_error!("add_on_enable_function error: {}", e);
             }
         }
     }
@@ -85,7 +179,7 @@ impl NetworkLoop {
                 start_functions.push(func);
             }
             Err(e) => {
-                log_error!(format!("add_start_function error: {}", e));
+                log_error!("add_start_function error: {}", e);
             }
         }
     }
@@ -101,7 +195,7 @@ impl NetworkLoop {
                 early_update_functions.push(func);
             }
             Err(e) => {
-                log_error!(format!("add_early_update_function error: {}", e));
+                log_error!("add_early_update_function error: {}", e);
             }
         }
     }
@@ -118,7 +212,7 @@ impl NetworkLoop {
                 update_functions.push(func);
             }
             Err(e) => {
-                log_error!(format!("add_update_function error: {}", e));
+                log_error!("add_update_function error: {}", e);
             }
         }
     }
@@ -135,7 +229,7 @@ impl NetworkLoop {
                 late_update_functions.push(func);
             }
             Err(e) => {
-                log_error!(format!("add_late_update_function error: {}", e));
+                log_error!("add_late_update_function error: {}", e);
             }
         }
     }
@@ -151,7 +245,7 @@ impl NetworkLoop {
                 on_disable_functions.push(func);
             }
             Err(e) => {
-                log_error!(format!("add_on_disable_function error: {}", e));
+                log_error!("add_on_disable_function error: {}", e);
             }
         }
     }
@@ -166,7 +260,7 @@ impl NetworkLoop {
                 on_destroy_functions.push(func);
             }
             Err(e) => {
-                log_error!(format!("add_on_destroy_function error: {}", e));
+                log_error!("add_on_destroy_function error: {}", e);
             }
         }
     }
@@ -181,7 +275,7 @@ impl NetworkLoop {
                 network_behaviour_factory_functions.push(func);
             }
             Err(e) => {
-                log_error!(format!("add_network_behaviour_factory error: {}", e));
+                log_error!("add_network_behaviour_factory error: {}", e);
             }
         }
     }
@@ -197,10 +291,7 @@ impl NetworkLoop {
                 *network_common_behaviour_delegate_function = func;
             }
             Err(e) => {
-                log_error!(format!(
-                    "add_network_common_behaviour_delegate error: {}",
-                    e
-                ));
+                log_error!("add_network_common_behaviour_delegate error: {}", e);
             }
         }
     }
@@ -220,10 +311,10 @@ impl NetworkLoop {
                 }
             }
             Err(e) => {
-                log_error!(format!(
+                log_error!(
                     "NetworkLoop.register_network_behaviour_factory() error: {}",
                     e
-                ));
+                );
             }
         }
     }
@@ -237,7 +328,7 @@ impl NetworkLoop {
                 }
             }
             Err(e) => {
-                log_error!(format!("NetworkLoop.awake() error: {}", e));
+                log_error!("NetworkLoop.awake() error: {}", e);
             }
         }
     }
@@ -251,7 +342,7 @@ impl NetworkLoop {
                 }
             }
             Err(e) => {
-                log_error!(format!("NetworkLoop.on_enable() error: {}", e));
+                log_error!("NetworkLoop.on_enable() error: {}", e);
             }
         }
     }
@@ -268,7 +359,7 @@ impl NetworkLoop {
                 }
             }
             Err(e) => {
-                log_error!(format!("NetworkLoop.start() error: {}", e));
+                log_error!("NetworkLoop.start() error: {}", e);
             }
         }
     }
@@ -278,6 +369,7 @@ impl NetworkLoop {
         // NetworkEarlyUpdate
         // AddToPlayerLoop(NetworkEarlyUpdate, typeof(NetworkLoop), ref playerLoop, typeof(EarlyUpdate), AddMode.End);
         NetworkServer::network_early_update();
+        NetworkClient::network_early_update();
 
         match Self::early_update_functions().try_read() {
             Ok(early_update_functions) => {
@@ -286,7 +378,7 @@ impl NetworkLoop {
                 }
             }
             Err(e) => {
-                log_error!(format!("NetworkLoop.early_update() error: {}", e));
+                log_error!("NetworkLoop.early_update() error: {}", e);
             }
         }
     }
@@ -296,32 +388,43 @@ impl NetworkLoop {
         // NetworkManager update
         NetworkManagerStatic::network_manager_singleton().update();
 
-        // NetworkBehaviour update  模拟
-        NetworkServerStatic::spawned_network_identities()
-            .iter()
-            .for_each(|identity| {
-                for i in 0..identity.network_behaviours_count {
-                    match NETWORK_BEHAVIOURS.try_get_mut(&format!("{}_{}", identity.net_id(), i)) {
-                        TryResult::Present(mut network_behaviour) => {
-                            network_behaviour.update();
-                        }
-                        TryResult::Absent => {
-                            log_error!(format!(
-                                "NetworkBehaviour not found by net_id: {}, component_index: {}",
-                                identity.net_id(),
-                                i
-                            ));
-                        }
-                        TryResult::Locked => {
-                            log_error!(format!(
-                                "NetworkBehaviour locked by net_id: {}, component_index: {}",
-                                identity.net_id(),
-                                i
-                            ));
+        // NetworkBehaviour update  模拟, 暂停时跳过
+        if !NetworkServerStatic::paused() {
+            NetworkServerStatic::spawned_network_identities()
+                .iter()
+                .for_each(|identity| {
+                    for i in 0..identity.network_behaviours_count {
+                        match NETWORK_BEHAVIOURS.try_get_mut(&(identity.net_id(), i)) {
+                            TryResult::Present(mut network_behaviour) => {
+                                if !network_behaviour.enabled() {
+                                    continue;
+                                }
+                                let begin = BehaviourProfiler::begin();
+                                network_behaviour.update();
+                                BehaviourProfiler::end(
+                                    begin,
+                                    ProfilePhase::Update,
+                                    &**network_behaviour,
+                                );
+                            }
+                            TryResult::Absent => {
+                                log_error!(
+                                    "NetworkBehaviour not found by net_id: {}, component_index: {}",
+                                    identity.net_id(),
+                                    i
+                                );
+                            }
+                            TryResult::Locked => {
+                                log_error!(
+                                    "NetworkBehaviour locked by net_id: {}, component_index: {}",
+                                    identity.net_id(),
+                                    i
+                                );
+                            }
                         }
                     }
-                }
-            });
+                });
+        }
 
         match Self::update_functions().try_read() {
             Ok(update_functions) => {
@@ -330,7 +433,7 @@ impl NetworkLoop {
                 }
             }
             Err(e) => {
-                log_error!(format!("NetworkLoop.update() error: {}", e));
+                log_error!("NetworkLoop.update() error: {}", e);
             }
         }
     }
@@ -340,36 +443,48 @@ impl NetworkLoop {
         // NetworkLateUpdate
         // AddToPlayerLoop(NetworkLateUpdate, typeof(NetworkLoop), ref playerLoop, typeof(PreLateUpdate), AddMode.End);
         NetworkServer::network_late_update();
+        NetworkClient::network_late_update();
 
         // NetworkBehaviour late_update  模拟
         NetworkManagerStatic::network_manager_singleton().late_update();
 
-        // NetworkBehaviour late_update
-        NetworkServerStatic::spawned_network_identities()
-            .iter()
-            .for_each(|identity| {
-                for i in 0..identity.network_behaviours_count {
-                    match NETWORK_BEHAVIOURS.try_get_mut(&format!("{}_{}", identity.net_id(), i)) {
-                        TryResult::Present(mut network_behaviour) => {
-                            network_behaviour.late_update();
-                        }
-                        TryResult::Absent => {
-                            log_error!(format!(
-                                "NetworkBehaviour not found by net_id: {}, component_index: {}",
-                                identity.net_id(),
-                                i
-                            ));
-                        }
-                        TryResult::Locked => {
-                            log_error!(format!(
-                                "NetworkBehaviour locked by net_id: {}, component_index: {}",
-                                identity.net_id(),
-                                i
-                            ));
+        // NetworkBehaviour late_update, 暂停时跳过
+        if !NetworkServerStatic::paused() {
+            NetworkServerStatic::spawned_network_identities()
+                .iter()
+                .for_each(|identity| {
+                    for i in 0..identity.network_behaviours_count {
+                        match NETWORK_BEHAVIOURS.try_get_mut(&(identity.net_id(), i)) {
+                            TryResult::Present(mut network_behaviour) => {
+                                if !network_behaviour.enabled() {
+                                    continue;
+                                }
+                                let begin = BehaviourProfiler::begin();
+                                network_behaviour.late_update();
+                                BehaviourProfiler::end(
+                                    begin,
+                                    ProfilePhase::LateUpdate,
+                                    &**network_behaviour,
+                                );
+                            }
+                            TryResult::Absent => {
+                                log_error!(
+                                    "NetworkBehaviour not found by net_id: {}, component_index: {}",
+                                    identity.net_id(),
+                                    i
+                                );
+                            }
+                            TryResult::Locked => {
+                                log_error!(
+                                    "NetworkBehaviour locked by net_id: {}, component_index: {}",
+                                    identity.net_id(),
+                                    i
+                                );
+                            }
                         }
                     }
-                }
-            });
+                });
+        }
 
         match Self::late_update_functions().try_read() {
             Ok(late_update_functions) => {
@@ -378,7 +493,7 @@ impl NetworkLoop {
                 }
             }
             Err(e) => {
-                log_error!(format!("NetworkLoop.late_update() error: {}", e));
+                log_error!("NetworkLoop.late_update() error: {}", e);
             }
         }
     }
@@ -392,7 +507,7 @@ impl NetworkLoop {
                 }
             }
             Err(e) => {
-                log_error!(format!("NetworkLoop.on_disable() error: {}", e));
+                log_error!("NetworkLoop.on_disable() error: {}", e);
             }
         }
     }
@@ -409,7 +524,7 @@ impl NetworkLoop {
                 }
             }
             Err(e) => {
-                log_error!(format!("NetworkLoop.on_destroy() error: {}", e));
+                log_error!("NetworkLoop.on_destroy() error: {}", e);
             }
         }
     }
@@ -417,13 +532,18 @@ impl NetworkLoop {
     pub fn run() {
         // 注册 NetworkBehaviourFactory
         Self::register_network_behaviour_factory();
+        // panic 时输出最近的 tick 指标
+        FrameReports::install_panic_hook();
 
         // 每一帧的目标时间
         let target_frame_time = Duration::from_secs(1) / NetworkServerStatic::tick_rate();
+        // stop_server 之后服务器保持停止, 直到调用 start_server
+        let mut started = false;
         // 循环
         while !Self::stop_signal() {
             // 初始化
-            if !NetworkServerStatic::active() {
+            if !started {
+                started = true;
                 // 1
                 Self::awake();
                 // 2
@@ -440,6 +560,14 @@ impl NetworkLoop {
             Self::late_update();
             // 计算帧数
             NetworkTime::increment_frame_count();
+            // 空闲时睡眠更久, 有数据时立即唤醒
+            if Self::is_idle() {
+                if Self::idle_sleep(Self::idle_sleep_time(target_frame_time)) {
+                    IDLE_TICKS.store(0, Ordering::Relaxed);
+                }
+                continue;
+            }
+            IDLE_TICKS.store(0, Ordering::Relaxed);
             // 计算睡眠时间
             let sleep_time = match NetworkServerStatic::full_update_duration().try_read() {
                 Ok(full_update_duration) => {
@@ -459,10 +587,10 @@ impl NetworkLoop {
                     }
                 }
                 Err(e) => {
-                    log_error!(format!(
+                    log_error!(
                         "Server.network_late_update() full_update_duration error: {}",
                         e
-                    ));
+                    );
                     Duration::from_secs(0)
                 }
             };
@@ -474,3 +602,39 @@ impl NetworkLoop {
         Self::on_destroy();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::transports::memory::memory_transport::test_util::*;
+    use crate::mirror::transports::memory::memory_transport::MemoryTransport;
+
+    #[test]
+    fn test_idle_mode() {
+        with_server(|| {
+            assert!(!NetworkLoop::is_idle());
+            NetworkLoop::set_idle_enabled(true);
+            assert!(NetworkLoop::is_idle());
+            // 之前的测试留下的唤醒
+            NetworkLoop::idle_sleep(Duration::ZERO);
+
+            // 传输层收到连接时立即唤醒
+            let client = std::thread::spawn(|| {
+                std::thread::sleep(Duration::from_millis(50));
+                MemoryTransport::client_connect(1);
+            });
+            let begin = std::time::Instant::now();
+            assert!(NetworkLoop::idle_sleep(Duration::from_secs(10)));
+            assert!(begin.elapsed() < Duration::from_secs(5));
+            client.join().unwrap();
+            tick();
+            assert!(!NetworkLoop::is_idle());
+
+            MemoryTransport::client_disconnect(1);
+            tick();
+            assert!(NetworkLoop::is_idle());
+
+            NetworkLoop::set_idle_enabled(false);
+        });
+    }
+}
@@ -8,29 +8,33 @@ use crate::mirror::core::backend_data::{
     BackendDataStatic, NetworkManagerSetting, SnapshotInterpolationSetting,
 };
 use crate::mirror::core::connection_quality::ConnectionQualityMethod;
+use crate::mirror::core::interest_management::{InterestManagement, InterestManagementStatic};
+use crate::mirror::core::loadout_phase::LoadoutPhase;
 use crate::mirror::core::messages::{AddPlayerMessage, ReadyMessage, SceneMessage, SceneOperation};
 use crate::mirror::core::network_behaviour::GameObject;
 use crate::mirror::core::network_connection::NetworkConnectionTrait;
 use crate::mirror::core::network_connection_to_client::NetworkConnectionToClient;
+use crate::mirror::core::network_context::{ContextCell, ContextLocal};
 use crate::mirror::core::network_reader::NetworkReader;
 use crate::mirror::core::network_server::{EventHandlerType, NetworkServer, NetworkServerStatic};
+use crate::mirror::core::network_start_position::NetworkStartPosition;
+use crate::mirror::core::tools::handshake_timing::{HandshakeStage, HandshakeTiming};
 use crate::mirror::core::transport::{Transport, TransportChannel, TransportError};
 use crate::{log_debug, log_error, log_warn};
-use atomic::Atomic;
 use dashmap::try_result::TryResult;
 use lazy_static::lazy_static;
 use nalgebra::Vector3;
 use rand::Rng;
 use std::any::Any;
-use std::sync::atomic::Ordering;
 use std::sync::{Arc, RwLock};
 
-static mut NETWORK_MANAGER_SINGLETON: Option<Box<dyn NetworkManagerTrait>> = None;
-
 lazy_static! {
-    static ref START_POSITIONS: Arc<RwLock<Vec<Transform>>> = Arc::new(RwLock::new(Vec::new()));
-    static ref START_POSITIONS_INDEX: Atomic<usize> = Atomic::new(0);
-    static ref NETWORK_SCENE_NAME: RwLock<String> = RwLock::new("".to_string());
+    // 每个上下文一个 NetworkManager
+    static ref NETWORK_MANAGER_SINGLETON:
+        ContextLocal<ContextCell<Option<Box<dyn NetworkManagerTrait>>>> =
+        ContextLocal::new(|| ContextCell::new(None));
+    static ref NETWORK_SCENE_NAME: ContextLocal<RwLock<String>> =
+        ContextLocal::new(|| RwLock::new("".to_string()));
 }
 
 // NetworkManagerStatic
@@ -39,8 +43,9 @@ pub struct NetworkManagerStatic;
 // NetworkManagerStatic 的默认实现
 impl NetworkManagerStatic {
     pub fn network_manager_singleton() -> &'static mut Box<dyn NetworkManagerTrait> {
+        let cell: &'static ContextCell<_> = &NETWORK_MANAGER_SINGLETON;
         unsafe {
-            if let Some(ref mut singleton) = NETWORK_MANAGER_SINGLETON {
+            if let Some(ref mut singleton) = *cell.as_ptr() {
                 return singleton;
             }
             panic!("NetworkManager singleton not found.");
@@ -49,13 +54,13 @@ impl NetworkManagerStatic {
 
     #[allow(warnings)]
     pub fn network_manager_singleton_exists() -> bool {
-        unsafe { NETWORK_MANAGER_SINGLETON.is_some() }
+        unsafe { (*NETWORK_MANAGER_SINGLETON.as_ptr()).is_some() }
     }
 
     #[allow(warnings)]
     pub fn set_network_manager_singleton(network_manager: Box<dyn NetworkManagerTrait>) {
         unsafe {
-            NETWORK_MANAGER_SINGLETON.replace(network_manager);
+            (*NETWORK_MANAGER_SINGLETON.as_ptr()).replace(network_manager);
         }
     }
 
@@ -75,31 +80,14 @@ impl NetworkManagerStatic {
         }
     }
 
-    pub fn start_positions_index() -> usize {
-        START_POSITIONS_INDEX.load(Ordering::Relaxed)
-    }
-
-    pub fn set_start_positions_index(index: usize) {
-        START_POSITIONS_INDEX.store(index, Ordering::Relaxed);
-    }
-
-    pub fn start_positions() -> &'static Arc<RwLock<Vec<Transform>>> {
-        &START_POSITIONS
-    }
-
-    pub fn add_start_position(start: Transform) {
-        match START_POSITIONS.write() {
-            Ok(mut sps) => {
-                sps.push(start);
-            }
-            Err(e) => {
-                log_error!(format!("Failed to add start position: {:?}", e));
-            }
-        }
+    // 在 NetworkServer::reset_all_statics 中调用, 单例和注册的出生点保留
+    pub fn reset_statics() {
+        NetworkStartPosition::reset_round_robin();
+        Self::set_network_scene_name("".to_string());
     }
 }
 
-#[derive(Debug, PartialOrd, PartialEq)]
+#[derive(Debug, PartialOrd, PartialEq, Clone, Copy)]
 pub enum PlayerSpawnMethod {
     Random,
     RoundRobin,
@@ -129,6 +117,8 @@ pub struct NetworkManager {
     pub disconnect_inactive_connections: bool,
     pub disconnect_inactive_timeout: f32,
     pub authenticator: Option<Box<dyn NetworkAuthenticatorTrait>>,
+    // 自定义兴趣管理, 在 setup_server 中设置
+    pub interest_management: Option<Arc<dyn InterestManagement>>,
     pub auto_create_player: bool,
     pub player_spawn_method: PlayerSpawnMethod,
     pub spawn_prefabs: Vec<GameObject>,
@@ -164,6 +154,7 @@ impl NetworkManager {
                 .disconnect_inactive_connections,
             disconnect_inactive_timeout: network_manager_setting.disconnect_inactive_timeout,
             authenticator: None,
+            interest_management: None,
             player_obj: GameObject::new_with_prefab(network_manager_setting.player_prefab.clone()),
             auto_create_player: network_manager_setting.auto_create_player,
             player_spawn_method: PlayerSpawnMethod::Random,
@@ -194,6 +185,10 @@ impl NetworkManager {
             );
         }
 
+        if let Some(ref interest_management) = self.interest_management {
+            InterestManagementStatic::set(interest_management.clone());
+        }
+
         NetworkServer::listen(self.max_connections);
 
         Self::register_server_messages();
@@ -241,6 +236,7 @@ impl NetworkManager {
     pub fn on_server_authenticated(conn: &mut NetworkConnectionToClient) {
         // 获取 NetworkManagerTrait 的单例
         conn.set_authenticated(true);
+        HandshakeTiming::record(conn.connection_id(), HandshakeStage::Authenticated);
 
         // 获取 NetworkManagerTrait 的单例
         let network_manager = NetworkManagerStatic::network_manager_singleton();
@@ -261,6 +257,9 @@ impl NetworkManager {
             conn.send_network_message(&mut scene_message, TransportChannel::Reliable);
         }
 
+        // 可选的角色 / 装备选择阶段
+        LoadoutPhase::on_authenticated(conn);
+
         Self::on_server_connect(conn);
     }
 
@@ -308,23 +307,23 @@ impl NetworkManager {
         // 如果 NetworkManager 的 auto_create_player 为 false
         match NetworkServerStatic::network_connections().try_get(&conn_id) {
             TryResult::Present(coon) => {
-                if coon.net_id() != 0 {
+                if coon.net_id() != 0 && !NetworkServerStatic::add_player_replace() {
                     log_error!("There is already a player for this connection.");
                     return;
                 }
             }
             TryResult::Absent => {
-                log_error!(format!(
+                log_error!(
                     "Failed to on_server_add_player_internal for coon {} because of absent",
                     conn_id
-                ));
+                );
                 return;
             }
             TryResult::Locked => {
-                log_error!(format!(
+                log_error!(
                     "Failed to on_server_add_player_internal for coon {} because of locked",
                     conn_id
-                ));
+                );
                 return;
             }
         }
@@ -341,7 +340,7 @@ impl NetworkManager {
             let v3 = Vector3::new(x, y, z);
             start.position = v3;
             start.local_position = v3;
-            NetworkManagerStatic::add_start_position(start);
+            NetworkStartPosition::register(NetworkStartPosition::ANY_SCENE, start);
         }
     }
 
@@ -423,33 +422,34 @@ pub trait NetworkManagerTrait: Any {
         });
         num_players
     }
+    // 在同一个进程中重新启动服务器, 例如大厅结束后复用进程开始对局
+    fn start_server(&mut self) {
+        if NetworkServerStatic::active() {
+            log_warn!("Server already started.");
+            return;
+        }
+        self.start();
+    }
+    // 关闭 Transport 监听, 断开所有客户端, 清空生成的对象和 net_id 计数, 之后可以再次 start_server
     fn stop_server(&mut self) {
         if !NetworkServerStatic::active() {
             log_warn!("Server already stopped.");
             return;
         }
 
-        if let Some(ref mut authenticator) =
-            NetworkManagerStatic::network_manager_singleton().authenticator()
-        {
+        if let Some(ref mut authenticator) = self.authenticator() {
             authenticator.on_stop_server();
         }
 
         self.on_stop_server();
 
-        NetworkManagerStatic::start_positions()
-            .write()
-            .unwrap()
-            .clear();
-        NetworkManagerStatic::set_start_positions_index(0);
+        NetworkStartPosition::reset_round_robin();
         NetworkManagerStatic::set_network_scene_name("".to_string());
 
         NetworkServer::shutdown();
 
         self.set_mode(NetworkManagerMode::Offline);
 
-        NetworkManagerStatic::set_start_positions_index(0);
-
         NetworkManagerStatic::set_network_scene_name("".to_string());
     }
     fn reset(&mut self);
@@ -468,8 +468,16 @@ pub trait NetworkManagerTrait: Any {
     fn late_update(&mut self);
     fn on_destroy(&mut self);
     fn server_change_scene(&mut self, new_scene_name: String);
+    fn player_spawn_method(&self) -> PlayerSpawnMethod {
+        PlayerSpawnMethod::Random
+    }
+    // 当前场景的下一个出生点, 没有出生点时使用原点
     fn get_start_position(&mut self) -> Transform {
-        Transform::default()
+        NetworkStartPosition::next_start_position(
+            &NetworkManagerStatic::network_scene_name(),
+            self.player_spawn_method(),
+        )
+        .unwrap_or_else(Transform::default)
     }
     fn on_server_connect(conn: &mut NetworkConnectionToClient)
     where
@@ -651,10 +659,10 @@ impl NetworkManagerTrait for NetworkManager {
         if NetworkServerStatic::is_loading_scene()
             && new_scene_name == NetworkManagerStatic::network_scene_name()
         {
-            log_error!(format!(
+            log_error!(
                 "Scene change is already in progress for scene: {}",
                 new_scene_name
-            ));
+            );
             return;
         }
 
@@ -679,38 +687,10 @@ impl NetworkManagerTrait for NetworkManager {
                 false,
             );
         }
-
-        NetworkManagerStatic::set_start_positions_index(0);
-        NetworkManagerStatic::start_positions().write().unwrap().clear();
     }
 
-    fn get_start_position(&mut self) -> Transform {
-        if NetworkManagerStatic::start_positions()
-            .read()
-            .unwrap()
-            .len()
-            == 0
-        {
-            return Transform::default();
-        }
-
-        if self.player_spawn_method == PlayerSpawnMethod::Random {
-            let index = rand::random::<u32>()
-                % NetworkManagerStatic::start_positions()
-                .read()
-                .unwrap()
-                .len() as u32;
-            return NetworkManagerStatic::start_positions().read().unwrap()[index as usize].clone();
-        }
-        let index = NetworkManagerStatic::start_positions_index();
-        NetworkManagerStatic::set_start_positions_index(
-            index
-                + 1 % NetworkManagerStatic::start_positions()
-                .read()
-                .unwrap()
-                .len(),
-        );
-        NetworkManagerStatic::start_positions().read().unwrap()[index].clone()
+    fn player_spawn_method(&self) -> PlayerSpawnMethod {
+        self.player_spawn_method
     }
 
     // OnServerDisconnect
@@ -1,8 +1,11 @@
 use crate::log_warn;
+use crate::mirror::core::network_behaviour::NetworkBehaviourRef;
+use crate::mirror::core::unity_types::{Color, Color32, Guid, Plane, Quat, Ray, Rect, Vec3};
 use half::f16;
-use nalgebra::{Quaternion, Vector2, Vector3, Vector4};
+use nalgebra::{Matrix4, Quaternion, Vector2, Vector3, Vector4};
 use rust_decimal::Decimal;
 use std::fmt;
+use std::time::SystemTime;
 
 pub struct NetworkReader {
     data: Vec<u8>,
@@ -156,6 +159,7 @@ pub trait NetworkReaderTrait {
     fn read_double_nullable(&mut self) -> Option<f64>;
 
     fn read_string(&mut self) -> String;
+    fn read_string_nullable(&mut self) -> Option<String>;
 
     fn read_var_int(&mut self) -> i32;
     fn read_var_uint(&mut self) -> u32;
@@ -182,6 +186,47 @@ pub trait NetworkReaderTrait {
 
     fn read_quaternion(&mut self) -> Quaternion<f32>;
     fn read_quaternion_nullable(&mut self) -> Option<Quaternion<f32>>;
+
+    // 不依赖 nalgebra 的 Vec3 / Quat, 字节与 read_vector3 / read_quaternion 相同
+    fn read_vec3(&mut self) -> Vec3;
+    fn read_vec3_nullable(&mut self) -> Option<Vec3>;
+
+    fn read_quat(&mut self) -> Quat;
+    fn read_quat_nullable(&mut self) -> Option<Quat>;
+
+    fn read_color(&mut self) -> Color;
+    fn read_color_nullable(&mut self) -> Option<Color>;
+
+    fn read_color32(&mut self) -> Color32;
+    fn read_color32_nullable(&mut self) -> Option<Color32>;
+
+    fn read_rect(&mut self) -> Rect;
+    fn read_rect_nullable(&mut self) -> Option<Rect>;
+
+    fn read_plane(&mut self) -> Plane;
+    fn read_plane_nullable(&mut self) -> Option<Plane>;
+
+    fn read_ray(&mut self) -> Ray;
+    fn read_ray_nullable(&mut self) -> Option<Ray>;
+
+    fn read_matrix4x4(&mut self) -> Matrix4<f32>;
+    fn read_matrix4x4_nullable(&mut self) -> Option<Matrix4<f32>>;
+
+    fn read_guid(&mut self) -> Guid;
+    fn read_guid_nullable(&mut self) -> Option<Guid>;
+
+    fn read_date_time(&mut self) -> SystemTime;
+    fn read_date_time_nullable(&mut self) -> Option<SystemTime>;
+
+    // 对象没有生成时返回 None, 与 C# 读取到 null 相同
+    fn read_network_identity(&mut self) -> Option<u32>;
+    fn read_game_object(&mut self) -> Option<u32>;
+    fn read_network_behaviour(&mut self) -> Option<NetworkBehaviourRef>;
+
+    fn read_array<T, F: Fn(&mut Self) -> T>(&mut self, read: F) -> Option<Vec<T>>;
+    fn read_list<T, F: Fn(&mut Self) -> T>(&mut self, read: F) -> Option<Vec<T>>;
+    fn read_segment<T, F: Fn(&mut Self) -> T>(&mut self, read: F) -> Vec<T>;
+
     fn decompress_var(&mut self) -> Vec<u8>;
     fn decompress_var_int(&mut self) -> i32;
     fn decompress_var_uint(&mut self) -> u32;
@@ -191,7 +236,11 @@ pub trait NetworkReaderTrait {
 
 impl fmt::Display for NetworkReader {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let hex_string = self.data.iter().map(|byte| format!("{:02X}", byte)).collect::<String>();
-        write!(f, "[{} @ {}/{}]", hex_string, self.position, self.capacity())
+        // 逐字节写入 Formatter, 不构造中间字符串
+        write!(f, "[")?;
+        for byte in self.data.iter() {
+            write!(f, "{:02X}", byte)?;
+        }
+        write!(f, " @ {}/{}]", self.position, self.capacity())
     }
 }
\ No newline at end of file
@@ -1,8 +1,15 @@
+use crate::mirror::core::network_behaviour::NetworkBehaviourRef;
 use crate::mirror::core::network_reader::{NetworkReader, NetworkReaderTrait, Readable};
-use crate::{log_error, log_trace};
+use crate::mirror::core::network_serialization::{ActiveSerializationBackend, SerializationBackend};
+use crate::mirror::core::network_server::NetworkServerStatic;
+use crate::mirror::core::network_writer_extensions::NetworkWriterExtensions;
+use crate::mirror::core::unity_types::{Color, Color32, Guid, Plane, Quat, Ray, Rect, Vec3};
+use crate::{log_error, log_trace, log_warn};
+use dashmap::try_result::TryResult;
 use half::f16;
-use nalgebra::{Quaternion, Vector2, Vector3, Vector4};
+use nalgebra::{Matrix4, Quaternion, Vector2, Vector3, Vector4};
 use rust_decimal::Decimal;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 pub struct NetworkReaderExtensions;
 impl NetworkReaderExtensions {
@@ -19,6 +26,63 @@ impl NetworkReaderExtensions {
             String::new()
         }
     }
+
+    // DateTime.FromOADate, 毫秒精度
+    fn from_oa_date(value: f64) -> SystemTime {
+        let milliseconds =
+            ((value - NetworkWriterExtensions::OA_DATE_UNIX_EPOCH) * 86_400_000.0).round();
+        if milliseconds >= 0.0 {
+            UNIX_EPOCH + Duration::from_millis(milliseconds as u64)
+        } else {
+            UNIX_EPOCH - Duration::from_millis(-milliseconds as u64)
+        }
+    }
+
+    // 返回对象的组件数量, 没有生成时返回 None
+    fn spawned_behaviours_count(net_id: u32) -> Option<u8> {
+        match NetworkServerStatic::spawned_network_identities().try_get(&net_id) {
+            TryResult::Present(identity) => Some(identity.network_behaviours_count),
+            // 正在被修改, 对象一定存在
+            TryResult::Locked => Some(u8::MAX),
+            TryResult::Absent => {
+                log_warn!("NetworkReader: netId {} not found in spawned", net_id);
+                None
+            }
+        }
+    }
+
+    fn read_collection<T, F: Fn(&mut NetworkReader) -> T>(
+        reader: &mut NetworkReader,
+        read: F,
+    ) -> Option<Vec<T>> {
+        // count + 1, 0 表示 null
+        let count = reader.decompress_var_uint() as usize;
+        if count == 0 {
+            return None;
+        }
+        Some(Self::read_items(reader, count - 1, read))
+    }
+
+    fn read_items<T, F: Fn(&mut NetworkReader) -> T>(
+        reader: &mut NetworkReader,
+        length: usize,
+        read: F,
+    ) -> Vec<T> {
+        if length > NetworkReader::ALLOCATION_LIMIT {
+            log_warn!(
+                "NetworkReader attempted to allocate {} items, which is larger than the allowed limit of {}",
+                length,
+                NetworkReader::ALLOCATION_LIMIT
+            );
+            return Vec::new();
+        }
+        // 每个元素至少 1 字节, 不按恶意的长度预分配
+        let mut items = Vec::with_capacity(length.min(reader.remaining()));
+        for _ in 0..length {
+            items.push(read(reader));
+        }
+        items
+    }
 }
 impl NetworkReaderTrait for NetworkReader {
     fn read_byte(&mut self) -> u8 {
@@ -37,20 +101,27 @@ impl NetworkReaderTrait for NetworkReader {
         self.read_blittable_nullable::<i8>()
     }
 
+    // C# 的 char 是 2 字节的 UTF-16 代码单元
     fn read_char(&mut self) -> char {
-        self.read_blittable::<char>()
+        char::from_u32(self.read_ushort() as u32).unwrap_or(char::REPLACEMENT_CHARACTER)
     }
 
     fn read_char_nullable(&mut self) -> Option<char> {
-        self.read_blittable_nullable::<char>()
+        let has_value = self.read_bool();
+        if has_value {
+            Some(self.read_char())
+        } else {
+            None
+        }
     }
 
+    // 不直接读取 bool, 非 0 / 1 的字节不是合法的 bool
     fn read_bool(&mut self) -> bool {
-        self.read_blittable::<bool>()
+        self.read_byte() != 0
     }
 
     fn read_bool_nullable(&mut self) -> Option<bool> {
-        self.read_blittable_nullable::<bool>()
+        self.read_byte_nullable().map(|v| v != 0)
     }
 
     fn read_short(&mut self) -> i16 {
@@ -70,7 +141,7 @@ impl NetworkReaderTrait for NetworkReader {
     }
 
     fn read_int(&mut self) -> i32 {
-        self.read_blittable::<i32>()
+        ActiveSerializationBackend::read_int(self)
     }
 
     fn read_int_nullable(&mut self) -> Option<i32> {
@@ -78,7 +149,7 @@ impl NetworkReaderTrait for NetworkReader {
     }
 
     fn read_uint(&mut self) -> u32 {
-        self.read_blittable::<u32>()
+        ActiveSerializationBackend::read_uint(self)
     }
 
     fn read_uint_nullable(&mut self) -> Option<u32> {
@@ -86,7 +157,7 @@ impl NetworkReaderTrait for NetworkReader {
     }
 
     fn read_long(&mut self) -> i64 {
-        self.read_blittable::<i64>()
+        ActiveSerializationBackend::read_long(self)
     }
 
     fn read_long_nullable(&mut self) -> Option<i64> {
@@ -94,7 +165,7 @@ impl NetworkReaderTrait for NetworkReader {
     }
 
     fn read_ulong(&mut self) -> u64 {
-        self.read_blittable::<u64>()
+        ActiveSerializationBackend::read_ulong(self)
     }
 
     fn read_ulong_nullable(&mut self) -> Option<u64> {
@@ -121,6 +192,16 @@ impl NetworkReaderTrait for NetworkReader {
         self.read()
     }
 
+    fn read_string_nullable(&mut self) -> Option<String> {
+        // 长度 0 表示 null, 不移动位置
+        let position = self.get_position();
+        if self.read_ushort() == 0 {
+            return None;
+        }
+        self.set_position(position);
+        Some(self.read_string())
+    }
+
     fn read_var_int(&mut self) -> i32 {
         self.decompress_var_long() as i32
     }
@@ -217,6 +298,179 @@ impl NetworkReaderTrait for NetworkReader {
         }
     }
 
+    fn read_vec3(&mut self) -> Vec3 {
+        self.read_blittable::<Vec3>()
+    }
+
+    fn read_vec3_nullable(&mut self) -> Option<Vec3> {
+        let has_value = self.read_bool();
+        if has_value {
+            Some(self.read_vec3())
+        } else {
+            None
+        }
+    }
+
+    fn read_quat(&mut self) -> Quat {
+        self.read_blittable::<Quat>()
+    }
+
+    fn read_quat_nullable(&mut self) -> Option<Quat> {
+        let has_value = self.read_bool();
+        if has_value {
+            Some(self.read_quat())
+        } else {
+            None
+        }
+    }
+
+    fn read_color(&mut self) -> Color {
+        self.read_blittable::<Color>()
+    }
+
+    fn read_color_nullable(&mut self) -> Option<Color> {
+        let has_value = self.read_bool();
+        if has_value {
+            Some(self.read_color())
+        } else {
+            None
+        }
+    }
+
+    fn read_color32(&mut self) -> Color32 {
+        self.read_blittable::<Color32>()
+    }
+
+    fn read_color32_nullable(&mut self) -> Option<Color32> {
+        let has_value = self.read_bool();
+        if has_value {
+            Some(self.read_color32())
+        } else {
+            None
+        }
+    }
+
+    fn read_rect(&mut self) -> Rect {
+        Rect::new(
+            self.read_float(),
+            self.read_float(),
+            self.read_float(),
+            self.read_float(),
+        )
+    }
+
+    fn read_rect_nullable(&mut self) -> Option<Rect> {
+        let has_value = self.read_bool();
+        if has_value {
+            Some(self.read_rect())
+        } else {
+            None
+        }
+    }
+
+    fn read_plane(&mut self) -> Plane {
+        let normal = self.read_vector3();
+        Plane::new(normal, self.read_float())
+    }
+
+    fn read_plane_nullable(&mut self) -> Option<Plane> {
+        let has_value = self.read_bool();
+        if has_value {
+            Some(self.read_plane())
+        } else {
+            None
+        }
+    }
+
+    fn read_ray(&mut self) -> Ray {
+        let origin = self.read_vector3();
+        Ray::new(origin, self.read_vector3())
+    }
+
+    fn read_ray_nullable(&mut self) -> Option<Ray> {
+        let has_value = self.read_bool();
+        if has_value {
+            Some(self.read_ray())
+        } else {
+            None
+        }
+    }
+
+    fn read_matrix4x4(&mut self) -> Matrix4<f32> {
+        self.read_blittable::<Matrix4<f32>>()
+    }
+
+    fn read_matrix4x4_nullable(&mut self) -> Option<Matrix4<f32>> {
+        let has_value = self.read_bool();
+        if has_value {
+            Some(self.read_matrix4x4())
+        } else {
+            None
+        }
+    }
+
+    fn read_guid(&mut self) -> Guid {
+        Guid(self.read_blittable::<[u8; 16]>())
+    }
+
+    fn read_guid_nullable(&mut self) -> Option<Guid> {
+        self.read_blittable_nullable::<[u8; 16]>().map(Guid)
+    }
+
+    fn read_date_time(&mut self) -> SystemTime {
+        NetworkReaderExtensions::from_oa_date(self.read_double())
+    }
+
+    fn read_date_time_nullable(&mut self) -> Option<SystemTime> {
+        self.read_double_nullable()
+            .map(NetworkReaderExtensions::from_oa_date)
+    }
+
+    fn read_network_identity(&mut self) -> Option<u32> {
+        let net_id = self.read_uint();
+        if net_id == 0 {
+            return None;
+        }
+        NetworkReaderExtensions::spawned_behaviours_count(net_id).map(|_| net_id)
+    }
+
+    fn read_game_object(&mut self) -> Option<u32> {
+        self.read_network_identity()
+    }
+
+    fn read_network_behaviour(&mut self) -> Option<NetworkBehaviourRef> {
+        let net_id = self.read_uint();
+        if net_id == 0 {
+            return None;
+        }
+        // 对象不存在时也要读取组件索引
+        let component_index = self.read_byte();
+        let count = NetworkReaderExtensions::spawned_behaviours_count(net_id)?;
+        if component_index >= count {
+            log_warn!(
+                "NetworkReader: netId {} has no component at index {}",
+                net_id,
+                component_index
+            );
+            return None;
+        }
+        Some(NetworkBehaviourRef::new(net_id, component_index))
+    }
+
+    fn read_array<T, F: Fn(&mut Self) -> T>(&mut self, read: F) -> Option<Vec<T>> {
+        NetworkReaderExtensions::read_collection(self, read)
+    }
+
+    fn read_list<T, F: Fn(&mut Self) -> T>(&mut self, read: F) -> Option<Vec<T>> {
+        NetworkReaderExtensions::read_collection(self, read)
+    }
+
+    fn read_segment<T, F: Fn(&mut Self) -> T>(&mut self, read: F) -> Vec<T> {
+        // 写入时不会是 null, 收到 0 时按空处理
+        let count = self.decompress_var_uint() as usize;
+        NetworkReaderExtensions::read_items(self, count.saturating_sub(1), read)
+    }
+
     fn decompress_var(&mut self) -> Vec<u8> {
         let mut value = Vec::new();
         let a0 = self.read_byte();
@@ -403,4 +657,184 @@ mod tests {
         let value = reader.read_string();
         assert_eq!(value, "Hello, World!");
     }
+
+    #[test]
+    fn read_unity_types() {
+        use crate::mirror::core::unity_types::{Color, Color32, Plane, Ray, Rect};
+        use nalgebra::{Matrix4, Vector3};
+
+        let color = Color::new(1.0, 0.5, 0.25, 1.0);
+        let color32 = Color32::new(1, 2, 3, 4);
+        let rect = Rect::new(1.0, 2.0, 3.0, 4.0);
+        let plane = Plane::new(Vector3::new(0.0, 1.0, 0.0), 5.0);
+        let ray = Ray::new(Vector3::new(1.0, 2.0, 3.0), Vector3::new(0.0, 0.0, 1.0));
+        // 按行传入, m01 = 2
+        let matrix = Matrix4::new(
+            1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0, 11.0, 12.0, 13.0, 14.0, 15.0, 16.0,
+        );
+
+        let mut writer = NetworkWriter::new();
+        writer.write_color(color);
+        writer.write_color32(color32);
+        writer.write_rect(rect);
+        writer.write_plane(plane);
+        writer.write_ray(ray);
+        writer.write_matrix4x4(matrix);
+        writer.write_color_nullable(None);
+        writer.write_color32_nullable(Some(color32));
+        let bytes = writer.to_bytes();
+        assert_eq!(bytes.len(), 16 + 4 + 16 + 16 + 24 + 64 + 1 + 5);
+        assert_eq!(&bytes[16..20], &[1, 2, 3, 4]);
+        // 与 C# 相同的列主序: m00 m10 m20 m30 m01
+        assert_eq!(&bytes[80..84], &5.0f32.to_le_bytes());
+        assert_eq!(&bytes[92..96], &2.0f32.to_le_bytes());
+        assert_eq!(&bytes[140..], &[0, 1, 1, 2, 3, 4]);
+
+        let mut reader = NetworkReader::new_with_bytes(bytes);
+        assert_eq!(reader.read_color(), color);
+        assert_eq!(reader.read_color32(), color32);
+        assert_eq!(reader.read_rect(), rect);
+        assert_eq!(reader.read_plane(), plane);
+        assert_eq!(reader.read_ray(), ray);
+        assert_eq!(reader.read_matrix4x4(), matrix);
+        assert_eq!(reader.read_color_nullable(), None);
+        assert_eq!(reader.read_color32_nullable(), Some(color32));
+        assert_eq!(reader.remaining(), 0);
+
+        assert_eq!(Color32::from(color), Color32::new(255, 128, 64, 255));
+    }
+
+    #[test]
+    fn read_vec3_and_quat() {
+        use crate::mirror::core::unity_types::{Quat, Vec3};
+        use nalgebra::{Quaternion, Vector3};
+
+        // 与 nalgebra 类型写入的字节相同, 可以互相读取
+        let mut writer = NetworkWriter::new();
+        writer.write_vector3(Vector3::new(1.0, 2.0, 3.0));
+        writer.write_quaternion(Quaternion::new(4.0, 1.0, 2.0, 3.0));
+        let expected = writer.to_bytes();
+
+        writer.reset();
+        writer.write_vec3(Vec3::new(1.0, 2.0, 3.0));
+        writer.write_quat(Quat::new(1.0, 2.0, 3.0, 4.0));
+        writer.write_vec3_nullable(None);
+        writer.write_quat_nullable(Some(Quat::IDENTITY));
+        let bytes = writer.to_bytes();
+        assert_eq!(&bytes[..28], &expected[..]);
+        assert_eq!(bytes.len(), 28 + 1 + 17);
+
+        let mut reader = NetworkReader::new_with_bytes(bytes);
+        assert_eq!(reader.read_vector3(), Vector3::new(1.0, 2.0, 3.0));
+        assert_eq!(reader.read_quat(), Quat::new(1.0, 2.0, 3.0, 4.0));
+        assert_eq!(reader.read_vec3_nullable(), None);
+        assert_eq!(
+            reader.read_quaternion_nullable(),
+            Some(Quaternion::new(1.0, 0.0, 0.0, 0.0))
+        );
+        assert_eq!(reader.remaining(), 0);
+    }
+
+    #[test]
+    fn read_nullable_and_collections() {
+        use crate::mirror::core::unity_types::Guid;
+        use nalgebra::Vector3;
+        use std::time::{Duration, UNIX_EPOCH};
+
+        // 与 C# 的 Mirror 相同的字节
+        let mut writer = NetworkWriter::new();
+        writer.write_int_nullable(Some(7));
+        writer.write_int_nullable(None);
+        writer.write_bool_nullable(Some(true));
+        writer.write_char_nullable(Some('A'));
+        writer.write_vector3_nullable(None);
+        writer.write_string_nullable(None);
+        writer.write_string_nullable(Some(""));
+        assert_eq!(
+            writer.to_bytes(),
+            vec![1, 7, 0, 0, 0, 0, 1, 1, 1, 65, 0, 0, 0, 0, 1, 0]
+        );
+
+        writer.reset();
+        writer.write_vector3_nullable(Some(Vector3::new(1.0, 2.0, 3.0)));
+        writer.write_array(Some(&[1, 2, 3]), |w, v| w.write_int(*v));
+        writer.write_list::<String, _>(None, |w, v| w.write_str(v));
+        writer.write_list(Some(&["a".to_string()]), |w, v| w.write_str(v));
+        writer.write_segment(&[true, false], |w, v| w.write_bool(*v));
+        writer.write_guid(Guid::new([9; 16]));
+        // 2000-01-01T00:00:00Z
+        let date = UNIX_EPOCH + Duration::from_secs(946_684_800);
+        writer.write_date_time(date);
+        let bytes = writer.to_bytes();
+        assert_eq!(bytes[13], 4);
+        assert_eq!(bytes[26], 0);
+        assert_eq!(bytes[27], 2);
+        assert_eq!(bytes[31], 3);
+        assert_eq!(&bytes[bytes.len() - 8..], &36526.0f64.to_le_bytes());
+
+        let mut reader = NetworkReader::new_with_bytes(bytes);
+        assert_eq!(
+            reader.read_vector3_nullable(),
+            Some(Vector3::new(1.0, 2.0, 3.0))
+        );
+        assert_eq!(reader.read_array(|r| r.read_int()), Some(vec![1, 2, 3]));
+        assert_eq!(reader.read_list(|r| r.read_string()), None);
+        assert_eq!(
+            reader.read_list(|r| r.read_string()),
+            Some(vec!["a".to_string()])
+        );
+        assert_eq!(reader.read_segment(|r| r.read_bool()), vec![true, false]);
+        assert_eq!(reader.read_guid(), Guid::new([9; 16]));
+        assert_eq!(reader.read_date_time(), date);
+        assert_eq!(reader.remaining(), 0);
+
+        let mut reader =
+            NetworkReader::new_with_bytes(vec![1, 7, 0, 0, 0, 0, 1, 1, 1, 65, 0, 0, 0, 0, 1, 0]);
+        assert_eq!(reader.read_int_nullable(), Some(7));
+        assert_eq!(reader.read_int_nullable(), None);
+        assert_eq!(reader.read_bool_nullable(), Some(true));
+        assert_eq!(reader.read_char_nullable(), Some('A'));
+        assert_eq!(reader.read_vector3_nullable(), None);
+        assert_eq!(reader.read_string_nullable(), None);
+        assert_eq!(reader.read_string_nullable(), Some(String::new()));
+    }
+
+    #[test]
+    fn read_object_references() {
+        use crate::mirror::core::network_behaviour::NetworkBehaviourRef;
+        use crate::mirror::core::network_identity::NetworkIdentity;
+        use crate::mirror::transports::memory::memory_transport::test_util::with_isolated_context;
+
+        with_isolated_context(|| {
+            let mut identity = NetworkIdentity::new_with_asset_id(1);
+            identity.set_net_id(3);
+            identity.network_behaviours_count = 2;
+            NetworkServerStatic::add_spawned_network_identity(identity);
+
+            let mut writer = NetworkWriter::new();
+            writer.write_network_identity(Some(3));
+            writer.write_game_object(None);
+            writer.write_network_behaviour(Some(NetworkBehaviourRef::new(3, 1)));
+            writer.write_network_behaviour(None);
+            // 没有生成的对象和不存在的组件
+            writer.write_network_identity(Some(4));
+            writer.write_network_behaviour(Some(NetworkBehaviourRef::new(4, 0)));
+            writer.write_network_behaviour(Some(NetworkBehaviourRef::new(3, 2)));
+            let bytes = writer.to_bytes();
+            assert_eq!(&bytes[..13], &[3, 0, 0, 0, 0, 0, 0, 0, 3, 0, 0, 0, 1]);
+
+            let mut reader = NetworkReader::new_with_bytes(bytes);
+            assert_eq!(reader.read_network_identity(), Some(3));
+            assert_eq!(reader.read_game_object(), None);
+            assert_eq!(
+                reader.read_network_behaviour(),
+                Some(NetworkBehaviourRef::new(3, 1))
+            );
+            assert_eq!(reader.read_network_behaviour(), None);
+            assert_eq!(reader.read_network_identity(), None);
+            assert_eq!(reader.read_network_behaviour(), None);
+            assert_eq!(reader.read_network_behaviour(), None);
+            assert_eq!(reader.remaining(), 0);
+        });
+    }
 }
@@ -1,5 +1,6 @@
 use crate::log_warn;
 use crate::mirror::core::network_reader::NetworkReader;
+use crate::mirror::core::tools::alloc_audit::{AllocAudit, AllocSite};
 use crate::mirror::core::tools::pool::Pool;
 use lazy_static::lazy_static;
 use std::sync::{Arc, Mutex};
@@ -19,8 +20,21 @@ impl NetworkReaderPool {
         }
     }
 
+    // 池中 NetworkReader 的缓冲区大小之和
+    pub fn memory_usage() -> usize {
+        if let Ok(pool) = NETWORK_READER_POOL.lock() {
+            pool.memory_usage(|reader| size_of::<NetworkReader>() + reader.capacity())
+        } else {
+            log_warn!("NetworkReaderPool::memory_usage() failed to lock NETWORK_READER_POOL");
+            0
+        }
+    }
+
     pub fn get() -> NetworkReader {
         if let Ok(mut pool) = NETWORK_READER_POOL.lock() {
+            if pool.count() == 0 {
+                AllocAudit::record(AllocSite::ReaderPoolMiss);
+            }
             let mut reader = pool.get();
             reader.reset();
             reader
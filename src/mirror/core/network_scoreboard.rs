@@ -0,0 +1,259 @@
+use crate::log_error;
+use crate::mirror::core::messages::{ScoreEntry, ScoreboardDeltaMessage, ScoreboardFullMessage};
+use crate::mirror::core::network_connection::NetworkConnectionTrait;
+use crate::mirror::core::network_connection_to_client::NetworkConnectionToClient;
+use crate::mirror::core::network_context::ContextLocal;
+use crate::mirror::core::network_server::NetworkServer;
+use crate::mirror::core::network_time::NetworkTime;
+use crate::mirror::core::transport::TransportChannel;
+use atomic::Atomic;
+use dashmap::DashMap;
+use lazy_static::lazy_static;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::atomic::Ordering;
+use std::sync::RwLock;
+
+#[derive(Debug, Default)]
+struct PendingChanges {
+    // (player, stat)
+    dirty: BTreeSet<(u64, String)>,
+    removed: BTreeSet<u64>,
+}
+
+// NetworkScoreboard 静态变量
+lazy_static! {
+    // player -> stat -> value
+    static ref SCORES: ContextLocal<DashMap<u64, HashMap<String, i32>>> =
+        ContextLocal::new(DashMap::new);
+    static ref PENDING: ContextLocal<RwLock<PendingChanges>> =
+        ContextLocal::new(|| RwLock::new(PendingChanges::default()));
+    // 单位秒, 记分板不需要每个 tick 同步
+    static ref SYNC_INTERVAL: ContextLocal<Atomic<f64>> = ContextLocal::new(|| Atomic::new(1.0));
+    static ref LAST_SYNC_TIME: ContextLocal<Atomic<f64>> = ContextLocal::new(|| Atomic::new(0.0));
+}
+
+// 全局的玩家统计 (击杀 / 死亡 / 分数), 不属于任何 NetworkIdentity
+// 修改只记录脏标记, 按 sync_interval 把变化合并成一个 ScoreboardDeltaMessage 发给所有准备好的客户端,
+// 客户端准备好时先收到一个完整的 ScoreboardFullMessage
+// player 通常是 connection_id, 断开连接时自动移除
+pub struct NetworkScoreboard;
+
+impl NetworkScoreboard {
+    pub fn sync_interval() -> f64 {
+        SYNC_INTERVAL.load(Ordering::Relaxed)
+    }
+
+    pub fn set_sync_interval(value: f64) {
+        SYNC_INTERVAL.store(value, Ordering::Relaxed);
+    }
+
+    pub fn get(player: u64, stat: &str) -> i32 {
+        SCORES
+            .get(&player)
+            .and_then(|stats| stats.get(stat).copied())
+            .unwrap_or(0)
+    }
+
+    pub fn stats(player: u64) -> HashMap<String, i32> {
+        SCORES
+            .get(&player)
+            .map(|stats| stats.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn set(player: u64, stat: &str, value: i32) {
+        let changed = {
+            let mut stats = SCORES.entry(player).or_default();
+            stats.insert(stat.to_string(), value) != Some(value)
+        };
+        if changed {
+            Self::mark_dirty(player, stat);
+        }
+    }
+
+    // 返回修改后的值
+    pub fn add(player: u64, stat: &str, delta: i32) -> i32 {
+        let value = {
+            let mut stats = SCORES.entry(player).or_default();
+            let value = stats.entry(stat.to_string()).or_insert(0);
+            *value = value.saturating_add(delta);
+            *value
+        };
+        if delta != 0 {
+            Self::mark_dirty(player, stat);
+        }
+        value
+    }
+
+    pub fn remove_player(player: u64) {
+        if SCORES.remove(&player).is_none() {
+            return;
+        }
+        match PENDING.write() {
+            Ok(mut pending) => {
+                pending
+                    .dirty
+                    .retain(|(dirty_player, _)| *dirty_player != player);
+                pending.removed.insert(player);
+            }
+            Err(e) => {
+                log_error!("NetworkScoreboard failed to write PENDING: {:?}", e);
+            }
+        }
+    }
+
+    // 按 stat 从高到低排序, 没有这项统计的玩家不参与排名
+    pub fn leaderboard(stat: &str, count: usize) -> Vec<(u64, i32)> {
+        let mut ranking: Vec<(u64, i32)> = SCORES
+            .iter()
+            .filter_map(|stats| stats.get(stat).map(|value| (*stats.key(), *value)))
+            .collect();
+        ranking.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        ranking.truncate(count);
+        ranking
+    }
+
+    fn mark_dirty(player: u64, stat: &str) {
+        match PENDING.write() {
+            Ok(mut pending) => {
+                pending.dirty.insert((player, stat.to_string()));
+            }
+            Err(e) => {
+                log_error!("NetworkScoreboard failed to write PENDING: {:?}", e);
+            }
+        }
+    }
+
+    fn entries() -> Vec<ScoreEntry> {
+        let mut entries = Vec::new();
+        for stats in SCORES.iter() {
+            for (stat, value) in stats.iter() {
+                entries.push(ScoreEntry::new(*stats.key(), stat.clone(), *value));
+            }
+        }
+        entries
+    }
+
+    // 在 NetworkServer::set_client_ready 中调用, 已经准备好的客户端之后只接收增量
+    pub(crate) fn on_client_ready(connection: &mut NetworkConnectionToClient) {
+        if SCORES.is_empty() {
+            return;
+        }
+        let mut message = ScoreboardFullMessage::new(Self::entries());
+        connection.send_network_message(&mut message, TransportChannel::Reliable);
+    }
+
+    // 在 NetworkServer::on_transport_disconnected 中调用
+    pub(crate) fn on_disconnected(connection_id: u64) {
+        Self::remove_player(connection_id);
+    }
+
+    // 在 NetworkServer::network_late_update 中 broadcast 之前调用
+    pub fn update() {
+        let local_time = NetworkTime::local_time();
+        if local_time - LAST_SYNC_TIME.load(Ordering::Relaxed) < Self::sync_interval() {
+            return;
+        }
+        let pending = match PENDING.write() {
+            Ok(mut pending) => std::mem::take(&mut *pending),
+            Err(_) => return,
+        };
+        if pending.dirty.is_empty() && pending.removed.is_empty() {
+            return;
+        }
+        LAST_SYNC_TIME.store(local_time, Ordering::Relaxed);
+
+        let entries = pending
+            .dirty
+            .into_iter()
+            .map(|(player, stat)| {
+                let value = Self::get(player, &stat);
+                ScoreEntry::new(player, stat, value)
+            })
+            .collect();
+        let mut message =
+            ScoreboardDeltaMessage::new(entries, pending.removed.into_iter().collect());
+        NetworkServer::send_to_all(&mut message, TransportChannel::Reliable, true);
+    }
+
+    pub fn reset() {
+        SCORES.clear();
+        if let Ok(mut pending) = PENDING.write() {
+            *pending = PendingChanges::default();
+        }
+        LAST_SYNC_TIME.store(0.0, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::transports::memory::memory_transport::test_util::*;
+    use crate::mirror::transports::memory::memory_transport::MemoryTransport;
+
+    #[test]
+    fn test_scoreboard() {
+        with_server(|| {
+            NetworkScoreboard::set_sync_interval(0.0);
+            for conn_id in [1, 2] {
+                MemoryTransport::client_connect(conn_id);
+            }
+            tick();
+            NetworkServer::set_client_ready(1);
+            MemoryTransport::client_receive(1);
+
+            NetworkScoreboard::add(1, "Kills", 2);
+            NetworkScoreboard::add(1, "Kills", 1);
+            NetworkScoreboard::set(1, "Deaths", 1);
+            NetworkScoreboard::set(2, "Kills", 5);
+            tick();
+            // 多次修改合并成一条
+            assert_eq!(
+                received::<ScoreboardDeltaMessage>(1),
+                vec![ScoreboardDeltaMessage::new(
+                    vec![
+                        ScoreEntry::new(1, "Deaths".to_string(), 1),
+                        ScoreEntry::new(1, "Kills".to_string(), 3),
+                        ScoreEntry::new(2, "Kills".to_string(), 5),
+                    ],
+                    vec![],
+                )]
+            );
+            assert!(received::<ScoreboardDeltaMessage>(2).is_empty());
+            assert_eq!(
+                NetworkScoreboard::leaderboard("Kills", 10),
+                vec![(2, 5), (1, 3)]
+            );
+
+            // 没有变化时不发送
+            NetworkScoreboard::set(1, "Deaths", 1);
+            tick();
+            assert!(received::<ScoreboardDeltaMessage>(1).is_empty());
+
+            // 后准备好的客户端先收到完整的记分板
+            NetworkServer::set_client_ready(2);
+            tick();
+            let mut full = received::<ScoreboardFullMessage>(2);
+            assert_eq!(full.len(), 1);
+            full[0]
+                .entries
+                .sort_by(|a, b| (a.player, &a.stat).cmp(&(b.player, &b.stat)));
+            assert_eq!(full[0].entries.len(), 3);
+            assert_eq!(
+                full[0].entries[1],
+                ScoreEntry::new(1, "Kills".to_string(), 3)
+            );
+
+            MemoryTransport::client_disconnect(2);
+            tick();
+            tick();
+            assert_eq!(
+                received::<ScoreboardDeltaMessage>(1),
+                vec![ScoreboardDeltaMessage::new(vec![], vec![2])]
+            );
+            assert_eq!(NetworkScoreboard::leaderboard("Kills", 10), vec![(1, 3)]);
+            NetworkScoreboard::set_sync_interval(1.0);
+            NetworkScoreboard::reset();
+        });
+    }
+}
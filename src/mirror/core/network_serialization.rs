@@ -0,0 +1,135 @@
+use crate::mirror::core::network_reader::NetworkReader;
+use crate::mirror::core::network_writer::NetworkWriter;
+#[cfg(feature = "bitpacking")]
+use crate::mirror::core::{network_reader::NetworkReaderTrait, network_writer::NetworkWriterTrait};
+
+// 序列化后端
+// NetworkWriter / NetworkReader 的 int / uint / long / ulong 都通过当前后端读写.
+// 默认使用 ByteBackend, 与 Mirror (C#) 的字节格式完全一致.
+// 开启 `bitpacking` feature 后切换为 BitPackingBackend, 只在双端都可控时使用.
+pub trait SerializationBackend {
+    fn write_int(writer: &mut NetworkWriter, value: i32);
+    fn write_uint(writer: &mut NetworkWriter, value: u32);
+    fn write_long(writer: &mut NetworkWriter, value: i64);
+    fn write_ulong(writer: &mut NetworkWriter, value: u64);
+
+    fn read_int(reader: &mut NetworkReader) -> i32;
+    fn read_uint(reader: &mut NetworkReader) -> u32;
+    fn read_long(reader: &mut NetworkReader) -> i64;
+    fn read_ulong(reader: &mut NetworkReader) -> u64;
+}
+
+// Mirror 兼容的定长小端字节格式
+pub struct ByteBackend;
+
+impl SerializationBackend for ByteBackend {
+    fn write_int(writer: &mut NetworkWriter, value: i32) {
+        writer.write_blittable(value);
+    }
+
+    fn write_uint(writer: &mut NetworkWriter, value: u32) {
+        writer.write_blittable(value);
+    }
+
+    fn write_long(writer: &mut NetworkWriter, value: i64) {
+        writer.write_blittable(value);
+    }
+
+    fn write_ulong(writer: &mut NetworkWriter, value: u64) {
+        writer.write_blittable(value);
+    }
+
+    fn read_int(reader: &mut NetworkReader) -> i32 {
+        reader.read_blittable::<i32>()
+    }
+
+    fn read_uint(reader: &mut NetworkReader) -> u32 {
+        reader.read_blittable::<u32>()
+    }
+
+    fn read_long(reader: &mut NetworkReader) -> i64 {
+        reader.read_blittable::<i64>()
+    }
+
+    fn read_ulong(reader: &mut NetworkReader) -> u64 {
+        reader.read_blittable::<u64>()
+    }
+}
+
+// 按有效位压缩的变长格式 (zigzag + VarUInt), 小数值只占 1 个字节
+#[cfg(feature = "bitpacking")]
+pub struct BitPackingBackend;
+
+#[cfg(feature = "bitpacking")]
+impl SerializationBackend for BitPackingBackend {
+    fn write_int(writer: &mut NetworkWriter, value: i32) {
+        writer.compress_var_int(value);
+    }
+
+    fn write_uint(writer: &mut NetworkWriter, value: u32) {
+        writer.compress_var_uint(value);
+    }
+
+    fn write_long(writer: &mut NetworkWriter, value: i64) {
+        writer.compress_var_long(value);
+    }
+
+    fn write_ulong(writer: &mut NetworkWriter, value: u64) {
+        writer.compress_var_ulong(value);
+    }
+
+    fn read_int(reader: &mut NetworkReader) -> i32 {
+        reader.decompress_var_int()
+    }
+
+    fn read_uint(reader: &mut NetworkReader) -> u32 {
+        reader.decompress_var_uint()
+    }
+
+    fn read_long(reader: &mut NetworkReader) -> i64 {
+        reader.decompress_var_long()
+    }
+
+    fn read_ulong(reader: &mut NetworkReader) -> u64 {
+        reader.decompress_var_ulong()
+    }
+}
+
+#[cfg(not(feature = "bitpacking"))]
+pub type ActiveSerializationBackend = ByteBackend;
+
+#[cfg(feature = "bitpacking")]
+pub type ActiveSerializationBackend = BitPackingBackend;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_byte_backend_is_mirror_compatible() {
+        let mut writer = NetworkWriter::new();
+        ByteBackend::write_uint(&mut writer, 1);
+        ByteBackend::write_long(&mut writer, -2);
+        assert_eq!(writer.get_position(), 12);
+        assert_eq!(&writer.to_bytes()[..4], &1u32.to_le_bytes());
+
+        let mut reader = NetworkReader::new_with_bytes(writer.to_bytes());
+        assert_eq!(ByteBackend::read_uint(&mut reader), 1);
+        assert_eq!(ByteBackend::read_long(&mut reader), -2);
+    }
+
+    #[cfg(feature = "bitpacking")]
+    #[test]
+    fn test_bit_packing_backend() {
+        let mut writer = NetworkWriter::new();
+        BitPackingBackend::write_int(&mut writer, -1);
+        BitPackingBackend::write_uint(&mut writer, 200);
+        BitPackingBackend::write_ulong(&mut writer, u64::MAX);
+        assert_eq!(writer.get_position(), 1 + 1 + 9);
+
+        let mut reader = NetworkReader::new_with_bytes(writer.to_bytes());
+        assert_eq!(BitPackingBackend::read_int(&mut reader), -1);
+        assert_eq!(BitPackingBackend::read_uint(&mut reader), 200);
+        assert_eq!(BitPackingBackend::read_ulong(&mut reader), u64::MAX);
+    }
+}
@@ -1,37 +1,88 @@
+use crate::mirror::core::anti_cheat::{AntiCheat, CheatSignal};
 use crate::mirror::core::backend_data::BackendDataStatic;
+use crate::mirror::core::batching::batcher::Batcher;
 use crate::mirror::core::batching::un_batcher::UnBatcher;
+use crate::mirror::core::blob_transfer::BlobTransfer;
+use crate::mirror::core::component_authority::ComponentAuthority;
+use crate::mirror::core::ephemeral::Ephemeral;
+use crate::mirror::core::gameplay_events::GameplayEvents;
+use crate::mirror::core::hit_registration::HitRegistration;
+use crate::mirror::core::interest_management::InterestManagementStatic;
+use crate::mirror::core::interest_radius::InterestRadius;
+use crate::mirror::core::lag_compensation::LagCompensation;
+use crate::mirror::core::loadout_phase::LoadoutPhase;
+use crate::mirror::core::master_server::MasterServer;
 use crate::mirror::core::messages::{
-    ChangeOwnerMessage, CommandMessage, EntityStateMessage, NetworkMessageHandler,
-    NetworkMessageHandlerFunc, NetworkMessageTrait, NetworkPingMessage, NetworkPongMessage,
-    NotReadyMessage, ObjectDestroyMessage, ObjectHideMessage, ObjectSpawnFinishedMessage,
-    ObjectSpawnStartedMessage, ReadyMessage, SpawnMessage, TimeSnapshotMessage,
+    AddPlayerMessage, BatchSpawnEntry, BatchSpawnMessage, BlobAckMessage, ChangeOwnerMessage,
+    CommandMessage, DisconnectMessage, DisconnectReason, EntityStateMessage, ErrorMessage,
+    LoadoutMessage, NetworkMessageHandler, NetworkMessageHandlerFunc, NetworkMessageTrait,
+    NetworkPingMessage, NetworkPongMessage, NotReadyMessage, ObjectDestroyMessage,
+    ObjectHideMessage, ObjectSpawnFinishedMessage, ObjectSpawnStartedMessage, PauseMessage,
+    ProtocolRejectMessage, ProtocolVersionMessage, QueuePositionMessage, ReadyMessage,
+    SpawnMessage, TickSnapshotMessage, TickedEntityStateMessage, TimeSnapshotMessage,
 };
+use crate::mirror::core::network_attachment::NetworkAttachment;
 use crate::mirror::core::network_behaviour::{GameObject, NetworkBehaviourTrait};
+use crate::mirror::core::network_client::NetworkClient;
 use crate::mirror::core::network_connection::NetworkConnectionTrait;
 use crate::mirror::core::network_connection_to_client::NetworkConnectionToClient;
+use crate::mirror::core::network_context::ContextLocal;
+use crate::mirror::core::network_events::NetworkEvents;
 use crate::mirror::core::network_identity::Visibility::ForceShown;
 use crate::mirror::core::network_identity::{NetworkIdentity, Visibility};
 use crate::mirror::core::network_manager::NetworkManagerStatic;
 use crate::mirror::core::network_messages::NetworkMessages;
-use crate::mirror::core::network_reader::NetworkReader;
+use crate::mirror::core::network_reader::{NetworkReader, NetworkReaderTrait};
 use crate::mirror::core::network_reader_pool::NetworkReaderPool;
+use crate::mirror::core::network_scoreboard::NetworkScoreboard;
 use crate::mirror::core::network_time::NetworkTime;
+use crate::mirror::core::network_writer::NetworkWriterTrait;
 use crate::mirror::core::network_writer_pool::NetworkWriterPool;
+use crate::mirror::core::outbound_interceptors::OutboundInterceptors;
+use crate::mirror::core::overload_controller::OverloadController;
+use crate::mirror::core::parallel_serialization::ParallelSerialization;
+use crate::mirror::core::region_streaming::RegionStreaming;
 use crate::mirror::core::remote_calls::{RemoteCallType, RemoteProcedureCalls};
+use crate::mirror::core::scheduler::Scheduler;
+use crate::mirror::core::session_resume::SessionResume;
 use crate::mirror::core::snapshot_interpolation::time_snapshot::TimeSnapshot;
+use crate::mirror::core::steering::Steering;
+use crate::mirror::core::sync_object_persistence::SyncObjectPersistence;
+use crate::mirror::core::sync_var_events::SyncVarEvents;
+use crate::mirror::core::task_bridge::TaskBridge;
+use crate::mirror::core::tools::alloc_audit::AllocAudit;
+use crate::mirror::core::tools::bandwidth_report::BandwidthReport;
+use crate::mirror::core::tools::behaviour_profiler::BehaviourProfiler;
+use crate::mirror::core::tools::frame_report::{FramePhase, FrameReports};
+use crate::mirror::core::tools::handshake_timing::{HandshakeStage, HandshakeTiming};
+use crate::mirror::core::tools::memory_report::MemoryReport;
+use crate::mirror::core::tools::serialization_check::SerializationCheck;
+use crate::mirror::core::tools::stable_hash_registry::{
+    StableHashDomain, StableHashKind, StableHashRegistry,
+};
 use crate::mirror::core::tools::time_sample::TimeSample;
+use crate::mirror::core::tools::traffic_log::{TrafficDirection, TrafficLog};
 use crate::mirror::core::transport::{
     Transport, TransportCallback, TransportCallbackType, TransportChannel, TransportError,
 };
+use crate::mirror::core::unreliable_sequencing::UnreliableSequencing;
+use crate::mirror::core::voice_relay::VoiceRelay;
+use crate::mirror::core::world_query::WorldQuery;
 use crate::{log_debug, log_error, log_info, log_warn};
 use atomic::Atomic;
 use dashmap::mapref::multiple::RefMutMulti;
 use dashmap::try_result::TryResult;
 use dashmap::{DashMap, DashSet};
 use lazy_static::lazy_static;
+use nalgebra::{Quaternion, Vector3};
+use ordered_float::OrderedFloat;
+use std::any::Any;
+use std::collections::{BTreeMap, VecDeque};
 use std::fmt::Debug;
+use std::panic::{self, AssertUnwindSafe};
 use std::sync::atomic::Ordering;
-use std::sync::RwLock;
+use std::sync::{RwLock, RwLockWriteGuard};
+use std::time::{Duration, Instant};
 
 pub enum ReplacePlayerOptions {
     KeepAuthority,
@@ -49,6 +100,57 @@ pub enum RemovePlayerOptions {
     Destroy,
 }
 
+// 快照缓冲区 (连接的 TimeSnapshot / NetworkTransform 的 server_snapshots) 达到 snapshot_buffer_size_limit 时的处理
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SnapshotOverflowPolicy {
+    // 丢弃新的快照 (原有行为), 插值会停在缓冲区末尾
+    #[default]
+    Ignore = 0,
+    // 丢弃最旧的快照, 插入新的
+    DropOldest = 1,
+    // 清空缓冲区, 从新的快照重新开始插值
+    ResetAndResync = 2,
+    // 断开连接
+    Disconnect = 3,
+}
+impl SnapshotOverflowPolicy {
+    pub fn from(value: u8) -> SnapshotOverflowPolicy {
+        match value {
+            1 => SnapshotOverflowPolicy::DropOldest,
+            2 => SnapshotOverflowPolicy::ResetAndResync,
+            3 => SnapshotOverflowPolicy::Disconnect,
+            _ => SnapshotOverflowPolicy::Ignore,
+        }
+    }
+    pub fn to_u8(&self) -> u8 {
+        *self as u8
+    }
+}
+
+// 客户端时间戳 (批次中的 remote_time_stamp) 异常时的处理
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RemoteTimestampPolicy {
+    // 不检查 (原有行为)
+    #[default]
+    Off = 0,
+    // 限制到合理范围内
+    Clamp = 1,
+    // 丢弃, 保留上一个时间戳
+    Reject = 2,
+}
+impl RemoteTimestampPolicy {
+    pub fn from(value: u8) -> RemoteTimestampPolicy {
+        match value {
+            1 => RemoteTimestampPolicy::Clamp,
+            2 => RemoteTimestampPolicy::Reject,
+            _ => RemoteTimestampPolicy::Off,
+        }
+    }
+    pub fn to_u8(&self) -> u8 {
+        *self as u8
+    }
+}
+
 // EventHandler 静态变量
 type EventHandler = fn(&mut NetworkConnectionToClient, TransportError);
 
@@ -60,50 +162,122 @@ pub enum EventHandlerType {
     OnTransportExceptionEvent,
 }
 
+// 未知消息的自定义处理, 返回 true 表示已处理
+pub type UnknownMessageHandlerFunc = fn(u64, u16, &mut NetworkReader, TransportChannel) -> bool;
+
+// 未知消息的统计
+#[derive(Debug, Default, Clone, Copy)]
+pub struct UnknownMessageStats {
+    pub count: u64,
+    pub last_log_time: f64,
+    pub suppressed: u64,
+}
+
 // NetworkServer 静态变量
 lazy_static! {
-    static ref CONNECTED_EVENT: DashMap<EventHandlerType, Box<EventHandler>> = DashMap::new();
-    static ref Initialized: Atomic<bool> = Atomic::new(false);
-    static ref TickRate: Atomic<u32> = Atomic::new(60);
-    static ref TICK_INTERVAL: Atomic<f32> =
-        Atomic::new(1f32 / NetworkServerStatic::tick_rate() as f32);
-    static ref SEND_RATE: Atomic<u32> = Atomic::new(NetworkServerStatic::tick_rate());
-    static ref SEND_INTERVAL: Atomic<f32> =
-        Atomic::new(1f32 / NetworkServerStatic::send_rate() as f32);
-    static ref LAST_SEND_TIME: Atomic<f64> = Atomic::new(0.0);
-    static ref DONT_LISTEN: Atomic<bool> = Atomic::new(true);
-    static ref ACTIVE: Atomic<bool> = Atomic::new(false);
-    static ref IS_LOADING_SCENE: Atomic<bool> = Atomic::new(false);
-    static ref EXCEPTIONS_DISCONNECT: Atomic<bool> = Atomic::new(false);
-    static ref DISCONNECT_INACTIVE_CONNECTIONS: Atomic<bool> = Atomic::new(false);
-    static ref DISCONNECT_INACTIVE_TIMEOUT: Atomic<f32> = Atomic::new(10.0);
-    static ref ACTUAL_TICK_RATE: Atomic<u32> = Atomic::new(0);
-    static ref ACTUAL_TICK_RATE_START: Atomic<f64> = Atomic::new(0.0);
-    static ref ACTUAL_TICK_RATE_COUNTER: Atomic<u32> = Atomic::new(0);
-    static ref MAX_CONNECTIONS: Atomic<usize> = Atomic::new(0);
-    static ref EARLY_UPDATE_DURATION: RwLock<TimeSample> = RwLock::new(TimeSample::new(0));
-    static ref LATE_UPDATE_DURATION: RwLock<TimeSample> = RwLock::new(TimeSample::new(0));
-    static ref FULL_UPDATE_DURATION: RwLock<TimeSample> = RwLock::new(TimeSample::new(0));
-    static ref NETWORK_CONNECTIONS: DashMap<u64, NetworkConnectionToClient> = DashMap::new();
-    static ref SPAWNED_NETWORK_IDS: DashSet<u32> = DashSet::new();
-    static ref SPAWNED_NETWORK_IDENTITIES: DashMap<u32, NetworkIdentity> = DashMap::new();
-    pub static ref NETWORK_BEHAVIOURS: DashMap<String, Box<dyn NetworkBehaviourTrait>> =
-        DashMap::new();
-    static ref NETWORK_MESSAGE_HANDLERS: DashMap<u16, NetworkMessageHandler> = DashMap::new();
-    static ref TRANSPORT_DATA_UN_BATCHER: RwLock<UnBatcher> = RwLock::new(UnBatcher::new());
+    static ref CONNECTED_EVENT: ContextLocal<DashMap<EventHandlerType, Box<EventHandler>>> =
+        ContextLocal::new(DashMap::new);
+    static ref Initialized: ContextLocal<Atomic<bool>> = ContextLocal::new(|| Atomic::new(false));
+    static ref TickRate: ContextLocal<Atomic<u32>> = ContextLocal::new(|| Atomic::new(60));
+    static ref TICK_INTERVAL: ContextLocal<Atomic<f32>> =
+        ContextLocal::new(|| Atomic::new(1f32 / NetworkServerStatic::tick_rate() as f32));
+    static ref SEND_RATE: ContextLocal<Atomic<u32>> =
+        ContextLocal::new(|| Atomic::new(NetworkServerStatic::tick_rate()));
+    static ref SEND_INTERVAL: ContextLocal<Atomic<f32>> =
+        ContextLocal::new(|| Atomic::new(1f32 / NetworkServerStatic::send_rate() as f32));
+    static ref LAST_SEND_TIME: ContextLocal<Atomic<f64>> = ContextLocal::new(|| Atomic::new(0.0));
+    static ref DONT_LISTEN: ContextLocal<Atomic<bool>> = ContextLocal::new(|| Atomic::new(true));
+    static ref ACTIVE: ContextLocal<Atomic<bool>> = ContextLocal::new(|| Atomic::new(false));
+    static ref IS_LOADING_SCENE: ContextLocal<Atomic<bool>> =
+        ContextLocal::new(|| Atomic::new(false));
+    static ref EXCEPTIONS_DISCONNECT: ContextLocal<Atomic<bool>> =
+        ContextLocal::new(|| Atomic::new(false));
+    static ref ISOLATE_HANDLER_PANICS: ContextLocal<Atomic<bool>> =
+        ContextLocal::new(|| Atomic::new(true));
+    static ref HANDLER_PANIC_COUNT: ContextLocal<Atomic<u64>> =
+        ContextLocal::new(|| Atomic::new(0));
+    static ref DISCONNECT_INACTIVE_CONNECTIONS: ContextLocal<Atomic<bool>> =
+        ContextLocal::new(|| Atomic::new(false));
+    static ref DISCONNECT_INACTIVE_TIMEOUT: ContextLocal<Atomic<f32>> =
+        ContextLocal::new(|| Atomic::new(10.0));
+    static ref ACTUAL_TICK_RATE: ContextLocal<Atomic<u32>> = ContextLocal::new(|| Atomic::new(0));
+    static ref ACTUAL_TICK_RATE_START: ContextLocal<Atomic<f64>> =
+        ContextLocal::new(|| Atomic::new(0.0));
+    static ref ACTUAL_TICK_RATE_COUNTER: ContextLocal<Atomic<u32>> =
+        ContextLocal::new(|| Atomic::new(0));
+    static ref MAX_CONNECTIONS: ContextLocal<Atomic<usize>> = ContextLocal::new(|| Atomic::new(0));
+    static ref CONNECTION_QUEUE_ENABLED: ContextLocal<Atomic<bool>> =
+        ContextLocal::new(|| Atomic::new(false));
+    static ref CONNECTION_QUEUE_POSITION_INTERVAL: ContextLocal<Atomic<f64>> =
+        ContextLocal::new(|| Atomic::new(1.0));
+    static ref CONNECTION_QUEUE_LAST_POSITION_TIME: ContextLocal<Atomic<f64>> =
+        ContextLocal::new(|| Atomic::new(0.0));
+    static ref CONNECTION_QUEUE: ContextLocal<RwLock<VecDeque<u64>>> =
+        ContextLocal::new(|| RwLock::new(VecDeque::new()));
+    // 与 CONNECTION_QUEUE 同步, 收到数据时不用扫描队列
+    static ref QUEUED_CONNECTIONS: ContextLocal<DashSet<u64>> = ContextLocal::new(DashSet::new);
+    static ref PROTOCOL_HANDSHAKE: ContextLocal<Atomic<bool>> =
+        ContextLocal::new(|| Atomic::new(false));
+    static ref REQUIRED_FEATURES: ContextLocal<Atomic<u32>> = ContextLocal::new(|| Atomic::new(0));
+    static ref UNKNOWN_MESSAGE_REPLY: ContextLocal<Atomic<bool>> =
+        ContextLocal::new(|| Atomic::new(false));
+    static ref UNKNOWN_MESSAGE_LOG_INTERVAL: ContextLocal<Atomic<f64>> =
+        ContextLocal::new(|| Atomic::new(1.0));
+    static ref UNKNOWN_MESSAGE_STATS: ContextLocal<DashMap<u16, UnknownMessageStats>> =
+        ContextLocal::new(DashMap::new);
+    static ref PENDING_DISCONNECTS: ContextLocal<DashSet<u64>> = ContextLocal::new(DashSet::new);
+    static ref ADD_PLAYER_REPLACE: ContextLocal<Atomic<bool>> =
+        ContextLocal::new(|| Atomic::new(false));
+    static ref PAUSED: ContextLocal<Atomic<bool>> = ContextLocal::new(|| Atomic::new(false));
+    static ref SPAWN_STREAM_BUDGET: ContextLocal<Atomic<usize>> =
+        ContextLocal::new(|| Atomic::new(0));
+    static ref ORDERED_FLUSH: ContextLocal<Atomic<bool>> = ContextLocal::new(|| Atomic::new(true));
+    static ref TIME_SNAPSHOT_RATE: ContextLocal<Atomic<u32>> = ContextLocal::new(|| Atomic::new(0));
+    static ref RPC_SUPPRESSED_COUNT: ContextLocal<Atomic<u64>> =
+        ContextLocal::new(|| Atomic::new(0));
+    static ref SNAPSHOT_OVERFLOW_POLICY: ContextLocal<Atomic<u8>> =
+        ContextLocal::new(|| Atomic::new(SnapshotOverflowPolicy::Ignore.to_u8()));
+    static ref SNAPSHOT_OVERFLOW_COUNT: ContextLocal<Atomic<u64>> =
+        ContextLocal::new(|| Atomic::new(0));
+    static ref REMOTE_TIMESTAMP_POLICY: ContextLocal<Atomic<u8>> =
+        ContextLocal::new(|| Atomic::new(RemoteTimestampPolicy::Off.to_u8()));
+    static ref REMOTE_TIMESTAMP_TOLERANCE: ContextLocal<Atomic<f64>> =
+        ContextLocal::new(|| Atomic::new(1.0));
+    // 因快照缓冲区溢出而等待断开的连接, 在 network_late_update 中处理
+    static ref SNAPSHOT_OVERFLOW_DISCONNECTS: ContextLocal<DashSet<u64>> =
+        ContextLocal::new(DashSet::new);
+    static ref UNKNOWN_MESSAGE_HANDLER: ContextLocal<RwLock<Option<UnknownMessageHandlerFunc>>> =
+        ContextLocal::new(|| RwLock::new(None));
+    static ref EARLY_UPDATE_DURATION: ContextLocal<RwLock<TimeSample>> =
+        ContextLocal::new(|| RwLock::new(TimeSample::new(0)));
+    static ref LATE_UPDATE_DURATION: ContextLocal<RwLock<TimeSample>> =
+        ContextLocal::new(|| RwLock::new(TimeSample::new(0)));
+    static ref FULL_UPDATE_DURATION: ContextLocal<RwLock<TimeSample>> =
+        ContextLocal::new(|| RwLock::new(TimeSample::new(0)));
+    static ref NETWORK_CONNECTIONS: ContextLocal<DashMap<u64, NetworkConnectionToClient>> =
+        ContextLocal::new(DashMap::new);
+    static ref SPAWNED_NETWORK_IDS: ContextLocal<DashSet<u32>> = ContextLocal::new(DashSet::new);
+    static ref SPAWNED_NETWORK_IDENTITIES: ContextLocal<DashMap<u32, NetworkIdentity>> =
+        ContextLocal::new(DashMap::new);
+    pub static ref NETWORK_BEHAVIOURS: ContextLocal<DashMap<(u32, u8), Box<dyn NetworkBehaviourTrait>>> =
+        ContextLocal::new(DashMap::new);
+    static ref NETWORK_MESSAGE_HANDLERS: ContextLocal<DashMap<u16, NetworkMessageHandler>> =
+        ContextLocal::new(DashMap::new);
+    static ref TRANSPORT_DATA_UN_BATCHER: ContextLocal<RwLock<UnBatcher>> =
+        ContextLocal::new(|| RwLock::new(UnBatcher::new()));
 }
 
 // Box<dyn NetworkBehaviourTrait> 静态变量方法
 impl NETWORK_BEHAVIOURS {
     // 添加 NetworkBehaviour
     pub fn add_behaviour(net_id: u32, index: u8, behaviour: Box<dyn NetworkBehaviourTrait>) {
-        NETWORK_BEHAVIOURS.insert(format!("{}_{}", net_id, index), behaviour);
+        NETWORK_BEHAVIOURS.insert((net_id, index), behaviour);
     }
     // 更新 NetworkBehaviour 的 NetId
     pub fn update_behaviour_net_id(o_net_id: u32, n_net_id: u32, count: u8) {
         for i in 0..count {
-            let o_key = format!("{}_{}", o_net_id, i);
-            let n_key = format!("{}_{}", n_net_id, i);
+            let o_key = (o_net_id, i);
+            let n_key = (n_net_id, i);
             if let Some((_, mut behaviour)) = NETWORK_BEHAVIOURS.remove(&o_key) {
                 behaviour.set_net_id(n_net_id);
                 NETWORK_BEHAVIOURS.insert(n_key, behaviour);
@@ -113,18 +287,16 @@ impl NETWORK_BEHAVIOURS {
     // 更新 NetworkBehaviour 的 ConnectionId
     pub fn update_behaviour_conn_id(net_id: u32, conn_id: u64, count: u8) {
         for i in 0..count {
-            if let Some((_, mut behaviour)) =
-                NETWORK_BEHAVIOURS.remove(&format!("{}_{}", net_id, i))
-            {
+            if let Some((_, mut behaviour)) = NETWORK_BEHAVIOURS.remove(&(net_id, i)) {
                 behaviour.set_connection_to_client(conn_id);
-                NETWORK_BEHAVIOURS.insert(format!("{}_{}", net_id, i), behaviour);
+                NETWORK_BEHAVIOURS.insert((net_id, i), behaviour);
             }
         }
     }
     // 移除 NetworkBehaviour
     pub fn remove_behaviour(net_id: u32, count: u8) {
         for i in 0..count {
-            NETWORK_BEHAVIOURS.remove(&format!("{}_{}", net_id, i));
+            NETWORK_BEHAVIOURS.remove(&(net_id, i));
         }
     }
 }
@@ -139,6 +311,16 @@ impl NetworkServerStatic {
     pub fn set_exceptions_disconnect(value: bool) {
         EXCEPTIONS_DISCONNECT.store(value, Ordering::Relaxed);
     }
+    // 消息处理器 panic 时只断开发送消息的连接, 关闭后 panic 会传播到 NetworkLoop
+    pub fn isolate_handler_panics() -> bool {
+        ISOLATE_HANDLER_PANICS.load(Ordering::Relaxed)
+    }
+    pub fn set_isolate_handler_panics(value: bool) {
+        ISOLATE_HANDLER_PANICS.store(value, Ordering::Relaxed);
+    }
+    pub fn handler_panic_count() -> u64 {
+        HANDLER_PANIC_COUNT.load(Ordering::Relaxed)
+    }
     pub fn connected_event() -> &'static DashMap<EventHandlerType, Box<EventHandler>> {
         &CONNECTED_EVENT
     }
@@ -154,6 +336,144 @@ impl NetworkServerStatic {
     pub fn set_max_connections(value: usize) {
         MAX_CONNECTIONS.store(value, Ordering::Relaxed);
     }
+    // 是否在连接后进行协议版本握手 (Mirror C# 客户端不支持, 默认关闭)
+    pub fn protocol_handshake() -> bool {
+        PROTOCOL_HANDSHAKE.load(Ordering::Relaxed)
+    }
+    pub fn set_protocol_handshake(value: bool) {
+        PROTOCOL_HANDSHAKE.store(value, Ordering::Relaxed);
+    }
+    // 客户端必须支持的 feature 位
+    pub fn required_features() -> u32 {
+        REQUIRED_FEATURES.load(Ordering::Relaxed)
+    }
+    pub fn set_required_features(value: u32) {
+        REQUIRED_FEATURES.store(value, Ordering::Relaxed);
+    }
+    // 每个连接每个 tick 初始生成的字节预算, 0 表示一次性全部发送
+    pub fn spawn_stream_budget() -> usize {
+        SPAWN_STREAM_BUDGET.load(Ordering::Relaxed)
+    }
+    pub fn set_spawn_stream_budget(value: usize) {
+        SPAWN_STREAM_BUDGET.store(value, Ordering::Relaxed);
+    }
+    // broadcast 结束后按连接 id 顺序统一 flush, 而不是在遍历 DashMap 时逐个 flush
+    // transport 按固定顺序收到所有连接的数据, kcp2k 在 server_late_update 中一次发出
+    pub fn ordered_flush() -> bool {
+        ORDERED_FLUSH.load(Ordering::Relaxed)
+    }
+    pub fn set_ordered_flush(value: bool) {
+        ORDERED_FLUSH.store(value, Ordering::Relaxed);
+    }
+    // 每秒发送 TimeSnapshotMessage 的次数, 0 表示每次 broadcast 都发送 (与 send_rate 相同)
+    // 时钟同步不需要完整的 tick 频率, 例如 30 Hz 状态同步配合 10 Hz 时间同步
+    // 客户端按 sendInterval 计算缓冲时间, 降低频率时需要相应增大 bufferTimeMultiplier
+    pub fn time_snapshot_rate() -> u32 {
+        TIME_SNAPSHOT_RATE.load(Ordering::Relaxed)
+    }
+    pub fn set_time_snapshot_rate(value: u32) {
+        TIME_SNAPSHOT_RATE.store(value, Ordering::Relaxed);
+    }
+    pub fn time_snapshot_interval() -> f64 {
+        match Self::time_snapshot_rate() {
+            0 => 0.0,
+            rate => 1.0 / rate as f64,
+        }
+    }
+    // 满员时新连接进入等待队列而不是直接断开
+    pub fn connection_queue_enabled() -> bool {
+        CONNECTION_QUEUE_ENABLED.load(Ordering::Relaxed)
+    }
+    pub fn set_connection_queue_enabled(value: bool) {
+        CONNECTION_QUEUE_ENABLED.store(value, Ordering::Relaxed);
+    }
+    // 向排队中的连接发送 QueuePositionMessage 的间隔 (秒)
+    pub fn connection_queue_position_interval() -> f64 {
+        CONNECTION_QUEUE_POSITION_INTERVAL.load(Ordering::Relaxed)
+    }
+    pub fn set_connection_queue_position_interval(value: f64) {
+        CONNECTION_QUEUE_POSITION_INTERVAL.store(value, Ordering::Relaxed);
+    }
+    pub fn connection_queue() -> &'static RwLock<VecDeque<u64>> {
+        &CONNECTION_QUEUE
+    }
+    pub fn is_connection_queued(connection_id: u64) -> bool {
+        QUEUED_CONNECTIONS.contains(&connection_id)
+    }
+    // 世界模拟是否暂停, 通过 NetworkServer::pause / resume 修改
+    pub fn paused() -> bool {
+        PAUSED.load(Ordering::Relaxed)
+    }
+    // 已经有玩家的连接再次 AddPlayer 时, true 替换玩家 (旧玩家保留所有权), false 拒绝
+    pub fn add_player_replace() -> bool {
+        ADD_PLAYER_REPLACE.load(Ordering::Relaxed)
+    }
+    pub fn set_add_player_replace(value: bool) {
+        ADD_PLAYER_REPLACE.store(value, Ordering::Relaxed);
+    }
+    // 收到未知消息时是否回复 ErrorMessage
+    pub fn unknown_message_reply() -> bool {
+        UNKNOWN_MESSAGE_REPLY.load(Ordering::Relaxed)
+    }
+    pub fn set_unknown_message_reply(value: bool) {
+        UNKNOWN_MESSAGE_REPLY.store(value, Ordering::Relaxed);
+    }
+    // 同一个未知消息id 两次日志之间的最小间隔 (秒)
+    pub fn unknown_message_log_interval() -> f64 {
+        UNKNOWN_MESSAGE_LOG_INTERVAL.load(Ordering::Relaxed)
+    }
+    pub fn set_unknown_message_log_interval(value: f64) {
+        UNKNOWN_MESSAGE_LOG_INTERVAL.store(value, Ordering::Relaxed);
+    }
+    pub fn unknown_message_stats() -> &'static DashMap<u16, UnknownMessageStats> {
+        &UNKNOWN_MESSAGE_STATS
+    }
+    // 没有可以接收的观察者而没有发送的 RPC 数量
+    pub fn rpc_suppressed_count() -> u64 {
+        RPC_SUPPRESSED_COUNT.load(Ordering::Relaxed)
+    }
+    pub fn add_rpc_suppressed_count() {
+        RPC_SUPPRESSED_COUNT.fetch_add(1, Ordering::Relaxed);
+    }
+    pub fn snapshot_overflow_policy() -> SnapshotOverflowPolicy {
+        SnapshotOverflowPolicy::from(SNAPSHOT_OVERFLOW_POLICY.load(Ordering::Relaxed))
+    }
+    pub fn set_snapshot_overflow_policy(value: SnapshotOverflowPolicy) {
+        SNAPSHOT_OVERFLOW_POLICY.store(value.to_u8(), Ordering::Relaxed);
+    }
+    pub fn remote_timestamp_policy() -> RemoteTimestampPolicy {
+        RemoteTimestampPolicy::from(REMOTE_TIMESTAMP_POLICY.load(Ordering::Relaxed))
+    }
+    pub fn set_remote_timestamp_policy(value: RemoteTimestampPolicy) {
+        REMOTE_TIMESTAMP_POLICY.store(value.to_u8(), Ordering::Relaxed);
+    }
+    // 客户端时间比服务器时间走得快的容差 (秒)
+    pub fn remote_timestamp_tolerance() -> f64 {
+        REMOTE_TIMESTAMP_TOLERANCE.load(Ordering::Relaxed)
+    }
+    pub fn set_remote_timestamp_tolerance(value: f64) {
+        REMOTE_TIMESTAMP_TOLERANCE.store(value, Ordering::Relaxed);
+    }
+    // 快照缓冲区溢出的总次数, 持续增长说明客户端发送过快或服务器处理不过来
+    pub fn snapshot_overflow_count() -> u64 {
+        SNAPSHOT_OVERFLOW_COUNT.load(Ordering::Relaxed)
+    }
+    pub fn unknown_message_count(message_id: u16) -> u64 {
+        UNKNOWN_MESSAGE_STATS
+            .get(&message_id)
+            .map(|stats| stats.count)
+            .unwrap_or(0)
+    }
+    pub fn unknown_message_handler() -> Option<UnknownMessageHandlerFunc> {
+        *UNKNOWN_MESSAGE_HANDLER
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+    }
+    pub fn set_unknown_message_handler(func: Option<UnknownMessageHandlerFunc>) {
+        *UNKNOWN_MESSAGE_HANDLER
+            .write()
+            .unwrap_or_else(|e| e.into_inner()) = func;
+    }
     pub fn network_connections_size() -> usize {
         NETWORK_CONNECTIONS.len()
     }
@@ -280,13 +600,14 @@ impl NetworkServerStatic {
         &SPAWNED_NETWORK_IDENTITIES
     }
     pub fn add_spawned_network_identity(identity: NetworkIdentity) {
+        WorldQuery::invalidate();
         Self::spawned_network_ids().insert(identity.net_id());
         SPAWNED_NETWORK_IDENTITIES.insert(identity.net_id(), identity);
     }
     pub fn remove_spawned_network_identity(net_id: &u32) {
         if let Some((net_id, sni)) = SPAWNED_NETWORK_IDENTITIES.remove(net_id) {
             for i in 0..sni.network_behaviours_count {
-                NETWORK_BEHAVIOURS.remove(&format!("{}_{}", net_id, i));
+                NETWORK_BEHAVIOURS.remove(&(net_id, i));
             }
         }
         SPAWNED_NETWORK_IDS.remove(net_id);
@@ -325,6 +646,17 @@ pub struct NetworkServer;
 
 // NetworkServer 结构体方法
 impl NetworkServer {
+    // 协议版本, 修改消息格式时递增
+    pub const PROTOCOL_VERSION: u16 = 1;
+    // 客户端支持 BatchSpawnMessage
+    pub const FEATURE_BATCH_SPAWN: u32 = 1 << 0;
+    // 客户端支持 TickSnapshotMessage 和 TickedEntityStateMessage
+    pub const FEATURE_STATE_TICK: u32 = 1 << 1;
+    // 客户端支持 InterpolationHintMessage
+    pub const FEATURE_INTERPOLATION_HINT: u32 = 1 << 2;
+    // export_player_state 的数据格式版本
+    pub const PLAYER_STATE_VERSION: u16 = 1;
+
     fn initialize() {
         if NetworkServerStatic::initialized() {
             return;
@@ -377,11 +709,22 @@ impl NetworkServer {
 
         // 注册消息处理器
         Self::register_message_handlers();
+
+        // 向主服务器注册
+        MasterServer::start();
     }
 
     pub fn shutdown() {
         if NetworkServerStatic::initialized() {
+            // 在断开连接和销毁对象之前保存 SyncObject
+            SyncObjectPersistence::checkpoint();
+            MasterServer::stop();
             Self::disconnect_all();
+            // 在停止 Transport 之前把 DisconnectMessage 发出去
+            NetworkServerStatic::for_each_network_connection(|mut connection| {
+                connection.update();
+            });
+            Self::process_pending_disconnects();
 
             if let Some(transport) = Transport::active_transport() {
                 transport.server_stop();
@@ -390,34 +733,210 @@ impl NetworkServer {
             NetworkServerStatic::set_active(false);
             NetworkServerStatic::set_initialized(false);
         }
+        Self::reset_all_statics();
+    }
+
+    // 在 shutdown 中调用, 清空一次服务器运行期间的所有全局状态, 同一个进程中可以多次 listen / shutdown
+    // 不清空的全局变量:
+    // - 配置 (tick_rate、各个子系统的开关和参数), 由使用者设置, 跨运行保持
+    // - 启动时注册的代码: RemoteProcedureCalls、NetworkBehaviour 工厂、NetworkEvents 订阅者
+    // - NetworkWriterPool / NetworkReaderPool, 取出时会 reset, 不携带状态
+    pub fn reset_all_statics() {
         NETWORK_MESSAGE_HANDLERS.clear();
+        UNKNOWN_MESSAGE_STATS.clear();
+        PENDING_DISCONNECTS.clear();
+        Self::write_connection_queue().clear();
+        QUEUED_CONNECTIONS.clear();
+        PAUSED.store(false, Ordering::Relaxed);
+        RPC_SUPPRESSED_COUNT.store(0, Ordering::Relaxed);
+        SNAPSHOT_OVERFLOW_COUNT.store(0, Ordering::Relaxed);
+        SNAPSHOT_OVERFLOW_DISCONNECTS.clear();
+        AntiCheat::reset();
+        Ephemeral::reset();
+        SessionResume::reset();
+        OutboundInterceptors::reset();
+        NetworkAttachment::reset();
+        VoiceRelay::reset();
+        GameplayEvents::reset();
+        WorldQuery::reset();
+        LagCompensation::reset();
+        HitRegistration::reset();
+        NetworkScoreboard::reset();
+        LoadoutPhase::reset();
+        Scheduler::reset();
+        TaskBridge::reset();
+        UnreliableSequencing::reset();
+        InterestRadius::reset();
+        InterestManagementStatic::reset();
+        RegionStreaming::reset();
+        Steering::reset();
+        ComponentAuthority::reset();
+        SyncObjectPersistence::reset();
+        SyncVarEvents::reset();
+        OverloadController::reset();
         NetworkServerStatic::network_connections().clear();
         NetworkServerStatic::spawned_network_ids().clear();
         NetworkServerStatic::spawned_network_identities().clear();
+        NETWORK_BEHAVIOURS.clear();
+        NetworkServerStatic::set_is_loading_scene(false);
+        // 同一个进程中重新 listen 时 net_id 从 1 开始
+        NetworkIdentity::reset_server_statics();
         NetworkServerStatic::transport_data_un_batcher()
             .write()
             .unwrap()
             .clear();
+        CONNECTED_EVENT.clear();
+        HANDLER_PANIC_COUNT.store(0, Ordering::Relaxed);
+        LAST_SEND_TIME.store(0.0, Ordering::Relaxed);
+        ACTUAL_TICK_RATE.store(0, Ordering::Relaxed);
+        ACTUAL_TICK_RATE_START.store(0.0, Ordering::Relaxed);
+        ACTUAL_TICK_RATE_COUNTER.store(0, Ordering::Relaxed);
+        CONNECTION_QUEUE_LAST_POSITION_TIME.store(0.0, Ordering::Relaxed);
+        NetworkTime::reset_statics();
+        NetworkManagerStatic::reset_statics();
+        FrameReports::reset();
+        BandwidthReport::reset();
+        MemoryReport::reset();
+        HandshakeTiming::reset();
+        SerializationCheck::reset();
+        BehaviourProfiler::reset();
+        TrafficLog::reset();
+    }
+
+    // 暂停世界模拟: 停止 NetworkBehaviour 的 update 和状态广播
+    // ping / TimeSnapshot 照常处理, 连接不会因此超时
+    pub fn pause() {
+        if PAUSED.swap(true, Ordering::Relaxed) {
+            return;
+        }
+        log_info!("Server paused.");
+        Self::send_to_all(
+            &mut PauseMessage::new(true),
+            TransportChannel::Reliable,
+            false,
+        );
+    }
+
+    // 恢复世界模拟
+    pub fn resume() {
+        if !PAUSED.swap(false, Ordering::Relaxed) {
+            return;
+        }
+        log_info!("Server resumed.");
+        Self::send_to_all(
+            &mut PauseMessage::new(false),
+            TransportChannel::Reliable,
+            false,
+        );
     }
 
     fn disconnect_all() {
         NetworkServerStatic::for_each_network_connection(|mut connection| {
-            connection.disconnect();
+            Self::disconnect_connection_with_reason(
+                &mut connection,
+                DisconnectReason::ServerShutdown,
+            );
         });
     }
 
+    // 发送 DisconnectMessage 之后断开连接
+    pub fn disconnect_with_reason(connection_id: u64, reason: DisconnectReason) {
+        match NetworkServerStatic::network_connections().try_get_mut(&connection_id) {
+            TryResult::Present(mut connection) => {
+                Self::disconnect_connection_with_reason(&mut connection, reason);
+            }
+            TryResult::Absent => {
+                log_error!(
+                    "Server.DisconnectWithReason: connectionId: {} not found.",
+                    connection_id
+                );
+            }
+            TryResult::Locked => {
+                log_error!(
+                    "Server.DisconnectWithReason: connectionId: {} is locked.",
+                    connection_id
+                );
+            }
+        }
+    }
+
+    // 传输层的断开延迟到 network_late_update, 保证 DisconnectMessage 先被发送
+    pub fn disconnect_connection_with_reason(
+        connection: &mut NetworkConnectionToClient,
+        reason: DisconnectReason,
+    ) {
+        if connection.disconnect_reason.is_some() {
+            return;
+        }
+        connection.disconnect_reason = Some(reason);
+        let mut message = DisconnectMessage::new(reason.to_u8(), reason.text().to_string());
+        connection.send_network_message(&mut message, TransportChannel::Reliable);
+        connection.disconnect();
+        PENDING_DISCONNECTS.insert(connection.connection_id());
+    }
+
+    // 快照缓冲区已满时按 SnapshotOverflowPolicy 处理, 返回 true 表示可以插入新的快照
+    // 调用时可能持有连接的引用, 断开连接延迟到 network_late_update
+    pub fn on_snapshot_overflow<T>(
+        snapshots: &mut BTreeMap<OrderedFloat<f64>, T>,
+        connection_id: u64,
+    ) -> bool {
+        SNAPSHOT_OVERFLOW_COUNT.fetch_add(1, Ordering::Relaxed);
+        FrameReports::record_snapshot_overflow();
+        match NetworkServerStatic::snapshot_overflow_policy() {
+            SnapshotOverflowPolicy::Ignore => false,
+            SnapshotOverflowPolicy::DropOldest => {
+                snapshots.pop_first();
+                true
+            }
+            SnapshotOverflowPolicy::ResetAndResync => {
+                snapshots.clear();
+                true
+            }
+            SnapshotOverflowPolicy::Disconnect => {
+                if SNAPSHOT_OVERFLOW_DISCONNECTS.insert(connection_id) {
+                    log_warn!(
+                        "Server: disconnecting connection {} because its snapshot buffer overflowed.",
+                        connection_id
+                    );
+                }
+                false
+            }
+        }
+    }
+
+    fn process_snapshot_overflow_disconnects() {
+        let connection_ids: Vec<u64> = SNAPSHOT_OVERFLOW_DISCONNECTS.iter().map(|id| *id).collect();
+        SNAPSHOT_OVERFLOW_DISCONNECTS.clear();
+        for connection_id in connection_ids {
+            Self::disconnect_with_reason(connection_id, DisconnectReason::Kick);
+        }
+    }
+
+    fn process_pending_disconnects() {
+        let connection_ids: Vec<u64> = PENDING_DISCONNECTS.iter().map(|id| *id).collect();
+        PENDING_DISCONNECTS.clear();
+        if let Some(transport) = Transport::active_transport() {
+            for connection_id in connection_ids {
+                transport.server_disconnect(connection_id);
+            }
+        }
+    }
+
     // 网络早期更新
     pub fn network_early_update() {
+        let begin = Instant::now();
+        NetworkTime::update_delta_time();
         if NetworkServerStatic::active() {
             match EARLY_UPDATE_DURATION.try_write() {
                 Ok(mut early_update_duration) => {
                     early_update_duration.begin();
                 }
                 Err(e) => {
-                    log_warn!(format!(
+                    log_warn!(
                         "Server.network_early_update() failed to get EARLY_UPDATE_DURATION: {:?}",
                         e
-                    ));
+                    );
                 }
             }
             match FULL_UPDATE_DURATION.try_write() {
@@ -425,10 +944,10 @@ impl NetworkServer {
                     full_update_duration.begin();
                 }
                 Err(e) => {
-                    log_warn!(format!(
+                    log_warn!(
                         "Server.network_early_update() failed to get FULL_UPDATE_DURATION: {:?}",
                         e
-                    ));
+                    );
                 }
             }
         }
@@ -448,34 +967,69 @@ impl NetworkServer {
                     early_update_duration.end();
                 }
                 Err(e) => {
-                    log_warn!(format!(
+                    log_warn!(
                         "Server.network_early_update() failed to get EARLY_UPDATE_DURATION: {:?}",
                         e
-                    ));
+                    );
                 }
             }
+            FrameReports::record_phase(FramePhase::EarlyUpdate, begin.elapsed());
         }
     }
 
     // 网络更新
     pub fn network_late_update() {
+        let begin = Instant::now();
+        let mut broadcast_elapsed = Duration::ZERO;
         if NetworkServerStatic::active() {
             match LATE_UPDATE_DURATION.try_write() {
                 Ok(mut late_update_duration) => {
                     late_update_duration.begin();
                 }
                 Err(e) => {
-                    log_warn!(format!(
+                    log_warn!(
                         "Server.network_late_update() failed to get LATE_UPDATE_DURATION: {:?}",
                         e
-                    ));
+                    );
                 }
             }
+            AntiCheat::update();
+            // 定时器回调, 修改在本 tick 广播
+            Scheduler::update();
+            // 后台任务的完成回调
+            TaskBridge::update();
+            Ephemeral::update();
+            SessionResume::update();
+            Self::process_connection_queue();
+            // 选择超时和推迟的 AddPlayerMessage
+            LoadoutPhase::update();
+            // 按距离、连接质量和区域重建观察者, 过载时暂停
+            if !OverloadController::interest_rebuild_paused() {
+                InterestRadius::update();
+                RegionStreaming::update();
+                InterestManagementStatic::update();
+            }
+            MasterServer::update();
+            Self::stream_pending_spawns();
+            BlobTransfer::update();
+            // 先移动 NPC, 挂载的子对象再跟随
+            Steering::update();
+            NetworkAttachment::update();
+            NetworkScoreboard::update();
+            SyncObjectPersistence::update();
+            // 记录本 tick 广播给客户端的位置
+            LagCompensation::record();
+            // DisconnectMessage 在本 tick 的广播中发出
+            Self::process_snapshot_overflow_disconnects();
+            let broadcast_begin = Instant::now();
             Self::broadcast();
+            broadcast_elapsed = broadcast_begin.elapsed();
+            FrameReports::record_phase(FramePhase::Broadcast, broadcast_elapsed);
         }
         if let Some(active_transport) = Transport::active_transport() {
             active_transport.server_late_update();
         }
+        Self::process_pending_disconnects();
 
         if NetworkServerStatic::active() {
             let actual_tick_rate_counter = NetworkServerStatic::actual_tick_rate_counter();
@@ -491,17 +1045,31 @@ impl NetworkServer {
                 );
                 NetworkServerStatic::set_actual_tick_rate_start(local_time);
                 NetworkServerStatic::set_actual_tick_rate_counter(0);
+                if let Some(report) = AllocAudit::report(5) {
+                    log_info!(report);
+                }
             }
+            AllocAudit::end_tick();
+            BandwidthReport::update();
+            MemoryReport::update();
+            HandshakeTiming::update();
+            BehaviourProfiler::end_tick();
+            BehaviourProfiler::update();
+            FrameReports::record_phase(
+                FramePhase::LateUpdate,
+                begin.elapsed().saturating_sub(broadcast_elapsed),
+            );
+            FrameReports::end_tick(NetworkTime::tick(), local_time);
 
             match LATE_UPDATE_DURATION.try_write() {
                 Ok(mut late_update_duration) => {
                     late_update_duration.end();
                 }
                 Err(e) => {
-                    log_warn!(format!(
+                    log_warn!(
                         "Server.network_late_update() failed to get LATE_UPDATE_DURATION: {:?}",
                         e
-                    ));
+                    );
                 }
             }
             match FULL_UPDATE_DURATION.try_write() {
@@ -509,18 +1077,39 @@ impl NetworkServer {
                     full_update_duration.end();
                 }
                 Err(e) => {
-                    log_warn!(format!(
+                    log_warn!(
                         "Server.network_late_update() failed to get FULL_UPDATE_DURATION: {:?}",
                         e
-                    ));
+                    );
                 }
             }
+            OverloadController::update();
         }
     }
 
     // Broadcast
     fn broadcast() {
+        // 对象很多时先在工作线程中序列化, 下面按连接组包时直接使用缓存
+        ParallelSerialization::serialize(NetworkTime::frame_count());
+        let ordered_flush = NetworkServerStatic::ordered_flush();
+        let mut flush_ids = Vec::new();
+        let mut flush_elapsed = Duration::ZERO;
+        let mut flush = |connection: &mut NetworkConnectionToClient| {
+            if ordered_flush {
+                flush_ids.push(connection.connection_id());
+            } else {
+                let flush_begin = Instant::now();
+                connection.update();
+                flush_elapsed += flush_begin.elapsed();
+            }
+        };
         NetworkServerStatic::for_each_network_connection(|mut connection| {
+            // 如果连接正在断开, 只发送剩余的消息
+            if connection.disconnect_reason.is_some() {
+                flush(&mut connection);
+                return;
+            }
+
             // 如果连接不活跃
             if Self::disconnect_if_inactive(&mut connection) {
                 return;
@@ -532,28 +1121,65 @@ impl NetworkServer {
             }
 
             if connection.is_ready() {
-                connection.send_network_message(
-                    &mut TimeSnapshotMessage::default(),
-                    TransportChannel::Unreliable,
-                );
-                Self::broadcast_to_connection(&mut connection);
+                // 两种时间快照都按 time_snapshot_interval 限制频率
+                if connection.time_snapshot_due(NetworkTime::local_time()) {
+                    if connection.features & Self::FEATURE_STATE_TICK != 0 {
+                        connection.send_network_message(
+                            &mut TickSnapshotMessage::new(NetworkTime::tick()),
+                            TransportChannel::Unreliable,
+                        );
+                    } else {
+                        connection.send_network_message(
+                            &mut TimeSnapshotMessage,
+                            TransportChannel::Unreliable,
+                        );
+                    }
+                }
+                // 暂停时不广播对象状态
+                if !NetworkServerStatic::paused() {
+                    Self::broadcast_to_connection(&mut connection);
+                }
             }
-            connection.update();
+            flush(&mut connection);
         });
+
+        if ordered_flush {
+            let flush_begin = Instant::now();
+            flush_ids.sort_unstable();
+            for connection_id in flush_ids {
+                if let TryResult::Present(mut connection) =
+                    NetworkServerStatic::network_connections().try_get_mut(&connection_id)
+                {
+                    connection.update();
+                }
+            }
+            flush_elapsed += flush_begin.elapsed();
+        }
+        FrameReports::record_phase(FramePhase::Flush, flush_elapsed);
     }
 
     // BroadcastToConnection(NetworkConnectionToClient connection)
     fn broadcast_to_connection(conn: &mut NetworkConnectionToClient) {
+        let ticked = conn.features & Self::FEATURE_STATE_TICK != 0;
         for net_id in conn.observing.to_vec().iter() {
             if *net_id != 0 {
                 if let Some(mut message) =
                     Self::serialize_for_connection(*net_id, conn.connection_id())
                 {
                     // debug!(format!("Server.broadcast_to_connection: connectionId: {}, netId: {}", conn.connection_id(), net_id));
-                    conn.send_network_message(&mut message, TransportChannel::Reliable);
+                    if ticked {
+                        let mut message = TickedEntityStateMessage::new(
+                            message.net_id,
+                            NetworkTime::tick(),
+                            message.payload,
+                        );
+                        conn.send_network_message(&mut message, TransportChannel::Reliable);
+                    } else {
+                        conn.send_network_message(&mut message, TransportChannel::Reliable);
+                    }
                 }
             } else {
-                log_warn!(format!("Server.broadcast_to_connection: identity is null. Removing from observing list. connectionId: {}, netId: {}", conn.connection_id(), net_id));
+                log_warn!("Server.broadcast_to_connection: identity is null. Removing from observing list. connectionId: {}, netId: {}", conn.connection_id(), net_id);
                 conn.observing.retain(|id| id != net_id);
             }
         }
@@ -565,53 +1191,80 @@ impl NetworkServer {
             TryResult::Present(mut identity) => {
                 let owned = identity.connection_to_client() == conn_id;
                 let net_id = identity.net_id();
+                let asset_id = identity.asset_id;
                 let serialization =
                     identity.get_server_serialization_at_tick(NetworkTime::frame_count());
-                match owned {
-                    true => {
-                        if serialization.owner_writer.get_position() > 0 {
-                            return Some(EntityStateMessage::new(
-                                net_id,
-                                serialization.owner_writer.to_bytes(),
-                            ));
-                        }
-                    }
-                    false => {
-                        if serialization.observers_writer.get_position() > 0 {
-                            return Some(EntityStateMessage::new(
-                                net_id,
-                                serialization.observers_writer.to_bytes(),
-                            ));
-                        }
+                let (writer, component_bytes) = match owned {
+                    true => (
+                        &serialization.owner_writer,
+                        &serialization.owner_component_bytes,
+                    ),
+                    false => (
+                        &serialization.observers_writer,
+                        &serialization.observers_component_bytes,
+                    ),
+                };
+                if writer.get_position() > 0 {
+                    if BandwidthReport::enabled() {
+                        Self::record_state_bandwidth(
+                            asset_id,
+                            net_id,
+                            writer.get_position(),
+                            component_bytes,
+                        );
                     }
+                    return Some(EntityStateMessage::new(net_id, writer.to_bytes()));
                 }
             }
             TryResult::Absent => {
-                log_warn!(format!(
+                log_warn!(
                     "Server.SerializeForConnection: netId {} not found in spawned.",
                     net_id
-                ));
+                );
             }
             TryResult::Locked => {
-                log_warn!(format!(
-                    "Server.SerializeForConnection: netId {} is locked.",
-                    net_id
-                ));
+                FrameReports::record_lock_contention();
+                log_warn!("Server.SerializeForConnection: netId {} is locked.", net_id);
             }
         }
         None
     }
 
+    // 组件之外的字节 (脏标记) 记在 IDENTITY_COMPONENT 上
+    fn record_state_bandwidth(
+        asset_id: u32,
+        net_id: u32,
+        payload_len: usize,
+        component_bytes: &[(u8, usize)],
+    ) {
+        let mut components_len = 0;
+        for (index, bytes) in component_bytes.iter() {
+            components_len += bytes;
+            BandwidthReport::record(asset_id, *index, *bytes, || {
+                match NETWORK_BEHAVIOURS.try_get(&(net_id, *index)) {
+                    TryResult::Present(component) => component.sub_class(),
+                    _ => "Unknown".to_string(),
+                }
+            });
+        }
+        BandwidthReport::record(
+            asset_id,
+            BandwidthReport::IDENTITY_COMPONENT,
+            payload_len.saturating_sub(components_len),
+            || "NetworkIdentity".to_string(),
+        );
+    }
+
     // DisconnectIfInactive
     fn disconnect_if_inactive(connection: &mut NetworkConnectionToClient) -> bool {
         if NetworkServerStatic::disconnect_inactive_connections()
             && !connection.is_alive(NetworkServerStatic::disconnect_inactive_timeout() as f64)
         {
-            log_warn!(format!(
+            log_warn!(
                 "Server.DisconnectIfInactive: connectionId: {} is inactive. Disconnecting.",
                 connection.connection_id()
-            ));
-            connection.disconnect();
+            );
+            Self::disconnect_connection_with_reason(connection, DisconnectReason::Timeout);
             return true;
         }
         false
@@ -623,11 +1276,11 @@ impl NetworkServer {
             && !connection.is_ready()
             && NetworkTime::local_time() - connection.first_conn_loc_time_stamp() > 5.0
         {
-            log_warn!(format!(
+            log_warn!(
                 "Server.DisconnectIfNoAuthNotReady: connectionId: {} is not authenticated and not ready. Disconnecting.",
                 connection.connection_id()
-            ));
-            connection.disconnect();
+            );
+            Self::disconnect_connection_with_reason(connection, DisconnectReason::Timeout);
             return true;
         }
         false
@@ -677,11 +1330,15 @@ impl NetworkServer {
             identity.game_object().transform.local_scale,
             payload,
         );
-        // 发送 SpawnMessage
-        conn.send_network_message(&mut spawn_message, TransportChannel::Reliable);
+        // 发送 SpawnMessage, 记录字节数用于初始生成的分帧发送
+        conn.spawn_bytes_sent +=
+            conn.send_network_message(&mut spawn_message, TransportChannel::Reliable);
     }
 
-    fn create_spawn_message_payload(is_owner: bool, identity: &mut NetworkIdentity) -> Vec<u8> {
+    pub(crate) fn create_spawn_message_payload(
+        is_owner: bool,
+        identity: &mut NetworkIdentity,
+    ) -> Vec<u8> {
         let mut payload = Vec::new();
         // 如果没有 NetworkBehaviours
         if identity.network_behaviours_count == 0 {
@@ -708,44 +1365,45 @@ impl NetworkServer {
     fn transport_callback(tcb: TransportCallback) {
         match tcb.r#type {
             TransportCallbackType::OnServerConnected => {
-                log_info!(format!(
-                    "Server.HandleConnect: connectionId: {}",
-                    tcb.conn_id
-                ));
+                log_info!("Server.HandleConnect: connectionId: {}", tcb.conn_id);
                 Self::on_transport_connected(tcb.conn_id)
             }
             TransportCallbackType::OnServerDataReceived => {
                 Self::on_transport_data(tcb.conn_id, tcb.data, tcb.channel)
             }
             TransportCallbackType::OnServerDisconnected => {
-                log_info!(format!(
-                    "Server.HandleDisconnect: connectionId: {}",
-                    tcb.conn_id
-                ));
+                log_info!("Server.HandleDisconnect: connectionId: {}", tcb.conn_id);
                 Self::on_transport_disconnected(tcb.conn_id)
             }
             TransportCallbackType::OnServerError => {
-                log_error!(format!(
+                log_error!(
                     "Server.HandleError: connectionId: {} error: {:?}",
-                    tcb.conn_id, tcb.error
-                ));
+                    tcb.conn_id,
+                    tcb.error
+                );
                 Self::on_transport_error(tcb.conn_id, tcb.error)
             }
             TransportCallbackType::OnServerTransportException => {
-                log_error!(format!(
+                log_error!(
                     "Server.HandleTransportException: connectionId: {} error: {:?}",
-                    tcb.conn_id, tcb.error
-                ));
+                    tcb.conn_id,
+                    tcb.error
+                );
                 Self::on_transport_exception(tcb.conn_id, tcb.error)
             }
             TransportCallbackType::OnServerDataSent => {}
+            // 同一个 Transport 同时用于客户端模式时, 客户端事件交给 NetworkClient
+            TransportCallbackType::OnClientConnected
+            | TransportCallbackType::OnClientDataReceived
+            | TransportCallbackType::OnClientDisconnected
+            | TransportCallbackType::OnClientError => NetworkClient::transport_callback(tcb),
         }
     }
 
     // 处理 TransportConnected 消息
     fn on_transport_connected(connection_id: u64) {
         if connection_id == 0 {
-            log_error!(format!("Server.HandleConnect: invalid connectionId: {}. Needs to be != 0, because 0 is reserved for local player.", connection_id));
+            log_error!("Server.HandleConnect: invalid connectionId: {}. Needs to be != 0, because 0 is reserved for local player.", connection_id);
             if let Some(transport) = Transport::active_transport() {
                 transport.server_disconnect(connection_id);
             }
@@ -753,23 +1411,39 @@ impl NetworkServer {
         }
 
         if NetworkServerStatic::network_connections().contains_key(&connection_id) {
-            log_error!(format!(
+            log_error!(
                 "Server.HandleConnect: connectionId {} already exists.",
                 connection_id
-            ));
+            );
             if let Some(transport) = Transport::active_transport() {
                 transport.server_disconnect(connection_id);
             }
             return;
         }
 
+        if let Some(transport) = Transport::active_transport() {
+            let address = transport.server_get_client_address(connection_id);
+            if AntiCheat::is_banned(&address) {
+                log_warn!(
+                    "Server.HandleConnect: connectionId: {} address {} is banned.",
+                    connection_id,
+                    address
+                );
+                transport.server_disconnect(connection_id);
+                return;
+            }
+        }
         if NetworkServerStatic::network_connections_size() >= NetworkServerStatic::max_connections()
         {
-            log_error!(format!(
+            if NetworkServerStatic::connection_queue_enabled() {
+                Self::enqueue_connection(connection_id);
+                return;
+            }
+            log_error!(
                 "Server.HandleConnect: max_connections reached: {}. Disconnecting connectionId: {}",
                 NetworkServerStatic::max_connections(),
                 connection_id
-            ));
+            );
             if let Some(transport) = Transport::active_transport() {
                 transport.server_disconnect(connection_id);
             }
@@ -779,8 +1453,91 @@ impl NetworkServer {
         Self::on_connected(connection);
     }
 
+    // 满员时把连接放进等待队列, 只发送 QueuePositionMessage, 不创建 NetworkConnectionToClient
+    fn enqueue_connection(connection_id: u64) {
+        let (position, queue_length) = {
+            let mut queue = Self::write_connection_queue();
+            queue.push_back(connection_id);
+            QUEUED_CONNECTIONS.insert(connection_id);
+            (queue.len(), queue.len())
+        };
+        log_info!(
+            "Server.HandleConnect: max_connections reached: {}. Queued connectionId: {} at position {}",
+            NetworkServerStatic::max_connections(),
+            connection_id,
+            position
+        );
+        Self::send_queue_position(connection_id, position, queue_length);
+    }
+
+    // 排队中的连接没有 Batcher, 直接打包成一个批次交给 Transport
+    fn send_queue_position(connection_id: u64, position: usize, queue_length: usize) {
+        let Some(transport) = Transport::active_transport() else {
+            return;
+        };
+        let mut message = QueuePositionMessage::new(position as u32, queue_length as u32);
+        let mut batcher = Batcher::new(transport.get_max_packet_size(TransportChannel::Reliable));
+        NetworkWriterPool::get_return(|writer| {
+            message.serialize(writer);
+            batcher.add_message(writer.to_array_segment(), NetworkTime::local_time());
+            writer.reset();
+            while batcher.get_batcher_writer(writer) {
+                FrameReports::record_bytes_sent(writer.get_position());
+                transport.server_send(connection_id, writer.to_bytes(), TransportChannel::Reliable);
+                writer.reset();
+            }
+        });
+    }
+
+    // 处理器 panic 时锁可能被毒化, 继续使用里面的数据
+    fn write_connection_queue() -> RwLockWriteGuard<'static, VecDeque<u64>> {
+        CONNECTION_QUEUE.write().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // 有空位时按顺序放行排队的连接, 并定期通知剩余连接的位置
+    fn process_connection_queue() {
+        let mut admitted = Vec::new();
+        let waiting = {
+            let mut queue = Self::write_connection_queue();
+            let free = NetworkServerStatic::max_connections()
+                .saturating_sub(NetworkServerStatic::network_connections_size());
+            for _ in 0..free.min(queue.len()) {
+                if let Some(connection_id) = queue.pop_front() {
+                    QUEUED_CONNECTIONS.remove(&connection_id);
+                    admitted.push(connection_id);
+                }
+            }
+            queue.iter().copied().collect::<Vec<u64>>()
+        };
+        for connection_id in admitted {
+            Self::on_connected(NetworkConnectionToClient::new(connection_id));
+        }
+
+        let local_time = NetworkTime::local_time();
+        if local_time - CONNECTION_QUEUE_LAST_POSITION_TIME.load(Ordering::Relaxed)
+            < NetworkServerStatic::connection_queue_position_interval()
+        {
+            return;
+        }
+        CONNECTION_QUEUE_LAST_POSITION_TIME.store(local_time, Ordering::Relaxed);
+        for (index, connection_id) in waiting.iter().enumerate() {
+            Self::send_queue_position(*connection_id, index + 1, waiting.len());
+        }
+    }
+
     // 处理 TransportData 消息
     fn on_transport_data(connection_id: u64, data: Vec<u8>, channel: TransportChannel) {
+        FrameReports::record_bytes_received(data.len());
+        // 排队中的连接发来的数据直接丢弃
+        if NetworkServerStatic::is_connection_queued(connection_id) {
+            return;
+        }
+        // 语音帧不经过 un_batcher 和消息处理器
+        if channel == TransportChannel::Unreliable
+            && VoiceRelay::try_fast_path(connection_id, &data)
+        {
+            return;
+        }
         // 获取 transport_data_un_batcher
         if let Ok(mut transport_data_un_batcher) =
             NetworkServerStatic::transport_data_un_batcher().write()
@@ -790,51 +1547,55 @@ impl NetworkServer {
                 TryResult::Present(mut connection) => {
                     // 添加数据到 transport_data_un_batcher
                     if !transport_data_un_batcher.add_batch_with_bytes(data) {
+                        AntiCheat::report(connection_id, CheatSignal::MalformedPacket);
                         if NetworkServerStatic::exceptions_disconnect() {
-                            log_error!(format!(
+                            log_error!(
                             "Server.HandleData: connectionId: {} failed to add un_batch. Disconnecting.",
                             connection_id
-                        ));
+                        );
                             connection.disconnect();
                             return;
                         }
-                        log_warn!(format!(
+                        log_warn!(
                             "Server.HandleData: connectionId: {} failed to add un_batch.",
                             connection_id
-                        ));
+                        );
                         return;
                     }
                 }
                 // 如果没有找到连接
                 TryResult::Absent => {
-                    log_error!(format!(
+                    log_error!(
                         "Server.HandleData: connectionId: {} not found.",
                         connection_id
-                    ));
+                    );
                     return;
                 }
                 TryResult::Locked => {
-                    log_error!(format!(
+                    FrameReports::record_lock_contention();
+                    log_error!(
                         "Server.HandleData: connectionId: {} is locked.",
                         connection_id
-                    ));
+                    );
                     return;
                 }
             }
 
             // 如果正在加载场景
             if NetworkServerStatic::is_loading_scene() {
-                log_error!(format!(
+                log_error!(
                     "Server.HandleData: connectionId: {} is loading scene. Ignoring message.",
                     connection_id
-                ));
+                );
                 return;
             }
 
             // 处理消息
+            let mut panicked = false;
             while let Some((message, remote_time_stamp)) =
                 transport_data_un_batcher.get_next_message()
             {
+                TrafficLog::log(TrafficDirection::Receive, connection_id, channel, message);
                 NetworkReaderPool::get_with_array_segment_return(&message, |reader| {
                     match reader.remaining() >= NetworkMessages::ID_SIZE {
                         // 如果消息长度大于 NetworkMessages::ID_SIZE
@@ -844,27 +1605,50 @@ impl NetworkServer {
                                 .try_get_mut(&connection_id)
                             {
                                 TryResult::Present(mut connection) => {
-                                    connection.set_remote_time_stamp(remote_time_stamp);
+                                    if let Some(remote_time_stamp) = connection
+                                        .validate_remote_time_stamp(
+                                            remote_time_stamp,
+                                            NetworkTime::local_time(),
+                                        )
+                                    {
+                                        connection.set_remote_time_stamp(remote_time_stamp);
+                                    }
                                 }
                                 TryResult::Absent => {
-                                    log_error!(format!(
+                                    log_error!(
                                         "Server.HandleData: connectionId: {} not found.",
                                         connection_id
-                                    ));
+                                    );
                                     return;
                                 }
                                 TryResult::Locked => {
-                                    log_error!(format!(
+                                    FrameReports::record_lock_contention();
+                                    log_error!(
                                         "Server.HandleData: connectionId: {} is locked.",
                                         connection_id
-                                    ));
+                                    );
                                     return;
                                 }
                             }
                             // 处理消息
-                            if !Self::unpack_and_invoke(connection_id, reader, channel) {
+                            let invoked = if NetworkServerStatic::isolate_handler_panics() {
+                                match panic::catch_unwind(AssertUnwindSafe(|| {
+                                    Self::unpack_and_invoke(connection_id, reader, channel)
+                                })) {
+                                    Ok(invoked) => invoked,
+                                    Err(payload) => {
+                                        Self::on_handler_panic(connection_id, message, payload);
+                                        panicked = true;
+                                        return;
+                                    }
+                                }
+                            } else {
+                                Self::unpack_and_invoke(connection_id, reader, channel)
+                            };
+                            if !invoked {
+                                AntiCheat::report(connection_id, CheatSignal::MalformedPacket);
                                 if NetworkServerStatic::exceptions_disconnect() {
-                                    log_error!(format!("Server.HandleData: connectionId: {} failed to unpack and invoke message. Disconnecting.", connection_id));
+                                    log_error!("Server.HandleData: connectionId: {} failed to unpack and invoke message. Disconnecting.", connection_id);
                                     match NetworkServerStatic::network_connections()
                                         .try_get_mut(&connection_id)
                                     {
@@ -872,28 +1656,30 @@ impl NetworkServer {
                                             connection.disconnect();
                                         }
                                         TryResult::Absent => {
-                                            log_error!(format!(
+                                            log_error!(
                                                 "Server.HandleData: connectionId: {} not found.",
                                                 connection_id
-                                            ));
+                                            );
                                         }
                                         TryResult::Locked => {
-                                            log_error!(format!(
+                                            FrameReports::record_lock_contention();
+                                            log_error!(
                                                 "Server.HandleData: connectionId: {} is locked.",
                                                 connection_id
-                                            ));
+                                            );
                                         }
                                     }
                                 } else {
-                                    log_warn!(format!("Server.HandleData: connectionId: {} failed to unpack and invoke message.", connection_id));
+                                    log_warn!("Server.HandleData: connectionId: {} failed to unpack and invoke message.", connection_id);
                                 }
                                 return;
                             }
                         }
                         // 如果消息长度小于 NetworkMessages::ID_SIZE
                         false => {
+                            AntiCheat::report(connection_id, CheatSignal::MalformedPacket);
                             if NetworkServerStatic::exceptions_disconnect() {
-                                log_error!(format!("Server.HandleData: connectionId: {} message too small. Disconnecting.", connection_id));
+                                log_error!("Server.HandleData: connectionId: {} message too small. Disconnecting.", connection_id);
                                 match NetworkServerStatic::network_connections()
                                     .try_get_mut(&connection_id)
                                 {
@@ -901,38 +1687,100 @@ impl NetworkServer {
                                         connection.disconnect();
                                     }
                                     TryResult::Absent => {
-                                        log_error!(format!(
+                                        log_error!(
                                             "Server.HandleData: connectionId: {} not found.",
                                             connection_id
-                                        ));
+                                        );
                                     }
                                     TryResult::Locked => {
-                                        log_error!(format!(
+                                        FrameReports::record_lock_contention();
+                                        log_error!(
                                             "Server.HandleData: connectionId: {} is locked.",
                                             connection_id
-                                        ));
+                                        );
                                     }
                                 }
                             } else {
-                                log_warn!(format!(
+                                log_warn!(
                                     "Server.HandleData: connectionId: {} message too small.",
                                     connection_id
-                                ));
+                                );
                             }
                             return;
                         }
                     }
                 });
+                // 同一个 batch 中剩余的消息不再处理
+                if panicked {
+                    break;
+                }
+            }
+            if panicked {
+                transport_data_un_batcher.clear();
             }
 
             if transport_data_un_batcher.batches_count() > 0 {
-                log_error!(format!(
+                log_error!(
                     "Server.HandleData: connectionId: {} unprocessed batches: {}",
                     connection_id,
                     transport_data_un_batcher.batches_count()
-                ));
+                );
+            }
+        }
+    }
+
+    // 处理器 panic: 记录消息内容后断开这个连接, 服务器继续为其他连接服务
+    fn on_handler_panic(connection_id: u64, message: &[u8], payload: Box<dyn Any + Send>) {
+        HANDLER_PANIC_COUNT.fetch_add(1, Ordering::Relaxed);
+        let reason = if let Some(reason) = payload.downcast_ref::<&str>() {
+            reason.to_string()
+        } else if let Some(reason) = payload.downcast_ref::<String>() {
+            reason.clone()
+        } else {
+            "unknown".to_string()
+        };
+        let hex: String = message.iter().map(|byte| format!("{:02X}", byte)).collect();
+        log_error!(
+            "Server.HandleData: connectionId: {} handler panicked: {}. Payload: {}. Disconnecting.",
+            connection_id,
+            reason,
+            hex
+        );
+        match NetworkServerStatic::network_connections().try_get_mut(&connection_id) {
+            TryResult::Present(mut connection) => {
+                Self::disconnect_connection_with_reason(&mut connection, DisconnectReason::Kick);
+            }
+            TryResult::Absent => {}
+            TryResult::Locked => {
+                // 处理器可能在持有连接时 panic, guard 在 unwind 时已经释放, 这里只是兜底
+                FrameReports::record_lock_contention();
+                PENDING_DISCONNECTS.insert(connection_id);
+            }
+        }
+    }
+
+    // 在网络循环中重新处理之前推迟的消息, payload 不包含消息id
+    pub(crate) fn invoke_handler<T>(connection_id: u64, payload: &[u8]) -> bool
+    where
+        T: NetworkMessageTrait + Send + Sync + 'static,
+    {
+        // 复制处理函数, 调用时不持有 NETWORK_MESSAGE_HANDLERS
+        let func = match NETWORK_MESSAGE_HANDLERS.get(&T::get_hash_code()) {
+            Some(handler) => handler.func,
+            None => return false,
+        };
+        let mut reader = NetworkReader::new_with_array_segment(payload);
+        if NetworkServerStatic::isolate_handler_panics() {
+            if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| {
+                func(connection_id, &mut reader, TransportChannel::Reliable)
+            })) {
+                Self::on_handler_panic(connection_id, reader.to_array_segment(), payload);
+                return false;
             }
+        } else {
+            func(connection_id, &mut reader, TransportChannel::Reliable);
         }
+        true
     }
 
     fn unpack_and_invoke(
@@ -942,40 +1790,157 @@ impl NetworkServer {
     ) -> bool {
         // 解包消息id
         let message_id = NetworkMessages::unpack_id(reader);
+        // 握手完成之前只处理握手和 ping / pong 消息
+        if !Self::protocol_message_allowed(connection_id, message_id) {
+            log_warn!(
+                "Server.HandleData: connectionId: {} sent message id: {} before protocol handshake.",
+                connection_id, message_id
+            );
+            return false;
+        }
+        // 选择装备之前的 AddPlayerMessage 推迟到选择完成
+        if message_id == AddPlayerMessage::get_hash_code()
+            && LoadoutPhase::defer_add_player(connection_id)
+        {
+            return true;
+        }
         // 如果消息id在 NETWORK_MESSAGE_HANDLERS 中
         if let Some(handler) = NETWORK_MESSAGE_HANDLERS.get(&message_id) {
             (handler.func)(connection_id, reader, channel);
+            FrameReports::record_message();
             match NetworkServerStatic::network_connections().try_get_mut(&connection_id) {
                 TryResult::Present(mut connection) => {
                     connection.set_last_message_time(NetworkTime::local_time());
                 }
                 TryResult::Absent => {
-                    log_error!(format!(
+                    log_error!(
                         "Server.HandleData: connectionId: {} not found.",
                         connection_id
-                    ));
+                    );
                 }
                 TryResult::Locked => {
-                    log_error!(format!(
+                    FrameReports::record_lock_contention();
+                    log_error!(
                         "Server.HandleData: connectionId: {} is locked.",
                         connection_id
-                    ));
+                    );
                 }
             }
             return true;
         }
-        log_warn!(format!(
-            "Server.HandleData: connectionId: {} unknown message id: {}",
-            connection_id, message_id
-        ));
-        false
+        Self::on_unknown_message(connection_id, message_id, reader, channel)
     }
 
-    // 处理 TransportDisconnected 消息
+    // 未知消息: 统计次数, 交给自定义处理, 限频打印日志, 可选回复 ErrorMessage
+    fn on_unknown_message(
+        connection_id: u64,
+        message_id: u16,
+        reader: &mut NetworkReader,
+        channel: TransportChannel,
+    ) -> bool {
+        let now = NetworkTime::local_time();
+        let mut log_suppressed = None;
+        {
+            let mut stats = UNKNOWN_MESSAGE_STATS.entry(message_id).or_default();
+            stats.count += 1;
+            if stats.count == 1
+                || now - stats.last_log_time >= NetworkServerStatic::unknown_message_log_interval()
+            {
+                log_suppressed = Some(stats.suppressed);
+                stats.last_log_time = now;
+                stats.suppressed = 0;
+            } else {
+                stats.suppressed += 1;
+            }
+        }
+
+        if let Some(handler) = NetworkServerStatic::unknown_message_handler() {
+            if handler(connection_id, message_id, reader, channel) {
+                return true;
+            }
+        }
+
+        if let Some(suppressed) = log_suppressed {
+            log_warn!(
+                "Server.HandleData: connectionId: {} unknown message id: {} (total: {}, suppressed: {})",
+                connection_id,
+                message_id,
+                NetworkServerStatic::unknown_message_count(message_id),
+                suppressed
+            );
+        }
+
+        if NetworkServerStatic::unknown_message_reply() {
+            match NetworkServerStatic::network_connections().try_get_mut(&connection_id) {
+                TryResult::Present(mut connection) => {
+                    let mut message =
+                        ErrorMessage::new(message_id, "Unknown message id.".to_string());
+                    connection.send_network_message(&mut message, TransportChannel::Reliable);
+                }
+                TryResult::Absent => {
+                    log_error!(
+                        "Server.HandleData: connectionId: {} not found.",
+                        connection_id
+                    );
+                }
+                TryResult::Locked => {
+                    FrameReports::record_lock_contention();
+                    log_error!(
+                        "Server.HandleData: connectionId: {} is locked.",
+                        connection_id
+                    );
+                }
+            }
+        }
+        false
+    }
+
+    fn protocol_message_allowed(connection_id: u64, message_id: u16) -> bool {
+        if !NetworkServerStatic::protocol_handshake()
+            || message_id == ProtocolVersionMessage::get_hash_code()
+            || message_id == NetworkPingMessage::get_hash_code()
+            || message_id == NetworkPongMessage::get_hash_code()
+        {
+            return true;
+        }
+        match NetworkServerStatic::network_connections().try_get(&connection_id) {
+            TryResult::Present(connection) => connection.protocol_verified,
+            TryResult::Absent => {
+                log_error!(
+                    "Server.HandleData: connectionId: {} not found.",
+                    connection_id
+                );
+                false
+            }
+            TryResult::Locked => {
+                FrameReports::record_lock_contention();
+                log_error!(
+                    "Server.HandleData: connectionId: {} is locked.",
+                    connection_id
+                );
+                false
+            }
+        }
+    }
+
+    // 处理 TransportDisconnected 消息
     fn on_transport_disconnected(connection_id: u64) {
+        if QUEUED_CONNECTIONS.remove(&connection_id).is_some() {
+            Self::write_connection_queue().retain(|id| *id != connection_id);
+        }
+        AntiCheat::on_disconnected(connection_id);
+        Ephemeral::on_disconnected(connection_id);
+        NetworkScoreboard::on_disconnected(connection_id);
+        LoadoutPhase::on_disconnected(connection_id);
+        UnreliableSequencing::on_disconnected(connection_id);
+        InterestRadius::on_disconnected(connection_id);
+        RegionStreaming::on_disconnected(connection_id);
+        HandshakeTiming::on_disconnected(connection_id);
         if let Some((_, mut connection)) =
             NetworkServerStatic::network_connections().remove(&connection_id)
         {
+            // 保留会话时 owned 对象不会被销毁
+            SessionResume::on_disconnected(&mut connection);
             if let Some(on_disconnected_event) =
                 NetworkServerStatic::connected_event().get(&EventHandlerType::OnDisconnectedEvent)
             {
@@ -984,6 +1949,7 @@ impl NetworkServer {
                 Self::destroy_player_for_connection(&mut connection);
             }
             connection.cleanup();
+            NetworkEvents::publish_disconnect(connection_id);
         }
     }
 
@@ -1032,17 +1998,14 @@ impl NetworkServer {
                         Self::send_change_owner_message(&mut identity, conn);
                     }
                     TryResult::Absent => {
-                        log_error!(format!(
+                        log_error!(
                             "Server.RemovePlayer: netId {} not found in spawned.",
                             conn.net_id()
-                        ));
+                        );
                         return;
                     }
                     TryResult::Locked => {
-                        log_error!(format!(
-                            "Server.RemovePlayer: netId {} is locked.",
-                            conn.net_id()
-                        ));
+                        log_error!("Server.RemovePlayer: netId {} is locked.", conn.net_id());
                         return;
                     }
                 }
@@ -1053,10 +2016,10 @@ impl NetworkServer {
                         Self::un_spawn(conn, &mut identity);
                     }
                     None => {
-                        log_error!(format!(
+                        log_error!(
                             "Server.RemovePlayer: netId {} not found in spawned.",
                             conn.net_id()
-                        ));
+                        );
                         return;
                     }
                 }
@@ -1068,17 +2031,14 @@ impl NetworkServer {
                         Self::destroy(conn, &mut identity);
                     }
                     TryResult::Absent => {
-                        log_error!(format!(
+                        log_error!(
                             "Server.RemovePlayer: netId {} not found in spawned.",
                             conn.net_id()
-                        ));
+                        );
                         return;
                     }
                     TryResult::Locked => {
-                        log_error!(format!(
-                            "Server.RemovePlayer: netId {} is locked.",
-                            conn.net_id()
-                        ));
+                        log_error!("Server.RemovePlayer: netId {} is locked.", conn.net_id());
                         return;
                     }
                 }
@@ -1149,6 +2109,15 @@ impl NetworkServer {
 
         identity.on_stop_server();
 
+        NetworkEvents::publish_despawn(identity.net_id(), !reset_state);
+        NetworkAttachment::on_despawn(identity.net_id());
+        GameplayEvents::on_despawn(identity.net_id());
+        RegionStreaming::on_despawn(identity.net_id());
+        Steering::on_despawn(identity.net_id());
+        ComponentAuthority::on_despawn(identity.net_id());
+        SyncObjectPersistence::on_despawn(identity.net_id());
+        SyncVarEvents::on_despawn(identity.net_id());
+
         if reset_state {
             identity.reset_state();
             identity.set_active(false);
@@ -1178,7 +2147,9 @@ impl NetworkServer {
             for conn_id in identity.observers().iter() {
                 match NetworkServerStatic::network_connections().try_get_mut(conn_id) {
                     TryResult::Present(mut connection) => {
-                        connection.send(segment, channel);
+                        if connection.is_ready() {
+                            connection.send(segment, channel);
+                        }
                     }
                     TryResult::Absent => {
                         conn.send(segment, channel);
@@ -1195,8 +2166,8 @@ impl NetworkServer {
         if let Some(mut identity) = player.get_identity_by_prefab() {
             match NetworkServerStatic::network_connections().try_get_mut(&conn_id) {
                 TryResult::Present(mut connection) => {
-                    if connection.net_id() != 0 {
-                        log_warn!(format!("AddPlayer: connection already has a player GameObject. Please remove the current player GameObject from {}", connection.is_ready()));
+                    if connection.net_id() != 0 && !NetworkServerStatic::add_player_replace() {
+                        log_warn!("AddPlayer: connection already has a player GameObject. Please remove the current player GameObject from {}", connection.is_ready());
                         return None;
                     }
                     // 设置连接的 NetworkIdentity
@@ -1205,14 +2176,14 @@ impl NetworkServer {
                     identity.set_client_owner(conn_id);
                 }
                 TryResult::Absent => {
-                    log_warn!(format!(
+                    log_warn!(
                         "AddPlayer: connectionId {} not found in connections",
                         conn_id
-                    ));
+                    );
                     return None;
                 }
                 TryResult::Locked => {
-                    log_error!(format!("AddPlayer: connectionId {} is locked", conn_id));
+                    log_error!("AddPlayer: connectionId {} is locked", conn_id);
                     return None;
                 }
             }
@@ -1222,19 +2193,472 @@ impl NetworkServer {
     }
 
     pub fn add_player_for_connection(conn_id: u64, player: &GameObject) -> bool {
+        let old_net_id = match NetworkServerStatic::network_connections().try_get(&conn_id) {
+            TryResult::Present(connection) => connection.net_id(),
+            _ => 0,
+        };
         match Self::init_identity_by_game_obj(conn_id, &player) {
             None => {
-                log_warn!(format!("AddPlayer: player GameObject has no NetworkIdentity. Please add a NetworkIdentity to {:?}",1));
+                log_warn!("AddPlayer: player GameObject has no NetworkIdentity. Please add a NetworkIdentity to {:?}",1);
                 false
             }
             Some(identity) => {
                 Self::set_client_ready(conn_id);
                 Self::respawn(identity);
+                HandshakeTiming::record(conn_id, HandshakeStage::Spawned);
+                // 替换了旧玩家, 旧玩家保留所有权但不再是本地玩家
+                if old_net_id != 0 {
+                    Self::send_change_owner_message_for_net_id(conn_id, old_net_id);
+                }
                 true
             }
         }
     }
 
+    // 把连接的玩家替换为已经生成的对象
+    // keep_authority 为 true 时旧玩家仍然属于该连接, 一个连接可以同时控制多个对象
+    pub fn replace_player_for_connection_with_net_id(
+        conn_id: u64,
+        new_net_id: u32,
+        keep_authority: bool,
+    ) -> bool {
+        // 新玩家不能属于其他连接
+        match NetworkServerStatic::spawned_network_identities().try_get(&new_net_id) {
+            TryResult::Present(identity) => {
+                if identity.connection_to_client() != 0
+                    && identity.connection_to_client() != conn_id
+                {
+                    log_error!(
+                        "Cannot replace player for connection. New player is already owned by a different connection. netId: {}, connId: {}",
+                        new_net_id, conn_id
+                    );
+                    return false;
+                }
+            }
+            TryResult::Absent => {
+                log_error!("ReplacePlayer: netId {} not found in spawned", new_net_id);
+                return false;
+            }
+            TryResult::Locked => {
+                log_error!("ReplacePlayer: netId {} is locked", new_net_id);
+                return false;
+            }
+        }
+
+        // 修改连接的玩家
+        let old_net_id = match NetworkServerStatic::network_connections().try_get_mut(&conn_id) {
+            TryResult::Present(mut connection) => {
+                let old_net_id = connection.net_id();
+                connection.set_net_id(new_net_id);
+                old_net_id
+            }
+            TryResult::Absent => {
+                log_error!(
+                    "ReplacePlayer: connectionId {} not found in connections",
+                    conn_id
+                );
+                return false;
+            }
+            TryResult::Locked => {
+                log_error!("ReplacePlayer: connectionId {} is locked", conn_id);
+                return false;
+            }
+        };
+
+        // 新玩家: 设置所有者, 已经在观察的发送 ChangeOwnerMessage, 否则生成
+        match NetworkServerStatic::spawned_network_identities().try_get_mut(&new_net_id) {
+            TryResult::Present(mut identity) => {
+                if identity.connection_to_client() != conn_id {
+                    identity.set_connection_to_client(conn_id);
+                }
+                let observing =
+                    match NetworkServerStatic::network_connections().try_get_mut(&conn_id) {
+                        TryResult::Present(mut connection) => {
+                            let observing = connection.observing.contains(&new_net_id);
+                            if observing {
+                                Self::send_change_owner_message(&mut identity, &mut connection);
+                            }
+                            observing
+                        }
+                        _ => true,
+                    };
+                if !observing {
+                    identity.add_observer(conn_id);
+                }
+            }
+            TryResult::Absent => {
+                log_error!("ReplacePlayer: netId {} not found in spawned", new_net_id);
+                return false;
+            }
+            TryResult::Locked => {
+                log_error!("ReplacePlayer: netId {} is locked", new_net_id);
+                return false;
+            }
+        }
+
+        if old_net_id == 0 || old_net_id == new_net_id {
+            return true;
+        }
+
+        // 旧玩家
+        if keep_authority {
+            Self::send_change_owner_message_for_net_id(conn_id, old_net_id);
+        } else {
+            match NetworkServerStatic::spawned_network_identities().try_get_mut(&old_net_id) {
+                TryResult::Present(mut identity) => {
+                    identity.remove_client_authority();
+                }
+                TryResult::Absent => {
+                    log_error!("ReplacePlayer: netId {} not found in spawned", old_net_id);
+                }
+                TryResult::Locked => {
+                    log_error!("ReplacePlayer: netId {} is locked", old_net_id);
+                }
+            }
+            if let TryResult::Present(mut connection) =
+                NetworkServerStatic::network_connections().try_get_mut(&conn_id)
+            {
+                connection.remove_owned_object(old_net_id);
+            }
+        }
+        true
+    }
+
+    // 为连接生成附属对象 (宠物 / 召唤物), 连接的玩家保持不变, 有玩家时生成在玩家的位置
+    // 返回生成后的 net_id, 失败时为 0
+    pub fn spawn_owned(conn_id: u64, asset_id: u32) -> u32 {
+        let mut identity = NetworkIdentity::new_with_asset_id(asset_id);
+        // 没有 GameObject 的对象无法被 destroy, 后端数据中没有这个资源时用 asset_id 作为 prefab
+        let prefab = BackendDataStatic::get_backend_data()
+            .get_asset_name_by_asset_id(asset_id)
+            .unwrap_or_else(|| asset_id.to_string());
+        let mut game_object = GameObject::new_with_prefab(prefab);
+        let player_net_id = match NetworkServerStatic::network_connections().try_get(&conn_id) {
+            TryResult::Present(connection) => connection.net_id(),
+            _ => 0,
+        };
+        if let TryResult::Present(player) =
+            NetworkServerStatic::spawned_network_identities().try_get(&player_net_id)
+        {
+            game_object.transform.position = player.game_object().transform.position;
+            game_object.transform.local_position = player.game_object().transform.position;
+        }
+        identity.set_game_object(game_object);
+        Self::spawn_owned_identity(conn_id, identity)
+    }
+
+    // 生成已经设置好的 identity 并交给连接, 与 spawn_owned 相同
+    pub fn spawn_owned_identity(conn_id: u64, identity: NetworkIdentity) -> u32 {
+        match NetworkServerStatic::network_connections().try_get(&conn_id) {
+            TryResult::Present(_) => {}
+            TryResult::Absent => {
+                log_error!(
+                    "Server.SpawnOwned: connectionId {} not found in connections",
+                    conn_id
+                );
+                return 0;
+            }
+            TryResult::Locked => {
+                log_error!("Server.SpawnOwned: connectionId {} is locked", conn_id);
+                return 0;
+            }
+        }
+        if identity.connection_to_client() != 0 && identity.connection_to_client() != conn_id {
+            log_error!(
+                "Server.SpawnOwned: identity is already owned by connectionId {}",
+                identity.connection_to_client()
+            );
+            return 0;
+        }
+        // 连接的 net_id 不变, 观察者收到的 SpawnMessage 中 is_local_player 为 false
+        Self::spawn(identity, conn_id)
+    }
+
+    // 把没有所有者的对象交给连接, 所有者收到 ChangeOwnerMessage
+    pub fn assign_owner(net_id: u32, conn_id: u64) -> bool {
+        match NetworkServerStatic::spawned_network_identities().try_get_mut(&net_id) {
+            TryResult::Present(mut identity) => {
+                if identity.connection_to_client() == conn_id {
+                    return true;
+                }
+                if identity.connection_to_client() != 0 {
+                    log_error!(
+                        "Server.AssignOwner: netId {} is already owned by connectionId {}, remove the owner first",
+                        net_id,
+                        identity.connection_to_client()
+                    );
+                    return false;
+                }
+                if !NetworkServerStatic::network_connections().contains_key(&conn_id) {
+                    log_error!(
+                        "Server.AssignOwner: connectionId {} not found in connections",
+                        conn_id
+                    );
+                    return false;
+                }
+                identity.set_connection_to_client(conn_id);
+            }
+            TryResult::Absent => {
+                log_error!("Server.AssignOwner: netId {} not found in spawned", net_id);
+                return false;
+            }
+            TryResult::Locked => {
+                log_error!("Server.AssignOwner: netId {} is locked", net_id);
+                return false;
+            }
+        }
+        Self::send_change_owner_message_for_net_id(conn_id, net_id);
+        true
+    }
+
+    // 连接拥有的所有对象, 包括玩家
+    pub fn owned_objects(conn_id: u64) -> Vec<u32> {
+        match NetworkServerStatic::network_connections().try_get_mut(&conn_id) {
+            TryResult::Present(mut connection) => connection.owned().clone(),
+            _ => Vec::new(),
+        }
+    }
+
+    // 连接拥有的玩家以外的对象
+    pub fn secondary_objects(conn_id: u64) -> Vec<u32> {
+        match NetworkServerStatic::network_connections().try_get_mut(&conn_id) {
+            TryResult::Present(mut connection) => {
+                let player_net_id = connection.net_id();
+                connection
+                    .owned()
+                    .iter()
+                    .filter(|net_id| **net_id != player_net_id)
+                    .copied()
+                    .collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    pub(crate) fn send_change_owner_message_for_net_id(conn_id: u64, net_id: u32) {
+        match NetworkServerStatic::spawned_network_identities().try_get_mut(&net_id) {
+            TryResult::Present(mut identity) => {
+                match NetworkServerStatic::network_connections().try_get_mut(&conn_id) {
+                    TryResult::Present(mut connection) => {
+                        Self::send_change_owner_message(&mut identity, &mut connection);
+                    }
+                    TryResult::Absent => {
+                        log_error!(
+                            "Server.SendChangeOwnerMessage: connectionId {} not found in connections",
+                            conn_id
+                        );
+                    }
+                    TryResult::Locked => {
+                        log_error!(
+                            "Server.SendChangeOwnerMessage: connectionId {} is locked",
+                            conn_id
+                        );
+                    }
+                }
+            }
+            TryResult::Absent => {
+                log_error!(
+                    "Server.SendChangeOwnerMessage: netId {} not found in spawned",
+                    net_id
+                );
+            }
+            TryResult::Locked => {
+                log_error!("Server.SendChangeOwnerMessage: netId {} is locked", net_id);
+            }
+        }
+    }
+
+    // 导出连接的玩家状态 (认证数据 + 所有 owned 对象的位置和完整同步状态), 用于迁移到其他服务器实例
+    // 对象的 net_id 不会导出, 导入时重新分配
+    pub fn export_player_state(conn_id: u64) -> Option<Vec<u8>> {
+        let (player_net_id, auth_data) =
+            match NetworkServerStatic::network_connections().try_get_mut(&conn_id) {
+                TryResult::Present(mut connection) => {
+                    let auth_data = if NetworkManagerStatic::network_manager_singleton_exists() {
+                        match NetworkManagerStatic::network_manager_singleton().authenticator() {
+                            Some(authenticator) => {
+                                authenticator.export_authentication_data(&mut connection)
+                            }
+                            None => Vec::new(),
+                        }
+                    } else {
+                        Vec::new()
+                    };
+                    (connection.net_id(), auth_data)
+                }
+                TryResult::Absent => {
+                    log_error!(
+                        "Server.ExportPlayerState: connectionId {} not found in connections",
+                        conn_id
+                    );
+                    return None;
+                }
+                TryResult::Locked => {
+                    log_error!(
+                        "Server.ExportPlayerState: connectionId {} is locked",
+                        conn_id
+                    );
+                    return None;
+                }
+            };
+
+        let mut owned = Vec::new();
+        NetworkServerStatic::for_each_spawned(|identity| {
+            if identity.connection_to_client() == conn_id {
+                owned.push(identity.net_id());
+            }
+        });
+        // 玩家最后导入, 导入后仍然是本地玩家
+        owned.sort_by_key(|net_id| *net_id == player_net_id);
+
+        let mut blob = None;
+        NetworkWriterPool::get_return(|writer| {
+            writer.write_ushort(Self::PLAYER_STATE_VERSION);
+            writer.write_array_segment_and_size(&auth_data);
+            writer.compress_var_uint(owned.len() as u32);
+            for net_id in owned.iter() {
+                match NetworkServerStatic::spawned_network_identities().try_get_mut(net_id) {
+                    TryResult::Present(mut identity) => {
+                        let payload = Self::create_spawn_message_payload(true, &mut identity);
+                        let transform = &identity.game_object().transform;
+                        writer.write_bool(*net_id == player_net_id);
+                        writer.write_uint(identity.asset_id);
+                        writer.write_vector3(transform.local_position);
+                        writer.write_quaternion(transform.local_rotation);
+                        writer.write_vector3(transform.local_scale);
+                        writer.write_array_segment_and_size(&payload);
+                    }
+                    _ => {
+                        log_error!(
+                            "Server.ExportPlayerState: netId {} is not available",
+                            net_id
+                        );
+                        return;
+                    }
+                }
+            }
+            blob = Some(writer.to_bytes());
+        });
+        blob
+    }
+
+    // 导入 export_player_state 导出的状态, 为连接重新生成所有 owned 对象, 返回玩家的 net_id
+    // 连接会被标记为已认证, 由外部编排保证数据来源可信
+    pub fn import_player_state(conn_id: u64, blob: &[u8]) -> Option<u32> {
+        struct ImportedObject {
+            is_player: bool,
+            asset_id: u32,
+            position: Vector3<f32>,
+            rotation: Quaternion<f32>,
+            scale: Vector3<f32>,
+            payload: Vec<u8>,
+        }
+
+        if !NetworkServerStatic::active() {
+            log_error!("Server.ImportPlayerState: NetworkServer is not active.");
+            return None;
+        }
+
+        // 先完整解析, 数据有误时不生成任何对象
+        let mut reader = NetworkReader::new_with_array_segment(blob);
+        let version = reader.read_ushort();
+        if version != Self::PLAYER_STATE_VERSION {
+            log_error!("Server.ImportPlayerState: unsupported version {}", version);
+            return None;
+        }
+        let auth_data = reader.read_bytes_and_size();
+        let count = reader.decompress_var_uint();
+        let mut objects = Vec::new();
+        for _ in 0..count {
+            if reader.remaining() == 0 {
+                break;
+            }
+            objects.push(ImportedObject {
+                is_player: reader.read_bool(),
+                asset_id: reader.read_uint(),
+                position: reader.read_vector3(),
+                rotation: reader.read_quaternion(),
+                scale: reader.read_vector3(),
+                payload: reader.read_bytes_and_size(),
+            });
+        }
+        if objects.len() != count as usize || reader.remaining() != 0 {
+            log_error!(
+                "Server.ImportPlayerState: connectionId {} invalid player state",
+                conn_id
+            );
+            return None;
+        }
+
+        match NetworkServerStatic::network_connections().try_get_mut(&conn_id) {
+            TryResult::Present(mut connection) => {
+                connection.set_authenticated(true);
+                if NetworkManagerStatic::network_manager_singleton_exists() {
+                    if let Some(authenticator) =
+                        NetworkManagerStatic::network_manager_singleton().authenticator()
+                    {
+                        authenticator.import_authentication_data(&mut connection, &auth_data);
+                    }
+                }
+            }
+            TryResult::Absent => {
+                log_error!(
+                    "Server.ImportPlayerState: connectionId {} not found in connections",
+                    conn_id
+                );
+                return None;
+            }
+            TryResult::Locked => {
+                log_error!(
+                    "Server.ImportPlayerState: connectionId {} is locked",
+                    conn_id
+                );
+                return None;
+            }
+        }
+
+        // 逐个生成, 未生成的对象的 NetworkBehaviour 使用 net_id 0 作为 key
+        let mut net_ids = Vec::new();
+        let mut player_net_id = 0;
+        for object in objects {
+            let mut identity = NetworkIdentity::new_with_asset_id(object.asset_id);
+            let mut game_object = identity.game_object().clone();
+            game_object.transform.position = object.position;
+            game_object.transform.rotation = object.rotation;
+            game_object.transform.scale = object.scale;
+            game_object.transform.local_position = object.position;
+            game_object.transform.local_rotation = object.rotation;
+            game_object.transform.local_scale = object.scale;
+            identity.set_game_object(game_object);
+            let mut payload = NetworkReader::new_with_array_segment(&object.payload);
+            if !identity.deserialize_initial_state(&mut payload) {
+                log_warn!(
+                    "Server.ImportPlayerState: failed to apply state for asset_id {}",
+                    object.asset_id
+                );
+            }
+            identity.set_client_owner(conn_id);
+            let net_id = Self::spawn(identity, conn_id);
+            if object.is_player {
+                player_net_id = net_id;
+            }
+            net_ids.push(net_id);
+        }
+
+        // 生成 owned 对象时连接的 net_id 会被覆盖, 最后恢复为玩家并通知客户端
+        if let TryResult::Present(mut connection) =
+            NetworkServerStatic::network_connections().try_get_mut(&conn_id)
+        {
+            connection.set_net_id(player_net_id);
+        }
+        for net_id in net_ids {
+            if net_id != player_net_id {
+                Self::send_change_owner_message_for_net_id(conn_id, net_id);
+            }
+        }
+        Some(player_net_id)
+    }
+
     pub fn replace_player_for_connection(
         conn_id: u64,
         player: &GameObject,
@@ -1248,49 +2672,49 @@ impl NetworkServer {
                         if identity.connection_to_client() != 0
                             && identity.connection_to_client() != conn_id
                         {
-                            log_error!(format!(
+                            log_error!(
                                 "Cannot replace player for connection. New player is already owned by a different connection. netId: {}, connId: {}",
                                 conn.net_id(),
                                 conn.is_ready()
-                            ));
+                            );
                             return false;
                         }
                     }
                     TryResult::Absent => {
-                        log_error!(format!(
+                        log_error!(
                             "ReplacePlayer: netId {} not found in spawned",
                             conn.net_id()
-                        ));
+                        );
                         return false;
                     }
                     TryResult::Locked => {
-                        log_error!(format!("ReplacePlayer: netId {} is locked", conn.net_id()));
+                        log_error!("ReplacePlayer: netId {} is locked", conn.net_id());
                         return false;
                     }
                 }
             }
             TryResult::Absent => {
-                log_error!(format!(
+                log_error!(
                     "ReplacePlayer: connectionId {} not found in connections",
                     conn_id
-                ));
+                );
                 return false;
             }
             TryResult::Locked => {
-                log_error!(format!("ReplacePlayer: connectionId {} is locked", conn_id));
+                log_error!("ReplacePlayer: connectionId {} is locked", conn_id);
                 return false;
             }
         }
 
-        log_debug!(format!(
+        log_debug!(
             "ReplacePlayer: replacing player for connectionId: {} {}",
             conn_id,
             player.prefab
-        ));
+        );
         // 初始化 NetworkIdentity
         match player.get_identity_by_prefab() {
             None => {
-                log_warn!(format!("ReplacePlayer: player GameObject has no NetworkIdentity. Please add a NetworkIdentity to {:?}",1));
+                log_warn!("ReplacePlayer: player GameObject has no NetworkIdentity. Please add a NetworkIdentity to {:?}",1);
                 return false;
             }
             Some(identity) => {
@@ -1381,16 +2805,16 @@ impl NetworkServer {
                 }
             }
             TryResult::Absent => {
-                log_error!(format!(
+                log_error!(
                     "Failed to on_server_ready for conn {} because of absent",
                     conn_id
-                ));
+                );
             }
             TryResult::Locked => {
-                log_error!(format!(
+                log_error!(
                     "Failed to on_server_ready for conn {} because of locked",
                     conn_id
-                ));
+                );
             }
         }
         true
@@ -1416,35 +2840,40 @@ impl NetworkServer {
         }
     }
 
-    fn spawn(identity: NetworkIdentity, conn_id: u64) {
-        Self::spawn_object(identity, conn_id);
+    // 返回生成后的 net_id, 失败时为 0
+    fn spawn(identity: NetworkIdentity, conn_id: u64) -> u32 {
+        Self::spawn_object(identity, conn_id)
     }
 
     // SpawnObject(
-    fn spawn_object(mut identity: NetworkIdentity, conn_id: u64) {
+    fn spawn_object(mut identity: NetworkIdentity, conn_id: u64) -> u32 {
         if !NetworkServerStatic::active() {
-            log_error!(format!("SpawnObject for {:?}, NetworkServer is not active. Cannot spawn objects without an active server.", identity.game_object()));
-            return;
+            log_error!("SpawnObject for {:?}, NetworkServer is not active. Cannot spawn objects without an active server.", identity.game_object());
+            return 0;
         }
 
         if identity.spawned_from_instantiate {
-            return;
+            return 0;
         }
 
         if NetworkServerStatic::spawned_network_identities().contains_key(&identity.net_id()) {
-            log_warn!(format!(
+            log_warn!(
                 "SpawnObject for {:?}, netId {} already exists. Use UnSpawnObject first.",
                 identity.game_object(),
                 identity.net_id()
-            ));
-            return;
+            );
+            return 0;
         }
 
         // 如果 identity 的 net_id 为 0
         if identity.net_id() == 0 {
             // 必须先分配 NetworkIdentity 的 net_id 再设置连接的 NetworkIdentity
-            // 分配 NetworkIdentity 的 net_id
-            identity.set_net_id(NetworkIdentity::get_static_next_network_id());
+            // 分配 NetworkIdentity 的 net_id, 命名空间用完时放弃生成
+            let net_id = NetworkIdentity::get_static_next_network_id();
+            if net_id == 0 {
+                return 0;
+            }
+            identity.set_net_id(net_id);
 
             // 设置连接的 NetworkIdentity
             identity.set_connection_to_client(conn_id);
@@ -1455,17 +2884,152 @@ impl NetworkServer {
             // 重建观察者
             Self::rebuild_observers(&mut identity, true);
 
+            let net_id = identity.net_id();
+            NetworkEvents::publish_spawn(&identity);
             // 添加到 SPAWNED 中
             NetworkServerStatic::add_spawned_network_identity(identity);
 
-            return;
+            return net_id;
         }
 
         // TODO aoi
         Self::rebuild_observers(&mut identity, true);
+        identity.net_id()
+    }
+
+    // 保留 identity 原来的 net_id 生成 (恢复 WorldSnapshot), net_id 已被占用时重新分配
+    // 返回生成后的 net_id, 失败时为 0
+    pub fn spawn_with_net_id(mut identity: NetworkIdentity) -> u32 {
+        if !NetworkServerStatic::active() {
+            log_error!("SpawnWithNetId for {:?}, NetworkServer is not active. Cannot spawn objects without an active server.", identity.game_object());
+            return 0;
+        }
+
+        if identity.net_id() == 0
+            || NetworkServerStatic::spawned_network_identities().contains_key(&identity.net_id())
+        {
+            let net_id = NetworkIdentity::get_static_next_network_id();
+            if net_id == 0 {
+                return 0;
+            }
+            log_warn!(
+                "SpawnWithNetId: netId {} already exists, using netId {} instead.",
+                identity.net_id(),
+                net_id
+            );
+            identity.set_net_id(net_id);
+        }
+
+        identity.on_start_server();
+        Self::rebuild_observers(&mut identity, true);
+
+        let net_id = identity.net_id();
+        NetworkEvents::publish_spawn(&identity);
+        NetworkServerStatic::add_spawned_network_identity(identity);
+        net_id
     }
 
-    fn rebuild_observers(identity: &mut NetworkIdentity, initialize: bool) {
+    // 批量生成同一 asset_id 的对象, 对象不能有 NetworkBehaviour, 也不能属于任何连接
+    // 支持 FEATURE_BATCH_SPAWN 的客户端收到 BatchSpawnMessage, 其他客户端收到单独的 SpawnMessage
+    pub fn batch_spawn(identities: Vec<NetworkIdentity>) -> Vec<u32> {
+        if !NetworkServerStatic::active() {
+            log_error!("Server.BatchSpawn: NetworkServer is not active. Cannot spawn objects without an active server.");
+            return Vec::new();
+        }
+        let asset_id = match identities.first() {
+            Some(identity) => identity.asset_id,
+            None => return Vec::new(),
+        };
+        if identities.iter().any(|identity| {
+            identity.asset_id != asset_id
+                || identity.net_id() != 0
+                || identity.connection_to_client() != 0
+                || identity.network_behaviours_count > 0
+                || identity.server_only
+        }) {
+            log_error!(
+                "Server.BatchSpawn: all identities must be unspawned, unowned instances of asset_id {} without NetworkBehaviours.",
+                asset_id
+            );
+            return Vec::new();
+        }
+
+        let mut net_ids = Vec::with_capacity(identities.len());
+        for mut identity in identities {
+            let net_id = NetworkIdentity::get_static_next_network_id();
+            if net_id == 0 {
+                break;
+            }
+            identity.set_net_id(net_id);
+            identity.on_start_server();
+            net_ids.push(identity.net_id());
+            NetworkEvents::publish_spawn(&identity);
+            NetworkServerStatic::add_spawned_network_identity(identity);
+        }
+
+        let mut connections = Vec::new();
+        NetworkServerStatic::for_each_network_connection(|connection| {
+            if connection.is_ready() {
+                connections.push((
+                    connection.connection_id(),
+                    connection.observe_all,
+                    connection.features & Self::FEATURE_BATCH_SPAWN != 0,
+                ));
+            }
+        });
+        for (conn_id, observe_all, batch) in connections {
+            let mut entries = Vec::new();
+            for net_id in net_ids.iter() {
+                if let TryResult::Present(mut identity) =
+                    NetworkServerStatic::spawned_network_identities().try_get_mut(net_id)
+                {
+                    if !Self::is_visible_to(&identity, conn_id, observe_all) {
+                        continue;
+                    }
+                    if batch {
+                        identity.add_observer_without_spawn(conn_id);
+                        entries.push(BatchSpawnEntry::new(
+                            *net_id,
+                            identity.game_object().transform.local_position,
+                            identity.game_object().transform.local_rotation,
+                        ));
+                    } else {
+                        identity.add_observer(conn_id);
+                    }
+                }
+            }
+            if !entries.is_empty() {
+                Self::send_batch_spawn_message(conn_id, asset_id, entries);
+            }
+        }
+        net_ids
+    }
+
+    // 按最大消息大小拆分 BatchSpawnMessage
+    fn send_batch_spawn_message(conn_id: u64, asset_id: u32, entries: Vec<BatchSpawnEntry>) {
+        let max = NetworkMessages::max_message_size(TransportChannel::Reliable);
+        let per_message =
+            (max.saturating_sub(BatchSpawnMessage::HEADER_SIZE) / BatchSpawnEntry::MAX_SIZE).max(1);
+        match NetworkServerStatic::network_connections().try_get_mut(&conn_id) {
+            TryResult::Present(mut connection) => {
+                for chunk in entries.chunks(per_message) {
+                    let mut message = BatchSpawnMessage::new(asset_id, chunk.to_vec());
+                    connection.send_network_message(&mut message, TransportChannel::Reliable);
+                }
+            }
+            TryResult::Absent => {
+                log_error!(
+                    "Server.BatchSpawn: connectionId {} not found in connections",
+                    conn_id
+                );
+            }
+            TryResult::Locked => {
+                log_error!("Server.BatchSpawn: connectionId {} is locked", conn_id);
+            }
+        }
+    }
+
+    pub(crate) fn rebuild_observers(identity: &mut NetworkIdentity, initialize: bool) {
         // TODO aoi
         if "aoi" == "aoi" || identity.visibility == ForceShown {
             Self::rebuild_observers_default(identity, initialize);
@@ -1476,19 +3040,40 @@ impl NetworkServer {
 
     fn rebuild_observers_default(identity: &mut NetworkIdentity, initialize: bool) {
         if initialize {
-            if identity.visibility != Visibility::ForceHidden {
-                Self::add_all_ready_server_connections_to_observers(identity);
-            } else if identity.connection_to_client() != 0 {
+            if identity.visibility == Visibility::ForceHidden
+                && identity.connection_to_client() != 0
+            {
                 // force hidden, but add owner connection
                 identity.add_observer(identity.connection_to_client());
             }
+            Self::add_all_ready_server_connections_to_observers(identity);
+        }
+        Self::remove_hidden_observers(identity);
+    }
+
+    // 连接是否可以观察 identity
+    // observe_all 的连接 (观战 / 管理员) 可以观察所有对象, ForceHidden 只对所有者可见
+    pub fn is_visible_to(identity: &NetworkIdentity, conn_id: u64, observe_all: bool) -> bool {
+        if observe_all || identity.connection_to_client() == conn_id {
+            return true;
+        }
+        match identity.visibility {
+            Visibility::ForceHidden => false,
+            Visibility::ForceShown => true,
+            Visibility::Default => {
+                InterestRadius::in_range(identity, conn_id)
+                    && RegionStreaming::in_active_region(identity, conn_id)
+                    && InterestManagementStatic::check_observer(identity, conn_id)
+            }
         }
     }
 
     fn add_all_ready_server_connections_to_observers(identity: &mut NetworkIdentity) {
         let mut conn_ids = Vec::new();
         NetworkServerStatic::for_each_network_connection(|connection| {
-            if connection.is_ready() {
+            if connection.is_ready()
+                && Self::is_visible_to(identity, connection.connection_id(), connection.observe_all)
+            {
                 conn_ids.push(connection.connection_id());
             }
         });
@@ -1497,6 +3082,85 @@ impl NetworkServer {
         }
     }
 
+    fn remove_hidden_observers(identity: &mut NetworkIdentity) {
+        let mut hidden = Vec::new();
+        for conn_id in identity.observers().iter() {
+            let observe_all = match NetworkServerStatic::network_connections().try_get(conn_id) {
+                TryResult::Present(connection) => connection.observe_all,
+                TryResult::Absent => false,
+                // 连接正在被使用, 留到下一次重建观察者时处理
+                TryResult::Locked => continue,
+            };
+            if !Self::is_visible_to(identity, *conn_id, observe_all) {
+                hidden.push(*conn_id);
+            }
+        }
+        for conn_id in hidden {
+            match NetworkServerStatic::network_connections().try_get_mut(&conn_id) {
+                TryResult::Present(mut connection) => {
+                    identity.remove_observer(conn_id);
+                    connection.remove_from_observing(identity, false);
+                }
+                TryResult::Absent => {
+                    identity.remove_observer(conn_id);
+                    log_error!(
+                        "Server.RemoveHiddenObservers: connectionId {} not found in connections",
+                        conn_id
+                    );
+                }
+                // observers 和 observing 必须一起修改, 连接被占用时两边都保持不变
+                TryResult::Locked => {
+                    log_warn!(
+                        "Server.RemoveHiddenObservers: connectionId {} is locked",
+                        conn_id
+                    );
+                }
+            }
+        }
+    }
+
+    // 修改 identity 的可见性并重建观察者
+    pub fn set_visibility(net_id: u32, visibility: Visibility) {
+        match NetworkServerStatic::spawned_network_identities().try_get_mut(&net_id) {
+            TryResult::Present(mut identity) => {
+                identity.visibility = visibility;
+                Self::rebuild_observers(&mut identity, true);
+            }
+            TryResult::Absent => {
+                log_error!(
+                    "Server.SetVisibility: netId {} not found in spawned",
+                    net_id
+                );
+            }
+            TryResult::Locked => {
+                log_error!("Server.SetVisibility: netId {} is locked", net_id);
+            }
+        }
+    }
+
+    // 设置连接为观战 / 管理员模式, 忽略兴趣管理和 ForceHidden 观察所有对象
+    pub fn set_observe_all(conn_id: u64, observe_all: bool) {
+        match NetworkServerStatic::network_connections().try_get_mut(&conn_id) {
+            TryResult::Present(mut connection) => {
+                connection.observe_all = observe_all;
+            }
+            TryResult::Absent => {
+                log_error!(
+                    "Server.SetObserveAll: connectionId {} not found in connections",
+                    conn_id
+                );
+                return;
+            }
+            TryResult::Locked => {
+                log_error!("Server.SetObserveAll: connectionId {} is locked", conn_id);
+                return;
+            }
+        }
+        NetworkServerStatic::for_each_spawned(|mut identity| {
+            Self::rebuild_observers(&mut identity, true);
+        });
+    }
+
     fn respawn(mut identity: NetworkIdentity) {
         match identity.net_id() {
             0 => {
@@ -1512,16 +3176,16 @@ impl NetworkServer {
                         Self::send_spawn_message(&mut identity, &mut connection);
                     }
                     TryResult::Absent => {
-                        log_error!(format!(
+                        log_error!(
                             "Server.Respawn: connectionId {} not found in connections",
                             identity.connection_to_client()
-                        ));
+                        );
                     }
                     TryResult::Locked => {
-                        log_error!(format!(
+                        log_error!(
                             "Server.Respawn: connectionId {} is locked",
                             identity.connection_to_client()
-                        ));
+                        );
                     }
                 }
             }
@@ -1544,10 +3208,11 @@ impl NetworkServer {
 
     // 处理 ServerTransportException 消息
     fn on_transport_exception(connection_id: u64, transport_error: TransportError) {
-        log_warn!(format!(
+        log_warn!(
             "Server.HandleTransportException: connectionId: {}, error: {:?}",
-            connection_id, transport_error
-        ));
+            connection_id,
+            transport_error
+        );
         match NetworkServerStatic::network_connections().try_get_mut(&connection_id) {
             TryResult::Present(mut connection) => {
                 if let Some(on_exception_event) = NetworkServerStatic::connected_event()
@@ -1562,6 +3227,8 @@ impl NetworkServer {
 
     // 处理 Connected 消息
     fn on_connected(mut conn: NetworkConnectionToClient) {
+        // OnConnectedEvent 中可能直接完成认证, 先记录连接时间
+        HandshakeTiming::record(conn.connection_id(), HandshakeStage::Connected);
         // 如果有 OnConnectedEvent
         if let Some(on_connected_event) =
             NetworkServerStatic::connected_event().get(&EventHandlerType::OnConnectedEvent)
@@ -1570,8 +3237,20 @@ impl NetworkServer {
         } else {
             log_warn!("OnConnectedEvent is null");
         }
+        // 发送协议版本信息
+        if NetworkServerStatic::protocol_handshake() {
+            let mut message = ProtocolVersionMessage::new(
+                Self::PROTOCOL_VERSION,
+                NetworkServerStatic::required_features(),
+                NetworkServerStatic::tick_rate(),
+            );
+            conn.send_network_message(&mut message, TransportChannel::Reliable);
+        }
+        let connection_id = conn.connection_id();
+        let address = conn.address.clone();
         // 添加连接 到 NETWORK_CONNECTIONS
         NetworkServerStatic::add_network_connection(conn);
+        NetworkEvents::publish_connect(connection_id, &address);
     }
 
     // 注册消息处理程序
@@ -1581,6 +3260,9 @@ impl NetworkServer {
         // 注册 CommandMessage 处理程序
         Self::register_handler::<CommandMessage>(Self::on_command_message, true);
 
+        // 注册 ProtocolVersionMessage 处理程序
+        Self::register_handler::<ProtocolVersionMessage>(Self::on_protocol_version_message, false);
+
         // 注册 NetworkPingMessage 处理程序
         Self::register_handler::<NetworkPingMessage>(NetworkTime::on_server_ping, false);
         // 注册 NetworkPongMessage 处理程序
@@ -1590,6 +3272,81 @@ impl NetworkServer {
         Self::register_handler::<EntityStateMessage>(Self::on_entity_state_message, true);
         // 注册 TimeSnapshotMessage 处理程序
         Self::register_handler::<TimeSnapshotMessage>(Self::on_time_snapshot_message, true);
+        // 注册 TickSnapshotMessage 处理程序
+        Self::register_handler::<TickSnapshotMessage>(Self::on_tick_snapshot_message, true);
+
+        // 注册 BlobAckMessage 处理程序
+        Self::register_handler::<BlobAckMessage>(BlobTransfer::on_blob_ack_message, true);
+
+        // 注册 LoadoutMessage 处理程序
+        Self::register_handler::<LoadoutMessage>(LoadoutPhase::on_loadout_message, true);
+    }
+
+    // 修改 tick_rate / send_rate 之后调用, 立即通知支持 FEATURE_INTERPOLATION_HINT 的客户端, 不等下一次 pong
+    pub fn send_interpolation_hints() {
+        NetworkServerStatic::for_each_network_connection(|mut connection| {
+            connection.send_interpolation_hint(false);
+        });
+    }
+
+    // 处理 ProtocolVersionMessage 消息
+    fn on_protocol_version_message(
+        connection_id: u64,
+        reader: &mut NetworkReader,
+        channel: TransportChannel,
+    ) {
+        let message = ProtocolVersionMessage::deserialize(reader);
+        match NetworkServerStatic::network_connections().try_get_mut(&connection_id) {
+            TryResult::Present(mut connection) => {
+                let required_features = NetworkServerStatic::required_features();
+                let reason = if message.version != Self::PROTOCOL_VERSION {
+                    Some(format!(
+                        "Protocol version mismatch: server {}, client {}.",
+                        Self::PROTOCOL_VERSION,
+                        message.version
+                    ))
+                } else if message.features & required_features != required_features {
+                    Some(format!(
+                        "Missing required features: {:#010x}.",
+                        required_features & !message.features
+                    ))
+                } else {
+                    None
+                };
+                match reason {
+                    None => {
+                        connection.protocol_verified = true;
+                        connection.features = message.features;
+                        connection.send_interpolation_hint(true);
+                    }
+                    Some(reason) => {
+                        log_warn!(
+                            "Server.ProtocolHandshake: connectionId: {} rejected. {}",
+                            connection_id,
+                            reason
+                        );
+                        let mut reject = ProtocolRejectMessage::new(Self::PROTOCOL_VERSION, reason);
+                        connection.send_network_message(&mut reject, channel);
+                        Self::disconnect_connection_with_reason(
+                            &mut connection,
+                            DisconnectReason::ProtocolMismatch,
+                        );
+                    }
+                }
+            }
+            TryResult::Absent => {
+                log_error!(
+                    "Server.ProtocolHandshake: connectionId: {} not found.",
+                    connection_id
+                );
+            }
+            TryResult::Locked => {
+                log_error!(
+                    "Server.ProtocolHandshake: connectionId: {} is locked.",
+                    connection_id
+                );
+            }
+        }
     }
 
     // 处理 ReadyMessage 消息
@@ -1605,18 +3362,25 @@ impl NetworkServer {
         match NetworkServerStatic::network_connections().try_get_mut(&conn_id) {
             TryResult::Present(mut connection) => {
                 connection.set_ready(true);
+                HandshakeTiming::record(conn_id, HandshakeStage::Ready);
+                // 暂停期间准备好的客户端也需要显示暂停
+                if NetworkServerStatic::paused() {
+                    connection.send_network_message(
+                        &mut PauseMessage::new(true),
+                        TransportChannel::Reliable,
+                    );
+                }
+                NetworkScoreboard::on_client_ready(&mut connection);
+                SessionResume::on_client_ready(&mut connection);
             }
             TryResult::Absent => {
-                log_error!(format!(
+                log_error!(
                     "Server.SetClientReady: connectionId {} not found in connections",
                     conn_id
-                ));
+                );
             }
             TryResult::Locked => {
-                log_error!(format!(
-                    "Server.SetClientReady: connectionId {} is locked",
-                    conn_id
-                ));
+                log_error!("Server.SetClientReady: connectionId {} is locked", conn_id);
             }
         }
         // 为连接生成观察者
@@ -1632,11 +3396,11 @@ impl NetworkServer {
             return;
         }
 
-        NetworkWriterPool::get_return(|writer| {
+        NetworkWriterPool::get_return_for_message::<T, _>(|writer| {
             message.serialize(writer);
             let max = NetworkMessages::max_message_size(channel);
             if writer.get_position() > max {
-                log_error!("Message too large to send: ", writer.get_position());
+                log_error!("Message too large to send: {}", writer.get_position());
                 return;
             }
             NetworkServerStatic::for_each_network_connection(|mut connection| {
@@ -1647,51 +3411,97 @@ impl NetworkServer {
             });
         });
     }
-    // 设置所有客户端未准备就绪
+    // 设置所有客户端未准备就绪, 切换场景时调用
     pub fn set_all_clients_not_ready() {
-        NetworkServerStatic::for_each_network_connection(|mut connection| {
-            connection.set_ready(false);
-            connection.remove_from_observings_observers();
-            connection
-                .send_network_message(&mut NotReadyMessage::default(), TransportChannel::Reliable);
-        });
+        let conn_ids: Vec<u64> = NetworkServerStatic::network_connections()
+            .iter()
+            .map(|connection| connection.connection_id())
+            .collect();
+        for conn_id in conn_ids {
+            Self::set_client_not_ready(conn_id);
+        }
+    }
+    // 设置客户端未准备就绪: 不再接收状态和 RPC, 从所有对象的观察者中移除,
+    // 客户端重新发送 ReadyMessage 后再重新生成观察者
+    pub fn set_client_not_ready(conn_id: u64) {
+        let player_net_id = match NetworkServerStatic::network_connections().try_get_mut(&conn_id) {
+            TryResult::Present(mut connection) => {
+                if !connection.is_ready() {
+                    return;
+                }
+                connection.set_ready(false);
+                connection.pending_spawns.clear();
+                connection.spawn_streaming = false;
+                connection.remove_from_observings_observers();
+                connection.send_network_message(
+                    &mut NotReadyMessage,
+                    TransportChannel::Reliable,
+                );
+                connection.net_id()
+            }
+            TryResult::Absent => {
+                log_error!(
+                    "Server.SetClientNotReady: connectionId {} not found in connections",
+                    conn_id
+                );
+                return;
+            }
+            TryResult::Locked => {
+                log_error!(
+                    "Server.SetClientNotReady: connectionId {} is locked",
+                    conn_id
+                );
+                return;
+            }
+        };
+        // remove_from_observings_observers 跳过了自己的玩家对象
+        if player_net_id != 0 {
+            if let TryResult::Present(mut identity) =
+                NetworkServerStatic::spawned_network_identities().try_get_mut(&player_net_id)
+            {
+                identity.remove_observer(conn_id);
+            }
+        }
     }
     // 为连接生成观察者
     fn spawn_observers_for_connection(conn_id: u64) {
+        let mut observe_all = false;
         // 发送 ObjectSpawnStartedMessage 消息
         match NetworkServerStatic::network_connections().try_get_mut(&conn_id) {
             TryResult::Present(mut connection) => {
                 if !connection.is_ready() {
                     return;
                 }
+                observe_all = connection.observe_all;
                 connection.send_network_message(
                     &mut ObjectSpawnStartedMessage::default(),
                     TransportChannel::Reliable,
                 );
             }
             TryResult::Absent => {
-                log_error!(format!(
+                log_error!(
                     "Server.SpawnObserversForConnection: connectionId {} not found in connections",
                     conn_id
-                ));
+                );
             }
             TryResult::Locked => {
-                log_error!(format!(
+                log_error!(
                     "Server.SpawnObserversForConnection: connectionId {} is locked",
                     conn_id
-                ));
+                );
             }
         }
 
+        // 对象太多时分帧发送, 全部发送后再发送 ObjectSpawnFinishedMessage
+        if NetworkServerStatic::spawn_stream_budget() > 0 {
+            Self::queue_spawns_for_connection(conn_id, observe_all);
+            return;
+        }
+
         // add connection to each nearby NetworkIdentity's observers, which
         // internally sends a spawn message for each one to the connection.
         NetworkServerStatic::for_each_spawned(|mut identity| {
-            if identity.visibility == ForceShown {
-                identity.add_observer(conn_id);
-            } else if identity.visibility == Visibility::ForceHidden {
-                // do nothing
-            } else if identity.visibility == Visibility::Default {
-                // TODO aoi system
+            if Self::is_visible_to(&identity, conn_id, observe_all) {
                 identity.add_observer(conn_id);
             }
         });
@@ -1705,16 +3515,132 @@ impl NetworkServer {
                 );
             }
             TryResult::Absent => {
-                log_error!(format!(
+                log_error!(
                     "Server.SpawnObserversForConnection: connectionId {} not found in connections",
                     conn_id
-                ));
+                );
             }
             TryResult::Locked => {
-                log_error!(format!(
+                log_error!(
                     "Server.SpawnObserversForConnection: connectionId {} is locked",
                     conn_id
-                ));
+                );
+            }
+        }
+    }
+
+    // 按优先级排队初始生成: 玩家, 拥有的对象, 然后按离玩家的距离
+    fn queue_spawns_for_connection(conn_id: u64, observe_all: bool) {
+        let player_net_id = match NetworkServerStatic::network_connections().try_get(&conn_id) {
+            TryResult::Present(connection) => connection.net_id(),
+            _ => 0,
+        };
+        let player_position =
+            match NetworkServerStatic::spawned_network_identities().try_get(&player_net_id) {
+                TryResult::Present(identity) => Some(identity.game_object().transform.position),
+                _ => None,
+            };
+
+        let mut pending = Vec::new();
+        NetworkServerStatic::for_each_spawned(|identity| {
+            if !Self::is_visible_to(&identity, conn_id, observe_all) {
+                return;
+            }
+            let rank = if identity.net_id() == player_net_id {
+                0
+            } else if identity.connection_to_client() == conn_id {
+                1
+            } else {
+                2
+            };
+            let distance = match player_position {
+                Some(position) => {
+                    (identity.game_object().transform.position - position).norm_squared()
+                }
+                None => 0.0,
+            };
+            pending.push((rank, distance, identity.net_id()));
+        });
+        pending.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.total_cmp(&b.1)).then(a.2.cmp(&b.2)));
+
+        match NetworkServerStatic::network_connections().try_get_mut(&conn_id) {
+            TryResult::Present(mut connection) => {
+                connection.pending_spawns =
+                    pending.into_iter().map(|(_, _, net_id)| net_id).collect();
+                connection.spawn_streaming = true;
+            }
+            TryResult::Absent => {
+                log_error!(
+                    "Server.QueueSpawnsForConnection: connectionId {} not found in connections",
+                    conn_id
+                );
+            }
+            TryResult::Locked => {
+                log_error!(
+                    "Server.QueueSpawnsForConnection: connectionId {} is locked",
+                    conn_id
+                );
+            }
+        }
+    }
+
+    // 每个 tick 在字节预算内发送排队的初始生成, 至少发送一个
+    fn stream_pending_spawns() {
+        let budget = NetworkServerStatic::spawn_stream_budget();
+        let conn_ids: Vec<u64> = NetworkServerStatic::network_connections()
+            .iter()
+            .filter(|connection| connection.spawn_streaming)
+            .map(|connection| connection.connection_id())
+            .collect();
+
+        for conn_id in conn_ids {
+            let mut first = true;
+            loop {
+                let (net_id, observe_all) =
+                    match NetworkServerStatic::network_connections().try_get_mut(&conn_id) {
+                        TryResult::Present(mut connection) => {
+                            if first {
+                                connection.spawn_bytes_sent = 0;
+                                first = false;
+                            } else if connection.spawn_bytes_sent >= budget {
+                                break;
+                            }
+                            // 客户端不再准备好
+                            if !connection.is_ready() {
+                                connection.pending_spawns.clear();
+                                connection.spawn_streaming = false;
+                                break;
+                            }
+                            match connection.pending_spawns.pop_front() {
+                                Some(net_id) => (net_id, connection.observe_all),
+                                None => {
+                                    connection.spawn_streaming = false;
+                                    connection.send_network_message(
+                                        &mut ObjectSpawnFinishedMessage,
+                                        TransportChannel::Reliable,
+                                    );
+                                    break;
+                                }
+                            }
+                        }
+                        TryResult::Absent => break,
+                        TryResult::Locked => {
+                            log_error!(
+                                "Server.StreamPendingSpawns: connectionId {} is locked",
+                                conn_id
+                            );
+                            break;
+                        }
+                    };
+
+                // 排队期间对象可能已经被销毁或隐藏
+                if let TryResult::Present(mut identity) =
+                    NetworkServerStatic::spawned_network_identities().try_get_mut(&net_id)
+                {
+                    if Self::is_visible_to(&identity, conn_id, observe_all) {
+                        identity.add_observer(conn_id);
+                    }
+                }
             }
         }
     }
@@ -1726,9 +3652,12 @@ impl NetworkServer {
         channel: TransportChannel,
     ) {
         let message = CommandMessage::deserialize(reader);
+        AntiCheat::on_command(connection_id);
 
         // 如果 connection_id 在 NETWORK_CONNECTIONS 中
-        match NetworkServerStatic::network_connections().try_get_mut(&connection_id) {
+        let remote_time_stamp = match NetworkServerStatic::network_connections()
+            .try_get_mut(&connection_id)
+        {
             TryResult::Present(connection) => {
                 // connection 没有准备好
                 if !connection.is_ready() {
@@ -1747,23 +3676,23 @@ impl NetworkServer {
                                             message.function_hash,
                                         )
                                     {
-                                        log_warn!(format!("Command {} received for {} [netId={}] component  [index={}] when client not ready.\nThis may be ignored if client intentionally set NotReady.", method_name, identity.net_id(), message.net_id, message.component_index));
+                                        log_warn!("Command {} received for {} [netId={}] component  [index={}] when client not ready.\nThis may be ignored if client intentionally set NotReady.", method_name, identity.net_id(), message.net_id, message.component_index);
                                         return;
                                     }
                                 }
                             }
                             TryResult::Absent => {
-                                log_error!(format!(
+                                log_error!(
                                     "Server.HandleCommand: connectionId {} not found in connections",
                                     connection_id
-                                ));
+                                );
                                 return;
                             }
                             TryResult::Locked => {
-                                log_error!(format!(
+                                log_error!(
                                     "Server.HandleCommand: connectionId {} is locked",
                                     connection_id
-                                ));
+                                );
                                 return;
                             }
                         }
@@ -1771,22 +3700,23 @@ impl NetworkServer {
                     }
                     return;
                 }
+                connection.remote_time_stamp()
             }
             TryResult::Absent => {
-                log_error!(format!(
+                log_error!(
                     "Server.HandleCommand: connectionId {} not found in connections",
                     connection_id
-                ));
+                );
                 return;
             }
             TryResult::Locked => {
-                log_error!(format!(
+                log_error!(
                     "Server.HandleCommand: connectionId {} is locked",
                     connection_id
-                ));
+                );
                 return;
             }
-        }
+        };
 
         // 如果 message.net_id 在 SPAWNED 中
         match NetworkServerStatic::spawned_network_identities().try_get(&message.net_id) {
@@ -1794,24 +3724,52 @@ impl NetworkServer {
                 // 是否需要权限
                 let requires_authority =
                     RemoteProcedureCalls::command_requires_authority(message.function_hash);
-                // 如果需要权限并且 identity.connection_id_to_client != connection.connection_id
-                if requires_authority && identity.connection_to_client() != connection_id {
+                // 如果需要权限, 按组件的权限覆盖检查 connection_id 是否有权限
+                if requires_authority
+                    && !ComponentAuthority::has_authority(
+                        message.net_id,
+                        message.component_index,
+                        identity.connection_to_client(),
+                        connection_id,
+                    )
+                {
                     // Attempt to identify the component and method to narrow down the cause of the log_error.
                     if identity.network_behaviours_count > message.component_index {
                         if let Some(method_name) =
                             RemoteProcedureCalls::get_function_method_name(message.function_hash)
                         {
-                            log_warn!(format!("Command {} received for {} [netId={}] component [index={}] without authority", method_name, identity.net_id(), message.net_id,  message.component_index));
+                            log_warn!("Command {} received for {} [netId={}] component [index={}] without authority", method_name, identity.net_id(), message.net_id,  message.component_index);
                             return;
                         }
                     }
-                    log_warn!(format!(
+                    log_warn!(
                         "Command received for {} [netId={}] without authority",
                         identity.net_id(),
                         message.net_id
-                    ));
+                    );
                     return;
                 }
+                // 状态类 Command 按组件的同步方向检查
+                if RemoteProcedureCalls::command_writes_state(message.function_hash) {
+                    let is_owner = identity.connection_to_client() == connection_id;
+                    let accepted = match NETWORK_BEHAVIOURS
+                        .try_get_mut(&(message.net_id, message.component_index))
+                    {
+                        TryResult::Present(mut component) => {
+                            component.sync_direction().accepts_client_write(is_owner)
+                        }
+                        _ => false,
+                    };
+                    if !accepted {
+                        log_warn!(
+                            "Command received for {} [netId={}] component [index={}] against its sync direction",
+                            identity.net_id(),
+                            message.net_id,
+                            message.component_index
+                        );
+                        return;
+                    }
+                }
             }
             TryResult::Absent => {
                 // over reliable channel, commands should always come after spawn.
@@ -1819,22 +3777,32 @@ impl NetworkServer {
                 // for example, NetworkTransform.
                 // let's not spam the console for unreliable out-of-order messages.
                 if channel == TransportChannel::Reliable {
-                    log_warn!(format!(
+                    log_warn!(
                         "Spawned object not found when handling Command message netId={}",
                         message.net_id
-                    ));
+                    );
                 }
                 return;
             }
             TryResult::Locked => {
-                log_error!(format!(
-                    "Server.HandleCommand: netId {} is locked",
-                    message.net_id
-                ));
+                log_error!("Server.HandleCommand: netId {} is locked", message.net_id);
                 return;
             }
         }
 
+        // 乱序到达的旧状态直接丢弃
+        if channel == TransportChannel::Unreliable
+            && RemoteProcedureCalls::command_latest_only(message.function_hash)
+            && !UnreliableSequencing::accept(
+                connection_id,
+                message.net_id,
+                message.component_index,
+                remote_time_stamp,
+            )
+        {
+            return;
+        }
+
         // 处理远程调用
         NetworkReaderPool::get_with_bytes_return(message.payload, |reader| {
             NetworkIdentity::handle_remote_call(
@@ -1848,20 +3816,40 @@ impl NetworkServer {
         });
     }
 
+    // 当前消息所在批次的客户端发送时间
+    fn remote_time_stamp(connection_id: u64) -> f64 {
+        match NetworkServerStatic::network_connections().try_get(&connection_id) {
+            TryResult::Present(connection) => connection.remote_time_stamp(),
+            _ => 0.0,
+        }
+    }
+
     // 处理 OnEntityStateMessage 消息
     fn on_entity_state_message(
         connection_id: u64,
         reader: &mut NetworkReader,
-        _channel: TransportChannel,
+        channel: TransportChannel,
     ) {
         let message = EntityStateMessage::deserialize(reader);
         match NetworkServerStatic::spawned_network_identities().try_get_mut(&message.net_id) {
             TryResult::Present(mut identity) => {
                 if identity.connection_to_client() == connection_id {
+                    // 乱序到达的旧状态直接丢弃
+                    if channel == TransportChannel::Unreliable
+                        && !UnreliableSequencing::accept(
+                            connection_id,
+                            message.net_id,
+                            UnreliableSequencing::ENTITY_COMPONENT,
+                            Self::remote_time_stamp(connection_id),
+                        )
+                    {
+                        return;
+                    }
                     NetworkReaderPool::get_with_bytes_return(message.payload, |reader| {
-                        if !identity.deserialize_server(reader) {
+                        if !identity.deserialize_server(reader, connection_id) {
+                            AntiCheat::report(connection_id, CheatSignal::MalformedPacket);
                             if NetworkServerStatic::exceptions_disconnect() {
-                                log_error!(format!("Server failed to deserialize client state for {} with netId={}, Disconnecting.", identity.connection_to_client(), identity.net_id()));
+                                log_error!("Server failed to deserialize client state for {} with netId={}, Disconnecting.", identity.connection_to_client(), identity.net_id());
                                 match NetworkServerStatic::network_connections()
                                     .try_get_mut(&connection_id)
                                 {
@@ -1869,50 +3857,68 @@ impl NetworkServer {
                                         connection.disconnect();
                                     }
                                     TryResult::Absent => {
-                                        log_error!(format!(
+                                        log_error!(
                                             "Server.HandleEntityState: connectionId {} not found.",
                                             connection_id
-                                        ));
+                                        );
                                     }
                                     TryResult::Locked => {
-                                        log_error!(format!(
+                                        log_error!(
                                             "Server.HandleEntityState: connectionId {} is locked.",
                                             connection_id
-                                        ));
+                                        );
                                     }
                                 }
                             } else {
-                                log_warn!(format!(
+                                log_warn!(
                                 "Server failed to deserialize client state for {} with netId={}",
                                 identity.connection_to_client(),
                                 identity.net_id()
-                            ));
+                            );
                             }
                         }
                     });
                 } else {
-                    log_warn!(format!(
+                    log_warn!(
                         "EntityStateMessage from {} for {} without authority.",
                         connection_id,
                         identity.net_id()
-                    ));
+                    );
                 }
             }
             TryResult::Absent => {
-                log_warn!(format!(
+                log_warn!(
                     "EntityStateMessage for netId={} not found in spawned.",
                     message.net_id
-                ));
+                );
             }
             TryResult::Locked => {
-                log_error!(format!(
+                log_error!(
                     "Server.HandleEntityState: netId {} is locked",
                     message.net_id
-                ));
+                );
             }
         }
     }
 
+    // 处理 TickSnapshotMessage 消息, 除了记录客户端的 tick 之外与 TimeSnapshotMessage 相同
+    fn on_tick_snapshot_message(
+        connection_id: u64,
+        reader: &mut NetworkReader,
+        channel: TransportChannel,
+    ) {
+        let message = TickSnapshotMessage::deserialize(reader);
+        if let TryResult::Present(mut connection) =
+            NetworkServerStatic::network_connections().try_get_mut(&connection_id)
+        {
+            // Unreliable 通道可能乱序
+            if message.tick > connection.remote_tick {
+                connection.remote_tick = message.tick;
+            }
+        }
+        Self::on_time_snapshot_message(connection_id, reader, channel);
+    }
+
     // 处理 OnTimeSnapshotMessage 消息
     fn on_time_snapshot_message(
         connection_id: u64,
@@ -1921,21 +3927,22 @@ impl NetworkServer {
     ) {
         match NetworkServerStatic::network_connections().try_get_mut(&connection_id) {
             TryResult::Present(mut connection) => {
-                let snapshot =
-                    TimeSnapshot::new(connection.remote_time_stamp(), NetworkTime::local_time());
+                let remote_time_stamp = connection.remote_time_stamp();
+                let snapshot = TimeSnapshot::new(remote_time_stamp, NetworkTime::local_time());
                 connection.on_time_snapshot(snapshot);
+                AntiCheat::on_time_snapshot(connection_id, remote_time_stamp);
             }
             TryResult::Absent => {
-                log_error!(format!(
+                log_error!(
                     "Server.HandleTimeSnapshot: connectionId {} not found.",
                     connection_id
-                ));
+                );
             }
             TryResult::Locked => {
-                log_error!(format!(
+                log_error!(
                     "Server.HandleTimeSnapshot: connectionId {} is locked.",
                     connection_id
-                ));
+                );
             }
         }
     }
@@ -1948,9 +3955,15 @@ impl NetworkServer {
         T: NetworkMessageTrait + Send + Sync + 'static,
     {
         let hash_code = T::get_hash_code();
+        StableHashRegistry::register(
+            StableHashDomain::Message,
+            StableHashKind::Message,
+            T::get_full_name(),
+            hash_code,
+        );
 
         if NETWORK_MESSAGE_HANDLERS.contains_key(&hash_code) {
-            log_warn!(format!("NetworkServer.RegisterHandler replacing handler for id={}. If replacement is intentional, use ReplaceHandler instead to avoid this log_warning.", hash_code));
+            log_warn!("NetworkServer.RegisterHandler replacing handler for id={}. If replacement is intentional, use ReplaceHandler instead to avoid this log_warning.", hash_code);
             return;
         }
         NETWORK_MESSAGE_HANDLERS.insert(
@@ -1966,6 +3979,12 @@ impl NetworkServer {
         T: NetworkMessageTrait + Send + Sync + 'static,
     {
         let hash_code = T::get_hash_code();
+        StableHashRegistry::register(
+            StableHashDomain::Message,
+            StableHashKind::Message,
+            T::get_full_name(),
+            hash_code,
+        );
         NETWORK_MESSAGE_HANDLERS.insert(
             hash_code,
             NetworkMessageHandler::wrap_handler(network_message_handler, require_authentication),
@@ -1977,5 +3996,1128 @@ impl NetworkServer {
     {
         let hash_code = T::get_hash_code();
         NETWORK_MESSAGE_HANDLERS.remove(&hash_code);
+        StableHashRegistry::unregister(StableHashDomain::Message, hash_code);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::components::network_transform::network_transform_base::Transform;
+    use crate::mirror::core::messages::InterpolationHintMessage;
+    use crate::mirror::core::net_id_allocator::NetIdAllocator;
+    use crate::mirror::core::network_behaviour::SyncMode;
+    use crate::mirror::core::network_manager::PlayerSpawnMethod;
+    use crate::mirror::core::network_start_position::NetworkStartPosition;
+    use crate::mirror::core::network_writer::NetworkWriter;
+    use crate::mirror::core::transport::TransportTrait;
+    use crate::mirror::transports::memory::memory_transport::test_util::*;
+    use crate::mirror::transports::memory::memory_transport::MemoryTransport;
+
+    #[test]
+    fn test_unknown_message() {
+        with_server(|| {
+            const UNKNOWN: u16 = 0xFFFE;
+            NetworkServerStatic::set_unknown_message_reply(true);
+            MemoryTransport::client_connect(1);
+            tick();
+            send_raw(1, &UNKNOWN.to_le_bytes());
+            send_raw(1, &UNKNOWN.to_le_bytes());
+            tick();
+            assert_eq!(NetworkServerStatic::unknown_message_count(UNKNOWN), 2);
+            let errors = received::<ErrorMessage>(1);
+            assert_eq!(errors.len(), 2);
+            assert_eq!(errors[0].message_id, UNKNOWN);
+
+            // 自定义处理后不再回复
+            fn handler(
+                _: u64,
+                message_id: u16,
+                reader: &mut NetworkReader,
+                _: TransportChannel,
+            ) -> bool {
+                message_id == UNKNOWN && reader.read_byte() == 7
+            }
+            NetworkServerStatic::set_unknown_message_handler(Some(handler));
+            let [low, high] = UNKNOWN.to_le_bytes();
+            send_raw(1, &[low, high, 7]);
+            tick();
+            assert_eq!(NetworkServerStatic::unknown_message_count(UNKNOWN), 3);
+            assert!(received::<ErrorMessage>(1).is_empty());
+
+            NetworkServerStatic::set_unknown_message_reply(false);
+            NetworkServerStatic::set_unknown_message_handler(None);
+        });
+    }
+
+    #[test]
+    fn test_disconnect_with_reason() {
+        with_server(|| {
+            MemoryTransport::client_connect(1);
+            tick();
+            MemoryTransport::client_receive(1);
+            NetworkServer::disconnect_with_reason(1, DisconnectReason::Kick);
+            tick();
+            assert!(!MemoryTransport::client_connected(1));
+            let messages = received::<DisconnectMessage>(1);
+            assert_eq!(messages.len(), 1);
+            assert_eq!(
+                DisconnectReason::from(messages[0].reason),
+                DisconnectReason::Kick
+            );
+            tick();
+            assert!(!NetworkServerStatic::network_connections().contains_key(&1));
+
+            // 服务器关闭
+            MemoryTransport::client_connect(2);
+            tick();
+            MemoryTransport::client_receive(2);
+            NetworkServer::shutdown();
+            let messages = received::<DisconnectMessage>(2);
+            assert_eq!(messages.len(), 1);
+            assert_eq!(
+                DisconnectReason::from(messages[0].reason),
+                DisconnectReason::ServerShutdown
+            );
+            NetworkServer::listen(16);
+        });
+    }
+
+    #[test]
+    fn test_visibility_override() {
+        with_server(|| {
+            MemoryTransport::client_connect(1);
+            MemoryTransport::client_connect(2);
+            tick();
+            NetworkServer::set_client_ready(1);
+            NetworkServer::set_client_ready(2);
+
+            let mut identity = NetworkIdentity::new_with_asset_id(0);
+            identity.set_net_id(100);
+            identity.set_connection_to_client(1);
+            NetworkServerStatic::add_spawned_network_identity(identity);
+            let observers = || {
+                let mut observers = NetworkServerStatic::spawned_network_identities()
+                    .get(&100)
+                    .unwrap()
+                    .observers()
+                    .clone();
+                observers.sort();
+                observers
+            };
+
+            NetworkServer::set_visibility(100, Visibility::Default);
+            assert_eq!(observers(), vec![1, 2]);
+            tick();
+            MemoryTransport::client_receive(2);
+
+            // ForceHidden 只对所有者可见
+            NetworkServer::set_visibility(100, Visibility::ForceHidden);
+            assert_eq!(observers(), vec![1]);
+            tick();
+            assert_eq!(received::<ObjectHideMessage>(2).len(), 1);
+
+            // 管理员可以观察所有对象
+            NetworkServer::set_observe_all(2, true);
+            assert_eq!(observers(), vec![1, 2]);
+            NetworkServer::set_observe_all(2, false);
+            assert_eq!(observers(), vec![1]);
+
+            // 连接被占用时不移除观察者, observers 和 observing 保持一致
+            NetworkServer::set_observe_all(2, true);
+            assert_eq!(observers(), vec![1, 2]);
+            NetworkServerStatic::network_connections()
+                .get_mut(&2)
+                .unwrap()
+                .observe_all = false;
+            let observing = || {
+                NetworkServerStatic::network_connections()
+                    .get(&2)
+                    .unwrap()
+                    .observing
+                    .contains(&100)
+            };
+            assert!(observing());
+            {
+                let _connection = NetworkServerStatic::network_connections().get_mut(&2).unwrap();
+                let mut identity = NetworkServerStatic::spawned_network_identities()
+                    .get_mut(&100)
+                    .unwrap();
+                NetworkServer::rebuild_observers(&mut identity, false);
+            }
+            assert_eq!(observers(), vec![1, 2]);
+            assert!(observing());
+            {
+                let _connection = NetworkServerStatic::network_connections().get(&2).unwrap();
+                let mut identity = NetworkServerStatic::spawned_network_identities()
+                    .get_mut(&100)
+                    .unwrap();
+                NetworkServer::rebuild_observers(&mut identity, false);
+            }
+            assert_eq!(observers(), vec![1, 2]);
+            assert!(observing());
+
+            // 连接释放后正常移除
+            NetworkServer::set_visibility(100, Visibility::ForceHidden);
+            assert_eq!(observers(), vec![1]);
+            assert!(!observing());
+            NetworkServerStatic::remove_spawned_network_identity(&100);
+        });
+    }
+
+    #[test]
+    fn test_replace_player_for_connection_with_net_id() {
+        with_server(|| {
+            MemoryTransport::client_connect(1);
+            tick();
+            NetworkServer::set_client_ready(1);
+            for net_id in [100, 101] {
+                let mut identity = NetworkIdentity::new_with_asset_id(0);
+                identity.set_net_id(net_id);
+                identity.set_connection_to_client(1);
+                NetworkServerStatic::add_spawned_network_identity(identity);
+                NetworkServer::set_visibility(net_id, Visibility::Default);
+            }
+            NetworkServerStatic::network_connections()
+                .get_mut(&1)
+                .unwrap()
+                .set_net_id(100);
+            tick();
+            MemoryTransport::client_receive(1);
+
+            // 旧玩家保留所有权
+            assert!(NetworkServer::replace_player_for_connection_with_net_id(1, 101, true));
+            assert_eq!(
+                NetworkServerStatic::network_connections()
+                    .get(&1)
+                    .unwrap()
+                    .net_id(),
+                101
+            );
+            tick();
+            let mut messages = received::<ChangeOwnerMessage>(1);
+            messages.sort_by_key(|message| message.net_id);
+            assert_eq!(messages.len(), 2);
+            assert!(messages[0].is_owner && !messages[0].is_local_player);
+            assert!(messages[1].is_owner && messages[1].is_local_player);
+
+            // 旧玩家失去所有权
+            assert!(NetworkServer::replace_player_for_connection_with_net_id(1, 100, false));
+            assert_eq!(
+                NetworkServerStatic::spawned_network_identities()
+                    .get(&101)
+                    .unwrap()
+                    .connection_to_client(),
+                0
+            );
+            assert!(!NetworkServerStatic::network_connections()
+                .get_mut(&1)
+                .unwrap()
+                .owned()
+                .contains(&101));
+            for net_id in [100, 101] {
+                NetworkServerStatic::remove_spawned_network_identity(&net_id);
+            }
+        });
+    }
+
+    #[test]
+    fn test_pause_and_resume() {
+        with_server(|| {
+            MemoryTransport::client_connect(1);
+            tick();
+            NetworkServer::set_client_ready(1);
+            MemoryTransport::client_receive(1);
+
+            NetworkServer::pause();
+            NetworkServer::pause();
+            assert!(NetworkServerStatic::paused());
+            tick();
+            let messages = received::<PauseMessage>(1);
+            assert_eq!(messages, vec![PauseMessage::new(true)]);
+
+            // 暂停时仍然回复 ping
+            let mut ping = NetworkPingMessage::new(0.5, 0.5);
+            MemoryTransport::client_send_message(1, &mut ping, TransportChannel::Reliable);
+            tick();
+            assert_eq!(received::<NetworkPongMessage>(1).len(), 1);
+
+            NetworkServer::resume();
+            assert!(!NetworkServerStatic::paused());
+            tick();
+            assert_eq!(received::<PauseMessage>(1), vec![PauseMessage::new(false)]);
+        });
+    }
+
+    #[test]
+    fn test_spawn_streaming() {
+        with_server(|| {
+            NetworkServerStatic::set_spawn_stream_budget(1);
+            MemoryTransport::client_connect(1);
+            tick();
+            // 100 是玩家, 102 属于连接, 103 比 101 离玩家近
+            for (net_id, owner, x) in [(100, 1, 0.0), (101, 0, 10.0), (102, 1, 20.0), (103, 0, 5.0)]
+            {
+                let mut identity = NetworkIdentity::new_with_asset_id(0);
+                identity.set_net_id(net_id);
+                identity.set_connection_to_client(owner);
+                let mut game_object = identity.game_object().clone();
+                game_object.transform.position = Vector3::new(x, 0.0, 0.0);
+                identity.set_game_object(game_object);
+                NetworkServerStatic::add_spawned_network_identity(identity);
+            }
+            NetworkServerStatic::network_connections()
+                .get_mut(&1)
+                .unwrap()
+                .set_net_id(100);
+            NetworkServer::set_client_ready(1);
+
+            // 每个 tick 只发送一个
+            let mut spawned = Vec::new();
+            for _ in 0..4 {
+                tick();
+                let messages = MemoryTransport::client_receive_messages(1);
+                let spawns: Vec<u32> = messages
+                    .iter()
+                    .filter_map(|(message, _)| {
+                        let mut reader = NetworkReader::new_with_bytes(message.clone());
+                        if reader.read_ushort() == SpawnMessage::get_hash_code() {
+                            Some(SpawnMessage::deserialize(&mut reader).net_id)
+                        } else {
+                            None
+                        }
+                    })
+                    .collect();
+                assert_eq!(spawns.len(), 1);
+                spawned.extend(spawns);
+                let finished = messages.iter().any(|(message, _)| {
+                    NetworkReader::new_with_bytes(message.clone()).read_ushort()
+                        == ObjectSpawnFinishedMessage::get_hash_code()
+                });
+                assert!(!finished);
+            }
+            assert_eq!(spawned, vec![100, 102, 103, 101]);
+
+            tick();
+            assert_eq!(received::<ObjectSpawnFinishedMessage>(1).len(), 1);
+            assert!(
+                !NetworkServerStatic::network_connections()
+                    .get(&1)
+                    .unwrap()
+                    .spawn_streaming
+            );
+
+            NetworkServerStatic::set_spawn_stream_budget(0);
+            for net_id in [100, 101, 102, 103] {
+                NetworkServerStatic::remove_spawned_network_identity(&net_id);
+            }
+        });
+    }
+
+    #[test]
+    fn test_batch_spawn() {
+        with_server(|| {
+            NetworkServerStatic::set_protocol_handshake(true);
+            MemoryTransport::client_connect(1);
+            MemoryTransport::client_connect(2);
+            tick();
+            let mut version = ProtocolVersionMessage::new(
+                NetworkServer::PROTOCOL_VERSION,
+                NetworkServer::FEATURE_BATCH_SPAWN,
+                60,
+            );
+            MemoryTransport::client_send_message(1, &mut version, TransportChannel::Reliable);
+            let mut version = ProtocolVersionMessage::new(NetworkServer::PROTOCOL_VERSION, 0, 60);
+            MemoryTransport::client_send_message(2, &mut version, TransportChannel::Reliable);
+            tick();
+            NetworkServer::set_client_ready(1);
+            NetworkServer::set_client_ready(2);
+            tick();
+            MemoryTransport::client_receive(1);
+            MemoryTransport::client_receive(2);
+
+            let identities = (0..3)
+                .map(|_| NetworkIdentity::new_with_asset_id(7))
+                .collect();
+            let net_ids = NetworkServer::batch_spawn(identities);
+            assert_eq!(net_ids.len(), 3);
+            for net_id in net_ids.iter() {
+                let mut observers = NetworkServerStatic::spawned_network_identities()
+                    .get(net_id)
+                    .unwrap()
+                    .observers()
+                    .clone();
+                observers.sort();
+                assert_eq!(observers, vec![1, 2]);
+            }
+            tick();
+
+            // 支持的客户端收到一条 BatchSpawnMessage
+            let messages = MemoryTransport::client_receive_messages(1);
+            let batches: Vec<BatchSpawnMessage> = messages
+                .iter()
+                .filter_map(|(message, _)| {
+                    let mut reader = NetworkReader::new_with_bytes(message.clone());
+                    if reader.read_ushort() == BatchSpawnMessage::get_hash_code() {
+                        Some(BatchSpawnMessage::deserialize(&mut reader))
+                    } else {
+                        None
+                    }
+                })
+                .collect();
+            assert_eq!(batches.len(), 1);
+            assert_eq!(batches[0].asset_id, 7);
+            let batch_net_ids: Vec<u32> = batches[0].entries.iter().map(|e| e.net_id).collect();
+            assert_eq!(batch_net_ids, net_ids);
+            assert!(!messages.iter().any(|(message, _)| {
+                NetworkReader::new_with_bytes(message.clone()).read_ushort()
+                    == SpawnMessage::get_hash_code()
+            }));
+
+            // 其他客户端收到单独的 SpawnMessage
+            assert_eq!(received::<SpawnMessage>(2).len(), 3);
+
+            NetworkServerStatic::set_protocol_handshake(false);
+        });
+    }
+
+    #[test]
+    fn test_connection_queue() {
+        with_server(|| {
+            NetworkServerStatic::set_max_connections(1);
+            NetworkServerStatic::set_connection_queue_enabled(true);
+            NetworkServerStatic::set_connection_queue_position_interval(0.0);
+            MemoryTransport::client_connect(1);
+            MemoryTransport::client_connect(2);
+            MemoryTransport::client_connect(3);
+            tick();
+            assert!(NetworkServerStatic::network_connections().contains_key(&1));
+            assert!(!NetworkServerStatic::network_connections().contains_key(&2));
+            assert!(NetworkServerStatic::is_connection_queued(3));
+            let positions = received::<QueuePositionMessage>(3);
+            assert_eq!(positions.first(), Some(&QueuePositionMessage::new(2, 2)));
+
+            // 排队中的连接发来的消息被忽略
+            MemoryTransport::client_send_message(2, &mut ReadyMessage, TransportChannel::Reliable);
+            tick();
+            assert!(!NetworkServerStatic::network_connections().contains_key(&2));
+
+            // 有空位后按顺序放行
+            MemoryTransport::client_disconnect(1);
+            received::<QueuePositionMessage>(3);
+            tick();
+            assert!(NetworkServerStatic::network_connections().contains_key(&2));
+            assert!(!NetworkServerStatic::is_connection_queued(2));
+            assert_eq!(
+                received::<QueuePositionMessage>(3).last(),
+                Some(&QueuePositionMessage::new(1, 1))
+            );
+
+            // 关闭队列后直接拒绝
+            NetworkServerStatic::set_connection_queue_enabled(false);
+            MemoryTransport::client_connect(4);
+            tick();
+            assert!(!NetworkServerStatic::is_connection_queued(4));
+            assert!(!NetworkServerStatic::network_connections().contains_key(&4));
+
+            NetworkServerStatic::set_connection_queue_position_interval(1.0);
+        });
+    }
+
+    #[test]
+    fn test_export_and_import_player_state() {
+        with_server(|| {
+            MemoryTransport::client_connect(1);
+            MemoryTransport::client_connect(2);
+            tick();
+            NetworkServer::set_client_ready(1);
+            NetworkServer::set_client_ready(2);
+            for (net_id, asset_id) in [(100, 7), (101, 8)] {
+                let mut identity = NetworkIdentity::new_with_asset_id(asset_id);
+                identity.set_net_id(net_id);
+                identity.set_connection_to_client(1);
+                let mut game_object = identity.game_object().clone();
+                game_object.transform.local_position = Vector3::new(net_id as f32, 0.0, 0.0);
+                identity.set_game_object(game_object);
+                NetworkServerStatic::add_spawned_network_identity(identity);
+            }
+            NetworkServerStatic::network_connections()
+                .get_mut(&1)
+                .unwrap()
+                .set_net_id(100);
+
+            let blob = NetworkServer::export_player_state(1).unwrap();
+            assert_eq!(NetworkServer::import_player_state(2, &blob[1..]), None);
+            tick();
+            MemoryTransport::client_receive(2);
+
+            // 玩家在另一个实例上重新生成, net_id 重新分配
+            let player_net_id = NetworkServer::import_player_state(2, &blob).unwrap();
+            {
+                let connection = NetworkServerStatic::network_connections().get(&2).unwrap();
+                assert_eq!(connection.net_id(), player_net_id);
+                assert!(connection.is_authenticated());
+            }
+            let mut owned = Vec::new();
+            NetworkServerStatic::for_each_spawned(|identity| {
+                if identity.connection_to_client() == 2 {
+                    owned.push((
+                        identity.net_id() == player_net_id,
+                        identity.asset_id,
+                        identity.game_object().transform.local_position.x,
+                    ));
+                }
+            });
+            owned.sort_by_key(|(_, asset_id, _)| *asset_id);
+            assert_eq!(owned, vec![(true, 7, 100.0), (false, 8, 101.0)]);
+
+            // 先生成的对象随后通过 ChangeOwnerMessage 改为非本地玩家
+            tick();
+            let changes = received::<ChangeOwnerMessage>(2);
+            assert_eq!(changes.len(), 1);
+            assert!(changes[0].net_id != player_net_id && !changes[0].is_local_player);
+            for net_id in [100, 101, player_net_id, changes[0].net_id] {
+                NetworkServerStatic::remove_spawned_network_identity(&net_id);
+            }
+            NetIdAllocator::reset();
+        });
+    }
+
+    #[test]
+    fn test_state_tick() {
+        with_server(|| {
+            MemoryTransport::client_connect(1);
+            tick();
+            NetworkServer::set_client_ready(1);
+            NetworkServerStatic::network_connections()
+                .get_mut(&1)
+                .unwrap()
+                .features = NetworkServer::FEATURE_STATE_TICK;
+
+            spawn_test_identity(600, 0, 1);
+            NetworkServer::set_visibility(600, Visibility::Default);
+            tick();
+            MemoryTransport::client_receive(1);
+
+            // 状态广播带上当前的服务器 tick
+            NetworkTime::increment_frame_count();
+            {
+                let mut behaviour = NETWORK_BEHAVIOURS.get_mut(&(600, 0)).unwrap();
+                behaviour.set_sync_var_dirty_bits(1);
+                behaviour.set_last_sync_time(-1.0);
+            }
+            tick();
+            let messages = MemoryTransport::client_receive_messages(1);
+            assert_eq!(
+                decode::<TickSnapshotMessage>(&messages),
+                vec![TickSnapshotMessage::new(NetworkTime::tick())]
+            );
+            let states = decode::<TickedEntityStateMessage>(&messages);
+            assert_eq!(states.len(), 1);
+            assert_eq!(
+                (states[0].net_id, states[0].tick),
+                (600, NetworkTime::tick())
+            );
+            assert!(decode::<EntityStateMessage>(&messages).is_empty());
+
+            NetworkServerStatic::remove_spawned_network_identity(&600);
+        });
+    }
+
+    fn on_test_hit(_connection_id: u64, reader: &mut NetworkReader, _channel: TransportChannel) {
+        if TestHit::deserialize(reader).0 == 13 {
+            panic!("bad hit");
+        }
+    }
+
+    #[test]
+    fn test_handler_panic_isolation() {
+        with_server(|| {
+            NetworkServer::register_handler::<TestHit>(on_test_hit, false);
+            for conn_id in [1, 2] {
+                MemoryTransport::client_connect(conn_id);
+            }
+            tick();
+            let panics = NetworkServerStatic::handler_panic_count();
+
+            // TestHit 作为 RPC 参数时不写消息 id, 这里手动加上
+            let hit = |value: i32| {
+                let mut writer = NetworkWriter::new();
+                writer.write_ushort(TestHit::get_hash_code());
+                TestHit(value).serialize(&mut writer);
+                writer.to_bytes()
+            };
+            let mut ready = NetworkWriter::new();
+            ReadyMessage.serialize(&mut ready);
+
+            // 同一个 batch 中 panic 之后的 ReadyMessage 不再处理
+            let mut batcher = Batcher::new(MemoryTransport::MAX_PACKET_SIZE);
+            batcher.add_message(&hit(13), NetworkTime::local_time());
+            batcher.add_message(&ready.to_bytes(), NetworkTime::local_time());
+            NetworkWriterPool::get_return(|writer| {
+                while batcher.get_batcher_writer(writer) {
+                    MemoryTransport::client_send(1, writer.to_bytes(), TransportChannel::Reliable);
+                    writer.reset();
+                }
+            });
+            send_raw(2, &hit(1));
+            send_raw(2, &ready.to_bytes());
+            tick();
+            tick();
+
+            assert_eq!(NetworkServerStatic::handler_panic_count(), panics + 1);
+            assert!(!MemoryTransport::client_connected(1));
+            assert!(MemoryTransport::client_connected(2));
+            assert!(NetworkServerStatic::network_connections()
+                .get(&2)
+                .unwrap()
+                .is_ready());
+            NetworkServer::unregister_handler::<TestHit>();
+        });
+    }
+
+    fn on_test_lock_panic(
+        _connection_id: u64,
+        _reader: &mut NetworkReader,
+        _channel: TransportChannel,
+    ) {
+        let _queue = CONNECTION_QUEUE.write().unwrap();
+        panic!("panic while holding CONNECTION_QUEUE");
+    }
+
+    #[test]
+    fn test_poisoned_lock_recovery() {
+        with_server(|| {
+            NetworkServer::register_handler::<TestHit>(on_test_lock_panic, false);
+            NetworkServerStatic::set_max_connections(2);
+            NetworkServerStatic::set_connection_queue_enabled(true);
+            NetworkServerStatic::set_connection_queue_position_interval(0.0);
+            MemoryTransport::client_connect(1);
+            MemoryTransport::client_connect(2);
+            tick();
+            let mut writer = NetworkWriter::new();
+            writer.write_ushort(TestHit::get_hash_code());
+            TestHit(0).serialize(&mut writer);
+            send_raw(2, &writer.to_bytes());
+            tick();
+            assert!(CONNECTION_QUEUE.is_poisoned());
+            assert!(!MemoryTransport::client_connected(2));
+
+            // 锁被毒化后队列仍然可用
+            MemoryTransport::client_connect(3);
+            MemoryTransport::client_connect(4);
+            tick();
+            assert!(NetworkServerStatic::network_connections().contains_key(&3));
+            assert!(NetworkServerStatic::is_connection_queued(4));
+            assert_eq!(
+                received::<QueuePositionMessage>(4).first(),
+                Some(&QueuePositionMessage::new(1, 1))
+            );
+            MemoryTransport::client_disconnect(1);
+            tick();
+            assert!(NetworkServerStatic::network_connections().contains_key(&4));
+
+            NetworkServer::unregister_handler::<TestHit>();
+            NetworkServerStatic::set_connection_queue_enabled(false);
+            NetworkServerStatic::set_connection_queue_position_interval(1.0);
+            CONNECTION_QUEUE.clear_poison();
+        });
+    }
+
+    #[test]
+    fn test_client_not_ready() {
+        with_server(|| {
+            for conn_id in [1, 2] {
+                MemoryTransport::client_connect(conn_id);
+            }
+            tick();
+            NetworkServer::set_client_ready(1);
+            NetworkServer::set_client_ready(2);
+            for net_id in [950, 951] {
+                spawn_test_identity(net_id, 0, 1);
+                NetworkServer::set_visibility(net_id, Visibility::Default);
+            }
+            // 951 是连接 1 的玩家对象
+            NetworkServerStatic::network_connections()
+                .get_mut(&1)
+                .unwrap()
+                .set_net_id(951);
+            tick();
+            for conn_id in [1, 2] {
+                MemoryTransport::client_receive(conn_id);
+            }
+            let observers = |net_id: u32| {
+                NetworkServerStatic::spawned_network_identities()
+                    .get(&net_id)
+                    .unwrap()
+                    .observers()
+                    .clone()
+            };
+            let broadcast = |conn_id: u64| {
+                NetworkTime::increment_frame_count();
+                for net_id in [950, 951] {
+                    let mut behaviour = NETWORK_BEHAVIOURS.get_mut(&(net_id, 0)).unwrap();
+                    behaviour.set_sync_var_dirty_bits(1);
+                    behaviour.set_last_sync_time(-1.0);
+                }
+                tick();
+                let mut net_ids: Vec<u32> = decode::<EntityStateMessage>(
+                    &MemoryTransport::client_receive_messages(conn_id),
+                )
+                .iter()
+                .map(|state| state.net_id)
+                .collect();
+                net_ids.sort();
+                net_ids
+            };
+
+            // 切换场景: 所有客户端收到 NotReadyMessage, 不再是任何对象的观察者
+            NetworkServer::set_all_clients_not_ready();
+            tick();
+            for conn_id in [1, 2] {
+                assert_eq!(received::<NotReadyMessage>(conn_id).len(), 1);
+                assert!(NetworkServerStatic::network_connections()
+                    .get(&conn_id)
+                    .unwrap()
+                    .observing
+                    .is_empty());
+            }
+            assert!(observers(950).is_empty());
+            assert!(observers(951).is_empty());
+            assert!(broadcast(1).is_empty());
+
+            // 没有准备好的连接不能成为观察者
+            NetworkServerStatic::spawned_network_identities()
+                .get_mut(&950)
+                .unwrap()
+                .add_observer(2);
+            assert!(observers(950).is_empty());
+
+            // 重新准备好后恢复, 包括自己的玩家对象
+            NetworkServer::set_client_ready(1);
+            tick();
+            assert_eq!(received::<SpawnMessage>(1).len(), 2);
+            assert_eq!(observers(950), vec![1]);
+            assert_eq!(observers(951), vec![1]);
+            assert_eq!(broadcast(1), vec![950, 951]);
+            assert!(broadcast(2).is_empty());
+
+            for net_id in [950, 951] {
+                NetworkServerStatic::remove_spawned_network_identity(&net_id);
+            }
+        });
+    }
+
+    #[test]
+    fn test_server_restart() {
+        with_server(|| {
+            let mut first_net_id = None;
+            for _ in 0..2 {
+                MemoryTransport::client_connect(1);
+                tick();
+                assert!(NetworkServerStatic::network_connections().contains_key(&1));
+
+                let net_id = NetworkIdentity::get_static_next_network_id();
+                assert_eq!(*first_net_id.get_or_insert(net_id), net_id);
+                spawn_test_identity(net_id, 0, 1);
+                NetworkServerStatic::set_is_loading_scene(true);
+
+                // 停止后客户端被断开, 对象和组件被清空
+                NetworkServer::shutdown();
+                assert!(!NetworkServerStatic::active());
+                assert!(!MemoryTransport::client_connected(1));
+                assert!(NetworkServerStatic::network_connections().is_empty());
+                assert!(NetworkServerStatic::spawned_network_identities().is_empty());
+                assert!(NETWORK_BEHAVIOURS.is_empty());
+                assert!(!NetworkServerStatic::is_loading_scene());
+
+                // 同一个进程中重新监听
+                MemoryTransport::awake();
+                NetworkServer::listen(16);
+                assert!(NetworkServerStatic::active());
+            }
+        });
+    }
+
+    #[test]
+    fn test_reset_all_statics() {
+        with_server(|| {
+            let tick_rate = NetworkServerStatic::tick_rate();
+            for _ in 0..2 {
+                // 在一次运行中留下各种状态
+                MemoryTransport::client_connect(1);
+                tick();
+                send_raw(1, &[0xFE, 0xFF]);
+                tick();
+                assert!(!NetworkServerStatic::unknown_message_stats().is_empty());
+                NetworkServer::pause();
+                Scheduler::after(Duration::from_secs(60), || {});
+                NetworkServerStatic::set_last_send_time(10.0);
+                NetworkManagerStatic::set_network_scene_name("Match".to_string());
+                for x in [1.0, 2.0] {
+                    let mut start = Transform::default();
+                    start.position = Vector3::new(x, 0.0, 0.0);
+                    NetworkStartPosition::register("Match", start);
+                }
+                NetworkStartPosition::next_start_position("Match", PlayerSpawnMethod::RoundRobin);
+
+                NetworkServer::shutdown();
+                assert!(NetworkServerStatic::network_connections().is_empty());
+                assert!(NetworkServerStatic::unknown_message_stats().is_empty());
+                assert!(NetworkServerStatic::connected_event().is_empty());
+                assert!(!NetworkServerStatic::paused());
+                assert_eq!(Scheduler::count(), 0);
+                assert_eq!(NetworkServerStatic::last_send_time(), 0.0);
+                assert_eq!(NetworkServerStatic::handler_panic_count(), 0);
+                assert_eq!(NetworkManagerStatic::network_scene_name(), "");
+                // 出生点保留, RoundRobin 重新开始
+                let start = NetworkStartPosition::next_start_position(
+                    "Match",
+                    PlayerSpawnMethod::RoundRobin,
+                );
+                assert_eq!(start.map(|start| start.position.x), Some(1.0));
+                NetworkStartPosition::unregister_scene("Match");
+                // 配置跨运行保持
+                assert_eq!(NetworkServerStatic::tick_rate(), tick_rate);
+
+                MemoryTransport::awake();
+                NetworkServer::listen(16);
+            }
+        });
+    }
+
+    #[test]
+    fn test_owner_sync_mode_routing() {
+        with_server(|| {
+            for conn_id in [1, 2] {
+                MemoryTransport::client_connect(conn_id);
+            }
+            tick();
+            for conn_id in [1, 2] {
+                NetworkServer::set_client_ready(conn_id);
+            }
+
+            // 0: 所有观察者可见, 1: 只有所有者可见 (例如背包)
+            spawn_test_identity(620, 1, 2);
+            for (index, sync_mode) in [(0, SyncMode::Observers), (1, SyncMode::Owners)] {
+                NETWORK_BEHAVIOURS
+                    .get_mut(&(620, index))
+                    .unwrap()
+                    .set_sync_mode(sync_mode);
+            }
+            NetworkServer::set_visibility(620, Visibility::Default);
+            tick();
+
+            // 生成时的初始状态
+            let spawn_mask = |conn_id: u64| {
+                let spawns = received::<SpawnMessage>(conn_id);
+                assert_eq!(spawns.len(), 1);
+                NetworkReader::new_with_bytes(spawns[0].payload.clone()).decompress_var_ulong()
+            };
+            assert_eq!(spawn_mask(1), 0b11);
+            assert_eq!(spawn_mask(2), 0b01);
+
+            let dirty = |indices: &[u8]| {
+                NetworkTime::increment_frame_count();
+                for index in indices {
+                    let mut behaviour = NETWORK_BEHAVIOURS.get_mut(&(620, *index)).unwrap();
+                    behaviour.set_sync_var_dirty_bits(1);
+                    behaviour.set_last_sync_time(-1.0);
+                }
+                tick();
+            };
+            let state_masks = |conn_id: u64| -> Vec<u64> {
+                received::<EntityStateMessage>(conn_id)
+                    .into_iter()
+                    .map(|state| {
+                        NetworkReader::new_with_bytes(state.payload).decompress_var_ulong()
+                    })
+                    .collect()
+            };
+
+            dirty(&[0, 1]);
+            assert_eq!(state_masks(1), vec![0b11]);
+            assert_eq!(state_masks(2), vec![0b01]);
+
+            // 只有 Owner 组件变化时, 其他观察者收不到任何状态
+            dirty(&[1]);
+            assert_eq!(state_masks(1), vec![0b10]);
+            assert!(state_masks(2).is_empty());
+
+            // 组件自己发送的状态也只发给所有者
+            let payload = NetworkWriter::new();
+            NETWORK_BEHAVIOURS
+                .get(&(620, 1))
+                .unwrap()
+                .send_entity_internal(&payload, TransportChannel::Reliable, true);
+            NetworkTime::increment_frame_count();
+            tick();
+            assert_eq!(received::<EntityStateMessage>(1).len(), 1);
+            assert!(received::<EntityStateMessage>(2).is_empty());
+
+            NetworkServerStatic::remove_spawned_network_identity(&620);
+        });
+    }
+
+    #[test]
+    fn test_ordered_flush() {
+        with_server(|| {
+            for conn_id in [7, 3, 12, 5, 9, 1] {
+                MemoryTransport::client_connect(conn_id);
+            }
+            tick();
+            for conn_id in [7, 3, 12, 5, 9, 1] {
+                NetworkServer::set_client_ready(conn_id);
+            }
+            tick();
+            MemoryTransport::take_send_order();
+
+            // 每个连接每个 tick 至少有时间快照, 按连接 id 顺序交给 transport
+            tick();
+            assert_eq!(MemoryTransport::take_send_order(), vec![1, 3, 5, 7, 9, 12]);
+            let report = *FrameReports::reports().last().unwrap();
+            assert!(report.flush <= report.broadcast);
+
+            // 关闭后按 DashMap 的遍历顺序 flush, 所有连接仍然收到数据
+            NetworkServerStatic::set_ordered_flush(false);
+            tick();
+            let mut order = MemoryTransport::take_send_order();
+            order.sort();
+            assert_eq!(order, vec![1, 3, 5, 7, 9, 12]);
+            NetworkServerStatic::set_ordered_flush(true);
+        });
+    }
+
+    #[test]
+    fn test_time_snapshot_rate() {
+        with_server(|| {
+            MemoryTransport::client_connect(1);
+            tick();
+            NetworkServer::set_client_ready(1);
+            tick();
+            MemoryTransport::client_receive_messages(1);
+
+            // 默认每次 broadcast 都发送
+            tick();
+            tick();
+            assert_eq!(received::<TimeSnapshotMessage>(1).len(), 2);
+
+            // 1 Hz: 第一次 broadcast 发送, 之后一秒内不再发送
+            NetworkServerStatic::set_time_snapshot_rate(1);
+            assert_eq!(NetworkServerStatic::time_snapshot_interval(), 1.0);
+            tick();
+            tick();
+            tick();
+            assert_eq!(received::<TimeSnapshotMessage>(1).len(), 1);
+            let next = NetworkServerStatic::network_connections()
+                .get(&1)
+                .unwrap()
+                .next_time_snapshot_time;
+            assert!(next > NetworkTime::local_time());
+
+            // 到时间后再次发送
+            NetworkServerStatic::network_connections()
+                .get_mut(&1)
+                .unwrap()
+                .next_time_snapshot_time = 0.0;
+            tick();
+            assert_eq!(received::<TimeSnapshotMessage>(1).len(), 1);
+            NetworkServerStatic::set_time_snapshot_rate(0);
+        });
+    }
+
+    #[test]
+    fn test_tick_snapshot_rate() {
+        with_server(|| {
+            MemoryTransport::client_connect(1);
+            tick();
+            NetworkServer::set_client_ready(1);
+            NetworkServerStatic::network_connections()
+                .get_mut(&1)
+                .unwrap()
+                .features = NetworkServer::FEATURE_STATE_TICK;
+            tick();
+            MemoryTransport::client_receive_messages(1);
+
+            // TickSnapshotMessage 同样受 time_snapshot_rate 限制
+            NetworkServerStatic::set_time_snapshot_rate(1);
+            tick();
+            tick();
+            tick();
+            let messages = MemoryTransport::client_receive_messages(1);
+            assert_eq!(decode::<TickSnapshotMessage>(&messages).len(), 1);
+            assert!(decode::<TimeSnapshotMessage>(&messages).is_empty());
+
+            NetworkServerStatic::network_connections()
+                .get_mut(&1)
+                .unwrap()
+                .next_time_snapshot_time = 0.0;
+            tick();
+            assert_eq!(received::<TickSnapshotMessage>(1).len(), 1);
+            NetworkServerStatic::set_time_snapshot_rate(0);
+        });
+    }
+
+    #[test]
+    fn test_snapshot_overflow_policy() {
+        with_server(|| {
+            MemoryTransport::client_connect(1);
+            tick();
+            MemoryTransport::client_receive(1);
+            let mut snapshots: BTreeMap<OrderedFloat<f64>, u32> =
+                (1..=3).map(|i| (OrderedFloat(i as f64), i)).collect();
+            let count = NetworkServerStatic::snapshot_overflow_count();
+
+            // 默认丢弃新的快照
+            assert!(!NetworkServer::on_snapshot_overflow(&mut snapshots, 1));
+            assert_eq!(snapshots.len(), 3);
+
+            NetworkServerStatic::set_snapshot_overflow_policy(SnapshotOverflowPolicy::DropOldest);
+            assert!(NetworkServer::on_snapshot_overflow(&mut snapshots, 1));
+            assert_eq!(snapshots.values().copied().collect::<Vec<_>>(), vec![2, 3]);
+
+            NetworkServerStatic::set_snapshot_overflow_policy(
+                SnapshotOverflowPolicy::ResetAndResync,
+            );
+            assert!(NetworkServer::on_snapshot_overflow(&mut snapshots, 1));
+            assert!(snapshots.is_empty());
+
+            // 断开连接延迟到 network_late_update
+            NetworkServerStatic::set_snapshot_overflow_policy(SnapshotOverflowPolicy::Disconnect);
+            assert!(!NetworkServer::on_snapshot_overflow(&mut snapshots, 1));
+            assert!(MemoryTransport::client_connected(1));
+            tick();
+            assert!(!MemoryTransport::client_connected(1));
+            let messages = received::<DisconnectMessage>(1);
+            assert_eq!(messages.len(), 1);
+            assert_eq!(NetworkServerStatic::snapshot_overflow_count(), count + 4);
+            assert_eq!(
+                FrameReports::reports().last().unwrap().snapshot_overflows,
+                4
+            );
+            NetworkServerStatic::set_snapshot_overflow_policy(SnapshotOverflowPolicy::Ignore);
+        });
+    }
+
+    #[test]
+    fn test_interpolation_hint() {
+        with_server(|| {
+            NetworkServerStatic::set_protocol_handshake(true);
+            NetworkServerStatic::set_send_rate(30);
+            MemoryTransport::client_connect(1);
+            MemoryTransport::client_connect(2);
+            tick();
+            let mut version = ProtocolVersionMessage::new(
+                NetworkServer::PROTOCOL_VERSION,
+                NetworkServer::FEATURE_INTERPOLATION_HINT,
+                60,
+            );
+            MemoryTransport::client_send_message(1, &mut version, TransportChannel::Reliable);
+            let mut version = ProtocolVersionMessage::new(NetworkServer::PROTOCOL_VERSION, 0, 60);
+            MemoryTransport::client_send_message(2, &mut version, TransportChannel::Reliable);
+            tick();
+
+            // 握手完成时发送一次
+            let hints = received::<InterpolationHintMessage>(1);
+            assert_eq!(hints.len(), 1);
+            assert_eq!(hints[0].send_interval, (1.0f32 / 30.0) as f64);
+            assert!(hints[0].buffer_time() > 0.0);
+            assert!(received::<InterpolationHintMessage>(2).is_empty());
+
+            // 没有变化时 pong 后面不发送
+            let mut ping = NetworkPingMessage::new(0.5, 0.5);
+            MemoryTransport::client_send_message(1, &mut ping, TransportChannel::Reliable);
+            tick();
+            assert!(received::<InterpolationHintMessage>(1).is_empty());
+
+            // 服务器修改发送频率后, 下一次 pong 后面发送新的间隔
+            NetworkServerStatic::set_send_rate(20);
+            MemoryTransport::client_send_message(1, &mut ping, TransportChannel::Reliable);
+            MemoryTransport::client_send_message(2, &mut ping, TransportChannel::Reliable);
+            tick();
+            let messages = MemoryTransport::client_receive_messages(1);
+            assert_eq!(decode::<NetworkPongMessage>(&messages).len(), 1);
+            let hints = decode::<InterpolationHintMessage>(&messages);
+            assert_eq!(hints.len(), 1);
+            assert_eq!(hints[0].send_interval, (1.0f32 / 20.0) as f64);
+            assert!(received::<InterpolationHintMessage>(2).is_empty());
+
+            // 恢复默认的发送频率并主动通知
+            NetworkServerStatic::set_send_rate(NetworkServerStatic::tick_rate());
+            NetworkServer::send_interpolation_hints();
+            tick();
+            let hints = received::<InterpolationHintMessage>(1);
+            assert_eq!(hints.len(), 1);
+            assert_eq!(
+                hints[0].send_interval,
+                NetworkServerStatic::send_interval() as f64
+            );
+            NetworkServerStatic::set_protocol_handshake(false);
+        });
+    }
+
+    #[test]
+    fn test_spawn_owned() {
+        with_server(|| {
+            for conn_id in [1, 2] {
+                MemoryTransport::client_connect(conn_id);
+            }
+            tick();
+            for conn_id in [1, 2] {
+                NetworkServer::set_client_ready(conn_id);
+            }
+            let player = NetworkServer::spawn_owned(1, 7);
+            NetworkServerStatic::network_connections()
+                .get_mut(&1)
+                .unwrap()
+                .set_net_id(player);
+            tick();
+            MemoryTransport::client_receive(1);
+            MemoryTransport::client_receive(2);
+
+            // 附属对象属于连接 1, 但不是本地玩家
+            let pet = NetworkServer::spawn_owned(1, 8);
+            assert_ne!(pet, 0);
+            assert_eq!(NetworkServer::spawn_owned(3, 8), 0);
+            tick();
+            let spawns = received::<SpawnMessage>(1);
+            assert_eq!(spawns.len(), 1);
+            assert!(spawns[0].is_owner && !spawns[0].is_local_player);
+            let spawns = received::<SpawnMessage>(2);
+            assert!(!spawns[0].is_owner && !spawns[0].is_local_player);
+            assert_eq!(NetworkServer::owned_objects(1), vec![player, pet]);
+            assert_eq!(NetworkServer::secondary_objects(1), vec![pet]);
+
+            // 连接 2 的召唤物交给连接 1
+            let minion = NetworkServer::spawn_owned(2, 9);
+            tick();
+            NetworkServerStatic::spawned_network_identities()
+                .get_mut(&minion)
+                .unwrap()
+                .remove_client_authority();
+            assert!(NetworkServer::owned_objects(2).is_empty());
+            assert!(!NetworkServer::assign_owner(player, 2));
+            assert!(NetworkServer::assign_owner(minion, 1));
+            tick();
+            assert_eq!(
+                received::<ChangeOwnerMessage>(2),
+                vec![ChangeOwnerMessage::new(minion, false, false)]
+            );
+            assert_eq!(
+                received::<ChangeOwnerMessage>(1),
+                vec![ChangeOwnerMessage::new(minion, true, false)]
+            );
+            assert_eq!(NetworkServer::secondary_objects(1), vec![pet, minion]);
+
+            // 断开连接时先销毁附属对象, 最后销毁玩家
+            MemoryTransport::client_disconnect(1);
+            tick();
+            let destroyed: Vec<u32> = received::<ObjectDestroyMessage>(2)
+                .iter()
+                .map(|message| message.net_id)
+                .collect();
+            assert_eq!(destroyed, vec![pet, minion, player]);
+            NetIdAllocator::reset();
+        });
     }
 }
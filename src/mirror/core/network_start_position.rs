@@ -1,10 +1,171 @@
+use crate::log_error;
 use crate::mirror::components::network_transform::network_transform_base::Transform;
-use crate::mirror::core::network_manager::NetworkManager;
+use crate::mirror::core::network_context::ContextLocal;
+use crate::mirror::core::network_manager::{NetworkManager, PlayerSpawnMethod};
+use lazy_static::lazy_static;
+use rand::Rng;
+use std::collections::HashMap;
+use std::sync::RwLock;
 
+#[derive(Debug, Default)]
+struct SceneStartPositions {
+    positions: Vec<Transform>,
+    // RoundRobin 的下一个位置
+    next: usize,
+}
+
+// NetworkStartPosition 静态变量
+lazy_static! {
+    // 场景名 -> 出生点
+    static ref START_POSITIONS: ContextLocal<RwLock<HashMap<String, SceneStartPositions>>> =
+        ContextLocal::new(|| RwLock::new(HashMap::new()));
+}
+
+// 按场景注册的出生点, 玩家生成时由 NetworkManagerTrait::get_start_position 选择
+// 场景没有注册出生点时使用 ANY_SCENE 的出生点
 pub struct NetworkStartPosition;
+
 impl NetworkStartPosition {
+    // 所有场景通用的出生点
+    pub const ANY_SCENE: &'static str = "";
 
-    pub fn awake(){
+    pub fn awake() {
         NetworkManager::register_start_position(Transform::default());
     }
-}
\ No newline at end of file
+
+    pub fn register(scene: &str, start: Transform) {
+        match START_POSITIONS.write() {
+            Ok(mut start_positions) => {
+                start_positions
+                    .entry(scene.to_string())
+                    .or_default()
+                    .positions
+                    .push(start);
+            }
+            Err(e) => {
+                log_error!("Failed to register start position: {:?}", e);
+            }
+        }
+    }
+
+    pub fn unregister_scene(scene: &str) {
+        match START_POSITIONS.write() {
+            Ok(mut start_positions) => {
+                start_positions.remove(scene);
+            }
+            Err(e) => {
+                log_error!("Failed to unregister start positions: {:?}", e);
+            }
+        }
+    }
+
+    // 只返回 scene 自己注册的出生点
+    pub fn start_positions(scene: &str) -> Vec<Transform> {
+        match START_POSITIONS.read() {
+            Ok(start_positions) => start_positions
+                .get(scene)
+                .map(|scene_positions| scene_positions.positions.clone())
+                .unwrap_or_default(),
+            Err(e) => {
+                log_error!("Failed to get start positions: {:?}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    // 选择 scene 的下一个出生点, scene 和 ANY_SCENE 都没有出生点时返回 None
+    pub fn next_start_position(scene: &str, method: PlayerSpawnMethod) -> Option<Transform> {
+        let mut start_positions = match START_POSITIONS.write() {
+            Ok(start_positions) => start_positions,
+            Err(e) => {
+                log_error!("Failed to get start positions: {:?}", e);
+                return None;
+            }
+        };
+        let scene = match start_positions.get(scene) {
+            Some(scene_positions) if !scene_positions.positions.is_empty() => scene,
+            _ => Self::ANY_SCENE,
+        };
+        let scene_positions = start_positions.get_mut(scene)?;
+        let count = scene_positions.positions.len();
+        if count == 0 {
+            return None;
+        }
+        let index = match method {
+            PlayerSpawnMethod::Random => rand::rng().random_range(0..count),
+            PlayerSpawnMethod::RoundRobin => {
+                let index = scene_positions.next % count;
+                scene_positions.next = (index + 1) % count;
+                index
+            }
+        };
+        Some(scene_positions.positions[index])
+    }
+
+    // 所有场景的 RoundRobin 从第一个出生点重新开始, 注册的出生点保留
+    pub fn reset_round_robin() {
+        if let Ok(mut start_positions) = START_POSITIONS.write() {
+            for scene_positions in start_positions.values_mut() {
+                scene_positions.next = 0;
+            }
+        }
+    }
+
+    pub fn clear() {
+        if let Ok(mut start_positions) = START_POSITIONS.write() {
+            start_positions.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::transports::memory::memory_transport::test_util::with_isolated_context;
+    use nalgebra::Vector3;
+
+    fn at(x: f32) -> Transform {
+        let mut transform = Transform::default();
+        transform.position = Vector3::new(x, 0.0, 0.0);
+        transform
+    }
+
+    fn next_x(scene: &str, method: PlayerSpawnMethod) -> Option<f32> {
+        NetworkStartPosition::next_start_position(scene, method).map(|start| start.position.x)
+    }
+
+    #[test]
+    fn test_start_positions_by_scene() {
+        with_isolated_context(|| {
+            assert_eq!(next_x("Match", PlayerSpawnMethod::Random), None);
+
+            NetworkStartPosition::register(NetworkStartPosition::ANY_SCENE, at(9.0));
+            for x in [1.0, 2.0, 3.0] {
+                NetworkStartPosition::register("Match", at(x));
+            }
+            assert_eq!(NetworkStartPosition::start_positions("Match").len(), 3);
+
+            let round_robin: Vec<Option<f32>> = (0..4)
+                .map(|_| next_x("Match", PlayerSpawnMethod::RoundRobin))
+                .collect();
+            assert_eq!(
+                round_robin,
+                vec![Some(1.0), Some(2.0), Some(3.0), Some(1.0)]
+            );
+            for _ in 0..10 {
+                let x = next_x("Match", PlayerSpawnMethod::Random).unwrap();
+                assert!([1.0, 2.0, 3.0].contains(&x));
+            }
+            // 没有注册的场景使用通用出生点
+            assert_eq!(next_x("Lobby", PlayerSpawnMethod::RoundRobin), Some(9.0));
+
+            NetworkStartPosition::reset_round_robin();
+            assert_eq!(next_x("Match", PlayerSpawnMethod::RoundRobin), Some(1.0));
+
+            NetworkStartPosition::unregister_scene("Match");
+            assert_eq!(next_x("Match", PlayerSpawnMethod::RoundRobin), Some(9.0));
+            NetworkStartPosition::clear();
+            assert_eq!(next_x("Match", PlayerSpawnMethod::RoundRobin), None);
+        });
+    }
+}
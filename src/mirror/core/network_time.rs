@@ -1,5 +1,6 @@
 use crate::mirror::core::messages::{NetworkMessageTrait, NetworkPingMessage, NetworkPongMessage};
 use crate::mirror::core::network_connection::NetworkConnectionTrait;
+use crate::mirror::core::network_context::ContextLocal;
 use crate::mirror::core::network_reader::NetworkReader;
 use crate::mirror::core::network_server::NetworkServerStatic;
 use crate::mirror::core::transport::TransportChannel;
@@ -7,18 +8,31 @@ use crate::{log_error, log_warn};
 use atomic::Atomic;
 use dashmap::try_result::TryResult;
 use lazy_static::lazy_static;
+use std::collections::VecDeque;
 use std::sync::atomic::Ordering;
 use std::sync::RwLock;
 use std::time::Instant;
 
 lazy_static! {
     // 全局启动时间锚点
-    static ref START_INSTANT: RwLock<Instant> = RwLock::new(Instant::now());
-    static ref LAST_PING_TIME: Atomic<f64> = Atomic::new(0.0);
-    static ref PING_INTERVAL: Atomic<f64> = Atomic::new(NetworkTime::DEFAULT_PING_INTERVAL);
-    static ref FRAME_COUNT: Atomic<u32> = Atomic::new(0);
-    static ref _RTT: RwLock<ExponentialMovingAverage> = RwLock::new(ExponentialMovingAverage::new(NetworkTime::PING_WINDOW_SIZE));
-    static ref _PREDICTION_ERROR_UNADJUSTED: RwLock<ExponentialMovingAverage> = RwLock::new(ExponentialMovingAverage::new(NetworkTime::PREDICTION_ERROR_WINDOW_SIZE));
+    static ref START_INSTANT: ContextLocal<RwLock<Instant>> =
+        ContextLocal::new(|| RwLock::new(Instant::now()));
+    static ref LAST_PING_TIME: ContextLocal<Atomic<f64>> = ContextLocal::new(|| Atomic::new(0.0));
+    static ref PING_INTERVAL: ContextLocal<Atomic<f64>> =
+        ContextLocal::new(|| Atomic::new(NetworkTime::DEFAULT_PING_INTERVAL));
+    static ref FRAME_COUNT: ContextLocal<Atomic<u32>> = ContextLocal::new(|| Atomic::new(0));
+    static ref LAST_FRAME_TIME: ContextLocal<Atomic<f64>> = ContextLocal::new(|| Atomic::new(0.0));
+    static ref DELTA_TIME: ContextLocal<Atomic<f64>> = ContextLocal::new(|| Atomic::new(0.0));
+    static ref _RTT: ContextLocal<RwLock<ExponentialMovingAverage>> =
+        ContextLocal::new(|| {
+            RwLock::new(ExponentialMovingAverage::new(NetworkTime::PING_WINDOW_SIZE))
+        });
+    static ref _PREDICTION_ERROR_UNADJUSTED: ContextLocal<RwLock<ExponentialMovingAverage>> =
+        ContextLocal::new(|| {
+            RwLock::new(ExponentialMovingAverage::new(
+                NetworkTime::PREDICTION_ERROR_WINDOW_SIZE,
+            ))
+        });
 }
 
 pub struct NetworkTime;
@@ -27,15 +41,34 @@ impl NetworkTime {
     pub const DEFAULT_PING_INTERVAL: f64 = 0.1;
     pub const PING_WINDOW_SIZE: u32 = 50;
     pub const PREDICTION_ERROR_WINDOW_SIZE: u32 = 20;
+    pub const CLOCK_OFFSET_WINDOW_SIZE: usize = 16;
 
     pub fn frame_count() -> u32 {
         FRAME_COUNT.load(Ordering::Relaxed)
     }
 
+    // 服务器模拟 tick, 与 get_server_serialization_at_tick 使用的一致
+    // 通过 FEATURE_STATE_TICK 附带在状态广播中
+    pub fn tick() -> u32 {
+        Self::frame_count()
+    }
+
     pub fn increment_frame_count() {
         FRAME_COUNT.fetch_add(1, Ordering::Relaxed);
     }
 
+    // 上一帧到这一帧的时间, 对应 Unity 的 Time.unscaledDeltaTime
+    pub fn delta_time() -> f64 {
+        DELTA_TIME.load(Ordering::Relaxed)
+    }
+
+    // 在 NetworkServer::network_early_update 开始时调用, 每帧一次
+    pub fn update_delta_time() {
+        let local_time = Self::local_time();
+        let last_frame_time = LAST_FRAME_TIME.swap(local_time, Ordering::Relaxed);
+        DELTA_TIME.store((local_time - last_frame_time).max(0.0), Ordering::Relaxed);
+    }
+
     #[allow(dead_code)]
     pub fn local_time() -> f64 {
         if let Ok(start_instant) = START_INSTANT.read() {
@@ -93,6 +126,8 @@ impl NetworkTime {
         }
         Self::set_ping_interval(Self::DEFAULT_PING_INTERVAL);
         Self::set_last_ping_time(0.0);
+        LAST_FRAME_TIME.store(0.0, Ordering::Relaxed);
+        DELTA_TIME.store(0.0, Ordering::Relaxed);
     }
 
     #[allow(dead_code)]
@@ -111,26 +146,31 @@ impl NetworkTime {
             NetworkPongMessage::new(message.local_time, unadjusted_error, adjusted_error);
         match NetworkServerStatic::network_connections().try_get_mut(&connection_id) {
             TryResult::Present(mut connection) => {
+                // 客户端时钟偏移 = 客户端发送时间 + 单程延迟 - 服务器接收时间
+                let offset = message.local_time + connection._rtt.value / 2.0 - local_time;
+                connection.clock_offset.add_sample(offset);
                 // send pong message
                 connection.send_network_message(&mut pong_message, TransportChannel::Reliable);
+                // 发送间隔或者建议的缓冲变化时跟在 pong 后面通知客户端
+                connection.send_interpolation_hint(false);
             }
             TryResult::Absent => {
-                log_error!(format!(
+                log_error!(
                     "NetworkTime::on_server_ping() failed to get connection: {}",
                     connection_id
-                ));
+                );
             }
             TryResult::Locked => {
-                log_error!(format!(
+                log_error!(
                     "NetworkTime::on_server_ping() failed to get connection: {}",
                     connection_id
-                ));
+                );
             }
         }
     }
 
     pub fn on_server_pong(
-        _connection_id: u64,
+        connection_id: u64,
         un_batch: &mut NetworkReader,
         _channel: TransportChannel,
     ) {
@@ -144,6 +184,34 @@ impl NetworkTime {
         } else {
             log_warn!("NetworkTime::on_server_pong() failed to get rtt");
         }
+        // 每个连接单独的 rtt, 用于估算时钟偏移
+        if let TryResult::Present(mut connection) =
+            NetworkServerStatic::network_connections().try_get_mut(&connection_id)
+        {
+            connection._rtt.add(new_rtt);
+        }
+    }
+
+    // 估算的客户端当前时间 (服务器本地时间 + 平滑后的时钟偏移)
+    // 比 remote_time_stamp 更适合做延迟补偿和反作弊判断
+    pub fn estimated_client_time(connection_id: u64) -> f64 {
+        match NetworkServerStatic::network_connections().try_get(&connection_id) {
+            TryResult::Present(connection) => Self::local_time() + connection.clock_offset.offset(),
+            TryResult::Absent => {
+                log_error!(
+                    "NetworkTime::estimated_client_time() failed to get connection: {}",
+                    connection_id
+                );
+                Self::local_time()
+            }
+            TryResult::Locked => {
+                log_error!(
+                    "NetworkTime::estimated_client_time() connection locked: {}",
+                    connection_id
+                );
+                Self::local_time()
+            }
+        }
     }
 
     #[allow(dead_code)]
@@ -228,6 +296,97 @@ impl ExponentialMovingAverage {
     }
 }
 
+// NTP 风格的时钟偏移估算
+// 保留最近 window_size 个原始样本, 偏离中位数超过 OUTLIER_THRESHOLD 倍绝对中位差的样本不参与平滑
+#[derive(Debug, Clone)]
+pub struct ClockOffsetEstimator {
+    samples: VecDeque<f64>,
+    window_size: usize,
+    ema: ExponentialMovingAverage,
+}
+
+impl ClockOffsetEstimator {
+    pub const OUTLIER_THRESHOLD: f64 = 3.0;
+    // 绝对中位差的下限, 避免样本完全一致时拒绝所有新样本
+    pub const MIN_DEVIATION: f64 = 0.001;
+
+    pub fn new(window_size: usize) -> Self {
+        Self {
+            samples: VecDeque::with_capacity(window_size),
+            window_size,
+            ema: ExponentialMovingAverage::new(window_size as u32),
+        }
+    }
+
+    // 添加样本, 返回样本是否被采用
+    pub fn add_sample(&mut self, offset: f64) -> bool {
+        let accepted = match Self::median_and_deviation(&self.samples) {
+            Some((median, deviation)) => {
+                (offset - median).abs() <= Self::OUTLIER_THRESHOLD * deviation.max(Self::MIN_DEVIATION)
+            }
+            None => true,
+        };
+        // 原始样本始终进入窗口, 这样持续的时钟跳变最终会成为新的中位数
+        if self.samples.len() >= self.window_size {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(offset);
+        if accepted {
+            self.ema.add(offset);
+        }
+        accepted
+    }
+
+    pub fn offset(&self) -> f64 {
+        self.ema.value
+    }
+
+    pub fn jitter(&self) -> f64 {
+        self.ema.standard_deviation
+    }
+
+    pub fn sample_count(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn reset(&mut self) {
+        self.samples.clear();
+        self.ema.reset();
+    }
+
+    fn median_and_deviation(samples: &VecDeque<f64>) -> Option<(f64, f64)> {
+        // 样本太少时不做异常值判断
+        if samples.len() < 3 {
+            return None;
+        }
+        let median = Self::median(samples.iter().copied().collect());
+        let deviation = Self::median(samples.iter().map(|sample| (sample - median).abs()).collect());
+        Some((median, deviation))
+    }
+
+    fn median(mut values: Vec<f64>) -> f64 {
+        values.sort_by(|a, b| a.total_cmp(b));
+        let mid = values.len() / 2;
+        if values.len().is_multiple_of(2) {
+            (values[mid - 1] + values[mid]) / 2.0
+        } else {
+            values[mid]
+        }
+    }
+}
+
+#[test]
+fn test_clock_offset_estimator() {
+    let mut estimator = ClockOffsetEstimator::new(NetworkTime::CLOCK_OFFSET_WINDOW_SIZE);
+    for offset in [0.100, 0.101, 0.099, 0.100, 0.102] {
+        assert!(estimator.add_sample(offset));
+    }
+    // 单个尖峰被拒绝, 不影响平滑后的偏移
+    assert!(!estimator.add_sample(5.0));
+    assert!((estimator.offset() - 0.1).abs() < 0.005);
+    assert_eq!(estimator.sample_count(), 6);
+}
+
 #[test]
 fn test_network_time() {
     NetworkTime::reset_statics();
@@ -1,7 +1,10 @@
+use crate::mirror::core::network_behaviour::NetworkBehaviourRef;
+use crate::mirror::core::unity_types::{Color, Color32, Guid, Plane, Quat, Ray, Rect, Vec3};
 use crate::{log_error, log_warn};
 use half::f16;
-use nalgebra::{Quaternion, Vector2, Vector3, Vector4};
+use nalgebra::{Matrix4, Quaternion, Vector2, Vector3, Vector4};
 use rust_decimal::Decimal;
+use std::time::SystemTime;
 use std::{fmt, ptr};
 
 #[derive(Debug)]
@@ -87,8 +90,8 @@ impl NetworkWriter {
         self.position += size;
     }
     pub fn write_blittable_nullable<T: Copy>(&mut self, value: Option<T>) {
-        // Write a boolean indicating whether the value is null
-        self.write_byte(value.is_none() as u8);
+        // Write a boolean indicating whether the value is present (C#: HasValue)
+        self.write_byte(value.is_some() as u8);
 
         // If the value is not null, write the value
         if let Some(value) = value {
@@ -169,6 +172,8 @@ pub trait NetworkWriterTrait {
 
     fn write_str(&mut self, value: &str);
     fn write_string(&mut self, value: String);
+    // None 对应 C# 的 null
+    fn write_string_nullable(&mut self, value: Option<&str>);
     fn write_bytes_and_size(&mut self, value: Vec<u8>);
     fn write_array_segment_and_size(&mut self, value: &[u8]);
     fn write_vector2(&mut self, value: Vector2<f32>);
@@ -182,6 +187,50 @@ pub trait NetworkWriterTrait {
 
     fn write_quaternion(&mut self, value: Quaternion<f32>);
     fn write_quaternion_nullable(&mut self, value: Option<Quaternion<f32>>);
+
+    // 不依赖 nalgebra 的 Vec3 / Quat, 字节与 write_vector3 / write_quaternion 相同
+    fn write_vec3(&mut self, value: Vec3);
+    fn write_vec3_nullable(&mut self, value: Option<Vec3>);
+
+    fn write_quat(&mut self, value: Quat);
+    fn write_quat_nullable(&mut self, value: Option<Quat>);
+
+    fn write_color(&mut self, value: Color);
+    fn write_color_nullable(&mut self, value: Option<Color>);
+
+    fn write_color32(&mut self, value: Color32);
+    fn write_color32_nullable(&mut self, value: Option<Color32>);
+
+    fn write_rect(&mut self, value: Rect);
+    fn write_rect_nullable(&mut self, value: Option<Rect>);
+
+    fn write_plane(&mut self, value: Plane);
+    fn write_plane_nullable(&mut self, value: Option<Plane>);
+
+    fn write_ray(&mut self, value: Ray);
+    fn write_ray_nullable(&mut self, value: Option<Ray>);
+
+    fn write_matrix4x4(&mut self, value: Matrix4<f32>);
+    fn write_matrix4x4_nullable(&mut self, value: Option<Matrix4<f32>>);
+
+    fn write_guid(&mut self, value: Guid);
+    fn write_guid_nullable(&mut self, value: Option<Guid>);
+
+    fn write_date_time(&mut self, value: SystemTime);
+    fn write_date_time_nullable(&mut self, value: Option<SystemTime>);
+
+    // NetworkIdentity 和 GameObject / Transform 都只写入 net_id, None 写入 0
+    fn write_network_identity(&mut self, value: Option<u32>);
+    fn write_game_object(&mut self, value: Option<u32>);
+    // net_id + component_index, None 只写入 0
+    fn write_network_behaviour(&mut self, value: Option<NetworkBehaviourRef>);
+
+    // T[] 和 List<T>, None 对应 C# 的 null
+    fn write_array<T, F: Fn(&mut Self, &T)>(&mut self, value: Option<&[T]>, write: F);
+    fn write_list<T, F: Fn(&mut Self, &T)>(&mut self, value: Option<&[T]>, write: F);
+    // ArraySegment<T>, 不能为 null
+    fn write_segment<T, F: Fn(&mut Self, &T)>(&mut self, value: &[T], write: F);
+
     fn compress_var_int(&mut self, value: i32);
     fn compress_var_uint(&mut self, value: u32);
     fn compress_var_long(&mut self, value: i64);
@@ -196,11 +245,12 @@ pub trait Writeable {
 
 impl fmt::Display for NetworkWriter {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let hex_string = self.to_array_segment()
-            .iter()
-            .map(|byte| format!("{:02X}", byte))
-            .collect::<String>();
-        write!(f, "[{} @ {}/{}]", hex_string, self.position, self.capacity())
+        // 逐字节写入 Formatter, 不构造中间字符串
+        write!(f, "[")?;
+        for byte in self.to_array_segment() {
+            write!(f, "{:02X}", byte)?;
+        }
+        write!(f, " @ {}/{}]", self.position, self.capacity())
     }
 }
 
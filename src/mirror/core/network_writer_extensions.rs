@@ -1,8 +1,12 @@
 use crate::log_error;
+use crate::mirror::core::network_behaviour::NetworkBehaviourRef;
+use crate::mirror::core::network_serialization::{ActiveSerializationBackend, SerializationBackend};
 use crate::mirror::core::network_writer::{NetworkWriter, NetworkWriterTrait, Writeable};
+use crate::mirror::core::unity_types::{Color, Color32, Guid, Plane, Quat, Ray, Rect, Vec3};
 use half::f16;
-use nalgebra::{Quaternion, Vector2, Vector3, Vector4};
+use nalgebra::{Matrix4, Quaternion, Vector2, Vector3, Vector4};
 use rust_decimal::Decimal;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 pub struct NetworkWriterExtensions;
 
@@ -16,6 +20,39 @@ impl NetworkWriterExtensions {
         writer.write_blittable(1 + length as u16);
         writer.write_array_segment_all(bytes);
     }
+
+    // 1970-01-01 的 OADate
+    pub(crate) const OA_DATE_UNIX_EPOCH: f64 = 25569.0;
+
+    // DateTime.ToOADate: 自 1899-12-30 起的天数, 按 UTC 处理
+    pub(crate) fn to_oa_date(value: SystemTime) -> f64 {
+        let seconds = match value.duration_since(UNIX_EPOCH) {
+            Ok(duration) => duration.as_secs_f64(),
+            Err(e) => -e.duration().as_secs_f64(),
+        };
+        // 毫秒精度, 与 C# 相同
+        let milliseconds = (seconds * 1000.0).round();
+        milliseconds / 86_400_000.0 + Self::OA_DATE_UNIX_EPOCH
+    }
+
+    fn write_collection<T, F: Fn(&mut NetworkWriter, &T)>(
+        writer: &mut NetworkWriter,
+        value: Option<&[T]>,
+        write: F,
+    ) {
+        // 与 write_bytes_and_size 相同, 写入 count + 1, 0 表示 null
+        let value = match value {
+            Some(value) => value,
+            None => {
+                writer.compress_var_uint(0);
+                return;
+            }
+        };
+        writer.compress_var_uint(value.len() as u32 + 1);
+        for item in value {
+            write(writer, item);
+        }
+    }
 }
 
 impl NetworkWriterTrait for NetworkWriter {
@@ -37,20 +74,14 @@ impl NetworkWriterTrait for NetworkWriter {
         self.write_blittable(value as u16);
     }
     fn write_char_nullable(&mut self, value: Option<char>) {
-        match value {
-            Some(v) => self.write_blittable(v as u16),
-            None => self.write_blittable(0u16),
-        }
+        self.write_blittable_nullable(value.map(|v| v as u16));
     }
 
     fn write_bool(&mut self, value: bool) {
         self.write_blittable(value as u8);
     }
     fn write_bool_nullable(&mut self, value: Option<bool>) {
-        match value {
-            Some(v) => self.write_blittable(v as u8),
-            None => self.write_blittable(0u8),
-        }
+        self.write_blittable_nullable(value.map(|v| v as u8));
     }
 
     fn write_short(&mut self, value: i16) {
@@ -68,28 +99,28 @@ impl NetworkWriterTrait for NetworkWriter {
     }
 
     fn write_int(&mut self, value: i32) {
-        self.write_blittable(value);
+        ActiveSerializationBackend::write_int(self, value);
     }
     fn write_int_nullable(&mut self, value: Option<i32>) {
         self.write_blittable_nullable(value);
     }
 
     fn write_uint(&mut self, value: u32) {
-        self.write_blittable(value);
+        ActiveSerializationBackend::write_uint(self, value);
     }
     fn write_uint_nullable(&mut self, value: Option<u32>) {
         self.write_blittable_nullable(value);
     }
 
     fn write_long(&mut self, value: i64) {
-        self.write_blittable(value);
+        ActiveSerializationBackend::write_long(self, value);
     }
     fn write_long_nullable(&mut self, value: Option<i64>) {
         self.write_blittable_nullable(value);
     }
 
     fn write_ulong(&mut self, value: u64) {
-        self.write_blittable(value);
+        ActiveSerializationBackend::write_ulong(self, value);
     }
     fn write_ulong_nullable(&mut self, value: Option<u64>) {
         self.write_blittable_nullable(value);
@@ -127,6 +158,13 @@ impl NetworkWriterTrait for NetworkWriter {
     fn write_string(&mut self, value: String) {
         self.write(value);
     }
+    fn write_string_nullable(&mut self, value: Option<&str>) {
+        match value {
+            Some(value) => self.write_str(value),
+            // null 写入长度 0
+            None => self.write_ushort(0),
+        }
+    }
 
     fn write_bytes_and_size(&mut self, value: Vec<u8>) {
         let count = value.len();
@@ -153,11 +191,7 @@ impl NetworkWriterTrait for NetworkWriter {
     }
 
     fn write_vector2_nullable(&mut self, value: Option<Vector2<f32>>) {
-        if let Some(v) = value {
-            self.write_blittable(v.data);
-        } else {
-            self.write_byte(0);
-        }
+        self.write_blittable_nullable(value.map(|v| v.data));
     }
 
     fn write_vector3(&mut self, value: Vector3<f32>) {
@@ -165,11 +199,7 @@ impl NetworkWriterTrait for NetworkWriter {
     }
 
     fn write_vector3_nullable(&mut self, value: Option<Vector3<f32>>) {
-        if let Some(v) = value {
-            self.write_blittable(v.data);
-        } else {
-            self.write_byte(0);
-        }
+        self.write_blittable_nullable(value.map(|v| v.data));
     }
 
     fn write_vector4(&mut self, value: Vector4<f32>) {
@@ -177,11 +207,7 @@ impl NetworkWriterTrait for NetworkWriter {
     }
 
     fn write_vector4_nullable(&mut self, value: Option<Vector4<f32>>) {
-        if let Some(v) = value {
-            self.write_blittable(v.data)
-        } else {
-            self.write_byte(0);
-        }
+        self.write_blittable_nullable(value.map(|v| v.data));
     }
 
     fn write_quaternion(&mut self, value: Quaternion<f32>) {
@@ -189,10 +215,144 @@ impl NetworkWriterTrait for NetworkWriter {
     }
 
     fn write_quaternion_nullable(&mut self, value: Option<Quaternion<f32>>) {
+        self.write_blittable_nullable(value.map(|v| v.coords.data));
+    }
+
+    fn write_vec3(&mut self, value: Vec3) {
+        self.write_blittable(value);
+    }
+
+    fn write_vec3_nullable(&mut self, value: Option<Vec3>) {
+        self.write_blittable_nullable(value);
+    }
+
+    fn write_quat(&mut self, value: Quat) {
+        self.write_blittable(value);
+    }
+
+    fn write_quat_nullable(&mut self, value: Option<Quat>) {
+        self.write_blittable_nullable(value);
+    }
+
+    fn write_color(&mut self, value: Color) {
+        self.write_blittable(value);
+    }
+
+    fn write_color_nullable(&mut self, value: Option<Color>) {
+        self.write_bool(value.is_some());
+        if let Some(v) = value {
+            self.write_color(v);
+        }
+    }
+
+    fn write_color32(&mut self, value: Color32) {
+        self.write_blittable(value);
+    }
+
+    fn write_color32_nullable(&mut self, value: Option<Color32>) {
+        self.write_bool(value.is_some());
+        if let Some(v) = value {
+            self.write_color32(v);
+        }
+    }
+
+    fn write_rect(&mut self, value: Rect) {
+        self.write_float(value.x);
+        self.write_float(value.y);
+        self.write_float(value.width);
+        self.write_float(value.height);
+    }
+
+    fn write_rect_nullable(&mut self, value: Option<Rect>) {
+        self.write_bool(value.is_some());
+        if let Some(v) = value {
+            self.write_rect(v);
+        }
+    }
+
+    fn write_plane(&mut self, value: Plane) {
+        self.write_vector3(value.normal);
+        self.write_float(value.distance);
+    }
+
+    fn write_plane_nullable(&mut self, value: Option<Plane>) {
+        self.write_bool(value.is_some());
+        if let Some(v) = value {
+            self.write_plane(v);
+        }
+    }
+
+    fn write_ray(&mut self, value: Ray) {
+        self.write_vector3(value.origin);
+        self.write_vector3(value.direction);
+    }
+
+    fn write_ray_nullable(&mut self, value: Option<Ray>) {
+        self.write_bool(value.is_some());
+        if let Some(v) = value {
+            self.write_ray(v);
+        }
+    }
+
+    // 列主序: m00 m10 m20 m30 m01 ...
+    fn write_matrix4x4(&mut self, value: Matrix4<f32>) {
+        self.write_blittable(value.data);
+    }
+
+    fn write_matrix4x4_nullable(&mut self, value: Option<Matrix4<f32>>) {
+        self.write_bool(value.is_some());
         if let Some(v) = value {
-            self.write_blittable(v.coords.data);
-        } else {
-            self.write_byte(0);
+            self.write_matrix4x4(v);
+        }
+    }
+
+    fn write_guid(&mut self, value: Guid) {
+        self.write_blittable(value.0);
+    }
+
+    fn write_guid_nullable(&mut self, value: Option<Guid>) {
+        self.write_blittable_nullable(value.map(|v| v.0));
+    }
+
+    fn write_date_time(&mut self, value: SystemTime) {
+        self.write_double(NetworkWriterExtensions::to_oa_date(value));
+    }
+
+    fn write_date_time_nullable(&mut self, value: Option<SystemTime>) {
+        self.write_double_nullable(value.map(NetworkWriterExtensions::to_oa_date));
+    }
+
+    fn write_network_identity(&mut self, value: Option<u32>) {
+        self.write_uint(value.unwrap_or(0));
+    }
+
+    fn write_game_object(&mut self, value: Option<u32>) {
+        self.write_network_identity(value);
+    }
+
+    fn write_network_behaviour(&mut self, value: Option<NetworkBehaviourRef>) {
+        match value {
+            Some(value) => {
+                self.write_uint(value.net_id);
+                self.write_byte(value.component_index);
+            }
+            None => self.write_uint(0),
+        }
+    }
+
+    fn write_array<T, F: Fn(&mut Self, &T)>(&mut self, value: Option<&[T]>, write: F) {
+        NetworkWriterExtensions::write_collection(self, value, write);
+    }
+
+    fn write_list<T, F: Fn(&mut Self, &T)>(&mut self, value: Option<&[T]>, write: F) {
+        NetworkWriterExtensions::write_collection(self, value, write);
+    }
+
+    fn write_segment<T, F: Fn(&mut Self, &T)>(&mut self, value: &[T], write: F) {
+        // 格式与 write_array 相同, 不会是 null
+        self.compress_var_uint(value.len() as u32 + 1);
+        for item in value {
+            write(self, item);
         }
     }
 
@@ -217,7 +377,7 @@ impl NetworkWriterTrait for NetworkWriter {
         if value <= 2287 {
             let a = ((value - 240) >> 8) as u16 + 241;
             let b = (value - 240) as u16;
-            self.write_ushort((b << 8u16) | a);
+            self.write_blittable::<u16>((b << 8u16) | a);
             return;
         }
         if value <= 67823 {
@@ -225,28 +385,28 @@ impl NetworkWriterTrait for NetworkWriter {
             let b = ((value - 2288) >> 8) as u16;
             let c = (value - 2288) as u16;
             self.write_byte(a);
-            self.write_ushort((c << 8u16) | b);
+            self.write_blittable::<u16>((c << 8u16) | b);
             return;
         }
         if value <= 16777215 {
             let a = 250;
             let b = (value << 8) as u32;
-            self.write_uint(b | a);
+            self.write_blittable::<u32>(b | a);
             return;
         }
         if value <= 4294967295 {
             let a = 251;
             let b = value as u32;
             self.write_byte(a);
-            self.write_uint(b);
+            self.write_blittable::<u32>(b);
             return;
         }
         if value <= 1099511627775 {
             let a = 252;
             let b = (value & 0xFF) as u16;
             let c = (value >> 8) as u32;
-            self.write_ushort(b << 8 | a);
-            self.write_uint(c);
+            self.write_blittable::<u16>(b << 8 | a);
+            self.write_blittable::<u32>(c);
             return;
         }
         if value <= 281474976710655 {
@@ -255,21 +415,21 @@ impl NetworkWriterTrait for NetworkWriter {
             let c = ((value >> 8) & 0xFF) as u16;
             let d = (value >> 16) as u32;
             self.write_byte(a);
-            self.write_ushort(c << 8 | b);
-            self.write_uint(d);
+            self.write_blittable::<u16>(c << 8 | b);
+            self.write_blittable::<u32>(d);
             return;
         }
         if value <= 72057594037927935 {
             let a = 254u64;
             let b = value << 8;
-            self.write_ulong(b | a);
+            self.write_blittable::<u64>(b | a);
             return;
         }
 
         // all others
         {
             self.write_byte(255);
-            self.write_ulong(value);
+            self.write_blittable::<u64>(value);
         }
     }
 }
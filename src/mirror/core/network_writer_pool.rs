@@ -1,17 +1,25 @@
 use crate::log_warn;
+use crate::mirror::core::messages::NetworkMessageTrait;
+use crate::mirror::core::network_time::ExponentialMovingAverage;
 use crate::mirror::core::network_writer::NetworkWriter;
+use crate::mirror::core::tools::alloc_audit::{AllocAudit, AllocSite};
 use crate::mirror::core::tools::pool::Pool;
+use dashmap::DashMap;
 use lazy_static::lazy_static;
 use std::sync::{Arc, Mutex};
 
 lazy_static! {
     static ref NETWORK_WRITER_POOL: Arc<Mutex<Pool<NetworkWriter>>> = Arc::new(Mutex::new(Pool::new(|| NetworkWriter::new(), 1000)));
+    // 每种消息序列化后的平均大小, 用于预先分配 NetworkWriter
+    static ref MESSAGE_SIZES: DashMap<&'static str, ExponentialMovingAverage> = DashMap::new();
 }
 
 #[derive(Clone)]
 pub struct NetworkWriterPool;
 
 impl NetworkWriterPool {
+    const MESSAGE_SIZE_SAMPLES: u32 = 16;
+
     pub fn count() -> usize {
         if let Ok(pool) = NETWORK_WRITER_POOL.lock() {
             pool.count()
@@ -21,8 +29,21 @@ impl NetworkWriterPool {
         }
     }
 
+    // 池中 NetworkWriter 的缓冲区大小之和
+    pub fn memory_usage() -> usize {
+        if let Ok(pool) = NETWORK_WRITER_POOL.lock() {
+            pool.memory_usage(|writer| size_of::<NetworkWriter>() + writer.capacity())
+        } else {
+            log_warn!("NetworkWriterPool::memory_usage() failed to lock NETWORK_WRITER_POOL");
+            0
+        }
+    }
+
     pub fn get() -> NetworkWriter {
         if let Ok(mut pool) = NETWORK_WRITER_POOL.lock() {
+            if pool.count() == 0 {
+                AllocAudit::record(AllocSite::WriterPoolMiss);
+            }
             let mut writer = pool.get();
             writer.reset();
             writer
@@ -41,6 +62,48 @@ impl NetworkWriterPool {
         Self::return_(writer);
     }
 
+    // 按消息类型的平均大小 (加一个标准差) 预先分配, 避免序列化大消息时反复扩容
+    pub fn get_for_message<T>() -> NetworkWriter
+    where
+        T: NetworkMessageTrait,
+    {
+        let mut writer = Self::get();
+        if let Some(size) = Self::average_message_size::<T>() {
+            writer.ensure_capacity(size);
+        }
+        writer
+    }
+
+    pub fn get_return_for_message<T, F>(func: F)
+    where
+        T: NetworkMessageTrait,
+        F: FnOnce(&mut NetworkWriter),
+    {
+        let mut writer = Self::get_for_message::<T>();
+        func(&mut writer);
+        Self::record_message_size::<T>(writer.get_position());
+        Self::return_(writer);
+    }
+
+    pub fn record_message_size<T>(size: usize)
+    where
+        T: NetworkMessageTrait,
+    {
+        MESSAGE_SIZES
+            .entry(T::get_full_name())
+            .or_insert_with(|| ExponentialMovingAverage::new(Self::MESSAGE_SIZE_SAMPLES))
+            .add(size as f64);
+    }
+
+    pub fn average_message_size<T>() -> Option<usize>
+    where
+        T: NetworkMessageTrait,
+    {
+        MESSAGE_SIZES
+            .get(T::get_full_name())
+            .map(|ema| (ema.value + ema.standard_deviation).ceil() as usize)
+    }
+
     pub fn return_(mut writer: NetworkWriter) {
         if let Ok(mut pool) = NETWORK_WRITER_POOL.lock() {
             writer.reset();
@@ -53,6 +116,45 @@ impl NetworkWriterPool {
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use crate::mirror::core::network_reader::NetworkReader;
+    use crate::mirror::core::network_writer::NetworkWriterTrait;
+    use std::any::Any;
+
+    struct LargeMessage;
+    impl NetworkMessageTrait for LargeMessage {
+        fn deserialize(_reader: &mut NetworkReader) -> Self {
+            Self
+        }
+        fn serialize(&mut self, writer: &mut NetworkWriter) {
+            for _ in 0..4000 {
+                writer.write_byte(0);
+            }
+        }
+        fn get_full_name() -> &'static str {
+            "Test.LargeMessage"
+        }
+        fn as_any_mut(&mut self) -> &mut dyn Any {
+            self
+        }
+    }
+
     #[test]
     fn test_network_writer_pool() {}
+
+    #[test]
+    fn test_get_for_message() {
+        assert_eq!(NetworkWriterPool::average_message_size::<LargeMessage>(), None);
+        NetworkWriterPool::get_return_for_message::<LargeMessage, _>(|writer| {
+            LargeMessage.serialize(writer);
+        });
+        assert_eq!(
+            NetworkWriterPool::average_message_size::<LargeMessage>(),
+            Some(4000)
+        );
+        let writer = NetworkWriterPool::get_for_message::<LargeMessage>();
+        assert!(writer.capacity() >= 4000);
+        assert_eq!(writer.get_position(), 0);
+        NetworkWriterPool::return_(writer);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,266 @@
+use crate::mirror::core::messages::NetworkMessageTrait;
+use crate::mirror::core::network_context::ContextLocal;
+use crate::mirror::core::network_messages::NetworkMessages;
+use crate::mirror::core::transport::TransportChannel;
+use crate::{log_error, log_warn};
+use lazy_static::lazy_static;
+use std::borrow::Cow;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::RwLock;
+
+// 拦截器的处理结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InterceptAction {
+    Pass,
+    Drop,
+    // 替换为新的消息, 必须以消息 id 开头, 可以是另一种消息
+    Rewrite(Vec<u8>),
+}
+
+// (连接 id, 消息 id, 完整的消息, 通道)
+pub type OutboundInterceptorFunc =
+    Box<dyn Fn(u64, u16, &[u8], TransportChannel) -> InterceptAction + Send + Sync>;
+
+struct OutboundInterceptor {
+    id: u64,
+    // None 表示所有消息
+    message_id: Option<u16>,
+    func: OutboundInterceptorFunc,
+}
+
+// OutboundInterceptors 静态变量
+lazy_static! {
+    // 按注册顺序执行
+    static ref INTERCEPTORS: ContextLocal<RwLock<Vec<OutboundInterceptor>>> =
+        ContextLocal::new(|| RwLock::new(Vec::new()));
+    // 没有拦截器时 apply 只读取这个值
+    static ref COUNT: ContextLocal<AtomicUsize> = ContextLocal::new(|| AtomicUsize::new(0));
+    static ref NEXT_ID: ContextLocal<AtomicU64> = ContextLocal::new(|| AtomicU64::new(1));
+    static ref DROPPED_COUNT: ContextLocal<AtomicU64> = ContextLocal::new(|| AtomicU64::new(0));
+    static ref REWRITTEN_COUNT: ContextLocal<AtomicU64> = ContextLocal::new(|| AtomicU64::new(0));
+}
+
+// 服务器发出的消息在进入连接的 batcher 之前经过拦截器, 可以按连接丢弃或者改写
+// 例如去掉可疑玩家收到的高精度位置、按连接的语言替换字符串
+// 前一个拦截器改写后, 之后的拦截器看到改写后的消息; 任何一个拦截器丢弃时停止
+// VoiceRelay 快速通道直接发给 Transport, 不经过拦截器; 拦截器中不能添加或者移除拦截器
+pub struct OutboundInterceptors;
+
+impl OutboundInterceptors {
+    // 拦截所有消息, 返回用于 remove 的 id
+    pub fn add<F>(func: F) -> u64
+    where
+        F: Fn(u64, u16, &[u8], TransportChannel) -> InterceptAction + Send + Sync + 'static,
+    {
+        Self::insert(None, Box::new(func))
+    }
+
+    // 只拦截类型为 T 的消息
+    pub fn add_for<T, F>(func: F) -> u64
+    where
+        T: NetworkMessageTrait,
+        F: Fn(u64, u16, &[u8], TransportChannel) -> InterceptAction + Send + Sync + 'static,
+    {
+        Self::insert(Some(T::get_hash_code()), Box::new(func))
+    }
+
+    fn insert(message_id: Option<u16>, func: OutboundInterceptorFunc) -> u64 {
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        match INTERCEPTORS.write() {
+            Ok(mut interceptors) => {
+                interceptors.push(OutboundInterceptor {
+                    id,
+                    message_id,
+                    func,
+                });
+                COUNT.store(interceptors.len(), Ordering::Relaxed);
+            }
+            Err(e) => {
+                log_error!("OutboundInterceptors failed to write INTERCEPTORS: {:?}", e);
+            }
+        }
+        id
+    }
+
+    pub fn remove(id: u64) -> bool {
+        match INTERCEPTORS.write() {
+            Ok(mut interceptors) => {
+                let len = interceptors.len();
+                interceptors.retain(|interceptor| interceptor.id != id);
+                COUNT.store(interceptors.len(), Ordering::Relaxed);
+                interceptors.len() != len
+            }
+            Err(e) => {
+                log_error!("OutboundInterceptors failed to write INTERCEPTORS: {:?}", e);
+                false
+            }
+        }
+    }
+
+    pub fn count() -> usize {
+        COUNT.load(Ordering::Relaxed)
+    }
+
+    pub fn dropped_count() -> u64 {
+        DROPPED_COUNT.load(Ordering::Relaxed)
+    }
+
+    pub fn rewritten_count() -> u64 {
+        REWRITTEN_COUNT.load(Ordering::Relaxed)
+    }
+
+    // 在 NetworkConnection::send 中调用, 返回 None 表示丢弃
+    pub(crate) fn apply<'a>(
+        conn_id: u64,
+        channel: TransportChannel,
+        segment: &'a [u8],
+    ) -> Option<Cow<'a, [u8]>> {
+        if Self::count() == 0 || segment.len() < NetworkMessages::ID_SIZE {
+            return Some(Cow::Borrowed(segment));
+        }
+        let interceptors = match INTERCEPTORS.read() {
+            Ok(interceptors) => interceptors,
+            Err(e) => {
+                log_error!("OutboundInterceptors failed to read INTERCEPTORS: {:?}", e);
+                return Some(Cow::Borrowed(segment));
+            }
+        };
+        let mut segment = Cow::Borrowed(segment);
+        let mut rewritten = false;
+        for interceptor in interceptors.iter() {
+            if segment.len() < NetworkMessages::ID_SIZE {
+                break;
+            }
+            let message_id = u16::from_le_bytes([segment[0], segment[1]]);
+            if interceptor
+                .message_id
+                .is_some_and(|filter| filter != message_id)
+            {
+                continue;
+            }
+            match (interceptor.func)(conn_id, message_id, &segment, channel) {
+                InterceptAction::Pass => {}
+                InterceptAction::Drop => {
+                    DROPPED_COUNT.fetch_add(1, Ordering::Relaxed);
+                    return None;
+                }
+                InterceptAction::Rewrite(data) => {
+                    segment = Cow::Owned(data);
+                    rewritten = true;
+                }
+            }
+        }
+        if rewritten {
+            let max = NetworkMessages::max_message_size(channel);
+            if segment.len() < NetworkMessages::ID_SIZE || segment.len() > max {
+                log_warn!(
+                    "OutboundInterceptors: dropping invalid rewritten message of {} bytes for connection {}",
+                    segment.len(),
+                    conn_id
+                );
+                DROPPED_COUNT.fetch_add(1, Ordering::Relaxed);
+                return None;
+            }
+            REWRITTEN_COUNT.fetch_add(1, Ordering::Relaxed);
+        }
+        Some(segment)
+    }
+
+    pub fn reset() {
+        if let Ok(mut interceptors) = INTERCEPTORS.write() {
+            interceptors.clear();
+        }
+        COUNT.store(0, Ordering::Relaxed);
+        DROPPED_COUNT.store(0, Ordering::Relaxed);
+        REWRITTEN_COUNT.store(0, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::core::messages::{PauseMessage, QueuePositionMessage};
+    use crate::mirror::core::network_connection::NetworkConnectionTrait;
+    use crate::mirror::core::network_server::NetworkServerStatic;
+    use crate::mirror::core::network_writer::NetworkWriter;
+    use crate::mirror::transports::memory::memory_transport::test_util::*;
+    use crate::mirror::transports::memory::memory_transport::MemoryTransport;
+
+    #[test]
+    fn test_outbound_interceptors() {
+        with_server(|| {
+            MemoryTransport::client_connect(1);
+            MemoryTransport::client_connect(2);
+            tick();
+            MemoryTransport::client_receive(1);
+            MemoryTransport::client_receive(2);
+            let send = || {
+                for connection_id in [1, 2] {
+                    let mut connection = NetworkServerStatic::network_connections()
+                        .get_mut(&connection_id)
+                        .unwrap();
+                    connection.send_network_message(
+                        &mut PauseMessage::new(true),
+                        TransportChannel::Reliable,
+                    );
+                    connection.send_network_message(
+                        &mut QueuePositionMessage::new(3, 10),
+                        TransportChannel::Reliable,
+                    );
+                }
+                tick();
+            };
+
+            // 只丢弃连接 1 的 PauseMessage
+            let drop_id =
+                OutboundInterceptors::add_for::<PauseMessage, _>(|connection_id, _, _, _| {
+                    match connection_id {
+                        1 => InterceptAction::Drop,
+                        _ => InterceptAction::Pass,
+                    }
+                });
+            // 改写所有连接的 QueuePositionMessage
+            let rewrite_id = OutboundInterceptors::add(|_, message_id, _, _| {
+                if message_id != QueuePositionMessage::get_hash_code() {
+                    return InterceptAction::Pass;
+                }
+                let mut writer = NetworkWriter::new();
+                QueuePositionMessage::new(1, 1).serialize(&mut writer);
+                InterceptAction::Rewrite(writer.to_bytes())
+            });
+            assert_eq!(OutboundInterceptors::count(), 2);
+            send();
+            let messages = MemoryTransport::client_receive_messages(1);
+            assert!(decode::<PauseMessage>(&messages).is_empty());
+            let positions = decode::<QueuePositionMessage>(&messages);
+            assert_eq!(positions.len(), 1);
+            assert_eq!(positions[0].position, 1);
+            let messages = MemoryTransport::client_receive_messages(2);
+            assert_eq!(decode::<PauseMessage>(&messages).len(), 1);
+            assert_eq!(decode::<QueuePositionMessage>(&messages)[0].position, 1);
+            assert_eq!(OutboundInterceptors::dropped_count(), 1);
+            assert_eq!(OutboundInterceptors::rewritten_count(), 2);
+
+            // 改写后的消息不合法时丢弃
+            let invalid_id = OutboundInterceptors::add_for::<PauseMessage, _>(|_, _, _, _| {
+                InterceptAction::Rewrite(Vec::new())
+            });
+            send();
+            assert!(received::<PauseMessage>(2).is_empty());
+            assert_eq!(OutboundInterceptors::dropped_count(), 3);
+            MemoryTransport::client_receive(1);
+
+            // 移除后照常发送
+            assert!(OutboundInterceptors::remove(drop_id));
+            assert!(OutboundInterceptors::remove(rewrite_id));
+            assert!(OutboundInterceptors::remove(invalid_id));
+            assert!(!OutboundInterceptors::remove(drop_id));
+            assert_eq!(OutboundInterceptors::count(), 0);
+            send();
+            let messages = MemoryTransport::client_receive_messages(1);
+            assert_eq!(decode::<PauseMessage>(&messages).len(), 1);
+            assert_eq!(decode::<QueuePositionMessage>(&messages)[0].position, 3);
+            OutboundInterceptors::reset();
+        });
+    }
+}
@@ -0,0 +1,278 @@
+use crate::log_error;
+use crate::mirror::core::network_context::ContextLocal;
+use crate::mirror::core::network_events::NetworkEvents;
+use crate::mirror::core::network_server::NetworkServerStatic;
+use atomic::Atomic;
+use lazy_static::lazy_static;
+use std::sync::atomic::Ordering;
+use std::sync::RwLock;
+
+// 降级阶梯, 每一级包含之前所有级别的措施
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum OverloadLevel {
+    #[default]
+    Normal = 0,
+    // NetworkTransform 按 send_interval_multiplier 倍的间隔发送
+    ReduceSendRate = 1,
+    // 暂停 InterestRadius 的观察者重建
+    PauseInterestRebuild = 2,
+    // 每个 RPC 最多发送给 rpc_fan_out_limit 个观察者
+    CapRpcFanOut = 3,
+}
+
+impl OverloadLevel {
+    pub fn from(value: u8) -> OverloadLevel {
+        match value {
+            1 => OverloadLevel::ReduceSendRate,
+            2 => OverloadLevel::PauseInterestRebuild,
+            3 => OverloadLevel::CapRpcFanOut,
+            _ => OverloadLevel::Normal,
+        }
+    }
+    pub fn to_u8(&self) -> u8 {
+        *self as u8
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OverloadSettings {
+    // 每个 tick 的时间预算 (秒), 0 表示使用 tick_interval
+    pub tick_budget: f64,
+    // 平均 tick 时间连续超过预算多少个 tick 后升一级
+    pub overload_ticks: u32,
+    // 平均 tick 时间连续低于 预算 * recovery_ratio 多少个 tick 后降一级
+    pub recovery_ticks: u32,
+    pub recovery_ratio: f64,
+    // 最高降级到哪一级
+    pub max_level: OverloadLevel,
+    pub send_interval_multiplier: u32,
+    pub rpc_fan_out_limit: usize,
+}
+
+impl Default for OverloadSettings {
+    fn default() -> Self {
+        Self {
+            tick_budget: 0.0,
+            overload_ticks: 30,
+            recovery_ticks: 120,
+            recovery_ratio: 0.8,
+            max_level: OverloadLevel::CapRpcFanOut,
+            send_interval_multiplier: 2,
+            rpc_fan_out_limit: 32,
+        }
+    }
+}
+
+// OverloadController 静态变量
+lazy_static! {
+    static ref ENABLED: ContextLocal<Atomic<bool>> = ContextLocal::new(|| Atomic::new(false));
+    static ref SETTINGS: ContextLocal<RwLock<OverloadSettings>> =
+        ContextLocal::new(|| RwLock::new(OverloadSettings::default()));
+    static ref LEVEL: ContextLocal<Atomic<u8>> =
+        ContextLocal::new(|| Atomic::new(OverloadLevel::Normal.to_u8()));
+    static ref OVER_BUDGET_TICKS: ContextLocal<Atomic<u32>> = ContextLocal::new(|| Atomic::new(0));
+    static ref RECOVERED_TICKS: ContextLocal<Atomic<u32>> = ContextLocal::new(|| Atomic::new(0));
+    // 因 CapRpcFanOut 没有发送的 RPC 数量 (每个连接计一次)
+    static ref RPC_CAPPED_COUNT: ContextLocal<Atomic<u64>> = ContextLocal::new(|| Atomic::new(0));
+}
+
+// 服务器过载时逐级降低负载, 负载恢复后逐级还原
+// 按 NetworkServerStatic::full_update_duration 的平均值判断, 级别变化时发布 NetworkEvents::on_overload
+pub struct OverloadController;
+
+impl OverloadController {
+    pub fn enabled() -> bool {
+        ENABLED.load(Ordering::Relaxed)
+    }
+
+    pub fn set_enabled(value: bool) {
+        ENABLED.store(value, Ordering::Relaxed);
+        if !value {
+            Self::set_level(OverloadLevel::Normal, 0.0);
+        }
+    }
+
+    pub fn settings() -> OverloadSettings {
+        match SETTINGS.read() {
+            Ok(settings) => settings.clone(),
+            Err(e) => {
+                log_error!("OverloadController failed to get settings: {:?}", e);
+                OverloadSettings::default()
+            }
+        }
+    }
+
+    pub fn set_settings(settings: OverloadSettings) {
+        match SETTINGS.write() {
+            Ok(mut s) => *s = settings,
+            Err(e) => {
+                log_error!("OverloadController failed to set settings: {:?}", e);
+            }
+        }
+    }
+
+    pub fn level() -> OverloadLevel {
+        OverloadLevel::from(LEVEL.load(Ordering::Relaxed))
+    }
+
+    pub fn tick_budget() -> f64 {
+        match Self::settings().tick_budget {
+            budget if budget > 0.0 => budget,
+            _ => NetworkServerStatic::tick_interval() as f64,
+        }
+    }
+
+    // NetworkTransform 服务器广播使用的发送间隔
+    pub fn send_interval() -> f64 {
+        let send_interval = NetworkServerStatic::send_interval() as f64;
+        if Self::level() >= OverloadLevel::ReduceSendRate {
+            send_interval * Self::settings().send_interval_multiplier.max(1) as f64
+        } else {
+            send_interval
+        }
+    }
+
+    pub fn interest_rebuild_paused() -> bool {
+        Self::level() >= OverloadLevel::PauseInterestRebuild
+    }
+
+    // 每个 RPC 最多发送的连接数, 没有限制时为 usize::MAX
+    pub fn rpc_fan_out_limit() -> usize {
+        if Self::level() >= OverloadLevel::CapRpcFanOut {
+            Self::settings().rpc_fan_out_limit
+        } else {
+            usize::MAX
+        }
+    }
+
+    pub fn rpc_capped_count() -> u64 {
+        RPC_CAPPED_COUNT.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn add_rpc_capped_count(count: u64) {
+        RPC_CAPPED_COUNT.fetch_add(count, Ordering::Relaxed);
+    }
+
+    // 在 NetworkServer::network_late_update 末尾调用
+    pub fn update() {
+        if !Self::enabled() {
+            return;
+        }
+        let average = match NetworkServerStatic::full_update_duration().read() {
+            Ok(duration) => duration.average(),
+            Err(e) => {
+                log_error!(
+                    "OverloadController failed to get full update duration: {:?}",
+                    e
+                );
+                return;
+            }
+        };
+        Self::record_tick_time(average);
+    }
+
+    // 记录一次平均 tick 时间并调整级别
+    pub fn record_tick_time(average: f64) {
+        let settings = Self::settings();
+        let budget = Self::tick_budget();
+        let level = Self::level();
+        if average > budget {
+            RECOVERED_TICKS.store(0, Ordering::Relaxed);
+            let ticks = OVER_BUDGET_TICKS.fetch_add(1, Ordering::Relaxed) + 1;
+            if ticks >= settings.overload_ticks && level < settings.max_level {
+                OVER_BUDGET_TICKS.store(0, Ordering::Relaxed);
+                Self::set_level(OverloadLevel::from(level.to_u8() + 1), average);
+            }
+        } else if average <= budget * settings.recovery_ratio {
+            OVER_BUDGET_TICKS.store(0, Ordering::Relaxed);
+            let ticks = RECOVERED_TICKS.fetch_add(1, Ordering::Relaxed) + 1;
+            if ticks >= settings.recovery_ticks && level > OverloadLevel::Normal {
+                RECOVERED_TICKS.store(0, Ordering::Relaxed);
+                Self::set_level(OverloadLevel::from(level.to_u8() - 1), average);
+            }
+        } else {
+            // 预算附近不升也不降
+            OVER_BUDGET_TICKS.store(0, Ordering::Relaxed);
+            RECOVERED_TICKS.store(0, Ordering::Relaxed);
+        }
+    }
+
+    fn set_level(level: OverloadLevel, average_tick_time: f64) {
+        let previous = OverloadLevel::from(LEVEL.swap(level.to_u8(), Ordering::Relaxed));
+        if previous != level {
+            NetworkEvents::publish_overload(previous, level, average_tick_time);
+        }
+    }
+
+    pub fn reset() {
+        LEVEL.store(OverloadLevel::Normal.to_u8(), Ordering::Relaxed);
+        OVER_BUDGET_TICKS.store(0, Ordering::Relaxed);
+        RECOVERED_TICKS.store(0, Ordering::Relaxed);
+        RPC_CAPPED_COUNT.store(0, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::transports::memory::memory_transport::test_util::with_isolated_context;
+
+    #[test]
+    fn test_overload_ladder() {
+        with_isolated_context(|| {
+            OverloadController::set_settings(OverloadSettings {
+                tick_budget: 0.01,
+                overload_ticks: 2,
+                recovery_ticks: 3,
+                max_level: OverloadLevel::PauseInterestRebuild,
+                ..Default::default()
+            });
+            OverloadController::set_enabled(true);
+            let events = NetworkEvents::on_overload();
+
+            OverloadController::record_tick_time(0.02);
+            assert_eq!(OverloadController::level(), OverloadLevel::Normal);
+            OverloadController::record_tick_time(0.02);
+            assert_eq!(OverloadController::level(), OverloadLevel::ReduceSendRate);
+            assert_eq!(
+                OverloadController::send_interval(),
+                NetworkServerStatic::send_interval() as f64 * 2.0
+            );
+            for _ in 0..10 {
+                OverloadController::record_tick_time(0.02);
+            }
+            // 不超过 max_level
+            assert_eq!(
+                OverloadController::level(),
+                OverloadLevel::PauseInterestRebuild
+            );
+            assert!(OverloadController::interest_rebuild_paused());
+            assert_eq!(OverloadController::rpc_fan_out_limit(), usize::MAX);
+
+            // 预算附近不恢复
+            for _ in 0..10 {
+                OverloadController::record_tick_time(0.009);
+            }
+            assert_eq!(
+                OverloadController::level(),
+                OverloadLevel::PauseInterestRebuild
+            );
+            for _ in 0..3 {
+                OverloadController::record_tick_time(0.001);
+            }
+            assert_eq!(OverloadController::level(), OverloadLevel::ReduceSendRate);
+
+            OverloadController::set_enabled(false);
+            let levels: Vec<OverloadLevel> = events.try_iter().map(|event| event.level).collect();
+            assert_eq!(
+                levels,
+                vec![
+                    OverloadLevel::ReduceSendRate,
+                    OverloadLevel::PauseInterestRebuild,
+                    OverloadLevel::ReduceSendRate,
+                    OverloadLevel::Normal,
+                ]
+            );
+        });
+    }
+}
@@ -0,0 +1,271 @@
+use crate::log_error;
+use crate::mirror::core::network_connection::NetworkConnectionTrait;
+use crate::mirror::core::network_context::{ContextLocal, NetworkContext};
+use crate::mirror::core::network_server::NetworkServerStatic;
+use crossbeam_channel::{bounded, unbounded, Receiver, Sender};
+use dashmap::try_result::TryResult;
+use lazy_static::lazy_static;
+use std::cell::Cell;
+use std::collections::BTreeSet;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+struct SerializeJob {
+    net_ids: Vec<u32>,
+    tick: u32,
+    // 提交任务的上下文
+    context: usize,
+    // 完成后返回已序列化的数量
+    done: Sender<usize>,
+}
+
+// ParallelSerialization 静态变量
+lazy_static! {
+    static ref JOBS: (Sender<SerializeJob>, Receiver<SerializeJob>) = unbounded();
+    // 0 或 1 表示不并行
+    static ref WORKER_COUNT: AtomicUsize = AtomicUsize::new(0);
+    static ref STARTED_WORKERS: AtomicUsize = AtomicUsize::new(0);
+    // 需要序列化的对象少于这个数量时串行更快
+    static ref MIN_IDENTITIES: AtomicUsize = AtomicUsize::new(256);
+    static ref LAST_PARALLEL_COUNT: ContextLocal<AtomicUsize> =
+        ContextLocal::new(|| AtomicUsize::new(0));
+    static ref LAST_FALLBACK_COUNT: ContextLocal<AtomicUsize> =
+        ContextLocal::new(|| AtomicUsize::new(0));
+}
+
+thread_local! {
+    static IN_WORKER: Cell<bool> = const { Cell::new(false) };
+}
+
+// 并行序列化: broadcast 之前把本 tick 需要广播的对象分给工作线程,
+// 结果写入每个 NetworkIdentity 的 last_serialization (按 tick 缓存),
+// broadcast 仍然在主线程按每个连接的 observing 顺序组包, 输出与串行时完全相同
+// 工作线程没能序列化的对象 (例如分片被占用) 由 broadcast 在主线程序列化
+pub struct ParallelSerialization;
+
+impl ParallelSerialization {
+    // 等待分片锁的最大重试次数
+    const LOCK_RETRIES: usize = 1024;
+
+    pub fn worker_count() -> usize {
+        WORKER_COUNT.load(Ordering::Relaxed)
+    }
+
+    // 已经启动的工作线程不会退出
+    pub fn set_worker_count(value: usize) {
+        WORKER_COUNT.store(value, Ordering::Relaxed);
+    }
+
+    pub fn min_identities() -> usize {
+        MIN_IDENTITIES.load(Ordering::Relaxed)
+    }
+
+    pub fn set_min_identities(value: usize) {
+        MIN_IDENTITIES.store(value, Ordering::Relaxed);
+    }
+
+    pub fn enabled() -> bool {
+        Self::worker_count() > 1
+    }
+
+    // 上一个 tick 在工作线程中序列化的对象数量
+    pub fn last_parallel_count() -> usize {
+        LAST_PARALLEL_COUNT.load(Ordering::Relaxed)
+    }
+
+    // 上一个 tick 留给主线程序列化的对象数量
+    pub fn last_fallback_count() -> usize {
+        LAST_FALLBACK_COUNT.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn in_worker() -> bool {
+        IN_WORKER.with(|in_worker| in_worker.get())
+    }
+
+    // 工作线程之间只会短暂地占用同一个分片, 稍等后重试; 主线程中直接返回
+    pub(crate) fn retry_locked<T, F: FnMut() -> TryResult<T>>(mut func: F) -> TryResult<T> {
+        if !Self::in_worker() {
+            return func();
+        }
+        for _ in 0..Self::LOCK_RETRIES {
+            match func() {
+                TryResult::Locked => thread::yield_now(),
+                result => return result,
+            }
+        }
+        func()
+    }
+
+    // 在 NetworkServer::broadcast 之前调用
+    pub(crate) fn serialize(tick: u32) {
+        LAST_PARALLEL_COUNT.store(0, Ordering::Relaxed);
+        LAST_FALLBACK_COUNT.store(0, Ordering::Relaxed);
+        if !Self::enabled() || NetworkServerStatic::paused() {
+            return;
+        }
+
+        // 只序列化本 tick 会广播给某个连接的对象, 与串行时清除脏标记的范围一致
+        let mut net_ids = BTreeSet::new();
+        NetworkServerStatic::for_each_network_connection(|connection| {
+            if connection.is_ready() && connection.disconnect_reason.is_none() {
+                net_ids.extend(connection.observing.iter().filter(|net_id| **net_id != 0));
+            }
+        });
+        if net_ids.len() < Self::min_identities() {
+            return;
+        }
+        let net_ids: Vec<u32> = net_ids.into_iter().collect();
+
+        Self::ensure_workers();
+        let worker_count = Self::worker_count().min(STARTED_WORKERS.load(Ordering::Relaxed));
+        if worker_count == 0 {
+            return;
+        }
+        let chunk_size = net_ids.len().div_ceil(worker_count);
+        let (done, results) = bounded(worker_count);
+        let mut jobs = 0;
+        for chunk in net_ids.chunks(chunk_size) {
+            let job = SerializeJob {
+                net_ids: chunk.to_vec(),
+                tick,
+                context: NetworkContext::current(),
+                done: done.clone(),
+            };
+            if JOBS.0.send(job).is_ok() {
+                jobs += 1;
+            }
+        }
+        drop(done);
+
+        let mut serialized = 0;
+        for _ in 0..jobs {
+            match results.recv() {
+                Ok(count) => serialized += count,
+                // 工作线程 panic
+                Err(_) => break,
+            }
+        }
+        LAST_PARALLEL_COUNT.store(serialized, Ordering::Relaxed);
+        LAST_FALLBACK_COUNT.store(net_ids.len() - serialized, Ordering::Relaxed);
+    }
+
+    fn ensure_workers() {
+        while STARTED_WORKERS.load(Ordering::Relaxed) < Self::worker_count() {
+            let index = STARTED_WORKERS.fetch_add(1, Ordering::Relaxed);
+            let receiver = JOBS.1.clone();
+            let spawned = thread::Builder::new()
+                .name(format!("serialize-{}", index))
+                .spawn(move || {
+                    IN_WORKER.with(|in_worker| in_worker.set(true));
+                    while let Ok(job) = receiver.recv() {
+                        match catch_unwind(AssertUnwindSafe(|| {
+                            NetworkContext::enter(job.context, || {
+                                Self::serialize_chunk(&job.net_ids, job.tick)
+                            })
+                        })) {
+                            Ok(count) => {
+                                let _ = job.done.send(count);
+                            }
+                            Err(_) => {
+                                log_error!(
+                                    "ParallelSerialization worker panicked while serializing"
+                                );
+                            }
+                        }
+                    }
+                });
+            if let Err(e) = spawned {
+                log_error!("ParallelSerialization failed to start worker: {}", e);
+                STARTED_WORKERS.fetch_sub(1, Ordering::Relaxed);
+                return;
+            }
+        }
+    }
+
+    fn serialize_chunk(net_ids: &[u32], tick: u32) -> usize {
+        let spawned = NetworkServerStatic::spawned_network_identities();
+        let mut count = 0;
+        for net_id in net_ids {
+            if let TryResult::Present(mut identity) =
+                Self::retry_locked(|| spawned.try_get_mut(net_id))
+            {
+                identity.get_server_serialization_at_tick(tick);
+                count += 1;
+            }
+        }
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::core::messages::EntityStateMessage;
+    use crate::mirror::core::network_identity::Visibility;
+    use crate::mirror::core::network_server::{NetworkServer, NETWORK_BEHAVIOURS};
+    use crate::mirror::core::network_time::NetworkTime;
+    use crate::mirror::transports::memory::memory_transport::test_util::*;
+    use crate::mirror::transports::memory::memory_transport::MemoryTransport;
+
+    #[test]
+    fn test_parallel_serialization() {
+        with_server(|| {
+            for conn_id in [1, 2] {
+                MemoryTransport::client_connect(conn_id);
+            }
+            tick();
+            NetworkServer::set_client_ready(1);
+            NetworkServer::set_client_ready(2);
+            let net_ids: Vec<u32> = (900..940).collect();
+            for net_id in net_ids.iter() {
+                spawn_test_identity(*net_id, 0, 1);
+                NetworkServer::set_visibility(*net_id, Visibility::Default);
+            }
+            tick();
+            for conn_id in [1, 2] {
+                MemoryTransport::client_receive(conn_id);
+            }
+
+            let broadcast = || {
+                NetworkTime::increment_frame_count();
+                for net_id in net_ids.iter() {
+                    let mut behaviour = NETWORK_BEHAVIOURS.get_mut(&(*net_id, 0)).unwrap();
+                    behaviour.set_sync_var_dirty_bits(1);
+                    behaviour.set_last_sync_time(-1.0);
+                }
+                tick();
+                [1, 2].map(|conn_id| {
+                    decode::<EntityStateMessage>(&MemoryTransport::client_receive_messages(conn_id))
+                })
+            };
+
+            let serial = broadcast();
+            assert_eq!(serial[0].len(), net_ids.len());
+            assert_eq!(ParallelSerialization::last_parallel_count(), 0);
+
+            // 输出与串行时完全相同
+            ParallelSerialization::set_worker_count(4);
+            ParallelSerialization::set_min_identities(8);
+            let parallel = broadcast();
+            assert_eq!(parallel, serial);
+            assert_eq!(
+                ParallelSerialization::last_parallel_count()
+                    + ParallelSerialization::last_fallback_count(),
+                net_ids.len()
+            );
+            assert!(ParallelSerialization::last_parallel_count() > 0);
+
+            // 对象太少时不并行
+            ParallelSerialization::set_min_identities(1000);
+            assert_eq!(broadcast(), serial);
+            assert_eq!(ParallelSerialization::last_parallel_count(), 0);
+
+            ParallelSerialization::set_worker_count(0);
+            ParallelSerialization::set_min_identities(256);
+            for net_id in net_ids.iter() {
+                NetworkServerStatic::remove_spawned_network_identity(net_id);
+            }
+        });
+    }
+}
@@ -0,0 +1,565 @@
+use crate::log_warn;
+use crate::mirror::core::backend_data::{BackendDataStatic, SyncVarData};
+use crate::mirror::core::messages::SpawnMessage;
+use crate::mirror::core::network_server::{NetworkServer, NetworkServerStatic, NETWORK_BEHAVIOURS};
+use crate::mirror::core::tools::utils::to_hex_string;
+use dashmap::try_result::TryResult;
+use std::fmt::{Display, Formatter};
+
+// 解码时使用的组件布局: 组件类型和按索引排序的同步变量
+#[derive(Debug, Clone)]
+pub struct ComponentLayout {
+    pub component: String,
+    pub sync_vars: Vec<SyncVarData>,
+}
+
+impl ComponentLayout {
+    pub fn new(component: &str, sync_vars: Vec<SyncVarData>) -> Self {
+        Self {
+            component: component.to_string(),
+            sync_vars,
+        }
+    }
+
+    // 同步变量来自后端数据, 与 NetworkCommonBehaviour 使用的相同
+    pub fn from_backend(component: &str) -> Self {
+        let sync_vars =
+            BackendDataStatic::get_backend_data().get_sync_var_data_s_by_sub_class(component);
+        Self::new(component, sync_vars)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedField {
+    pub name: String,
+    pub type_name: String,
+    // 在 payload 中的偏移
+    pub offset: usize,
+    pub value: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedComponent {
+    pub index: u8,
+    // 布局中没有该索引时为 "?"
+    pub component: String,
+    // 安全字节所在的偏移
+    pub offset: usize,
+    pub safety: u8,
+    // 组件内容, 不包括安全字节
+    pub content: Vec<u8>,
+    // 组件内容无法按同步变量解码时为空
+    pub fields: Vec<DecodedField>,
+    // 初始状态时组件被禁用, 或者 delta 中 enabled 有修改
+    pub enabled: Option<bool>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedPayload {
+    pub initial_state: bool,
+    pub size: usize,
+    pub mask: u64,
+    pub components: Vec<DecodedComponent>,
+    // 解码所有组件之后剩余的字节
+    pub trailing: Vec<u8>,
+    // 第一个无法继续解码的位置
+    pub error: Option<String>,
+}
+
+impl DecodedPayload {
+    // 所有组件的安全字节都匹配并且没有多余的字节
+    pub fn is_valid(&self) -> bool {
+        self.error.is_none()
+            && self.trailing.is_empty()
+            && self
+                .components
+                .iter()
+                .all(|component| component.error.is_none())
+    }
+}
+
+impl Display for DecodedPayload {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} payload {}B mask {:#b}",
+            if self.initial_state {
+                "initial"
+            } else {
+                "delta"
+            },
+            self.size,
+            self.mask
+        )?;
+        for component in self.components.iter() {
+            write!(
+                f,
+                "\n  [{}] {} @{} safety {} content {}B: {}",
+                component.index,
+                component.component,
+                component.offset,
+                component.safety,
+                component.content.len(),
+                to_hex_string(&component.content)
+            )?;
+            for field in component.fields.iter() {
+                write!(
+                    f,
+                    "\n    {}: {} @{} = {}",
+                    field.name,
+                    field.type_name,
+                    field.offset,
+                    format_value(&field.type_name, &field.value)
+                )?;
+            }
+            if let Some(enabled) = component.enabled {
+                write!(f, "\n    enabled = {}", enabled)?;
+            }
+            if let Some(error) = component.error.as_ref() {
+                write!(f, "\n    error: {}", error)?;
+            }
+        }
+        if !self.trailing.is_empty() {
+            write!(
+                f,
+                "\n  trailing {}B: {}",
+                self.trailing.len(),
+                to_hex_string(&self.trailing)
+            )?;
+        }
+        if let Some(error) = self.error.as_ref() {
+            write!(f, "\n  error: {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+// 常见类型显示为可读的值, 其它类型只显示十六进制
+fn format_value(type_name: &str, value: &[u8]) -> String {
+    let hex = to_hex_string(value);
+    let float = |bytes: &[u8]| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    let readable = match (type_name, value.len()) {
+        ("System.Boolean", 1) => Some((value[0] != 0).to_string()),
+        ("System.Byte", 1) => Some(value[0].to_string()),
+        ("System.Int32", 4) => {
+            Some(i32::from_le_bytes([value[0], value[1], value[2], value[3]]).to_string())
+        }
+        ("System.UInt32", 4) => {
+            Some(u32::from_le_bytes([value[0], value[1], value[2], value[3]]).to_string())
+        }
+        ("System.Single" | "System.Float", 4) => Some(float(value).to_string()),
+        ("UnityEngine.Vector3", 12) => Some(format!(
+            "({}, {}, {})",
+            float(&value[0..4]),
+            float(&value[4..8]),
+            float(&value[8..12])
+        )),
+        ("System.String", _) => value
+            .get(2..)
+            .map(|bytes| format!("{:?}", String::from_utf8_lossy(bytes))),
+        _ => None,
+    };
+    match readable {
+        Some(readable) => format!("{} ({})", readable, hex),
+        None => hex,
+    }
+}
+
+// compress_var_ulong 的字节数, 由第一个字节决定
+fn var_ulong_size(first: u8) -> usize {
+    match first {
+        0..=240 => 1,
+        241..=248 => 2,
+        249 => 3,
+        _ => first as usize - 246,
+    }
+}
+
+struct PayloadCursor<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> PayloadCursor<'a> {
+    fn remaining(&self) -> usize {
+        self.data.len() - self.position
+    }
+
+    fn take(&mut self, count: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.position..self.position + count)?;
+        self.position += count;
+        Some(bytes)
+    }
+
+    fn read_var_ulong(&mut self) -> Option<u64> {
+        let first = *self.data.get(self.position)?;
+        let bytes = self.take(var_ulong_size(first))?;
+        let tail = |count: usize| {
+            bytes[1..=count]
+                .iter()
+                .rev()
+                .fold(0u64, |value, byte| (value << 8) | *byte as u64)
+        };
+        Some(match first {
+            0..=240 => first as u64,
+            241..=248 => 240 + ((first as u64 - 241) << 8) + bytes[1] as u64,
+            249 => 2288 + ((bytes[1] as u64) << 8) + bytes[2] as u64,
+            _ => tail(bytes.len() - 1),
+        })
+    }
+
+    // 同步变量的字节数: 字符串按长度前缀, 其它类型与后端数据中的初始值等长
+    fn read_sync_var(&mut self, sync_var: &SyncVarData) -> Result<&'a [u8], String> {
+        let start = self.position;
+        let size = match sync_var.r#type.as_str() {
+            "System.String" => {
+                let length = self
+                    .data
+                    .get(start..start + 2)
+                    .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]) as usize)
+                    .ok_or_else(|| format!("{}: missing string length", sync_var.name))?;
+                2 + length.saturating_sub(1)
+            }
+            _ if sync_var.value.is_empty() => {
+                return Err(format!(
+                    "{}: unknown size for {}",
+                    sync_var.name, sync_var.r#type
+                ));
+            }
+            _ => sync_var.value.len(),
+        };
+        self.take(size).ok_or_else(|| {
+            format!(
+                "{}: needs {}B, {}B left",
+                sync_var.name,
+                size,
+                self.remaining()
+            )
+        })
+    }
+}
+
+// 解码 SpawnMessage.payload 或 EntityStateMessage.payload, 逐个组件列出掩码、安全字节和同步变量
+// 用于排查客户端 "spawn failed to deserialize": 安全字节不匹配的组件就是两端布局不一致的组件
+// 只能解码 NetworkCommonBehaviour 的同步变量, 其它组件 (例如 NetworkTransform) 按安全字节整体显示
+pub struct PayloadDecoder;
+
+impl PayloadDecoder {
+    // layouts 按组件索引排列
+    pub fn decode(
+        payload: &[u8],
+        layouts: &[ComponentLayout],
+        initial_state: bool,
+    ) -> DecodedPayload {
+        let mut cursor = PayloadCursor {
+            data: payload,
+            position: 0,
+        };
+        let mut decoded = DecodedPayload {
+            initial_state,
+            size: payload.len(),
+            mask: 0,
+            components: Vec::new(),
+            trailing: Vec::new(),
+            error: None,
+        };
+        if payload.is_empty() {
+            return decoded;
+        }
+        decoded.mask = match cursor.read_var_ulong() {
+            Some(mask) => mask,
+            None => {
+                decoded.error = Some("truncated component mask".to_string());
+                return decoded;
+            }
+        };
+        for index in 0..64u8 {
+            if decoded.mask & (1 << index) == 0 {
+                continue;
+            }
+            let offset = cursor.position;
+            let safety = match cursor.take(1) {
+                Some(safety) => safety[0],
+                None => {
+                    decoded.error =
+                        Some(format!("component {} missing at offset {}", index, offset));
+                    return decoded;
+                }
+            };
+            let layout = layouts.get(index as usize);
+            let component = Self::decode_component(&mut cursor, layout, safety, initial_state);
+            decoded.components.push(DecodedComponent {
+                index,
+                component: layout.map_or("?".to_string(), |layout| layout.component.clone()),
+                offset,
+                safety,
+                ..component
+            });
+        }
+        decoded.trailing = payload[cursor.position..].to_vec();
+        decoded
+    }
+
+    fn decode_component(
+        cursor: &mut PayloadCursor,
+        layout: Option<&ComponentLayout>,
+        safety: u8,
+        initial_state: bool,
+    ) -> DecodedComponent {
+        let start = cursor.position;
+        let mut component = DecodedComponent {
+            index: 0,
+            component: String::new(),
+            offset: 0,
+            safety,
+            content: Vec::new(),
+            fields: Vec::new(),
+            enabled: None,
+            error: None,
+        };
+        if let Some(layout) = layout.filter(|layout| !layout.sync_vars.is_empty()) {
+            match Self::decode_sync_vars(cursor, layout, initial_state) {
+                Ok(fields) => {
+                    component.fields = fields;
+                    // 末尾的 enabled 让安全字节多 1
+                    let size = cursor.position - start;
+                    if (size + 1) as u8 == safety && cursor.remaining() > 0 {
+                        component.enabled = cursor.take(1).map(|enabled| enabled[0] != 0);
+                    }
+                }
+                Err(error) => {
+                    cursor.position = start;
+                    component.error = Some(error);
+                }
+            }
+        }
+        // 无法按同步变量解码时, 按安全字节推断组件大小 (小于 256 字节)
+        if component.fields.is_empty() {
+            match cursor.take(safety as usize) {
+                Some(_) => {}
+                None => {
+                    let error = format!("content needs {}B, {}B left", safety, cursor.remaining());
+                    component.error = Some(match component.error.take() {
+                        Some(previous) => format!("{}; {}", previous, error),
+                        None => error,
+                    });
+                    cursor.position = cursor.data.len();
+                }
+            }
+        }
+        component.content = cursor.data[start..cursor.position].to_vec();
+        let size = component.content.len() as u8;
+        if component.error.is_none() && size != safety {
+            component.error = Some(format!(
+                "safety mismatch: decoded {}B, expected {}",
+                component.content.len(),
+                safety
+            ));
+        }
+        component
+    }
+
+    fn decode_sync_vars(
+        cursor: &mut PayloadCursor,
+        layout: &ComponentLayout,
+        initial_state: bool,
+    ) -> Result<Vec<DecodedField>, String> {
+        let mut dirty = u64::MAX;
+        if !initial_state {
+            // delta 先写 SyncObject 的 ulong 掩码, NetworkCommonBehaviour 没有 SyncObject
+            match cursor.take(8) {
+                Some(bytes) if bytes.iter().all(|byte| *byte == 0) => {}
+                Some(_) => return Err("unexpected sync object delta".to_string()),
+                None => return Err("missing sync object mask".to_string()),
+            }
+            dirty = cursor
+                .read_var_ulong()
+                .ok_or_else(|| "missing sync var dirty bits".to_string())?;
+        }
+        let mut fields = Vec::new();
+        for (i, sync_var) in layout.sync_vars.iter().enumerate().take(64) {
+            if dirty & (1 << i) == 0 {
+                continue;
+            }
+            let offset = cursor.position;
+            let value = cursor.read_sync_var(sync_var)?;
+            fields.push(DecodedField {
+                name: sync_var.name.clone(),
+                type_name: sync_var.r#type.clone(),
+                offset,
+                value: value.to_vec(),
+            });
+        }
+        Ok(fields)
+    }
+
+    // 按 asset_id 或 scene_id 从后端数据取组件布局
+    pub fn layouts(asset_id: u32, scene_id: u64) -> Vec<ComponentLayout> {
+        let backend_data = BackendDataStatic::get_backend_data();
+        let mut components = if scene_id != 0 {
+            backend_data
+                .get_network_identity_data_network_behaviour_components_by_scene_id(scene_id)
+        } else {
+            backend_data
+                .get_network_identity_data_network_behaviour_components_by_asset_id(asset_id)
+        };
+        components.sort_by_key(|component| component.index);
+        components
+            .iter()
+            .map(|component| ComponentLayout::from_backend(&component.sub_class))
+            .collect()
+    }
+
+    pub fn decode_spawn(message: &SpawnMessage) -> DecodedPayload {
+        let layouts = Self::layouts(message.asset_id, message.scene_id);
+        Self::decode(&message.payload, &layouts, true)
+    }
+
+    // 服务器当前会发给新观察者 (is_owner 时为所有者) 的 SpawnMessage.payload
+    pub fn server_spawn_payload(net_id: u32, is_owner: bool) -> Option<Vec<u8>> {
+        match NetworkServerStatic::spawned_network_identities().try_get_mut(&net_id) {
+            TryResult::Present(mut identity) => Some(NetworkServer::create_spawn_message_payload(
+                is_owner,
+                &mut identity,
+            )),
+            TryResult::Absent => None,
+            TryResult::Locked => {
+                log_warn!("PayloadDecoder: netId {} is locked.", net_id);
+                None
+            }
+        }
+    }
+
+    // 已生成对象的组件布局, 组件类型取自运行时的组件
+    pub fn server_layouts(net_id: u32) -> Vec<ComponentLayout> {
+        let count = match NetworkServerStatic::spawned_network_identities().try_get(&net_id) {
+            TryResult::Present(identity) => identity.network_behaviours_count,
+            _ => return Vec::new(),
+        };
+        (0..count)
+            .map(|index| match NETWORK_BEHAVIOURS.try_get(&(net_id, index)) {
+                TryResult::Present(component) => {
+                    ComponentLayout::from_backend(&component.sub_class())
+                }
+                _ => ComponentLayout::new("?", Vec::new()),
+            })
+            .collect()
+    }
+
+    // 对比客户端收到的 payload 和服务器当前的 payload, 输出两者的解码结果和第一个不同的字节
+    // 对象不存在时返回 None
+    pub fn diff(net_id: u32, is_owner: bool, payload: &[u8]) -> Option<String> {
+        let expected = Self::server_spawn_payload(net_id, is_owner)?;
+        Some(Self::diff_payloads(
+            &expected,
+            payload,
+            &Self::server_layouts(net_id),
+            true,
+        ))
+    }
+
+    pub fn diff_payloads(
+        expected: &[u8],
+        actual: &[u8],
+        layouts: &[ComponentLayout],
+        initial_state: bool,
+    ) -> String {
+        let first_difference = expected
+            .iter()
+            .zip(actual.iter())
+            .position(|(a, b)| a != b)
+            .or_else(|| {
+                (expected.len() != actual.len()).then_some(expected.len().min(actual.len()))
+            });
+        let mut report = match first_difference {
+            Some(offset) => format!("payloads differ at offset {}", offset),
+            None => "payloads are identical".to_string(),
+        };
+        report.push_str(&format!(
+            "\nexpected {}\nactual {}",
+            Self::decode(expected, layouts, initial_state),
+            Self::decode(actual, layouts, initial_state)
+        ));
+        report
+    }
+
+    pub fn dump(payload: &[u8], layouts: &[ComponentLayout], initial_state: bool) -> String {
+        Self::decode(payload, layouts, initial_state).to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sync_var(name: &str, r#type: &str, value: Vec<u8>) -> SyncVarData {
+        SyncVarData {
+            full_name: format!("Test.Player.{}", name),
+            sub_class: "Test.Player".to_string(),
+            name: name.to_string(),
+            r#type: r#type.to_string(),
+            value,
+            dirty_bit: 0,
+        }
+    }
+
+    fn layouts() -> Vec<ComponentLayout> {
+        vec![
+            ComponentLayout::new("Mirror.NetworkTransformUnreliable", Vec::new()),
+            ComponentLayout::new(
+                "Test.Player",
+                vec![
+                    sync_var("Health", "System.Int32", vec![0; 4]),
+                    sync_var("Name", "System.String", vec![0, 0]),
+                ],
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_decode_payload() {
+        // 组件 0: 3 字节不透明内容; 组件 1: Health = 100, Name = "ab"
+        let payload = vec![0b11, 3, 1, 2, 3, 8, 100, 0, 0, 0, 3, 0, b'a', b'b'];
+        let decoded = PayloadDecoder::decode(&payload, &layouts(), true);
+        assert!(decoded.is_valid(), "{}", decoded);
+        assert_eq!(decoded.mask, 0b11);
+        assert_eq!(decoded.components.len(), 2);
+        assert_eq!(decoded.components[0].content, vec![1, 2, 3]);
+        let fields = &decoded.components[1].fields;
+        assert_eq!(fields.len(), 2);
+        assert_eq!((fields[0].offset, fields[1].offset), (6, 10));
+        let dump = decoded.to_string();
+        assert!(dump.contains("Health: System.Int32 @6 = 100 (64000000)"));
+        assert!(dump.contains("Name: System.String @10 = \"ab\""));
+
+        // 客户端和服务器的同步变量不一致时, 安全字节不匹配
+        let mut mismatched = payload.clone();
+        mismatched[5] = 7;
+        let decoded = PayloadDecoder::decode(&mismatched, &layouts(), true);
+        assert!(!decoded.is_valid());
+        assert!(decoded.components[1]
+            .error
+            .as_ref()
+            .unwrap()
+            .starts_with("safety mismatch"));
+
+        // 禁用的组件在末尾多一个 enabled
+        let mut disabled = payload.clone();
+        disabled[5] = 9;
+        disabled.push(0);
+        let decoded = PayloadDecoder::decode(&disabled, &layouts(), true);
+        assert!(decoded.is_valid(), "{}", decoded);
+        assert_eq!(decoded.components[1].enabled, Some(false));
+
+        // delta: SyncObject 掩码 + 同步变量掩码, 只有 Name
+        let delta = vec![0b10, 11, 0, 0, 0, 0, 0, 0, 0, 0, 0b10, 1, 0];
+        let decoded = PayloadDecoder::decode(&delta, &layouts(), false);
+        assert!(decoded.is_valid(), "{}", decoded);
+        assert_eq!(decoded.components[0].fields[0].name, "Name");
+        assert!(!PayloadDecoder::decode(&delta[..12], &layouts(), false).is_valid());
+
+        let report = PayloadDecoder::diff_payloads(&payload, &mismatched, &layouts(), true);
+        assert!(report.starts_with("payloads differ at offset 5"));
+    }
+}
@@ -0,0 +1,374 @@
+use crate::log_error;
+use crate::mirror::core::network_connection::NetworkConnectionTrait;
+use crate::mirror::core::network_context::ContextLocal;
+use crate::mirror::core::network_identity::{NetworkIdentity, Visibility};
+use crate::mirror::core::network_server::{NetworkServer, NetworkServerStatic};
+use crate::mirror::core::network_time::NetworkTime;
+use atomic::Atomic;
+use dashmap::try_result::TryResult;
+use dashmap::DashMap;
+use lazy_static::lazy_static;
+use nalgebra::Vector3;
+use std::collections::HashSet;
+use std::sync::atomic::Ordering;
+
+// 地图中的一块区域, 用包围盒表示
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StreamingRegion {
+    pub id: u32,
+    pub min: Vector3<f32>,
+    pub max: Vector3<f32>,
+}
+
+impl StreamingRegion {
+    pub fn new(id: u32, min: Vector3<f32>, max: Vector3<f32>) -> Self {
+        Self { id, min, max }
+    }
+
+    pub fn contains(&self, position: &Vector3<f32>) -> bool {
+        self.distance(position) == 0.0
+    }
+
+    // 到包围盒的距离, 在包围盒内为 0
+    pub fn distance(&self, position: &Vector3<f32>) -> f32 {
+        let closest = Vector3::new(
+            position.x.clamp(self.min.x, self.max.x),
+            position.y.clamp(self.min.y, self.max.y),
+            position.z.clamp(self.min.z, self.max.z),
+        );
+        (position - closest).norm()
+    }
+}
+
+// RegionStreaming 静态变量
+lazy_static! {
+    static ref REGIONS: ContextLocal<DashMap<u32, StreamingRegion>> =
+        ContextLocal::new(DashMap::new);
+    // net_id -> region id
+    static ref MEMBERS: ContextLocal<DashMap<u32, u32>> = ContextLocal::new(DashMap::new);
+    // conn_id -> 对该连接激活的区域
+    static ref ACTIVE: ContextLocal<DashMap<u64, HashSet<u32>>> = ContextLocal::new(DashMap::new);
+    // 玩家对象距离区域多远时激活
+    static ref ACTIVATION_DISTANCE: ContextLocal<Atomic<f32>> =
+        ContextLocal::new(|| Atomic::new(50.0));
+    // 离开 activation_distance + deactivation_margin 后才停用, 避免在边界来回生成销毁
+    static ref DEACTIVATION_MARGIN: ContextLocal<Atomic<f32>> =
+        ContextLocal::new(|| Atomic::new(10.0));
+    // 单位秒
+    static ref UPDATE_INTERVAL: ContextLocal<Atomic<f64>> = ContextLocal::new(|| Atomic::new(0.5));
+    static ref LAST_UPDATE_TIME: ContextLocal<Atomic<f64>> =
+        ContextLocal::new(|| Atomic::new(0.0));
+}
+
+// 按区域流式加载大地图: 场景对象分组到区域, 区域只对玩家对象在附近的连接激活
+// 激活时区域内的对象生成给该连接, 停用时销毁, 与 InterestRadius 同时生效
+// 没有区域时不生效, 不属于任何区域的对象不受影响, 还没有玩家对象的连接看不到任何区域
+pub struct RegionStreaming;
+
+impl RegionStreaming {
+    pub fn enabled() -> bool {
+        !REGIONS.is_empty()
+    }
+
+    pub fn activation_distance() -> f32 {
+        ACTIVATION_DISTANCE.load(Ordering::Relaxed)
+    }
+
+    pub fn set_activation_distance(value: f32) {
+        ACTIVATION_DISTANCE.store(value, Ordering::Relaxed);
+    }
+
+    pub fn deactivation_margin() -> f32 {
+        DEACTIVATION_MARGIN.load(Ordering::Relaxed)
+    }
+
+    pub fn set_deactivation_margin(value: f32) {
+        DEACTIVATION_MARGIN.store(value, Ordering::Relaxed);
+    }
+
+    pub fn update_interval() -> f64 {
+        UPDATE_INTERVAL.load(Ordering::Relaxed)
+    }
+
+    pub fn set_update_interval(value: f64) {
+        UPDATE_INTERVAL.store(value, Ordering::Relaxed);
+    }
+
+    pub fn add_region(region: StreamingRegion) {
+        REGIONS.insert(region.id, region);
+    }
+
+    // 区域内的对象不再属于任何区域, 对所有连接可见
+    pub fn remove_region(region_id: u32) {
+        if REGIONS.remove(&region_id).is_none() {
+            return;
+        }
+        let members: Vec<u32> = MEMBERS
+            .iter()
+            .filter(|member| *member.value() == region_id)
+            .map(|member| *member.key())
+            .collect();
+        for net_id in members.iter() {
+            MEMBERS.remove(net_id);
+        }
+        for mut active in ACTIVE.iter_mut() {
+            active.remove(&region_id);
+        }
+        Self::rebuild(&members);
+    }
+
+    pub fn region(region_id: u32) -> Option<StreamingRegion> {
+        REGIONS.get(&region_id).map(|region| *region)
+    }
+
+    pub fn assign(net_id: u32, region_id: u32) {
+        if !REGIONS.contains_key(&region_id) {
+            log_error!(
+                "RegionStreaming.Assign: region {} not found for netId {}",
+                region_id,
+                net_id
+            );
+            return;
+        }
+        if MEMBERS.insert(net_id, region_id) != Some(region_id) {
+            Self::rebuild(&[net_id]);
+        }
+    }
+
+    pub fn unassign(net_id: u32) {
+        if MEMBERS.remove(&net_id).is_some() {
+            Self::rebuild(&[net_id]);
+        }
+    }
+
+    pub fn region_of(net_id: u32) -> Option<u32> {
+        MEMBERS.get(&net_id).map(|region_id| *region_id)
+    }
+
+    // 把所有已生成的场景对象分配到包含它的区域, 返回分配的数量
+    // 在 NetworkServer::spawn_objects 之后调用
+    pub fn assign_scene_objects() -> usize {
+        let mut assigned = Vec::new();
+        for identity in NetworkServerStatic::spawned_network_identities().iter() {
+            if identity.scene_id == 0 {
+                continue;
+            }
+            let position = identity.game_object().transform.position;
+            if let Some(region) = REGIONS.iter().find(|region| region.contains(&position)) {
+                assigned.push((identity.net_id(), region.id));
+            }
+        }
+        for (net_id, region_id) in assigned.iter() {
+            MEMBERS.insert(*net_id, *region_id);
+        }
+        let net_ids: Vec<u32> = assigned.iter().map(|(net_id, _)| *net_id).collect();
+        Self::rebuild(&net_ids);
+        net_ids.len()
+    }
+
+    pub fn active_regions(connection_id: u64) -> Vec<u32> {
+        let mut regions: Vec<u32> = ACTIVE
+            .get(&connection_id)
+            .map(|active| active.iter().copied().collect())
+            .unwrap_or_default();
+        regions.sort();
+        regions
+    }
+
+    // 区域是否对任意一个连接激活
+    pub fn is_active(region_id: u32) -> bool {
+        ACTIVE.iter().any(|active| active.contains(&region_id))
+    }
+
+    // 在 NetworkServer::is_visible_to 中调用
+    pub(crate) fn in_active_region(identity: &NetworkIdentity, connection_id: u64) -> bool {
+        let region_id = match MEMBERS.get(&identity.net_id()) {
+            Some(region_id) => *region_id,
+            None => return true,
+        };
+        ACTIVE
+            .get(&connection_id)
+            .is_some_and(|active| active.contains(&region_id))
+    }
+
+    // 在 NetworkServer::network_late_update 中调用
+    pub fn update() {
+        if !Self::enabled() {
+            return;
+        }
+        let local_time = NetworkTime::local_time();
+        if local_time - LAST_UPDATE_TIME.load(Ordering::Relaxed) < Self::update_interval() {
+            return;
+        }
+        LAST_UPDATE_TIME.store(local_time, Ordering::Relaxed);
+
+        let mut players = Vec::new();
+        NetworkServerStatic::for_each_network_connection(|connection| {
+            players.push((connection.connection_id(), connection.net_id()));
+        });
+        let spawned = NetworkServerStatic::spawned_network_identities();
+        let activation_distance = Self::activation_distance();
+        let deactivation_distance = activation_distance + Self::deactivation_margin();
+        let mut changed = HashSet::new();
+        for (connection_id, net_id) in players {
+            let position = match spawned.try_get(&net_id) {
+                TryResult::Present(identity) => Some(identity.game_object().transform.position),
+                _ => None,
+            };
+            let mut active = ACTIVE.entry(connection_id).or_default();
+            let next: HashSet<u32> = match position {
+                Some(position) => REGIONS
+                    .iter()
+                    .filter(|region| {
+                        let distance = region.distance(&position);
+                        distance <= activation_distance
+                            || (active.contains(&region.id) && distance <= deactivation_distance)
+                    })
+                    .map(|region| region.id)
+                    .collect(),
+                None => HashSet::new(),
+            };
+            changed.extend(active.symmetric_difference(&next).copied());
+            *active = next;
+        }
+        if changed.is_empty() {
+            return;
+        }
+        let members: Vec<u32> = MEMBERS
+            .iter()
+            .filter(|member| changed.contains(member.value()))
+            .map(|member| *member.key())
+            .collect();
+        Self::rebuild(&members);
+    }
+
+    // 重建区域对象的观察者, 激活的连接收到 SpawnMessage, 停用的连接收到 ObjectHideMessage
+    fn rebuild(net_ids: &[u32]) {
+        let spawned = NetworkServerStatic::spawned_network_identities();
+        for net_id in net_ids {
+            match spawned.try_get_mut(net_id) {
+                TryResult::Present(mut identity) => {
+                    if identity.visibility == Visibility::Default {
+                        NetworkServer::rebuild_observers(&mut identity, true);
+                    }
+                }
+                TryResult::Absent => {}
+                TryResult::Locked => {
+                    log_error!("RegionStreaming: netId {} is locked.", net_id);
+                }
+            }
+        }
+    }
+
+    // 在 NetworkServer::un_spawn_internal 中调用
+    pub(crate) fn on_despawn(net_id: u32) {
+        MEMBERS.remove(&net_id);
+    }
+
+    // 在 NetworkServer::on_transport_disconnected 中调用
+    pub(crate) fn on_disconnected(connection_id: u64) {
+        ACTIVE.remove(&connection_id);
+    }
+
+    // 区域定义保留, 成员和激活状态清空
+    pub fn reset() {
+        MEMBERS.clear();
+        ACTIVE.clear();
+        LAST_UPDATE_TIME.store(0.0, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::core::network_behaviour::GameObject;
+    use crate::mirror::transports::memory::memory_transport::test_util::*;
+    use crate::mirror::transports::memory::memory_transport::MemoryTransport;
+
+    #[test]
+    fn test_region_streaming() {
+        with_server(|| {
+            MemoryTransport::client_connect(1);
+            tick();
+            NetworkServer::set_client_ready(1);
+            RegionStreaming::set_activation_distance(20.0);
+            RegionStreaming::set_deactivation_margin(10.0);
+            RegionStreaming::set_update_interval(0.0);
+            RegionStreaming::add_region(StreamingRegion::new(
+                1,
+                Vector3::new(100.0, -10.0, -10.0),
+                Vector3::new(120.0, 10.0, 10.0),
+            ));
+            // 970 是连接 1 的玩家对象, 980 在区域内, 981 不属于任何区域
+            for (net_id, scene_id, x) in [(970, 0, 0.0), (980, 5, 110.0), (981, 6, 0.0)] {
+                let mut game_object = GameObject::default();
+                game_object.transform.position = Vector3::new(x, 0.0, 0.0);
+                let mut identity = NetworkIdentity::new_with_scene_id(scene_id);
+                identity.set_net_id(net_id);
+                identity.set_game_object(game_object);
+                if scene_id == 0 {
+                    identity.set_connection_to_client(1);
+                }
+                NetworkServerStatic::add_spawned_network_identity(identity);
+                NetworkServer::set_visibility(net_id, Visibility::Default);
+            }
+            NetworkServerStatic::network_connections()
+                .get_mut(&1)
+                .unwrap()
+                .set_net_id(970);
+            assert_eq!(RegionStreaming::assign_scene_objects(), 1);
+            assert_eq!(RegionStreaming::region_of(980), Some(1));
+            assert_eq!(RegionStreaming::region_of(981), None);
+
+            let observing = || {
+                let mut observing = NetworkServerStatic::network_connections()
+                    .get(&1)
+                    .unwrap()
+                    .observing
+                    .clone();
+                observing.sort();
+                observing
+            };
+            let move_player = |x: f32| {
+                let mut identity = NetworkServerStatic::spawned_network_identities()
+                    .get_mut(&970)
+                    .unwrap();
+                let mut game_object = identity.game_object().clone();
+                game_object.transform.position = Vector3::new(x, 0.0, 0.0);
+                identity.set_game_object(game_object);
+            };
+
+            tick();
+            assert_eq!(observing(), vec![970, 981]);
+            assert!(RegionStreaming::active_regions(1).is_empty());
+
+            // 进入激活距离
+            move_player(85.0);
+            tick();
+            assert_eq!(RegionStreaming::active_regions(1), vec![1]);
+            assert!(RegionStreaming::is_active(1));
+            assert_eq!(observing(), vec![970, 980, 981]);
+
+            // 在停用距离内保持激活
+            move_player(75.0);
+            tick();
+            assert_eq!(observing(), vec![970, 980, 981]);
+
+            move_player(60.0);
+            tick();
+            assert!(!RegionStreaming::is_active(1));
+            assert_eq!(observing(), vec![970, 981]);
+
+            // 移除区域后对象对所有连接可见
+            RegionStreaming::remove_region(1);
+            assert_eq!(RegionStreaming::region_of(980), None);
+            assert_eq!(observing(), vec![970, 980, 981]);
+            RegionStreaming::set_activation_distance(50.0);
+            RegionStreaming::set_update_interval(0.5);
+            RegionStreaming::reset();
+            for net_id in [970, 980, 981] {
+                NetworkServerStatic::remove_spawned_network_identity(&net_id);
+            }
+        });
+    }
+}
@@ -2,6 +2,9 @@ use crate::log_error;
 use crate::mirror::components::network_common_behaviour::NetworkCommonBehaviour;
 use crate::mirror::core::network_reader::NetworkReader;
 use crate::mirror::core::tools::stable_hash::StableHash;
+use crate::mirror::core::tools::stable_hash_registry::{
+    StableHashDomain, StableHashKind, StableHashRegistry,
+};
 use dashmap::mapref::one::RefMut;
 use dashmap::DashMap;
 use lazy_static::lazy_static;
@@ -21,6 +24,10 @@ pub struct Invoker {
     pub call_type: RemoteCallType,
     pub function: RemoteCallDelegate,
     pub cmd_requires_authority: bool,
+    // 写入组件状态的 Command, 只接受所有者对 ClientToServer 组件的调用
+    pub cmd_writes_state: bool,
+    // Unreliable 通道只保留最新状态的 Command, 乱序到达的旧消息被丢弃
+    pub cmd_latest_only: bool,
 }
 
 impl Invoker {
@@ -35,6 +42,8 @@ impl Invoker {
             call_type,
             function,
             cmd_requires_authority,
+            cmd_writes_state: false,
+            cmd_latest_only: false,
         }
     }
     pub fn are_equal(
@@ -77,6 +86,11 @@ impl RemoteProcedureCalls {
         cmd_requires_authority: bool,
     ) -> u16 {
         let hash = function_full_name.get_fn_stable_hash_code();
+        let kind = match remote_call_type {
+            RemoteCallType::Command => StableHashKind::Command,
+            RemoteCallType::ClientRpc => StableHashKind::ClientRpc,
+        };
+        StableHashRegistry::register(StableHashDomain::RemoteCall, kind, function_full_name, hash);
         let type_id = Self::generate_type_id::<T>();
         if Self::check_if_delegate_exists(type_id, remote_call_type, &func, hash) {
             return hash;
@@ -99,6 +113,30 @@ impl RemoteProcedureCalls {
         )
     }
 
+    // 同步方向由 NetworkServer 在分发 Command 时统一检查, 组件中不需要再判断
+    pub fn register_state_command_delegate<T: 'static>(
+        function_full_name: &str,
+        func: RemoteCallDelegate,
+    ) -> u16 {
+        let hash = Self::register_command_delegate::<T>(function_full_name, func, true);
+        if let Some(mut invoker) = NETWORK_MESSAGE_HANDLERS.get_mut(&hash) {
+            invoker.cmd_writes_state = true;
+        }
+        hash
+    }
+
+    // 只在开启 UnreliableSequencing 时生效
+    pub fn register_latest_only_state_command_delegate<T: 'static>(
+        function_full_name: &str,
+        func: RemoteCallDelegate,
+    ) -> u16 {
+        let hash = Self::register_state_command_delegate::<T>(function_full_name, func);
+        if let Some(mut invoker) = NETWORK_MESSAGE_HANDLERS.get_mut(&hash) {
+            invoker.cmd_latest_only = true;
+        }
+        hash
+    }
+
     pub fn register_rpc_delegate<T: 'static>(
         function_full_name: &str,
         func: RemoteCallDelegate,
@@ -108,6 +146,7 @@ impl RemoteProcedureCalls {
 
     pub fn remove_delegate(func_hash: u16) {
         NETWORK_MESSAGE_HANDLERS.remove(&func_hash);
+        StableHashRegistry::unregister(StableHashDomain::RemoteCall, func_hash);
     }
 
     pub fn get_function_method_name(func_hash: u16) -> Option<String> {
@@ -167,6 +206,20 @@ impl RemoteProcedureCalls {
         false
     }
 
+    pub fn command_writes_state(func_hash: u16) -> bool {
+        if let Some(invoker) = NETWORK_MESSAGE_HANDLERS.get(&func_hash) {
+            return invoker.cmd_writes_state;
+        }
+        false
+    }
+
+    pub fn command_latest_only(func_hash: u16) -> bool {
+        if let Some(invoker) = NETWORK_MESSAGE_HANDLERS.get(&func_hash) {
+            return invoker.cmd_latest_only;
+        }
+        false
+    }
+
     pub fn get_delegate(func_hash: u16) -> Option<RefMut<'static, u16, Invoker>> {
         NETWORK_MESSAGE_HANDLERS.get_mut(&func_hash)
     }
@@ -0,0 +1,220 @@
+use crate::mirror::core::network_context::ContextLocal;
+use crate::mirror::core::network_time::NetworkTime;
+use lazy_static::lazy_static;
+use std::cmp::{Ordering as CmpOrdering, Reverse};
+use std::collections::{BinaryHeap, HashMap};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{RwLock, RwLockWriteGuard};
+use std::time::Duration;
+
+pub type SchedulerCallback = Box<dyn FnMut() + Send + Sync>;
+
+struct Timer {
+    callback: SchedulerCallback,
+    // None 表示只执行一次
+    interval: Option<f64>,
+}
+
+// 按到期时间排序, 时间相同时先加入的先执行
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Due(f64, u64);
+
+impl Eq for Due {}
+
+impl PartialOrd for Due {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Due {
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        self.0.total_cmp(&other.0).then(self.1.cmp(&other.1))
+    }
+}
+
+#[derive(Default)]
+struct Timers {
+    // 取消的定时器只从 callbacks 中移除, 堆中的条目到期时跳过
+    queue: BinaryHeap<Reverse<Due>>,
+    callbacks: HashMap<u64, Timer>,
+}
+
+// Scheduler 静态变量
+lazy_static! {
+    static ref TIMERS: ContextLocal<RwLock<Timers>> =
+        ContextLocal::new(|| RwLock::new(Timers::default()));
+    static ref NEXT_ID: ContextLocal<AtomicU64> = ContextLocal::new(|| AtomicU64::new(1));
+    // 正在执行的定时器, 回调中取消自己时使用
+    static ref RUNNING: ContextLocal<AtomicU64> = ContextLocal::new(|| AtomicU64::new(0));
+    static ref RUNNING_CANCELLED: ContextLocal<AtomicBool> =
+        ContextLocal::new(|| AtomicBool::new(false));
+}
+
+// 定时器: 回调在 NetworkServer::network_late_update 开始时执行 (网络循环线程, broadcast 之前),
+// 回调中可以修改对象状态或者再安排新的定时器, 修改在同一个 tick 广播
+// 精度是一个 tick
+pub struct Scheduler;
+
+impl Scheduler {
+    // 返回定时器 id, 用于 cancel
+    pub fn after<F: FnMut() + Send + Sync + 'static>(delay: Duration, callback: F) -> u64 {
+        Self::schedule(delay.as_secs_f64(), None, Box::new(callback))
+    }
+
+    // 第一次在 interval 之后执行
+    pub fn every<F: FnMut() + Send + Sync + 'static>(interval: Duration, callback: F) -> u64 {
+        let interval = interval.as_secs_f64();
+        Self::schedule(interval, Some(interval), Box::new(callback))
+    }
+
+    fn schedule(delay: f64, interval: Option<f64>, callback: SchedulerCallback) -> u64 {
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let due = NetworkTime::local_time() + delay;
+        let mut timers = Self::timers();
+        timers.queue.push(Reverse(Due(due, id)));
+        timers.callbacks.insert(id, Timer { callback, interval });
+        id
+    }
+
+    // 定时器不存在或者已经执行完时返回 false
+    pub fn cancel(id: u64) -> bool {
+        if RUNNING.load(Ordering::Relaxed) == id {
+            RUNNING_CANCELLED.store(true, Ordering::Relaxed);
+            return true;
+        }
+        Self::timers().callbacks.remove(&id).is_some()
+    }
+
+    pub fn is_scheduled(id: u64) -> bool {
+        if RUNNING.load(Ordering::Relaxed) == id {
+            return !RUNNING_CANCELLED.load(Ordering::Relaxed);
+        }
+        Self::timers().callbacks.contains_key(&id)
+    }
+
+    pub fn count() -> usize {
+        Self::timers().callbacks.len()
+    }
+
+    // 最早到期的定时器时间 (NetworkTime::local_time), 可能是已经取消的定时器
+    pub fn next_due() -> Option<f64> {
+        Self::timers().queue.peek().map(|Reverse(due)| due.0)
+    }
+
+    // 在 NetworkServer::network_late_update 开始时调用
+    pub fn update() {
+        let local_time = NetworkTime::local_time();
+        // 周期定时器的下次到期在本次 update 结束后再加入队列, 每个 tick 最多执行一次
+        let mut repeating = Vec::new();
+        loop {
+            // 执行回调时不持有锁, 回调中可以安排或取消定时器
+            let (due, id, mut timer) = {
+                let mut timers = Self::timers();
+                let Due(due, id) = match timers.queue.peek() {
+                    Some(Reverse(due)) if due.0 <= local_time => due.to_owned(),
+                    _ => break,
+                };
+                timers.queue.pop();
+                match timers.callbacks.remove(&id) {
+                    Some(timer) => (due, id, timer),
+                    None => continue,
+                }
+            };
+
+            RUNNING.store(id, Ordering::Relaxed);
+            RUNNING_CANCELLED.store(false, Ordering::Relaxed);
+            (timer.callback)();
+            RUNNING.store(0, Ordering::Relaxed);
+
+            if let Some(interval) = timer.interval {
+                if RUNNING_CANCELLED.load(Ordering::Relaxed) {
+                    continue;
+                }
+                // 落后太多时不补执行, 从现在开始重新计时
+                let mut next = due + interval;
+                if next <= local_time {
+                    next = local_time + interval;
+                }
+                Self::timers().callbacks.insert(id, timer);
+                repeating.push(Reverse(Due(next, id)));
+            }
+        }
+        if repeating.is_empty() {
+            return;
+        }
+        Self::timers().queue.extend(repeating);
+    }
+
+    pub fn reset() {
+        *Self::timers() = Timers::default();
+    }
+
+    // 回调或处理器 panic 时锁可能被毒化, 继续使用里面的数据
+    fn timers() -> RwLockWriteGuard<'static, Timers> {
+        TIMERS.write().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::transports::memory::memory_transport::test_util::*;
+    use std::sync::atomic::AtomicU32;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_scheduler() {
+        with_server(|| {
+            let once = Arc::new(AtomicU32::new(0));
+            let periodic = Arc::new(AtomicU32::new(0));
+            let counter = once.clone();
+            let once_id = Scheduler::after(Duration::ZERO, move || {
+                counter.fetch_add(1, Ordering::Relaxed);
+            });
+            let counter = periodic.clone();
+            let periodic_id = Scheduler::every(Duration::ZERO, move || {
+                counter.fetch_add(1, Ordering::Relaxed);
+            });
+            let later_id = Scheduler::after(Duration::from_secs(60), || {});
+            assert_eq!(Scheduler::count(), 3);
+
+            // 周期定时器每个 tick 最多执行一次
+            tick();
+            tick();
+            assert_eq!(once.load(Ordering::Relaxed), 1);
+            assert_eq!(periodic.load(Ordering::Relaxed), 2);
+            assert!(!Scheduler::is_scheduled(once_id));
+            assert!(!Scheduler::cancel(once_id));
+            assert!(Scheduler::is_scheduled(later_id));
+
+            assert!(Scheduler::cancel(periodic_id));
+            assert!(Scheduler::cancel(later_id));
+            tick();
+            assert_eq!(periodic.load(Ordering::Relaxed), 2);
+            assert_eq!(Scheduler::count(), 0);
+
+            // 回调中取消自己, 并安排新的定时器
+            let chained = Arc::new(AtomicU32::new(0));
+            let counter = chained.clone();
+            let self_id = Arc::new(AtomicU64::new(0));
+            let id = self_id.clone();
+            self_id.store(
+                Scheduler::every(Duration::ZERO, move || {
+                    assert!(Scheduler::cancel(id.load(Ordering::Relaxed)));
+                    let counter = counter.clone();
+                    Scheduler::after(Duration::ZERO, move || {
+                        counter.fetch_add(1, Ordering::Relaxed);
+                    });
+                }),
+                Ordering::Relaxed,
+            );
+            tick();
+            tick();
+            tick();
+            assert_eq!(chained.load(Ordering::Relaxed), 1);
+            assert_eq!(Scheduler::count(), 0);
+            Scheduler::reset();
+        });
+    }
+}
@@ -0,0 +1,412 @@
+use crate::mirror::core::messages::{
+    DisconnectReason, NetworkMessageTrait, SessionResumeMessage, SessionResumeResultMessage,
+    SessionTokenMessage,
+};
+use crate::mirror::core::network_connection::NetworkConnectionTrait;
+use crate::mirror::core::network_connection_to_client::NetworkConnectionToClient;
+use crate::mirror::core::network_context::ContextLocal;
+use crate::mirror::core::network_manager::NetworkManagerStatic;
+use crate::mirror::core::network_reader::NetworkReader;
+use crate::mirror::core::network_server::{NetworkServer, NetworkServerStatic};
+use crate::mirror::core::network_time::NetworkTime;
+use crate::mirror::core::transport::TransportChannel;
+use crate::{log_error, log_info};
+use atomic::Atomic;
+use dashmap::try_result::TryResult;
+use dashmap::DashMap;
+use lazy_static::lazy_static;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+// 断开后保留的会话: owned 对象留在场景中, 暂时没有所有者
+#[derive(Debug, Clone)]
+struct ParkedSession {
+    player_net_id: u32,
+    owned: Vec<u32>,
+    // 认证器导出的认证数据
+    auth_data: Vec<u8>,
+    parked_time: f64,
+}
+
+// SessionResume 静态变量
+lazy_static! {
+    static ref ENABLED: ContextLocal<AtomicBool> = ContextLocal::new(|| AtomicBool::new(false));
+    // 断开后保留会话的时间, 单位秒
+    static ref GRACE_PERIOD: ContextLocal<Atomic<f64>> = ContextLocal::new(|| Atomic::new(30.0));
+    // 连接 id -> 会话令牌
+    static ref TOKENS: ContextLocal<DashMap<u64, u64>> = ContextLocal::new(DashMap::new);
+    // 会话令牌 -> 等待恢复的会话
+    static ref PARKED: ContextLocal<DashMap<u64, ParkedSession>> = ContextLocal::new(DashMap::new);
+    static ref RESUMED_COUNT: ContextLocal<AtomicU64> = ContextLocal::new(|| AtomicU64::new(0));
+    static ref EXPIRED_COUNT: ContextLocal<AtomicU64> = ContextLocal::new(|| AtomicU64::new(0));
+}
+
+// 会话恢复: 客户端断开后 (例如 UDP 被阻断) 可以通过任意 Transport 重新连接, 发送 SessionResumeMessage 继续之前的会话
+// 客户端第一次准备好时收到 SessionTokenMessage; 断开时 owned 对象不销毁, 在 grace_period 内等待恢复
+// 恢复时新连接接管所有 owned 对象和认证数据 (通过认证器的 export / import_authentication_data), 之后照常发送 ReadyMessage
+// 旧连接仍然在线时 (Transport 还没有检测到断开) 直接接管, 旧连接以 SessionTakenOver 断开
+// 令牌只能使用一次, 每次恢复后发放新的令牌
+pub struct SessionResume;
+
+impl SessionResume {
+    pub fn enable() {
+        ENABLED.store(true, Ordering::Relaxed);
+        NetworkServer::register_handler::<SessionResumeMessage>(
+            Self::on_session_resume_message,
+            false,
+        );
+    }
+
+    pub fn enabled() -> bool {
+        ENABLED.load(Ordering::Relaxed)
+    }
+
+    pub fn grace_period() -> f64 {
+        GRACE_PERIOD.load(Ordering::Relaxed)
+    }
+
+    pub fn set_grace_period(value: f64) {
+        GRACE_PERIOD.store(value.max(0.0), Ordering::Relaxed);
+    }
+
+    pub fn token(connection_id: u64) -> Option<u64> {
+        TOKENS.get(&connection_id).map(|token| *token)
+    }
+
+    pub fn parked_count() -> usize {
+        PARKED.len()
+    }
+
+    pub fn resumed_count() -> u64 {
+        RESUMED_COUNT.load(Ordering::Relaxed)
+    }
+
+    // 超过保留时间被销毁的会话
+    pub fn expired_count() -> u64 {
+        EXPIRED_COUNT.load(Ordering::Relaxed)
+    }
+
+    fn new_token() -> u64 {
+        loop {
+            let token = rand::random::<u64>();
+            if token != 0 && !PARKED.contains_key(&token) {
+                return token;
+            }
+        }
+    }
+
+    // 在 NetworkServer::set_client_ready 中调用, 第一次准备好时发放令牌
+    pub(crate) fn on_client_ready(connection: &mut NetworkConnectionToClient) {
+        if !Self::enabled() || TOKENS.contains_key(&connection.connection_id()) {
+            return;
+        }
+        let token = Self::new_token();
+        TOKENS.insert(connection.connection_id(), token);
+        connection.send_network_message(
+            &mut SessionTokenMessage::new(token),
+            TransportChannel::Reliable,
+        );
+    }
+
+    // 在 NetworkServer::on_transport_disconnected 中调用, 销毁玩家之前
+    pub(crate) fn on_disconnected(connection: &mut NetworkConnectionToClient) {
+        let token = match TOKENS.remove(&connection.connection_id()) {
+            Some((_, token)) => token,
+            None => return,
+        };
+        if !Self::enabled() || connection.owned().is_empty() {
+            return;
+        }
+        PARKED.insert(token, Self::park(connection));
+    }
+
+    // 解除连接和 owned 对象的关系, 之后销毁玩家不会销毁这些对象
+    fn park(connection: &mut NetworkConnectionToClient) -> ParkedSession {
+        let auth_data = if NetworkManagerStatic::network_manager_singleton_exists() {
+            match NetworkManagerStatic::network_manager_singleton().authenticator() {
+                Some(authenticator) => authenticator.export_authentication_data(connection),
+                None => Vec::new(),
+            }
+        } else {
+            Vec::new()
+        };
+        let owned = std::mem::take(connection.owned());
+        for net_id in owned.iter() {
+            if let TryResult::Present(mut identity) =
+                NetworkServerStatic::spawned_network_identities().try_get_mut(net_id)
+            {
+                identity.set_connection_to_client(0);
+            }
+        }
+        let player_net_id = connection.net_id();
+        connection.set_net_id(0);
+        ParkedSession {
+            player_net_id,
+            owned,
+            auth_data,
+            parked_time: NetworkTime::local_time(),
+        }
+    }
+
+    // 把会话交给新连接, 返回新的令牌, 对象的所有者由 restore_owned 在释放连接之后设置
+    fn restore(connection: &mut NetworkConnectionToClient, session: &ParkedSession) -> u64 {
+        connection.set_authenticated(true);
+        if NetworkManagerStatic::network_manager_singleton_exists() {
+            if let Some(authenticator) =
+                NetworkManagerStatic::network_manager_singleton().authenticator()
+            {
+                authenticator.import_authentication_data(connection, &session.auth_data);
+            }
+        }
+        // 保留期间被销毁的玩家跳过
+        if NetworkServerStatic::spawned_network_identities().contains_key(&session.player_net_id) {
+            connection.set_net_id(session.player_net_id);
+        }
+        let token = Self::new_token();
+        TOKENS.insert(connection.connection_id(), token);
+        RESUMED_COUNT.fetch_add(1, Ordering::Relaxed);
+        token
+    }
+
+    // set_connection_to_client 会获取连接并加入 owned, 调用时不能持有该连接
+    fn restore_owned(connection_id: u64, session: &ParkedSession) {
+        // 保留期间被销毁的对象跳过
+        for net_id in session.owned.iter() {
+            if let TryResult::Present(mut identity) =
+                NetworkServerStatic::spawned_network_identities().try_get_mut(net_id)
+            {
+                identity.set_connection_to_client(connection_id);
+            }
+        }
+    }
+
+    fn on_session_resume_message(
+        connection_id: u64,
+        reader: &mut NetworkReader,
+        channel: TransportChannel,
+    ) {
+        let message = SessionResumeMessage::deserialize(reader);
+        // 旧连接仍然在线, 先把会话保留下来
+        let old_connection_id = TOKENS
+            .iter()
+            .find(|entry| *entry.value() == message.token)
+            .map(|entry| *entry.key());
+        if let Some(old_connection_id) = old_connection_id {
+            if old_connection_id != connection_id {
+                Self::take_over(old_connection_id, message.token);
+            }
+        }
+
+        let session = match NetworkServerStatic::network_connections().try_get_mut(&connection_id) {
+            TryResult::Present(mut connection) => {
+                let session = PARKED.remove(&message.token).map(|(_, session)| session);
+                let mut result = match session.as_ref() {
+                    Some(session) => {
+                        log_info!(
+                            "SessionResume: connectionId {} resumed session of player {}",
+                            connection_id,
+                            session.player_net_id
+                        );
+                        SessionResumeResultMessage::new(
+                            true,
+                            Self::restore(&mut connection, session),
+                        )
+                    }
+                    None => SessionResumeResultMessage::new(false, 0),
+                };
+                connection.send_network_message(&mut result, channel);
+                session
+            }
+            TryResult::Absent => {
+                log_error!(
+                    "SessionResume: connectionId {} not found in connections",
+                    connection_id
+                );
+                None
+            }
+            TryResult::Locked => {
+                log_error!("SessionResume: connectionId {} is locked", connection_id);
+                None
+            }
+        };
+        if let Some(session) = session {
+            Self::restore_owned(connection_id, &session);
+        }
+    }
+
+    fn take_over(old_connection_id: u64, token: u64) {
+        match NetworkServerStatic::network_connections().try_get_mut(&old_connection_id) {
+            TryResult::Present(mut connection) => {
+                TOKENS.remove(&old_connection_id);
+                PARKED.insert(token, Self::park(&mut connection));
+                // park 之后 net_id 为 0, 玩家对象的观察者中也会移除旧连接
+                connection.remove_from_observings_observers();
+                NetworkServer::disconnect_connection_with_reason(
+                    &mut connection,
+                    DisconnectReason::SessionTakenOver,
+                );
+            }
+            TryResult::Absent => {
+                TOKENS.remove(&old_connection_id);
+            }
+            TryResult::Locked => {
+                log_error!(
+                    "SessionResume: connectionId {} is locked",
+                    old_connection_id
+                );
+            }
+        }
+    }
+
+    // 在 NetworkServer::network_late_update 中调用, 销毁超过保留时间的会话
+    pub fn update() {
+        if PARKED.is_empty() {
+            return;
+        }
+        let expire_time = NetworkTime::local_time() - Self::grace_period();
+        let expired: Vec<u64> = PARKED
+            .iter()
+            .filter(|session| session.parked_time <= expire_time)
+            .map(|session| *session.key())
+            .collect();
+        for token in expired {
+            if let Some((_, session)) = PARKED.remove(&token) {
+                Self::destroy(session);
+                EXPIRED_COUNT.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    // 用一个临时连接按正常断开的流程销毁对象, 保留期间已经销毁的对象跳过
+    fn destroy(session: ParkedSession) {
+        let mut connection = NetworkConnectionToClient::new(0);
+        connection
+            .owned()
+            .extend(session.owned.into_iter().filter(|net_id| {
+                NetworkServerStatic::spawned_network_identities().contains_key(net_id)
+            }));
+        connection.set_net_id(session.player_net_id);
+        NetworkServer::destroy_player_for_connection(&mut connection);
+    }
+
+    // 保留的对象随场景一起清除
+    pub fn reset() {
+        ENABLED.store(false, Ordering::Relaxed);
+        TOKENS.clear();
+        PARKED.clear();
+        RESUMED_COUNT.store(0, Ordering::Relaxed);
+        EXPIRED_COUNT.store(0, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::core::messages::DisconnectMessage;
+    use crate::mirror::core::network_identity::NetworkIdentity;
+    use crate::mirror::transports::memory::memory_transport::test_util::*;
+    use crate::mirror::transports::memory::memory_transport::MemoryTransport;
+
+    #[test]
+    fn test_session_resume() {
+        with_server(|| {
+            SessionResume::enable();
+            SessionResume::set_grace_period(30.0);
+            MemoryTransport::client_connect(1);
+            tick();
+            MemoryTransport::client_receive(1);
+            NetworkServer::set_client_ready(1);
+            tick();
+            let tokens = received::<SessionTokenMessage>(1);
+            assert_eq!(tokens.len(), 1);
+            let token = tokens[0].token;
+            assert_eq!(SessionResume::token(1), Some(token));
+
+            let mut identity = NetworkIdentity::new_with_asset_id(7);
+            identity.set_net_id(100);
+            identity.set_connection_to_client(1);
+            NetworkServerStatic::add_spawned_network_identity(identity);
+            // set_connection_to_client 已经把 100 加入连接 1 的 owned
+            NetworkServerStatic::network_connections()
+                .get_mut(&1)
+                .unwrap()
+                .set_net_id(100);
+            let owner = || {
+                NetworkServerStatic::spawned_network_identities()
+                    .get(&100)
+                    .map(|identity| identity.connection_to_client())
+            };
+
+            // 断开后玩家保留在场景中, 没有所有者
+            MemoryTransport::client_disconnect(1);
+            tick();
+            assert!(!NetworkServerStatic::network_connections().contains_key(&1));
+            assert_eq!(owner(), Some(0));
+            assert_eq!(SessionResume::parked_count(), 1);
+
+            // 通过另一个连接恢复, 接管玩家并得到新的令牌
+            MemoryTransport::client_connect(2);
+            tick();
+            MemoryTransport::client_receive(2);
+            let mut resume = SessionResumeMessage::new(token);
+            MemoryTransport::client_send_message(2, &mut resume, TransportChannel::Reliable);
+            tick();
+            let results = received::<SessionResumeResultMessage>(2);
+            assert_eq!(results.len(), 1);
+            assert!(results[0].success);
+            assert_ne!(results[0].token, token);
+            let token = results[0].token;
+            {
+                let mut connection = NetworkServerStatic::network_connections()
+                    .get_mut(&2)
+                    .unwrap();
+                assert_eq!(connection.net_id(), 100);
+                assert!(connection.is_authenticated());
+                // 所有者只加入一次
+                assert_eq!(connection.owned(), &vec![100]);
+            }
+            assert_eq!(owner(), Some(2));
+            assert_eq!(SessionResume::parked_count(), 0);
+            assert_eq!(SessionResume::resumed_count(), 1);
+
+            // 令牌只能使用一次
+            MemoryTransport::client_connect(3);
+            tick();
+            MemoryTransport::client_receive(3);
+            MemoryTransport::client_send_message(3, &mut resume, TransportChannel::Reliable);
+            tick();
+            let results = received::<SessionResumeResultMessage>(3);
+            assert_eq!(results.len(), 1);
+            assert!(!results[0].success);
+
+            // 旧连接仍然在线时直接接管, 旧连接被断开
+            MemoryTransport::client_receive(2);
+            let mut resume = SessionResumeMessage::new(token);
+            MemoryTransport::client_send_message(3, &mut resume, TransportChannel::Reliable);
+            tick();
+            let results = received::<SessionResumeResultMessage>(3);
+            assert_eq!(results.len(), 1);
+            assert!(results[0].success);
+            assert_eq!(owner(), Some(3));
+            let messages = received::<DisconnectMessage>(2);
+            assert_eq!(messages.len(), 1);
+            assert_eq!(
+                DisconnectReason::from(messages[0].reason),
+                DisconnectReason::SessionTakenOver
+            );
+            tick();
+            assert!(!NetworkServerStatic::network_connections().contains_key(&2));
+            assert_eq!(owner(), Some(3));
+            assert_eq!(NetworkServer::owned_objects(3), vec![100]);
+
+            // 超过保留时间后销毁
+            SessionResume::set_grace_period(0.0);
+            MemoryTransport::client_disconnect(3);
+            tick();
+            assert_eq!(owner(), None);
+            assert_eq!(SessionResume::parked_count(), 0);
+            assert_eq!(SessionResume::expired_count(), 1);
+            SessionResume::set_grace_period(30.0);
+            SessionResume::reset();
+        });
+    }
+}
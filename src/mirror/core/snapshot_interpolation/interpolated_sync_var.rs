@@ -0,0 +1,226 @@
+use crate::log_error;
+use crate::mirror::core::network_connection::NetworkConnectionTrait;
+use crate::mirror::core::network_server::NetworkServerStatic;
+use crate::mirror::core::network_time::NetworkTime;
+use crate::mirror::core::snapshot_interpolation::snapshot::Snapshot;
+use crate::mirror::core::snapshot_interpolation::snapshot_interpolation::SnapshotInterpolation;
+use dashmap::try_result::TryResult;
+use nalgebra::Vector3;
+use ordered_float::OrderedFloat;
+use std::collections::BTreeMap;
+
+// 可以插值的 SyncVar 类型
+pub trait Interpolate: Copy {
+    fn interpolate(from: Self, to: Self, t: f64) -> Self;
+}
+
+impl Interpolate for f32 {
+    fn interpolate(from: Self, to: Self, t: f64) -> Self {
+        from + (to - from) * t as f32
+    }
+}
+
+impl Interpolate for f64 {
+    fn interpolate(from: Self, to: Self, t: f64) -> Self {
+        from + (to - from) * t
+    }
+}
+
+impl Interpolate for Vector3<f32> {
+    fn interpolate(from: Self, to: Self, t: f64) -> Self {
+        from.lerp(&to, t as f32)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ValueSnapshot<T: Interpolate> {
+    pub remote_time: f64,
+    pub local_time: f64,
+    pub value: T,
+}
+
+impl<T: Interpolate> PartialEq for ValueSnapshot<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.remote_time == other.remote_time
+    }
+}
+
+impl<T: Interpolate> Eq for ValueSnapshot<T> {}
+
+impl<T: Interpolate> PartialOrd for ValueSnapshot<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: Interpolate> Ord for ValueSnapshot<T> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        OrderedFloat(self.remote_time).cmp(&OrderedFloat(other.remote_time))
+    }
+}
+
+impl<T: Interpolate> Snapshot for ValueSnapshot<T> {
+    fn local_time(&self) -> f64 {
+        self.local_time
+    }
+
+    fn remote_time(&self) -> f64 {
+        self.remote_time
+    }
+
+    fn set_local_time(&mut self, local_time: f64) {
+        self.local_time = local_time;
+    }
+
+    fn set_remote_time(&mut self, remote_time: f64) {
+        self.remote_time = remote_time;
+    }
+}
+
+// ClientToServer 的 SyncVar (例如摇杆输入) 在服务器端按时间缓存,
+// 与 NetworkTransform 一样在连接的 remote_timeline 上采样, 服务器模拟时得到平滑的值
+pub struct InterpolatedSyncVar<T: Interpolate> {
+    snapshots: BTreeMap<OrderedFloat<f64>, ValueSnapshot<T>>,
+    buffer_limit: usize,
+    value: T,
+}
+
+impl<T: Interpolate> InterpolatedSyncVar<T> {
+    pub fn new(value: T, buffer_limit: usize) -> Self {
+        Self {
+            snapshots: BTreeMap::new(),
+            buffer_limit,
+            value,
+        }
+    }
+
+    // 最近一次采样的值
+    pub fn value(&self) -> T {
+        self.value
+    }
+
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+
+    // remote_time 为客户端发送时的时间戳, 缓冲区已满或时间戳重复时返回 false
+    pub fn insert(&mut self, remote_time: f64, value: T) -> bool {
+        // insert_if_not_exists 会覆盖相同时间戳的值
+        if self.snapshots.contains_key(&OrderedFloat(remote_time)) {
+            return false;
+        }
+        SnapshotInterpolation::insert_if_not_exists(
+            &mut self.snapshots,
+            self.buffer_limit,
+            ValueSnapshot {
+                remote_time,
+                local_time: NetworkTime::local_time(),
+                value,
+            },
+        )
+    }
+
+    // 在 Command / OnDeserialize 中调用, 使用连接当前 batch 的时间戳
+    pub fn insert_from_connection(&mut self, conn_id: u64, value: T) -> bool {
+        match NetworkServerStatic::network_connections().try_get(&conn_id) {
+            TryResult::Present(conn) => {
+                let remote_time = conn.remote_time_stamp();
+                drop(conn);
+                self.insert(remote_time, value)
+            }
+            TryResult::Absent => {
+                log_error!(
+                    "InterpolatedSyncVar failed because connection {} is absent.",
+                    conn_id
+                );
+                false
+            }
+            TryResult::Locked => {
+                log_error!(
+                    "InterpolatedSyncVar failed because connection {} is locked.",
+                    conn_id
+                );
+                false
+            }
+        }
+    }
+
+    // 在 remote_timeline 上采样, 缓冲区为空时保持上一次的值
+    pub fn sample(&mut self, remote_timeline: f64) -> T {
+        if self.snapshots.is_empty() {
+            return self.value;
+        }
+        let (from, to, t) =
+            SnapshotInterpolation::step_interpolation(&mut self.snapshots, remote_timeline);
+        self.value = T::interpolate(from.value, to.value, t);
+        self.value
+    }
+
+    // 在服务器 update 中调用, 使用连接的 remote_timeline
+    pub fn sample_for_connection(&mut self, conn_id: u64) -> T {
+        match NetworkServerStatic::network_connections().try_get(&conn_id) {
+            TryResult::Present(conn) => {
+                let remote_timeline = conn.remote_timeline;
+                drop(conn);
+                self.sample(remote_timeline)
+            }
+            TryResult::Absent => {
+                log_error!(
+                    "InterpolatedSyncVar failed because connection {} is absent.",
+                    conn_id
+                );
+                self.value
+            }
+            TryResult::Locked => {
+                log_error!(
+                    "InterpolatedSyncVar failed because connection {} is locked.",
+                    conn_id
+                );
+                self.value
+            }
+        }
+    }
+
+    pub fn reset(&mut self, value: T) {
+        self.snapshots.clear();
+        self.value = value;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interpolated_f32() {
+        let mut var = InterpolatedSyncVar::new(0.0f32, 4);
+        assert_eq!(var.sample(1.0), 0.0);
+        assert!(var.insert(1.0, 0.0));
+        assert!(var.insert(2.0, 10.0));
+        assert!(!var.insert(2.0, 20.0));
+        assert!(var.insert(3.0, 20.0));
+
+        assert_eq!(var.sample(1.5), 5.0);
+        assert_eq!(var.sample(2.25), 12.5);
+        // 超过最后一个时保持最后的值, 最后一个快照保留在缓冲区中
+        assert_eq!(var.sample(5.0), 20.0);
+        assert_eq!(var.len(), 1);
+        assert_eq!(var.sample(6.0), 20.0);
+    }
+
+    #[test]
+    fn test_interpolated_vector3() {
+        let mut var = InterpolatedSyncVar::new(Vector3::zeros(), 4);
+        var.insert(0.0, Vector3::new(0.0, 0.0, 0.0));
+        var.insert(1.0, Vector3::new(2.0, 4.0, -2.0));
+        assert_eq!(var.sample(0.5), Vector3::new(1.0, 2.0, -1.0));
+
+        var.reset(Vector3::new(1.0, 1.0, 1.0));
+        assert!(var.is_empty());
+        assert_eq!(var.value(), Vector3::new(1.0, 1.0, 1.0));
+    }
+}
@@ -1,4 +1,5 @@
 pub mod snapshot;
 pub mod time_snapshot;
 pub mod snapshot_interpolation;
-pub mod snapshot_interpolation_settings;
\ No newline at end of file
+pub mod snapshot_interpolation_settings;
+pub mod interpolated_sync_var;
\ No newline at end of file
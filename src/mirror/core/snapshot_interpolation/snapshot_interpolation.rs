@@ -141,7 +141,7 @@ impl SnapshotInterpolation {
         T: Snapshot,
     {
         let mut i = 0;
-        while buffer.len() > 1 && i < buffer.len() - 2 {
+        while buffer.len() > 1 && i < buffer.len() - 1 {
             let first = buffer.iter().nth(i).unwrap();
             let second = buffer.iter().nth(i + 1).unwrap();
             // debug!(format!("1 {} {} {} {}",buffer.len(), first.1.remote_time(), local_timeline, second.1.remote_time()));
@@ -184,13 +184,12 @@ impl SnapshotInterpolation {
         T: Snapshot,
     {
         let (from, to, t) = Self::sample(buffer, local_timeline);
-        if from == to {
-            let snapshot = buffer.remove(&from).unwrap();
-            return (snapshot.clone(), snapshot.clone(), t);
-        }
-        let from_snapshot = buffer.remove(&from).unwrap();
-        let to_snapshot = buffer.get(&to).unwrap();
-        (from_snapshot, *to_snapshot, t)
+        let from_snapshot = *buffer.get(&from).unwrap();
+        let to_snapshot = *buffer.get(&to).unwrap();
+        // 与 C# 的 buffer.RemoveRange(from) 一致: 只删除 from 之前的快照, from 本身保留,
+        // 下一帧仍然可以在 from 和 to 之间插值, 超过最后一个快照时保持最后的值
+        *buffer = buffer.split_off(&from);
+        (from_snapshot, to_snapshot, t)
     }
 
     pub fn step<T>(
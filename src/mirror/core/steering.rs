@@ -0,0 +1,354 @@
+use crate::log_error;
+use crate::mirror::core::network_context::ContextLocal;
+use crate::mirror::core::network_server::NetworkServerStatic;
+use crate::mirror::core::network_time::NetworkTime;
+use atomic::Atomic;
+use dashmap::try_result::TryResult;
+use dashmap::DashMap;
+use lazy_static::lazy_static;
+use nalgebra::{UnitQuaternion, Vector3};
+use rand::Rng;
+use std::sync::atomic::Ordering;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SteeringBehaviour {
+    // 移动到 target 后停止
+    Seek {
+        target: Vector3<f32>,
+    },
+    // 在 center 周围 radius 范围内 (水平面) 随机移动
+    Wander {
+        center: Vector3<f32>,
+        radius: f32,
+        target: Option<Vector3<f32>>,
+    },
+    // 依次经过 points, looping 时回到第一个点
+    Waypoints {
+        points: Vec<Vector3<f32>>,
+        index: usize,
+        looping: bool,
+    },
+}
+
+impl SteeringBehaviour {
+    pub fn seek(target: Vector3<f32>) -> Self {
+        SteeringBehaviour::Seek { target }
+    }
+
+    pub fn wander(center: Vector3<f32>, radius: f32) -> Self {
+        SteeringBehaviour::Wander {
+            center,
+            radius,
+            target: None,
+        }
+    }
+
+    pub fn waypoints(points: Vec<Vector3<f32>>, looping: bool) -> Self {
+        SteeringBehaviour::Waypoints {
+            points,
+            index: 0,
+            looping,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SteeringAgent {
+    pub behaviour: SteeringBehaviour,
+    // 单位每秒
+    pub max_speed: f32,
+    // 距离目标多近算到达
+    pub arrive_distance: f32,
+    // 朝向移动方向 (绕 y 轴)
+    pub face_movement: bool,
+    // Seek 到达目标或者 Waypoints 走完
+    pub arrived: bool,
+}
+
+impl SteeringAgent {
+    pub fn new(behaviour: SteeringBehaviour, max_speed: f32) -> Self {
+        Self {
+            behaviour,
+            max_speed,
+            arrive_distance: 0.1,
+            face_movement: true,
+            arrived: false,
+        }
+    }
+
+    // 当前的目标点, 没有目标时返回 None
+    fn target(&mut self, position: &Vector3<f32>) -> Option<Vector3<f32>> {
+        let arrive_distance = self.arrive_distance;
+        let reached = |target: &Vector3<f32>| (target - position).norm() <= arrive_distance;
+        match &mut self.behaviour {
+            SteeringBehaviour::Seek { target } => {
+                if reached(target) {
+                    self.arrived = true;
+                    return None;
+                }
+                Some(*target)
+            }
+            SteeringBehaviour::Wander {
+                center,
+                radius,
+                target,
+            } => {
+                if target.as_ref().is_none_or(reached) {
+                    let angle = rand::rng().random_range(0.0..std::f32::consts::TAU);
+                    let distance = rand::rng().random_range(0.0..=radius.max(0.0));
+                    *target = Some(Vector3::new(
+                        center.x + angle.cos() * distance,
+                        center.y,
+                        center.z + angle.sin() * distance,
+                    ));
+                }
+                *target
+            }
+            SteeringBehaviour::Waypoints {
+                points,
+                index,
+                looping,
+            } => {
+                // 最多前进一圈, 所有点重合时不会死循环
+                for _ in 0..points.len() {
+                    if *index >= points.len() || !reached(&points[*index]) {
+                        break;
+                    }
+                    if *index + 1 < points.len() {
+                        *index += 1;
+                    } else if *looping && points.len() > 1 {
+                        *index = 0;
+                    } else {
+                        *index = points.len();
+                    }
+                }
+                if *index >= points.len() {
+                    self.arrived = true;
+                    return None;
+                }
+                Some(points[*index])
+            }
+        }
+    }
+
+    // 返回移动 delta_time 之后的位置, 不需要移动时返回 None
+    fn next_position(&mut self, position: &Vector3<f32>, delta_time: f32) -> Option<Vector3<f32>> {
+        if self.arrived {
+            return None;
+        }
+        let target = self.target(position)?;
+        let offset = target - position;
+        let distance = offset.norm();
+        if distance <= f32::EPSILON {
+            return None;
+        }
+        let step = (self.max_speed * delta_time).min(distance);
+        Some(position + offset / distance * step)
+    }
+}
+
+// Steering 静态变量
+lazy_static! {
+    // net_id -> agent
+    static ref AGENTS: ContextLocal<DashMap<u32, SteeringAgent>> = ContextLocal::new(DashMap::new);
+    static ref LAST_UPDATE_TIME: ContextLocal<Atomic<f64>> =
+        ContextLocal::new(|| Atomic::new(0.0));
+}
+
+// 服务器端的简单移动驱动: 不需要导航网格, 直线移动到目标
+// 每个 tick 修改对象的 GameObject transform, NetworkTransform 组件照常广播
+pub struct Steering;
+
+impl Steering {
+    // 替换已有的 agent
+    pub fn attach(net_id: u32, agent: SteeringAgent) {
+        if !NetworkServerStatic::spawned_network_identities().contains_key(&net_id) {
+            log_error!("Steering.Attach: netId {} not found in spawned", net_id);
+            return;
+        }
+        AGENTS.insert(net_id, agent);
+    }
+
+    pub fn detach(net_id: u32) -> Option<SteeringAgent> {
+        AGENTS.remove(&net_id).map(|(_, agent)| agent)
+    }
+
+    pub fn agent(net_id: u32) -> Option<SteeringAgent> {
+        AGENTS.get(&net_id).map(|agent| agent.clone())
+    }
+
+    pub fn set_behaviour(net_id: u32, behaviour: SteeringBehaviour) {
+        if let Some(mut agent) = AGENTS.get_mut(&net_id) {
+            agent.behaviour = behaviour;
+            agent.arrived = false;
+        }
+    }
+
+    pub fn arrived(net_id: u32) -> bool {
+        AGENTS.get(&net_id).is_some_and(|agent| agent.arrived)
+    }
+
+    pub fn count() -> usize {
+        AGENTS.len()
+    }
+
+    // 在 NetworkServer::network_late_update 中 broadcast 之前调用, 暂停时不移动
+    pub fn update() {
+        let local_time = NetworkTime::local_time();
+        let last_update_time = LAST_UPDATE_TIME.swap(local_time, Ordering::Relaxed);
+        if last_update_time == 0.0 || NetworkServerStatic::paused() {
+            return;
+        }
+        Self::step((local_time - last_update_time) as f32);
+    }
+
+    // 所有 agent 移动 delta_time 秒
+    pub fn step(delta_time: f32) {
+        if delta_time <= 0.0 {
+            return;
+        }
+        let spawned = NetworkServerStatic::spawned_network_identities();
+        let net_ids: Vec<u32> = AGENTS.iter().map(|agent| *agent.key()).collect();
+        for net_id in net_ids {
+            let mut identity = match spawned.try_get_mut(&net_id) {
+                TryResult::Present(identity) => identity,
+                TryResult::Absent => {
+                    AGENTS.remove(&net_id);
+                    continue;
+                }
+                TryResult::Locked => {
+                    log_error!("Steering: netId {} is locked.", net_id);
+                    continue;
+                }
+            };
+            let mut game_object = identity.game_object().clone();
+            let position = game_object.transform.position;
+            let (next, face_movement) = match AGENTS.get_mut(&net_id) {
+                Some(mut agent) => (
+                    agent.next_position(&position, delta_time),
+                    agent.face_movement,
+                ),
+                None => continue,
+            };
+            let next = match next {
+                Some(next) => next,
+                None => continue,
+            };
+            game_object.transform.position = next;
+            game_object.transform.local_position = next;
+            let direction = next - position;
+            if face_movement && (direction.x != 0.0 || direction.z != 0.0) {
+                // 与 Unity 一致, +z 为前方
+                let rotation = UnitQuaternion::from_axis_angle(
+                    &Vector3::y_axis(),
+                    direction.x.atan2(direction.z),
+                )
+                .into_inner();
+                game_object.transform.rotation = rotation;
+                game_object.transform.local_rotation = rotation;
+            }
+            identity.set_game_object(game_object);
+        }
+    }
+
+    // 在 NetworkServer::un_spawn_internal 中调用
+    pub(crate) fn on_despawn(net_id: u32) {
+        AGENTS.remove(&net_id);
+    }
+
+    pub fn reset() {
+        AGENTS.clear();
+        LAST_UPDATE_TIME.store(0.0, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::core::network_identity::NetworkIdentity;
+    use crate::mirror::transports::memory::memory_transport::test_util::*;
+
+    fn step(agent: &mut SteeringAgent, position: &mut Vector3<f32>, delta_time: f32) {
+        if let Some(next) = agent.next_position(position, delta_time) {
+            *position = next;
+        }
+    }
+
+    #[test]
+    fn test_seek_and_waypoints() {
+        let mut position = Vector3::zeros();
+        let mut agent =
+            SteeringAgent::new(SteeringBehaviour::seek(Vector3::new(3.0, 0.0, 0.0)), 2.0);
+        step(&mut agent, &mut position, 1.0);
+        assert_eq!(position, Vector3::new(2.0, 0.0, 0.0));
+        // 不会越过目标
+        step(&mut agent, &mut position, 1.0);
+        assert_eq!(position, Vector3::new(3.0, 0.0, 0.0));
+        step(&mut agent, &mut position, 1.0);
+        assert!(agent.arrived);
+
+        let mut position = Vector3::zeros();
+        let points = vec![Vector3::new(1.0, 0.0, 0.0), Vector3::new(1.0, 0.0, 1.0)];
+        let mut agent =
+            SteeringAgent::new(SteeringBehaviour::waypoints(points.clone(), false), 1.0);
+        step(&mut agent, &mut position, 1.0);
+        step(&mut agent, &mut position, 1.0);
+        assert_eq!(position, points[1]);
+        step(&mut agent, &mut position, 1.0);
+        assert!(agent.arrived);
+
+        let mut agent = SteeringAgent::new(SteeringBehaviour::waypoints(points.clone(), true), 1.0);
+        step(&mut agent, &mut position, 1.0);
+        assert!(!agent.arrived);
+        assert_eq!(position, points[0]);
+    }
+
+    #[test]
+    fn test_wander_stays_in_radius() {
+        let center = Vector3::new(5.0, 1.0, 5.0);
+        let mut position = center;
+        let mut agent = SteeringAgent::new(SteeringBehaviour::wander(center, 4.0), 3.0);
+        for _ in 0..100 {
+            step(&mut agent, &mut position, 0.1);
+            assert!((position - center).norm() <= 4.0 + 1e-4);
+            assert_eq!(position.y, 1.0);
+        }
+        assert!(!agent.arrived);
+    }
+
+    #[test]
+    fn test_steering_moves_identity() {
+        with_server(|| {
+            let mut identity = NetworkIdentity::new_with_asset_id(0);
+            identity.set_net_id(990);
+            NetworkServerStatic::add_spawned_network_identity(identity);
+            Steering::attach(
+                990,
+                SteeringAgent::new(SteeringBehaviour::seek(Vector3::new(4.0, 0.5, 0.0)), 2.0),
+            );
+            assert_eq!(Steering::count(), 1);
+
+            Steering::step(1.0);
+            let transform = NetworkServerStatic::spawned_network_identities()
+                .get(&990)
+                .unwrap()
+                .game_object()
+                .transform;
+            // 默认位置为 (0, 0.5, 0)
+            assert_eq!(transform.position, Vector3::new(2.0, 0.5, 0.0));
+            // 朝向 +x
+            let forward =
+                UnitQuaternion::from_quaternion(transform.rotation) * Vector3::new(0.0, 0.0, 1.0);
+            assert!((forward - Vector3::new(1.0, 0.0, 0.0)).norm() < 1e-5);
+
+            Steering::step(2.0);
+            Steering::step(1.0);
+            assert!(Steering::arrived(990));
+
+            // 对象销毁后 agent 一起移除
+            NetworkServerStatic::remove_spawned_network_identity(&990);
+            Steering::step(1.0);
+            assert_eq!(Steering::count(), 0);
+        });
+    }
+}
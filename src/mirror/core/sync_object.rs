@@ -13,6 +13,10 @@ pub trait SyncObject: Any + Send + Sync + Debug {
     fn is_writable(&self) -> bool {
         true
     }
+    // 是否有未发送的修改, 有修改的 SyncObject 会在 delta 中序列化
+    fn is_dirty(&self) -> bool {
+        false
+    }
     fn clear_changes(&mut self);
     fn on_serialize_all(&self, writer: &mut NetworkWriter);
     fn on_serialize_delta(&self, writer: &mut NetworkWriter);
@@ -0,0 +1,375 @@
+use crate::mirror::core::network_context::ContextLocal;
+use crate::mirror::core::network_reader::NetworkReader;
+use crate::mirror::core::network_server::NETWORK_BEHAVIOURS;
+use crate::mirror::core::network_time::NetworkTime;
+use crate::mirror::core::network_writer::NetworkWriter;
+use crate::mirror::core::network_writer_pool::NetworkWriterPool;
+use crate::mirror::core::sync_object::SyncObject;
+use crate::{log_error, log_warn};
+use atomic::Atomic;
+use dashmap::try_result::TryResult;
+use dashmap::DashMap;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, RwLock};
+
+// 把 SyncObject 的持久状态写入 writer
+pub type PersistenceCallback = Arc<dyn Fn(&dyn SyncObject, &mut NetworkWriter) + Send + Sync>;
+// 接收一次保存的所有记录, 例如写入数据库
+pub type PersistenceSink = Arc<dyn Fn(&[PersistedSyncObject]) + Send + Sync>;
+
+// 一个 SyncObject 保存的状态, key 由注册者指定, 重启后 net_id 会变化, 按 key 找回
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PersistedSyncObject {
+    pub key: String,
+    pub net_id: u32,
+    pub component_index: u8,
+    pub sync_object_index: usize,
+    pub data: Vec<u8>,
+}
+
+#[derive(Clone)]
+struct Registration {
+    net_id: u32,
+    component_index: u8,
+    sync_object_index: usize,
+    save: PersistenceCallback,
+}
+
+// SyncObjectPersistence 静态变量
+lazy_static! {
+    // key -> 注册的 SyncObject
+    static ref REGISTRATIONS: ContextLocal<DashMap<String, Registration>> =
+        ContextLocal::new(DashMap::new);
+    static ref SINK: ContextLocal<RwLock<Option<PersistenceSink>>> =
+        ContextLocal::new(|| RwLock::new(None));
+    // 单位秒, 0 表示不定期保存, 只在 shutdown 时保存
+    static ref CHECKPOINT_INTERVAL: ContextLocal<Atomic<f64>> =
+        ContextLocal::new(|| Atomic::new(0.0));
+    static ref LAST_CHECKPOINT_TIME: ContextLocal<Atomic<f64>> =
+        ContextLocal::new(|| Atomic::new(0.0));
+}
+
+// 按对象持久化 SyncObject (背包、计分板等): 注册的回调在定期保存和 NetworkServer::shutdown 时调用,
+// 保存的记录交给 sink. 与 WorldSnapshot 不同, 只保存注册的 SyncObject, 不重新生成对象
+// 对象销毁时注册自动移除, 需要在销毁前保存时先调用 checkpoint_object
+pub struct SyncObjectPersistence;
+
+impl SyncObjectPersistence {
+    pub fn checkpoint_interval() -> f64 {
+        CHECKPOINT_INTERVAL.load(Ordering::Relaxed)
+    }
+
+    pub fn set_checkpoint_interval(value: f64) {
+        CHECKPOINT_INTERVAL.store(value, Ordering::Relaxed);
+    }
+
+    pub fn set_sink<F: Fn(&[PersistedSyncObject]) + Send + Sync + 'static>(sink: F) {
+        match SINK.write() {
+            Ok(mut current) => *current = Some(Arc::new(sink)),
+            Err(e) => {
+                log_error!("SyncObjectPersistence failed to set sink: {:?}", e);
+            }
+        }
+    }
+
+    pub fn clear_sink() {
+        if let Ok(mut current) = SINK.write() {
+            *current = None;
+        }
+    }
+
+    // 同一个 key 重复注册时替换
+    pub fn register<F: Fn(&dyn SyncObject, &mut NetworkWriter) + Send + Sync + 'static>(
+        key: &str,
+        net_id: u32,
+        component_index: u8,
+        sync_object_index: usize,
+        save: F,
+    ) {
+        REGISTRATIONS.insert(
+            key.to_string(),
+            Registration {
+                net_id,
+                component_index,
+                sync_object_index,
+                save: Arc::new(save),
+            },
+        );
+    }
+
+    // 保存 on_serialize_all 的输出, 可以用 restore 恢复
+    pub fn register_default(key: &str, net_id: u32, component_index: u8, sync_object_index: usize) {
+        Self::register(
+            key,
+            net_id,
+            component_index,
+            sync_object_index,
+            |sync_object, writer| sync_object.on_serialize_all(writer),
+        );
+    }
+
+    pub fn unregister(key: &str) -> bool {
+        REGISTRATIONS.remove(key).is_some()
+    }
+
+    pub fn is_registered(key: &str) -> bool {
+        REGISTRATIONS.contains_key(key)
+    }
+
+    pub fn count() -> usize {
+        REGISTRATIONS.len()
+    }
+
+    // 保存所有注册的 SyncObject 并交给 sink, 按 key 排序
+    pub fn checkpoint() -> Vec<PersistedSyncObject> {
+        let mut keys: Vec<String> = REGISTRATIONS
+            .iter()
+            .map(|entry| entry.key().clone())
+            .collect();
+        keys.sort();
+        let records: Vec<PersistedSyncObject> =
+            keys.iter().filter_map(|key| Self::save(key)).collect();
+        Self::deliver(&records);
+        records
+    }
+
+    // 只保存一个 net_id 的 SyncObject, 例如玩家断开连接、对象销毁之前
+    pub fn checkpoint_object(net_id: u32) -> Vec<PersistedSyncObject> {
+        let mut keys: Vec<String> = REGISTRATIONS
+            .iter()
+            .filter(|entry| entry.net_id == net_id)
+            .map(|entry| entry.key().clone())
+            .collect();
+        keys.sort();
+        let records: Vec<PersistedSyncObject> =
+            keys.iter().filter_map(|key| Self::save(key)).collect();
+        Self::deliver(&records);
+        records
+    }
+
+    fn save(key: &str) -> Option<PersistedSyncObject> {
+        // 取出回调后释放锁, 回调中可以注册或注销
+        let registration = REGISTRATIONS.get(key)?.clone();
+        let mut component = match NETWORK_BEHAVIOURS
+            .try_get_mut(&(registration.net_id, registration.component_index))
+        {
+            TryResult::Present(component) => component,
+            TryResult::Absent => {
+                log_warn!(
+                    "SyncObjectPersistence: {} netId {} component {} not found",
+                    key,
+                    registration.net_id,
+                    registration.component_index
+                );
+                return None;
+            }
+            TryResult::Locked => {
+                log_warn!(
+                    "SyncObjectPersistence: {} netId {} component {} is locked",
+                    key,
+                    registration.net_id,
+                    registration.component_index
+                );
+                return None;
+            }
+        };
+        let sync_object = match component.sync_objects().get(registration.sync_object_index) {
+            Some(sync_object) => sync_object,
+            None => {
+                log_warn!(
+                    "SyncObjectPersistence: {} netId {} has no SyncObject {}",
+                    key,
+                    registration.net_id,
+                    registration.sync_object_index
+                );
+                return None;
+            }
+        };
+        let mut data = Vec::new();
+        NetworkWriterPool::get_return(|writer| {
+            (registration.save)(&**sync_object, writer);
+            data = writer.to_bytes();
+        });
+        Some(PersistedSyncObject {
+            key: key.to_string(),
+            net_id: registration.net_id,
+            component_index: registration.component_index,
+            sync_object_index: registration.sync_object_index,
+            data,
+        })
+    }
+
+    fn deliver(records: &[PersistedSyncObject]) {
+        if records.is_empty() {
+            return;
+        }
+        let sink = match SINK.read() {
+            Ok(sink) => sink.clone(),
+            Err(e) => {
+                log_error!("SyncObjectPersistence failed to get sink: {:?}", e);
+                return;
+            }
+        };
+        if let Some(sink) = sink {
+            sink(records);
+        }
+    }
+
+    // 用 on_deserialize_all 把 register_default 保存的状态恢复到 net_id 的 SyncObject
+    // 重启后对象的 net_id 可能不同, 由调用者按 key 找到新的 net_id
+    pub fn restore(net_id: u32, record: &PersistedSyncObject) -> bool {
+        match NETWORK_BEHAVIOURS.try_get_mut(&(net_id, record.component_index)) {
+            TryResult::Present(mut component) => {
+                match component.sync_objects().get_mut(record.sync_object_index) {
+                    Some(sync_object) => {
+                        let mut reader = NetworkReader::new_with_array_segment(&record.data);
+                        sync_object.on_deserialize_all(&mut reader)
+                    }
+                    None => false,
+                }
+            }
+            _ => {
+                log_warn!(
+                    "SyncObjectPersistence.Restore: {} netId {} component {} not available",
+                    record.key,
+                    net_id,
+                    record.component_index
+                );
+                false
+            }
+        }
+    }
+
+    // 在 NetworkServer::network_late_update 中调用, 按 checkpoint_interval 定期保存
+    pub fn update() {
+        let interval = Self::checkpoint_interval();
+        if interval <= 0.0 || REGISTRATIONS.is_empty() {
+            return;
+        }
+        let local_time = NetworkTime::local_time();
+        let last_checkpoint_time = LAST_CHECKPOINT_TIME.load(Ordering::Relaxed);
+        if last_checkpoint_time == 0.0 {
+            // 第一次调用只记录时间
+            LAST_CHECKPOINT_TIME.store(local_time, Ordering::Relaxed);
+            return;
+        }
+        if local_time - last_checkpoint_time < interval {
+            return;
+        }
+        LAST_CHECKPOINT_TIME.store(local_time, Ordering::Relaxed);
+        Self::checkpoint();
+    }
+
+    // 在 NetworkServer::un_spawn_internal 中调用
+    pub(crate) fn on_despawn(net_id: u32) {
+        REGISTRATIONS.retain(|_, registration| registration.net_id != net_id);
+    }
+
+    // 注册清空, checkpoint_interval 和 sink 保留
+    pub fn reset() {
+        REGISTRATIONS.clear();
+        LAST_CHECKPOINT_TIME.store(0.0, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::core::network_reader::NetworkReaderTrait;
+    use crate::mirror::core::network_server::NetworkServer;
+    use crate::mirror::core::network_writer::NetworkWriterTrait;
+    use crate::mirror::core::sync_object::SyncObject;
+    use crate::mirror::transports::memory::memory_transport::test_util::*;
+    use std::sync::Mutex;
+
+    #[derive(Debug, Default)]
+    struct TestInventory {
+        items: Vec<u32>,
+    }
+
+    impl SyncObject for TestInventory {
+        fn sub_class_name() -> &'static str {
+            "TestInventory"
+        }
+        fn clear_changes(&mut self) {}
+        fn on_serialize_all(&self, writer: &mut NetworkWriter) {
+            writer.write_uint(self.items.len() as u32);
+            for item in self.items.iter() {
+                writer.write_uint(*item);
+            }
+        }
+        fn on_serialize_delta(&self, _writer: &mut NetworkWriter) {}
+        fn on_deserialize_all(&mut self, reader: &mut NetworkReader) -> bool {
+            let count = reader.read_uint();
+            self.items = (0..count).map(|_| reader.read_uint()).collect();
+            true
+        }
+        fn on_deserialize_delta(&mut self, _reader: &mut NetworkReader) -> bool {
+            true
+        }
+        fn reset(&mut self) {
+            self.items.clear();
+        }
+    }
+
+    fn spawn_with_inventory(net_id: u32, items: Vec<u32>) {
+        spawn_test_identity(net_id, 0, 1);
+        NETWORK_BEHAVIOURS
+            .get_mut(&(net_id, 0))
+            .unwrap()
+            .add_sync_object(Box::new(TestInventory { items }));
+    }
+
+    #[test]
+    fn test_sync_object_persistence() {
+        with_server(|| {
+            let saved = Arc::new(Mutex::new(Vec::new()));
+            let sink = saved.clone();
+            SyncObjectPersistence::set_sink(move |records| {
+                sink.lock().unwrap().extend_from_slice(records);
+            });
+            spawn_with_inventory(995, vec![3, 7]);
+            SyncObjectPersistence::register_default("player:1:inventory", 995, 0, 0);
+            SyncObjectPersistence::register("player:1:tagged", 995, 0, 0, |sync_object, writer| {
+                writer.write_byte(1);
+                sync_object.on_serialize_all(writer);
+            });
+            // 不存在的 SyncObject 不保存
+            SyncObjectPersistence::register_default("player:1:missing", 995, 0, 1);
+
+            let records = SyncObjectPersistence::checkpoint();
+            assert_eq!(records.len(), 2);
+            assert_eq!(records[0].key, "player:1:inventory");
+            assert_eq!(records[1].data[0], 1);
+            assert_eq!(records[1].data[1..], records[0].data[..]);
+            assert_eq!(saved.lock().unwrap().len(), 2);
+
+            // 恢复到另一个对象
+            spawn_with_inventory(996, Vec::new());
+            assert!(SyncObjectPersistence::restore(996, &records[0]));
+            SyncObjectPersistence::register_default("player:2:inventory", 996, 0, 0);
+            let restored = SyncObjectPersistence::checkpoint_object(996);
+            assert_eq!(restored.len(), 1);
+            assert_eq!(restored[0].data, records[0].data);
+
+            SyncObjectPersistence::on_despawn(996);
+            assert!(!SyncObjectPersistence::is_registered("player:2:inventory"));
+            assert!(SyncObjectPersistence::unregister("player:1:missing"));
+            saved.lock().unwrap().clear();
+
+            // shutdown 时保存剩下的注册
+            NetworkServer::shutdown();
+            let keys: Vec<String> = saved
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|record| record.key.clone())
+                .collect();
+            assert_eq!(keys, vec!["player:1:inventory", "player:1:tagged"]);
+            assert_eq!(SyncObjectPersistence::count(), 0);
+            SyncObjectPersistence::clear_sink();
+        });
+    }
+}
@@ -0,0 +1,227 @@
+use crate::mirror::core::network_behaviour::{NetworkBehaviour, NetworkBehaviourTrait};
+use crate::mirror::core::network_context::ContextLocal;
+use crate::mirror::core::network_events::{NetworkEvents, SyncVarChangeEvent};
+use crate::mirror::core::network_time::NetworkTime;
+use atomic::Atomic;
+use dashmap::DashMap;
+use lazy_static::lazy_static;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+
+// 一个同步变量的采样状态
+#[derive(Debug, Clone, Copy)]
+struct VarSample {
+    last_publish_time: f64,
+    changes: u64,
+}
+
+// SyncVarEvents 静态变量
+lazy_static! {
+    // 同一个同步变量两次发布的最小间隔, 单位秒, 0 表示每次修改都发布
+    static ref SAMPLE_INTERVAL: ContextLocal<Atomic<f64>> = ContextLocal::new(|| Atomic::new(0.0));
+    // 每个同步变量每 N 次修改发布一次, 1 表示每次修改都发布
+    static ref SAMPLE_RATE: ContextLocal<AtomicU32> = ContextLocal::new(|| AtomicU32::new(1));
+    // (net_id, component_index, var_index) -> 采样状态
+    static ref SAMPLES: ContextLocal<DashMap<(u32, u8, u8), VarSample>> =
+        ContextLocal::new(DashMap::new);
+    static ref PUBLISHED_COUNT: ContextLocal<AtomicU64> = ContextLocal::new(|| AtomicU64::new(0));
+    static ref SKIPPED_COUNT: ContextLocal<AtomicU64> = ContextLocal::new(|| AtomicU64::new(0));
+}
+
+// 把服务器上同步变量的修改发布到 NetworkEvents::on_sync_var_change, 供统计 / 遥测系统记录, 组件不需要修改
+// 修改在状态广播序列化时检测, 同一个 tick 内的多次修改只发布最后的值, 没有观察者的对象不会序列化, 也不会发布
+// 值来自组件的 inspect_sync_vars; 开启 ParallelSerialization 时事件在工作线程发布, 不同对象之间的顺序不固定
+pub struct SyncVarEvents;
+
+impl SyncVarEvents {
+    pub fn sample_interval() -> f64 {
+        SAMPLE_INTERVAL.load(Ordering::Relaxed)
+    }
+
+    pub fn set_sample_interval(value: f64) {
+        SAMPLE_INTERVAL.store(value.max(0.0), Ordering::Relaxed);
+    }
+
+    pub fn sample_rate() -> u32 {
+        SAMPLE_RATE.load(Ordering::Relaxed)
+    }
+
+    pub fn set_sample_rate(value: u32) {
+        SAMPLE_RATE.store(value.max(1), Ordering::Relaxed);
+    }
+
+    pub fn published_count() -> u64 {
+        PUBLISHED_COUNT.load(Ordering::Relaxed)
+    }
+
+    // 被采样规则跳过的修改
+    pub fn skipped_count() -> u64 {
+        SKIPPED_COUNT.load(Ordering::Relaxed)
+    }
+
+    // 在 NetworkIdentity::serialize_server_components 中调用, 清除脏标记之前
+    pub(crate) fn record(component: &dyn NetworkBehaviourTrait) {
+        let dirty_bits = component.sync_var_dirty_bits() & !NetworkBehaviour::ENABLED_DIRTY_BIT;
+        if dirty_bits == 0 || !NetworkEvents::has_sync_var_change_subscribers() {
+            return;
+        }
+        let (net_id, component_index) = (component.net_id(), component.index());
+        let local_time = NetworkTime::local_time();
+        let mut fields = None;
+        for var_index in 0..63u8 {
+            if dirty_bits & (1 << var_index) == 0 {
+                continue;
+            }
+            if !Self::sample((net_id, component_index, var_index), local_time) {
+                SKIPPED_COUNT.fetch_add(1, Ordering::Relaxed);
+                continue;
+            }
+            let fields = fields.get_or_insert_with(|| component.inspect_sync_vars());
+            let (name, value) = match fields.get(var_index as usize) {
+                Some(field) => (field.name.clone(), field.value.clone()),
+                None => (String::new(), Vec::new()),
+            };
+            NetworkEvents::publish_sync_var_change(SyncVarChangeEvent {
+                net_id,
+                component_index,
+                component: component.sub_class(),
+                var_index,
+                name,
+                value,
+            });
+            PUBLISHED_COUNT.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn sample(key: (u32, u8, u8), local_time: f64) -> bool {
+        let mut sample = SAMPLES.entry(key).or_insert(VarSample {
+            last_publish_time: f64::NEG_INFINITY,
+            changes: 0,
+        });
+        sample.changes += 1;
+        if !(sample.changes - 1).is_multiple_of(Self::sample_rate() as u64) {
+            return false;
+        }
+        if local_time - sample.last_publish_time < Self::sample_interval() {
+            return false;
+        }
+        sample.last_publish_time = local_time;
+        true
+    }
+
+    // 在 NetworkServer::un_spawn_internal 中调用
+    pub(crate) fn on_despawn(net_id: u32) {
+        SAMPLES.retain(|key, _| key.0 != net_id);
+    }
+
+    // 采样规则保留, 采样状态和计数清空
+    pub fn reset() {
+        SAMPLES.clear();
+        PUBLISHED_COUNT.store(0, Ordering::Relaxed);
+        SKIPPED_COUNT.store(0, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::components::network_room_player::NetworkRoomPlayer;
+    use crate::mirror::core::backend_data::NetworkBehaviourSetting;
+    use crate::mirror::core::network_behaviour::GameObject;
+    use crate::mirror::core::network_identity::{NetworkIdentity, Visibility};
+    use crate::mirror::core::network_server::{
+        NetworkServer, NetworkServerStatic, NETWORK_BEHAVIOURS,
+    };
+    use crate::mirror::transports::memory::memory_transport::test_util::*;
+    use crate::mirror::transports::memory::memory_transport::MemoryTransport;
+
+    #[test]
+    fn test_sync_var_events() {
+        with_server(|| {
+            MemoryTransport::client_connect(1);
+            tick();
+            NetworkServer::set_client_ready(1);
+            let mut player = NetworkRoomPlayer {
+                network_behaviour: NetworkBehaviour::new(
+                    GameObject::default(),
+                    NetworkBehaviourSetting::default(),
+                    0,
+                    NetworkRoomPlayer::COMPONENT_TAG.to_string(),
+                ),
+                ready_to_begin: false,
+                index: 3,
+            };
+            player.set_net_id(720);
+            NETWORK_BEHAVIOURS::add_behaviour(720, 0, Box::new(player));
+            let mut identity = NetworkIdentity::new_with_asset_id(0);
+            identity.set_net_id(720);
+            identity.network_behaviours_count = 1;
+            NetworkServerStatic::add_spawned_network_identity(identity);
+            NetworkServer::set_visibility(720, Visibility::Default);
+            tick();
+            MemoryTransport::client_receive(1);
+
+            let events = NetworkEvents::on_sync_var_change();
+            let change_index = |index: i32| {
+                {
+                    let mut behaviour = NETWORK_BEHAVIOURS.get_mut(&(720, 0)).unwrap();
+                    behaviour
+                        .as_any_mut()
+                        .downcast_mut::<NetworkRoomPlayer>()
+                        .unwrap()
+                        .index = index;
+                    behaviour.set_sync_var_dirty_bits(1 << 1);
+                    behaviour.set_last_sync_time(-1.0);
+                }
+                // 同一帧内复用上一次的序列化结果
+                NetworkTime::increment_frame_count();
+                tick();
+            };
+            let index_value = || {
+                NETWORK_BEHAVIOURS
+                    .get(&(720, 0))
+                    .unwrap()
+                    .inspect_sync_vars()[1]
+                    .value
+                    .clone()
+            };
+
+            change_index(4);
+            assert_eq!(
+                events.try_iter().collect::<Vec<SyncVarChangeEvent>>(),
+                vec![SyncVarChangeEvent {
+                    net_id: 720,
+                    component_index: 0,
+                    component: NetworkRoomPlayer::COMPONENT_TAG.to_string(),
+                    var_index: 1,
+                    name: "index".to_string(),
+                    value: index_value(),
+                }]
+            );
+
+            // 每 2 次修改发布一次
+            SyncVarEvents::set_sample_rate(2);
+            change_index(5);
+            change_index(6);
+            let value = index_value();
+            change_index(7);
+            let published: Vec<SyncVarChangeEvent> = events.try_iter().collect();
+            assert_eq!(published.len(), 1);
+            assert_eq!(published[0].value, value);
+            assert_eq!(SyncVarEvents::published_count(), 2);
+            assert_eq!(SyncVarEvents::skipped_count(), 2);
+
+            // 间隔内的修改跳过
+            SyncVarEvents::set_sample_rate(1);
+            SyncVarEvents::set_sample_interval(10.0);
+            change_index(8);
+            assert!(events.try_recv().is_err());
+            assert_eq!(SyncVarEvents::skipped_count(), 3);
+
+            SyncVarEvents::set_sample_interval(0.0);
+            NETWORK_BEHAVIOURS.remove(&(720, 0));
+            NetworkServerStatic::remove_spawned_network_identity(&720);
+            NetworkEvents::reset();
+            SyncVarEvents::reset();
+        });
+    }
+}
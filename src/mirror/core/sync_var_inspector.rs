@@ -0,0 +1,237 @@
+use crate::log_warn;
+use crate::mirror::core::network_server::{NetworkServerStatic, NETWORK_BEHAVIOURS};
+use crate::mirror::core::network_writer_pool::NetworkWriterPool;
+use dashmap::try_result::TryResult;
+use std::fmt::{Display, Formatter};
+
+// 一个同步变量的名称、类型和当前的序列化值, 由组件的 NetworkBehaviourTrait::inspect_sync_vars 提供
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyncVarField {
+    pub name: String,
+    pub type_name: String,
+    pub value: Vec<u8>,
+}
+
+impl SyncVarField {
+    pub fn new(name: &str, type_name: &str, value: Vec<u8>) -> Self {
+        Self {
+            name: name.to_string(),
+            type_name: type_name.to_string(),
+            value,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComponentInspection {
+    pub index: u8,
+    pub component: String,
+    pub enabled: bool,
+    pub sync_var_dirty_bits: u64,
+    pub sync_object_dirty_bits: u64,
+    // on_serialize(initial_state = true) 的输出, 与新观察者收到的内容相同
+    pub serialized: Vec<u8>,
+    // 组件没有提供注解时为空
+    pub fields: Vec<SyncVarField>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IdentityInspection {
+    pub net_id: u32,
+    pub asset_id: u32,
+    pub connection_to_client: u64,
+    pub observers: usize,
+    pub components: Vec<ComponentInspection>,
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+impl Display for IdentityInspection {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "netId {} asset {} owner {} observers {}",
+            self.net_id, self.asset_id, self.connection_to_client, self.observers
+        )?;
+        for component in self.components.iter() {
+            write!(
+                f,
+                "\n  [{}] {} enabled {} dirty {:#x}/{:#x} serialized {}B: {}",
+                component.index,
+                component.component,
+                component.enabled,
+                component.sync_var_dirty_bits,
+                component.sync_object_dirty_bits,
+                component.serialized.len(),
+                hex(&component.serialized)
+            )?;
+            for field in component.fields.iter() {
+                write!(
+                    f,
+                    "\n    {}: {} = {}",
+                    field.name,
+                    field.type_name,
+                    hex(&field.value)
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// 运行时查看对象的组件和同步变量, 用于线上调试, 只在开启 `inspector` feature 时可用
+// 只读: 序列化使用 initial_state, 不清除脏标记, 不影响下一次状态广播
+// 在网络循环线程中调用, 例如管理接口或控制台命令的处理函数中
+pub struct SyncVarInspector;
+
+impl SyncVarInspector {
+    pub const ENABLED: bool = cfg!(feature = "inspector");
+
+    // 所有已生成对象的 (net_id, asset_id), 按 net_id 排序
+    pub fn identities() -> Vec<(u32, u32)> {
+        if !Self::ENABLED {
+            return Vec::new();
+        }
+        let mut identities: Vec<(u32, u32)> = NetworkServerStatic::spawned_network_identities()
+            .iter()
+            .map(|identity| (*identity.key(), identity.asset_id))
+            .collect();
+        identities.sort();
+        identities
+    }
+
+    pub fn inspect(net_id: u32) -> Option<IdentityInspection> {
+        if !Self::ENABLED {
+            return None;
+        }
+        let (asset_id, connection_to_client, observers, count) =
+            match NetworkServerStatic::spawned_network_identities().try_get(&net_id) {
+                TryResult::Present(identity) => (
+                    identity.asset_id,
+                    identity.connection_to_client(),
+                    identity.observers().len(),
+                    identity.network_behaviours_count,
+                ),
+                TryResult::Absent => return None,
+                TryResult::Locked => {
+                    log_warn!("SyncVarInspector: netId {} is locked.", net_id);
+                    return None;
+                }
+            };
+
+        let mut components = Vec::with_capacity(count as usize);
+        for index in 0..count {
+            match NETWORK_BEHAVIOURS.try_get_mut(&(net_id, index)) {
+                TryResult::Present(mut component) => {
+                    let mut serialized = Vec::new();
+                    NetworkWriterPool::get_return(|writer| {
+                        component.on_serialize(writer, true);
+                        serialized = writer.to_bytes();
+                    });
+                    components.push(ComponentInspection {
+                        index,
+                        component: component.sub_class(),
+                        enabled: component.enabled(),
+                        sync_var_dirty_bits: component.sync_var_dirty_bits(),
+                        sync_object_dirty_bits: component.sync_object_dirty_bits(),
+                        serialized,
+                        fields: component.inspect_sync_vars(),
+                    });
+                }
+                TryResult::Absent => {}
+                TryResult::Locked => {
+                    log_warn!(
+                        "SyncVarInspector: netId {} component {} is locked.",
+                        net_id,
+                        index
+                    );
+                }
+            }
+        }
+        Some(IdentityInspection {
+            net_id,
+            asset_id,
+            connection_to_client,
+            observers,
+            components,
+        })
+    }
+
+    // 文本格式, 可以直接作为管理接口或 RCON 的响应
+    pub fn dump(net_id: u32) -> Option<String> {
+        Self::inspect(net_id).map(|inspection| inspection.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::components::network_room_player::NetworkRoomPlayer;
+    use crate::mirror::core::backend_data::NetworkBehaviourSetting;
+    use crate::mirror::core::network_behaviour::{
+        GameObject, NetworkBehaviour, NetworkBehaviourTrait,
+    };
+    use crate::mirror::core::network_identity::NetworkIdentity;
+    use crate::mirror::core::network_server::NETWORK_BEHAVIOURS;
+    use crate::mirror::transports::memory::memory_transport::test_util::with_isolated_context;
+
+    #[test]
+    fn test_sync_var_inspector() {
+        with_isolated_context(|| {
+            let mut player = NetworkRoomPlayer {
+                network_behaviour: NetworkBehaviour::new(
+                    GameObject::default(),
+                    NetworkBehaviourSetting::default(),
+                    0,
+                    NetworkRoomPlayer::COMPONENT_TAG.to_string(),
+                ),
+                ready_to_begin: true,
+                index: 3,
+            };
+            player.set_net_id(5);
+            player.__set_sync_var_dirty_bits(1);
+            NETWORK_BEHAVIOURS::add_behaviour(5, 0, Box::new(player));
+            let mut identity = NetworkIdentity::new_with_asset_id(2);
+            identity.set_net_id(5);
+            identity.network_behaviours_count = 1;
+            NetworkServerStatic::add_spawned_network_identity(identity);
+
+            if !SyncVarInspector::ENABLED {
+                assert!(SyncVarInspector::inspect(5).is_none());
+                assert!(SyncVarInspector::identities().is_empty());
+                return;
+            }
+            assert_eq!(SyncVarInspector::identities(), vec![(5, 2)]);
+            assert!(SyncVarInspector::inspect(6).is_none());
+
+            let inspection = SyncVarInspector::inspect(5).unwrap();
+            assert_eq!(inspection.asset_id, 2);
+            let component = &inspection.components[0];
+            assert_eq!(component.component, NetworkRoomPlayer::COMPONENT_TAG);
+            assert_eq!(component.serialized, vec![1, 6]);
+            assert_eq!(
+                component.fields[0],
+                SyncVarField::new("readyToBegin", "System.Boolean", vec![1])
+            );
+            assert_eq!(component.fields[1].value, vec![6]);
+            // 只读, 不清除脏标记
+            assert_eq!(component.sync_var_dirty_bits, 1);
+            assert_eq!(
+                NETWORK_BEHAVIOURS
+                    .get(&(5, 0))
+                    .unwrap()
+                    .sync_var_dirty_bits(),
+                1
+            );
+            assert!(SyncVarInspector::dump(5)
+                .unwrap()
+                .contains("\n    index: System.Int32 = 06"));
+        });
+    }
+}
@@ -0,0 +1,188 @@
+use crate::log_error;
+use crate::mirror::core::network_context::{ContextLocal, NetworkContext};
+use crate::mirror::core::network_loop::NetworkLoop;
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use lazy_static::lazy_static;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::thread;
+
+type Job = Box<dyn FnOnce() + Send>;
+// (generation, 完成回调)
+type Completion = (u64, Box<dyn FnOnce() + Send>);
+
+// TaskBridge 静态变量
+lazy_static! {
+    static ref JOBS: (Sender<Job>, Receiver<Job>) = unbounded();
+    static ref COMPLETIONS: ContextLocal<(Sender<Completion>, Receiver<Completion>)> =
+        ContextLocal::new(unbounded);
+    static ref WORKER_COUNT: AtomicUsize = AtomicUsize::new(4);
+    static ref STARTED_WORKERS: AtomicUsize = AtomicUsize::new(0);
+    // reset 之后完成的旧任务不再执行回调
+    static ref GENERATION: ContextLocal<AtomicU64> = ContextLocal::new(|| AtomicU64::new(0));
+    static ref PENDING: ContextLocal<AtomicUsize> = ContextLocal::new(|| AtomicUsize::new(0));
+}
+
+// 后台任务: work 在工作线程中执行 (数据库查询、HTTP 请求等阻塞操作),
+// 结果交给 on_complete, 在之后的 NetworkServer::network_late_update 中于网络循环线程执行
+// work panic 时只记录日志, 不执行 on_complete
+pub struct TaskBridge;
+
+impl TaskBridge {
+    pub fn worker_count() -> usize {
+        WORKER_COUNT.load(Ordering::Relaxed)
+    }
+
+    // 已经启动的工作线程不会退出, 只能增加
+    pub fn set_worker_count(value: usize) {
+        WORKER_COUNT.store(value.max(1), Ordering::Relaxed);
+    }
+
+    // 已提交但回调还没有执行的任务数量
+    pub fn pending_count() -> usize {
+        PENDING.load(Ordering::Relaxed)
+    }
+
+    pub fn spawn<T, W, C>(work: W, on_complete: C)
+    where
+        T: Send + 'static,
+        W: FnOnce() -> T + Send + 'static,
+        C: FnOnce(T) + Send + 'static,
+    {
+        Self::ensure_workers();
+        PENDING.fetch_add(1, Ordering::Relaxed);
+        let generation = GENERATION.load(Ordering::Relaxed);
+        // 工作线程是共享的, 结果交回提交任务的上下文
+        let context = NetworkContext::current();
+        let job: Job = Box::new(move || {
+            NetworkContext::enter(context, || match catch_unwind(AssertUnwindSafe(work)) {
+                Ok(result) => {
+                    let completion: Completion =
+                        (generation, Box::new(move || on_complete(result)));
+                    let _ = COMPLETIONS.0.send(completion);
+                    NetworkLoop::wake();
+                }
+                Err(payload) => {
+                    let reason = if let Some(reason) = payload.downcast_ref::<&str>() {
+                        reason.to_string()
+                    } else if let Some(reason) = payload.downcast_ref::<String>() {
+                        reason.clone()
+                    } else {
+                        "unknown".to_string()
+                    };
+                    log_error!("TaskBridge task panicked: {}", reason);
+                    if GENERATION.load(Ordering::Relaxed) == generation {
+                        PENDING.fetch_sub(1, Ordering::Relaxed);
+                    }
+                }
+            })
+        });
+        if JOBS.0.send(job).is_err() {
+            PENDING.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+
+    fn ensure_workers() {
+        while STARTED_WORKERS.load(Ordering::Relaxed) < Self::worker_count() {
+            let index = STARTED_WORKERS.fetch_add(1, Ordering::Relaxed);
+            let receiver = JOBS.1.clone();
+            let spawned = thread::Builder::new()
+                .name(format!("task-bridge-{}", index))
+                .spawn(move || {
+                    while let Ok(job) = receiver.recv() {
+                        job();
+                    }
+                });
+            if let Err(e) = spawned {
+                log_error!("TaskBridge failed to start worker: {}", e);
+                STARTED_WORKERS.fetch_sub(1, Ordering::Relaxed);
+                return;
+            }
+        }
+    }
+
+    // 在 NetworkServer::network_late_update 中 Scheduler::update 之后调用
+    // 只执行调用时已经完成的任务, 回调中提交的任务最早下一个 tick 返回
+    pub fn update() {
+        let generation = GENERATION.load(Ordering::Relaxed);
+        let count = COMPLETIONS.1.len();
+        for (task_generation, on_complete) in COMPLETIONS.1.try_iter().take(count) {
+            if task_generation != generation {
+                continue;
+            }
+            PENDING.fetch_sub(1, Ordering::Relaxed);
+            // 一个回调 panic 不影响网络循环和其他回调
+            if catch_unwind(AssertUnwindSafe(on_complete)).is_err() {
+                log_error!("TaskBridge completion callback panicked");
+            }
+        }
+    }
+
+    // 正在执行的任务会继续完成, 但回调被丢弃
+    pub fn reset() {
+        GENERATION.fetch_add(1, Ordering::Relaxed);
+        PENDING.store(0, Ordering::Relaxed);
+        while COMPLETIONS.1.try_recv().is_ok() {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::transports::memory::memory_transport::test_util::*;
+    use std::sync::atomic::AtomicU32;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    #[test]
+    fn test_task_bridge() {
+        with_server(|| {
+            let results = Arc::new(Mutex::new(Vec::new()));
+            let loop_thread = std::thread::current().id();
+            for value in 1..=3 {
+                let results = results.clone();
+                TaskBridge::spawn(
+                    move || {
+                        // 模拟阻塞 I/O
+                        std::thread::sleep(Duration::from_millis(10));
+                        value * 10
+                    },
+                    move |result| {
+                        // 回调在网络循环线程执行
+                        assert_eq!(std::thread::current().id(), loop_thread);
+                        results.lock().unwrap().push(result);
+                    },
+                );
+            }
+            TaskBridge::spawn(|| -> i32 { panic!("query failed") }, |_| unreachable!());
+            assert_eq!(TaskBridge::pending_count(), 4);
+
+            for _ in 0..200 {
+                if TaskBridge::pending_count() == 0 {
+                    break;
+                }
+                std::thread::sleep(Duration::from_millis(5));
+                tick();
+            }
+            let mut results = results.lock().unwrap().clone();
+            results.sort();
+            assert_eq!(results, vec![10, 20, 30]);
+            assert_eq!(TaskBridge::pending_count(), 0);
+
+            // 完成的任务在 tick 之前不执行回调
+            let done = Arc::new(AtomicU32::new(0));
+            let counter = done.clone();
+            TaskBridge::spawn(
+                || (),
+                move |_| {
+                    counter.fetch_add(1, Ordering::Relaxed);
+                },
+            );
+            std::thread::sleep(Duration::from_millis(50));
+            assert_eq!(done.load(Ordering::Relaxed), 0);
+            tick();
+            assert_eq!(done.load(Ordering::Relaxed), 1);
+            TaskBridge::reset();
+        });
+    }
+}
@@ -0,0 +1,153 @@
+use dashmap::DashMap;
+use lazy_static::lazy_static;
+use std::fmt::{Display, Formatter};
+
+// 热路径上已知会分配内存的位置
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+pub enum AllocSite {
+    // NetworkWriterPool 为空, 新建 NetworkWriter
+    WriterPoolMiss,
+    // NetworkReaderPool 为空, 新建 NetworkReader
+    ReaderPoolMiss,
+    // 日志字符串格式化
+    LogFormat,
+    // observers / observing 的 Vec 扩容
+    ObserverGrowth,
+}
+
+impl Display for AllocSite {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AllocSite::WriterPoolMiss => write!(f, "WriterPoolMiss"),
+            AllocSite::ReaderPoolMiss => write!(f, "ReaderPoolMiss"),
+            AllocSite::LogFormat => write!(f, "LogFormat"),
+            AllocSite::ObserverGrowth => write!(f, "ObserverGrowth"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AllocAuditEntry {
+    pub site: AllocSite,
+    // 上一个 tick 的次数
+    pub last_tick: u64,
+    // 单个 tick 的最大次数
+    pub peak_tick: u64,
+    pub total: u64,
+}
+
+lazy_static! {
+    static ref TICK_COUNTS: DashMap<AllocSite, u64> = DashMap::new();
+    static ref ENTRIES: DashMap<AllocSite, AllocAuditEntry> = DashMap::new();
+}
+
+// 分配审计, 只在开启 `alloc_audit` feature 时计数, 否则 record 为空操作
+pub struct AllocAudit;
+
+impl AllocAudit {
+    pub const ENABLED: bool = cfg!(feature = "alloc_audit");
+
+    #[inline]
+    pub fn record(site: AllocSite) {
+        if Self::ENABLED {
+            *TICK_COUNTS.entry(site).or_insert(0) += 1;
+        }
+    }
+
+    // push 之前调用, len == capacity 时 Vec 会扩容
+    #[inline]
+    pub fn record_growth(site: AllocSite, len: usize, capacity: usize) {
+        if Self::ENABLED && len == capacity {
+            Self::record(site);
+        }
+    }
+
+    // 在 NetworkServer::network_late_update 末尾调用, 把本 tick 的计数归档
+    pub fn end_tick() {
+        if !Self::ENABLED {
+            return;
+        }
+        for mut entry in ENTRIES.iter_mut() {
+            entry.last_tick = 0;
+        }
+        let counts: Vec<(AllocSite, u64)> = TICK_COUNTS
+            .iter()
+            .map(|count| (*count.key(), *count.value()))
+            .collect();
+        TICK_COUNTS.clear();
+        for (site, count) in counts {
+            let mut entry = ENTRIES.entry(site).or_insert(AllocAuditEntry {
+                site,
+                last_tick: 0,
+                peak_tick: 0,
+                total: 0,
+            });
+            entry.last_tick = count;
+            entry.peak_tick = entry.peak_tick.max(count);
+            entry.total += count;
+        }
+    }
+
+    // 按总次数排序, 返回前 n 个
+    pub fn top(n: usize) -> Vec<AllocAuditEntry> {
+        let mut entries: Vec<AllocAuditEntry> = ENTRIES.iter().map(|entry| *entry).collect();
+        entries.sort_by(|a, b| b.total.cmp(&a.total).then(b.peak_tick.cmp(&a.peak_tick)));
+        entries.truncate(n);
+        entries
+    }
+
+    // 与 actual_tick_rate 一起每秒输出一次
+    pub fn report(n: usize) -> Option<String> {
+        let top = Self::top(n);
+        if top.is_empty() {
+            return None;
+        }
+        let mut report = String::from("AllocAudit top offenders:");
+        for entry in top {
+            report.push_str(&format!(
+                " {} (tick {}, peak {}, total {})",
+                entry.site, entry.last_tick, entry.peak_tick, entry.total
+            ));
+        }
+        Some(report)
+    }
+
+    pub fn reset() {
+        TICK_COUNTS.clear();
+        ENTRIES.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alloc_audit() {
+        AllocAudit::reset();
+        AllocAudit::record(AllocSite::WriterPoolMiss);
+        AllocAudit::record(AllocSite::WriterPoolMiss);
+        AllocAudit::record_growth(AllocSite::ObserverGrowth, 0, 0);
+        AllocAudit::end_tick();
+        AllocAudit::record(AllocSite::WriterPoolMiss);
+        AllocAudit::end_tick();
+
+        if !AllocAudit::ENABLED {
+            assert!(AllocAudit::top(5).is_empty());
+            assert!(AllocAudit::report(5).is_none());
+            return;
+        }
+        // 其他测试也会计数, 只检查下限
+        let top = AllocAudit::top(usize::MAX);
+        let writer = top
+            .iter()
+            .find(|entry| entry.site == AllocSite::WriterPoolMiss)
+            .unwrap();
+        assert!(writer.peak_tick >= 1 && writer.total >= 3);
+        assert!(top
+            .iter()
+            .any(|entry| entry.site == AllocSite::ObserverGrowth));
+        assert!(AllocAudit::report(5).is_some());
+        AllocAudit::reset();
+    }
+}
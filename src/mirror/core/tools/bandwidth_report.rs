@@ -0,0 +1,289 @@
+use crate::log_info;
+use crate::mirror::core::network_context::ContextLocal;
+use crate::mirror::core::network_time::NetworkTime;
+use atomic::Atomic;
+use dashmap::DashMap;
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// 一类对象 (asset_id) 上一种组件发送的字节数
+#[derive(Debug, Clone, PartialEq)]
+pub struct BandwidthEntry {
+    pub asset_id: u32,
+    pub component_index: u8,
+    // 组件类型, 例如 Mirror.NetworkTransformUnreliable
+    pub component: String,
+    pub bytes: u64,
+    pub messages: u64,
+    // 占所有记录字节数的比例, 0 ~ 1
+    pub share: f64,
+}
+
+impl Display for BandwidthEntry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "asset {} {}[{}] {}B ({:.1}%) messages {}",
+            self.asset_id,
+            self.component,
+            self.component_index,
+            self.bytes,
+            self.share * 100.0,
+            self.messages
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+struct BandwidthCounter {
+    component: String,
+    bytes: u64,
+    messages: u64,
+}
+
+// BandwidthReport 静态变量
+lazy_static! {
+    static ref ENABLED: ContextLocal<AtomicBool> = ContextLocal::new(|| AtomicBool::new(false));
+    // (asset_id, component_index) -> 计数, 同一个 asset 的组件顺序相同
+    static ref COUNTERS: ContextLocal<DashMap<(u32, u8), BandwidthCounter>> =
+        ContextLocal::new(DashMap::new);
+    // 单位秒, 0 表示不输出日志
+    static ref LOG_INTERVAL: ContextLocal<Atomic<f64>> = ContextLocal::new(|| Atomic::new(0.0));
+    static ref LAST_LOG_TIME: ContextLocal<Atomic<f64>> = ContextLocal::new(|| Atomic::new(0.0));
+}
+
+// 按 (asset_id, 组件类型) 统计发送给客户端的字节数, 找出占用带宽最多的组件
+// 统计状态同步 (EntityStateMessage) 和 Rpc 的负载, 每个接收的连接计算一次
+// 默认关闭, 开启后序列化时额外记录每个组件的字节数
+pub struct BandwidthReport;
+
+impl BandwidthReport {
+    // 不属于某个组件的字节, 例如 EntityStateMessage 中的脏标记
+    pub const IDENTITY_COMPONENT: u8 = u8::MAX;
+
+    pub fn enabled() -> bool {
+        ENABLED.load(Ordering::Relaxed)
+    }
+
+    pub fn set_enabled(value: bool) {
+        ENABLED.store(value, Ordering::Relaxed);
+    }
+
+    pub fn log_interval() -> f64 {
+        LOG_INTERVAL.load(Ordering::Relaxed)
+    }
+
+    pub fn set_log_interval(value: f64) {
+        LOG_INTERVAL.store(value, Ordering::Relaxed);
+    }
+
+    // component 只在第一次记录时调用
+    pub fn record<F: FnOnce() -> String>(
+        asset_id: u32,
+        component_index: u8,
+        bytes: usize,
+        component: F,
+    ) {
+        Self::record_messages(asset_id, component_index, bytes, 1, component);
+    }
+
+    // 同一个消息发送给 messages 个连接
+    pub fn record_messages<F: FnOnce() -> String>(
+        asset_id: u32,
+        component_index: u8,
+        bytes: usize,
+        messages: u64,
+        component: F,
+    ) {
+        if !Self::enabled() || messages == 0 {
+            return;
+        }
+        let mut counter = COUNTERS
+            .entry((asset_id, component_index))
+            .or_insert_with(|| BandwidthCounter {
+                component: component(),
+                bytes: 0,
+                messages: 0,
+            });
+        counter.bytes += bytes as u64 * messages;
+        counter.messages += messages;
+    }
+
+    // 按字节数从大到小排序
+    pub fn report() -> Vec<BandwidthEntry> {
+        let total: u64 = COUNTERS.iter().map(|counter| counter.bytes).sum();
+        let mut entries: Vec<BandwidthEntry> = COUNTERS
+            .iter()
+            .map(|counter| {
+                let (asset_id, component_index) = *counter.key();
+                BandwidthEntry {
+                    asset_id,
+                    component_index,
+                    component: counter.component.clone(),
+                    bytes: counter.bytes,
+                    messages: counter.messages,
+                    share: Self::share(counter.bytes, total),
+                }
+            })
+            .collect();
+        entries.sort_by(|a, b| {
+            b.bytes
+                .cmp(&a.bytes)
+                .then(a.asset_id.cmp(&b.asset_id))
+                .then(a.component_index.cmp(&b.component_index))
+        });
+        entries
+    }
+
+    // 按组件类型合并所有 asset, 返回 (组件类型, 字节数, 比例), 按字节数从大到小排序
+    pub fn by_component() -> Vec<(String, u64, f64)> {
+        let mut totals: HashMap<String, u64> = HashMap::new();
+        for counter in COUNTERS.iter() {
+            *totals.entry(counter.component.clone()).or_insert(0) += counter.bytes;
+        }
+        let total: u64 = totals.values().sum();
+        let mut components: Vec<(String, u64, f64)> = totals
+            .into_iter()
+            .map(|(component, bytes)| (component, bytes, Self::share(bytes, total)))
+            .collect();
+        components.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        components
+    }
+
+    fn share(bytes: u64, total: u64) -> f64 {
+        if total == 0 {
+            return 0.0;
+        }
+        bytes as f64 / total as f64
+    }
+
+    // 前 n 项的文本报告, 没有记录时返回 None
+    pub fn summary(n: usize) -> Option<String> {
+        let report = Self::report();
+        if report.is_empty() {
+            return None;
+        }
+        let total: u64 = report.iter().map(|entry| entry.bytes).sum();
+        let mut summary = format!("BandwidthReport: total {}B", total);
+        for entry in report.iter().take(n) {
+            summary.push_str(&format!("\n  {}", entry));
+        }
+        Some(summary)
+    }
+
+    // 在 NetworkServer::network_late_update 中调用, 按 log_interval 输出报告
+    pub fn update() {
+        let interval = Self::log_interval();
+        if !Self::enabled() || interval <= 0.0 {
+            return;
+        }
+        let local_time = NetworkTime::local_time();
+        if local_time - LAST_LOG_TIME.load(Ordering::Relaxed) < interval {
+            return;
+        }
+        LAST_LOG_TIME.store(local_time, Ordering::Relaxed);
+        if let Some(summary) = Self::summary(10) {
+            log_info!(summary);
+        }
+    }
+
+    pub fn reset() {
+        COUNTERS.clear();
+        LAST_LOG_TIME.store(0.0, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::core::network_identity::Visibility;
+    use crate::mirror::core::network_server::{
+        NetworkServer, NetworkServerStatic, NETWORK_BEHAVIOURS,
+    };
+    use crate::mirror::transports::memory::memory_transport::test_util::*;
+    use crate::mirror::transports::memory::memory_transport::MemoryTransport;
+
+    #[test]
+    fn test_bandwidth_report() {
+        with_isolated_context(|| {
+            BandwidthReport::record(1, 0, 100, || "A".to_string());
+            assert!(BandwidthReport::report().is_empty());
+
+            BandwidthReport::set_enabled(true);
+            BandwidthReport::record(1, 0, 100, || "Transform".to_string());
+            BandwidthReport::record(1, 0, 600, || unreachable!());
+            BandwidthReport::record(1, 1, 100, || "Animator".to_string());
+            BandwidthReport::record(2, 0, 200, || "Transform".to_string());
+
+            let report = BandwidthReport::report();
+            assert_eq!(report.len(), 3);
+            assert_eq!((report[0].asset_id, report[0].bytes), (1, 700));
+            assert_eq!(report[0].messages, 2);
+            assert!((report[0].share - 0.7).abs() < 1e-9);
+            assert_eq!(report[1].asset_id, 2);
+
+            let components = BandwidthReport::by_component();
+            assert_eq!(components[0].0, "Transform");
+            assert_eq!(components[0].1, 900);
+            assert!(BandwidthReport::summary(1)
+                .unwrap()
+                .starts_with("BandwidthReport: total 1000B\n  asset 1 Transform[0] 700B (70.0%)"));
+
+            BandwidthReport::reset();
+            assert!(BandwidthReport::summary(10).is_none());
+        });
+    }
+
+    #[test]
+    fn test_bandwidth_report_attribution() {
+        with_server(|| {
+            BandwidthReport::set_enabled(true);
+            for conn_id in [1, 2] {
+                MemoryTransport::client_connect(conn_id);
+            }
+            tick();
+            for conn_id in [1, 2] {
+                NetworkServer::set_client_ready(conn_id);
+            }
+
+            spawn_test_identity(610, 0, 1);
+            NetworkServerStatic::spawned_network_identities()
+                .get_mut(&610)
+                .unwrap()
+                .asset_id = 9;
+            NetworkServer::set_visibility(610, Visibility::Default);
+            tick();
+            BandwidthReport::reset();
+
+            NetworkTime::increment_frame_count();
+            {
+                let mut behaviour = NETWORK_BEHAVIOURS.get_mut(&(610, 0)).unwrap();
+                behaviour.set_sync_var_dirty_bits(1);
+                behaviour.set_last_sync_time(-1.0);
+            }
+            tick();
+
+            // 一次序列化发送给两个连接, 每个连接计算一次
+            let report = BandwidthReport::report();
+            let component = report
+                .iter()
+                .find(|entry| entry.asset_id == 9 && entry.component_index == 0)
+                .unwrap();
+            assert_eq!(component.component, "Test");
+            assert_eq!(component.messages, 2);
+            assert!(component.bytes > 0);
+            let identity = report
+                .iter()
+                .find(|entry| entry.component_index == BandwidthReport::IDENTITY_COMPONENT)
+                .unwrap();
+            assert_eq!((identity.asset_id, identity.messages), (9, 2));
+            assert_eq!(BandwidthReport::by_component()[0].0, "Test");
+
+            BandwidthReport::set_enabled(false);
+            NetworkServerStatic::remove_spawned_network_identity(&610);
+            BandwidthReport::reset();
+        });
+    }
+}
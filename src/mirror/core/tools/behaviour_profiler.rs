@@ -0,0 +1,262 @@
+use crate::log_info;
+use crate::mirror::core::network_behaviour::NetworkBehaviourTrait;
+use crate::mirror::core::network_context::ContextLocal;
+use crate::mirror::core::network_time::NetworkTime;
+use atomic::Atomic;
+use dashmap::DashMap;
+use lazy_static::lazy_static;
+use std::any::TypeId;
+use std::fmt::{Display, Formatter};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProfilePhase {
+    Update = 0,
+    LateUpdate = 1,
+    Serialize = 2,
+}
+
+// 一种组件在统计期间平均每个 tick 的耗时
+#[derive(Debug, Clone, PartialEq)]
+pub struct BehaviourProfileEntry {
+    // 组件类型, 例如 Mirror.NetworkTransformUnreliable
+    pub component: String,
+    // 平均每个 tick 调用的实例数
+    pub instances: f64,
+    // 单位毫秒, 按 ProfilePhase 的顺序
+    pub phases: [f64; 3],
+}
+
+impl BehaviourProfileEntry {
+    pub fn total(&self) -> f64 {
+        self.phases.iter().sum()
+    }
+}
+
+impl Display for BehaviourProfileEntry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {:.1}ms/tick across {:.0} instances (update {:.2}ms, late_update {:.2}ms, serialize {:.2}ms)",
+            self.component,
+            self.total(),
+            self.instances,
+            self.phases[ProfilePhase::Update as usize],
+            self.phases[ProfilePhase::LateUpdate as usize],
+            self.phases[ProfilePhase::Serialize as usize]
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ProfileBucket {
+    component: String,
+    elapsed: [Duration; 3],
+    calls: [u64; 3],
+}
+
+// BehaviourProfiler 静态变量
+lazy_static! {
+    static ref ENABLED: ContextLocal<AtomicBool> = ContextLocal::new(|| AtomicBool::new(false));
+    // 组件类型 -> 耗时
+    static ref BUCKETS: ContextLocal<DashMap<TypeId, ProfileBucket>> =
+        ContextLocal::new(DashMap::new);
+    static ref TICKS: ContextLocal<Atomic<u64>> = ContextLocal::new(|| Atomic::new(0));
+    // 单位秒, 0 表示不输出日志
+    static ref LOG_INTERVAL: ContextLocal<Atomic<f64>> = ContextLocal::new(|| Atomic::new(0.0));
+    static ref LAST_LOG_TIME: ContextLocal<Atomic<f64>> = ContextLocal::new(|| Atomic::new(0.0));
+}
+
+// 按组件类型统计 update / late_update / serialize 的耗时, 找出占用 tick 时间最多的组件
+// 默认关闭, 关闭时只多一次原子读取
+pub struct BehaviourProfiler;
+
+impl BehaviourProfiler {
+    pub fn enabled() -> bool {
+        ENABLED.load(Ordering::Relaxed)
+    }
+
+    pub fn set_enabled(value: bool) {
+        ENABLED.store(value, Ordering::Relaxed);
+    }
+
+    pub fn log_interval() -> f64 {
+        LOG_INTERVAL.load(Ordering::Relaxed)
+    }
+
+    pub fn set_log_interval(value: f64) {
+        LOG_INTERVAL.store(value, Ordering::Relaxed);
+    }
+
+    // 开启时返回计时起点, 与 end 成对使用
+    pub fn begin() -> Option<Instant> {
+        if Self::enabled() {
+            Some(Instant::now())
+        } else {
+            None
+        }
+    }
+
+    pub fn end(begin: Option<Instant>, phase: ProfilePhase, component: &dyn NetworkBehaviourTrait) {
+        if let Some(begin) = begin {
+            Self::record((*component).type_id(), phase, begin.elapsed(), || {
+                component.sub_class()
+            });
+        }
+    }
+
+    // component 只在第一次记录时调用
+    pub fn record<F: FnOnce() -> String>(
+        type_id: TypeId,
+        phase: ProfilePhase,
+        elapsed: Duration,
+        component: F,
+    ) {
+        if !Self::enabled() {
+            return;
+        }
+        let mut bucket = BUCKETS.entry(type_id).or_insert_with(|| ProfileBucket {
+            component: component(),
+            elapsed: [Duration::ZERO; 3],
+            calls: [0; 3],
+        });
+        bucket.elapsed[phase as usize] += elapsed;
+        bucket.calls[phase as usize] += 1;
+    }
+
+    // 在 NetworkServer::network_late_update 中每个 tick 调用一次
+    pub fn end_tick() {
+        if Self::enabled() {
+            TICKS.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    // 按总耗时从大到小排序
+    pub fn report() -> Vec<BehaviourProfileEntry> {
+        let ticks = TICKS.load(Ordering::Relaxed).max(1) as f64;
+        let mut entries: Vec<BehaviourProfileEntry> = BUCKETS
+            .iter()
+            .map(|bucket| BehaviourProfileEntry {
+                component: bucket.component.clone(),
+                // serialize 只在有修改时调用, 用调用最多的阶段估计实例数
+                instances: *bucket.calls.iter().max().unwrap_or(&0) as f64 / ticks,
+                phases: bucket
+                    .elapsed
+                    .map(|elapsed| elapsed.as_secs_f64() * 1000.0 / ticks),
+            })
+            .collect();
+        entries.sort_by(|a, b| {
+            b.total()
+                .total_cmp(&a.total())
+                .then(a.component.cmp(&b.component))
+        });
+        entries
+    }
+
+    // 前 n 项的文本报告, 没有记录时返回 None
+    pub fn summary(n: usize) -> Option<String> {
+        let report = Self::report();
+        if report.is_empty() {
+            return None;
+        }
+        let total: f64 = report.iter().map(|entry| entry.total()).sum();
+        let mut summary = format!(
+            "BehaviourProfiler: {:.1}ms/tick over {} ticks",
+            total,
+            TICKS.load(Ordering::Relaxed)
+        );
+        for entry in report.iter().take(n) {
+            summary.push_str(&format!("\n  {}", entry));
+        }
+        Some(summary)
+    }
+
+    // 在 NetworkServer::network_late_update 中调用, 按 log_interval 输出报告
+    // 每次输出后重新统计, 报告的是最近一个周期的平均值
+    pub fn update() {
+        let interval = Self::log_interval();
+        if !Self::enabled() || interval <= 0.0 {
+            return;
+        }
+        let local_time = NetworkTime::local_time();
+        if local_time - LAST_LOG_TIME.load(Ordering::Relaxed) < interval {
+            return;
+        }
+        LAST_LOG_TIME.store(local_time, Ordering::Relaxed);
+        if let Some(summary) = Self::summary(10) {
+            log_info!(summary);
+        }
+        BUCKETS.clear();
+        TICKS.store(0, Ordering::Relaxed);
+    }
+
+    pub fn reset() {
+        BUCKETS.clear();
+        TICKS.store(0, Ordering::Relaxed);
+        LAST_LOG_TIME.store(0.0, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::transports::memory::memory_transport::test_util::with_isolated_context;
+
+    struct Transform;
+    struct Animator;
+
+    #[test]
+    fn test_behaviour_profiler() {
+        with_isolated_context(|| {
+            let transform = TypeId::of::<Transform>();
+            let animator = TypeId::of::<Animator>();
+            BehaviourProfiler::record(
+                transform,
+                ProfilePhase::Update,
+                Duration::from_millis(1),
+                || "A".to_string(),
+            );
+            assert!(BehaviourProfiler::report().is_empty());
+
+            BehaviourProfiler::set_enabled(true);
+            for _ in 0..2 {
+                for _ in 0..3 {
+                    BehaviourProfiler::record(
+                        transform,
+                        ProfilePhase::Update,
+                        Duration::from_millis(1),
+                        || "Transform".to_string(),
+                    );
+                }
+                BehaviourProfiler::record(
+                    transform,
+                    ProfilePhase::Serialize,
+                    Duration::from_millis(2),
+                    || unreachable!(),
+                );
+                BehaviourProfiler::record(
+                    animator,
+                    ProfilePhase::LateUpdate,
+                    Duration::from_millis(1),
+                    || "Animator".to_string(),
+                );
+                BehaviourProfiler::end_tick();
+            }
+
+            let report = BehaviourProfiler::report();
+            assert_eq!(report.len(), 2);
+            assert_eq!(report[0].component, "Transform");
+            assert!((report[0].instances - 3.0).abs() < 1e-9);
+            assert!((report[0].phases[ProfilePhase::Update as usize] - 3.0).abs() < 1e-6);
+            assert!((report[0].total() - 5.0).abs() < 1e-6);
+            assert_eq!(report[1].component, "Animator");
+            assert!(BehaviourProfiler::summary(1).unwrap().starts_with(
+                "BehaviourProfiler: 6.0ms/tick over 2 ticks\n  Transform: 5.0ms/tick across 3 instances"
+            ));
+
+            BehaviourProfiler::reset();
+            assert!(BehaviourProfiler::summary(10).is_none());
+        });
+    }
+}
@@ -188,6 +188,25 @@ impl Compress {
         v
     }
 
+    // 四元数的四个分量分别量化, 用于和上一帧做 delta 压缩
+    pub fn quaternion_to_vector4long(value: Quaternion<f32>, precision: f32) -> Vector4<i64> {
+        Vector4::new(
+            Self::float_to_long(value.i, precision).1,
+            Self::float_to_long(value.j, precision).1,
+            Self::float_to_long(value.k, precision).1,
+            Self::float_to_long(value.w, precision).1,
+        )
+    }
+
+    pub fn vector4long_to_quaternion(value: Vector4<i64>, precision: f32) -> Quaternion<f32> {
+        Self::quaternion_normalize_safe(Vector4::new(
+            Self::long_to_float(value.x, precision),
+            Self::long_to_float(value.y, precision),
+            Self::long_to_float(value.z, precision),
+            Self::long_to_float(value.w, precision),
+        ))
+    }
+
     pub fn var_uint_size(value: u64) -> usize {
         if value <= 240 {
             return 1;
@@ -1,5 +1,6 @@
 use crate::mirror::core::network_reader::{NetworkReader, NetworkReaderTrait};
 use crate::mirror::core::network_writer::{NetworkWriter, NetworkWriterTrait};
+use crate::mirror::core::tools::compress::Compress;
 use nalgebra::{Vector3, Vector4};
 
 pub struct DeltaCompression;
@@ -23,6 +24,24 @@ impl DeltaCompression {
             Self::decompress_long(reader, last.z),
         )
     }
+    // 只写入 axes 中为 true 的轴
+    pub fn compress_vector3long_axes(writer: &mut NetworkWriter, last: Vector3<i64>, current: Vector3<i64>, axes: [bool; 3]) {
+        for (i, sync) in axes.iter().enumerate() {
+            if *sync {
+                Self::compress_long(writer, last[i], current[i]);
+            }
+        }
+    }
+    // 未同步的轴保持 last 的值
+    pub fn decompress_vector3long_axes(reader: &mut NetworkReader, last: Vector3<i64>, axes: [bool; 3]) -> Vector3<i64> {
+        let mut value = last;
+        for (i, sync) in axes.iter().enumerate() {
+            if *sync {
+                value[i] = Self::decompress_long(reader, last[i]);
+            }
+        }
+        value
+    }
     pub fn compress_vector4long(writer: &mut NetworkWriter, last: Vector4<i64>, current: Vector4<i64>) {
         Self::compress_long(writer, last.x, current.x);
         Self::compress_long(writer, last.y, current.y);
@@ -37,4 +56,179 @@ impl DeltaCompression {
             Self::decompress_long(reader, last.w),
         )
     }
-}
\ No newline at end of file
+}
+
+// 可以做 delta 压缩的量化值, 默认值是初始状态的 delta 基准
+pub trait DeltaValue: Copy + PartialEq + Default {
+    fn compress(writer: &mut NetworkWriter, last: Self, current: Self);
+    fn decompress(reader: &mut NetworkReader, last: Self) -> Self;
+}
+
+impl DeltaValue for i64 {
+    fn compress(writer: &mut NetworkWriter, last: Self, current: Self) {
+        DeltaCompression::compress_long(writer, last, current);
+    }
+    fn decompress(reader: &mut NetworkReader, last: Self) -> Self {
+        DeltaCompression::decompress_long(reader, last)
+    }
+}
+
+impl DeltaValue for Vector3<i64> {
+    fn compress(writer: &mut NetworkWriter, last: Self, current: Self) {
+        DeltaCompression::compress_vector3long(writer, last, current);
+    }
+    fn decompress(reader: &mut NetworkReader, last: Self) -> Self {
+        DeltaCompression::decompress_vector3long(reader, last)
+    }
+}
+
+impl DeltaValue for Vector4<i64> {
+    fn compress(writer: &mut NetworkWriter, last: Self, current: Self) {
+        DeltaCompression::compress_vector4long(writer, last, current);
+    }
+    fn decompress(reader: &mut NetworkReader, last: Self) -> Self {
+        DeltaCompression::decompress_vector4long(reader, last)
+    }
+}
+
+// 使用 delta 压缩的同步变量, 任何组件都可以在 serialize_sync_vars / deserialize_sync_vars 中使用
+// 与 NetworkTransformReliable 一样, 初始状态写入上一次序列化的值, 新观察者的 delta 基准与其他观察者一致
+// 观察者变化时调用 on_observers_changed: 所有观察者离开后基准重置, 下一个观察者直接收到当前值
+// 组件的 on_stop_server / reset_state 中调用 reset
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct DeltaVar<T: DeltaValue> {
+    value: T,
+    // None 表示还没有序列化过
+    last_serialized: Option<T>,
+    last_deserialized: T,
+}
+
+impl<T: DeltaValue> DeltaVar<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            value,
+            last_serialized: None,
+            last_deserialized: T::default(),
+        }
+    }
+
+    pub fn value(&self) -> T {
+        self.value
+    }
+
+    // 返回值是否改变, 改变时由调用者设置同步变量的脏位
+    pub fn set(&mut self, value: T) -> bool {
+        if self.value == value {
+            return false;
+        }
+        self.value = value;
+        true
+    }
+
+    // 与上一次序列化的值不同
+    pub fn changed(&self) -> bool {
+        self.last_serialized != Some(self.value)
+    }
+
+    pub fn serialize(&mut self, writer: &mut NetworkWriter, initial_state: bool) {
+        if initial_state {
+            let last_serialized = *self.last_serialized.get_or_insert(self.value);
+            T::compress(writer, T::default(), last_serialized);
+            return;
+        }
+        T::compress(writer, self.last_serialized.unwrap_or_default(), self.value);
+        // save serialized as 'last' for next delta compression
+        self.last_serialized = Some(self.value);
+    }
+
+    // 返回读取后的值, 同时更新 value
+    pub fn deserialize(&mut self, reader: &mut NetworkReader, initial_state: bool) -> T {
+        let last = if initial_state {
+            T::default()
+        } else {
+            self.last_deserialized
+        };
+        self.last_deserialized = T::decompress(reader, last);
+        self.value = self.last_deserialized;
+        self.value
+    }
+
+    // 在组件的 add_observer / remove_observer 中调用, observers 是变化后的观察者数量
+    pub fn on_observers_changed(&mut self, observers: usize) {
+        if observers == 0 {
+            self.last_serialized = None;
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.last_serialized = None;
+        self.last_deserialized = T::default();
+    }
+}
+
+// 位置等浮点向量按 precision 量化后同步
+impl DeltaVar<Vector3<i64>> {
+    pub fn from_vector3(value: Vector3<f32>, precision: f32) -> Self {
+        Self::new(Compress::vector3float_to_vector3long(value, precision).1)
+    }
+
+    pub fn vector3(&self, precision: f32) -> Vector3<f32> {
+        Compress::vector3long_to_vector3float(self.value, precision)
+    }
+
+    pub fn set_vector3(&mut self, value: Vector3<f32>, precision: f32) -> bool {
+        self.set(Compress::vector3float_to_vector3long(value, precision).1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn serialize(var: &mut DeltaVar<Vector3<i64>>, initial_state: bool) -> Vec<u8> {
+        let mut writer = NetworkWriter::new();
+        var.serialize(&mut writer, initial_state);
+        writer.to_bytes()
+    }
+
+    #[test]
+    fn test_delta_var() {
+        let mut server = DeltaVar::from_vector3(Vector3::new(1.0, 2.0, 3.0), 0.01);
+        let mut client = DeltaVar::<Vector3<i64>>::default();
+        let mut late_client = DeltaVar::<Vector3<i64>>::default();
+        let initial = serialize(&mut server, true);
+        client.deserialize(&mut NetworkReader::new_with_array_segment(&initial), true);
+        assert_eq!(client.value(), Vector3::new(100, 200, 300));
+
+        // delta 只写入变化量
+        assert!(server.set_vector3(Vector3::new(1.5, 2.0, 3.0), 0.01));
+        assert!(!server.set_vector3(Vector3::new(1.5, 2.0, 3.0), 0.01));
+        assert!(server.changed());
+        let delta = serialize(&mut server, false);
+        assert_eq!(delta.len(), 3);
+        assert!(!server.changed());
+        client.deserialize(&mut NetworkReader::new_with_array_segment(&delta), false);
+        assert_eq!(client.value(), server.value());
+
+        // 新观察者收到上一次序列化的值, 与其他观察者使用同一个 delta 基准
+        server.set_vector3(Vector3::new(5.0, 2.0, 3.0), 0.01);
+        let initial = serialize(&mut server, true);
+        late_client.deserialize(&mut NetworkReader::new_with_array_segment(&initial), true);
+        assert_eq!(late_client.value(), Vector3::new(150, 200, 300));
+        let delta = serialize(&mut server, false);
+        for var in [&mut client, &mut late_client] {
+            var.deserialize(&mut NetworkReader::new_with_array_segment(&delta), false);
+            assert_eq!(var.value(), Vector3::new(500, 200, 300));
+        }
+
+        // 所有观察者离开后, 下一个观察者直接收到当前值
+        server.set_vector3(Vector3::new(6.0, 2.0, 3.0), 0.01);
+        server.on_observers_changed(0);
+        let initial = serialize(&mut server, true);
+        client.reset();
+        client.deserialize(&mut NetworkReader::new_with_array_segment(&initial), true);
+        assert_eq!(client.value(), Vector3::new(600, 200, 300));
+        assert!((client.vector3(0.01) - Vector3::new(6.0, 2.0, 3.0)).norm() < 0.001);
+        assert!(!server.changed());
+    }
+}
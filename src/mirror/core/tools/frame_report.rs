@@ -0,0 +1,296 @@
+use crate::log_error;
+use crate::mirror::core::network_context::ContextLocal;
+use atomic::Atomic;
+use lazy_static::lazy_static;
+use std::collections::VecDeque;
+use std::fmt::{Display, Formatter};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::{Once, RwLock};
+use std::time::Duration;
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum FramePhase {
+    // transport 接收数据和消息处理
+    EarlyUpdate,
+    // 不包括 broadcast
+    LateUpdate,
+    Broadcast,
+    // broadcast 中把批次交给 transport 的部分, 已经包含在 Broadcast 中
+    Flush,
+}
+
+// 一个 tick 的关键指标, 时间单位为秒
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FrameReport {
+    pub tick: u32,
+    pub local_time: f64,
+    pub early_update: f64,
+    pub late_update: f64,
+    pub broadcast: f64,
+    pub flush: f64,
+    pub messages_processed: u32,
+    pub bytes_received: u64,
+    pub bytes_sent: u64,
+    // DashMap try_get_mut 返回 Locked 的次数
+    pub lock_contention: u32,
+    // 快照缓冲区溢出的次数
+    pub snapshot_overflows: u32,
+}
+
+impl FrameReport {
+    pub fn total(&self) -> f64 {
+        self.early_update + self.late_update + self.broadcast
+    }
+}
+
+impl Display for FrameReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "tick {} @{:.3}s total {:.3}ms (early {:.3}ms, late {:.3}ms, broadcast {:.3}ms, flush {:.3}ms) messages {} received {}B sent {}B locked {} overflows {}",
+            self.tick,
+            self.local_time,
+            self.total() * 1000.0,
+            self.early_update * 1000.0,
+            self.late_update * 1000.0,
+            self.broadcast * 1000.0,
+            self.flush * 1000.0,
+            self.messages_processed,
+            self.bytes_received,
+            self.bytes_sent,
+            self.lock_contention,
+            self.snapshot_overflows
+        )
+    }
+}
+
+// 固定容量的环形缓冲区, 满了之后丢弃最旧的
+pub struct FrameReportBuffer {
+    reports: VecDeque<FrameReport>,
+    capacity: usize,
+}
+
+impl FrameReportBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            reports: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, report: FrameReport) {
+        if self.capacity == 0 {
+            return;
+        }
+        while self.reports.len() >= self.capacity {
+            self.reports.pop_front();
+        }
+        self.reports.push_back(report);
+    }
+
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.reports.len() > capacity {
+            self.reports.pop_front();
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.reports.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.reports.is_empty()
+    }
+
+    pub fn to_vec(&self) -> Vec<FrameReport> {
+        self.reports.iter().copied().collect()
+    }
+
+    // 最慢的 tick 排在报告开头, 之后按时间顺序列出所有 tick
+    pub fn dump(&self) -> String {
+        let mut dump = format!("FrameReports: last {} ticks", self.reports.len());
+        if let Some(slowest) = self
+            .reports
+            .iter()
+            .max_by(|a, b| a.total().total_cmp(&b.total()))
+        {
+            dump.push_str(&format!("\n  slowest: {}", slowest));
+        }
+        for report in self.reports.iter() {
+            dump.push_str(&format!("\n  {}", report));
+        }
+        dump
+    }
+
+    pub fn clear(&mut self) {
+        self.reports.clear();
+    }
+}
+
+// FrameReports 静态变量
+lazy_static! {
+    static ref BUFFER: ContextLocal<RwLock<FrameReportBuffer>> =
+        ContextLocal::new(|| RwLock::new(FrameReportBuffer::new(300)));
+    static ref EARLY_UPDATE: ContextLocal<Atomic<f64>> = ContextLocal::new(|| Atomic::new(0.0));
+    static ref LATE_UPDATE: ContextLocal<Atomic<f64>> = ContextLocal::new(|| Atomic::new(0.0));
+    static ref BROADCAST: ContextLocal<Atomic<f64>> = ContextLocal::new(|| Atomic::new(0.0));
+    static ref FLUSH: ContextLocal<Atomic<f64>> = ContextLocal::new(|| Atomic::new(0.0));
+    static ref MESSAGES_PROCESSED: ContextLocal<AtomicU32> =
+        ContextLocal::new(|| AtomicU32::new(0));
+    static ref BYTES_RECEIVED: ContextLocal<AtomicU64> = ContextLocal::new(|| AtomicU64::new(0));
+    static ref BYTES_SENT: ContextLocal<AtomicU64> = ContextLocal::new(|| AtomicU64::new(0));
+    static ref LOCK_CONTENTION: ContextLocal<AtomicU32> = ContextLocal::new(|| AtomicU32::new(0));
+    static ref SNAPSHOT_OVERFLOWS: ContextLocal<AtomicU32> =
+        ContextLocal::new(|| AtomicU32::new(0));
+    static ref PANIC_HOOK: Once = Once::new();
+}
+
+// 保存最近 N 个 tick 的指标, TimeSample 的平均值会掩盖偶发的卡顿
+// 出现卡顿或 panic 时输出整个缓冲区用于事后分析
+pub struct FrameReports;
+
+impl FrameReports {
+    pub fn capacity() -> usize {
+        match BUFFER.read() {
+            Ok(buffer) => buffer.capacity,
+            Err(_) => 0,
+        }
+    }
+
+    pub fn set_capacity(capacity: usize) {
+        if let Ok(mut buffer) = BUFFER.write() {
+            buffer.set_capacity(capacity);
+        }
+    }
+
+    #[inline]
+    pub fn record_phase(phase: FramePhase, elapsed: Duration) {
+        let counter: &Atomic<f64> = match phase {
+            FramePhase::EarlyUpdate => &EARLY_UPDATE,
+            FramePhase::LateUpdate => &LATE_UPDATE,
+            FramePhase::Broadcast => &BROADCAST,
+            FramePhase::Flush => &FLUSH,
+        };
+        let _ = counter.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |value| {
+            Some(value + elapsed.as_secs_f64())
+        });
+    }
+
+    #[inline]
+    pub fn record_message() {
+        MESSAGES_PROCESSED.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn record_bytes_received(bytes: usize) {
+        BYTES_RECEIVED.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn record_bytes_sent(bytes: usize) {
+        BYTES_SENT.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn record_lock_contention() {
+        LOCK_CONTENTION.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn record_snapshot_overflow() {
+        SNAPSHOT_OVERFLOWS.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // 在 NetworkServer::network_late_update 末尾调用, 把本 tick 的计数归档
+    pub fn end_tick(tick: u32, local_time: f64) {
+        let report = FrameReport {
+            tick,
+            local_time,
+            early_update: EARLY_UPDATE.swap(0.0, Ordering::Relaxed),
+            late_update: LATE_UPDATE.swap(0.0, Ordering::Relaxed),
+            broadcast: BROADCAST.swap(0.0, Ordering::Relaxed),
+            flush: FLUSH.swap(0.0, Ordering::Relaxed),
+            messages_processed: MESSAGES_PROCESSED.swap(0, Ordering::Relaxed),
+            bytes_received: BYTES_RECEIVED.swap(0, Ordering::Relaxed),
+            bytes_sent: BYTES_SENT.swap(0, Ordering::Relaxed),
+            lock_contention: LOCK_CONTENTION.swap(0, Ordering::Relaxed),
+            snapshot_overflows: SNAPSHOT_OVERFLOWS.swap(0, Ordering::Relaxed),
+        };
+        if let Ok(mut buffer) = BUFFER.write() {
+            buffer.push(report);
+        }
+    }
+
+    // 按时间顺序, 最旧的在前
+    pub fn reports() -> Vec<FrameReport> {
+        match BUFFER.read() {
+            Ok(buffer) => buffer.to_vec(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    pub fn dump() -> String {
+        // panic 时缓冲区可能正被持有, 不能阻塞
+        match BUFFER.try_read() {
+            Ok(buffer) => buffer.dump(),
+            Err(_) => "FrameReports: buffer is locked".to_string(),
+        }
+    }
+
+    // panic 时先输出缓冲区, 再调用之前的 hook, 多次调用只安装一次
+    pub fn install_panic_hook() {
+        PANIC_HOOK.call_once(|| {
+            let previous = std::panic::take_hook();
+            std::panic::set_hook(Box::new(move |info| {
+                log_error!(Self::dump());
+                previous(info);
+            }));
+        });
+    }
+
+    pub fn reset() {
+        if let Ok(mut buffer) = BUFFER.write() {
+            buffer.clear();
+        }
+        EARLY_UPDATE.store(0.0, Ordering::Relaxed);
+        LATE_UPDATE.store(0.0, Ordering::Relaxed);
+        BROADCAST.store(0.0, Ordering::Relaxed);
+        FLUSH.store(0.0, Ordering::Relaxed);
+        MESSAGES_PROCESSED.store(0, Ordering::Relaxed);
+        BYTES_RECEIVED.store(0, Ordering::Relaxed);
+        BYTES_SENT.store(0, Ordering::Relaxed);
+        LOCK_CONTENTION.store(0, Ordering::Relaxed);
+        SNAPSHOT_OVERFLOWS.store(0, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_report_buffer() {
+        let mut buffer = FrameReportBuffer::new(3);
+        for tick in 1..=5 {
+            buffer.push(FrameReport {
+                tick,
+                early_update: tick as f64 * 0.001,
+                messages_processed: tick,
+                ..Default::default()
+            });
+        }
+        let ticks: Vec<u32> = buffer.to_vec().iter().map(|report| report.tick).collect();
+        assert_eq!(ticks, vec![3, 4, 5]);
+
+        let dump = buffer.dump();
+        assert!(dump.starts_with("FrameReports: last 3 ticks\n  slowest: tick 5 "));
+        assert_eq!(dump.lines().count(), 5);
+
+        buffer.set_capacity(1);
+        assert_eq!(buffer.to_vec()[0].tick, 5);
+        buffer.set_capacity(0);
+        buffer.push(FrameReport::default());
+        assert!(buffer.is_empty());
+    }
+}
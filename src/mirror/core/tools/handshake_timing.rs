@@ -0,0 +1,434 @@
+use crate::log_warn;
+use crate::mirror::core::network_context::ContextLocal;
+use crate::mirror::core::network_time::NetworkTime;
+use atomic::Atomic;
+use dashmap::DashMap;
+use lazy_static::lazy_static;
+use std::fmt::{Display, Formatter};
+use std::sync::atomic::Ordering;
+use std::sync::RwLock;
+
+// 连接从建立到玩家生成经过的阶段, 按顺序
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HandshakeStage {
+    Connected = 0,
+    Authenticated = 1,
+    Ready = 2,
+    Spawned = 3,
+}
+
+impl HandshakeStage {
+    pub const ALL: [HandshakeStage; 4] = [
+        HandshakeStage::Connected,
+        HandshakeStage::Authenticated,
+        HandshakeStage::Ready,
+        HandshakeStage::Spawned,
+    ];
+
+    pub fn from(value: u8) -> Self {
+        match value {
+            0 => HandshakeStage::Connected,
+            1 => HandshakeStage::Authenticated,
+            2 => HandshakeStage::Ready,
+            _ => HandshakeStage::Spawned,
+        }
+    }
+
+    pub fn to_u8(&self) -> u8 {
+        *self as u8
+    }
+
+    fn next(&self) -> Option<HandshakeStage> {
+        match self {
+            HandshakeStage::Spawned => None,
+            stage => Some(Self::from(stage.to_u8() + 1)),
+        }
+    }
+}
+
+impl Display for HandshakeStage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            HandshakeStage::Connected => "connected",
+            HandshakeStage::Authenticated => "authenticated",
+            HandshakeStage::Ready => "ready",
+            HandshakeStage::Spawned => "spawned",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// 耗时分布, 单位毫秒
+#[derive(Debug, Clone, PartialEq)]
+pub struct HandshakeHistogram {
+    // 每个桶的计数, 按 BUCKETS 的顺序, 最后一个桶没有上限
+    pub counts: [u64; 9],
+    pub total: u64,
+    pub sum: f64,
+    pub max: f64,
+}
+
+impl Default for HandshakeHistogram {
+    fn default() -> Self {
+        Self {
+            counts: [0; 9],
+            total: 0,
+            sum: 0.0,
+            max: 0.0,
+        }
+    }
+}
+
+impl HandshakeHistogram {
+    // 桶的上限, 单位毫秒
+    pub const BUCKETS: [f64; 8] = [50.0, 100.0, 250.0, 500.0, 1000.0, 2500.0, 5000.0, 10000.0];
+
+    fn add(&mut self, milliseconds: f64) {
+        let bucket = Self::BUCKETS
+            .iter()
+            .position(|bound| milliseconds <= *bound)
+            .unwrap_or(Self::BUCKETS.len());
+        self.counts[bucket] += 1;
+        self.total += 1;
+        self.sum += milliseconds;
+        self.max = self.max.max(milliseconds);
+    }
+
+    pub fn mean(&self) -> f64 {
+        if self.total == 0 {
+            return 0.0;
+        }
+        self.sum / self.total as f64
+    }
+
+    // 按桶的上限估计, 落在最后一个桶时返回 max
+    pub fn percentile(&self, percentile: f64) -> f64 {
+        if self.total == 0 {
+            return 0.0;
+        }
+        let rank = ((percentile / 100.0) * self.total as f64).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (i, count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return Self::BUCKETS
+                    .get(i)
+                    .map_or(self.max, |bound| bound.min(self.max));
+            }
+        }
+        self.max
+    }
+}
+
+impl Display for HandshakeHistogram {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "n {} mean {:.0}ms p50 {:.0}ms p95 {:.0}ms max {:.0}ms",
+            self.total,
+            self.mean(),
+            self.percentile(50.0),
+            self.percentile(95.0),
+            self.max
+        )
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct HandshakeProgress {
+    // 按 HandshakeStage 的顺序, 单位秒 (NetworkTime::local_time)
+    times: [Option<f64>; 4],
+    // 已经告警过的阶段
+    warned: [bool; 4],
+}
+
+impl HandshakeProgress {
+    fn current(&self) -> Option<HandshakeStage> {
+        HandshakeStage::ALL
+            .iter()
+            .rev()
+            .find(|stage| self.times[stage.to_u8() as usize].is_some())
+            .copied()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct HandshakeStats {
+    // 进入每个阶段所用的时间 (从上一个阶段开始), Connected 的位置记录 connect -> spawned 的总时间
+    histograms: [HandshakeHistogram; 4],
+    // 在每个阶段断开连接的数量
+    abandoned: [u64; 4],
+}
+
+// HandshakeTiming 静态变量
+lazy_static! {
+    // conn_id -> 各阶段的时间, 玩家生成或断开连接后移除
+    static ref PROGRESS: ContextLocal<DashMap<u64, HandshakeProgress>> =
+        ContextLocal::new(DashMap::new);
+    static ref STATS: ContextLocal<RwLock<HandshakeStats>> =
+        ContextLocal::new(|| RwLock::new(HandshakeStats::default()));
+    // 进入 Authenticated / Ready / Spawned 的超时, 单位秒, 按阶段的顺序, Connected 不使用
+    static ref THRESHOLDS: ContextLocal<[Atomic<f64>; 4]> = ContextLocal::new(|| {
+        [
+            Atomic::new(0.0),
+            Atomic::new(5.0),
+            Atomic::new(10.0),
+            Atomic::new(10.0),
+        ]
+    });
+    static ref LAST_CHECK_TIME: ContextLocal<Atomic<f64>> = ContextLocal::new(|| Atomic::new(0.0));
+}
+
+// 记录每个连接 transport 连接 -> 认证完成 -> ready -> 玩家生成 的时间, 统计每个阶段的耗时分布
+// 超过阈值 (完成得慢或者一直没有完成) 时输出警告, 用于排查 "卡在加载中"
+pub struct HandshakeTiming;
+
+impl HandshakeTiming {
+    // 每秒检查一次卡住的连接
+    const CHECK_INTERVAL: f64 = 1.0;
+
+    pub fn threshold(stage: HandshakeStage) -> f64 {
+        THRESHOLDS[stage.to_u8() as usize].load(Ordering::Relaxed)
+    }
+
+    // 从上一个阶段到 stage 的超时, 单位秒, 0 表示不告警
+    pub fn set_threshold(stage: HandshakeStage, value: f64) {
+        THRESHOLDS[stage.to_u8() as usize].store(value, Ordering::Relaxed);
+    }
+
+    pub fn record(conn_id: u64, stage: HandshakeStage) {
+        Self::record_at(conn_id, stage, NetworkTime::local_time());
+    }
+
+    // 每个阶段只记录第一次, 跳过的阶段 (例如没有 ready 直接生成) 按 time 补齐
+    pub fn record_at(conn_id: u64, stage: HandshakeStage, time: f64) {
+        let index = stage.to_u8() as usize;
+        let mut progress = match stage {
+            HandshakeStage::Connected => PROGRESS.entry(conn_id).or_default(),
+            _ => match PROGRESS.get_mut(&conn_id) {
+                Some(progress) => progress,
+                // 玩家已经生成过, 或者连接没有记录 (例如主机的本地连接)
+                None => return,
+            },
+        };
+        if progress.times[index].is_some() {
+            return;
+        }
+        let mut durations = Vec::new();
+        for i in 1..=index {
+            if progress.times[i].is_some() {
+                continue;
+            }
+            progress.times[i] = Some(time);
+            if let Some(previous) = progress.times[i - 1] {
+                durations.push((HandshakeStage::from(i as u8), time - previous));
+            }
+        }
+        progress.times[index] = Some(time);
+        let total = match (stage, progress.times[0]) {
+            (HandshakeStage::Spawned, Some(connected)) => Some(time - connected),
+            _ => None,
+        };
+        drop(progress);
+        if stage == HandshakeStage::Spawned {
+            PROGRESS.remove(&conn_id);
+        }
+
+        for (stage, seconds) in durations.iter() {
+            let threshold = Self::threshold(*stage);
+            if threshold > 0.0 && *seconds > threshold {
+                log_warn!(
+                    "HandshakeTiming: connection {} took {:.1}s to become {} (threshold {:.1}s)",
+                    conn_id,
+                    seconds,
+                    stage,
+                    threshold
+                );
+            }
+        }
+        if let Ok(mut stats) = STATS.write() {
+            for (stage, seconds) in durations.iter() {
+                stats.histograms[stage.to_u8() as usize].add(seconds * 1000.0);
+            }
+            if let Some(total) = total {
+                stats.histograms[HandshakeStage::Connected.to_u8() as usize].add(total * 1000.0);
+            }
+        }
+    }
+
+    // 连接当前到达的阶段, 玩家已经生成或者没有记录时返回 None
+    pub fn stage(conn_id: u64) -> Option<HandshakeStage> {
+        PROGRESS
+            .get(&conn_id)
+            .and_then(|progress| progress.current())
+    }
+
+    pub fn pending() -> usize {
+        PROGRESS.len()
+    }
+
+    // 进入 stage 的耗时分布, Connected 为 connect -> spawned 的总时间
+    pub fn histogram(stage: HandshakeStage) -> HandshakeHistogram {
+        STATS
+            .read()
+            .map(|stats| stats.histograms[stage.to_u8() as usize].clone())
+            .unwrap_or_default()
+    }
+
+    // 停在 stage 时断开连接的数量
+    pub fn abandoned(stage: HandshakeStage) -> u64 {
+        STATS
+            .read()
+            .map(|stats| stats.abandoned[stage.to_u8() as usize])
+            .unwrap_or(0)
+    }
+
+    // 没有记录时返回 None
+    pub fn summary() -> Option<String> {
+        let stats = STATS.read().ok()?;
+        if stats
+            .histograms
+            .iter()
+            .all(|histogram| histogram.total == 0)
+            && stats.abandoned.iter().all(|abandoned| *abandoned == 0)
+        {
+            return None;
+        }
+        let mut summary = format!("HandshakeTiming: {} pending", PROGRESS.len());
+        for stage in HandshakeStage::ALL.iter().skip(1) {
+            summary.push_str(&format!(
+                "\n  -> {}: {}",
+                stage,
+                stats.histograms[stage.to_u8() as usize]
+            ));
+        }
+        summary.push_str(&format!(
+            "\n  total: {}",
+            stats.histograms[HandshakeStage::Connected.to_u8() as usize]
+        ));
+        summary.push_str(&format!(
+            "\n  abandoned: connected {} authenticated {} ready {}",
+            stats.abandoned[0], stats.abandoned[1], stats.abandoned[2]
+        ));
+        Some(summary)
+    }
+
+    // 在 NetworkServer::network_late_update 中调用, 对还没有进入下一阶段的连接告警一次
+    pub fn update() {
+        let local_time = NetworkTime::local_time();
+        if local_time - LAST_CHECK_TIME.load(Ordering::Relaxed) < Self::CHECK_INTERVAL {
+            return;
+        }
+        LAST_CHECK_TIME.store(local_time, Ordering::Relaxed);
+        Self::check_stalled_at(local_time);
+    }
+
+    // 返回本次告警的连接数
+    pub fn check_stalled_at(time: f64) -> usize {
+        let mut warned = 0;
+        for mut progress in PROGRESS.iter_mut() {
+            let conn_id = *progress.key();
+            let current = match progress.current() {
+                Some(current) => current,
+                None => continue,
+            };
+            let next = match current.next() {
+                Some(next) => next,
+                None => continue,
+            };
+            let threshold = Self::threshold(next);
+            let since = match progress.times[current.to_u8() as usize] {
+                Some(since) => since,
+                None => continue,
+            };
+            if threshold <= 0.0 || progress.warned[next.to_u8() as usize] {
+                continue;
+            }
+            if time - since > threshold {
+                progress.warned[next.to_u8() as usize] = true;
+                warned += 1;
+                log_warn!(
+                    "HandshakeTiming: connection {} stuck at {} for {:.1}s, not {} yet",
+                    conn_id,
+                    current,
+                    time - since,
+                    next
+                );
+            }
+        }
+        warned
+    }
+
+    // 在 NetworkServer::on_transport_disconnected 中调用
+    pub(crate) fn on_disconnected(conn_id: u64) {
+        if let Some((_, progress)) = PROGRESS.remove(&conn_id) {
+            if let (Some(stage), Ok(mut stats)) = (progress.current(), STATS.write()) {
+                stats.abandoned[stage.to_u8() as usize] += 1;
+            }
+        }
+    }
+
+    // 阈值保留, 记录和统计清空
+    pub fn reset() {
+        PROGRESS.clear();
+        if let Ok(mut stats) = STATS.write() {
+            *stats = HandshakeStats::default();
+        }
+        LAST_CHECK_TIME.store(0.0, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::transports::memory::memory_transport::test_util::with_isolated_context;
+
+    #[test]
+    fn test_handshake_timing() {
+        with_isolated_context(|| {
+            assert!(HandshakeTiming::summary().is_none());
+            // 没有 Connected 的连接不记录
+            HandshakeTiming::record_at(9, HandshakeStage::Ready, 1.0);
+            assert_eq!(HandshakeTiming::pending(), 0);
+
+            HandshakeTiming::record_at(1, HandshakeStage::Connected, 10.0);
+            HandshakeTiming::record_at(1, HandshakeStage::Authenticated, 10.2);
+            assert_eq!(
+                HandshakeTiming::stage(1),
+                Some(HandshakeStage::Authenticated)
+            );
+            // 跳过 Ready 时按生成的时间补齐
+            HandshakeTiming::record_at(1, HandshakeStage::Spawned, 11.0);
+            assert_eq!(HandshakeTiming::stage(1), None);
+            let authenticated = HandshakeTiming::histogram(HandshakeStage::Authenticated);
+            assert_eq!(authenticated.total, 1);
+            assert!((authenticated.percentile(50.0) - 200.0).abs() < 1e-6);
+            let ready = HandshakeTiming::histogram(HandshakeStage::Ready);
+            assert_eq!(ready.total, 1);
+            assert!((ready.max - 800.0).abs() < 1e-6);
+            assert_eq!(
+                HandshakeTiming::histogram(HandshakeStage::Spawned).counts[0],
+                1
+            );
+            assert!(
+                (HandshakeTiming::histogram(HandshakeStage::Connected).sum - 1000.0).abs() < 1e-6
+            );
+
+            // 卡在认证阶段只告警一次
+            HandshakeTiming::record_at(2, HandshakeStage::Connected, 20.0);
+            assert_eq!(HandshakeTiming::check_stalled_at(24.0), 0);
+            assert_eq!(HandshakeTiming::check_stalled_at(26.0), 1);
+            assert_eq!(HandshakeTiming::check_stalled_at(27.0), 0);
+            HandshakeTiming::on_disconnected(2);
+            assert_eq!(HandshakeTiming::abandoned(HandshakeStage::Connected), 1);
+            assert_eq!(HandshakeTiming::pending(), 0);
+
+            let summary = HandshakeTiming::summary().unwrap();
+            assert!(summary.starts_with("HandshakeTiming: 0 pending\n  -> authenticated: n 1 "));
+            assert!(summary.ends_with("abandoned: connected 1 authenticated 0 ready 0"));
+
+            HandshakeTiming::reset();
+            assert!(HandshakeTiming::summary().is_none());
+        });
+    }
+}
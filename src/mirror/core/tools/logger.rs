@@ -1,54 +1,152 @@
+use lazy_static::lazy_static;
+use std::sync::atomic::{AtomicU8, Ordering};
 pub use tklog;
+
+lazy_static! {
+    static ref LEVEL: AtomicU8 = AtomicU8::new(LogLevel::Trace as u8);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Trace = 0,
+    Debug = 1,
+    Info = 2,
+    Warn = 3,
+    Error = 4,
+    Fatal = 5,
+    Off = 6,
+}
+
+impl LogLevel {
+    fn from_u8(value: u8) -> LogLevel {
+        match value {
+            0 => LogLevel::Trace,
+            1 => LogLevel::Debug,
+            2 => LogLevel::Info,
+            3 => LogLevel::Warn,
+            4 => LogLevel::Error,
+            5 => LogLevel::Fatal,
+            _ => LogLevel::Off,
+        }
+    }
+}
+
+pub struct Logger;
+
+impl Logger {
+    // 低于该等级的日志不格式化, 默认全部输出
+    pub fn set_level(level: LogLevel) {
+        LEVEL.store(level as u8, Ordering::Relaxed);
+    }
+
+    pub fn level() -> LogLevel {
+        LogLevel::from_u8(LEVEL.load(Ordering::Relaxed))
+    }
+
+    pub fn enabled(level: LogLevel) -> bool {
+        level != LogLevel::Off && level >= Self::level()
+    }
+}
+
+// log_*!("... {}", x) 只在等级开启时格式化 (和计入 AllocSite::LogFormat), 也可以直接传入 String / &str
 #[macro_export]
 macro_rules! log_info {
-    ($($arg:tt)*) => {
-        {
-            $crate::mirror::core::tools::logger::tklog::info!($($arg)*);
+    ($fmt:literal $(, $arg:expr)* $(,)?) => {
+        $crate::log_info!(::std::format!($fmt $(, $arg)*))
+    };
+    ($msg:expr) => {
+        if $crate::mirror::core::tools::logger::Logger::enabled(
+            $crate::mirror::core::tools::logger::LogLevel::Info,
+        ) {
+            $crate::mirror::core::tools::alloc_audit::AllocAudit::record(
+                $crate::mirror::core::tools::alloc_audit::AllocSite::LogFormat,
+            );
+            $crate::mirror::core::tools::logger::tklog::info!($msg);
         }
     };
 }
 
 #[macro_export]
 macro_rules! log_error {
-    ($($arg:tt)*) => {
-        {
-            $crate::mirror::core::tools::logger::tklog::error!($($arg)*);
+    ($fmt:literal $(, $arg:expr)* $(,)?) => {
+        $crate::log_error!(::std::format!($fmt $(, $arg)*))
+    };
+    ($msg:expr) => {
+        if $crate::mirror::core::tools::logger::Logger::enabled(
+            $crate::mirror::core::tools::logger::LogLevel::Error,
+        ) {
+            $crate::mirror::core::tools::alloc_audit::AllocAudit::record(
+                $crate::mirror::core::tools::alloc_audit::AllocSite::LogFormat,
+            );
+            $crate::mirror::core::tools::logger::tklog::error!($msg);
         }
     };
 }
 
 #[macro_export]
 macro_rules! log_warn {
-    ($($arg:tt)*) => {
-        {
-            $crate::mirror::core::tools::logger::tklog::warn!($($arg)*);
+    ($fmt:literal $(, $arg:expr)* $(,)?) => {
+        $crate::log_warn!(::std::format!($fmt $(, $arg)*))
+    };
+    ($msg:expr) => {
+        if $crate::mirror::core::tools::logger::Logger::enabled(
+            $crate::mirror::core::tools::logger::LogLevel::Warn,
+        ) {
+            $crate::mirror::core::tools::alloc_audit::AllocAudit::record(
+                $crate::mirror::core::tools::alloc_audit::AllocSite::LogFormat,
+            );
+            $crate::mirror::core::tools::logger::tklog::warn!($msg);
         }
     };
 }
 
 #[macro_export]
 macro_rules! log_debug {
-    ($($arg:tt)*) => {
-        {
-            $crate::mirror::core::tools::logger::tklog::debug!($($arg)*);
+    ($fmt:literal $(, $arg:expr)* $(,)?) => {
+        $crate::log_debug!(::std::format!($fmt $(, $arg)*))
+    };
+    ($msg:expr) => {
+        if $crate::mirror::core::tools::logger::Logger::enabled(
+            $crate::mirror::core::tools::logger::LogLevel::Debug,
+        ) {
+            $crate::mirror::core::tools::alloc_audit::AllocAudit::record(
+                $crate::mirror::core::tools::alloc_audit::AllocSite::LogFormat,
+            );
+            $crate::mirror::core::tools::logger::tklog::debug!($msg);
         }
     };
 }
 
 #[macro_export]
 macro_rules! log_trace {
-    ($($arg:tt)*) => {
-        {
-            $crate::mirror::core::tools::logger::tklog::trace!($($arg)*);
+    ($fmt:literal $(, $arg:expr)* $(,)?) => {
+        $crate::log_trace!(::std::format!($fmt $(, $arg)*))
+    };
+    ($msg:expr) => {
+        if $crate::mirror::core::tools::logger::Logger::enabled(
+            $crate::mirror::core::tools::logger::LogLevel::Trace,
+        ) {
+            $crate::mirror::core::tools::alloc_audit::AllocAudit::record(
+                $crate::mirror::core::tools::alloc_audit::AllocSite::LogFormat,
+            );
+            $crate::mirror::core::tools::logger::tklog::trace!($msg);
         }
     };
 }
 
 #[macro_export]
 macro_rules! log_fatal {
-    ($($arg:tt)*) => {
-        {
-            $crate::mirror::core::tools::logger::tklog::fatal!($($arg)*);
+    ($fmt:literal $(, $arg:expr)* $(,)?) => {
+        $crate::log_fatal!(::std::format!($fmt $(, $arg)*))
+    };
+    ($msg:expr) => {
+        if $crate::mirror::core::tools::logger::Logger::enabled(
+            $crate::mirror::core::tools::logger::LogLevel::Fatal,
+        ) {
+            $crate::mirror::core::tools::alloc_audit::AllocAudit::record(
+                $crate::mirror::core::tools::alloc_audit::AllocSite::LogFormat,
+            );
+            $crate::mirror::core::tools::logger::tklog::fatal!($msg);
         }
     };
 }
@@ -56,6 +154,8 @@ macro_rules! log_fatal {
 // test
 #[cfg(test)]
 mod tests {
+    use super::*;
+
     #[test]
     fn test_logger() {
         log_info!("This is an info message");
@@ -65,4 +165,23 @@ mod tests {
         log_trace!("This is a trace message");
         log_fatal!("This is a fatal message");
     }
+
+    #[test]
+    fn test_logger_level() {
+        let mut formatted = 0;
+        let mut value = || {
+            formatted += 1;
+            7
+        };
+        Logger::set_level(LogLevel::Error);
+        log_warn!("skipped {}", value());
+        log_info!("skipped {}", value());
+        log_error!("kept {}", value());
+        Logger::set_level(LogLevel::Off);
+        log_fatal!("skipped {}", value());
+        Logger::set_level(LogLevel::Trace);
+        assert_eq!(formatted, 1);
+        assert!(Logger::enabled(LogLevel::Trace));
+        assert!(!Logger::enabled(LogLevel::Off));
+    }
 }
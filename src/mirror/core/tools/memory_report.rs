@@ -0,0 +1,197 @@
+use crate::log_info;
+use crate::mirror::core::network_context::ContextLocal;
+use crate::mirror::core::network_identity::NetworkIdentity;
+use crate::mirror::core::network_reader_pool::NetworkReaderPool;
+use crate::mirror::core::network_server::{NetworkServerStatic, NETWORK_BEHAVIOURS};
+use crate::mirror::core::network_time::NetworkTime;
+use crate::mirror::core::network_writer_pool::NetworkWriterPool;
+use atomic::Atomic;
+use lazy_static::lazy_static;
+use std::fmt::{Display, Formatter};
+use std::sync::atomic::Ordering;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemorySubsystem {
+    SpawnedIdentities,
+    Behaviours,
+    SnapshotBuffers,
+    Batchers,
+    WriterPool,
+    ReaderPool,
+}
+
+impl Display for MemorySubsystem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            MemorySubsystem::SpawnedIdentities => "spawned identities",
+            MemorySubsystem::Behaviours => "behaviours",
+            MemorySubsystem::SnapshotBuffers => "snapshot buffers",
+            MemorySubsystem::Batchers => "batchers",
+            MemorySubsystem::WriterPool => "writer pool",
+            MemorySubsystem::ReaderPool => "reader pool",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// 一个子系统持有的对象数量和大致字节数
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemoryEntry {
+    pub subsystem: MemorySubsystem,
+    pub count: usize,
+    pub bytes: usize,
+}
+
+impl Display for MemoryEntry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} {}B", self.subsystem, self.count, self.bytes)
+    }
+}
+
+// MemoryReport 静态变量
+lazy_static! {
+    // 单位秒, 0 表示不输出日志
+    static ref LOG_INTERVAL: ContextLocal<Atomic<f64>> = ContextLocal::new(|| Atomic::new(0.0));
+    static ref LAST_LOG_TIME: ContextLocal<Atomic<f64>> = ContextLocal::new(|| Atomic::new(0.0));
+}
+
+// 统计主要容器持有的内存, 用于排查长时间运行的服务器的内存泄漏
+// 只计算结构体大小和缓冲区长度, 不包含分配器和 DashMap / BTreeMap 的额外开销
+pub struct MemoryReport;
+
+impl MemoryReport {
+    pub fn log_interval() -> f64 {
+        LOG_INTERVAL.load(Ordering::Relaxed)
+    }
+
+    pub fn set_log_interval(value: f64) {
+        LOG_INTERVAL.store(value, Ordering::Relaxed);
+    }
+
+    pub fn collect() -> Vec<MemoryEntry> {
+        let spawned = NetworkServerStatic::spawned_network_identities();
+        let identities = MemoryEntry {
+            subsystem: MemorySubsystem::SpawnedIdentities,
+            count: spawned.len(),
+            bytes: spawned
+                .iter()
+                .map(|identity| Self::identity_memory_usage(&identity))
+                .sum(),
+        };
+
+        let behaviours = MemoryEntry {
+            subsystem: MemorySubsystem::Behaviours,
+            count: NETWORK_BEHAVIOURS.len(),
+            bytes: NETWORK_BEHAVIOURS
+                .iter()
+                .map(|behaviour| size_of::<(u32, u8)>() + size_of_val(&**behaviour.value()))
+                .sum(),
+        };
+
+        let connections = NetworkServerStatic::network_connections();
+        let mut snapshots = MemoryEntry {
+            subsystem: MemorySubsystem::SnapshotBuffers,
+            count: 0,
+            bytes: 0,
+        };
+        let mut batchers = MemoryEntry {
+            subsystem: MemorySubsystem::Batchers,
+            count: connections.len(),
+            bytes: 0,
+        };
+        for connection in connections.iter() {
+            snapshots.count += connection.snapshots.len();
+            snapshots.bytes += connection.snapshots_memory_usage();
+            batchers.bytes += connection.batcher_memory_usage();
+        }
+
+        let writer_pool = MemoryEntry {
+            subsystem: MemorySubsystem::WriterPool,
+            count: NetworkWriterPool::count(),
+            bytes: NetworkWriterPool::memory_usage(),
+        };
+        let reader_pool = MemoryEntry {
+            subsystem: MemorySubsystem::ReaderPool,
+            count: NetworkReaderPool::count(),
+            bytes: NetworkReaderPool::memory_usage(),
+        };
+
+        vec![
+            identities,
+            behaviours,
+            snapshots,
+            batchers,
+            writer_pool,
+            reader_pool,
+        ]
+    }
+
+    // NetworkIdentity 本身 + observers + 上一次序列化的缓冲区
+    fn identity_memory_usage(identity: &NetworkIdentity) -> usize {
+        size_of::<NetworkIdentity>()
+            + identity.observers().capacity() * size_of::<u64>()
+            + identity.last_serialization.owner_writer.capacity()
+            + identity.last_serialization.observers_writer.capacity()
+            + identity.scene_ids.len() * size_of::<(u64, u32)>()
+    }
+
+    pub fn total_bytes() -> usize {
+        Self::collect().iter().map(|entry| entry.bytes).sum()
+    }
+
+    pub fn summary() -> String {
+        let entries = Self::collect();
+        let total: usize = entries.iter().map(|entry| entry.bytes).sum();
+        let mut summary = format!("MemoryReport: total {}B", total);
+        for entry in entries.iter() {
+            summary.push_str(&format!("\n  {}", entry));
+        }
+        summary
+    }
+
+    // 在 NetworkServer::network_late_update 中调用, 按 log_interval 输出报告
+    pub fn update() {
+        let interval = Self::log_interval();
+        if interval <= 0.0 {
+            return;
+        }
+        let local_time = NetworkTime::local_time();
+        if local_time - LAST_LOG_TIME.load(Ordering::Relaxed) < interval {
+            return;
+        }
+        LAST_LOG_TIME.store(local_time, Ordering::Relaxed);
+        log_info!(Self::summary());
+    }
+
+    pub fn reset() {
+        LAST_LOG_TIME.store(0.0, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::transports::memory::memory_transport::test_util::with_isolated_context;
+
+    #[test]
+    fn test_memory_report() {
+        with_isolated_context(|| {
+            let entries = MemoryReport::collect();
+            assert_eq!(entries.len(), 6);
+            assert_eq!(entries[0].subsystem, MemorySubsystem::SpawnedIdentities);
+            assert_eq!((entries[0].count, entries[0].bytes), (0, 0));
+            assert_eq!((entries[2].count, entries[2].bytes), (0, 0));
+
+            let mut identity = NetworkIdentity::new_with_asset_id(1);
+            identity.set_net_id(1);
+            NetworkServerStatic::add_spawned_network_identity(identity);
+
+            let entries = MemoryReport::collect();
+            assert_eq!(entries[0].count, 1);
+            assert!(entries[0].bytes >= size_of::<NetworkIdentity>());
+            assert!(MemoryReport::total_bytes() >= entries[0].bytes);
+            assert!(MemoryReport::summary().starts_with("MemoryReport: total "));
+            assert!(MemoryReport::summary().contains("\n  spawned identities 1 "));
+        });
+    }
+}
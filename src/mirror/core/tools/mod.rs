@@ -5,4 +5,13 @@ pub mod pool;
 pub mod accurateinterval;
 pub mod delta_compression;
 pub mod utils;
-pub mod logger;
\ No newline at end of file
+pub mod logger;
+pub mod stable_hash_registry;
+pub mod alloc_audit;
+pub mod frame_report;
+pub mod bandwidth_report;
+pub mod memory_report;
+pub mod behaviour_profiler;
+pub mod traffic_log;
+pub mod serialization_check;
+pub mod handshake_timing;
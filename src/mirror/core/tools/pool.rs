@@ -36,4 +36,9 @@ impl<T> Pool<T> {
     pub fn count(&self) -> usize {
         self.objects_stack.len()
     }
+
+    // 池中对象占用的内存, size 返回单个对象的字节数
+    pub fn memory_usage<F: Fn(&T) -> usize>(&self, size: F) -> usize {
+        self.objects_stack.iter().map(size).sum()
+    }
 }
\ No newline at end of file
@@ -0,0 +1,338 @@
+use crate::log_error;
+use crate::mirror::components::network_animator::NetworkAnimator;
+use crate::mirror::components::network_common_behaviour::NetworkCommonBehaviour;
+use crate::mirror::components::network_rigidbody::network_rigidbody_unreliable::NetworkRigidbodyUnreliable;
+use crate::mirror::components::network_transform::network_transform_unreliable::NetworkTransformUnreliable;
+use crate::mirror::core::backend_data::{BackendDataStatic, NetworkBehaviourComponent};
+use crate::mirror::core::network_behaviour::{
+    NetworkBehaviourFactory, NetworkBehaviourTrait, SyncDirection,
+};
+use crate::mirror::core::network_context::ContextLocal;
+use crate::mirror::core::network_reader::NetworkReader;
+use crate::mirror::core::network_writer_pool::NetworkWriterPool;
+use crate::mirror::core::tools::utils::to_hex_string;
+use dashmap::DashMap;
+use lazy_static::lazy_static;
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+
+// 一次序列化和反序列化不对称的记录
+#[derive(Debug, Clone, PartialEq)]
+pub struct SerializationMismatch {
+    pub net_id: u32,
+    pub index: u8,
+    pub component: String,
+    pub initial_state: bool,
+    // serialize 写入的字节数, 包括安全字节
+    pub written: usize,
+    // deserialize 读取的字节数
+    pub read: usize,
+    pub reason: String,
+}
+
+impl Display for SerializationMismatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "SerializationCheck: netId {} [{}] {} {} wrote {}B read {}B: {}",
+            self.net_id,
+            self.index,
+            self.component,
+            if self.initial_state {
+                "initial"
+            } else {
+                "delta"
+            },
+            self.written,
+            self.read,
+            self.reason
+        )
+    }
+}
+
+// SerializationCheck 静态变量
+lazy_static! {
+    static ref ENABLED: ContextLocal<AtomicBool> = ContextLocal::new(|| AtomicBool::new(false));
+    // 默认只在 debug 构建中 panic, release 构建中只输出错误
+    static ref PANIC_ON_MISMATCH: ContextLocal<AtomicBool> =
+        ContextLocal::new(|| AtomicBool::new(cfg!(debug_assertions)));
+    // (asset_id, scene_id, 组件索引) -> 组件配置, 创建临时组件时使用
+    static ref COMPONENTS: ContextLocal<DashMap<(u32, u64, u8), NetworkBehaviourComponent>> =
+        ContextLocal::new(DashMap::new);
+    // 跳过检查的组件类型: 服务器只写不读的内置组件, 客户端的同步通过 Command 发送
+    static ref IGNORED: ContextLocal<RwLock<HashSet<String>>> = ContextLocal::new(|| {
+        RwLock::new(HashSet::from([
+            NetworkTransformUnreliable::COMPONENT_TAG.to_string(),
+            NetworkRigidbodyUnreliable::COMPONENT_TAG.to_string(),
+            NetworkAnimator::COMPONENT_TAG.to_string(),
+        ]))
+    });
+    static ref MISMATCHES: ContextLocal<RwLock<Vec<SerializationMismatch>>> =
+        ContextLocal::new(|| RwLock::new(Vec::new()));
+}
+
+// 严格模式: 服务器每次序列化组件后, 立即用 deserialize 读入一个新建的临时组件并比较
+// 写入和读取的字节数不一致、deserialize 失败、或者初始状态重新序列化的结果不同都算不对称
+// 用于开发期间发现 on_serialize / on_deserialize 不匹配, 而不是等客户端数据损坏
+// NetworkCommonBehaviour 在服务器上只保存同步变量的字节, 不反序列化, 与 ignore 的组件一样跳过检查
+// 开销很大, 只在开发和测试时开启
+pub struct SerializationCheck;
+
+impl SerializationCheck {
+    // 最多保留的记录数
+    pub const MAX_MISMATCHES: usize = 100;
+
+    pub fn enabled() -> bool {
+        ENABLED.load(Ordering::Relaxed)
+    }
+
+    pub fn set_enabled(value: bool) {
+        ENABLED.store(value, Ordering::Relaxed);
+    }
+
+    pub fn panic_on_mismatch() -> bool {
+        PANIC_ON_MISMATCH.load(Ordering::Relaxed)
+    }
+
+    pub fn set_panic_on_mismatch(value: bool) {
+        PANIC_ON_MISMATCH.store(value, Ordering::Relaxed);
+    }
+
+    pub fn ignore(component: &str) {
+        if let Ok(mut ignored) = IGNORED.write() {
+            ignored.insert(component.to_string());
+        }
+    }
+
+    pub fn unignore(component: &str) {
+        if let Ok(mut ignored) = IGNORED.write() {
+            ignored.remove(component);
+        }
+    }
+
+    pub fn is_ignored(component: &str) -> bool {
+        IGNORED
+            .read()
+            .is_ok_and(|ignored| ignored.contains(component))
+    }
+
+    // 在 NetworkIdentity::serialize_server_components 中调用, segment 是 serialize 的输出
+    pub fn check(
+        asset_id: u32,
+        scene_id: u64,
+        component: &mut dyn NetworkBehaviourTrait,
+        segment: &[u8],
+        initial_state: bool,
+    ) {
+        if !Self::enabled()
+            || component.as_any_mut().is::<NetworkCommonBehaviour>()
+            || Self::is_ignored(&component.sub_class())
+        {
+            return;
+        }
+        let config = match Self::component_config(asset_id, scene_id, component.index()) {
+            Some(config) => config,
+            None => return,
+        };
+        let scratch = NetworkBehaviourFactory::create_network_behaviour(
+            component.game_object().clone(),
+            &config,
+        );
+        if let Some(mut scratch) = scratch {
+            scratch.set_net_id(component.net_id());
+            if let Some(mismatch) = Self::round_trip(&mut *scratch, segment, initial_state) {
+                Self::report(SerializationMismatch {
+                    net_id: component.net_id(),
+                    index: component.index(),
+                    component: component.sub_class(),
+                    ..mismatch
+                });
+            }
+        }
+    }
+
+    // 把 segment 读入 scratch, 返回不对称的原因
+    pub fn round_trip(
+        scratch: &mut dyn NetworkBehaviourTrait,
+        segment: &[u8],
+        initial_state: bool,
+    ) -> Option<SerializationMismatch> {
+        // 临时组件不属于任何连接, 避免 ClientToServer 组件把数据当作客户端的同步处理
+        scratch.set_sync_direction(SyncDirection::ServerToClient);
+        let mut reader = NetworkReader::new_with_array_segment(segment);
+        let success = scratch.deserialize(&mut reader, initial_state);
+        let read = reader.get_position();
+        let (net_id, index, component) = (scratch.net_id(), scratch.index(), scratch.sub_class());
+        let mismatch = |reason: String| {
+            Some(SerializationMismatch {
+                net_id,
+                index,
+                component: component.clone(),
+                initial_state,
+                written: segment.len(),
+                read,
+                reason,
+            })
+        };
+        if !success {
+            return mismatch("deserialize failed".to_string());
+        }
+        if read != segment.len() {
+            return mismatch("size mismatch".to_string());
+        }
+        // delta 依赖双方之前的状态, 只检查读取的字节数
+        if !initial_state {
+            return None;
+        }
+        let mut reserialized = Vec::new();
+        NetworkWriterPool::get_return(|writer| {
+            scratch.serialize(writer, true);
+            reserialized = writer.to_bytes();
+        });
+        if reserialized != segment {
+            return mismatch(format!(
+                "reserialized {} != {}",
+                to_hex_string(&reserialized),
+                to_hex_string(segment)
+            ));
+        }
+        None
+    }
+
+    fn component_config(
+        asset_id: u32,
+        scene_id: u64,
+        index: u8,
+    ) -> Option<NetworkBehaviourComponent> {
+        let key = (asset_id, scene_id, index);
+        if let Some(config) = COMPONENTS.get(&key) {
+            return Some(config.clone());
+        }
+        let backend_data = BackendDataStatic::get_backend_data();
+        let components = if asset_id != 0 {
+            backend_data
+                .get_network_identity_data_network_behaviour_components_by_asset_id(asset_id)
+        } else {
+            backend_data
+                .get_network_identity_data_network_behaviour_components_by_scene_id(scene_id)
+        };
+        let config = components
+            .into_iter()
+            .find(|component| component.index == index)?;
+        COMPONENTS.insert(key, config.clone());
+        Some(config)
+    }
+
+    fn report(mismatch: SerializationMismatch) {
+        let message = mismatch.to_string();
+        if let Ok(mut mismatches) = MISMATCHES.write() {
+            if mismatches.len() >= Self::MAX_MISMATCHES {
+                mismatches.remove(0);
+            }
+            mismatches.push(mismatch);
+        }
+        if Self::panic_on_mismatch() {
+            panic!("{}", message);
+        }
+        log_error!(message);
+    }
+
+    // 最近的不对称记录, 从旧到新
+    pub fn mismatches() -> Vec<SerializationMismatch> {
+        MISMATCHES
+            .read()
+            .map(|mismatches| mismatches.clone())
+            .unwrap_or_default()
+    }
+
+    // 开关保留, 记录和组件配置缓存清空
+    pub fn reset() {
+        COMPONENTS.clear();
+        if let Ok(mut mismatches) = MISMATCHES.write() {
+            mismatches.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::components::network_transform::network_transform_reliable::NetworkTransformReliable;
+    use crate::mirror::core::backend_data::{
+        BackendDataBuilder, NetworkIdentityData, NetworkTransformBaseSetting,
+    };
+    use crate::mirror::core::network_behaviour::GameObject;
+    use crate::mirror::transports::memory::memory_transport::test_util::with_isolated_context;
+
+    fn serialize(component: &mut dyn NetworkBehaviourTrait) -> Vec<u8> {
+        let mut segment = Vec::new();
+        NetworkWriterPool::get_return(|writer| {
+            component.serialize(writer, true);
+            segment = writer.to_bytes();
+        });
+        segment
+    }
+
+    #[test]
+    fn test_serialization_check() {
+        with_isolated_context(|| {
+            NetworkBehaviourFactory::register_network_behaviour_factory();
+            let setting = NetworkTransformBaseSetting {
+                sync_position: true,
+                sync_rotation: true,
+                ..NetworkTransformBaseSetting::default()
+            };
+            let reliable = NetworkBehaviourComponent::new(NetworkTransformReliable::COMPONENT_TAG)
+                .with_transform_base_setting(setting);
+            let unreliable =
+                NetworkBehaviourComponent::new(NetworkTransformUnreliable::COMPONENT_TAG)
+                    .with_transform_base_setting(setting);
+            BackendDataBuilder::new()
+                .asset(
+                    7,
+                    "Player",
+                    NetworkIdentityData::new(0)
+                        .with_component(reliable)
+                        .with_component(unreliable),
+                )
+                .register()
+                .unwrap();
+            SerializationCheck::set_enabled(true);
+            SerializationCheck::set_panic_on_mismatch(false);
+
+            let components = BackendDataStatic::get_backend_data()
+                .get_network_identity_data_network_behaviour_components_by_asset_id(7);
+            let mut behaviours: Vec<Box<dyn NetworkBehaviourTrait>> = components
+                .iter()
+                .filter_map(|component| {
+                    NetworkBehaviourFactory::create_network_behaviour(
+                        GameObject::default(),
+                        component,
+                    )
+                })
+                .collect();
+            for behaviour in behaviours.iter_mut() {
+                let segment = serialize(&mut **behaviour);
+                SerializationCheck::check(7, 0, &mut **behaviour, &segment, true);
+            }
+            // NetworkTransformUnreliable 默认跳过
+            assert!(SerializationCheck::mismatches().is_empty());
+
+            // 服务器上的 NetworkTransformUnreliable 只写不读
+            SerializationCheck::unignore(NetworkTransformUnreliable::COMPONENT_TAG);
+            let segment = serialize(&mut *behaviours[1]);
+            SerializationCheck::check(7, 0, &mut *behaviours[1], &segment, true);
+            let mismatches = SerializationCheck::mismatches();
+            assert_eq!(mismatches.len(), 1);
+            assert_eq!(mismatches[0].index, 1);
+            assert_eq!(mismatches[0].written, segment.len());
+            assert_eq!(mismatches[0].reason, "deserialize failed");
+
+            SerializationCheck::reset();
+            assert!(SerializationCheck::mismatches().is_empty());
+            BackendDataStatic::clear_backend_data();
+        });
+    }
+}
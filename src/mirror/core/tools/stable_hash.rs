@@ -6,16 +6,7 @@ pub trait StableHash {
 
 impl StableHash for str {
     fn get_stable_hash_code(&self) -> i32 {
-        let mut hash: u32 = 0x811c9dc5;
-        let prime: u32 = 0x1000193;
-
-        for c in self.chars() {
-            let value = c as u8; // Note: this only works correctly for ASCII characters
-            hash ^= u32::from(value);
-            hash = hash.wrapping_mul(prime);
-        }
-
-        hash as i32
+        stable_hash_code(self)
     }
 
     fn get_stable_hash_code16(&self) -> u16 {
@@ -24,6 +15,138 @@ impl StableHash for str {
     }
 
     fn get_fn_stable_hash_code(&self) -> u16 {
-        (self.get_stable_hash_code() & 0xFFFF) as u16
+        fn_hash(self)
+    }
+}
+
+// const 版本, 与 C# 的 GetStableHashCode 一致
+pub const fn stable_hash_code(value: &str) -> i32 {
+    let bytes = value.as_bytes();
+    let mut hash: u32 = 0x811c9dc5;
+    let prime: u32 = 0x1000193;
+
+    let mut i = 0;
+    while i < bytes.len() {
+        // 按字符计算, 非 ASCII 字符只取低 8 位 (与 C# 的 (byte)c 相同)
+        let (value, len) = decode_utf8(bytes, i);
+        hash ^= value & 0xFF;
+        hash = hash.wrapping_mul(prime);
+        i += len;
+    }
+
+    hash as i32
+}
+
+// 远程调用的 hash, name 是 C# 的完整签名, 例如 "System.Void Mirror.NetworkTransformBase::CmdTeleport(UnityEngine.Vector3)"
+pub const fn fn_hash(name: &str) -> u16 {
+    (stable_hash_code(name) & 0xFFFF) as u16
+}
+
+const fn decode_utf8(bytes: &[u8], i: usize) -> (u32, usize) {
+    let first = bytes[i] as u32;
+    if first < 0x80 {
+        (first, 1)
+    } else if first < 0xE0 {
+        (((first & 0x1F) << 6) | (bytes[i + 1] as u32 & 0x3F), 2)
+    } else if first < 0xF0 {
+        (
+            ((first & 0x0F) << 12)
+                | ((bytes[i + 1] as u32 & 0x3F) << 6)
+                | (bytes[i + 2] as u32 & 0x3F),
+            3,
+        )
+    } else {
+        (
+            ((first & 0x07) << 18)
+                | ((bytes[i + 1] as u32 & 0x3F) << 12)
+                | ((bytes[i + 2] as u32 & 0x3F) << 6)
+                | (bytes[i + 3] as u32 & 0x3F),
+            4,
+        )
+    }
+}
+
+// 在编译时计算远程调用的 hash, 代替手写的数字
+// fn_hash!("System.Void Mirror.NetworkTransformUnreliable::RpcServerToClientSync(Mirror.SyncData)")
+#[macro_export]
+macro_rules! fn_hash {
+    ($name:expr) => {{
+        const HASH: u16 = $crate::mirror::core::tools::stable_hash::fn_hash($name);
+        HASH
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 旧版本中手写的 hash 与 Mirror C# weaver 生成的值相同
+    const BUILT_IN_HASHES: [(&str, i32); 8] = [
+        (
+            "System.Void Mirror.NetworkTransformUnreliable::RpcServerToClientSync(Mirror.SyncData)",
+            -1891602648,
+        ),
+        (
+            "System.Void Mirror.NetworkTransformUnreliable::RpcServerToClientSync(System.Nullable`1<UnityEngine.Vector3>,System.Nullable`1<UnityEngine.Quaternion>,System.Nullable`1<UnityEngine.Vector3>)",
+            1202296400,
+        ),
+        (
+            "System.Void Mirror.NetworkTransformBase::RpcTeleport(UnityEngine.Vector3)",
+            -1933368736,
+        ),
+        (
+            "System.Void Mirror.NetworkTransformBase::RpcTeleport(UnityEngine.Vector3,UnityEngine.Quaternion)",
+            -1675599861,
+        ),
+        (
+            "System.Void Mirror.NetworkAnimator::RpcOnAnimationClientMessage(System.Int32,System.Single,System.Int32,System.Single,System.Byte[])",
+            -392669502,
+        ),
+        (
+            "System.Void Mirror.NetworkAnimator::RpcOnAnimationParametersClientMessage(System.Byte[])",
+            -2095336766,
+        ),
+        (
+            "System.Void Mirror.NetworkAnimator::RpcOnAnimationTriggerClientMessage(System.Int32)",
+            1759094990,
+        ),
+        (
+            "System.Void Mirror.NetworkAnimator::RpcOnAnimationResetTriggerClientMessage(System.Int32)",
+            1545278305,
+        ),
+    ];
+
+    #[test]
+    fn test_built_in_fn_hashes() {
+        for (name, hash) in BUILT_IN_HASHES {
+            assert_eq!(stable_hash_code(name), hash, "{}", name);
+            assert_eq!(fn_hash(name), hash as u16, "{}", name);
+        }
+        const HASH: u16 = fn_hash(
+            "System.Void Mirror.NetworkTransformUnreliable::RpcServerToClientSync(Mirror.SyncData)",
+        );
+        assert_eq!(HASH, -1891602648i32 as u16);
+        assert_eq!(
+            crate::fn_hash!(
+                "System.Void Mirror.NetworkAnimator::RpcOnAnimationTriggerClientMessage(System.Int32)"
+            ),
+            1759094990i32 as u16
+        );
+    }
+
+    #[test]
+    fn test_const_hash_matches_chars() {
+        // 旧的实现按 char 计算, 非 ASCII 字符只取低 8 位
+        fn by_chars(value: &str) -> i32 {
+            let mut hash: u32 = 0x811c9dc5;
+            for c in value.chars() {
+                hash ^= u32::from(c as u8);
+                hash = hash.wrapping_mul(0x1000193);
+            }
+            hash as i32
+        }
+        for value in ["", "Mirror.ReadyMessage", "玩家.名字", "é€😀x"] {
+            assert_eq!(stable_hash_code(value), by_chars(value), "{}", value);
+        }
     }
 }
@@ -0,0 +1,184 @@
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+use lazy_static::lazy_static;
+use std::fmt::{Display, Formatter};
+
+// 消息id 与 远程调用id 是两个独立的 16 位空间
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+pub enum StableHashDomain {
+    Message,
+    RemoteCall,
+}
+
+// 注册时的用途, 同一个名字不能同时注册为 Command 与 ClientRpc
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum StableHashKind {
+    Message,
+    Command,
+    ClientRpc,
+}
+
+impl Display for StableHashKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StableHashKind::Message => write!(f, "Message"),
+            StableHashKind::Command => write!(f, "Command"),
+            StableHashKind::ClientRpc => write!(f, "ClientRpc"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StableHashEntry {
+    pub full_name: String,
+    pub kind: StableHashKind,
+}
+
+lazy_static! {
+    static ref STABLE_HASH_ENTRIES: DashMap<(StableHashDomain, u16), StableHashEntry> =
+        DashMap::new();
+}
+
+// 记录所有注册过的 FULL_NAME -> hash
+// 16 位的 hash 一旦冲突, 消息或 RPC 会被静默地路由到错误的处理函数, 所以在注册时直接失败
+pub struct StableHashRegistry;
+
+impl StableHashRegistry {
+    // 注册一个 hash, 同名同用途的重复注册会被忽略
+    pub fn register(domain: StableHashDomain, kind: StableHashKind, full_name: &str, hash: u16) {
+        if let Err(e) = Self::try_register(domain, kind, full_name, hash) {
+            panic!("{}", e);
+        }
+    }
+
+    pub fn try_register(
+        domain: StableHashDomain,
+        kind: StableHashKind,
+        full_name: &str,
+        hash: u16,
+    ) -> Result<(), String> {
+        match STABLE_HASH_ENTRIES.entry((domain, hash)) {
+            Entry::Occupied(entry) => {
+                let old = entry.get();
+                if old.full_name != full_name {
+                    return Err(format!(
+                        "StableHash collision for {:?} id {}: '{}' ({}) and '{}' ({}). Please rename one of them.",
+                        domain, hash, old.full_name, old.kind, full_name, kind
+                    ));
+                }
+                if old.kind != kind {
+                    return Err(format!(
+                        "StableHash duplicate for {:?} id {}: '{}' is registered as both {} and {}.",
+                        domain, hash, full_name, old.kind, kind
+                    ));
+                }
+                Ok(())
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(StableHashEntry {
+                    full_name: full_name.to_string(),
+                    kind,
+                });
+                Ok(())
+            }
+        }
+    }
+
+    pub fn get(domain: StableHashDomain, hash: u16) -> Option<StableHashEntry> {
+        STABLE_HASH_ENTRIES
+            .get(&(domain, hash))
+            .map(|entry| entry.value().clone())
+    }
+
+    pub fn unregister(domain: StableHashDomain, hash: u16) {
+        STABLE_HASH_ENTRIES.remove(&(domain, hash));
+    }
+
+    // 审计一组名字, 返回所有冲突的 hash 以及对应的名字
+    pub fn find_collisions<'a, I, F>(names: I, hash_fn: F) -> Vec<(u16, Vec<String>)>
+    where
+        I: IntoIterator<Item = &'a str>,
+        F: Fn(&str) -> u16,
+    {
+        let mut by_hash: Vec<(u16, Vec<String>)> = Vec::new();
+        for name in names {
+            let hash = hash_fn(name);
+            match by_hash.iter_mut().find(|(h, _)| *h == hash) {
+                Some((_, list)) => {
+                    if !list.iter().any(|n| n == name) {
+                        list.push(name.to_string());
+                    }
+                }
+                None => by_hash.push((hash, vec![name.to_string()])),
+            }
+        }
+        by_hash.retain(|(_, list)| list.len() > 1);
+        by_hash
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::core::tools::stable_hash::StableHash;
+
+    #[test]
+    fn test_stable_hash_registry() {
+        let a = "Test.StableHashRegistry::CmdA";
+        let hash = a.get_fn_stable_hash_code();
+        assert!(StableHashRegistry::try_register(
+            StableHashDomain::RemoteCall,
+            StableHashKind::Command,
+            a,
+            hash
+        )
+        .is_ok());
+        // 重复注册
+        assert!(StableHashRegistry::try_register(
+            StableHashDomain::RemoteCall,
+            StableHashKind::Command,
+            a,
+            hash
+        )
+        .is_ok());
+        // Command 与 ClientRpc 重名
+        assert!(StableHashRegistry::try_register(
+            StableHashDomain::RemoteCall,
+            StableHashKind::ClientRpc,
+            a,
+            hash
+        )
+        .is_err());
+        // 不同名字同一个 hash
+        let err = StableHashRegistry::try_register(
+            StableHashDomain::RemoteCall,
+            StableHashKind::Command,
+            "Test.Other",
+            hash,
+        )
+        .unwrap_err();
+        assert!(err.contains(a) && err.contains("Test.Other"));
+        // 不同的空间互不影响
+        assert!(StableHashRegistry::try_register(
+            StableHashDomain::Message,
+            StableHashKind::Message,
+            "Test.Other",
+            hash
+        )
+        .is_ok());
+
+        StableHashRegistry::unregister(StableHashDomain::RemoteCall, hash);
+        StableHashRegistry::unregister(StableHashDomain::Message, hash);
+        assert!(StableHashRegistry::get(StableHashDomain::RemoteCall, hash).is_none());
+    }
+
+    #[test]
+    fn test_find_collisions() {
+        let collisions =
+            StableHashRegistry::find_collisions(["a", "b", "a"], |name| name.len() as u16);
+        assert_eq!(
+            collisions,
+            vec![(1, vec!["a".to_string(), "b".to_string()])]
+        );
+    }
+}
@@ -0,0 +1,238 @@
+use crate::mirror::core::messages::NetworkMessageTrait;
+use crate::mirror::core::network_context::ContextLocal;
+use crate::mirror::core::network_messages::NetworkMessages;
+use crate::mirror::core::tools::stable_hash_registry::{StableHashDomain, StableHashRegistry};
+use crate::mirror::core::tools::utils::to_hex_string;
+use crate::mirror::core::transport::TransportChannel;
+use crate::{log_error, log_info};
+use lazy_static::lazy_static;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrafficDirection {
+    Send,
+    Receive,
+}
+
+impl Display for TrafficDirection {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TrafficDirection::Send => write!(f, "send"),
+            TrafficDirection::Receive => write!(f, "recv"),
+        }
+    }
+}
+
+// 为空的过滤条件匹配全部
+#[derive(Debug, Clone, Default)]
+struct TrafficFilter {
+    connections: HashSet<u64>,
+    message_ids: HashSet<u16>,
+    // watch_message_type 登记的名字, 没有注册处理函数的发送消息也能显示名字
+    names: HashMap<u16, &'static str>,
+    // 每条消息最多输出多少字节的十六进制, 0 表示不输出
+    hex_dump_bytes: usize,
+}
+
+// TrafficLog 静态变量
+lazy_static! {
+    static ref ENABLED: ContextLocal<AtomicBool> = ContextLocal::new(|| AtomicBool::new(false));
+    static ref FILTER: ContextLocal<RwLock<TrafficFilter>> =
+        ContextLocal::new(|| RwLock::new(TrafficFilter::default()));
+}
+
+// 调试用的收发日志, 每条消息 (解包之后 / 打包之前) 输出一行: 方向、连接、通道、大小、消息名和可选的十六进制
+// 可以在运行时按连接或消息类型过滤, 默认关闭, 关闭时只多一次原子读取
+pub struct TrafficLog;
+
+impl TrafficLog {
+    pub fn enabled() -> bool {
+        ENABLED.load(Ordering::Relaxed)
+    }
+
+    pub fn set_enabled(value: bool) {
+        ENABLED.store(value, Ordering::Relaxed);
+    }
+
+    pub fn watch_connection(conn_id: u64) {
+        Self::modify(|filter| {
+            filter.connections.insert(conn_id);
+        });
+    }
+
+    pub fn unwatch_connection(conn_id: u64) {
+        Self::modify(|filter| {
+            filter.connections.remove(&conn_id);
+        });
+    }
+
+    pub fn watch_message(message_id: u16) {
+        Self::modify(|filter| {
+            filter.message_ids.insert(message_id);
+        });
+    }
+
+    pub fn watch_message_type<T: NetworkMessageTrait>() {
+        Self::modify(|filter| {
+            filter.message_ids.insert(T::get_hash_code());
+            filter.names.insert(T::get_hash_code(), T::get_full_name());
+        });
+    }
+
+    pub fn unwatch_message(message_id: u16) {
+        Self::modify(|filter| {
+            filter.message_ids.remove(&message_id);
+        });
+    }
+
+    pub fn set_hex_dump_bytes(value: usize) {
+        Self::modify(|filter| filter.hex_dump_bytes = value);
+    }
+
+    // 清除连接和消息过滤条件, 保留 hex_dump_bytes
+    pub fn clear_filters() {
+        Self::modify(|filter| {
+            filter.connections.clear();
+            filter.message_ids.clear();
+        });
+    }
+
+    fn modify<F: FnOnce(&mut TrafficFilter)>(func: F) {
+        match FILTER.write() {
+            Ok(mut filter) => func(&mut filter),
+            Err(e) => {
+                log_error!("TrafficLog failed to modify filter: {:?}", e);
+            }
+        }
+    }
+
+    // segment 是一条完整的消息, 以消息 id 开头
+    pub fn log(
+        direction: TrafficDirection,
+        conn_id: u64,
+        channel: TransportChannel,
+        segment: &[u8],
+    ) {
+        if !Self::enabled() {
+            return;
+        }
+        if let Some(line) = Self::format(direction, conn_id, channel, segment) {
+            log_info!(line);
+        }
+    }
+
+    // 不匹配过滤条件时返回 None
+    pub fn format(
+        direction: TrafficDirection,
+        conn_id: u64,
+        channel: TransportChannel,
+        segment: &[u8],
+    ) -> Option<String> {
+        let filter = match FILTER.read() {
+            Ok(filter) => filter,
+            Err(e) => {
+                log_error!("TrafficLog failed to get filter: {:?}", e);
+                return None;
+            }
+        };
+        if !filter.connections.is_empty() && !filter.connections.contains(&conn_id) {
+            return None;
+        }
+        let message_id = match segment.get(..NetworkMessages::ID_SIZE) {
+            Some(id) => u16::from_le_bytes([id[0], id[1]]),
+            None => {
+                // 不完整的消息只在没有消息过滤时输出
+                if !filter.message_ids.is_empty() {
+                    return None;
+                }
+                return Some(format!(
+                    "Traffic {} conn {} {:?} {}B <truncated> {}",
+                    direction,
+                    conn_id,
+                    channel,
+                    segment.len(),
+                    to_hex_string(segment)
+                ));
+            }
+        };
+        if !filter.message_ids.is_empty() && !filter.message_ids.contains(&message_id) {
+            return None;
+        }
+        let name = match filter.names.get(&message_id) {
+            Some(name) => name.to_string(),
+            None => StableHashRegistry::get(StableHashDomain::Message, message_id)
+                .map(|entry| entry.full_name)
+                .unwrap_or_else(|| "?".to_string()),
+        };
+        let mut line = format!(
+            "Traffic {} conn {} {:?} {}B {}({})",
+            direction,
+            conn_id,
+            channel,
+            segment.len(),
+            name,
+            message_id
+        );
+        if filter.hex_dump_bytes > 0 {
+            let dump = &segment[..segment.len().min(filter.hex_dump_bytes)];
+            line.push(' ');
+            line.push_str(&to_hex_string(dump));
+            if dump.len() < segment.len() {
+                line.push_str("..");
+            }
+        }
+        Some(line)
+    }
+
+    pub fn reset() {
+        ENABLED.store(false, Ordering::Relaxed);
+        Self::modify(|filter| *filter = TrafficFilter::default());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::core::messages::NetworkPingMessage;
+    use crate::mirror::transports::memory::memory_transport::test_util::with_isolated_context;
+
+    #[test]
+    fn test_traffic_log_filters() {
+        with_isolated_context(|| {
+            let ping = NetworkPingMessage::get_hash_code();
+            let mut segment = ping.to_le_bytes().to_vec();
+            segment.extend_from_slice(&[0xAB; 8]);
+            let reliable = TransportChannel::Reliable;
+
+            let line = TrafficLog::format(TrafficDirection::Send, 1, reliable, &segment).unwrap();
+            assert!(line.starts_with("Traffic send conn 1 Reliable 10B "));
+            assert!(line.ends_with(&format!("({})", ping)));
+
+            TrafficLog::watch_connection(2);
+            assert!(TrafficLog::format(TrafficDirection::Send, 1, reliable, &segment).is_none());
+
+            TrafficLog::watch_message_type::<NetworkPingMessage>();
+            TrafficLog::set_hex_dump_bytes(4);
+            let line =
+                TrafficLog::format(TrafficDirection::Receive, 2, reliable, &segment).unwrap();
+            assert_eq!(
+                line,
+                format!(
+                    "Traffic recv conn 2 Reliable 10B {}({}) {}ABAB..",
+                    NetworkPingMessage::get_full_name(),
+                    ping,
+                    to_hex_string(&ping.to_le_bytes())
+                )
+            );
+            let other = ping.wrapping_add(1).to_le_bytes();
+            assert!(TrafficLog::format(TrafficDirection::Receive, 2, reliable, &other).is_none());
+
+            TrafficLog::clear_filters();
+            assert!(TrafficLog::format(TrafficDirection::Receive, 3, reliable, &other).is_some());
+            TrafficLog::reset();
+        });
+    }
+}
@@ -1,6 +1,17 @@
+use crate::log_error;
+use crate::mirror::core::network_context::{ContextCell, ContextLocal};
+use crate::mirror::core::network_loop::NetworkLoop;
+use crossbeam_channel::{bounded, Receiver, SendTimeoutError, Sender};
+use lazy_static::lazy_static;
 use std::fmt::Debug;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
 
-static mut ACTIVE_TRANSPORT: Option<Box<dyn TransportTrait>> = None;
+lazy_static! {
+    static ref ACTIVE_TRANSPORT: ContextLocal<ContextCell<Option<Box<dyn TransportTrait>>>> =
+        ContextLocal::new(|| ContextCell::new(None));
+}
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 #[repr(u8)]
@@ -18,6 +29,11 @@ pub enum TransportCallbackType {
     OnServerError,
     OnServerDataSent,
     OnServerTransportException,
+    // 客户端模式 (TransportTrait::client_connect) 的事件
+    OnClientConnected,
+    OnClientDataReceived,
+    OnClientDisconnected,
+    OnClientError,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -64,19 +80,175 @@ pub struct Transport {
 impl Transport {
     #[allow(warnings)]
     pub fn active_transport() -> Option<&'static mut Box<dyn TransportTrait>> {
-        unsafe { ACTIVE_TRANSPORT.as_mut() }
+        let cell: &'static ContextCell<_> = &ACTIVE_TRANSPORT;
+        unsafe { (*cell.as_ptr()).as_mut() }
     }
     #[allow(warnings)]
     pub fn active_transport_exists() -> bool {
-        unsafe { ACTIVE_TRANSPORT.is_some() }
+        unsafe { (*ACTIVE_TRANSPORT.as_ptr()).is_some() }
     }
     #[allow(warnings)]
     pub fn set_active_transport(transport: Box<dyn TransportTrait>) {
         unsafe {
-            ACTIVE_TRANSPORT.replace(transport);
+            (*ACTIVE_TRANSPORT.as_ptr()).replace(transport);
+        }
+    }
+    // 取出当前的 Transport, 用于包装 (例如 NetworkSimulator)
+    #[allow(warnings)]
+    pub fn take_active_transport() -> Option<Box<dyn TransportTrait>> {
+        unsafe { (*ACTIVE_TRANSPORT.as_ptr()).take() }
+    }
+
+    // 把配置中的 network_address 解析为监听地址
+    // "localhost" / 空字符串监听所有网卡, dual_mode 时使用 "::" 同时接受 IPv4 与 IPv6
+    // IPv6 地址可以带方括号, 也可以是主机名
+    pub fn listen_endpoint(
+        network_address: &str,
+        port: u16,
+        dual_mode: bool,
+    ) -> Option<SocketAddr> {
+        let address = network_address
+            .trim()
+            .trim_start_matches('[')
+            .trim_end_matches(']');
+        if address.is_empty() || address == "localhost" {
+            let ip = if dual_mode {
+                IpAddr::V6(Ipv6Addr::UNSPECIFIED)
+            } else {
+                IpAddr::V4(Ipv4Addr::UNSPECIFIED)
+            };
+            return Some(SocketAddr::new(ip, port));
+        }
+        if let Ok(ip) = address.parse::<IpAddr>() {
+            return Some(SocketAddr::new(ip, port));
+        }
+        (address, port)
+            .to_socket_addrs()
+            .ok()?
+            .find(|endpoint| dual_mode || endpoint.is_ipv4())
+    }
+
+    // 客户端地址的规范形式: 只保留 IP, IPv4 映射的 IPv6 地址 (::ffff:a.b.c.d) 转为 IPv4
+    // 无法解析的地址 (例如 memory://1) 原样返回
+    pub fn canonical_address(address: &str) -> String {
+        if let Ok(endpoint) = address.parse::<SocketAddr>() {
+            return endpoint.ip().to_canonical().to_string();
+        }
+        match address
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse::<IpAddr>()
+        {
+            Ok(ip) => ip.to_canonical().to_string(),
+            Err(_) => address.to_string(),
         }
     }
 }
+// 接收线程 -> 主循环 的有界队列
+// 队列满时接收线程等待, 积压留在 socket 缓冲区 (可靠通道由 kcp 重传), 不丢弃已经收到的事件
+// 主循环每次最多处理 budget 个, 突发的大量数据包分摊到多个 tick, 不会挤占模拟的时间
+pub struct TransportReceiveQueue {
+    sender: Sender<TransportCallback>,
+    receiver: Receiver<TransportCallback>,
+    closed: AtomicBool,
+    received_count: AtomicU64,
+    // 接收线程因为队列满而等待的次数
+    full_count: AtomicU64,
+    peak_len: AtomicUsize,
+}
+
+impl TransportReceiveQueue {
+    const FULL_WAIT: Duration = Duration::from_millis(10);
+
+    pub fn new(capacity: usize) -> Self {
+        let (sender, receiver) = bounded(capacity.max(1));
+        Self {
+            sender,
+            receiver,
+            closed: AtomicBool::new(false),
+            received_count: AtomicU64::new(0),
+            full_count: AtomicU64::new(0),
+            peak_len: AtomicUsize::new(0),
+        }
+    }
+
+    // 接收线程调用, 队列满时阻塞直到有空位, 关闭后返回 false
+    pub fn push(&self, tcb: TransportCallback) -> bool {
+        if self.closed.load(Ordering::Relaxed) {
+            return false;
+        }
+        let mut tcb = match self.sender.try_send(tcb) {
+            Ok(_) => {
+                self.on_pushed();
+                return true;
+            }
+            Err(e) => e.into_inner(),
+        };
+        self.full_count.fetch_add(1, Ordering::Relaxed);
+        while !self.closed.load(Ordering::Relaxed) {
+            match self.sender.send_timeout(tcb, Self::FULL_WAIT) {
+                Ok(_) => {
+                    self.on_pushed();
+                    return true;
+                }
+                Err(SendTimeoutError::Timeout(value)) => tcb = value,
+                Err(SendTimeoutError::Disconnected(_)) => return false,
+            }
+        }
+        false
+    }
+
+    fn on_pushed(&self) {
+        NetworkLoop::wake();
+        self.received_count.fetch_add(1, Ordering::Relaxed);
+        self.peak_len
+            .fetch_max(self.receiver.len(), Ordering::Relaxed);
+    }
+
+    // 主循环调用, 最多处理 budget 个 (0 表示不限制), 返回处理的数量
+    pub fn drain<F: FnMut(TransportCallback)>(&self, budget: usize, mut func: F) -> usize {
+        let budget = if budget == 0 { usize::MAX } else { budget };
+        let mut count = 0;
+        while count < budget {
+            match self.receiver.try_recv() {
+                Ok(tcb) => func(tcb),
+                Err(_) => break,
+            }
+            count += 1;
+        }
+        count
+    }
+
+    // 唤醒等待中的接收线程, 之后的 push 都返回 false
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::Relaxed);
+    }
+
+    pub fn len(&self) -> usize {
+        self.receiver.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.receiver.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.receiver.capacity().unwrap_or(0)
+    }
+
+    pub fn received_count(&self) -> u64 {
+        self.received_count.load(Ordering::Relaxed)
+    }
+
+    pub fn full_count(&self) -> u64 {
+        self.full_count.load(Ordering::Relaxed)
+    }
+
+    pub fn peak_len(&self) -> usize {
+        self.peak_len.load(Ordering::Relaxed)
+    }
+}
+
 pub trait TransportTrait {
     fn awake()
     where
@@ -93,6 +265,10 @@ pub trait TransportTrait {
     fn server_send(&mut self, connection_id: u64, data: Vec<u8>, channel: TransportChannel);
     fn server_disconnect(&mut self, connection_id: u64);
     fn server_get_client_address(&self, connection_id: u64) -> String;
+    // 服务器实际监听的地址, 不是 IP 传输层 (或尚未启动) 时为 None
+    fn server_local_endpoint(&self) -> Option<SocketAddr> {
+        None
+    }
     fn server_early_update(&mut self);
     fn server_late_update(&mut self);
     fn server_stop(&mut self);
@@ -102,4 +278,112 @@ pub trait TransportTrait {
     fn get_batcher_threshold(&self, channel: TransportChannel) -> usize {
         self.get_max_packet_size(channel)
     }
+
+    // 客户端模式: 作为客户端连接另一个 Mirror 服务器 (中继、跨服务器 NPC、测试), 由 NetworkClient 使用
+    // 事件通过 transport_cb_fn 以 OnClient* 回调, 不支持客户端模式的 Transport 返回 false
+    fn client_connect(&mut self, address: &str) -> bool {
+        log_error!(
+            "Transport does not support client mode, failed to connect to {}",
+            address
+        );
+        false
+    }
+    fn client_connected(&self) -> bool {
+        false
+    }
+    fn client_send(&mut self, data: Vec<u8>, channel: TransportChannel) {
+        let _ = (data, channel);
+    }
+    fn client_disconnect(&mut self) {}
+    fn client_early_update(&mut self) {}
+    fn client_late_update(&mut self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_listen_endpoint() {
+        assert_eq!(
+            Transport::listen_endpoint("localhost", 7777, false),
+            Some("0.0.0.0:7777".parse().unwrap())
+        );
+        assert_eq!(
+            Transport::listen_endpoint("localhost", 7777, true),
+            Some("[::]:7777".parse().unwrap())
+        );
+        assert_eq!(
+            Transport::listen_endpoint("[::1]", 7777, false),
+            Some("[::1]:7777".parse().unwrap())
+        );
+        assert_eq!(
+            Transport::listen_endpoint("192.168.1.2", 7777, true),
+            Some("192.168.1.2:7777".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn test_canonical_address() {
+        assert_eq!(
+            Transport::canonical_address("[::ffff:10.0.0.1]:7777"),
+            "10.0.0.1"
+        );
+        assert_eq!(Transport::canonical_address("10.0.0.1:7777"), "10.0.0.1");
+        assert_eq!(
+            Transport::canonical_address("[2001:DB8::1]:7777"),
+            "2001:db8::1"
+        );
+        assert_eq!(Transport::canonical_address("::ffff:10.0.0.1"), "10.0.0.1");
+        assert_eq!(Transport::canonical_address("memory://1"), "memory://1");
+    }
+
+    #[test]
+    fn test_receive_queue() {
+        let queue = std::sync::Arc::new(TransportReceiveQueue::new(4));
+        let producer = {
+            let queue = queue.clone();
+            std::thread::spawn(move || {
+                for conn_id in 0..10 {
+                    let tcb = TransportCallback {
+                        r#type: TransportCallbackType::OnServerDataReceived,
+                        conn_id,
+                        ..TransportCallback::default()
+                    };
+                    assert!(queue.push(tcb));
+                }
+            })
+        };
+        // 队列满时接收线程等待, 不丢弃
+        while queue.len() < 4 {
+            std::thread::yield_now();
+        }
+        let mut received = Vec::new();
+        assert_eq!(queue.drain(3, |tcb| received.push(tcb.conn_id)), 3);
+        while received.len() < 10 {
+            queue.drain(3, |tcb| received.push(tcb.conn_id));
+        }
+        producer.join().unwrap();
+        assert_eq!(received, (0..10).collect::<Vec<u64>>());
+        assert_eq!(queue.received_count(), 10);
+        assert!(queue.full_count() > 0);
+        assert!(queue.peak_len() > 0 && queue.peak_len() <= 4);
+        assert!(queue.is_empty());
+
+        // 关闭后等待中的 push 返回
+        for _ in 0..4 {
+            assert!(queue.push(TransportCallback::default()));
+        }
+        let blocked = {
+            let queue = queue.clone();
+            std::thread::spawn(move || queue.push(TransportCallback::default()))
+        };
+        queue.close();
+        assert!(!blocked.join().unwrap());
+        assert_eq!(queue.drain(0, |_| {}), 4);
+
+        // 关闭后即使有空位也丢弃
+        assert!(!queue.push(TransportCallback::default()));
+        assert!(queue.is_empty());
+    }
 }
@@ -0,0 +1,239 @@
+use nalgebra::{Quaternion, UnitQuaternion, Vector3};
+
+// UnityEngine 和 System 中常用的值类型, 内存布局与 C# 一致, 可以直接 write_blittable / read_blittable
+// Matrix4x4 使用 nalgebra::Matrix4<f32>, 两者都是列主序
+// System.DateTime 使用 std::time::SystemTime
+
+// UnityEngine.Vector3, 12 字节
+// 公开 API 中不依赖 nalgebra 版本的轻量类型, 与 nalgebra::Vector3<f32> 互相转换, 写入的字节相同
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Vec3 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Vec3 {
+    pub const ZERO: Vec3 = Vec3::new(0.0, 0.0, 0.0);
+
+    pub const fn new(x: f32, y: f32, z: f32) -> Self {
+        Self { x, y, z }
+    }
+}
+
+impl From<Vector3<f32>> for Vec3 {
+    fn from(value: Vector3<f32>) -> Self {
+        Self::new(value.x, value.y, value.z)
+    }
+}
+
+impl From<Vec3> for Vector3<f32> {
+    fn from(value: Vec3) -> Self {
+        Vector3::new(value.x, value.y, value.z)
+    }
+}
+
+impl From<[f32; 3]> for Vec3 {
+    fn from(value: [f32; 3]) -> Self {
+        Self::new(value[0], value[1], value[2])
+    }
+}
+
+impl From<Vec3> for [f32; 3] {
+    fn from(value: Vec3) -> Self {
+        [value.x, value.y, value.z]
+    }
+}
+
+// UnityEngine.Quaternion, 16 字节, 顺序为 x y z w
+// 与 nalgebra::Quaternion<f32> 互相转换, 注意 Quaternion::new 的参数顺序是 w x y z
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Quat {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub w: f32,
+}
+
+impl Quat {
+    pub const IDENTITY: Quat = Quat::new(0.0, 0.0, 0.0, 1.0);
+
+    pub const fn new(x: f32, y: f32, z: f32, w: f32) -> Self {
+        Self { x, y, z, w }
+    }
+}
+
+impl From<Quaternion<f32>> for Quat {
+    fn from(value: Quaternion<f32>) -> Self {
+        Self::new(value.i, value.j, value.k, value.w)
+    }
+}
+
+impl From<Quat> for Quaternion<f32> {
+    fn from(value: Quat) -> Self {
+        Quaternion::new(value.w, value.x, value.y, value.z)
+    }
+}
+
+impl From<UnitQuaternion<f32>> for Quat {
+    fn from(value: UnitQuaternion<f32>) -> Self {
+        Self::from(value.into_inner())
+    }
+}
+
+// 会重新归一化
+impl From<Quat> for UnitQuaternion<f32> {
+    fn from(value: Quat) -> Self {
+        UnitQuaternion::from_quaternion(value.into())
+    }
+}
+
+// UnityEngine.Color, 16 字节
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Color {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+impl Color {
+    pub fn new(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Self { r, g, b, a }
+    }
+}
+
+impl From<Color32> for Color {
+    fn from(value: Color32) -> Self {
+        Self::new(
+            value.r as f32 / 255.0,
+            value.g as f32 / 255.0,
+            value.b as f32 / 255.0,
+            value.a as f32 / 255.0,
+        )
+    }
+}
+
+// UnityEngine.Color32, 4 字节
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Color32 {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Color32 {
+    pub fn new(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self { r, g, b, a }
+    }
+}
+
+impl From<Color> for Color32 {
+    // 与 Unity 相同: 限制到 0 ~ 1 后四舍五入
+    fn from(value: Color) -> Self {
+        let to_byte = |v: f32| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+        Self::new(
+            to_byte(value.r),
+            to_byte(value.g),
+            to_byte(value.b),
+            to_byte(value.a),
+        )
+    }
+}
+
+// UnityEngine.Rect, 16 字节
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Rect {
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+}
+
+// UnityEngine.Plane, 16 字节
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Plane {
+    pub normal: Vector3<f32>,
+    pub distance: f32,
+}
+
+impl Plane {
+    pub fn new(normal: Vector3<f32>, distance: f32) -> Self {
+        Self { normal, distance }
+    }
+}
+
+// UnityEngine.Ray, 24 字节
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ray {
+    pub origin: Vector3<f32>,
+    pub direction: Vector3<f32>,
+}
+
+impl Ray {
+    pub fn new(origin: Vector3<f32>, direction: Vector3<f32>) -> Self {
+        Self { origin, direction }
+    }
+}
+
+// System.Guid, 字节顺序与 .NET 的 Guid.ToByteArray 相同 (前三段为小端序)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Guid(pub [u8; 16]);
+
+impl Guid {
+    pub const EMPTY: Guid = Guid([0; 16]);
+
+    pub fn new(bytes: [u8; 16]) -> Self {
+        Self(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vec3_quat_conversion() {
+        let vector = Vector3::new(1.0, 2.0, 3.0);
+        let vec3 = Vec3::from(vector);
+        assert_eq!(vec3, Vec3::new(1.0, 2.0, 3.0));
+        assert_eq!(Vector3::from(vec3), vector);
+        assert_eq!(<[f32; 3]>::from(vec3), [1.0, 2.0, 3.0]);
+
+        let quaternion = Quaternion::new(4.0, 1.0, 2.0, 3.0);
+        let quat = Quat::from(quaternion);
+        assert_eq!(quat, Quat::new(1.0, 2.0, 3.0, 4.0));
+        assert_eq!(Quaternion::from(quat), quaternion);
+        // 内存布局与 nalgebra 相同
+        assert_eq!(
+            quaternion.coords.data.0[0],
+            [quat.x, quat.y, quat.z, quat.w]
+        );
+
+        assert_eq!(Quat::from(UnitQuaternion::identity()), Quat::IDENTITY);
+        assert_eq!(
+            UnitQuaternion::from(Quat::IDENTITY),
+            UnitQuaternion::identity()
+        );
+    }
+}
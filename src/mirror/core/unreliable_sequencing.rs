@@ -0,0 +1,154 @@
+use crate::mirror::core::network_context::ContextLocal;
+use dashmap::DashMap;
+use lazy_static::lazy_static;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+// UnreliableSequencing 静态变量
+lazy_static! {
+    static ref ENABLED: ContextLocal<AtomicBool> = ContextLocal::new(|| AtomicBool::new(false));
+    // (connection_id, net_id, component_index) -> 最后接受的 remote_time_stamp
+    static ref LAST_ACCEPTED: ContextLocal<DashMap<(u64, u32, u8), f64>> =
+        ContextLocal::new(DashMap::new);
+    static ref DISCARDED_COUNT: ContextLocal<AtomicU64> = ContextLocal::new(|| AtomicU64::new(0));
+}
+
+// Unreliable 通道的最新状态语义: 按 (net_id, component_index) 记录客户端批次的发送时间 (remote_time_stamp),
+// 比已经接受的更旧的消息直接丢弃, 避免乱序到达的旧快照覆盖新状态
+// 只对注册为 latest_only 的 Command 和 Unreliable 通道的 EntityStateMessage 生效, 不修改协议
+pub struct UnreliableSequencing;
+
+impl UnreliableSequencing {
+    // EntityStateMessage 包含整个对象的状态, 使用这个组件索引
+    pub const ENTITY_COMPONENT: u8 = u8::MAX;
+
+    pub fn enabled() -> bool {
+        ENABLED.load(Ordering::Relaxed)
+    }
+
+    pub fn set_enabled(value: bool) {
+        ENABLED.store(value, Ordering::Relaxed);
+        if !value {
+            LAST_ACCEPTED.clear();
+        }
+    }
+
+    // 因为乱序被丢弃的消息数量
+    pub fn discarded_count() -> u64 {
+        DISCARDED_COUNT.load(Ordering::Relaxed)
+    }
+
+    // 同一个批次中的消息时间相同, 按顺序全部接受
+    pub(crate) fn accept(
+        connection_id: u64,
+        net_id: u32,
+        component_index: u8,
+        remote_time_stamp: f64,
+    ) -> bool {
+        if !Self::enabled() {
+            return true;
+        }
+        let mut last = LAST_ACCEPTED
+            .entry((connection_id, net_id, component_index))
+            .or_insert(f64::MIN);
+        if remote_time_stamp < *last {
+            DISCARDED_COUNT.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+        *last = remote_time_stamp;
+        true
+    }
+
+    // 在 NetworkServer::on_transport_disconnected 中调用
+    pub(crate) fn on_disconnected(connection_id: u64) {
+        if LAST_ACCEPTED.is_empty() {
+            return;
+        }
+        LAST_ACCEPTED.retain(|(id, _, _), _| *id != connection_id);
+    }
+
+    pub fn reset() {
+        LAST_ACCEPTED.clear();
+        DISCARDED_COUNT.store(0, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::core::batching::batcher::Batcher;
+    use crate::mirror::core::messages::{CommandMessage, NetworkMessageTrait};
+    use crate::mirror::core::network_behaviour::SyncDirection;
+    use crate::mirror::core::network_reader::NetworkReader;
+    use crate::mirror::core::network_server::{
+        NetworkServer, NetworkServerStatic, NETWORK_BEHAVIOURS,
+    };
+    use crate::mirror::core::network_writer::NetworkWriter;
+    use crate::mirror::core::network_writer_pool::NetworkWriterPool;
+    use crate::mirror::core::remote_calls::RemoteProcedureCalls;
+    use crate::mirror::core::transport::TransportChannel;
+    use crate::mirror::transports::memory::memory_transport::test_util::*;
+    use crate::mirror::transports::memory::memory_transport::MemoryTransport;
+    use std::sync::atomic::AtomicU32;
+
+    static LATEST_WRITES: AtomicU32 = AtomicU32::new(0);
+
+    fn on_test_latest_write(_: u64, _: u32, _: u8, _: u16, _: &mut NetworkReader) {
+        LATEST_WRITES.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[test]
+    fn test_unreliable_sequencing() {
+        with_server(|| {
+            let function_hash = RemoteProcedureCalls::register_latest_only_state_command_delegate::<
+                TestHit,
+            >(
+                "System.Void Test.TestHit::CmdSync()", on_test_latest_write
+            );
+            MemoryTransport::client_connect(1);
+            tick();
+            NetworkServer::set_client_ready(1);
+            spawn_test_identity(960, 1, 1);
+            NETWORK_BEHAVIOURS
+                .get_mut(&(960, 0))
+                .unwrap()
+                .set_sync_direction(SyncDirection::ClientToServer);
+
+            // 每个批次带客户端的发送时间
+            let send = |time_stamp: f64, channel: TransportChannel| {
+                let mut writer = NetworkWriter::new();
+                CommandMessage::new(960, 0, function_hash, Vec::new()).serialize(&mut writer);
+                let mut batcher = Batcher::new(MemoryTransport::MAX_PACKET_SIZE);
+                batcher.add_message(&writer.to_bytes(), time_stamp);
+                NetworkWriterPool::get_return(|writer| {
+                    while batcher.get_batcher_writer(writer) {
+                        MemoryTransport::client_send(1, writer.to_bytes(), channel);
+                        writer.reset();
+                    }
+                });
+                let writes = LATEST_WRITES.load(Ordering::Relaxed);
+                tick();
+                LATEST_WRITES.load(Ordering::Relaxed) - writes
+            };
+
+            // 关闭时全部接受
+            assert_eq!(send(2.0, TransportChannel::Unreliable), 1);
+            assert_eq!(send(1.0, TransportChannel::Unreliable), 1);
+
+            UnreliableSequencing::set_enabled(true);
+            assert_eq!(send(2.0, TransportChannel::Unreliable), 1);
+            // 同一时间的批次和更新的批次
+            assert_eq!(send(2.0, TransportChannel::Unreliable), 1);
+            assert_eq!(send(3.0, TransportChannel::Unreliable), 1);
+            // 乱序到达的旧批次被丢弃, Reliable 通道不受影响
+            assert_eq!(send(2.5, TransportChannel::Unreliable), 0);
+            assert_eq!(UnreliableSequencing::discarded_count(), 1);
+            assert_eq!(send(2.5, TransportChannel::Reliable), 1);
+            assert_eq!(send(4.0, TransportChannel::Unreliable), 1);
+
+            UnreliableSequencing::set_enabled(false);
+            RemoteProcedureCalls::remove_delegate(function_hash);
+            NetworkServerStatic::remove_spawned_network_identity(&960);
+            UnreliableSequencing::reset();
+        });
+    }
+}
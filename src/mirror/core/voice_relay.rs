@@ -0,0 +1,313 @@
+use crate::log_error;
+use crate::mirror::core::batching::batcher::Batcher;
+use crate::mirror::core::messages::{NetworkMessageTrait, VoiceMessage};
+use crate::mirror::core::network_connection::NetworkConnectionTrait;
+use crate::mirror::core::network_context::ContextLocal;
+use crate::mirror::core::network_reader::{NetworkReader, NetworkReaderTrait};
+use crate::mirror::core::network_server::{NetworkServer, NetworkServerStatic};
+use crate::mirror::core::network_time::NetworkTime;
+use crate::mirror::core::network_writer::NetworkWriterTrait;
+use crate::mirror::core::network_writer_pool::NetworkWriterPool;
+use crate::mirror::core::transport::TransportChannel;
+use atomic::Atomic;
+use dashmap::try_result::TryResult;
+use lazy_static::lazy_static;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+// 每个连接的语音状态, 存放在连接的 ext 中, 断开连接时一起释放
+#[derive(Debug, Default)]
+pub struct VoiceState {
+    // 这个连接不想听到的说话者
+    muted: HashSet<u64>,
+    // 令牌桶, 单位为字节
+    budget: f64,
+    last_refill: f64,
+}
+
+// VoiceRelay 静态变量
+lazy_static! {
+    static ref ENABLED: ContextLocal<AtomicBool> = ContextLocal::new(|| AtomicBool::new(false));
+    // 每个说话者每秒最多转发的字节数, 0 表示不限制
+    static ref MAX_BYTES_PER_SECOND: ContextLocal<Atomic<u32>> =
+        ContextLocal::new(|| Atomic::new(4000));
+    static ref MAX_FRAME_SIZE: ContextLocal<Atomic<usize>> = ContextLocal::new(|| Atomic::new(512));
+    static ref RELAYED_COUNT: ContextLocal<AtomicU64> = ContextLocal::new(|| AtomicU64::new(0));
+    static ref DROPPED_COUNT: ContextLocal<AtomicU64> = ContextLocal::new(|| AtomicU64::new(0));
+}
+
+// 语音转发: 客户端把每个 VoiceMessage 单独作为一个 Unreliable 的 batch 发送,
+// 服务器在 on_transport_data 中直接识别并转发给说话者玩家的观察者, 不经过 un_batcher 和消息处理器,
+// 转发时也不进入连接的 batcher, 立即发送
+// 和其他消息打包在一起的 VoiceMessage 由普通的消息处理器转发
+pub struct VoiceRelay;
+
+impl VoiceRelay {
+    pub fn enable() {
+        ENABLED.store(true, Ordering::Relaxed);
+        NetworkServer::register_handler::<VoiceMessage>(Self::on_voice_message, true);
+    }
+
+    pub fn enabled() -> bool {
+        ENABLED.load(Ordering::Relaxed)
+    }
+
+    pub fn max_bytes_per_second() -> u32 {
+        MAX_BYTES_PER_SECOND.load(Ordering::Relaxed)
+    }
+
+    pub fn set_max_bytes_per_second(value: u32) {
+        MAX_BYTES_PER_SECOND.store(value, Ordering::Relaxed);
+    }
+
+    pub fn max_frame_size() -> usize {
+        MAX_FRAME_SIZE.load(Ordering::Relaxed)
+    }
+
+    pub fn set_max_frame_size(value: usize) {
+        MAX_FRAME_SIZE.store(value, Ordering::Relaxed);
+    }
+
+    // 转发给每个听众计一次
+    pub fn relayed_count() -> u64 {
+        RELAYED_COUNT.load(Ordering::Relaxed)
+    }
+
+    // 超过帧大小或带宽上限被丢弃的帧
+    pub fn dropped_count() -> u64 {
+        DROPPED_COUNT.load(Ordering::Relaxed)
+    }
+
+    pub fn mute(listener: u64, speaker: u64) {
+        Self::with_state(listener, |state| {
+            state.muted.insert(speaker);
+        });
+    }
+
+    pub fn unmute(listener: u64, speaker: u64) {
+        Self::with_state(listener, |state| {
+            state.muted.remove(&speaker);
+        });
+    }
+
+    pub fn is_muted(listener: u64, speaker: u64) -> bool {
+        match NetworkServerStatic::network_connections().get(&listener) {
+            Some(connection) => connection
+                .get_ext::<VoiceState>()
+                .map(|state| state.muted.contains(&speaker))
+                .unwrap_or(false),
+            None => false,
+        }
+    }
+
+    fn with_state<F: FnOnce(&mut VoiceState)>(connection_id: u64, func: F) {
+        match NetworkServerStatic::network_connections().try_get_mut(&connection_id) {
+            TryResult::Present(mut connection) => {
+                if !connection.has_ext::<VoiceState>() {
+                    connection.insert_ext(VoiceState::default());
+                }
+                if let Some(state) = connection.get_ext_mut::<VoiceState>() {
+                    func(state);
+                }
+            }
+            TryResult::Absent => {
+                log_error!("VoiceRelay: connectionId {} not found.", connection_id);
+            }
+            TryResult::Locked => {
+                log_error!("VoiceRelay: connectionId {} is locked.", connection_id);
+            }
+        }
+    }
+
+    // 在 NetworkServer::on_transport_data 中调用, 只包含一个 VoiceMessage 的 batch 返回 true
+    pub(crate) fn try_fast_path(connection_id: u64, data: &[u8]) -> bool {
+        if !Self::enabled() || data.len() <= Batcher::TIMESTAMP_SIZE {
+            return false;
+        }
+        let mut reader = NetworkReader::new_with_array_segment(&data[Batcher::TIMESTAMP_SIZE..]);
+        let size = reader.decompress_var_ulong() as usize;
+        if size != reader.remaining() || size < 2 {
+            return false;
+        }
+        if reader.read_ushort() != VoiceMessage::get_hash_code() {
+            return false;
+        }
+        let message = VoiceMessage::deserialize(&mut reader);
+        Self::relay(connection_id, &message.frame);
+        true
+    }
+
+    fn on_voice_message(
+        connection_id: u64,
+        reader: &mut NetworkReader,
+        _channel: TransportChannel,
+    ) {
+        let message = VoiceMessage::deserialize(reader);
+        Self::relay(connection_id, &message.frame);
+    }
+
+    // 转发给说话者玩家的观察者 (兴趣管理), 返回转发的听众数量
+    pub fn relay(speaker: u64, frame: &[u8]) -> usize {
+        if frame.len() > Self::max_frame_size() {
+            DROPPED_COUNT.fetch_add(1, Ordering::Relaxed);
+            return 0;
+        }
+        let net_id = match NetworkServerStatic::network_connections().try_get_mut(&speaker) {
+            TryResult::Present(mut connection) => {
+                // 快速通道没有经过消息处理器的认证检查
+                if !connection.is_authenticated() || connection.net_id() == 0 {
+                    return 0;
+                }
+                connection.set_last_message_time(NetworkTime::local_time());
+                let net_id = connection.net_id();
+                if !connection.has_ext::<VoiceState>() {
+                    connection.insert_ext(VoiceState::default());
+                }
+                let allowed = match connection.get_ext_mut::<VoiceState>() {
+                    Some(state) => Self::consume_budget(state, frame.len()),
+                    None => false,
+                };
+                if !allowed {
+                    DROPPED_COUNT.fetch_add(1, Ordering::Relaxed);
+                    return 0;
+                }
+                net_id
+            }
+            _ => return 0,
+        };
+        let observers = match NetworkServerStatic::spawned_network_identities().try_get(&net_id) {
+            TryResult::Present(identity) => identity.observers().clone(),
+            _ => return 0,
+        };
+
+        let mut relayed = 0;
+        NetworkWriterPool::get_return(|writer| {
+            // 只包含一条消息的 batch, 所有听众共用
+            NetworkWriterPool::get_return(|message_writer| {
+                VoiceMessage::new(net_id, frame.to_vec()).serialize(message_writer);
+                writer.write_double(NetworkTime::local_time());
+                writer.compress_var_ulong(message_writer.get_position() as u64);
+                writer.write_array_segment_all(message_writer.to_array_segment());
+            });
+            let batch = writer.to_bytes();
+            for listener in observers.iter() {
+                if *listener == speaker {
+                    continue;
+                }
+                if let TryResult::Present(connection) =
+                    NetworkServerStatic::network_connections().try_get(listener)
+                {
+                    let muted = connection
+                        .get_ext::<VoiceState>()
+                        .map(|state| state.muted.contains(&speaker))
+                        .unwrap_or(false);
+                    if !connection.is_ready() || muted {
+                        continue;
+                    }
+                    connection.send_to_transport(batch.clone(), TransportChannel::Unreliable);
+                    relayed += 1;
+                }
+            }
+        });
+        RELAYED_COUNT.fetch_add(relayed as u64, Ordering::Relaxed);
+        relayed
+    }
+
+    fn consume_budget(state: &mut VoiceState, size: usize) -> bool {
+        let max = Self::max_bytes_per_second() as f64;
+        if max == 0.0 {
+            return true;
+        }
+        let local_time = NetworkTime::local_time();
+        if state.last_refill == 0.0 {
+            state.budget = max;
+        } else {
+            state.budget = (state.budget + (local_time - state.last_refill) * max).min(max);
+        }
+        state.last_refill = local_time;
+        if state.budget < size as f64 {
+            return false;
+        }
+        state.budget -= size as f64;
+        true
+    }
+
+    pub fn reset() {
+        ENABLED.store(false, Ordering::Relaxed);
+        RELAYED_COUNT.store(0, Ordering::Relaxed);
+        DROPPED_COUNT.store(0, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::core::network_identity::{NetworkIdentity, Visibility};
+    use crate::mirror::transports::memory::memory_transport::test_util::*;
+    use crate::mirror::transports::memory::memory_transport::MemoryTransport;
+
+    #[test]
+    fn test_voice_relay() {
+        with_server(|| {
+            VoiceRelay::enable();
+            for conn_id in [1, 2] {
+                MemoryTransport::client_connect(conn_id);
+                tick();
+                NetworkServer::set_client_ready(conn_id);
+            }
+            let mut identity = NetworkIdentity::new_with_asset_id(0);
+            identity.set_net_id(400);
+            identity.set_connection_to_client(1);
+            NetworkServerStatic::add_spawned_network_identity(identity);
+            NetworkServer::set_visibility(400, Visibility::Default);
+            {
+                let mut connection = NetworkServerStatic::network_connections()
+                    .get_mut(&1)
+                    .unwrap();
+                connection.set_net_id(400);
+                connection.set_authenticated(true);
+            }
+            tick();
+            MemoryTransport::client_receive(1);
+            MemoryTransport::client_receive(2);
+
+            // 单独一个 VoiceMessage 的 Unreliable batch 走快速通道, 不等 tick 就转发
+            let send_voice = |frame: Vec<u8>| {
+                NetworkWriterPool::get_return(|writer| {
+                    VoiceMessage::new(0, frame).serialize(writer);
+                    let mut batcher = Batcher::new(MemoryTransport::MAX_PACKET_SIZE);
+                    batcher.add_message(writer.to_array_segment(), NetworkTime::local_time());
+                    writer.reset();
+                    batcher.get_batcher_writer(writer);
+                    MemoryTransport::client_send(
+                        1,
+                        writer.to_bytes(),
+                        TransportChannel::Unreliable,
+                    );
+                });
+                NetworkServer::network_early_update();
+            };
+            send_voice(vec![1, 2, 3]);
+            assert_eq!(
+                received::<VoiceMessage>(2),
+                vec![VoiceMessage::new(400, vec![1, 2, 3])]
+            );
+            assert!(received::<VoiceMessage>(1).is_empty());
+
+            VoiceRelay::mute(2, 1);
+            assert!(VoiceRelay::is_muted(2, 1));
+            send_voice(vec![4]);
+            assert!(received::<VoiceMessage>(2).is_empty());
+            VoiceRelay::unmute(2, 1);
+
+            // 超过帧大小的被丢弃
+            send_voice(vec![0; VoiceRelay::max_frame_size() + 1]);
+            assert!(received::<VoiceMessage>(2).is_empty());
+            assert_eq!(VoiceRelay::dropped_count(), 1);
+            assert_eq!(VoiceRelay::relayed_count(), 1);
+            NetworkServerStatic::remove_spawned_network_identity(&400);
+            NetworkServer::unregister_handler::<VoiceMessage>();
+            VoiceRelay::reset();
+        });
+    }
+}
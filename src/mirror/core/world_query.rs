@@ -0,0 +1,256 @@
+use crate::mirror::core::network_context::ContextLocal;
+use crate::mirror::core::network_server::NetworkServerStatic;
+use crate::mirror::core::network_time::NetworkTime;
+use atomic::Atomic;
+use dashmap::try_result::TryResult;
+use lazy_static::lazy_static;
+use nalgebra::Vector3;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+
+// 按位置分桶的已生成对象, 查询时只检查半径覆盖的格子
+#[derive(Debug, Default)]
+struct SpatialGrid {
+    cell_size: f32,
+    cells: HashMap<(i32, i32, i32), Vec<u32>>,
+    frame: u32,
+}
+
+impl SpatialGrid {
+    fn cell_of(&self, position: Vector3<f32>) -> (i32, i32, i32) {
+        (
+            (position.x / self.cell_size).floor() as i32,
+            (position.y / self.cell_size).floor() as i32,
+            (position.z / self.cell_size).floor() as i32,
+        )
+    }
+
+    fn rebuild(&mut self, cell_size: f32, frame: u32) {
+        self.cell_size = cell_size;
+        self.frame = frame;
+        self.cells.clear();
+        // 调用方可能正持有某个 identity, 不能用 iter 阻塞等待
+        let spawned = NetworkServerStatic::spawned_network_identities();
+        for net_id in NetworkServerStatic::spawned_network_ids().iter() {
+            if let TryResult::Present(identity) = spawned.try_get(&net_id) {
+                let cell = self.cell_of(identity.game_object().transform.position);
+                self.cells.entry(cell).or_default().push(*net_id);
+            }
+        }
+    }
+
+    fn candidates(&self, center: Vector3<f32>, radius: f32) -> Vec<u32> {
+        let min = self.cell_of(center - Vector3::repeat(radius));
+        let max = self.cell_of(center + Vector3::repeat(radius));
+        let span = |min: i32, max: i32| max as i64 - min as i64 + 1;
+        let cells = span(min.0, max.0)
+            .saturating_mul(span(min.1, max.1))
+            .saturating_mul(span(min.2, max.2));
+        // 半径覆盖的格子比对象还多时直接遍历所有格子
+        if cells as usize > self.cells.len() {
+            return self.cells.values().flatten().copied().collect();
+        }
+        let mut candidates = Vec::new();
+        for x in min.0..=max.0 {
+            for y in min.1..=max.1 {
+                for z in min.2..=max.2 {
+                    if let Some(net_ids) = self.cells.get(&(x, y, z)) {
+                        candidates.extend_from_slice(net_ids);
+                    }
+                }
+            }
+        }
+        candidates
+    }
+}
+
+// WorldQuery 静态变量
+lazy_static! {
+    static ref CELL_SIZE: ContextLocal<Atomic<f32>> = ContextLocal::new(|| Atomic::new(16.0));
+    static ref GRID: ContextLocal<RwLock<SpatialGrid>> =
+        ContextLocal::new(|| RwLock::new(SpatialGrid::default()));
+    static ref DIRTY: ContextLocal<AtomicBool> = ContextLocal::new(|| AtomicBool::new(true));
+}
+
+// 按 tag / layer 查询半径内已生成的对象 (例如玩家附近的拾取物)
+// 网格在每个 tick 第一次查询时重建, 同一个 tick 内移动的对象按重建时的格子查找, 距离按当前位置计算
+pub struct WorldQuery;
+
+impl WorldQuery {
+    pub const ALL_LAYERS: u32 = u32::MAX;
+
+    pub fn cell_size() -> f32 {
+        CELL_SIZE.load(Ordering::Relaxed)
+    }
+
+    // 接近常用查询半径时效果最好
+    pub fn set_cell_size(value: f32) {
+        if value > 0.0 {
+            CELL_SIZE.store(value, Ordering::Relaxed);
+            Self::invalidate();
+        }
+    }
+
+    // 在 NetworkServerStatic::add_spawned_network_identity 中调用
+    pub fn invalidate() {
+        DIRTY.store(true, Ordering::Relaxed);
+    }
+
+    fn candidates(center: Vector3<f32>, radius: f32) -> Vec<u32> {
+        let frame = NetworkTime::frame_count();
+        if let Ok(grid) = GRID.read() {
+            if !DIRTY.load(Ordering::Relaxed) && grid.frame == frame {
+                return grid.candidates(center, radius);
+            }
+        }
+        match GRID.write() {
+            Ok(mut grid) => {
+                DIRTY.store(false, Ordering::Relaxed);
+                grid.rebuild(Self::cell_size(), frame);
+                grid.candidates(center, radius)
+            }
+            Err(_) => Vec::new(),
+        }
+    }
+
+    // 半径内 layer 在 layer_mask 中的对象, 由近到远排序
+    pub fn overlap_sphere(center: Vector3<f32>, radius: f32, layer_mask: u32) -> Vec<u32> {
+        Self::query(center, radius, None, layer_mask)
+    }
+
+    // 半径内指定 tag 的对象, 由近到远排序
+    pub fn find_with_tag_in_radius(
+        tag: &str,
+        center: Vector3<f32>,
+        radius: f32,
+        layer_mask: u32,
+    ) -> Vec<u32> {
+        Self::query(center, radius, Some(tag), layer_mask)
+    }
+
+    pub fn nearest_with_tag(
+        tag: &str,
+        center: Vector3<f32>,
+        radius: f32,
+        layer_mask: u32,
+    ) -> Option<u32> {
+        Self::query(center, radius, Some(tag), layer_mask)
+            .first()
+            .copied()
+    }
+
+    // 不限距离
+    pub fn find_with_tag(tag: &str) -> Vec<u32> {
+        let spawned = NetworkServerStatic::spawned_network_identities();
+        NetworkServerStatic::spawned_network_ids()
+            .iter()
+            .filter(|net_id| match spawned.try_get(net_id) {
+                TryResult::Present(identity) => identity.game_object().compare_tag(tag),
+                _ => false,
+            })
+            .map(|net_id| *net_id)
+            .collect()
+    }
+
+    fn query(center: Vector3<f32>, radius: f32, tag: Option<&str>, layer_mask: u32) -> Vec<u32> {
+        let spawned = NetworkServerStatic::spawned_network_identities();
+        let mut found: Vec<(u32, f32)> = Vec::new();
+        for net_id in Self::candidates(center, radius) {
+            if let TryResult::Present(identity) = spawned.try_get(&net_id) {
+                let game_object = identity.game_object();
+                if !game_object.in_layer_mask(layer_mask) {
+                    continue;
+                }
+                if let Some(tag) = tag {
+                    if !game_object.compare_tag(tag) {
+                        continue;
+                    }
+                }
+                let distance = (game_object.transform.position - center).norm();
+                if distance <= radius {
+                    found.push((net_id, distance));
+                }
+            }
+        }
+        found.sort_by(|a, b| a.1.total_cmp(&b.1));
+        found.into_iter().map(|(net_id, _)| net_id).collect()
+    }
+
+    pub fn reset() {
+        if let Ok(mut grid) = GRID.write() {
+            *grid = SpatialGrid::default();
+        }
+        DIRTY.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::core::network_behaviour::GameObject;
+    use crate::mirror::core::network_identity::NetworkIdentity;
+
+    use crate::mirror::transports::memory::memory_transport::test_util::*;
+
+    #[test]
+    fn test_world_query() {
+        with_server(|| {
+            WorldQuery::set_cell_size(4.0);
+            for (net_id, tag, layer, x) in [
+                (801, "Pickup", 0, 1.0),
+                (802, "Pickup", 0, -3.0),
+                (803, "Pickup", 5, 2.0),
+                (804, "Enemy", 0, 0.5),
+                (805, "Pickup", 0, 30.0),
+            ] {
+                let mut game_object = GameObject::default();
+                game_object.set_tag(tag);
+                game_object.set_layer(layer);
+                game_object.transform.position = Vector3::new(x, 0.0, 0.0);
+                let mut identity = NetworkIdentity::new_with_asset_id(0);
+                identity.set_net_id(net_id);
+                identity.set_game_object(game_object);
+                NetworkServerStatic::add_spawned_network_identity(identity);
+            }
+
+            let center = Vector3::zeros();
+            assert_eq!(
+                WorldQuery::find_with_tag_in_radius("Pickup", center, 5.0, WorldQuery::ALL_LAYERS),
+                vec![801, 803, 802]
+            );
+            assert_eq!(
+                WorldQuery::find_with_tag_in_radius("Pickup", center, 5.0, 1 << 0),
+                vec![801, 802]
+            );
+            assert_eq!(
+                WorldQuery::nearest_with_tag("Pickup", center, 5.0, WorldQuery::ALL_LAYERS),
+                Some(801)
+            );
+            assert_eq!(
+                WorldQuery::overlap_sphere(center, 1.5, WorldQuery::ALL_LAYERS),
+                vec![804, 801]
+            );
+            let mut pickups = WorldQuery::find_with_tag("Pickup");
+            pickups.sort();
+            assert_eq!(pickups, vec![801, 802, 803, 805]);
+
+            // 同一个 tick 中新生成的对象也能查到
+            let mut game_object = GameObject::default();
+            game_object.set_tag("Pickup");
+            let mut identity = NetworkIdentity::new_with_asset_id(0);
+            identity.set_net_id(806);
+            identity.set_game_object(game_object);
+            NetworkServerStatic::add_spawned_network_identity(identity);
+            assert_eq!(
+                WorldQuery::nearest_with_tag("Pickup", center, 5.0, WorldQuery::ALL_LAYERS),
+                Some(806)
+            );
+            WorldQuery::set_cell_size(16.0);
+            for net_id in 801..=806 {
+                NetworkServerStatic::remove_spawned_network_identity(&net_id);
+            }
+            WorldQuery::reset();
+        });
+    }
+}
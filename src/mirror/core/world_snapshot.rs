@@ -0,0 +1,375 @@
+use crate::mirror::core::net_id_allocator::NetIdAllocator;
+use crate::mirror::core::network_identity::NetworkIdentity;
+use crate::mirror::core::network_reader::{NetworkReader, NetworkReaderTrait};
+use crate::mirror::core::network_server::{NetworkServer, NetworkServerStatic};
+use crate::mirror::core::network_writer::{NetworkWriter, NetworkWriterTrait};
+use crate::mirror::core::network_writer_pool::NetworkWriterPool;
+use crate::{log_error, log_warn};
+use nalgebra::{Quaternion, Vector3};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum WorldSnapshotFormat {
+    Binary,
+    Json,
+}
+
+// 一个已生成的对象, 不包含所有者 (重启后连接都不存在了)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WorldSnapshotEntry {
+    pub net_id: u32,
+    pub asset_id: u32,
+    pub scene_id: u64,
+    pub server_only: bool,
+    pub position: [f32; 3],
+    // x, y, z, w
+    pub rotation: [f32; 4],
+    pub scale: [f32; 3],
+    // NetworkIdentity::serialize_server(true, ..) 的 owner 数据
+    pub state: Vec<u8>,
+}
+
+impl WorldSnapshotEntry {
+    pub(crate) fn write(&self, writer: &mut NetworkWriter) {
+        writer.write_uint(self.net_id);
+        writer.write_uint(self.asset_id);
+        writer.write_ulong(self.scene_id);
+        writer.write_bool(self.server_only);
+        writer.write_vector3(Vector3::from(self.position));
+        writer.write_quaternion(Quaternion::from(self.rotation));
+        writer.write_vector3(Vector3::from(self.scale));
+        writer.write_array_segment_and_size(&self.state);
+    }
+
+    pub(crate) fn read(reader: &mut NetworkReader) -> Self {
+        Self {
+            net_id: reader.read_uint(),
+            asset_id: reader.read_uint(),
+            scene_id: reader.read_ulong(),
+            server_only: reader.read_bool(),
+            position: reader.read_vector3().into(),
+            rotation: reader.read_quaternion().coords.into(),
+            scale: reader.read_vector3().into(),
+            state: reader.read_bytes_and_size(),
+        }
+    }
+}
+
+// 持久化世界的存档, 服务器重启后恢复所有已生成的对象
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WorldSnapshot {
+    pub version: u16,
+    pub entries: Vec<WorldSnapshotEntry>,
+}
+
+impl WorldSnapshot {
+    pub const VERSION: u16 = 1;
+    const MAGIC: &'static [u8; 4] = b"MWSS";
+
+    pub fn capture() -> Self {
+        let mut entries = Vec::new();
+        NetworkServerStatic::for_each_spawned(|mut identity| {
+            let transform = identity.game_object().transform;
+            let mut state = Vec::new();
+            if identity.network_behaviours_count > 0 {
+                NetworkWriterPool::get_return(|owner_writer| {
+                    NetworkWriterPool::get_return(|observers_writer| {
+                        identity.serialize_server(true, owner_writer, observers_writer);
+                        state = owner_writer.to_bytes();
+                    });
+                });
+            }
+            entries.push(WorldSnapshotEntry {
+                net_id: identity.net_id(),
+                asset_id: identity.asset_id,
+                scene_id: identity.scene_id,
+                server_only: identity.server_only,
+                position: transform.local_position.into(),
+                rotation: transform.local_rotation.coords.into(),
+                scale: transform.local_scale.into(),
+                state,
+            });
+        });
+        entries.sort_by_key(|entry| entry.net_id);
+        Self {
+            version: Self::VERSION,
+            entries,
+        }
+    }
+
+    // 恢复所有对象并尽量保留原来的 net_id, 返回恢复的数量
+    // 已经生成的场景对象 (NetworkServer::spawn_objects) 只恢复状态, 不重复生成
+    // 所有者不会恢复, 玩家对象应通过 NetworkServer::import_player_state 迁移
+    pub fn restore(&self) -> usize {
+        if !NetworkServerStatic::active() {
+            log_error!("WorldSnapshot.Restore: NetworkServer is not active.");
+            return 0;
+        }
+        // 先预留快照中的 net_id, 生成过程中新分配的 net_id 不会占用后面的对象
+        Self::reserve_net_ids(self.entries.iter().map(|entry| entry.net_id).max());
+        let mut restored = 0;
+        for entry in self.entries.iter() {
+            if Self::restore_entry(entry, 0) != 0 {
+                restored += 1;
+            }
+        }
+        restored
+    }
+
+    // 恢复一个对象并交给 owner (0 表示没有所有者), 返回 net_id, 失败时为 0
+    pub(crate) fn restore_entry(entry: &WorldSnapshotEntry, owner: u64) -> u32 {
+        if entry.scene_id != 0 {
+            let net_id = Self::restore_scene_object(entry, owner);
+            if net_id != 0 {
+                return net_id;
+            }
+        }
+        let mut identity = if entry.scene_id != 0 {
+            NetworkIdentity::new_with_scene_id(entry.scene_id)
+        } else {
+            NetworkIdentity::new_with_asset_id(entry.asset_id)
+        };
+        identity.server_only = entry.server_only;
+        Self::apply(entry, &mut identity);
+        identity.set_net_id(entry.net_id);
+        if owner != 0 {
+            identity.set_connection_to_client(owner);
+        }
+        NetworkServer::spawn_with_net_id(identity)
+    }
+
+    // 新对象的 net_id 不能与恢复的对象冲突
+    pub(crate) fn reserve_net_ids(max_net_id: Option<u32>) {
+        if let Some(max_net_id) = max_net_id {
+            if NetIdAllocator::default_next() <= max_net_id {
+                NetIdAllocator::set_default_next(max_net_id + 1);
+            }
+        }
+    }
+
+    fn restore_scene_object(entry: &WorldSnapshotEntry, owner: u64) -> u32 {
+        let mut net_id = 0;
+        NetworkServerStatic::for_each_spawned(|identity| {
+            if identity.scene_id == entry.scene_id {
+                net_id = identity.net_id();
+            }
+        });
+        if net_id == 0 {
+            return 0;
+        }
+        match NetworkServerStatic::spawned_network_identities().get_mut(&net_id) {
+            Some(mut identity) => {
+                Self::apply(entry, &mut identity);
+                if owner != 0 {
+                    identity.set_connection_to_client(owner);
+                }
+            }
+            None => return 0,
+        }
+        // 场景对象已经生成, 通知新的所有者
+        if owner != 0 {
+            NetworkServer::send_change_owner_message_for_net_id(owner, net_id);
+        }
+        net_id
+    }
+
+    fn apply(entry: &WorldSnapshotEntry, identity: &mut NetworkIdentity) {
+        let mut game_object = identity.game_object().clone();
+        let position = Vector3::from(entry.position);
+        let rotation = Quaternion::from(entry.rotation);
+        let scale = Vector3::from(entry.scale);
+        game_object.transform.position = position;
+        game_object.transform.rotation = rotation;
+        game_object.transform.scale = scale;
+        game_object.transform.local_position = position;
+        game_object.transform.local_rotation = rotation;
+        game_object.transform.local_scale = scale;
+        identity.set_game_object(game_object);
+        let mut reader = NetworkReader::new_with_array_segment(&entry.state);
+        if !identity.deserialize_initial_state(&mut reader) {
+            log_warn!(
+                "WorldSnapshot.Restore: failed to apply state for netId {}",
+                entry.net_id
+            );
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        NetworkWriterPool::get_return(|writer| {
+            writer.write_array_segment_all(Self::MAGIC);
+            writer.write_ushort(self.version);
+            writer.compress_var_uint(self.entries.len() as u32);
+            for entry in self.entries.iter() {
+                entry.write(writer);
+            }
+            bytes = writer.to_bytes();
+        });
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        if !bytes.starts_with(Self::MAGIC) {
+            return Err("not a world snapshot".to_string());
+        }
+        let mut reader = NetworkReader::new_with_array_segment(&bytes[Self::MAGIC.len()..]);
+        let version = reader.read_ushort();
+        if version != Self::VERSION {
+            return Err(format!("unsupported world snapshot version {}", version));
+        }
+        let count = reader.decompress_var_uint();
+        let mut entries = Vec::new();
+        for _ in 0..count {
+            if reader.remaining() == 0 {
+                break;
+            }
+            entries.push(WorldSnapshotEntry::read(&mut reader));
+        }
+        // 越界时 NetworkReader 只返回默认值, 重新序列化一遍比较长度
+        let snapshot = Self { version, entries };
+        if snapshot.entries.len() != count as usize
+            || reader.remaining() != 0
+            || snapshot.to_bytes().len() != bytes.len()
+        {
+            return Err("truncated world snapshot".to_string());
+        }
+        Ok(snapshot)
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|e| e.to_string())
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let snapshot: Self = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        if snapshot.version != Self::VERSION {
+            return Err(format!(
+                "unsupported world snapshot version {}",
+                snapshot.version
+            ));
+        }
+        Ok(snapshot)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P, format: WorldSnapshotFormat) -> Result<(), String> {
+        let bytes = match format {
+            WorldSnapshotFormat::Binary => self.to_bytes(),
+            WorldSnapshotFormat::Json => self.to_json()?.into_bytes(),
+        };
+        std::fs::write(path, bytes).map_err(|e| e.to_string())
+    }
+
+    // 根据文件头判断格式
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let bytes = std::fs::read(path).map_err(|e| e.to_string())?;
+        if bytes.starts_with(Self::MAGIC) {
+            return Self::from_bytes(&bytes);
+        }
+        let json = String::from_utf8(bytes).map_err(|e| e.to_string())?;
+        Self::from_json(&json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::transports::memory::memory_transport::test_util::*;
+
+    fn snapshot() -> WorldSnapshot {
+        WorldSnapshot {
+            version: WorldSnapshot::VERSION,
+            entries: vec![
+                WorldSnapshotEntry {
+                    net_id: 3,
+                    asset_id: 7,
+                    scene_id: 0,
+                    server_only: false,
+                    position: [1.0, 2.0, 3.0],
+                    rotation: [0.0, 0.0, 0.0, 1.0],
+                    scale: [1.0, 1.0, 1.0],
+                    state: vec![1, 2, 3],
+                },
+                WorldSnapshotEntry {
+                    net_id: 9,
+                    asset_id: 0,
+                    scene_id: 42,
+                    server_only: true,
+                    position: [0.0; 3],
+                    rotation: [0.0, 0.0, 0.0, 1.0],
+                    scale: [2.0; 3],
+                    state: Vec::new(),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_binary_round_trip() {
+        let snapshot = snapshot();
+        let bytes = snapshot.to_bytes();
+        assert_eq!(WorldSnapshot::from_bytes(&bytes), Ok(snapshot));
+        assert!(WorldSnapshot::from_bytes(&bytes[..bytes.len() - 2]).is_err());
+        assert!(WorldSnapshot::from_bytes(b"{}").is_err());
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let snapshot = snapshot();
+        let json = snapshot.to_json().unwrap();
+        assert_eq!(WorldSnapshot::from_json(&json), Ok(snapshot));
+    }
+
+    #[test]
+    fn test_world_snapshot_capture_and_restore() {
+        with_server(|| {
+            for (net_id, asset_id) in [(200, 7), (201, 8)] {
+                let mut identity = NetworkIdentity::new_with_asset_id(asset_id);
+                identity.set_net_id(net_id);
+                let mut game_object = identity.game_object().clone();
+                game_object.transform.local_position = Vector3::new(net_id as f32, 1.0, 2.0);
+                identity.set_game_object(game_object);
+                NetworkServerStatic::add_spawned_network_identity(identity);
+            }
+            let snapshot = WorldSnapshot::capture();
+            assert_eq!(snapshot.entries.len(), 2);
+
+            let path = std::env::temp_dir().join("mirror_world_snapshot_test.json");
+            snapshot.save(&path, WorldSnapshotFormat::Json).unwrap();
+            let loaded = WorldSnapshot::load(&path).unwrap();
+            std::fs::remove_file(&path).unwrap();
+            assert_eq!(loaded, snapshot);
+
+            // 模拟重启
+            for net_id in [200, 201] {
+                NetworkServerStatic::remove_spawned_network_identity(&net_id);
+            }
+            assert_eq!(loaded.restore(), 2);
+            let mut restored = Vec::new();
+            NetworkServerStatic::for_each_spawned(|identity| {
+                restored.push((
+                    identity.net_id(),
+                    identity.asset_id,
+                    identity.game_object().transform.local_position.x,
+                ));
+            });
+            restored.sort_by_key(|(net_id, _, _)| *net_id);
+            assert_eq!(restored, vec![(200, 7, 200.0), (201, 8, 201.0)]);
+            assert!(NetworkIdentity::get_static_next_network_id() > 201);
+
+            // 与已有对象冲突的条目换用新的 net_id, 不能占用后面条目的 net_id
+            let next = NetIdAllocator::default_next();
+            let mut identity = NetworkIdentity::new_with_asset_id(9);
+            identity.set_net_id(300);
+            NetworkServerStatic::add_spawned_network_identity(identity);
+            let mut colliding = self::snapshot();
+            colliding.entries[0].net_id = 300;
+            colliding.entries[1].net_id = next;
+            colliding.entries[1].scene_id = 0;
+            assert_eq!(colliding.restore(), 2);
+            let spawned = NetworkServerStatic::spawned_network_identities();
+            assert_eq!(spawned.get(&next).unwrap().asset_id, 0);
+            assert_eq!(spawned.get(&301).unwrap().asset_id, 7);
+        });
+    }
+}
@@ -0,0 +1,418 @@
+use crate::mirror::core::network_manager::NetworkManagerStatic;
+use crate::mirror::core::transport::{
+    Transport, TransportCallback, TransportCallbackType, TransportChannel, TransportError,
+    TransportFunc, TransportTrait,
+};
+use crate::{log_error, log_warn};
+use rusty_enet::{Event, Host, HostSettings, Packet, PeerID};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::{SocketAddr, UdpSocket};
+
+// ENet 通道的可靠性, 决定收发时对应的 TransportChannel
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnetChannelKind {
+    Reliable,
+    // ENet 的不可靠通道是有序的, 过期的包被丢弃
+    Unreliable,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct EnetTransportConfig {
+    pub port: u16,
+    pub dual_mode: bool,
+    pub peer_limit: usize,
+    // 按 ENet 通道编号排列; 发送时使用第一个可靠性匹配的通道, 接收时按通道的可靠性转换
+    pub channels: Vec<EnetChannelKind>,
+    // 带宽限制 (字节/秒), 0 表示不限制, ENet 按这个值调整发送窗口
+    pub incoming_bandwidth: u32,
+    pub outgoing_bandwidth: u32,
+    // 每个 tick 最多处理的事件数量, 0 表示不限制
+    pub max_receive_per_tick: usize,
+}
+
+impl Default for EnetTransportConfig {
+    fn default() -> Self {
+        Self {
+            port: 7777,
+            dual_mode: false,
+            peer_limit: 4096,
+            channels: vec![EnetChannelKind::Reliable, EnetChannelKind::Unreliable],
+            incoming_bandwidth: 0,
+            outgoing_bandwidth: 0,
+            max_receive_per_tick: 4096,
+        }
+    }
+}
+
+impl EnetTransportConfig {
+    // TransportChannel -> ENet 通道编号, 没有匹配的通道时使用 0
+    pub fn enet_channel(&self, channel: TransportChannel) -> u8 {
+        let kind = match channel {
+            TransportChannel::Reliable => EnetChannelKind::Reliable,
+            TransportChannel::Unreliable => EnetChannelKind::Unreliable,
+        };
+        self.channels
+            .iter()
+            .position(|channel_kind| *channel_kind == kind)
+            .unwrap_or(0) as u8
+    }
+
+    // ENet 通道编号 -> TransportChannel, 未配置的通道视为可靠
+    pub fn transport_channel(&self, channel_id: u8) -> TransportChannel {
+        match self.channels.get(channel_id as usize) {
+            Some(EnetChannelKind::Unreliable) => TransportChannel::Unreliable,
+            _ => TransportChannel::Reliable,
+        }
+    }
+}
+
+// 兼容 ENet 协议的 UDP Transport, 用于从 ENet 迁移的项目, 或者需要与 kcp2k 不同的拥塞控制时
+// 在主循环中接收 (server_early_update) 和发送 (server_late_update), 不使用接收线程
+// ENet 的 peer id 会被重用, 连接 id 单独分配, 从 1 开始递增
+pub struct EnetTransport {
+    pub transport: Transport,
+    config: EnetTransportConfig,
+    host: Option<Host<UdpSocket>>,
+    local_endpoint: Option<SocketAddr>,
+    // peer id <-> 连接 id
+    peer_connections: HashMap<usize, u64>,
+    connection_peers: HashMap<u64, usize>,
+    next_connection_id: u64,
+}
+
+impl EnetTransport {
+    #[allow(dead_code)]
+    pub const SCHEME: &'static str = "enet";
+    // ENET_HOST_DEFAULT_MAXIMUM_PACKET_SIZE
+    const MAX_PACKET_SIZE: usize = 32 * 1024 * 1024;
+    // 不可靠的包超过 MTU 时分片, 任何一片丢失整个包都会丢失
+    const MTU: usize = 1200;
+
+    pub fn new(config: EnetTransportConfig) -> Self {
+        Self {
+            transport: Transport::default(),
+            config,
+            host: None,
+            local_endpoint: None,
+            peer_connections: HashMap::new(),
+            connection_peers: HashMap::new(),
+            next_connection_id: 1,
+        }
+    }
+
+    pub fn config(&self) -> &EnetTransportConfig {
+        &self.config
+    }
+
+    fn callback(&self, tcb: TransportCallback) {
+        match self.transport.transport_cb_fn {
+            None => {
+                log_error!("EnetTransport callback error: transport_cb_fn is None");
+            }
+            Some(transport_cb_fn) => transport_cb_fn(tcb),
+        }
+    }
+
+    // 先收集事件再回调, 回调中可以再次访问 Transport
+    fn service(&mut self) -> Vec<(TransportCallbackType, usize, Vec<u8>, u8)> {
+        let mut events = Vec::new();
+        let host = match self.host.as_mut() {
+            Some(host) => host,
+            None => return events,
+        };
+        while self.config.max_receive_per_tick == 0
+            || events.len() < self.config.max_receive_per_tick
+        {
+            match host.service() {
+                Ok(Some(Event::Connect { peer, .. })) => {
+                    events.push((
+                        TransportCallbackType::OnServerConnected,
+                        peer.id().0,
+                        Vec::new(),
+                        0,
+                    ));
+                }
+                Ok(Some(Event::Disconnect { peer, .. })) => {
+                    events.push((
+                        TransportCallbackType::OnServerDisconnected,
+                        peer.id().0,
+                        Vec::new(),
+                        0,
+                    ));
+                }
+                Ok(Some(Event::Receive {
+                    peer,
+                    channel_id,
+                    packet,
+                })) => {
+                    events.push((
+                        TransportCallbackType::OnServerDataReceived,
+                        peer.id().0,
+                        packet.data().to_vec(),
+                        channel_id,
+                    ));
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    log_error!("EnetTransport service error: {:?}", e);
+                    break;
+                }
+            }
+        }
+        events
+    }
+}
+
+impl TransportTrait for EnetTransport {
+    // 使用默认配置, 其他配置使用 Transport::set_active_transport(Box::new(EnetTransport::new(config)))
+    fn awake()
+    where
+        Self: Sized,
+    {
+        Transport::set_active_transport(Box::new(Self::new(EnetTransportConfig::default())));
+    }
+
+    fn available(&self) -> bool {
+        true
+    }
+
+    fn server_active(&self) -> bool {
+        self.host.is_some()
+    }
+
+    fn server_start(&mut self) {
+        if self.config.channels.is_empty() || self.config.channels.len() > u8::MAX as usize {
+            log_error!(
+                "EnetTransport invalid channel count: {}",
+                self.config.channels.len()
+            );
+            return;
+        }
+        let network_address = NetworkManagerStatic::network_manager_singleton()
+            .network_address()
+            .to_string();
+        let endpoint = match Transport::listen_endpoint(
+            &network_address,
+            self.config.port,
+            self.config.dual_mode,
+        ) {
+            Some(endpoint) => endpoint,
+            None => {
+                log_error!(
+                    "EnetTransport failed to resolve listen address: {}",
+                    network_address
+                );
+                return;
+            }
+        };
+        let socket = match UdpSocket::bind(endpoint) {
+            Ok(socket) => socket,
+            Err(e) => {
+                log_error!("EnetTransport failed to bind {}: {}", endpoint, e);
+                return;
+            }
+        };
+        let local_endpoint = socket.local_addr().ok();
+        let settings = HostSettings {
+            peer_limit: self.config.peer_limit,
+            channel_limit: self.config.channels.len(),
+            incoming_bandwidth_limit: (self.config.incoming_bandwidth > 0)
+                .then_some(self.config.incoming_bandwidth),
+            outgoing_bandwidth_limit: (self.config.outgoing_bandwidth > 0)
+                .then_some(self.config.outgoing_bandwidth),
+            ..HostSettings::default()
+        };
+        match Host::new(socket, settings) {
+            Ok(host) => {
+                self.host = Some(host);
+                self.local_endpoint = local_endpoint;
+            }
+            Err(e) => {
+                log_error!("EnetTransport failed to create host: {:?}", e);
+            }
+        }
+    }
+
+    fn server_send(&mut self, connection_id: u64, data: Vec<u8>, channel: TransportChannel) {
+        let channel_id = self.config.enet_channel(channel);
+        let packet = match channel {
+            TransportChannel::Reliable => Packet::reliable(data.as_slice()),
+            TransportChannel::Unreliable => Packet::unreliable(data.as_slice()),
+        };
+        let sent = match (
+            self.host.as_mut(),
+            self.connection_peers.get(&connection_id),
+        ) {
+            (Some(host), Some(peer_id)) => host
+                .peer_mut(PeerID(*peer_id))
+                .send(channel_id, &packet)
+                .is_ok(),
+            _ => false,
+        };
+        let tcb = match sent {
+            true => TransportCallback {
+                r#type: TransportCallbackType::OnServerDataSent,
+                conn_id: connection_id,
+                data,
+                channel,
+                ..TransportCallback::default()
+            },
+            false => TransportCallback {
+                r#type: TransportCallbackType::OnServerError,
+                conn_id: connection_id,
+                error: TransportError::SendError,
+                ..TransportCallback::default()
+            },
+        };
+        self.callback(tcb);
+    }
+
+    // ENet 确认断开后在 server_early_update 中回调 OnServerDisconnected
+    fn server_disconnect(&mut self, connection_id: u64) {
+        if let (Some(host), Some(peer_id)) = (
+            self.host.as_mut(),
+            self.connection_peers.get(&connection_id),
+        ) {
+            host.peer_mut(PeerID(*peer_id)).disconnect(0);
+        }
+    }
+
+    fn server_get_client_address(&self, connection_id: u64) -> String {
+        let address = match (
+            self.host.as_ref(),
+            self.connection_peers.get(&connection_id),
+        ) {
+            (Some(host), Some(peer_id)) => host.peer(PeerID(*peer_id)).address(),
+            _ => None,
+        };
+        match address {
+            Some(address) => Transport::canonical_address(&address.to_string()),
+            None => "".to_string(),
+        }
+    }
+
+    fn server_local_endpoint(&self) -> Option<SocketAddr> {
+        self.local_endpoint
+    }
+
+    fn server_early_update(&mut self) {
+        for (r#type, peer_id, data, channel_id) in self.service() {
+            let connection_id = match r#type {
+                TransportCallbackType::OnServerConnected => {
+                    let connection_id = self.next_connection_id;
+                    self.next_connection_id += 1;
+                    self.peer_connections.insert(peer_id, connection_id);
+                    self.connection_peers.insert(connection_id, peer_id);
+                    connection_id
+                }
+                TransportCallbackType::OnServerDisconnected => {
+                    match self.peer_connections.remove(&peer_id) {
+                        Some(connection_id) => {
+                            self.connection_peers.remove(&connection_id);
+                            connection_id
+                        }
+                        None => continue,
+                    }
+                }
+                _ => match self.peer_connections.get(&peer_id) {
+                    Some(connection_id) => *connection_id,
+                    None => {
+                        log_warn!("EnetTransport received data from unknown peer {}", peer_id);
+                        continue;
+                    }
+                },
+            };
+            self.callback(TransportCallback {
+                r#type,
+                conn_id: connection_id,
+                data,
+                channel: self.config.transport_channel(channel_id),
+                ..TransportCallback::default()
+            });
+        }
+    }
+
+    fn server_late_update(&mut self) {
+        if let Some(host) = self.host.as_mut() {
+            host.flush();
+        }
+    }
+
+    fn server_stop(&mut self) {
+        if let Some(host) = self.host.as_mut() {
+            for peer_id in self.connection_peers.values() {
+                host.peer_mut(PeerID(*peer_id)).disconnect_now(0);
+            }
+        }
+        // 立即断开不会产生事件, 在这里回调
+        let connection_ids: Vec<u64> = self.connection_peers.keys().copied().collect();
+        self.peer_connections.clear();
+        self.connection_peers.clear();
+        for connection_id in connection_ids {
+            self.callback(TransportCallback {
+                r#type: TransportCallbackType::OnServerDisconnected,
+                conn_id: connection_id,
+                ..TransportCallback::default()
+            });
+        }
+        // 释放端口, 之后可以再次 server_start
+        self.host = None;
+        self.local_endpoint = None;
+    }
+
+    fn transport_cb_fn(&self) -> Option<TransportFunc> {
+        self.transport.transport_cb_fn
+    }
+
+    fn set_transport_cb_fn(&mut self, func: TransportFunc) {
+        self.transport.transport_cb_fn.replace(func);
+    }
+
+    fn get_max_packet_size(&self, channel: TransportChannel) -> usize {
+        match channel {
+            TransportChannel::Reliable => Self::MAX_PACKET_SIZE,
+            TransportChannel::Unreliable => Self::MTU,
+        }
+    }
+
+    fn get_batcher_threshold(&self, _channel: TransportChannel) -> usize {
+        Self::MTU
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_enet_channel_mapping() {
+        let config = EnetTransportConfig::default();
+        assert_eq!(config.enet_channel(TransportChannel::Reliable), 0);
+        assert_eq!(config.enet_channel(TransportChannel::Unreliable), 1);
+        assert_eq!(config.transport_channel(0), TransportChannel::Reliable);
+        assert_eq!(config.transport_channel(1), TransportChannel::Unreliable);
+
+        // 多个通道: 发送使用第一个匹配的通道, 接收按每个通道的可靠性
+        let config = EnetTransportConfig {
+            channels: vec![
+                EnetChannelKind::Unreliable,
+                EnetChannelKind::Reliable,
+                EnetChannelKind::Unreliable,
+            ],
+            ..EnetTransportConfig::default()
+        };
+        assert_eq!(config.enet_channel(TransportChannel::Reliable), 1);
+        assert_eq!(config.enet_channel(TransportChannel::Unreliable), 0);
+        assert_eq!(config.transport_channel(2), TransportChannel::Unreliable);
+        assert_eq!(config.transport_channel(9), TransportChannel::Reliable);
+
+        // 没有可靠通道时退回到通道 0
+        let config = EnetTransportConfig {
+            channels: vec![EnetChannelKind::Unreliable],
+            ..EnetTransportConfig::default()
+        };
+        assert_eq!(config.enet_channel(TransportChannel::Reliable), 0);
+    }
+}
@@ -0,0 +1 @@
+pub mod enet_transport;
\ No newline at end of file
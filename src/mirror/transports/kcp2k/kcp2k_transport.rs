@@ -1,8 +1,9 @@
 use crate::mirror::core::backend_data::BackendDataStatic;
+use crate::mirror::core::network_context::{ContextLocal, NetworkContext};
 use crate::mirror::core::network_manager::NetworkManagerStatic;
 use crate::mirror::core::transport::{
     Transport, TransportCallback, TransportCallbackType, TransportChannel, TransportError,
-    TransportFunc, TransportTrait,
+    TransportFunc, TransportReceiveQueue, TransportTrait,
 };
 use crate::log_error;
 use bytes::Bytes;
@@ -13,10 +14,27 @@ use kcp2k_rust::kcp2k_channel::Kcp2KChannel;
 use kcp2k_rust::kcp2k_config::Kcp2KConfig;
 use kcp2k_rust::kcp2k_connection::Kcp2KConnection;
 use kcp2k_rust::kcp2k_peer::Kcp2KPeer;
+use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
 use std::process::exit;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+// Kcp2kTransport 静态变量
+lazy_static! {
+    // 接收线程运行时, kcp2k_cb 把事件放进队列, 由 server_early_update 在主循环处理
+    // join 接收线程之后才清空, 不为 None 时事件不能直接分发
+    static ref RECEIVE_QUEUE: ContextLocal<RwLock<Option<Arc<TransportReceiveQueue>>>> =
+        ContextLocal::new(|| RwLock::new(None));
+    static ref RECEIVE_THREAD_RUNNING: ContextLocal<AtomicBool> =
+        ContextLocal::new(|| AtomicBool::new(false));
+}
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
 pub struct Kcp2kTransportConfig {
     pub port: u16,
     pub dual_mode: bool,
@@ -30,6 +48,11 @@ pub struct Kcp2kTransportConfig {
     pub send_win_size: u16,
     pub max_retransmits: u32,
     pub maximize_socket_buffer: bool,
+    // 在独立线程中接收数据, 主循环只处理队列中的事件, 默认关闭
+    pub receive_thread: bool,
+    pub receive_queue_capacity: usize,
+    // 每个 tick 最多处理的接收事件数量, 0 表示不限制
+    pub max_receive_per_tick: usize,
 }
 
 impl Default for Kcp2kTransportConfig {
@@ -47,6 +70,9 @@ impl Default for Kcp2kTransportConfig {
             send_win_size: 4096,
             max_retransmits: 40,
             maximize_socket_buffer: true,
+            receive_thread: false,
+            receive_queue_capacity: 8192,
+            max_receive_per_tick: 4096,
         }
     }
 }
@@ -56,12 +82,75 @@ pub struct Kcp2kTransport {
     pub server_active: bool,
     pub config: Kcp2KConfig,
     pub port: u16,
-    pub kcp_serv: Option<Kcp2K>,
+    pub kcp_serv: Option<Arc<Kcp2K>>,
+    pub local_endpoint: Option<SocketAddr>,
+    pub receive_thread: bool,
+    pub receive_queue_capacity: usize,
+    pub max_receive_per_tick: usize,
+    receive_handle: Option<JoinHandle<()>>,
 }
 
 impl Kcp2kTransport {
     #[allow(dead_code)]
     pub const SCHEME: &'static str = "kcp2k";
+    const RECEIVE_INTERVAL: Duration = Duration::from_millis(1);
+
+    // 接收线程没有运行时为 None, 用于查看积压情况
+    pub fn receive_queue() -> Option<Arc<TransportReceiveQueue>> {
+        match RECEIVE_QUEUE.read() {
+            Ok(queue) => queue.clone(),
+            Err(_) => None,
+        }
+    }
+
+    fn start_receive_thread(&mut self, kcp_serv: Arc<Kcp2K>) {
+        let queue = Arc::new(TransportReceiveQueue::new(self.receive_queue_capacity));
+        match RECEIVE_QUEUE.write() {
+            Ok(mut receive_queue) => *receive_queue = Some(queue),
+            Err(e) => {
+                log_error!("Kcp2kTransport failed to write RECEIVE_QUEUE: {:?}", e);
+                return;
+            }
+        }
+        RECEIVE_THREAD_RUNNING.store(true, Ordering::Relaxed);
+        // 接收回调写入当前上下文的 RECEIVE_QUEUE
+        let context = NetworkContext::current();
+        let spawned = thread::Builder::new()
+            .name("kcp2k-receive".to_string())
+            .spawn(move || {
+                NetworkContext::enter(context, || {
+                    while RECEIVE_THREAD_RUNNING.load(Ordering::Relaxed) {
+                        kcp_serv.tick_incoming();
+                        thread::sleep(Self::RECEIVE_INTERVAL);
+                    }
+                })
+            });
+        match spawned {
+            Ok(handle) => self.receive_handle = Some(handle),
+            Err(e) => {
+                // 退回到在主循环中接收
+                log_error!("Kcp2kTransport failed to start receive thread: {}", e);
+                RECEIVE_THREAD_RUNNING.store(false, Ordering::Relaxed);
+                if let Ok(mut receive_queue) = RECEIVE_QUEUE.write() {
+                    *receive_queue = None;
+                }
+            }
+        }
+    }
+
+    fn stop_receive_thread(&mut self) {
+        RECEIVE_THREAD_RUNNING.store(false, Ordering::Relaxed);
+        if let Some(queue) = Self::receive_queue() {
+            queue.close();
+        }
+        if let Some(handle) = self.receive_handle.take() {
+            let _ = handle.join();
+        }
+        if let Ok(mut receive_queue) = RECEIVE_QUEUE.write() {
+            *receive_queue = None;
+        }
+    }
+
     pub fn from_kcp2k_channel(kcp2k_channel: Kcp2KChannel) -> TransportChannel {
         match kcp2k_channel {
             Kcp2KChannel::Unreliable => TransportChannel::Unreliable,
@@ -107,6 +196,11 @@ impl Kcp2kTransport {
             error: Self::from_kcp2k_error_code(cb.error_code),
             ..TransportCallback::default()
         };
+        // 接收线程中不能访问 active_transport, 有队列时只放进队列, 队列关闭后 (正在停止) 丢弃
+        if let Some(queue) = Self::receive_queue() {
+            queue.push(tcb);
+            return;
+        }
         match Transport::active_transport() {
             None => {
                 log_error!("Kcp2kTransport kcp2k_cb error: active_transport is None");
@@ -149,6 +243,11 @@ impl TransportTrait for Kcp2kTransport {
             config,
             port: kcp2k_transport_config.port,
             kcp_serv: None,
+            local_endpoint: None,
+            receive_thread: kcp2k_transport_config.receive_thread,
+            receive_queue_capacity: kcp2k_transport_config.receive_queue_capacity,
+            max_receive_per_tick: kcp2k_transport_config.max_receive_per_tick,
+            receive_handle: None,
         };
         Transport::set_active_transport(Box::new(kcp2k_transport));
     }
@@ -162,23 +261,33 @@ impl TransportTrait for Kcp2kTransport {
     }
 
     fn server_start(&mut self) {
-        let mut network_address = NetworkManagerStatic::network_manager_singleton()
+        let network_address = NetworkManagerStatic::network_manager_singleton()
             .network_address()
             .to_string();
-        if network_address == "localhost" {
-            network_address = "0.0.0.0".to_string()
-        }
-        match Kcp2K::new_server(
-            self.config,
-            format!("{}:{}", network_address, self.port),
-            Self::kcp2k_cb,
-        ) {
+        // IPv6 地址需要方括号, 直接拼接 "{}:{}" 会得到无法解析的地址
+        let endpoint =
+            match Transport::listen_endpoint(&network_address, self.port, self.config.dual_mode) {
+                Some(endpoint) => endpoint,
+                None => {
+                    log_error!(
+                        "Kcp2kTransport failed to resolve listen address: {}",
+                        network_address
+                    );
+                    exit(1)
+                }
+            };
+        match Kcp2K::new_server(self.config, endpoint.to_string(), Self::kcp2k_cb) {
             Ok(server) => {
+                let server = Arc::new(server);
+                if self.receive_thread {
+                    self.start_receive_thread(server.clone());
+                }
                 self.kcp_serv = Some(server);
                 self.server_active = true;
+                self.local_endpoint = Some(endpoint);
             }
             Err(err) => {
-                log_error!(format!("Kcp2kTransport awake error: {:?}", err));
+                log_error!("Kcp2kTransport awake error: {:?}", err);
                 exit(1)
             }
         }
@@ -221,22 +330,54 @@ impl TransportTrait for Kcp2kTransport {
     }
 
     fn server_get_client_address(&self, connection_id: u64) -> String {
-        self.kcp_serv
-            .as_ref()
-            .unwrap()
-            .get_connection_address(connection_id)
+        Transport::canonical_address(
+            &self
+                .kcp_serv
+                .as_ref()
+                .unwrap()
+                .get_connection_address(connection_id),
+        )
+    }
+
+    fn server_local_endpoint(&self) -> Option<SocketAddr> {
+        self.local_endpoint
     }
 
     fn server_early_update(&mut self) {
-        self.kcp_serv.as_ref().unwrap().tick_incoming();
+        let queue = match Self::receive_queue() {
+            Some(queue) => queue,
+            None => {
+                if let Some(server) = self.kcp_serv.as_ref() {
+                    server.tick_incoming();
+                }
+                return;
+            }
+        };
+        match self.transport.transport_cb_fn {
+            None => {
+                log_error!("Kcp2kTransport server_early_update error: transport_cb_fn is None");
+            }
+            Some(transport_cb_fn) => {
+                queue.drain(self.max_receive_per_tick, transport_cb_fn);
+            }
+        }
     }
 
     fn server_late_update(&mut self) {
-        self.kcp_serv.as_ref().unwrap().tick_outgoing();
+        // server_stop 之后没有 kcp_serv
+        if let Some(server) = self.kcp_serv.as_ref() {
+            server.tick_outgoing();
+        }
     }
 
     fn server_stop(&mut self) {
-        let _ = self.kcp_serv.as_ref().unwrap().stop();
+        self.stop_receive_thread();
+        // 释放端口, 之后可以再次 server_start
+        if let Some(server) = self.kcp_serv.take() {
+            let _ = server.stop();
+        }
+        self.server_active = false;
+        self.local_endpoint = None;
     }
 
     fn transport_cb_fn(&self) -> Option<TransportFunc> {
@@ -263,3 +404,80 @@ impl TransportTrait for Kcp2kTransport {
         Kcp2KPeer::unreliable_max_message_size(self.config.mtu as u32)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::transports::memory::memory_transport::test_util::with_isolated_context;
+    use std::sync::Mutex;
+
+    static EVENTS: Mutex<Vec<(TransportCallbackType, Vec<u8>)>> = Mutex::new(Vec::new());
+
+    fn on_event(tcb: TransportCallback) {
+        EVENTS.lock().unwrap().push((tcb.r#type, tcb.data));
+    }
+
+    fn on_client_event(_: &Kcp2KConnection, _: Callback) {}
+
+    #[test]
+    fn test_receive_thread() {
+        with_isolated_context(|| {
+            let config = Kcp2KConfig::default();
+            // 由系统分配空闲端口
+            let port = std::net::UdpSocket::bind("127.0.0.1:0")
+                .unwrap()
+                .local_addr()
+                .unwrap()
+                .port();
+            let address = format!("127.0.0.1:{}", port);
+            let mut transport = Kcp2kTransport {
+                transport: Transport::default(),
+                server_active: false,
+                config,
+                port,
+                kcp_serv: None,
+                local_endpoint: None,
+                receive_thread: true,
+                receive_queue_capacity: 64,
+                max_receive_per_tick: 0,
+                receive_handle: None,
+            };
+            transport.set_transport_cb_fn(on_event);
+            let server = Arc::new(
+                Kcp2K::new_server(config, address.clone(), Kcp2kTransport::kcp2k_cb).unwrap(),
+            );
+            transport.start_receive_thread(server.clone());
+            transport.kcp_serv = Some(server);
+            assert!(Kcp2kTransport::receive_queue().is_some());
+
+            // 接收线程把事件放进队列, server_early_update 在当前线程处理
+            let client = Kcp2K::new_client(config, address, on_client_event).unwrap();
+            let connected = (TransportCallbackType::OnServerConnected, Vec::new());
+            let received = (TransportCallbackType::OnServerDataReceived, vec![1, 2, 3]);
+            let mut sent = false;
+            for _ in 0..500 {
+                client.tick_incoming();
+                client.tick_outgoing();
+                transport.server_early_update();
+                transport.server_late_update();
+                let events = EVENTS.lock().unwrap().clone();
+                if events.contains(&received) {
+                    break;
+                }
+                if !sent && events.contains(&connected) {
+                    let data = Bytes::from_static(&[1, 2, 3]);
+                    client.c_send(data, Kcp2KChannel::Reliable).unwrap();
+                    sent = true;
+                }
+                thread::sleep(Duration::from_millis(2));
+            }
+            let events = EVENTS.lock().unwrap().clone();
+            assert_eq!(events.first(), Some(&connected));
+            assert!(events.contains(&received));
+
+            transport.server_stop();
+            assert!(Kcp2kTransport::receive_queue().is_none());
+            let _ = client.stop();
+        });
+    }
+}
@@ -0,0 +1,703 @@
+use crate::log_error;
+use crate::mirror::core::batching::batcher::Batcher;
+use crate::mirror::core::batching::un_batcher::UnBatcher;
+use crate::mirror::core::messages::NetworkMessageTrait;
+use crate::mirror::core::network_context::ContextLocal;
+use crate::mirror::core::network_loop::NetworkLoop;
+use crate::mirror::core::network_time::NetworkTime;
+use crate::mirror::core::network_writer_pool::NetworkWriterPool;
+use crate::mirror::core::transport::{
+    Transport, TransportCallback, TransportCallbackType, TransportChannel, TransportError,
+    TransportFunc, TransportTrait,
+};
+use dashmap::{DashMap, DashSet};
+use lazy_static::lazy_static;
+use std::collections::VecDeque;
+use std::sync::RwLock;
+
+type ClientIncoming = VecDeque<(Vec<u8>, TransportChannel)>;
+
+// MemoryTransport 静态变量
+lazy_static! {
+    // 客户端 -> 服务器 的事件, 在 server_early_update 中处理
+    static ref SERVER_INCOMING: ContextLocal<RwLock<VecDeque<TransportCallback>>> =
+        ContextLocal::new(|| RwLock::new(VecDeque::new()));
+    // 服务器 -> 客户端 的数据, 服务器断开后客户端仍然可以读取剩余的数据
+    static ref CLIENT_INCOMING: ContextLocal<DashMap<u64, ClientIncoming>> =
+        ContextLocal::new(DashMap::new);
+    // 仍然连接的客户端
+    static ref CLIENT_CONNECTED: ContextLocal<DashSet<u64>> = ContextLocal::new(DashSet::new);
+    // server_send 的连接顺序, 连续发给同一个连接只记录一次
+    static ref SEND_ORDER: ContextLocal<RwLock<Vec<u64>>> = ContextLocal::new(|| RwLock::new(Vec::new()));
+}
+
+// 进程内的 Transport, 不使用 socket, 用于确定性的集成测试
+// 客户端的操作通过 MemoryTransport::client_* 静态方法完成
+// 也可以作为 NetworkClient 的 Transport, 连接同一个上下文中的服务器, 地址为 memory://<connection_id>
+#[derive(Default)]
+pub struct MemoryTransport {
+    pub transport: Transport,
+    pub server_active: bool,
+    // 客户端模式的连接
+    client_id: Option<u64>,
+    // 下一次 client_early_update 回调 OnClientConnected
+    client_connecting: bool,
+}
+
+impl MemoryTransport {
+    pub const MAX_PACKET_SIZE: usize = 1200;
+
+    fn push_server_incoming(tcb: TransportCallback) {
+        match SERVER_INCOMING.write() {
+            Ok(mut incoming) => {
+                incoming.push_back(tcb);
+                NetworkLoop::wake();
+            }
+            Err(e) => {
+                log_error!("MemoryTransport failed to get incoming queue: {:?}", e);
+            }
+        }
+    }
+
+    // 客户端连接
+    pub fn client_connect(connection_id: u64) {
+        CLIENT_INCOMING.insert(connection_id, VecDeque::new());
+        CLIENT_CONNECTED.insert(connection_id);
+        Self::push_server_incoming(TransportCallback {
+            r#type: TransportCallbackType::OnServerConnected,
+            conn_id: connection_id,
+            ..TransportCallback::default()
+        });
+    }
+
+    // 客户端断开
+    pub fn client_disconnect(connection_id: u64) {
+        CLIENT_INCOMING.remove(&connection_id);
+        Self::disconnect(connection_id);
+    }
+
+    fn disconnect(connection_id: u64) {
+        if CLIENT_CONNECTED.remove(&connection_id).is_some() {
+            Self::push_server_incoming(TransportCallback {
+                r#type: TransportCallbackType::OnServerDisconnected,
+                conn_id: connection_id,
+                ..TransportCallback::default()
+            });
+        }
+    }
+
+    // 客户端发送一个完整的批次 (timestamp + 消息)
+    pub fn client_send(connection_id: u64, data: Vec<u8>, channel: TransportChannel) {
+        Self::push_server_incoming(TransportCallback {
+            r#type: TransportCallbackType::OnServerDataReceived,
+            conn_id: connection_id,
+            data,
+            channel,
+            ..TransportCallback::default()
+        });
+    }
+
+    // 客户端发送消息, 按 Mirror 的批次格式打包
+    pub fn client_send_message<T>(connection_id: u64, message: &mut T, channel: TransportChannel)
+    where
+        T: NetworkMessageTrait + Send,
+    {
+        let mut batcher = Batcher::new(Self::MAX_PACKET_SIZE);
+        NetworkWriterPool::get_return(|writer| {
+            message.serialize(writer);
+            batcher.add_message(writer.to_array_segment(), NetworkTime::local_time());
+        });
+        NetworkWriterPool::get_return(|writer| {
+            while batcher.get_batcher_writer(writer) {
+                Self::client_send(connection_id, writer.to_bytes(), channel);
+                writer.reset();
+            }
+        });
+    }
+
+    // 取出上次调用之后服务器发送数据的连接顺序
+    pub fn take_send_order() -> Vec<u64> {
+        match SEND_ORDER.write() {
+            Ok(mut order) => std::mem::take(&mut *order),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    // 客户端收到的原始批次
+    pub fn client_receive(connection_id: u64) -> Vec<(Vec<u8>, TransportChannel)> {
+        match CLIENT_INCOMING.get_mut(&connection_id) {
+            Some(mut incoming) => incoming.drain(..).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    // 客户端收到的消息 (已拆包, 每条消息以 ushort 消息id 开头)
+    pub fn client_receive_messages(connection_id: u64) -> Vec<(Vec<u8>, TransportChannel)> {
+        let mut messages = Vec::new();
+        for (batch, channel) in Self::client_receive(connection_id) {
+            let mut un_batcher = UnBatcher::new();
+            if !un_batcher.add_batch_with_bytes(batch) {
+                log_error!(
+                    "MemoryTransport failed to un_batch data for connection {}",
+                    connection_id
+                );
+                continue;
+            }
+            while let Some((message, _)) = un_batcher.get_next_message() {
+                messages.push((message.to_vec(), channel));
+            }
+        }
+        messages
+    }
+
+    // 客户端是否仍然连接
+    pub fn client_connected(connection_id: u64) -> bool {
+        CLIENT_CONNECTED.contains(&connection_id)
+    }
+}
+
+impl TransportTrait for MemoryTransport {
+    fn awake()
+    where
+        Self: Sized,
+    {
+        Transport::set_active_transport(Box::new(Self::default()));
+    }
+
+    fn available(&self) -> bool {
+        true
+    }
+
+    fn server_active(&self) -> bool {
+        self.server_active
+    }
+
+    fn server_start(&mut self) {
+        self.server_active = true;
+    }
+
+    fn server_send(&mut self, connection_id: u64, data: Vec<u8>, channel: TransportChannel) {
+        match CLIENT_INCOMING.get_mut(&connection_id) {
+            Some(mut incoming) if CLIENT_CONNECTED.contains(&connection_id) => {
+                incoming.push_back((data, channel));
+                if let Ok(mut order) = SEND_ORDER.write() {
+                    if order.last() != Some(&connection_id) {
+                        order.push(connection_id);
+                    }
+                }
+            }
+            _ => Self::push_server_incoming(TransportCallback {
+                r#type: TransportCallbackType::OnServerError,
+                conn_id: connection_id,
+                error: TransportError::ConnectionNotFound,
+                ..TransportCallback::default()
+            }),
+        }
+    }
+
+    fn server_disconnect(&mut self, connection_id: u64) {
+        Self::disconnect(connection_id);
+    }
+
+    fn server_get_client_address(&self, connection_id: u64) -> String {
+        format!("memory://{}", connection_id)
+    }
+
+    fn server_early_update(&mut self) {
+        // 先取出全部事件, 回调中可能会再次访问 Transport
+        let incoming: Vec<TransportCallback> = match SERVER_INCOMING.write() {
+            Ok(mut incoming) => incoming.drain(..).collect(),
+            Err(e) => {
+                log_error!("MemoryTransport failed to get incoming queue: {:?}", e);
+                return;
+            }
+        };
+        match self.transport.transport_cb_fn {
+            None => {
+                log_error!("MemoryTransport server_early_update error: transport_cb_fn is None");
+            }
+            Some(transport_cb_fn) => {
+                for tcb in incoming {
+                    transport_cb_fn(tcb);
+                }
+            }
+        }
+    }
+
+    fn server_late_update(&mut self) {}
+
+    fn server_stop(&mut self) {
+        self.server_active = false;
+        CLIENT_CONNECTED.clear();
+        if let Ok(mut incoming) = SERVER_INCOMING.write() {
+            incoming.clear();
+        }
+        if let Ok(mut order) = SEND_ORDER.write() {
+            order.clear();
+        }
+    }
+
+    fn transport_cb_fn(&self) -> Option<TransportFunc> {
+        self.transport.transport_cb_fn
+    }
+
+    fn set_transport_cb_fn(&mut self, func: TransportFunc) {
+        self.transport.transport_cb_fn.replace(func);
+    }
+
+    fn get_max_packet_size(&self, _channel: TransportChannel) -> usize {
+        Self::MAX_PACKET_SIZE
+    }
+
+    fn client_connect(&mut self, address: &str) -> bool {
+        let connection_id = match address
+            .strip_prefix("memory://")
+            .and_then(|connection_id| connection_id.parse::<u64>().ok())
+        {
+            // 0 保留给本地玩家
+            Some(connection_id) if connection_id != 0 => connection_id,
+            _ => {
+                log_error!("MemoryTransport invalid client address: {}", address);
+                return false;
+            }
+        };
+        if Self::client_connected(connection_id) {
+            log_error!(
+                "MemoryTransport client connection {} already exists",
+                connection_id
+            );
+            return false;
+        }
+        Self::client_connect(connection_id);
+        self.client_id = Some(connection_id);
+        self.client_connecting = true;
+        true
+    }
+
+    fn client_connected(&self) -> bool {
+        self.client_id.is_some_and(Self::client_connected)
+    }
+
+    fn client_send(&mut self, data: Vec<u8>, channel: TransportChannel) {
+        if let Some(connection_id) = self.client_id {
+            Self::client_send(connection_id, data, channel);
+        }
+    }
+
+    fn client_disconnect(&mut self) {
+        self.client_connecting = false;
+        if let Some(connection_id) = self.client_id.take() {
+            Self::client_disconnect(connection_id);
+        }
+    }
+
+    fn client_early_update(&mut self) {
+        let connection_id = match self.client_id {
+            Some(connection_id) => connection_id,
+            None => return,
+        };
+        let transport_cb_fn = match self.transport.transport_cb_fn {
+            Some(transport_cb_fn) => transport_cb_fn,
+            None => {
+                log_error!("MemoryTransport client_early_update error: transport_cb_fn is None");
+                return;
+            }
+        };
+        if self.client_connecting {
+            self.client_connecting = false;
+            transport_cb_fn(TransportCallback {
+                r#type: TransportCallbackType::OnClientConnected,
+                conn_id: connection_id,
+                ..TransportCallback::default()
+            });
+        }
+        // 服务器断开之前发送的数据先送达
+        for (data, channel) in Self::client_receive(connection_id) {
+            transport_cb_fn(TransportCallback {
+                r#type: TransportCallbackType::OnClientDataReceived,
+                conn_id: connection_id,
+                data,
+                channel,
+                ..TransportCallback::default()
+            });
+        }
+        // 回调中可能已经 client_disconnect
+        if self.client_id.is_some() && !Self::client_connected(connection_id) {
+            self.client_id = None;
+            CLIENT_INCOMING.remove(&connection_id);
+            transport_cb_fn(TransportCallback {
+                r#type: TransportCallbackType::OnClientDisconnected,
+                conn_id: connection_id,
+                ..TransportCallback::default()
+            });
+        }
+    }
+}
+
+// 其他模块的服务器测试共用, MemoryTransport 充当客户端
+#[cfg(test)]
+pub(crate) mod test_util {
+    use super::MemoryTransport;
+    use crate::mirror::components::network_common_behaviour::NetworkCommonBehaviour;
+    use crate::mirror::core::backend_data::NetworkBehaviourSetting;
+    use crate::mirror::core::batching::batcher::Batcher;
+    use crate::mirror::core::messages::NetworkMessageTrait;
+    use crate::mirror::core::network_behaviour::{
+        GameObject, NetworkBehaviour, NetworkBehaviourTrait,
+    };
+    use crate::mirror::core::network_context::NetworkContext;
+    use crate::mirror::core::network_identity::NetworkIdentity;
+    use crate::mirror::core::network_reader::{NetworkReader, NetworkReaderTrait};
+    use crate::mirror::core::network_server::{
+        NetworkServer, NetworkServerStatic, NETWORK_BEHAVIOURS,
+    };
+    use crate::mirror::core::network_time::NetworkTime;
+    use crate::mirror::core::network_writer::{NetworkWriter, NetworkWriterTrait};
+    use crate::mirror::core::network_writer_pool::NetworkWriterPool;
+    use crate::mirror::core::transport::{TransportChannel, TransportTrait};
+    use dashmap::DashMap;
+    use std::any::Any;
+    use std::sync::{Mutex, MutexGuard};
+
+    // NetworkServer 是全局状态, 测试需要串行执行
+    static SERVER_LOCK: Mutex<()> = Mutex::new(());
+
+    // 测试 panic 时也关闭服务器, 避免影响后面的测试
+    struct ServerGuard(#[allow(dead_code)] MutexGuard<'static, ()>);
+
+    impl Drop for ServerGuard {
+        fn drop(&mut self) {
+            NetworkServer::shutdown();
+        }
+    }
+
+    pub(crate) fn with_server<F: FnOnce()>(func: F) {
+        let _guard = ServerGuard(SERVER_LOCK.lock().unwrap_or_else(|e| e.into_inner()));
+        MemoryTransport::awake();
+        NetworkServer::listen(16);
+        func();
+    }
+
+    // 在新建的上下文中运行 func, 上下文中的服务器和各个子系统的状态都是独立的,
+    // 不需要 with_server 的锁, 也不受其他测试中的服务器 (例如 NetworkServer::shutdown) 影响
+    pub(crate) fn with_isolated_context<R, F: FnOnce() -> R>(func: F) -> R {
+        NetworkContext::enter(NetworkContext::create(), func)
+    }
+
+    pub(crate) fn tick() {
+        NetworkServer::network_early_update();
+        NetworkServer::network_late_update();
+    }
+
+    pub(crate) fn send_raw(connection_id: u64, message: &[u8]) {
+        let mut batcher = Batcher::new(MemoryTransport::MAX_PACKET_SIZE);
+        batcher.add_message(message, NetworkTime::local_time());
+        NetworkWriterPool::get_return(|writer| {
+            while batcher.get_batcher_writer(writer) {
+                MemoryTransport::client_send(
+                    connection_id,
+                    writer.to_bytes(),
+                    TransportChannel::Reliable,
+                );
+                writer.reset();
+            }
+        });
+    }
+
+    // tests/golden 中从 C# Mirror 抓取的字节, 没有时返回 None, 见 tests/golden/README.md
+    pub(crate) fn captured_golden(name: &str) -> Option<Vec<u8>> {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/golden")
+            .join(format!("{}.bin", name));
+        std::fs::read(path).ok()
+    }
+
+    // 生成带 behaviour_count 个 NetworkCommonBehaviour 的 NetworkIdentity, owner 为 0 时没有所有者
+    // 需要修改组件时通过 NETWORK_BEHAVIOURS 获取, 用 remove_spawned_network_identity 移除
+    pub(crate) fn spawn_test_identity(net_id: u32, owner: u64, behaviour_count: u8) -> u32 {
+        for index in 0..behaviour_count {
+            let mut behaviour = NetworkCommonBehaviour {
+                network_behaviour: NetworkBehaviour::new(
+                    GameObject::default(),
+                    NetworkBehaviourSetting::default(),
+                    index,
+                    "Test".to_string(),
+                ),
+                sync_vars: DashMap::new(),
+            };
+            behaviour.set_net_id(net_id);
+            NETWORK_BEHAVIOURS::add_behaviour(net_id, index, Box::new(behaviour));
+        }
+        let mut identity = NetworkIdentity::new_with_asset_id(0);
+        identity.set_net_id(net_id);
+        identity.network_behaviours_count = behaviour_count;
+        if owner != 0 {
+            identity.set_connection_to_client(owner);
+        }
+        NetworkServerStatic::add_spawned_network_identity(identity);
+        net_id
+    }
+
+    pub(crate) fn received<T: NetworkMessageTrait>(connection_id: u64) -> Vec<T> {
+        decode(&MemoryTransport::client_receive_messages(connection_id))
+    }
+
+    pub(crate) fn decode<T: NetworkMessageTrait>(
+        messages: &[(Vec<u8>, TransportChannel)],
+    ) -> Vec<T> {
+        messages
+            .iter()
+            .filter_map(|(message, _)| {
+                let mut reader = NetworkReader::new_with_bytes(message.clone());
+                if reader.read_ushort() == T::get_hash_code() {
+                    Some(T::deserialize(&mut reader))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    // 用作测试消息和 RPC 参数
+    #[derive(Debug, Clone, Default, PartialEq)]
+    pub(crate) struct TestHit(pub(crate) i32);
+
+    impl NetworkMessageTrait for TestHit {
+        fn deserialize(reader: &mut NetworkReader) -> Self {
+            TestHit(reader.read_int())
+        }
+        fn serialize(&mut self, writer: &mut NetworkWriter) {
+            writer.write_int(self.0);
+        }
+        fn get_full_name() -> &'static str {
+            "Test.TestHit"
+        }
+        fn as_any_mut(&mut self) -> &mut dyn Any {
+            self
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_util::*;
+    use super::*;
+    use crate::mirror::core::messages::{
+        CommandMessage, EntityStateMessage, NetworkPingMessage, NetworkPongMessage,
+        ProtocolRejectMessage, ProtocolVersionMessage, ReadyMessage, SpawnMessage,
+        TimeSnapshotMessage,
+    };
+    use crate::mirror::core::network_behaviour::GameObject;
+    use crate::mirror::core::network_connection::NetworkConnectionTrait;
+    use crate::mirror::core::network_identity::{NetworkIdentity, Visibility};
+    use crate::mirror::core::network_reader::NetworkReader;
+    use crate::mirror::core::network_server::{
+        NetworkServer, NetworkServerStatic, NETWORK_BEHAVIOURS,
+    };
+    use crate::mirror::core::network_writer::NetworkWriter;
+    use crate::mirror::core::remote_calls::RemoteProcedureCalls;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_connect_and_disconnect() {
+        with_server(|| {
+            MemoryTransport::client_connect(1);
+            tick();
+            assert!(NetworkServerStatic::network_connections().contains_key(&1));
+
+            MemoryTransport::client_disconnect(1);
+            tick();
+            assert!(!NetworkServerStatic::network_connections().contains_key(&1));
+        });
+    }
+
+    #[test]
+    fn test_ready_message() {
+        with_server(|| {
+            MemoryTransport::client_connect(1);
+            tick();
+            MemoryTransport::client_send_message(1, &mut ReadyMessage, TransportChannel::Reliable);
+            tick();
+            assert!(NetworkServerStatic::network_connections()
+                .get(&1)
+                .unwrap()
+                .is_ready());
+        });
+    }
+
+    #[test]
+    fn test_ping_pong() {
+        with_server(|| {
+            MemoryTransport::client_connect(1);
+            tick();
+            MemoryTransport::client_receive(1);
+            let mut ping = NetworkPingMessage::new(0.5, 0.5);
+            MemoryTransport::client_send_message(1, &mut ping, TransportChannel::Reliable);
+            tick();
+            let pongs = received::<NetworkPongMessage>(1);
+            assert_eq!(pongs.len(), 1);
+            assert_eq!(pongs[0].local_time, 0.5);
+        });
+    }
+
+    #[test]
+    fn test_protocol_handshake() {
+        with_server(|| {
+            NetworkServerStatic::set_protocol_handshake(true);
+            MemoryTransport::client_connect(1);
+            MemoryTransport::client_connect(2);
+            tick();
+            let versions = received::<ProtocolVersionMessage>(1);
+            assert_eq!(versions.len(), 1);
+            assert_eq!(versions[0].version, NetworkServer::PROTOCOL_VERSION);
+
+            // 握手之前的消息被忽略
+            MemoryTransport::client_send_message(1, &mut ReadyMessage, TransportChannel::Reliable);
+            tick();
+            assert!(!NetworkServerStatic::network_connections()
+                .get(&1)
+                .unwrap()
+                .is_ready());
+
+            // 版本一致
+            let mut version = ProtocolVersionMessage::new(NetworkServer::PROTOCOL_VERSION, 0, 60);
+            MemoryTransport::client_send_message(1, &mut version, TransportChannel::Reliable);
+            MemoryTransport::client_send_message(1, &mut ReadyMessage, TransportChannel::Reliable);
+            tick();
+            assert!(NetworkServerStatic::network_connections()
+                .get(&1)
+                .unwrap()
+                .is_ready());
+
+            // 版本不一致
+            let mut version =
+                ProtocolVersionMessage::new(NetworkServer::PROTOCOL_VERSION + 1, 0, 60);
+            MemoryTransport::client_send_message(2, &mut version, TransportChannel::Reliable);
+            tick();
+            let rejects = received::<ProtocolRejectMessage>(2);
+            assert_eq!(rejects.len(), 1);
+            assert!(
+                !NetworkServerStatic::network_connections()
+                    .get(&2)
+                    .unwrap()
+                    .protocol_verified
+            );
+
+            NetworkServerStatic::set_protocol_handshake(false);
+        });
+    }
+
+    #[test]
+    fn test_spawn_message() {
+        with_server(|| {
+            MemoryTransport::client_connect(1);
+            MemoryTransport::client_connect(2);
+            tick();
+            NetworkServer::set_client_ready(1);
+            tick();
+            MemoryTransport::client_receive(1);
+            MemoryTransport::client_receive(2);
+
+            let mut identity = NetworkIdentity::new_with_asset_id(0);
+            identity.set_net_id(730);
+            identity.set_game_object(GameObject::new_with_prefab("Test".to_string()));
+            assert_eq!(NetworkServer::spawn_with_net_id(identity), 730);
+            tick();
+
+            // 只有 ready 的观察者收到 SpawnMessage
+            let spawns = received::<SpawnMessage>(1);
+            assert_eq!(spawns.len(), 1);
+            assert_eq!(spawns[0].net_id, 730);
+            assert!(!spawns[0].is_owner);
+            assert!(received::<SpawnMessage>(2).is_empty());
+            NetworkServerStatic::remove_spawned_network_identity(&730);
+        });
+    }
+
+    static COMMAND_CALLS: Mutex<Vec<(u64, u32, u8, i32)>> = Mutex::new(Vec::new());
+
+    fn on_test_command(
+        connection_id: u64,
+        net_id: u32,
+        component_index: u8,
+        _: u16,
+        reader: &mut NetworkReader,
+    ) {
+        COMMAND_CALLS.lock().unwrap().push((
+            connection_id,
+            net_id,
+            component_index,
+            TestHit::deserialize(reader).0,
+        ));
+    }
+
+    #[test]
+    fn test_command_dispatch() {
+        with_server(|| {
+            let function_hash = RemoteProcedureCalls::register_command_delegate::<TestHit>(
+                "System.Void Test.TestHit::CmdDispatch()",
+                on_test_command,
+                true,
+            );
+            MemoryTransport::client_connect(1);
+            tick();
+            NetworkServer::set_client_ready(1);
+            NetworkServerStatic::network_connections()
+                .get_mut(&1)
+                .unwrap()
+                .set_authenticated(true);
+
+            spawn_test_identity(740, 1, 1);
+
+            // 参数原样交给组件上注册的 command
+            let mut args = NetworkWriter::new();
+            TestHit(42).serialize(&mut args);
+            let mut writer = NetworkWriter::new();
+            CommandMessage::new(740, 0, function_hash, args.to_bytes()).serialize(&mut writer);
+            send_raw(1, &writer.to_bytes());
+            tick();
+            assert_eq!(
+                std::mem::take(&mut *COMMAND_CALLS.lock().unwrap()),
+                vec![(1, 740, 0, 42)]
+            );
+
+            RemoteProcedureCalls::remove_delegate(function_hash);
+            NetworkServerStatic::remove_spawned_network_identity(&740);
+        });
+    }
+
+    #[test]
+    fn test_state_broadcast() {
+        with_server(|| {
+            MemoryTransport::client_connect(1);
+            tick();
+            NetworkServer::set_client_ready(1);
+
+            spawn_test_identity(750, 0, 1);
+            NetworkServer::set_visibility(750, Visibility::Default);
+            tick();
+            MemoryTransport::client_receive(1);
+
+            // 脏组件的状态和 TimeSnapshotMessage 在同一次广播中发出
+            NetworkTime::increment_frame_count();
+            {
+                let mut behaviour = NETWORK_BEHAVIOURS.get_mut(&(750, 0)).unwrap();
+                behaviour.set_sync_var_dirty_bits(1);
+                behaviour.set_last_sync_time(-1.0);
+            }
+            tick();
+            let messages = MemoryTransport::client_receive_messages(1);
+            assert_eq!(decode::<TimeSnapshotMessage>(&messages).len(), 1);
+            let states = decode::<EntityStateMessage>(&messages);
+            assert_eq!(states.len(), 1);
+            assert_eq!(states[0].net_id, 750);
+
+            // 没有变化时不再发送状态
+            NetworkTime::increment_frame_count();
+            tick();
+            assert!(received::<EntityStateMessage>(1).is_empty());
+
+            NetworkServerStatic::remove_spawned_network_identity(&750);
+        });
+    }
+}
@@ -1 +1,8 @@
 pub mod kcp2k;
+pub mod memory;
+pub mod simulator;
+pub mod relay;
+#[cfg(feature = "steam")]
+pub mod steam;
+#[cfg(feature = "enet")]
+pub mod enet;
\ No newline at end of file
@@ -0,0 +1 @@
+pub mod relay_transport;
@@ -0,0 +1,777 @@
+use crate::mirror::core::backend_data::BackendDataStatic;
+use crate::mirror::core::network_context::{ContextLocal, NetworkContext};
+use crate::mirror::core::network_reader::{NetworkReader, NetworkReaderTrait};
+use crate::mirror::core::network_time::NetworkTime;
+use crate::mirror::core::network_writer::{NetworkWriter, NetworkWriterTrait};
+use crate::mirror::core::transport::{
+    Transport, TransportCallback, TransportCallbackType, TransportChannel, TransportError,
+    TransportFunc, TransportReceiveQueue, TransportTrait,
+};
+use crate::{log_error, log_info, log_warn};
+use dashmap::DashMap;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::{Arc, RwLock};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct RelayTransportConfig {
+    pub enabled: bool,
+    // 中继服务器地址 host:port
+    pub address: String,
+    // 中继用来验证主机的令牌
+    pub token: String,
+    // 向中继公布的名称
+    pub name: String,
+    // 心跳间隔 (秒)
+    pub heartbeat_interval: f64,
+    // 超过这个时间 (秒) 没有收到中继的数据视为断开, 中继需要定期发送 Heartbeat
+    pub timeout: f64,
+    // 断开后重新连接的间隔 (秒)
+    pub reconnect_interval: f64,
+}
+
+impl Default for RelayTransportConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            address: "".to_string(),
+            token: "".to_string(),
+            name: "".to_string(),
+            heartbeat_interval: 5.0,
+            timeout: 15.0,
+            reconnect_interval: 5.0,
+        }
+    }
+}
+
+// 中继协议的帧类型, 每帧为 u32 长度 (小端序, 包含类型) + u8 类型 + 内容
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum RelayOpcode {
+    // 主机 -> 中继: token, name
+    Register,
+    // 中继 -> 主机: join_code, 客户端通过 join_code 连接这个主机
+    Registered,
+    // 中继 -> 主机: reason, 之后不再重连
+    Rejected,
+    // 中继 -> 主机: client_id, address
+    ClientConnected,
+    // 双向: client_id
+    ClientDisconnected,
+    // 双向: client_id, channel, 剩余的字节
+    Data,
+    // 双向, 没有内容
+    Heartbeat,
+}
+
+impl RelayOpcode {
+    pub fn from(value: u8) -> Option<Self> {
+        match value {
+            1 => Some(RelayOpcode::Register),
+            2 => Some(RelayOpcode::Registered),
+            3 => Some(RelayOpcode::Rejected),
+            4 => Some(RelayOpcode::ClientConnected),
+            5 => Some(RelayOpcode::ClientDisconnected),
+            6 => Some(RelayOpcode::Data),
+            7 => Some(RelayOpcode::Heartbeat),
+            _ => None,
+        }
+    }
+
+    pub fn to_u8(&self) -> u8 {
+        match self {
+            RelayOpcode::Register => 1,
+            RelayOpcode::Registered => 2,
+            RelayOpcode::Rejected => 3,
+            RelayOpcode::ClientConnected => 4,
+            RelayOpcode::ClientDisconnected => 5,
+            RelayOpcode::Data => 6,
+            RelayOpcode::Heartbeat => 7,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum RelayState {
+    Disconnected,
+    Connecting,
+    Registered,
+    Rejected,
+}
+
+impl RelayState {
+    pub fn from(value: u8) -> Self {
+        match value {
+            1 => RelayState::Connecting,
+            2 => RelayState::Registered,
+            3 => RelayState::Rejected,
+            _ => RelayState::Disconnected,
+        }
+    }
+
+    pub fn to_u8(&self) -> u8 {
+        match self {
+            RelayState::Disconnected => 0,
+            RelayState::Connecting => 1,
+            RelayState::Registered => 2,
+            RelayState::Rejected => 3,
+        }
+    }
+}
+
+// RelayTransport 静态变量
+lazy_static! {
+    // 中继线程把事件放进队列, 由 server_early_update 在主循环处理
+    static ref RECEIVE_QUEUE: ContextLocal<RwLock<Option<Arc<TransportReceiveQueue>>>> =
+        ContextLocal::new(|| RwLock::new(None));
+    static ref RELAY_THREAD_RUNNING: ContextLocal<AtomicBool> =
+        ContextLocal::new(|| AtomicBool::new(false));
+    // 中继线程连接成功后放入, 主循环通过它发送
+    static ref STREAM: ContextLocal<RwLock<Option<TcpStream>>> =
+        ContextLocal::new(|| RwLock::new(None));
+    static ref STATE: ContextLocal<AtomicU8> = ContextLocal::new(|| AtomicU8::new(0));
+    static ref JOIN_CODE: ContextLocal<RwLock<String>> =
+        ContextLocal::new(|| RwLock::new(String::new()));
+    // 中继的连接 -> 客户端地址
+    static ref RELAYED: ContextLocal<DashMap<u64, String>> = ContextLocal::new(DashMap::new);
+}
+
+// 包装另一个 Transport, 同时向中继服务器注册并接受经过中继的客户端连接, 用于主机在 NAT 之后的情况
+// 先 awake 实际的 Transport, 再调用 RelayTransport::awake(), 配置来自 BackendData 的 relay_config
+// 直接连接仍然由内层 Transport 处理; 中继的连接 id 设置最高位, 不会与内层的连接冲突
+// 与中继之间使用一个 TCP 连接, 不可靠通道的数据也按顺序送达, 断开后所有中继的连接断开并定期重连
+pub struct RelayTransport {
+    pub transport: Transport,
+    inner: Box<dyn TransportTrait>,
+    config: RelayTransportConfig,
+    // 等待在 server_late_update 中发给中继的帧
+    outgoing: Vec<u8>,
+    last_heartbeat_time: f64,
+    relay_handle: Option<JoinHandle<()>>,
+}
+
+impl RelayTransport {
+    pub const RELAYED_CONNECTION_FLAG: u64 = 1 << 63;
+    const MAX_FRAME_SIZE: usize = 1 << 20;
+    const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+    const SLEEP_STEP: Duration = Duration::from_millis(50);
+
+    pub fn new(inner: Box<dyn TransportTrait>, config: RelayTransportConfig) -> Self {
+        Self {
+            transport: Transport::default(),
+            inner,
+            config,
+            outgoing: Vec::new(),
+            last_heartbeat_time: 0.0,
+            relay_handle: None,
+        }
+    }
+
+    pub fn state() -> RelayState {
+        RelayState::from(STATE.load(Ordering::Relaxed))
+    }
+
+    fn set_state(state: RelayState) {
+        STATE.store(state.to_u8(), Ordering::Relaxed);
+    }
+
+    // 中继分配的加入码, 没有注册时为空
+    pub fn join_code() -> String {
+        match JOIN_CODE.read() {
+            Ok(join_code) => join_code.clone(),
+            Err(_) => String::new(),
+        }
+    }
+
+    pub fn is_relayed(connection_id: u64) -> bool {
+        connection_id & Self::RELAYED_CONNECTION_FLAG != 0
+    }
+
+    pub fn relayed_count() -> usize {
+        RELAYED.len()
+    }
+
+    fn receive_queue() -> Option<Arc<TransportReceiveQueue>> {
+        match RECEIVE_QUEUE.read() {
+            Ok(queue) => queue.clone(),
+            Err(_) => None,
+        }
+    }
+
+    fn push(tcb: TransportCallback) {
+        if let Some(queue) = Self::receive_queue() {
+            queue.push(tcb);
+        }
+    }
+
+    fn push_event(r#type: TransportCallbackType, connection_id: u64) {
+        Self::push(TransportCallback {
+            r#type,
+            conn_id: connection_id,
+            ..TransportCallback::default()
+        });
+    }
+
+    pub fn encode_frame<F: FnOnce(&mut NetworkWriter)>(opcode: RelayOpcode, body: F) -> Vec<u8> {
+        let mut writer = NetworkWriter::new();
+        writer.write_uint(0);
+        writer.write_byte(opcode.to_u8());
+        body(&mut writer);
+        let mut frame = writer.to_bytes();
+        let length = (frame.len() - 4) as u32;
+        frame[..4].copy_from_slice(&length.to_le_bytes());
+        frame
+    }
+
+    // 读取一帧, 返回类型和内容
+    pub fn read_frame<R: Read>(stream: &mut R) -> std::io::Result<(Option<RelayOpcode>, Vec<u8>)> {
+        let mut header = [0u8; 4];
+        stream.read_exact(&mut header)?;
+        let length = u32::from_le_bytes(header) as usize;
+        if length == 0 || length > Self::MAX_FRAME_SIZE {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("invalid relay frame length: {}", length),
+            ));
+        }
+        let mut frame = vec![0u8; length];
+        stream.read_exact(&mut frame)?;
+        let body = frame.split_off(1);
+        Ok((RelayOpcode::from(frame[0]), body))
+    }
+
+    fn start_relay_thread(&mut self) {
+        let queue = Arc::new(TransportReceiveQueue::new(8192));
+        match RECEIVE_QUEUE.write() {
+            Ok(mut receive_queue) => *receive_queue = Some(queue),
+            Err(e) => {
+                log_error!("RelayTransport failed to write RECEIVE_QUEUE: {:?}", e);
+                return;
+            }
+        }
+        RELAY_THREAD_RUNNING.store(true, Ordering::Relaxed);
+        let config = self.config.clone();
+        let context = NetworkContext::current();
+        let spawned = thread::Builder::new()
+            .name("relay".to_string())
+            .spawn(move || NetworkContext::enter(context, || Self::relay_loop(config)));
+        match spawned {
+            Ok(handle) => self.relay_handle = Some(handle),
+            Err(e) => {
+                log_error!("RelayTransport failed to start relay thread: {}", e);
+                RELAY_THREAD_RUNNING.store(false, Ordering::Relaxed);
+            }
+        }
+    }
+
+    fn stop_relay_thread(&mut self) {
+        RELAY_THREAD_RUNNING.store(false, Ordering::Relaxed);
+        // 唤醒阻塞在读取上的中继线程
+        if let Ok(stream) = STREAM.read() {
+            if let Some(stream) = stream.as_ref() {
+                let _ = stream.shutdown(Shutdown::Both);
+            }
+        }
+        if let Some(queue) = Self::receive_queue() {
+            queue.close();
+        }
+        if let Some(handle) = self.relay_handle.take() {
+            let _ = handle.join();
+        }
+        if let Ok(mut receive_queue) = RECEIVE_QUEUE.write() {
+            *receive_queue = None;
+        }
+    }
+
+    fn relay_loop(config: RelayTransportConfig) {
+        while RELAY_THREAD_RUNNING.load(Ordering::Relaxed) {
+            Self::set_state(RelayState::Connecting);
+            match Self::connect(&config) {
+                Ok(stream) => {
+                    if let Err(e) = Self::receive(&config, stream) {
+                        if RELAY_THREAD_RUNNING.load(Ordering::Relaxed) {
+                            log_warn!("RelayTransport connection lost: {}", e);
+                        }
+                    }
+                }
+                Err(e) => {
+                    log_warn!(
+                        "RelayTransport failed to connect to {}: {}",
+                        config.address,
+                        e
+                    );
+                }
+            }
+            if let Ok(mut stream) = STREAM.write() {
+                *stream = None;
+            }
+            if let Ok(mut join_code) = JOIN_CODE.write() {
+                join_code.clear();
+            }
+            // 中继断开, 经过中继的连接全部断开
+            let connection_ids: Vec<u64> = RELAYED.iter().map(|entry| *entry.key()).collect();
+            RELAYED.clear();
+            for connection_id in connection_ids {
+                Self::push_event(TransportCallbackType::OnServerDisconnected, connection_id);
+            }
+            if Self::state() == RelayState::Rejected {
+                return;
+            }
+            Self::set_state(RelayState::Disconnected);
+            let mut waited = Duration::ZERO;
+            let reconnect_interval = Duration::from_secs_f64(config.reconnect_interval.max(0.0));
+            while waited < reconnect_interval && RELAY_THREAD_RUNNING.load(Ordering::Relaxed) {
+                thread::sleep(Self::SLEEP_STEP);
+                waited += Self::SLEEP_STEP;
+            }
+        }
+    }
+
+    fn connect(config: &RelayTransportConfig) -> std::io::Result<TcpStream> {
+        let endpoint: SocketAddr = config
+            .address
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "no address"))?;
+        let mut stream = TcpStream::connect_timeout(&endpoint, Self::CONNECT_TIMEOUT)?;
+        stream.set_nodelay(true)?;
+        if config.timeout > 0.0 {
+            stream.set_read_timeout(Some(Duration::from_secs_f64(config.timeout)))?;
+        }
+        stream.write_all(&Self::encode_frame(RelayOpcode::Register, |writer| {
+            writer.write_str(&config.token);
+            writer.write_str(&config.name);
+        }))?;
+        match STREAM.write() {
+            Ok(mut current) => *current = Some(stream.try_clone()?),
+            Err(e) => {
+                return Err(std::io::Error::other(format!(
+                    "failed to write STREAM: {:?}",
+                    e
+                )));
+            }
+        }
+        Ok(stream)
+    }
+
+    // 在中继线程中读取, 直到连接断开
+    fn receive(config: &RelayTransportConfig, mut stream: TcpStream) -> std::io::Result<()> {
+        while RELAY_THREAD_RUNNING.load(Ordering::Relaxed) {
+            let (opcode, body) = Self::read_frame(&mut stream)?;
+            let mut reader = NetworkReader::new_with_bytes(body);
+            match opcode {
+                Some(RelayOpcode::Registered) => {
+                    let join_code = reader.read_string();
+                    log_info!(
+                        "RelayTransport registered with {}, join code: {}",
+                        config.address,
+                        join_code
+                    );
+                    if let Ok(mut current) = JOIN_CODE.write() {
+                        *current = join_code;
+                    }
+                    Self::set_state(RelayState::Registered);
+                }
+                Some(RelayOpcode::Rejected) => {
+                    let reason = reader.read_string();
+                    log_error!(
+                        "RelayTransport registration rejected by {}: {}",
+                        config.address,
+                        reason
+                    );
+                    Self::set_state(RelayState::Rejected);
+                    return Ok(());
+                }
+                Some(RelayOpcode::ClientConnected) => {
+                    let connection_id = Self::RELAYED_CONNECTION_FLAG | reader.read_uint() as u64;
+                    let address = reader.read_string();
+                    if RELAYED.insert(connection_id, address).is_none() {
+                        Self::push_event(TransportCallbackType::OnServerConnected, connection_id);
+                    }
+                }
+                Some(RelayOpcode::ClientDisconnected) => {
+                    let connection_id = Self::RELAYED_CONNECTION_FLAG | reader.read_uint() as u64;
+                    if RELAYED.remove(&connection_id).is_some() {
+                        Self::push_event(
+                            TransportCallbackType::OnServerDisconnected,
+                            connection_id,
+                        );
+                    }
+                }
+                Some(RelayOpcode::Data) => {
+                    let connection_id = Self::RELAYED_CONNECTION_FLAG | reader.read_uint() as u64;
+                    let channel = match reader.read_byte() {
+                        2 => TransportChannel::Unreliable,
+                        _ => TransportChannel::Reliable,
+                    };
+                    // 已经断开的连接的数据直接丢弃
+                    if RELAYED.contains_key(&connection_id) {
+                        Self::push(TransportCallback {
+                            r#type: TransportCallbackType::OnServerDataReceived,
+                            conn_id: connection_id,
+                            data: reader.read_remaining_bytes(),
+                            channel,
+                            ..TransportCallback::default()
+                        });
+                    }
+                }
+                Some(RelayOpcode::Heartbeat) => {}
+                Some(RelayOpcode::Register) | None => {
+                    log_warn!("RelayTransport received unexpected frame from relay");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn flush_outgoing(&mut self) {
+        if self.outgoing.is_empty() {
+            return;
+        }
+        let outgoing = std::mem::take(&mut self.outgoing);
+        if let Ok(stream) = STREAM.read() {
+            if let Some(mut stream) = stream.as_ref() {
+                // 写入失败时关闭连接, 由中继线程断开所有中继的连接并重连
+                if let Err(e) = stream.write_all(&outgoing) {
+                    log_warn!("RelayTransport failed to send to relay: {}", e);
+                    let _ = stream.shutdown(Shutdown::Both);
+                }
+            }
+        }
+    }
+}
+
+impl TransportTrait for RelayTransport {
+    fn awake()
+    where
+        Self: Sized,
+    {
+        let config = BackendDataStatic::get_backend_data().relay_config;
+        match Transport::take_active_transport() {
+            Some(inner) => Transport::set_active_transport(Box::new(Self::new(inner, config))),
+            None => {
+                log_error!("RelayTransport awake error: no active transport to wrap");
+            }
+        }
+    }
+
+    fn available(&self) -> bool {
+        self.inner.available()
+    }
+
+    fn is_encrypted(&self) -> bool {
+        self.inner.is_encrypted()
+    }
+
+    fn encryption_cipher(&self) -> &str {
+        self.inner.encryption_cipher()
+    }
+
+    fn server_active(&self) -> bool {
+        self.inner.server_active()
+    }
+
+    fn server_start(&mut self) {
+        self.inner.server_start();
+        if self.config.enabled {
+            self.start_relay_thread();
+        }
+    }
+
+    fn server_send(&mut self, connection_id: u64, data: Vec<u8>, channel: TransportChannel) {
+        if !Self::is_relayed(connection_id) {
+            self.inner.server_send(connection_id, data, channel);
+            return;
+        }
+        if !RELAYED.contains_key(&connection_id) {
+            Self::push(TransportCallback {
+                r#type: TransportCallbackType::OnServerError,
+                conn_id: connection_id,
+                error: TransportError::ConnectionNotFound,
+                ..TransportCallback::default()
+            });
+            return;
+        }
+        let frame = Self::encode_frame(RelayOpcode::Data, |writer| {
+            writer.write_uint(connection_id as u32);
+            writer.write_byte(channel as u8);
+            writer.write_array_segment_all(&data);
+        });
+        self.outgoing.extend_from_slice(&frame);
+    }
+
+    fn server_disconnect(&mut self, connection_id: u64) {
+        if !Self::is_relayed(connection_id) {
+            self.inner.server_disconnect(connection_id);
+            return;
+        }
+        if RELAYED.remove(&connection_id).is_some() {
+            let frame = Self::encode_frame(RelayOpcode::ClientDisconnected, |writer| {
+                writer.write_uint(connection_id as u32);
+            });
+            self.outgoing.extend_from_slice(&frame);
+            Self::push_event(TransportCallbackType::OnServerDisconnected, connection_id);
+        }
+    }
+
+    fn server_get_client_address(&self, connection_id: u64) -> String {
+        if !Self::is_relayed(connection_id) {
+            return self.inner.server_get_client_address(connection_id);
+        }
+        match RELAYED.get(&connection_id) {
+            Some(address) => Transport::canonical_address(&address),
+            None => String::new(),
+        }
+    }
+
+    fn server_local_endpoint(&self) -> Option<SocketAddr> {
+        self.inner.server_local_endpoint()
+    }
+
+    fn server_early_update(&mut self) {
+        self.inner.server_early_update();
+        let queue = match Self::receive_queue() {
+            Some(queue) => queue,
+            None => return,
+        };
+        match self.transport.transport_cb_fn {
+            None => {
+                log_error!("RelayTransport server_early_update error: transport_cb_fn is None");
+            }
+            Some(transport_cb_fn) => {
+                queue.drain(0, transport_cb_fn);
+            }
+        }
+    }
+
+    fn server_late_update(&mut self) {
+        self.inner.server_late_update();
+        let local_time = NetworkTime::local_time();
+        if Self::state() == RelayState::Registered
+            && local_time - self.last_heartbeat_time >= self.config.heartbeat_interval
+        {
+            self.last_heartbeat_time = local_time;
+            let frame = Self::encode_frame(RelayOpcode::Heartbeat, |_| {});
+            self.outgoing.extend_from_slice(&frame);
+        }
+        self.flush_outgoing();
+    }
+
+    fn server_stop(&mut self) {
+        self.flush_outgoing();
+        self.stop_relay_thread();
+        self.inner.server_stop();
+        self.outgoing.clear();
+        RELAYED.clear();
+        Self::set_state(RelayState::Disconnected);
+    }
+
+    fn transport_cb_fn(&self) -> Option<TransportFunc> {
+        self.transport.transport_cb_fn
+    }
+
+    fn set_transport_cb_fn(&mut self, func: TransportFunc) {
+        self.transport.transport_cb_fn.replace(func);
+        // 直接连接的事件不经过中继, 直接交给同一个回调
+        self.inner.set_transport_cb_fn(func);
+    }
+
+    fn get_max_packet_size(&self, channel: TransportChannel) -> usize {
+        self.inner.get_max_packet_size(channel)
+    }
+
+    fn get_batcher_threshold(&self, channel: TransportChannel) -> usize {
+        self.inner.get_batcher_threshold(channel)
+    }
+
+    fn client_connect(&mut self, address: &str) -> bool {
+        self.inner.client_connect(address)
+    }
+
+    fn client_connected(&self) -> bool {
+        self.inner.client_connected()
+    }
+
+    fn client_send(&mut self, data: Vec<u8>, channel: TransportChannel) {
+        self.inner.client_send(data, channel);
+    }
+
+    fn client_disconnect(&mut self) {
+        self.inner.client_disconnect();
+    }
+
+    fn client_early_update(&mut self) {
+        self.inner.client_early_update();
+    }
+
+    fn client_late_update(&mut self) {
+        self.inner.client_late_update();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::transports::memory::memory_transport::test_util::with_isolated_context;
+    use crate::mirror::transports::memory::memory_transport::MemoryTransport;
+    use std::net::TcpListener;
+    use std::sync::Mutex;
+    use std::time::Instant;
+
+    static RECEIVED: Mutex<Vec<(TransportCallbackType, u64, Vec<u8>)>> = Mutex::new(Vec::new());
+
+    fn on_callback(tcb: TransportCallback) {
+        RECEIVED
+            .lock()
+            .unwrap()
+            .push((tcb.r#type, tcb.conn_id, tcb.data));
+    }
+
+    // 等待中继线程的事件
+    fn wait_received(
+        transport: &mut RelayTransport,
+        count: usize,
+    ) -> Vec<(TransportCallbackType, u64, Vec<u8>)> {
+        let begin = Instant::now();
+        while RECEIVED.lock().unwrap().len() < count && begin.elapsed() < Duration::from_secs(5) {
+            transport.server_early_update();
+            thread::sleep(Duration::from_millis(5));
+        }
+        std::mem::take(&mut *RECEIVED.lock().unwrap())
+    }
+
+    // 跳过心跳
+    fn read_relay_frame(relay: &mut TcpStream) -> (Option<RelayOpcode>, Vec<u8>) {
+        loop {
+            let (opcode, body) = RelayTransport::read_frame(relay).unwrap();
+            if opcode != Some(RelayOpcode::Heartbeat) {
+                return (opcode, body);
+            }
+        }
+    }
+
+    #[test]
+    fn test_relay_transport() {
+        with_isolated_context(|| {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let config = RelayTransportConfig {
+                enabled: true,
+                address: listener.local_addr().unwrap().to_string(),
+                token: "secret".to_string(),
+                name: "host".to_string(),
+                ..RelayTransportConfig::default()
+            };
+            let mut transport = RelayTransport::new(Box::new(MemoryTransport::default()), config);
+            transport.set_transport_cb_fn(on_callback);
+            transport.server_start();
+
+            // 注册
+            let (mut relay, _) = listener.accept().unwrap();
+            let (opcode, body) = RelayTransport::read_frame(&mut relay).unwrap();
+            assert_eq!(opcode, Some(RelayOpcode::Register));
+            let mut reader = NetworkReader::new_with_bytes(body);
+            assert_eq!(reader.read_string(), "secret");
+            assert_eq!(reader.read_string(), "host");
+            relay
+                .write_all(&RelayTransport::encode_frame(
+                    RelayOpcode::Registered,
+                    |w| w.write_str("ABCD"),
+                ))
+                .unwrap();
+
+            // 中继的客户端连接和数据
+            relay
+                .write_all(&RelayTransport::encode_frame(
+                    RelayOpcode::ClientConnected,
+                    |w| {
+                        w.write_uint(5);
+                        w.write_str("10.0.0.1:1234");
+                    },
+                ))
+                .unwrap();
+            relay
+                .write_all(&RelayTransport::encode_frame(RelayOpcode::Data, |w| {
+                    w.write_uint(5);
+                    w.write_byte(TransportChannel::Reliable as u8);
+                    w.write_array_segment_all(&[1, 2, 3]);
+                }))
+                .unwrap();
+            let connection_id = RelayTransport::RELAYED_CONNECTION_FLAG | 5;
+            assert_eq!(
+                wait_received(&mut transport, 2),
+                vec![
+                    (
+                        TransportCallbackType::OnServerConnected,
+                        connection_id,
+                        vec![]
+                    ),
+                    (
+                        TransportCallbackType::OnServerDataReceived,
+                        connection_id,
+                        vec![1, 2, 3]
+                    ),
+                ]
+            );
+            assert_eq!(RelayTransport::state(), RelayState::Registered);
+            assert_eq!(RelayTransport::join_code(), "ABCD");
+            assert!(RelayTransport::is_relayed(connection_id));
+            assert_eq!(RelayTransport::relayed_count(), 1);
+            assert_eq!(
+                transport.server_get_client_address(connection_id),
+                "10.0.0.1"
+            );
+
+            // 发送给中继的客户端, 在 server_late_update 中写入
+            transport.server_send(connection_id, vec![9], TransportChannel::Unreliable);
+            transport.server_late_update();
+            let (opcode, body) = read_relay_frame(&mut relay);
+            assert_eq!(opcode, Some(RelayOpcode::Data));
+            assert_eq!(body, vec![5, 0, 0, 0, 2, 9]);
+
+            // 服务器断开中继的客户端
+            transport.server_disconnect(connection_id);
+            transport.server_late_update();
+            let (opcode, body) = read_relay_frame(&mut relay);
+            assert_eq!(opcode, Some(RelayOpcode::ClientDisconnected));
+            assert_eq!(body, vec![5, 0, 0, 0]);
+            assert_eq!(
+                wait_received(&mut transport, 1),
+                vec![(
+                    TransportCallbackType::OnServerDisconnected,
+                    connection_id,
+                    vec![]
+                )]
+            );
+
+            // 中继断开时中继的客户端全部断开
+            relay
+                .write_all(&RelayTransport::encode_frame(
+                    RelayOpcode::ClientConnected,
+                    |w| {
+                        w.write_uint(6);
+                        w.write_str("10.0.0.2:1234");
+                    },
+                ))
+                .unwrap();
+            assert_eq!(wait_received(&mut transport, 1).len(), 1);
+            drop(relay);
+            assert_eq!(
+                wait_received(&mut transport, 1),
+                vec![(
+                    TransportCallbackType::OnServerDisconnected,
+                    RelayTransport::RELAYED_CONNECTION_FLAG | 6,
+                    vec![]
+                )]
+            );
+            assert_eq!(RelayTransport::relayed_count(), 0);
+
+            transport.server_stop();
+            assert_eq!(RelayTransport::state(), RelayState::Disconnected);
+        });
+    }
+}
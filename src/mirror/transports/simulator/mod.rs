@@ -0,0 +1 @@
+pub mod network_simulator;
\ No newline at end of file
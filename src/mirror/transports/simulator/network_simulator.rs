@@ -0,0 +1,401 @@
+use crate::log_error;
+use crate::mirror::core::network_context::ContextLocal;
+use crate::mirror::core::network_time::NetworkTime;
+use crate::mirror::core::transport::{
+    Transport, TransportCallback, TransportCallbackType, TransportChannel, TransportFunc,
+    TransportTrait,
+};
+use lazy_static::lazy_static;
+use std::net::SocketAddr;
+use std::sync::RwLock;
+
+// NetworkSimulator 静态变量
+lazy_static! {
+    static ref SETTINGS: ContextLocal<RwLock<NetworkSimulatorSettings>> =
+        ContextLocal::new(|| RwLock::new(NetworkSimulatorSettings::default()));
+    // 内层 Transport 的回调只能是函数指针, 收到的事件先放在这里
+    static ref INCOMING: ContextLocal<RwLock<DelayQueue<TransportCallback>>> =
+        ContextLocal::new(|| RwLock::new(DelayQueue::default()));
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NetworkSimulatorSettings {
+    pub enabled: bool,
+    // 单向延迟 (秒), 发送和接收各自计算
+    pub latency: f64,
+    // 在 latency 之上随机增加 0 ~ jitter 秒, 不可靠通道会因此乱序
+    pub jitter: f64,
+    // 不可靠通道的丢包率 0 ~ 1, 可靠通道由 kcp 保证, 不模拟丢包
+    pub loss: f32,
+    // 不可靠通道的重复率 0 ~ 1
+    pub duplicate: f32,
+}
+
+impl Default for NetworkSimulatorSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            latency: 0.0,
+            jitter: 0.0,
+            loss: 0.0,
+            duplicate: 0.0,
+        }
+    }
+}
+
+struct Delayed<T> {
+    due: f64,
+    seq: u64,
+    item: T,
+}
+
+struct DelayQueue<T> {
+    items: Vec<Delayed<T>>,
+    seq: u64,
+    // 可靠通道的数据和连接事件按顺序送达, 抖动不会让它们乱序
+    reliable_due: f64,
+}
+
+impl<T> Default for DelayQueue<T> {
+    fn default() -> Self {
+        Self {
+            items: Vec::new(),
+            seq: 0,
+            reliable_due: 0.0,
+        }
+    }
+}
+
+impl<T: Clone> DelayQueue<T> {
+    fn push(&mut self, item: T, reliable: bool, settings: &NetworkSimulatorSettings, now: f64) {
+        if reliable {
+            let due = Self::due(settings, now).max(self.reliable_due);
+            self.reliable_due = due;
+            self.insert(due, item);
+            return;
+        }
+        if settings.enabled && rand::random::<f32>() < settings.loss {
+            return;
+        }
+        if settings.enabled && rand::random::<f32>() < settings.duplicate {
+            self.insert(Self::due(settings, now), item.clone());
+        }
+        self.insert(Self::due(settings, now), item);
+    }
+
+    fn due(settings: &NetworkSimulatorSettings, now: f64) -> f64 {
+        if !settings.enabled {
+            return now;
+        }
+        now + settings.latency.max(0.0) + rand::random::<f64>() * settings.jitter.max(0.0)
+    }
+
+    fn insert(&mut self, due: f64, item: T) {
+        self.seq += 1;
+        self.items.push(Delayed {
+            due,
+            seq: self.seq,
+            item,
+        });
+    }
+
+    // 取出到期的数据, 按到期时间排序, 时间相同时按加入顺序
+    fn pop_due(&mut self, now: f64) -> Vec<T> {
+        if self.items.iter().all(|delayed| delayed.due > now) {
+            return Vec::new();
+        }
+        let (mut due, pending): (Vec<_>, Vec<_>) = std::mem::take(&mut self.items)
+            .into_iter()
+            .partition(|delayed| delayed.due <= now);
+        self.items = pending;
+        due.sort_by(|a, b| a.due.total_cmp(&b.due).then(a.seq.cmp(&b.seq)));
+        due.into_iter().map(|delayed| delayed.item).collect()
+    }
+
+    fn retain<F: Fn(&T) -> bool>(&mut self, func: F) {
+        self.items.retain(|delayed| func(&delayed.item));
+    }
+
+    fn clear(&mut self) {
+        self.items.clear();
+        self.reliable_due = 0.0;
+    }
+}
+
+// 包装另一个 Transport, 在发送和接收时加入延迟、抖动、丢包和重复, 用于测试差网络下的表现
+// 先 awake 实际的 Transport, 再调用 NetworkSimulator::awake()
+// 运行时通过 NetworkSimulator::set_settings 修改, enabled 为 false 时直接转发
+pub struct NetworkSimulator {
+    pub transport: Transport,
+    inner: Box<dyn TransportTrait>,
+    outgoing: DelayQueue<(u64, Vec<u8>, TransportChannel)>,
+}
+
+impl NetworkSimulator {
+    pub fn new(mut inner: Box<dyn TransportTrait>) -> Self {
+        inner.set_transport_cb_fn(Self::on_inner_callback);
+        Self {
+            transport: Transport::default(),
+            inner,
+            outgoing: DelayQueue::default(),
+        }
+    }
+
+    pub fn settings() -> NetworkSimulatorSettings {
+        match SETTINGS.read() {
+            Ok(settings) => *settings,
+            Err(_) => NetworkSimulatorSettings::default(),
+        }
+    }
+
+    pub fn set_settings(settings: NetworkSimulatorSettings) {
+        if let Ok(mut current) = SETTINGS.write() {
+            *current = settings;
+        }
+    }
+
+    pub fn set_enabled(enabled: bool) {
+        if let Ok(mut settings) = SETTINGS.write() {
+            settings.enabled = enabled;
+        }
+    }
+
+    fn on_inner_callback(tcb: TransportCallback) {
+        let reliable = tcb.r#type != TransportCallbackType::OnServerDataReceived
+            || tcb.channel == TransportChannel::Reliable;
+        let settings = Self::settings();
+        match INCOMING.write() {
+            Ok(mut incoming) => {
+                incoming.push(tcb, reliable, &settings, NetworkTime::local_time());
+            }
+            Err(e) => {
+                log_error!("NetworkSimulator failed to get incoming queue: {:?}", e);
+            }
+        }
+    }
+
+    fn flush_outgoing(&mut self) {
+        for (connection_id, data, channel) in self.outgoing.pop_due(NetworkTime::local_time()) {
+            self.inner.server_send(connection_id, data, channel);
+        }
+    }
+}
+
+impl TransportTrait for NetworkSimulator {
+    fn awake()
+    where
+        Self: Sized,
+    {
+        match Transport::take_active_transport() {
+            Some(inner) => Transport::set_active_transport(Box::new(Self::new(inner))),
+            None => {
+                log_error!("NetworkSimulator awake error: no active transport to wrap");
+            }
+        }
+    }
+
+    fn available(&self) -> bool {
+        self.inner.available()
+    }
+
+    fn is_encrypted(&self) -> bool {
+        self.inner.is_encrypted()
+    }
+
+    fn encryption_cipher(&self) -> &str {
+        self.inner.encryption_cipher()
+    }
+
+    fn server_active(&self) -> bool {
+        self.inner.server_active()
+    }
+
+    fn server_start(&mut self) {
+        self.inner.server_start();
+    }
+
+    fn server_send(&mut self, connection_id: u64, data: Vec<u8>, channel: TransportChannel) {
+        let reliable = channel == TransportChannel::Reliable;
+        self.outgoing.push(
+            (connection_id, data, channel),
+            reliable,
+            &Self::settings(),
+            NetworkTime::local_time(),
+        );
+    }
+
+    fn server_disconnect(&mut self, connection_id: u64) {
+        // 断开后内层 Transport 已经没有这个连接
+        self.outgoing.retain(|item| item.0 != connection_id);
+        self.inner.server_disconnect(connection_id);
+    }
+
+    fn server_get_client_address(&self, connection_id: u64) -> String {
+        self.inner.server_get_client_address(connection_id)
+    }
+
+    fn server_local_endpoint(&self) -> Option<SocketAddr> {
+        self.inner.server_local_endpoint()
+    }
+
+    fn server_early_update(&mut self) {
+        self.inner.server_early_update();
+        self.flush_outgoing();
+        // 先取出到期的事件, 回调中可能会再次访问 Transport
+        let incoming = match INCOMING.write() {
+            Ok(mut incoming) => incoming.pop_due(NetworkTime::local_time()),
+            Err(e) => {
+                log_error!("NetworkSimulator failed to get incoming queue: {:?}", e);
+                return;
+            }
+        };
+        match self.transport.transport_cb_fn {
+            None => {
+                log_error!("NetworkSimulator server_early_update error: transport_cb_fn is None");
+            }
+            Some(transport_cb_fn) => {
+                for tcb in incoming {
+                    transport_cb_fn(tcb);
+                }
+            }
+        }
+    }
+
+    fn server_late_update(&mut self) {
+        self.flush_outgoing();
+        self.inner.server_late_update();
+    }
+
+    fn server_stop(&mut self) {
+        self.inner.server_stop();
+        self.outgoing.clear();
+        if let Ok(mut incoming) = INCOMING.write() {
+            incoming.clear();
+        }
+    }
+
+    fn transport_cb_fn(&self) -> Option<TransportFunc> {
+        self.transport.transport_cb_fn
+    }
+
+    fn set_transport_cb_fn(&mut self, func: TransportFunc) {
+        self.transport.transport_cb_fn.replace(func);
+        self.inner.set_transport_cb_fn(Self::on_inner_callback);
+    }
+
+    fn get_max_packet_size(&self, channel: TransportChannel) -> usize {
+        self.inner.get_max_packet_size(channel)
+    }
+
+    fn get_batcher_threshold(&self, channel: TransportChannel) -> usize {
+        self.inner.get_batcher_threshold(channel)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::transports::memory::memory_transport::test_util::with_isolated_context;
+    use crate::mirror::transports::memory::memory_transport::MemoryTransport;
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    static RECEIVED: Mutex<Vec<(TransportCallbackType, Vec<u8>)>> = Mutex::new(Vec::new());
+
+    fn on_callback(tcb: TransportCallback) {
+        RECEIVED.lock().unwrap().push((tcb.r#type, tcb.data));
+    }
+
+    fn received() -> Vec<(TransportCallbackType, Vec<u8>)> {
+        std::mem::take(&mut *RECEIVED.lock().unwrap())
+    }
+
+    fn early_update() {
+        Transport::active_transport().unwrap().server_early_update();
+    }
+
+    #[test]
+    fn test_network_simulator() {
+        with_isolated_context(|| {
+            MemoryTransport::awake();
+            NetworkSimulator::awake();
+            let transport = Transport::active_transport().unwrap();
+            transport.set_transport_cb_fn(on_callback);
+            transport.server_start();
+            NetworkSimulator::set_settings(NetworkSimulatorSettings {
+                latency: 0.05,
+                ..NetworkSimulatorSettings::default()
+            });
+
+            // 接收: 延迟之后按顺序送达
+            MemoryTransport::client_connect(1);
+            MemoryTransport::client_send(1, vec![1], TransportChannel::Reliable);
+            early_update();
+            assert!(received().is_empty());
+            std::thread::sleep(Duration::from_millis(60));
+            early_update();
+            assert_eq!(
+                received(),
+                vec![
+                    (TransportCallbackType::OnServerConnected, vec![]),
+                    (TransportCallbackType::OnServerDataReceived, vec![1]),
+                ]
+            );
+
+            // 发送: 延迟之后才交给内层 Transport
+            let transport = Transport::active_transport().unwrap();
+            transport.server_send(1, vec![2], TransportChannel::Reliable);
+            transport.server_late_update();
+            assert!(MemoryTransport::client_receive(1).is_empty());
+            std::thread::sleep(Duration::from_millis(60));
+            transport.server_late_update();
+            assert_eq!(
+                MemoryTransport::client_receive(1),
+                vec![(vec![2], TransportChannel::Reliable)]
+            );
+
+            // 丢包只影响不可靠通道
+            NetworkSimulator::set_settings(NetworkSimulatorSettings {
+                loss: 1.0,
+                ..NetworkSimulatorSettings::default()
+            });
+            MemoryTransport::client_send(1, vec![3], TransportChannel::Unreliable);
+            MemoryTransport::client_send(1, vec![4], TransportChannel::Reliable);
+            early_update();
+            assert_eq!(
+                received(),
+                vec![(TransportCallbackType::OnServerDataReceived, vec![4])]
+            );
+
+            NetworkSimulator::set_settings(NetworkSimulatorSettings {
+                duplicate: 1.0,
+                ..NetworkSimulatorSettings::default()
+            });
+            MemoryTransport::client_send(1, vec![5], TransportChannel::Unreliable);
+            early_update();
+            assert_eq!(received().len(), 2);
+
+            // 关闭后直接转发
+            NetworkSimulator::set_settings(NetworkSimulatorSettings {
+                latency: 10.0,
+                loss: 1.0,
+                ..NetworkSimulatorSettings::default()
+            });
+            NetworkSimulator::set_enabled(false);
+            MemoryTransport::client_send(1, vec![6], TransportChannel::Unreliable);
+            early_update();
+            assert_eq!(
+                received(),
+                vec![(TransportCallbackType::OnServerDataReceived, vec![6])]
+            );
+
+            // 断开时丢弃还没有发送的数据
+            NetworkSimulator::set_enabled(true);
+            let transport = Transport::active_transport().unwrap();
+            transport.server_send(1, vec![7], TransportChannel::Reliable);
+            transport.server_disconnect(1);
+            transport.server_stop();
+            assert!(MemoryTransport::client_receive(1).is_empty());
+        });
+    }
+}
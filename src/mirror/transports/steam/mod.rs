@@ -0,0 +1 @@
+pub mod steam_transport;
\ No newline at end of file
@@ -0,0 +1,510 @@
+use crate::mirror::core::network_context::ContextLocal;
+use crate::mirror::core::transport::{
+    Transport, TransportCallback, TransportCallbackType, TransportChannel, TransportError,
+    TransportFunc, TransportTrait,
+};
+use crate::{log_error, log_info, log_warn};
+use dashmap::DashMap;
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::RwLock;
+use steamworks::networking_sockets::{ListenSocket, NetConnection};
+use steamworks::networking_types::{ListenSocketEvent, NetConnectionEnd, SendFlags};
+use steamworks::{
+    CallbackHandle, Server, ServerManager, ServerMode, SingleClient, SteamId,
+    ValidateAuthTicketResponse,
+};
+
+#[derive(Debug, Clone)]
+pub struct SteamTransportConfig {
+    pub ip: Ipv4Addr,
+    pub game_port: u16,
+    pub query_port: u16,
+    // 服务器版本, 客户端版本不一致时 Steam 不会列出这个服务器
+    pub version: String,
+    // true: 通过 Steam 中继接受 P2P 连接 (virtual_port); false: 监听 ip:game_port
+    pub p2p: bool,
+    pub virtual_port: i32,
+    // 每个连接每个 tick 最多接收的消息数量
+    pub max_receive_per_tick: usize,
+}
+
+impl Default for SteamTransportConfig {
+    fn default() -> Self {
+        Self {
+            ip: Ipv4Addr::UNSPECIFIED,
+            game_port: 7777,
+            query_port: 27016,
+            version: "1.0.0.0".to_string(),
+            p2p: false,
+            virtual_port: 0,
+            max_receive_per_tick: 256,
+        }
+    }
+}
+
+// Steam 验证认证票据的结果
+#[derive(Debug, Clone, Copy)]
+pub struct SteamAuthResult {
+    pub connection_id: u64,
+    pub steam_id: u64,
+    // 家庭共享时为游戏拥有者的 SteamID, 否则与 steam_id 相同
+    pub owner_steam_id: u64,
+    pub success: bool,
+}
+
+pub type SteamAuthResultFunc = fn(SteamAuthResult);
+
+// SteamTransport 静态变量
+lazy_static! {
+    // 连接 id <-> SteamID
+    static ref STEAM_IDS: ContextLocal<DashMap<u64, u64>> = ContextLocal::new(DashMap::new);
+    static ref CONNECTION_IDS: ContextLocal<DashMap<u64, u64>> = ContextLocal::new(DashMap::new);
+    // 等待在 server_early_update 中交给 Steam 验证的票据
+    static ref PENDING_TICKETS: ContextLocal<RwLock<Vec<(u64, Vec<u8>)>>> =
+        ContextLocal::new(|| RwLock::new(Vec::new()));
+    // Steam 回调写入, server_early_update 交给 AUTH_RESULT_HANDLER
+    static ref AUTH_RESULTS: ContextLocal<RwLock<Vec<SteamAuthResult>>> =
+        ContextLocal::new(|| RwLock::new(Vec::new()));
+    static ref AUTH_RESULT_HANDLER: ContextLocal<RwLock<Option<SteamAuthResultFunc>>> =
+        ContextLocal::new(|| RwLock::new(None));
+    // 已经开始认证会话的 SteamID, 断开时结束会话
+    static ref AUTH_SESSIONS: ContextLocal<DashMap<u64, ()>> = ContextLocal::new(DashMap::new);
+}
+
+// 基于 Steam Networking Sockets 的 Transport, 以 Steam 游戏服务器身份运行, 需要 Steamworks SDK 的动态库
+// 连接 id 从 1 开始分配, 与 SteamID 一一对应; 认证票据由 SteamAuthenticator 通过 begin_auth_session 提交
+// Steam 的回调只在主循环中处理 (run_callbacks), 不使用接收线程
+// 每条消息末尾附加一个字节的通道, 与 FizzySteamworks 相同
+pub struct SteamTransport {
+    pub transport: Transport,
+    config: SteamTransportConfig,
+    server: Option<Server>,
+    single: Option<SingleClient<ServerManager>>,
+    listen_socket: Option<ListenSocket<ServerManager>>,
+    connections: HashMap<u64, NetConnection<ServerManager>>,
+    auth_callback: Option<CallbackHandle<ServerManager>>,
+    next_connection_id: u64,
+}
+
+impl SteamTransport {
+    #[allow(dead_code)]
+    pub const SCHEME: &'static str = "steam";
+    // k_cbMaxSteamNetworkingSocketsMessageSizeSend
+    const MAX_RELIABLE_MESSAGE_SIZE: usize = 512 * 1024;
+    const MTU: usize = 1200;
+
+    pub fn new(config: SteamTransportConfig) -> Self {
+        Self {
+            transport: Transport::default(),
+            config,
+            server: None,
+            single: None,
+            listen_socket: None,
+            connections: HashMap::new(),
+            auth_callback: None,
+            next_connection_id: 1,
+        }
+    }
+
+    pub fn steam_id(connection_id: u64) -> Option<u64> {
+        STEAM_IDS.get(&connection_id).map(|steam_id| *steam_id)
+    }
+
+    pub fn connection_id(steam_id: u64) -> Option<u64> {
+        CONNECTION_IDS
+            .get(&steam_id)
+            .map(|connection_id| *connection_id)
+    }
+
+    // 提交客户端的认证票据, 结果通过 set_auth_result_handler 设置的函数返回
+    pub fn begin_auth_session(connection_id: u64, ticket: Vec<u8>) {
+        match PENDING_TICKETS.write() {
+            Ok(mut pending_tickets) => pending_tickets.push((connection_id, ticket)),
+            Err(e) => {
+                log_error!("SteamTransport failed to write PENDING_TICKETS: {:?}", e);
+            }
+        }
+    }
+
+    pub fn set_auth_result_handler(handler: Option<SteamAuthResultFunc>) {
+        if let Ok(mut auth_result_handler) = AUTH_RESULT_HANDLER.write() {
+            *auth_result_handler = handler;
+        }
+    }
+
+    fn push_auth_result(result: SteamAuthResult) {
+        if let Ok(mut auth_results) = AUTH_RESULTS.write() {
+            auth_results.push(result);
+        }
+    }
+
+    fn on_validate_auth_ticket(response: ValidateAuthTicketResponse) {
+        let steam_id = response.steam_id.raw();
+        // 票据验证完成之前已经断开
+        let connection_id = match Self::connection_id(steam_id) {
+            Some(connection_id) => connection_id,
+            None => return,
+        };
+        if let Err(e) = &response.response {
+            log_warn!(
+                "SteamTransport auth ticket of {} rejected: {:?}",
+                steam_id,
+                e
+            );
+        }
+        Self::push_auth_result(SteamAuthResult {
+            connection_id,
+            steam_id,
+            owner_steam_id: response.owner_steam_id.raw(),
+            success: response.response.is_ok(),
+        });
+    }
+
+    fn process_auth(&mut self) {
+        let server = match self.server.as_ref() {
+            Some(server) => server,
+            None => return,
+        };
+        let pending_tickets = match PENDING_TICKETS.write() {
+            Ok(mut pending_tickets) => std::mem::take(&mut *pending_tickets),
+            Err(_) => Vec::new(),
+        };
+        for (connection_id, ticket) in pending_tickets {
+            let steam_id = match Self::steam_id(connection_id) {
+                Some(steam_id) => steam_id,
+                None => continue,
+            };
+            match server.begin_authentication_session(SteamId::from_raw(steam_id), &ticket) {
+                Ok(()) => {
+                    AUTH_SESSIONS.insert(steam_id, ());
+                }
+                Err(e) => {
+                    log_warn!(
+                        "SteamTransport failed to begin auth session of {}: {:?}",
+                        steam_id,
+                        e
+                    );
+                    Self::push_auth_result(SteamAuthResult {
+                        connection_id,
+                        steam_id,
+                        owner_steam_id: steam_id,
+                        success: false,
+                    });
+                }
+            }
+        }
+
+        let auth_results = match AUTH_RESULTS.write() {
+            Ok(mut auth_results) => std::mem::take(&mut *auth_results),
+            Err(_) => Vec::new(),
+        };
+        if auth_results.is_empty() {
+            return;
+        }
+        let handler = AUTH_RESULT_HANDLER.read().ok().and_then(|handler| *handler);
+        match handler {
+            Some(handler) => auth_results.into_iter().for_each(handler),
+            None => {
+                log_warn!("SteamTransport dropped auth results: auth result handler is None");
+            }
+        }
+    }
+
+    fn process_listen_socket_events(&mut self) {
+        loop {
+            let event = match self.listen_socket.as_ref() {
+                Some(listen_socket) => match listen_socket.try_receive_event() {
+                    Some(event) => event,
+                    None => return,
+                },
+                None => return,
+            };
+            match event {
+                ListenSocketEvent::Connecting(request) => {
+                    // 只接受有 SteamID 的连接
+                    match request.remote().steam_id() {
+                        Some(_) => {
+                            if let Err(e) = request.accept() {
+                                log_warn!("SteamTransport failed to accept connection: {:?}", e);
+                            }
+                        }
+                        None => {
+                            request.reject(NetConnectionEnd::AppGeneric, Some("SteamID required"));
+                        }
+                    }
+                }
+                ListenSocketEvent::Connected(event) => {
+                    let steam_id = match event.remote().steam_id() {
+                        Some(steam_id) => steam_id.raw(),
+                        None => continue,
+                    };
+                    let connection_id = self.next_connection_id;
+                    self.next_connection_id += 1;
+                    STEAM_IDS.insert(connection_id, steam_id);
+                    CONNECTION_IDS.insert(steam_id, connection_id);
+                    self.connections
+                        .insert(connection_id, event.take_connection());
+                    log_info!(
+                        "SteamTransport connection {} from {}",
+                        connection_id,
+                        steam_id
+                    );
+                    self.callback(TransportCallback {
+                        r#type: TransportCallbackType::OnServerConnected,
+                        conn_id: connection_id,
+                        ..TransportCallback::default()
+                    });
+                }
+                ListenSocketEvent::Disconnected(event) => {
+                    let connection_id = match event
+                        .remote()
+                        .steam_id()
+                        .and_then(|steam_id| Self::connection_id(steam_id.raw()))
+                    {
+                        Some(connection_id) => connection_id,
+                        None => continue,
+                    };
+                    self.connections.remove(&connection_id);
+                    self.remove_connection(connection_id);
+                }
+            }
+        }
+    }
+
+    fn receive_messages(&mut self) {
+        let mut received = Vec::new();
+        for (connection_id, connection) in self.connections.iter_mut() {
+            for message in connection.receive_messages(self.config.max_receive_per_tick) {
+                received.push((*connection_id, message.data().to_vec()));
+            }
+        }
+        for (connection_id, mut data) in received {
+            let channel = match data.pop() {
+                Some(channel_id) => Self::from_channel_id(channel_id),
+                None => continue,
+            };
+            self.callback(TransportCallback {
+                r#type: TransportCallbackType::OnServerDataReceived,
+                conn_id: connection_id,
+                data,
+                channel,
+                ..TransportCallback::default()
+            });
+        }
+    }
+
+    // 清除映射, 结束认证会话, 回调 OnServerDisconnected
+    fn remove_connection(&mut self, connection_id: u64) {
+        if let Some((_, steam_id)) = STEAM_IDS.remove(&connection_id) {
+            CONNECTION_IDS.remove(&steam_id);
+            if AUTH_SESSIONS.remove(&steam_id).is_some() {
+                if let Some(server) = self.server.as_ref() {
+                    server.end_authentication_session(SteamId::from_raw(steam_id));
+                }
+            }
+        }
+        self.callback(TransportCallback {
+            r#type: TransportCallbackType::OnServerDisconnected,
+            conn_id: connection_id,
+            ..TransportCallback::default()
+        });
+    }
+
+    // Mirror 的通道编号: Channels.Reliable = 0, Channels.Unreliable = 1
+    pub fn to_channel_id(channel: TransportChannel) -> u8 {
+        match channel {
+            TransportChannel::Reliable => 0,
+            TransportChannel::Unreliable => 1,
+        }
+    }
+
+    pub fn from_channel_id(channel_id: u8) -> TransportChannel {
+        match channel_id {
+            1 => TransportChannel::Unreliable,
+            _ => TransportChannel::Reliable,
+        }
+    }
+
+    fn callback(&self, tcb: TransportCallback) {
+        match self.transport.transport_cb_fn {
+            None => {
+                log_error!("SteamTransport callback error: transport_cb_fn is None");
+            }
+            Some(transport_cb_fn) => transport_cb_fn(tcb),
+        }
+    }
+}
+
+impl TransportTrait for SteamTransport {
+    // 使用默认配置, 其他配置使用 Transport::set_active_transport(Box::new(SteamTransport::new(config)))
+    fn awake()
+    where
+        Self: Sized,
+    {
+        Transport::set_active_transport(Box::new(Self::new(SteamTransportConfig::default())));
+    }
+
+    fn available(&self) -> bool {
+        true
+    }
+
+    fn server_active(&self) -> bool {
+        self.listen_socket.is_some()
+    }
+
+    fn server_start(&mut self) {
+        let (server, single) = match Server::init(
+            self.config.ip,
+            self.config.game_port,
+            self.config.query_port,
+            ServerMode::Authentication,
+            &self.config.version,
+        ) {
+            Ok(server) => server,
+            Err(e) => {
+                log_error!("SteamTransport failed to init game server: {:?}", e);
+                return;
+            }
+        };
+        server.set_dedicated_server(true);
+        server.log_on_anonymous();
+
+        let sockets = server.networking_sockets();
+        let listen_socket = match self.config.p2p {
+            true => sockets.create_listen_socket_p2p(self.config.virtual_port, vec![]),
+            false => sockets.create_listen_socket_ip(
+                SocketAddr::new(IpAddr::V4(self.config.ip), self.config.game_port),
+                vec![],
+            ),
+        };
+        let listen_socket = match listen_socket {
+            Ok(listen_socket) => listen_socket,
+            Err(e) => {
+                log_error!("SteamTransport failed to create listen socket: {:?}", e);
+                return;
+            }
+        };
+        // 回调在 run_callbacks 中执行, 与主循环在同一个线程和上下文
+        self.auth_callback = Some(server.register_callback(Self::on_validate_auth_ticket));
+        self.listen_socket = Some(listen_socket);
+        self.server = Some(server);
+        self.single = Some(single);
+    }
+
+    fn server_send(&mut self, connection_id: u64, mut data: Vec<u8>, channel: TransportChannel) {
+        let connection = match self.connections.get(&connection_id) {
+            Some(connection) => connection,
+            None => {
+                self.callback(TransportCallback {
+                    r#type: TransportCallbackType::OnServerError,
+                    conn_id: connection_id,
+                    error: TransportError::ConnectionNotFound,
+                    ..TransportCallback::default()
+                });
+                return;
+            }
+        };
+        let send_flags = match channel {
+            TransportChannel::Reliable => SendFlags::RELIABLE_NO_NAGLE,
+            TransportChannel::Unreliable => SendFlags::UNRELIABLE_NO_NAGLE,
+        };
+        data.push(Self::to_channel_id(channel));
+        let tcb = match connection.send_message(&data, send_flags) {
+            Ok(_) => {
+                data.pop();
+                TransportCallback {
+                    r#type: TransportCallbackType::OnServerDataSent,
+                    conn_id: connection_id,
+                    data,
+                    channel,
+                    ..TransportCallback::default()
+                }
+            }
+            Err(e) => {
+                log_warn!(
+                    "SteamTransport failed to send to {}: {:?}",
+                    connection_id,
+                    e
+                );
+                TransportCallback {
+                    r#type: TransportCallbackType::OnServerError,
+                    conn_id: connection_id,
+                    error: TransportError::SendError,
+                    ..TransportCallback::default()
+                }
+            }
+        };
+        self.callback(tcb);
+    }
+
+    fn server_disconnect(&mut self, connection_id: u64) {
+        if let Some(connection) = self.connections.remove(&connection_id) {
+            connection.close(
+                NetConnectionEnd::AppGeneric,
+                Some("Disconnected by server"),
+                true,
+            );
+            self.remove_connection(connection_id);
+        }
+    }
+
+    fn server_get_client_address(&self, connection_id: u64) -> String {
+        match Self::steam_id(connection_id) {
+            Some(steam_id) => format!("{}://{}", Self::SCHEME, steam_id),
+            None => "".to_string(),
+        }
+    }
+
+    fn server_early_update(&mut self) {
+        if let Some(single) = self.single.as_ref() {
+            single.run_callbacks();
+        }
+        self.process_listen_socket_events();
+        self.receive_messages();
+        self.process_auth();
+    }
+
+    fn server_late_update(&mut self) {
+        // 发送时使用 NO_NAGLE, 不需要 flush
+    }
+
+    fn server_stop(&mut self) {
+        for (connection_id, connection) in std::mem::take(&mut self.connections) {
+            connection.close(NetConnectionEnd::AppGeneric, Some("Server stopped"), false);
+            self.remove_connection(connection_id);
+        }
+        self.listen_socket = None;
+        self.auth_callback = None;
+        if let Ok(mut pending_tickets) = PENDING_TICKETS.write() {
+            pending_tickets.clear();
+        }
+        if let Ok(mut auth_results) = AUTH_RESULTS.write() {
+            auth_results.clear();
+        }
+        // 释放 Server 时注销游戏服务器
+        self.single = None;
+        self.server = None;
+    }
+
+    fn transport_cb_fn(&self) -> Option<TransportFunc> {
+        self.transport.transport_cb_fn
+    }
+
+    fn set_transport_cb_fn(&mut self, func: TransportFunc) {
+        self.transport.transport_cb_fn.replace(func);
+    }
+
+    // 减去附加的通道字节
+    fn get_max_packet_size(&self, channel: TransportChannel) -> usize {
+        match channel {
+            TransportChannel::Reliable => Self::MAX_RELIABLE_MESSAGE_SIZE - 1,
+            TransportChannel::Unreliable => Self::MTU - 1,
+        }
+    }
+
+    fn get_batcher_threshold(&self, _channel: TransportChannel) -> usize {
+        Self::MTU - 1
+    }
+}